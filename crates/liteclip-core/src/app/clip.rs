@@ -1,29 +1,43 @@
 use crate::{
-    buffer::ReplayBuffer,
-    config::Config,
+    buffer::{RecoveredSession, ReplayBuffer, SavedClip},
+    config::{ClipSaveMode, Config, SaveContainer},
     host::CoreHost,
-    output::{spawn_clip_saver, MuxerConfig},
+    output::{
+        generate_thumbnail, metadata_sidecar_path, spawn_clip_saver, AudioTrackLayout,
+        ClipMetadata, ClipWindow, Muxer, MuxerConfig,
+    },
 };
-use anyhow::{bail, Result};
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
 
+/// How [`ClipManager::save_clip`] writes a save, decided by [`ClipManager::plan_save`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipSavePlan {
+    /// Write the requested window to a new file.
+    NewClip,
+    /// Re-mux from `start_pts` to the newest packet over the previous clip at `path`.
+    ExtendPrevious { path: PathBuf, start_pts: i64 },
+}
+
 /// Manages clip saving operations.
 ///
-/// Handles the process of saving the replay buffer to an MP4 file,
+/// Handles the process of saving the replay buffer to a clip file in the configured container,
 /// including output path generation and muxer configuration.
 pub struct ClipManager;
 
 impl ClipManager {
-    /// Saves the current replay buffer to an MP4 file.
+    /// Saves the current replay buffer to a clip file in the configured container.
     ///
     /// This is the main entry point for clip saving. It:
     /// 1. Generates an output path with timestamp
     /// 2. Validates buffer has packets and keyframes
     /// 3. Spawns a background task for muxing
     /// 4. Waits for completion and returns the final path
+    /// 5. Restarts the buffer, or keeps it and records the saved window, according to
+    ///    `general.clip_save_mode` (see [`Self::plan_save`] for overlapping saves)
     ///
     /// # Arguments
     ///
@@ -34,7 +48,7 @@ impl ClipManager {
     ///
     /// # Returns
     ///
-    /// Path to the saved MP4 file.
+    /// Path to the saved clip file.
     ///
     /// # Errors
     ///
@@ -60,6 +74,209 @@ impl ClipManager {
         crate::output::saver::log_save_memory("save_clip_entry", Some(buffer), None);
         info!("Clip: saving replay buffer");
 
+        let duration = Duration::from_secs(u64::from(config.general.replay_duration_secs));
+        let window = ClipWindow::Last(duration);
+        let mode = config.general.clip_save_mode;
+        let newest_pts = buffer.newest_pts();
+        let oldest_pts = buffer.oldest_pts();
+        let start_pts = match newest_pts {
+            Some(newest) => Some(window.resolve(newest, oldest_pts)?.0),
+            None => None,
+        };
+        let plan = match start_pts {
+            Some(start_pts) => {
+                let previous = buffer.last_saved_clip();
+                Self::plan_save(mode, previous.as_ref(), start_pts, oldest_pts)
+            }
+            None => ClipSavePlan::NewClip,
+        };
+
+        let (final_path, saved_start_pts) = match plan {
+            ClipSavePlan::NewClip => (
+                Self::save_window(config, buffer, window, game_name).await?,
+                start_pts,
+            ),
+            ClipSavePlan::ExtendPrevious { path, start_pts } => {
+                let end_pts = newest_pts.unwrap_or(start_pts);
+                info!(
+                    "Clip window overlaps previous save; extending {:?} to pts {}",
+                    path, end_pts
+                );
+                (
+                    Self::extend_clip(config, buffer, &path, start_pts, end_pts, game_name).await?,
+                    Some(start_pts),
+                )
+            }
+        };
+
+        match mode {
+            ClipSaveMode::Restart => {
+                info!("Clip saver completed; restarting replay buffer");
+                // Drop the existing replay contents and restart so subsequent clips
+                // start from a fresh buffer.
+                buffer.restart();
+                info!("Replay buffer restarted");
+            }
+            ClipSaveMode::Independent | ClipSaveMode::Extend => {
+                if let (Some(start_pts), Some(end_pts)) = (saved_start_pts, newest_pts) {
+                    buffer.record_saved_clip(SavedClip {
+                        path: final_path.clone(),
+                        start_pts,
+                        end_pts,
+                    });
+                }
+                info!("Clip saver completed; replay buffer kept ({:?} mode)", mode);
+            }
+        }
+
+        if let Some(h) = host {
+            h.on_clip_saved(&final_path);
+        }
+
+        Ok(final_path)
+    }
+
+    /// Saves an arbitrary [`ClipWindow`] of the replay buffer to a clip file in the configured
+    /// container.
+    ///
+    /// The clip starts at the last keyframe at or before the window start. Unlike
+    /// [`Self::save_clip`], the buffer is left intact so several differently-sized moments
+    /// can be saved from the same replay.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer is empty or has no keyframe, the window is invalid or
+    /// no longer buffered, or muxing fails.
+    pub async fn save_range(
+        config: &Config,
+        buffer: &ReplayBuffer,
+        window: ClipWindow,
+        game_name: Option<&str>,
+        host: Option<Arc<dyn CoreHost>>,
+    ) -> Result<PathBuf> {
+        crate::output::saver::log_save_memory("save_range_entry", Some(buffer), None);
+        info!("Clip: saving replay window {:?}", window);
+
+        let final_path = Self::save_window(config, buffer, window, game_name).await?;
+
+        if let Some(h) = host {
+            h.on_clip_saved(&final_path);
+        }
+
+        Ok(final_path)
+    }
+
+    /// Decides whether a save starting at `start_pts` becomes a new clip or extends
+    /// `previous`.
+    ///
+    /// Only [`ClipSaveMode::Extend`] extends, and only when the new window overlaps the
+    /// previous one, the previous file still exists, and its start is still buffered
+    /// (at or after `oldest_pts`). Everything else is a full independent clip.
+    pub fn plan_save(
+        mode: ClipSaveMode,
+        previous: Option<&SavedClip>,
+        start_pts: i64,
+        oldest_pts: Option<i64>,
+    ) -> ClipSavePlan {
+        let Some(previous) = previous else {
+            return ClipSavePlan::NewClip;
+        };
+        let overlaps = start_pts <= previous.end_pts;
+        let still_buffered = oldest_pts.map_or(false, |oldest| previous.start_pts >= oldest);
+        if mode == ClipSaveMode::Extend && overlaps && still_buffered && previous.path.exists() {
+            ClipSavePlan::ExtendPrevious {
+                path: previous.path.clone(),
+                start_pts: previous.start_pts,
+            }
+        } else {
+            ClipSavePlan::NewClip
+        }
+    }
+
+    /// Re-muxes `start_pts..=end_pts` over `previous`.
+    ///
+    /// The clip is written to a temporary file first and renamed over `previous`, so a
+    /// failed save leaves the earlier clip intact. The thumbnail is kept because the clip
+    /// still starts on the same frame. The clip is written in the same container as the
+    /// previous one, taken from its file extension.
+    async fn extend_clip(
+        config: &Config,
+        buffer: &ReplayBuffer,
+        previous: &Path,
+        start_pts: i64,
+        end_pts: i64,
+        game_name: Option<&str>,
+    ) -> Result<PathBuf> {
+        let extension = previous
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_else(|| config.general.save_container.extension());
+        let container = SaveContainer::for_extension(extension, config.general.save_container)
+            .unwrap_or(config.general.save_container);
+        let temp_path = previous.with_extension(format!("extending.{extension}"));
+        let muxer_config =
+            Self::muxer_config(config, buffer, &temp_path, game_name).with_container(container);
+        let handle = spawn_clip_saver(
+            buffer.clone(),
+            ClipWindow::PtsRange { start_pts, end_pts },
+            temp_path.clone(),
+            muxer_config,
+            PathBuf::from(&config.general.save_directory),
+            false,
+        );
+        let (written, metadata) = handle.await??;
+        std::fs::rename(&written, previous)
+            .with_context(|| format!("Failed to replace {:?} with extended clip", previous))?;
+        Self::write_metadata_sidecar(config, previous, metadata.as_ref());
+        Ok(previous.to_path_buf())
+    }
+
+    fn muxer_config(
+        config: &Config,
+        buffer: &ReplayBuffer,
+        output_path: &Path,
+        game_name: Option<&str>,
+    ) -> MuxerConfig {
+        let (width, height) = buffer
+            .snapshot_first_packet_resolution()
+            .or_else(|| config.video.target_resolution())
+            .unwrap_or((1920, 1080));
+        let fps = f64::from(config.video.framerate);
+
+        MuxerConfig::new(width, height, fps, output_path)
+            .with_video_codec("hevc")
+            .with_expect_audio(config.audio.capture_system || config.audio.capture_mic)
+            .with_buffer_audio_codec(config.audio.buffer_codec)
+            .with_audio_codec(config.audio.clip_codec, config.audio.clip_bitrate_kbps)
+            .with_audio_tracks(AudioTrackLayout::from_audio_config(&config.audio))
+            .with_container(config.general.save_container)
+            .with_metadata(ClipMetadata::from_replay(
+                config.video.framerate,
+                config.general.replay_duration_secs,
+                game_name,
+                buffer.video_encoder(),
+            ))
+    }
+
+    /// Writes `metadata` as the clip's JSON sidecar when `general.write_metadata_sidecar` is
+    /// set. Failures are logged; the clip itself is already saved.
+    fn write_metadata_sidecar(config: &Config, clip_path: &Path, metadata: Option<&ClipMetadata>) {
+        let Some(metadata) = metadata.filter(|_| config.general.write_metadata_sidecar) else {
+            return;
+        };
+        let save_dir = PathBuf::from(&config.general.save_directory);
+        if let Err(e) = metadata.write_sidecar(&metadata_sidecar_path(&save_dir, clip_path)) {
+            warn!("Failed to write clip metadata sidecar: {:#}", e);
+        }
+    }
+
+    /// Validates the buffer and runs [`spawn_clip_saver`] for `window`.
+    async fn save_window(
+        config: &Config,
+        buffer: &ReplayBuffer,
+        window: ClipWindow,
+        game_name: Option<&str>,
+    ) -> Result<PathBuf> {
         let output_path = Self::generate_output_path(config, game_name)?;
 
         let stats = buffer.stats();
@@ -80,50 +297,105 @@ impl ClipManager {
             );
         }
 
-        let (width, height) = buffer
-            .snapshot_first_packet_resolution()
-            .or_else(|| config.video.target_resolution())
-            .unwrap_or((1920, 1080));
-        let fps = f64::from(config.video.framerate);
-
-        let muxer_config = MuxerConfig::new(width, height, fps, &output_path)
-            .with_video_codec("hevc")
-            .with_expect_audio(config.audio.capture_system || config.audio.capture_mic);
+        let muxer_config = Self::muxer_config(config, buffer, &output_path, game_name);
 
         let buffer_clone = buffer.clone();
-        let duration = Duration::from_secs(u64::from(config.general.replay_duration_secs));
         let save_directory = PathBuf::from(&config.general.save_directory);
 
         crate::output::saver::log_save_memory("before_spawn_saver", Some(buffer), None);
         let handle = spawn_clip_saver(
             buffer_clone,
-            duration,
+            window,
             output_path.clone(),
             muxer_config,
             save_directory.clone(),
             config.general.generate_clip_thumbnail,
         );
-        let result = handle.await?;
-        let final_path = result?;
+        let (final_path, metadata) = handle.await??;
+        Self::write_metadata_sidecar(config, &final_path, metadata.as_ref());
+        Ok(final_path)
+    }
 
-        info!("Clip saver completed; restarting replay buffer");
-        // Drop the existing replay contents and restart so subsequent clips
-        // start from a fresh buffer.
-        buffer.restart();
-        info!("Replay buffer restarted");
+    /// Muxes a [`RecoveredSession`] from a crashed run into a clip under
+    /// `<save_directory>/Recovered`.
+    ///
+    /// This is synchronous (it runs the muxer on the calling thread); call it from a blocking
+    /// context. The session's checkpoint file is left in place; call
+    /// [`RecoveredSession::discard`] once the clip is saved. Audio is muxed with the buffer
+    /// codec and track layout recorded in the checkpoint, not the current settings; separate
+    /// tracks take their titles from the current settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output directory cannot be created or muxing fails.
+    pub fn save_recovered_session(config: &Config, session: &RecoveredSession) -> Result<PathBuf> {
+        let save_dir = PathBuf::from(&config.general.save_directory);
+        let output_dir = save_dir.join("Recovered");
+        std::fs::create_dir_all(&output_dir)?;
+        let output_path = output_dir.join(Self::timestamped_filename(config));
 
-        if let Some(h) = host {
-            h.on_clip_saved(&final_path);
-        }
+        let (width, height) = session
+            .resolution()
+            .or_else(|| config.video.target_resolution())
+            .unwrap_or((1920, 1080));
+        let audio_format = session.audio_format();
+        // Compressed buffers are stream-copied, so the clip codec has to match them.
+        let clip_audio_codec = audio_format
+            .buffer_codec
+            .clip_codec()
+            .unwrap_or(config.audio.clip_codec);
+        let audio_tracks = if audio_format.separate_tracks {
+            AudioTrackLayout::separate(&config.audio)
+        } else {
+            AudioTrackLayout::Mixed
+        };
+        let muxer_config = MuxerConfig::new(
+            width,
+            height,
+            f64::from(config.video.framerate),
+            &output_path,
+        )
+        .with_video_codec("hevc")
+        .with_expect_audio(session.has_audio())
+        .with_buffer_audio_codec(audio_format.buffer_codec)
+        .with_audio_codec(clip_audio_codec, config.audio.clip_bitrate_kbps)
+        .with_audio_tracks(audio_tracks)
+        .with_container(config.general.save_container)
+        .with_metadata(ClipMetadata {
+            configured_fps: Some(config.video.framerate),
+            replay_window_secs: Some(config.general.replay_duration_secs),
+            ..ClipMetadata::default()
+        });
+        let metadata = muxer_config.metadata.clone();
 
+        info!(
+            "Recovering previous session: {:.1}s, {} packets -> {:?}",
+            session.duration_secs(),
+            session.packets().len(),
+            output_path
+        );
+        let final_path = Muxer::mux_clip(&output_path, &muxer_config, session.packets())?;
+        Self::write_metadata_sidecar(config, &final_path, metadata.as_ref());
+
+        if config.general.generate_clip_thumbnail {
+            if let Err(e) = generate_thumbnail(&final_path, &save_dir) {
+                warn!("Failed to generate thumbnail for recovered clip: {}", e);
+            }
+        }
         Ok(final_path)
     }
 
-    fn generate_output_path(config: &Config, game_name: Option<&str>) -> Result<PathBuf> {
-        use chrono::Local;
+    fn timestamped_filename(config: &Config) -> String {
+        let timestamp = chrono::Local::now();
+        format!(
+            "{}.{}",
+            timestamp.format("%Y-%m-%d_%H-%M-%S_%3f"),
+            config.general.save_container.extension()
+        )
+    }
 
-        let timestamp = Local::now();
-        let filename = format!("{}.mp4", timestamp.format("%Y-%m-%d_%H-%M-%S_%3f"));
+    fn generate_output_path(config: &Config, game_name: Option<&str>) -> Result<PathBuf> {
+        let filename = Self::timestamped_filename(config);
 
         let save_dir = PathBuf::from(&config.general.save_directory);
 
@@ -28,6 +28,6 @@ pub mod clip;
 pub mod pipeline;
 pub mod state;
 
-pub use clip::ClipManager;
+pub use clip::{ClipManager, ClipSavePlan};
 pub use pipeline::{RecordingLifecycle, RecordingPipeline};
 pub use state::AppState;
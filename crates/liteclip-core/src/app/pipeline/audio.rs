@@ -2,6 +2,7 @@ use crate::{
     buffer::ReplayBuffer,
     capture::audio::{AudioLevelMonitor, WasapiAudioManager},
     config::Config,
+    encode::{ffmpeg::audio::BufferAudioEncoder, EncodedPacket, StreamType},
 };
 use anyhow::{Context, Result};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -111,10 +112,72 @@ pub struct AudioCaptureResult {
     pub forward_handle: Option<AudioForwardHandle>,
 }
 
+/// Encoders turning PCM into the buffered audio codec.
+///
+/// With `audio.separate_tracks` the microphone gets its own encoder, so the two tracks stay
+/// separate streams in the buffer.
+struct BufferEncoders {
+    system: BufferAudioEncoder,
+    microphone: Option<BufferAudioEncoder>,
+}
+
+impl BufferEncoders {
+    fn new(config: &Config) -> Result<Self> {
+        let open = || {
+            BufferAudioEncoder::new(
+                config.audio.buffer_codec,
+                config.audio.clip_bitrate_kbps as usize * 1000,
+            )
+            .context("Failed to create buffered audio encoder")
+        };
+        Ok(Self {
+            system: open()?,
+            microphone: if config.audio.separate_tracks {
+                Some(open()?)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn for_packet(&mut self, packet: &EncodedPacket) -> &mut BufferAudioEncoder {
+        match (packet.stream, self.microphone.as_mut()) {
+            (StreamType::Microphone, Some(microphone)) => microphone,
+            _ => &mut self.system,
+        }
+    }
+
+    fn flush(&mut self, out: &mut Vec<EncodedPacket>) {
+        for encoder in std::iter::once(&mut self.system).chain(self.microphone.as_mut()) {
+            if let Err(e) = encoder.flush(out) {
+                warn!("Failed to flush buffered audio encoder: {}", e);
+            }
+        }
+    }
+}
+
+/// Replaces a batch of PCM packets with the packets `encoders` produced from them.
+///
+/// The encoders buffer partial frames, so the output may hold fewer (or no) packets.
+fn encode_for_buffer(encoders: &mut BufferEncoders, batch: &mut Vec<EncodedPacket>) {
+    let mut encoded = Vec::with_capacity(batch.len());
+    for packet in batch.drain(..) {
+        if let Err(e) = encoders.for_packet(&packet).encode(&packet, &mut encoded) {
+            warn!(
+                "Dropping audio packet that failed to encode for the buffer: {}",
+                e
+            );
+        }
+    }
+    *batch = encoded;
+}
+
 /// Start audio capture and return both manager and forward handle.
 ///
 /// This function spawns a forwarding thread that moves audio packets from
-/// the audio manager to the replay buffer. The returned `AudioForwardHandle`
+/// the audio manager to the replay buffer. When `audio.buffer_codec` is compressed, the
+/// packets are encoded on that thread before they are pushed (one encoder per track when
+/// `audio.separate_tracks` is set). The returned `AudioForwardHandle`
 /// must be stored and used for cleanup when stopping the pipeline.
 ///
 /// # Arguments
@@ -144,6 +207,12 @@ pub fn start_audio_capture(
         });
     }
 
+    let mut buffer_encoders = if config.audio.buffer_codec.is_compressed() {
+        Some(BufferEncoders::new(config)?)
+    } else {
+        None
+    };
+
     let mut audio_manager = WasapiAudioManager::with_level_monitor(level_monitor)
         .context("Failed to create audio manager")?;
     audio_manager
@@ -188,6 +257,9 @@ pub fn start_audio_capture(
                         }
                     }
 
+                    if let Some(encoders) = buffer_encoders.as_mut() {
+                        encode_for_buffer(encoders, &mut packet_batch);
+                    }
                     buffer_clone.push_batch(std::mem::take(&mut packet_batch).into_iter());
 
                     if forwarded_packets <= 32 {
@@ -216,6 +288,12 @@ pub fn start_audio_capture(
             }
         }
 
+        if let Some(encoders) = buffer_encoders.as_mut() {
+            let mut tail = Vec::new();
+            encoders.flush(&mut tail);
+            buffer_clone.push_batch(tail);
+        }
+
         running_clone.store(false, Ordering::Release);
 
         debug!(
@@ -183,6 +183,13 @@ impl RecordingPipeline {
             &*self.encoder_factory,
         ) {
             Ok((capture, encoder_handle)) => {
+                buffer.set_video_encoder(Some(
+                    encoder_handle
+                        .effective_config
+                        .encoder_type
+                        .ffmpeg_hevc_codec_name()
+                        .to_string(),
+                ));
                 self.capture = Some(capture);
                 self.encoder_handle = Some(encoder_handle);
             }
@@ -1,8 +1,16 @@
 use crate::{
-    app::RecordingPipeline, buffer::ReplayBuffer, capture::audio::AudioLevelMonitor,
-    config::Config, host::CoreHost,
+    app::{ClipManager, RecordingPipeline},
+    buffer::{
+        checkpoint::{default_checkpoint_dir, DEFAULT_CHECKPOINT_INTERVAL},
+        Bookmark, CheckpointAudioFormat, RecoveredSession, ReplayBuffer, ReplayCheckpointer,
+    },
+    capture::audio::AudioLevelMonitor,
+    config::Config,
+    host::CoreHost,
+    output::AudioTrackLayout,
 };
 use anyhow::Result;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
@@ -53,6 +61,7 @@ impl Drop for TimerResolutionGuard {
 /// - Configuration
 /// - Replay buffer
 /// - Recording pipeline
+/// - Crash-recovery checkpoints of the replay buffer (when enabled)
 ///
 /// # Thread Safety
 ///
@@ -72,6 +81,10 @@ pub struct AppState {
     level_monitor: AudioLevelMonitor,
     /// Optional embedder hooks ([`CoreHost`]).
     host: Option<Arc<dyn CoreHost>>,
+    /// Periodic on-disk checkpoint of `buffer`; `None` unless crash recovery is enabled.
+    checkpointer: Option<ReplayCheckpointer>,
+    /// Replay contents left behind by a crashed previous session, awaiting recover/discard.
+    recovered_session: Option<RecoveredSession>,
     /// 1 ms Windows timer resolution guard. Held only while recording is active so
     /// the system-wide timer interrupt reverts to the ~15.6 ms default at idle.
     #[cfg(windows)]
@@ -84,6 +97,10 @@ impl AppState {
     /// Initializes the replay buffer and recording pipeline with the given
     /// configuration.
     ///
+    /// When `general.crash_recovery_enabled` is set, a checkpoint left behind by a crashed
+    /// previous session is picked up first (see [`Self::recoverable_session`] and
+    /// [`Self::recover_previous_session`]) and checkpointing of the new buffer starts.
+    ///
     /// # Arguments
     ///
     /// * `config` - Application configuration.
@@ -107,12 +124,28 @@ impl AppState {
         let mut pipeline = RecordingPipeline::with_defaults();
         pipeline.set_level_monitor(level_monitor.clone());
 
+        // Take the previous session's checkpoint before our own checkpointer truncates it.
+        let recovered_session = if config.general.crash_recovery_enabled {
+            match RecoveredSession::take(&default_checkpoint_dir()) {
+                Ok(session) => session,
+                Err(e) => {
+                    warn!("Failed to read previous session checkpoint: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let checkpointer = Self::start_checkpointer(&config, &buffer);
+
         Ok(Self {
             config,
             buffer,
             pipeline,
             level_monitor,
             host: None,
+            checkpointer,
+            recovered_session,
             #[cfg(windows)]
             timer_guard: None,
         })
@@ -202,10 +235,115 @@ impl AppState {
         (self.config.clone(), self.buffer.clone())
     }
 
+    /// Bookmarks the current moment of the recording, optionally with a label.
+    ///
+    /// Clips saved over the bookmark get a chapter there.
+    ///
+    /// # Returns
+    ///
+    /// The bookmark, or `None` when not recording or nothing is buffered yet.
+    pub fn add_bookmark(&self, label: Option<String>) -> Option<Bookmark> {
+        if !self.is_recording() {
+            return None;
+        }
+        self.buffer.add_bookmark(label)
+    }
+
     pub fn replay_buffer_stats(&self) -> crate::buffer::BufferStats {
         self.buffer.stats()
     }
 
+    /// Replay contents recovered from a crashed previous session, if any.
+    pub fn recoverable_session(&self) -> Option<&RecoveredSession> {
+        self.recovered_session.as_ref()
+    }
+
+    /// Takes the recovered session so it can be muxed off-thread with
+    /// [`ClipManager::save_recovered_session`].
+    pub fn take_recovered_session(&mut self) -> Option<RecoveredSession> {
+        self.recovered_session.take()
+    }
+
+    /// Muxes the recovered previous session into a clip and deletes its checkpoint.
+    ///
+    /// Blocks while muxing; see [`ClipManager::save_recovered_session`].
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(path))` - the recovered clip.
+    /// - `Ok(None)` - there was nothing to recover.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if muxing fails; the session stays available for another attempt.
+    pub fn recover_previous_session(&mut self) -> Result<Option<PathBuf>> {
+        let Some(session) = self.recovered_session.take() else {
+            return Ok(None);
+        };
+        match ClipManager::save_recovered_session(&self.config, &session) {
+            Ok(path) => {
+                info!("Recovered previous session to {:?}", path);
+                session.discard();
+                Ok(Some(path))
+            }
+            Err(e) => {
+                self.recovered_session = Some(session);
+                Err(e)
+            }
+        }
+    }
+
+    /// Stops checkpointing and deletes the live checkpoint.
+    ///
+    /// Call on a clean shutdown (before exiting without running destructors) so the next
+    /// start does not offer to recover this session.
+    pub fn stop_checkpointing(&mut self) {
+        self.checkpointer = None;
+    }
+
+    /// Drops the recovered previous session and deletes its checkpoint.
+    pub fn discard_previous_session(&mut self) {
+        if let Some(session) = self.recovered_session.take() {
+            session.discard();
+        }
+    }
+
+    fn start_checkpointer(config: &Config, buffer: &ReplayBuffer) -> Option<ReplayCheckpointer> {
+        if !config.general.crash_recovery_enabled {
+            return None;
+        }
+        // Compact once the file holds about two replay windows' worth of RAM contents.
+        let max_file_bytes =
+            u64::from(config.effective_replay_memory_limit_mb()).saturating_mul(2 * 1024 * 1024);
+        let audio_format = CheckpointAudioFormat {
+            buffer_codec: config.audio.buffer_codec,
+            separate_tracks: matches!(
+                AudioTrackLayout::from_audio_config(&config.audio),
+                AudioTrackLayout::Separate { .. }
+            ),
+        };
+        match ReplayCheckpointer::start(
+            buffer.clone(),
+            audio_format,
+            &default_checkpoint_dir(),
+            DEFAULT_CHECKPOINT_INTERVAL,
+            max_file_bytes,
+        ) {
+            Ok(checkpointer) => Some(checkpointer),
+            Err(e) => {
+                warn!("Replay checkpointing disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Restarts checkpointing against the current buffer and config.
+    fn refresh_checkpointer(&mut self) {
+        // Drop the old checkpointer first: it deletes the file the new one will create.
+        self.checkpointer = None;
+        self.checkpointer = Self::start_checkpointer(&self.config, &self.buffer);
+    }
+
     /// Checks if recording is currently active.
     ///
     /// # Returns
@@ -219,7 +357,8 @@ impl AppState {
     ///
     /// Some configuration changes require restarting the recording pipeline
     /// (e.g., encoder changes, resolution changes). This method handles the
-    /// restart automatically with rollback on failure.
+    /// restart automatically with rollback on failure. Replay duration and memory
+    /// limit changes resize the buffer in place and keep its contents.
     ///
     /// # Arguments
     ///
@@ -236,7 +375,10 @@ impl AppState {
     pub fn apply_config(&mut self, mut new_config: Config) -> Result<bool> {
         let needs_restart = self.config.requires_pipeline_restart(&new_config);
         let needs_hotkey_reregister = self.config.requires_hotkey_reregister(&new_config);
+        let needs_buffer_resize = self.config.requires_buffer_resize(&new_config);
         let audio_changed = self.config.audio != new_config.audio;
+        let crash_recovery_changed =
+            self.config.general.crash_recovery_enabled != new_config.general.crash_recovery_enabled;
 
         if needs_restart {
             // Validate the new config before swapping it in.
@@ -249,6 +391,7 @@ impl AppState {
             info!("Restarting pipeline with new configuration...");
 
             self.buffer = ReplayBuffer::new(&self.config)?;
+            self.refresh_checkpointer();
 
             if let Err(e) = self.pipeline.start(&self.config, &self.buffer) {
                 error!("Failed to start pipeline with new config: {}", e);
@@ -256,6 +399,7 @@ impl AppState {
 
                 self.config = old_config;
                 self.buffer = ReplayBuffer::new(&self.config)?;
+                self.refresh_checkpointer();
 
                 match self.pipeline.start(&self.config, &self.buffer) {
                     Ok(()) => {
@@ -275,6 +419,14 @@ impl AppState {
             new_config.validate();
             self.config = new_config;
 
+            if needs_buffer_resize {
+                self.buffer.resize(&self.config);
+            }
+
+            if crash_recovery_changed {
+                self.refresh_checkpointer();
+            }
+
             if audio_changed && self.pipeline.is_recording() {
                 self.pipeline.update_audio_config(&self.config.audio);
             }
@@ -304,4 +456,9 @@ impl AppState {
     pub fn level_monitor(&self) -> &AudioLevelMonitor {
         &self.level_monitor
     }
+
+    /// Gets the replay buffer, e.g. for showing its usage.
+    pub fn replay_buffer(&self) -> &ReplayBuffer {
+        &self.buffer
+    }
 }
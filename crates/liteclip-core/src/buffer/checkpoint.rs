@@ -0,0 +1,569 @@
+//! Crash-recoverable replay checkpoints.
+//!
+//! [`ReplayCheckpointer`] runs a background thread that periodically appends every packet pushed
+//! into the replay ring since the previous tick to a checkpoint file, together with the cached
+//! codec parameter sets (VPS/SPS/PPS). Each tick is flushed and synced, so after a crash or power
+//! loss the file holds the replay window up to the last tick.
+//!
+//! On startup [`RecoveredSession::take`] moves a leftover checkpoint aside and decodes it into a
+//! muxable packet list (see [`crate::app::ClipManager::save_recovered_session`]). A clean shutdown
+//! drops the checkpointer, which deletes its file, so nothing is offered after a normal exit.
+//!
+//! # File format
+//!
+//! [`CHECKPOINT_MAGIC`], an 8-byte audio format header, then records in the disk spill record
+//! format (see [`crate::buffer::ring::disk_spill`]):
+//!
+//! ```text
+//! u8 buffer_codec (0 = PCM, 1 = AAC, 2 = Opus) | u8 flags (bit 0: separate tracks) | 6 reserved
+//! ```
+//!
+//! The header records how the session buffered its audio, so a recovered session is muxed the
+//! way it was captured even when the audio settings changed before the next start. Parameter
+//! sets are stored as video records with the parameter-set flag and are re-emitted whenever the
+//! encoder's parameter sets change. The file is compacted (rewritten from the current ring
+//! contents) once it grows past its size budget, and truncated whenever the ring is cleared or
+//! restarted.
+
+use crate::buffer::ring::disk_spill::{
+    decode_tagged_records, encode_tagged_record_header, RECORD_FLAG_PARAMETER_SET,
+    RECORD_HEADER_LEN,
+};
+use crate::buffer::ring::spmc_ring::{snapshot_sort_key, RingCursor};
+use crate::buffer::ring::{qpc_frequency, SharedReplayBuffer};
+use crate::buffer::BufferResult;
+use crate::config::BufferAudioCodec;
+use crate::encode::{EncodedPacket, StreamType};
+use bytes::Bytes;
+use crossbeam_channel::{RecvTimeoutError, Sender};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Magic bytes at the start of every checkpoint file.
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"LCCKPT02";
+
+/// Length of the magic plus the audio format header; records start here.
+const CHECKPOINT_HEADER_LEN: usize = CHECKPOINT_MAGIC.len() + 8;
+
+/// Audio format header flag: system audio and microphone were buffered as separate tracks.
+const AUDIO_FLAG_SEPARATE_TRACKS: u8 = 0b0000_0001;
+
+/// How often the checkpointer appends new packets to disk.
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// File the live session appends to.
+const ACTIVE_FILE_NAME: &str = "replay-checkpoint.lcrec";
+
+/// A checkpoint left behind by a crashed session, moved aside so the new session can start its
+/// own checkpoint without overwriting it.
+const PENDING_FILE_NAME: &str = "replay-checkpoint.pending.lcrec";
+
+/// Lower bound for the checkpoint size budget before compaction.
+const MIN_CHECKPOINT_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default directory for replay checkpoints (`%LOCALAPPDATA%\liteclip\recovery`).
+#[cfg(not(test))]
+#[must_use]
+pub fn default_checkpoint_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("liteclip")
+        .join("recovery")
+}
+
+/// Unit tests must never pick up (or move aside) the checkpoint of a running app.
+#[cfg(test)]
+#[must_use]
+pub fn default_checkpoint_dir() -> PathBuf {
+    std::env::temp_dir().join("liteclip-tests").join("recovery")
+}
+
+/// How a session buffered its audio, stored in the checkpoint header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointAudioFormat {
+    /// Codec of the buffered audio packets.
+    pub buffer_codec: BufferAudioCodec,
+    /// System audio and microphone were kept as separate tracks rather than mixed.
+    pub separate_tracks: bool,
+}
+
+impl CheckpointAudioFormat {
+    fn encode(self) -> [u8; 8] {
+        let mut header = [0u8; 8];
+        header[0] = match self.buffer_codec {
+            BufferAudioCodec::Pcm => 0,
+            BufferAudioCodec::Aac => 1,
+            BufferAudioCodec::Opus => 2,
+        };
+        if self.separate_tracks {
+            header[1] |= AUDIO_FLAG_SEPARATE_TRACKS;
+        }
+        header
+    }
+
+    fn decode(header: &[u8]) -> Option<Self> {
+        let buffer_codec = match header.first()? {
+            0 => BufferAudioCodec::Pcm,
+            1 => BufferAudioCodec::Aac,
+            2 => BufferAudioCodec::Opus,
+            _ => return None,
+        };
+        Some(Self {
+            buffer_codec,
+            separate_tracks: header.get(1)? & AUDIO_FLAG_SEPARATE_TRACKS != 0,
+        })
+    }
+}
+
+/// Background writer that keeps an on-disk copy of the replay ring.
+///
+/// Dropping the checkpointer stops the thread and deletes the checkpoint file.
+pub struct ReplayCheckpointer {
+    path: PathBuf,
+    stop_tx: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReplayCheckpointer {
+    /// Starts checkpointing `buffer` into `dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - Replay buffer to mirror.
+    /// * `audio_format` - How `buffer` holds its audio; written to the file header.
+    /// * `dir` - Directory for the checkpoint file; created if missing.
+    /// * `interval` - Time between checkpoint ticks.
+    /// * `max_file_bytes` - Size at which the file is compacted to the current ring contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or file cannot be created or the thread cannot start.
+    pub fn start(
+        buffer: SharedReplayBuffer,
+        audio_format: CheckpointAudioFormat,
+        dir: &Path,
+        interval: Duration,
+        max_file_bytes: u64,
+    ) -> BufferResult<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(ACTIVE_FILE_NAME);
+        let file = CheckpointFile::create(&path, audio_format)?;
+
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(1);
+        let mut writer = CheckpointWriter {
+            buffer,
+            audio_format,
+            file,
+            cursor: RingCursor::default(),
+            written_param_sets: Vec::new(),
+            max_file_bytes: max_file_bytes.max(MIN_CHECKPOINT_FILE_BYTES),
+        };
+        let handle = std::thread::Builder::new()
+            .name("replay-checkpoint".to_string())
+            .spawn(move || loop {
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Err(e) = writer.tick() {
+                            warn!("Replay checkpoint write failed: {}", e);
+                        }
+                    }
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })?;
+
+        info!(
+            "Replay checkpointing every {:.1}s to {:?}",
+            interval.as_secs_f64(),
+            path
+        );
+        Ok(Self {
+            path,
+            stop_tx: Some(stop_tx),
+            handle: Some(handle),
+        })
+    }
+
+    /// Path of the live checkpoint file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ReplayCheckpointer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.stop_tx.take() {
+            let _ = tx.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        match fs::remove_file(&self.path) {
+            Ok(()) => debug!("Removed replay checkpoint {:?}", self.path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove replay checkpoint {:?}: {}", self.path, e),
+        }
+    }
+}
+
+struct CheckpointFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    len: u64,
+}
+
+impl CheckpointFile {
+    fn create(path: &Path, audio_format: CheckpointAudioFormat) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        writer.write_all(&audio_format.encode())?;
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            len: CHECKPOINT_HEADER_LEN as u64,
+        })
+    }
+
+    fn append(&mut self, packet: &EncodedPacket, extra_flags: u8) -> std::io::Result<()> {
+        self.writer
+            .write_all(&encode_tagged_record_header(packet, extra_flags))?;
+        self.writer.write_all(packet.data.as_ref())?;
+        self.len += (RECORD_HEADER_LEN + packet.data.len()) as u64;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+}
+
+struct CheckpointWriter {
+    buffer: SharedReplayBuffer,
+    audio_format: CheckpointAudioFormat,
+    file: CheckpointFile,
+    cursor: RingCursor,
+    written_param_sets: Vec<Bytes>,
+    max_file_bytes: u64,
+}
+
+impl CheckpointWriter {
+    fn tick(&mut self) -> std::io::Result<()> {
+        let (packets, next_cursor, reset) = self.buffer.packets_since(self.cursor);
+        self.cursor = next_cursor;
+
+        if reset {
+            debug!("Replay buffer was reset; truncating checkpoint");
+            self.file = CheckpointFile::create(&self.file.path, self.audio_format)?;
+            self.written_param_sets.clear();
+        }
+
+        self.write_param_sets_if_changed()?;
+        for packet in &packets {
+            self.file.append(packet, 0)?;
+        }
+        self.file.sync()?;
+
+        if self.file.len > self.max_file_bytes {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn write_param_sets_if_changed(&mut self) -> std::io::Result<()> {
+        let param_sets = self.buffer.parameter_set_packets(0);
+        let unchanged = param_sets.len() == self.written_param_sets.len()
+            && param_sets
+                .iter()
+                .zip(&self.written_param_sets)
+                .all(|(current, written)| current.data == *written);
+        if param_sets.is_empty() || unchanged {
+            return Ok(());
+        }
+        for packet in &param_sets {
+            self.file.append(packet, RECORD_FLAG_PARAMETER_SET)?;
+        }
+        self.written_param_sets = param_sets.into_iter().map(|p| p.data).collect();
+        Ok(())
+    }
+
+    /// Rewrites the checkpoint from the current ring contents via a temp file + rename so a
+    /// crash mid-compaction still leaves a readable checkpoint.
+    fn compact(&mut self) -> std::io::Result<()> {
+        let before = self.file.len;
+        let tmp_path = self.file.path.with_extension("lcrec.tmp");
+        let mut compacted = CheckpointFile::create(&tmp_path, self.audio_format)?;
+
+        let (packets, next_cursor, _) = self.buffer.packets_since(RingCursor::default());
+        let param_sets = self.buffer.parameter_set_packets(0);
+        for packet in &param_sets {
+            compacted.append(packet, RECORD_FLAG_PARAMETER_SET)?;
+        }
+        for packet in &packets {
+            compacted.append(packet, 0)?;
+        }
+        compacted.sync()?;
+        let compacted_len = compacted.len;
+        drop(compacted);
+
+        fs::rename(&tmp_path, &self.file.path)?;
+        let writer = BufWriter::new(OpenOptions::new().append(true).open(&self.file.path)?);
+        self.file.writer = writer;
+        self.file.len = compacted_len;
+        self.cursor = next_cursor;
+        self.written_param_sets = param_sets.into_iter().map(|p| p.data).collect();
+
+        debug!(
+            "Compacted replay checkpoint: {:.1}MB -> {:.1}MB",
+            before as f64 / 1_048_576.0,
+            compacted_len as f64 / 1_048_576.0
+        );
+        Ok(())
+    }
+}
+
+/// Replay contents recovered from a checkpoint left by a crashed session.
+#[derive(Debug)]
+pub struct RecoveredSession {
+    source: PathBuf,
+    audio_format: CheckpointAudioFormat,
+    packets: Vec<EncodedPacket>,
+}
+
+impl RecoveredSession {
+    /// Moves a leftover checkpoint in `dir` aside and decodes it.
+    ///
+    /// A checkpoint from the most recent crash replaces any older one that was never recovered
+    /// or discarded. Returns `None` when there is nothing to recover or the checkpoint holds no
+    /// decodable keyframe.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint exists but cannot be moved or read.
+    pub fn take(dir: &Path) -> BufferResult<Option<Self>> {
+        let active = dir.join(ACTIVE_FILE_NAME);
+        let pending = dir.join(PENDING_FILE_NAME);
+        if active.exists() {
+            fs::rename(&active, &pending)?;
+        }
+        if !pending.exists() {
+            return Ok(None);
+        }
+
+        let data = Bytes::from(fs::read(&pending)?);
+        let audio_format = data
+            .strip_prefix(CHECKPOINT_MAGIC.as_slice())
+            .and_then(CheckpointAudioFormat::decode);
+        let Some(audio_format) = audio_format else {
+            warn!(
+                "Ignoring replay checkpoint with unknown format: {:?}",
+                pending
+            );
+            fs::remove_file(&pending)?;
+            return Ok(None);
+        };
+
+        let packets =
+            prepare_recovered_packets(decode_tagged_records(&data, CHECKPOINT_HEADER_LEN));
+        if packets.is_empty() {
+            info!(
+                "Replay checkpoint {:?} had no recoverable keyframe",
+                pending
+            );
+            fs::remove_file(&pending)?;
+            return Ok(None);
+        }
+
+        let session = Self {
+            source: pending,
+            audio_format,
+            packets,
+        };
+        info!(
+            "Found recoverable replay session: {:.1}s, {} packets",
+            session.duration_secs(),
+            session.packets.len()
+        );
+        Ok(Some(session))
+    }
+
+    /// Packets ready for [`crate::output::Muxer::mux_clip`]: starting at a keyframe with the
+    /// parameter sets prepended, sorted by PTS.
+    #[must_use]
+    pub fn packets(&self) -> &[EncodedPacket] {
+        &self.packets
+    }
+
+    /// How the crashed session buffered its audio.
+    #[must_use]
+    pub fn audio_format(&self) -> CheckpointAudioFormat {
+        self.audio_format
+    }
+
+    /// Duration covered by the recovered video.
+    #[must_use]
+    pub fn duration_secs(&self) -> f64 {
+        let mut video_pts = self
+            .packets
+            .iter()
+            .filter(|p| matches!(p.stream, StreamType::Video))
+            .map(|p| p.pts);
+        let first = video_pts.next().unwrap_or(0);
+        let last = video_pts.last().unwrap_or(first);
+        (last - first) as f64 / qpc_frequency().max(1) as f64
+    }
+
+    /// Resolution reported by the first video packet that carries one.
+    #[must_use]
+    pub fn resolution(&self) -> Option<(u32, u32)> {
+        self.packets.iter().find_map(|p| p.resolution)
+    }
+
+    /// Whether any audio was recovered.
+    #[must_use]
+    pub fn has_audio(&self) -> bool {
+        self.packets
+            .iter()
+            .any(|p| !matches!(p.stream, StreamType::Video))
+    }
+
+    /// Deletes the checkpoint this session was read from.
+    pub fn discard(self) {
+        match fs::remove_file(&self.source) {
+            Ok(()) => debug!("Removed recovered replay checkpoint {:?}", self.source),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(
+                "Failed to remove recovered replay checkpoint {:?}: {}",
+                self.source, e
+            ),
+        }
+    }
+}
+
+/// Turns raw checkpoint records into a muxable clip: latest parameter sets prepended at the first
+/// keyframe, everything before that keyframe dropped.
+fn prepare_recovered_packets(records: Vec<(EncodedPacket, u8)>) -> Vec<EncodedPacket> {
+    let mut param_sets: Vec<EncodedPacket> = Vec::new();
+    let mut packets = Vec::with_capacity(records.len());
+    let mut in_param_group = false;
+    for (packet, flags) in records {
+        if flags & RECORD_FLAG_PARAMETER_SET != 0 {
+            // Each re-emission replaces the previous group wholesale.
+            if !in_param_group {
+                param_sets.clear();
+            }
+            param_sets.push(packet);
+            in_param_group = true;
+        } else {
+            packets.push(packet);
+            in_param_group = false;
+        }
+    }
+
+    packets.sort_by_key(snapshot_sort_key);
+
+    let Some(first_keyframe_pts) = packets
+        .iter()
+        .find(|p| p.is_keyframe && matches!(p.stream, StreamType::Video))
+        .map(|p| p.pts)
+    else {
+        return Vec::new();
+    };
+    packets.retain(|p| p.pts >= first_keyframe_pts);
+
+    let mut result = Vec::with_capacity(param_sets.len() + packets.len());
+    result.extend(param_sets.into_iter().map(|mut p| {
+        p.pts = first_keyframe_pts;
+        p.dts = first_keyframe_pts;
+        p
+    }));
+    result.extend(packets);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn video(pts: i64, is_keyframe: bool, payload: &[u8]) -> EncodedPacket {
+        EncodedPacket::new(
+            Bytes::copy_from_slice(payload),
+            pts,
+            pts,
+            is_keyframe,
+            StreamType::Video,
+        )
+    }
+
+    #[test]
+    fn recovered_packets_start_at_keyframe_with_param_sets() {
+        let sps = video(0, false, &[0, 0, 0, 1, 0x67, 1]);
+        let pps = video(0, false, &[0, 0, 0, 1, 0x68, 2]);
+        let records = vec![
+            (sps, RECORD_FLAG_PARAMETER_SET),
+            (pps, RECORD_FLAG_PARAMETER_SET),
+            (video(5, false, &[1]), 0),
+            (video(10, true, &[2]), 0),
+            (video(15, false, &[3]), 0),
+        ];
+
+        let packets = prepare_recovered_packets(records);
+        assert_eq!(packets.len(), 4);
+        assert!(packets[..2].iter().all(|p| p.pts == 10 && !p.is_keyframe));
+        assert!(packets[2].is_keyframe);
+        assert_eq!(packets[3].pts, 15);
+    }
+
+    #[test]
+    fn checkpoint_round_trip_recovers_after_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.general.replay_duration_secs = 60;
+        config.advanced.memory_limit_mb = 64;
+        let buffer = SharedReplayBuffer::new(&config).unwrap();
+        for i in 0..20 {
+            buffer.push(video(
+                i * 1_000_000,
+                i % 5 == 0,
+                &[0, 0, 0, 1, 0x65, i as u8],
+            ));
+        }
+
+        let audio_format = CheckpointAudioFormat {
+            buffer_codec: BufferAudioCodec::Opus,
+            separate_tracks: true,
+        };
+        let checkpointer = ReplayCheckpointer::start(
+            buffer.clone(),
+            audio_format,
+            dir.path(),
+            Duration::from_millis(10),
+            0,
+        )
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        // Simulate a crash: the thread stops but the file is left behind.
+        let active = checkpointer.path().to_path_buf();
+        let preserved = fs::read(&active).unwrap();
+        drop(checkpointer);
+        assert!(!active.exists(), "clean shutdown removes the checkpoint");
+        fs::write(&active, preserved).unwrap();
+
+        let session = RecoveredSession::take(dir.path()).unwrap().unwrap();
+        assert_eq!(session.packets().len(), 20);
+        assert!(session.packets()[0].is_keyframe);
+        assert!(!session.has_audio());
+        assert_eq!(session.audio_format(), audio_format);
+        assert!(!active.exists());
+
+        session.discard();
+        assert!(RecoveredSession::take(dir.path()).unwrap().is_none());
+    }
+}
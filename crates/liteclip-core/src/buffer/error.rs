@@ -10,6 +10,19 @@ pub enum BufferError {
     /// which would cause unbounded memory growth.
     #[error("Snapshot rejected: outstanding snapshot bytes ({current}) exceeds limit ({limit})")]
     SnapshotLimitExceeded { current: usize, limit: usize },
+    /// Pin rejected because pinned ranges already hold the whole pin budget.
+    #[error("Pin rejected: pinned bytes ({current}) exceed pin budget ({limit})")]
+    PinBudgetExceeded { current: usize, limit: usize },
+    /// A PTS range whose end precedes its start.
+    #[error("Invalid PTS range: {start_pts}..{end_pts}")]
+    InvalidRange { start_pts: i64, end_pts: i64 },
+    /// A chunked read fell behind eviction, or the buffer restarted, before it read every
+    /// packet of its window.
+    #[error("Chunked read overrun: {lost} packets left the buffer before they were read")]
+    ReadOverrun { lost: usize },
+    /// I/O failure in the disk spill tier.
+    #[error("Buffer I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 pub type BufferResult<T> = std::result::Result<T, BufferError>;
@@ -35,6 +48,30 @@ mod tests {
         assert!(msg.contains("256000000"));
     }
 
+    #[test]
+    fn pin_budget_exceeded_display() {
+        let err = BufferError::PinBudgetExceeded {
+            current: 300_000_000,
+            limit: 268_435_456,
+        };
+        let msg = format!("{}", err);
+        assert!(msg.contains("300000000"));
+        assert!(msg.contains("268435456"));
+    }
+
+    #[test]
+    fn read_overrun_display() {
+        let err = BufferError::ReadOverrun { lost: 42 };
+        assert!(format!("{}", err).contains("42"));
+    }
+
+    #[test]
+    fn io_error_display() {
+        let err: BufferError =
+            std::io::Error::new(std::io::ErrorKind::PermissionDenied, "spill dir").into();
+        assert_eq!(format!("{}", err), "Buffer I/O error: spill dir");
+    }
+
     #[test]
     fn buffer_result_alias() {
         fn returns_result() -> BufferResult<String> {
@@ -15,6 +15,9 @@
 //! - [`ReplayBuffer`] - Main buffer handle with configuration-based capacity
 //! - [`SharedReplayBuffer`] - Handle wrapping the ring implementation
 //! - [`BufferStats`] - Statistics about buffer utilization
+//! - [`ReplaySubscription`] - Live tail of newly pushed packets
+//! - [`SnapshotChunks`] - Clip window read in bounded chunks for streaming saves
+//! - [`ReplayCheckpointer`] / [`RecoveredSession`] - Crash-recovery checkpoints of the ring
 //!
 //! # Memory Management
 //!
@@ -40,8 +43,13 @@
 //! println!("Buffer: {:.1}s, {} MB", stats.duration_secs, stats.total_bytes / 1024 / 1024);
 //! ```
 
+pub mod checkpoint;
 pub mod error;
 pub mod ring;
 
+pub use checkpoint::{CheckpointAudioFormat, RecoveredSession, ReplayCheckpointer};
 pub use error::{BufferError, BufferResult};
-pub use ring::{BufferStats, ReplayBuffer, SharedReplayBuffer};
+pub use ring::{
+    Bookmark, BufferStats, PinHandle, ReplayBuffer, ReplaySubscription, SavedClip,
+    SharedReplayBuffer, SnapshotChunks, StreamGaps, StreamStats, TailBatch,
+};
@@ -0,0 +1,266 @@
+//! Bounded chunked reads
+//!
+//! [`SnapshotChunks`] walks a clip window of the ring in write order and yields it as a
+//! sequence of [`TrackedSnapshot`]s, each holding at most a fixed number of packet bytes.
+//! Consumers that process one chunk at a time (the clip saver feeding the muxer) keep their
+//! extra memory bounded by the chunk size instead of the clip length.
+//!
+//! Chunks are sorted like snapshots within themselves; across chunks each stream stays in
+//! push order. The first chunk starts at the window's keyframe, carries the audio lead-in a
+//! snapshot would include, and has the cached parameter sets prepended when needed.
+//!
+//! # Disk spill
+//!
+//! A window that starts before the oldest packet in RAM is read from the disk spill first,
+//! streaming only the records it needs, and then continues in the ring. Encoders emit no
+//! B-frames, so PTS grows within each stream in push order; the hand-over skips packets
+//! whose PTS is not past the last one returned for their stream, which the spill and the
+//! ring may both have read.
+//!
+//! # Overrun
+//!
+//! The window is fixed when the read starts: packets pushed afterwards are not included.
+//! The producer never waits for the reader, so if packets of the window are evicted or
+//! overwritten before the reader gets to them, or the buffer is cleared or restarted, the
+//! next chunk is [`BufferError::ReadOverrun`] and the iterator ends. Callers can then fall
+//! back to a full snapshot (pinned ranges keep evicted packets readable).
+//!
+//! With a disk spill, packets evicted mid-read are read back from the spill instead, so a
+//! read only overruns when the buffer is cleared or restarted, the spill loses packets of the
+//! window, or a pinned range takes them. An open-ended window that falls back to the spill
+//! mid-read may end a few packets later than it would have.
+
+use crate::buffer::{BufferError, BufferResult};
+use crate::encode::{EncodedPacket, StreamType};
+
+use super::disk_spill::SpillCursor;
+use super::spmc_ring::{stream_slot, LockFreeReplayBuffer, RingCursor, TrackedSnapshot};
+
+/// Result of [`LockFreeReplayBuffer::read_chunk`].
+pub(crate) struct ChunkRead {
+    pub(crate) packets: Vec<EncodedPacket>,
+    pub(crate) cursor: RingCursor,
+    /// The read reached the end of the window.
+    pub(crate) end_reached: bool,
+}
+
+/// Iterator over a clip window in bounded chunks.
+///
+/// Created by [`LockFreeReplayBuffer::snapshot_chunks`]. See the [module docs](self) for
+/// ordering and overrun semantics.
+pub struct SnapshotChunks {
+    buffer: LockFreeReplayBuffer,
+    cursor: RingCursor,
+    /// Write index at creation; the window ends before it.
+    end_idx: usize,
+    keyframe_pts: i64,
+    end_pts: i64,
+    max_chunk_bytes: usize,
+    /// Audio pushed before the keyframe but timed after it; goes into the first chunk.
+    lead_in: Option<Vec<EncodedPacket>>,
+    /// Disk spill position, for reads that started in the spill.
+    spill: Option<SpillPhase>,
+    /// PTS of the last packet returned per stream, indexed by [`stream_slot`].
+    last_pts: [Option<i64>; 3],
+    finished: bool,
+}
+
+/// Progress of the part of a read that goes through the disk spill.
+struct SpillPhase {
+    cursor: SpillCursor,
+    /// Reading from the spill (as opposed to the ring).
+    active: bool,
+    /// Where the ring part continues, once the spill has been read up to it.
+    handoff: Option<RingCursor>,
+}
+
+impl SnapshotChunks {
+    pub(super) fn new(
+        buffer: LockFreeReplayBuffer,
+        cursor: RingCursor,
+        end_idx: usize,
+        keyframe_pts: i64,
+        end_pts: i64,
+        max_chunk_bytes: usize,
+        lead_in: Vec<EncodedPacket>,
+    ) -> Self {
+        Self {
+            buffer,
+            cursor,
+            end_idx,
+            keyframe_pts,
+            end_pts,
+            max_chunk_bytes: max_chunk_bytes.max(1),
+            lead_in: Some(lead_in),
+            spill: None,
+            last_pts: [None; 3],
+            finished: false,
+        }
+    }
+
+    /// Starts the read at `spill_cursor` in the disk spill instead of in the ring.
+    pub(super) fn from_spill(
+        buffer: LockFreeReplayBuffer,
+        spill_cursor: SpillCursor,
+        cursor: RingCursor,
+        end_idx: usize,
+        keyframe_pts: i64,
+        end_pts: i64,
+        max_chunk_bytes: usize,
+    ) -> Self {
+        let mut chunks = Self::new(
+            buffer,
+            cursor,
+            end_idx,
+            keyframe_pts,
+            end_pts,
+            max_chunk_bytes,
+            Vec::new(),
+        );
+        chunks.spill = Some(SpillPhase {
+            cursor: spill_cursor,
+            active: true,
+            handoff: None,
+        });
+        chunks
+    }
+
+    /// PTS of the keyframe the clip starts at.
+    #[must_use]
+    pub fn start_pts(&self) -> i64 {
+        self.keyframe_pts
+    }
+
+    /// Whether the window starts in the disk spill. Such reads follow evictions into the
+    /// spill, so the window must not be pinned while they run.
+    #[must_use]
+    pub fn reads_spill(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Whether `pts` of `stream` comes after the last packet returned for that stream.
+    fn is_new(&self, stream: StreamType, pts: i64) -> bool {
+        self.last_pts[stream_slot(stream)].map_or(true, |last| pts > last)
+    }
+
+    /// Reads the next batch of packets, from the spill or the ring. An empty batch is not
+    /// the end of the window unless `finished` is set.
+    fn read_next(&mut self, max_bytes: usize) -> BufferResult<Vec<EncodedPacket>> {
+        if let Some(phase) = self.spill.as_ref().filter(|phase| phase.active) {
+            let last_pts = self.last_pts;
+            let (keyframe_pts, end_pts) = (self.keyframe_pts, self.end_pts);
+            let read = self.buffer.read_spill_chunk(
+                phase.cursor,
+                keyframe_pts,
+                max_bytes,
+                |stream, pts| {
+                    pts >= keyframe_pts
+                        && pts <= end_pts
+                        && last_pts[stream_slot(stream)].map_or(true, |last| pts > last)
+                },
+            )?;
+
+            let handoff = match (read.end_reached, phase.handoff) {
+                (false, handoff) => handoff,
+                // The spill holds everything evicted before the hand-over point once the
+                // writer has drained; read it up to there before switching to the ring.
+                (true, None) => Some(self.buffer.spill_handoff(
+                    self.cursor,
+                    self.keyframe_pts,
+                    self.end_pts,
+                    self.end_idx,
+                )?),
+                (true, Some(handoff)) => {
+                    self.cursor = handoff;
+                    None
+                }
+            };
+            if let Some(phase) = self.spill.as_mut() {
+                phase.cursor = read.cursor;
+                phase.active = !read.end_reached || handoff.is_some();
+                phase.handoff = handoff;
+            }
+            return Ok(read.packets);
+        }
+
+        let read = match self.buffer.read_chunk(
+            self.cursor,
+            self.end_idx,
+            self.keyframe_pts,
+            self.end_pts,
+            max_bytes,
+        ) {
+            Ok(read) => read,
+            // Packets evicted before the reader got to them are in the spill by now.
+            Err(BufferError::ReadOverrun { .. }) if self.spill.is_some() => {
+                if let Some(phase) = self.spill.as_mut() {
+                    phase.active = true;
+                }
+                return Ok(Vec::new());
+            }
+            Err(err) => return Err(err),
+        };
+        self.cursor = read.cursor;
+        self.finished = read.end_reached;
+        let mut packets = read.packets;
+        packets.retain(|packet| self.is_new(packet.stream, packet.pts));
+        Ok(packets)
+    }
+}
+
+impl Iterator for SnapshotChunks {
+    type Item = BufferResult<TrackedSnapshot>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lead_in = self.lead_in.take();
+        let lead_in_bytes = lead_in
+            .iter()
+            .flatten()
+            .map(|packet| packet.data.len())
+            .sum::<usize>();
+        let max_bytes = self.max_chunk_bytes.saturating_sub(lead_in_bytes).max(1);
+
+        let mut packets = Vec::new();
+        // Spill reads and hand-overs can come back empty; keep going until there is
+        // something to return.
+        while packets.is_empty() && !self.finished {
+            match self.read_next(max_bytes) {
+                Ok(read) => packets = read,
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        let first = lead_in.is_some();
+        packets.extend(lead_in.into_iter().flatten());
+        for packet in &packets {
+            let last = &mut self.last_pts[stream_slot(packet.stream)];
+            *last = Some(last.map_or(packet.pts, |last| last.max(packet.pts)));
+        }
+
+        packets.sort_by_key(super::spmc_ring::snapshot_sort_key);
+        if first {
+            packets = self.buffer.prepend_parameter_sets(packets);
+        }
+
+        if packets.is_empty() {
+            return None;
+        }
+        Some(Ok(self.buffer.track_snapshot(packets)))
+    }
+}
+
+impl std::fmt::Debug for SnapshotChunks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotChunks")
+            .field("cursor", &self.cursor)
+            .field("end_idx", &self.end_idx)
+            .field("keyframe_pts", &self.keyframe_pts)
+            .field("end_pts", &self.end_pts)
+            .field("max_chunk_bytes", &self.max_chunk_bytes)
+            .field("reads_spill", &self.reads_spill())
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
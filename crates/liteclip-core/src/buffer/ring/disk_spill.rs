@@ -0,0 +1,1370 @@
+//! Disk-backed spill tier for the replay ring.
+//!
+//! When `general.replay_disk_limit_gb` is non-zero, packets evicted from the in-memory ring
+//! (memory pressure, ring wrap, the duration limit or a resize) and not retained by a pin are
+//! handed to a background writer thread that appends them
+//! to rolling segment files in the cache directory. [`LockFreeReplayBuffer::snapshot_from`]
+//! reads those segments back when the requested window starts before the oldest packet still
+//! held in RAM, so long replay windows do not need gigabytes of memory.
+//!
+//! # Reading
+//!
+//! Segments are streamed from disk rather than loaded whole: record headers are scanned and
+//! only the payloads inside the requested window are read. Each segment indexes the record
+//! offset of its video keyframes, so a read starts one GOP before its window instead of at
+//! the start of the segment. [`DiskSpillTier::read_chunk`] reads the spill in bounded chunks
+//! for [`SnapshotChunks`](super::chunks::SnapshotChunks).
+//!
+//! # Segment format
+//!
+//! Each segment starts with [`SEGMENT_MAGIC`] followed by packet records:
+//!
+//! ```text
+//! u32 payload_len | i64 pts | i64 dts | u8 stream | u8 flags | u8 codec | u8 reserved
+//!   | u32 width | u32 height | payload
+//! ```
+//!
+//! All integers are little-endian. A `width`/`height` of zero means "no resolution".
+//! A truncated trailing record is ignored when decoding.
+//!
+//! # Eviction and cleanup
+//!
+//! - Whole segments are deleted oldest-first when the tier exceeds its byte budget.
+//! - Segments whose newest packet is older than the replay window are deleted.
+//! - [`DiskSpillTier::clear`] deletes every segment; dropping the tier removes its session
+//!   directory. Session directories left behind by processes that are no longer running are
+//!   removed at startup.
+//!
+//! The producer never blocks on disk I/O: evicted packets are queued with `try_send` and
+//! dropped (with a warning) if the writer falls behind. Dropped packets, and packets the writer
+//! failed to write, are recorded as [`SpillGap`]s; reads report the gaps in their window and
+//! callers start the clip after them instead of muxing across the hole.
+//!
+//! [`LockFreeReplayBuffer::snapshot_from`]: super::spmc_ring::LockFreeReplayBuffer::snapshot_from
+
+use crate::buffer::{BufferError, BufferResult};
+use crate::encode::{EncodedPacket, StreamType, VideoCodec};
+use bytes::Bytes;
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::spmc_ring::stream_slot;
+
+/// Magic bytes at the start of every spill segment file.
+pub const SEGMENT_MAGIC: &[u8; 8] = b"LCSPILL1";
+
+/// Bytes in one gigabyte as used by `replay_disk_limit_gb`.
+pub const BYTES_PER_GB: u64 = 1024 * 1024 * 1024;
+
+/// Size of the fixed record header preceding each packet payload.
+pub(crate) const RECORD_HEADER_LEN: usize = 32;
+
+/// Record flag: the packet is a keyframe.
+pub(crate) const RECORD_FLAG_KEYFRAME: u8 = 0b0000_0001;
+
+/// Record flag: the packet is a cached codec parameter set rather than a timed sample
+/// (used by replay checkpoints).
+pub(crate) const RECORD_FLAG_PARAMETER_SET: u8 = 0b0000_0010;
+
+/// Segments are rotated once they reach this size (or a quarter of the budget, if smaller).
+const SEGMENT_TARGET_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Maximum number of evicted packets queued for the writer thread.
+const SPILL_QUEUE_CAPACITY: usize = 4096;
+
+/// How long a snapshot waits for the writer to drain its queue before reading segments.
+const SPILL_SYNC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Read buffer for streaming a segment; also bounds how far a skipped payload is read ahead.
+const SEGMENT_READ_BUFFER_BYTES: usize = 256 * 1024;
+
+/// Log a queue-overflow warning once per this many dropped packets.
+const DROPPED_PACKET_WARN_INTERVAL: usize = 256;
+
+static NEXT_SESSION_ID: AtomicUsize = AtomicUsize::new(0);
+
+enum SpillCommand {
+    Packet {
+        packet: EncodedPacket,
+        newest_pts: i64,
+    },
+    Clear,
+    Sync(Sender<()>),
+    SetMaxDuration(i64),
+}
+
+/// Metadata for one segment file. Only bytes up to `committed_bytes` are guaranteed flushed.
+#[derive(Clone)]
+struct SpillSegment {
+    id: u64,
+    path: PathBuf,
+    committed_bytes: u64,
+    first_pts: i64,
+    last_pts: i64,
+    /// PTS and record offset of each video keyframe, in write order.
+    keyframes: Vec<(i64, u64)>,
+    packet_count: usize,
+    /// Records of each stream, indexed by [`stream_slot`].
+    streams: [SpillStreamUsage; 3],
+}
+
+/// Records of one stream held in the spill.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct SpillStreamUsage {
+    /// Bytes of the records, headers included.
+    pub(crate) bytes: u64,
+    pub(crate) packet_count: usize,
+    pub(crate) oldest_pts: Option<i64>,
+    pub(crate) newest_pts: Option<i64>,
+}
+
+impl SpillStreamUsage {
+    fn add(&mut self, other: &Self) {
+        self.bytes += other.bytes;
+        self.packet_count += other.packet_count;
+        self.oldest_pts = match (self.oldest_pts, other.oldest_pts) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.newest_pts = self.newest_pts.max(other.newest_pts);
+    }
+}
+
+impl SpillSegment {
+    fn new(id: u64, path: PathBuf) -> Self {
+        Self {
+            id,
+            path,
+            committed_bytes: 0,
+            first_pts: i64::MAX,
+            last_pts: i64::MIN,
+            keyframes: Vec::new(),
+            packet_count: 0,
+            streams: [SpillStreamUsage::default(); 3],
+        }
+    }
+
+    /// Offset to scan from for packets at or after `start_pts`: the keyframe before the last
+    /// one at or before `start_pts`, so audio written ahead of that keyframe is found too.
+    fn scan_offset(&self, start_pts: i64) -> u64 {
+        match self
+            .keyframes
+            .iter()
+            .rposition(|&(pts, _)| pts <= start_pts)
+        {
+            Some(pos) if pos > 0 => self.keyframes[pos - 1].1,
+            _ => SEGMENT_MAGIC.len() as u64,
+        }
+    }
+}
+
+/// Position of a chunked spill read: the offset of the next record, in write order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SpillCursor {
+    segment_id: u64,
+    offset: u64,
+}
+
+/// Result of [`DiskSpillTier::read_chunk`].
+pub(crate) struct SpillChunk {
+    pub(crate) packets: Vec<EncodedPacket>,
+    pub(crate) cursor: SpillCursor,
+    /// The read reached the last committed record.
+    pub(crate) end_reached: bool,
+}
+
+/// Inclusive PTS span of evicted packets that never reached disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SpillGap {
+    pub(crate) start_pts: i64,
+    pub(crate) end_pts: i64,
+}
+
+/// Packets read back from the spill, with the gaps overlapping the read window (oldest first).
+#[derive(Debug, Default)]
+pub(crate) struct SpillRead {
+    pub(crate) packets: Vec<EncodedPacket>,
+    pub(crate) gaps: Vec<SpillGap>,
+}
+
+impl SpillRead {
+    /// Drops the packets up to the newest gap and then up to the next video keyframe, so the
+    /// remaining packets can be muxed without crossing lost packets. Returns that gap.
+    pub(crate) fn cut_after_gaps(&mut self) -> Option<SpillGap> {
+        let gap = *self.gaps.last()?;
+        self.packets.retain(|packet| packet.pts > gap.end_pts);
+        match self
+            .packets
+            .iter()
+            .find(|p| p.is_keyframe && matches!(p.stream, StreamType::Video))
+        {
+            Some(keyframe) => {
+                let keyframe_pts = keyframe.pts;
+                self.packets.retain(|packet| packet.pts >= keyframe_pts);
+            }
+            None => self.packets.clear(),
+        }
+        Some(gap)
+    }
+}
+
+#[derive(Default)]
+struct SpillIndex {
+    segments: VecDeque<SpillSegment>,
+    total_bytes: u64,
+    total_packets: usize,
+    gaps: VecDeque<SpillGap>,
+}
+
+impl SpillIndex {
+    /// Records a packet lost at `pts`, extending the newest gap when nothing was spilled since.
+    fn record_gap(&mut self, pts: i64, extend: bool) {
+        match self.gaps.back_mut() {
+            Some(gap) if extend => {
+                gap.start_pts = gap.start_pts.min(pts);
+                gap.end_pts = gap.end_pts.max(pts);
+            }
+            _ => self.gaps.push_back(SpillGap {
+                start_pts: pts,
+                end_pts: pts,
+            }),
+        }
+    }
+
+    fn gaps_in(&self, start_pts: i64, end_pts: i64) -> Vec<SpillGap> {
+        self.gaps
+            .iter()
+            .filter(|gap| gap.end_pts >= start_pts && gap.start_pts < end_pts)
+            .copied()
+            .collect()
+    }
+}
+
+/// Rolling on-disk segment log fed by packets evicted from the RAM ring.
+pub(crate) struct DiskSpillTier {
+    session_dir: PathBuf,
+    tx: Option<Sender<SpillCommand>>,
+    index: Arc<parking_lot::Mutex<SpillIndex>>,
+    dropped_packets: AtomicUsize,
+    /// The last offered packet was dropped; the next drop extends the same gap.
+    dropping: AtomicBool,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl DiskSpillTier {
+    /// Creates the tier under the default cache root and starts its writer thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_bytes` - Disk budget for all segments of this tier.
+    /// * `max_duration_qpc` - Replay window; segments entirely older than this are deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session directory cannot be created or the writer thread
+    /// cannot be spawned.
+    pub(crate) fn new(max_bytes: u64, max_duration_qpc: i64) -> BufferResult<Self> {
+        Self::with_root(&spill_root(), max_bytes, max_duration_qpc)
+    }
+
+    fn with_root(root: &Path, max_bytes: u64, max_duration_qpc: i64) -> BufferResult<Self> {
+        remove_stale_sessions(root);
+
+        let session_dir = root.join(format!(
+            "{}{}",
+            session_prefix(),
+            NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&session_dir)?;
+
+        let index = Arc::new(parking_lot::Mutex::new(SpillIndex::default()));
+        let (tx, rx) = crossbeam_channel::bounded(SPILL_QUEUE_CAPACITY);
+        let writer = SpillWriter {
+            session_dir: session_dir.clone(),
+            index: Arc::clone(&index),
+            max_bytes,
+            max_duration_qpc,
+            segment_target_bytes: SEGMENT_TARGET_BYTES.min((max_bytes / 4).max(1024 * 1024)),
+            next_segment_id: 0,
+            current: None,
+            write_errors: 0,
+            last_write_failed: false,
+        };
+        let handle = std::thread::Builder::new()
+            .name("replay-disk-spill".to_string())
+            .spawn(move || writer.run(rx))?;
+
+        info!(
+            "Replay disk spill enabled: {:.1}GB budget in {:?}",
+            max_bytes as f64 / BYTES_PER_GB as f64,
+            session_dir
+        );
+
+        Ok(Self {
+            session_dir,
+            tx: Some(tx),
+            index,
+            dropped_packets: AtomicUsize::new(0),
+            dropping: AtomicBool::new(false),
+            writer: Some(handle),
+        })
+    }
+
+    /// Queues an evicted packet for the writer. Never blocks the producer; a packet that does
+    /// not fit in the queue is recorded as a gap.
+    pub(crate) fn offer(&self, packet: EncodedPacket, newest_pts: i64) {
+        let Some(tx) = self.tx.as_ref() else {
+            return;
+        };
+        match tx.try_send(SpillCommand::Packet { packet, newest_pts }) {
+            Ok(()) => self.dropping.store(false, Ordering::Relaxed),
+            Err(TrySendError::Full(SpillCommand::Packet { packet, .. })) => {
+                let extend = self.dropping.swap(true, Ordering::Relaxed);
+                self.index.lock().record_gap(packet.pts, extend);
+                let dropped = self.dropped_packets.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped % DROPPED_PACKET_WARN_INTERVAL == 1 {
+                    warn!(
+                        "Replay disk spill queue full; dropped {} evicted packets so far",
+                        dropped
+                    );
+                }
+            }
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    /// Deletes all spilled segments.
+    pub(crate) fn clear(&self) {
+        {
+            let mut index = self.index.lock();
+            index.segments.clear();
+            index.total_bytes = 0;
+            index.total_packets = 0;
+            index.gaps.clear();
+        }
+        if let Some(tx) = self.tx.as_ref() {
+            let _ = tx.send(SpillCommand::Clear);
+        }
+    }
+
+    /// Changes the replay window used to delete old segments; applied on the next write.
+    pub(crate) fn set_max_duration_qpc(&self, max_duration_qpc: i64) {
+        if let Some(tx) = self.tx.as_ref() {
+            let _ = tx.send(SpillCommand::SetMaxDuration(max_duration_qpc));
+        }
+    }
+
+    /// Waits (bounded) until every packet queued so far has been written and committed.
+    ///
+    /// Returns `false` if the writer did not drain in time.
+    pub(crate) fn sync(&self) -> bool {
+        let Some(tx) = self.tx.as_ref() else {
+            return true;
+        };
+        let (done_tx, done_rx) = crossbeam_channel::bounded(1);
+        if tx.send(SpillCommand::Sync(done_tx)).is_ok()
+            && done_rx.recv_timeout(SPILL_SYNC_TIMEOUT).is_err()
+        {
+            warn!(
+                "Replay disk spill did not drain within {:?}; snapshot may miss recent evictions",
+                SPILL_SYNC_TIMEOUT
+            );
+            return false;
+        }
+        true
+    }
+
+    /// PTS of the oldest committed spilled packet.
+    pub(crate) fn oldest_pts(&self) -> Option<i64> {
+        self.index
+            .lock()
+            .segments
+            .iter()
+            .find(|segment| segment.packet_count > 0)
+            .map(|segment| segment.first_pts)
+    }
+
+    /// Committed bytes and packet count currently held on disk.
+    pub(crate) fn usage(&self) -> (u64, usize) {
+        let index = self.index.lock();
+        (index.total_bytes, index.total_packets)
+    }
+
+    /// Committed records of `stream` currently held on disk.
+    pub(crate) fn stream_usage(&self, stream: StreamType) -> SpillStreamUsage {
+        let slot = stream_slot(stream);
+        let mut usage = SpillStreamUsage::default();
+        for segment in &self.index.lock().segments {
+            usage.add(&segment.streams[slot]);
+        }
+        usage
+    }
+
+    /// PTS of the last spilled video keyframe at or before `pts`.
+    pub(crate) fn keyframe_at_or_before(&self, pts: i64) -> Option<i64> {
+        self.index
+            .lock()
+            .segments
+            .iter()
+            .flat_map(|segment| segment.keyframes.iter().map(|&(pts, _)| pts))
+            .filter(|&keyframe_pts| keyframe_pts <= pts)
+            .max()
+    }
+
+    /// The newest recorded gap.
+    pub(crate) fn newest_gap(&self) -> Option<SpillGap> {
+        self.index.lock().gaps.back().copied()
+    }
+
+    /// Reads spilled packets in `[start, end_pts)`, where `start` is the last spilled keyframe
+    /// at or before `start_pts` (or the first one after it when none precede it).
+    ///
+    /// Returned packets are in segment order (oldest first). When the window crosses a gap,
+    /// the packets start at the first keyframe after the newest gap instead; the gaps in the
+    /// window are reported with them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a segment that is still indexed cannot be read.
+    pub(crate) fn read_range(&self, start_pts: i64, end_pts: i64) -> BufferResult<SpillRead> {
+        let keyframes: Vec<i64> = {
+            let index = self.index.lock();
+            index
+                .segments
+                .iter()
+                .filter(|segment| segment.packet_count > 0)
+                .flat_map(|segment| segment.keyframes.iter().map(|&(pts, _)| pts))
+                .collect()
+        };
+
+        let mut last_at_or_before = None;
+        let mut first_after = None;
+        for pts in keyframes {
+            if pts <= start_pts {
+                last_at_or_before = Some(pts);
+            } else if first_after.is_none() {
+                first_after = Some(pts);
+            }
+        }
+        let Some(aligned_start) = last_at_or_before.or(first_after) else {
+            return Ok(SpillRead::default());
+        };
+        if aligned_start >= end_pts {
+            return Ok(SpillRead::default());
+        }
+
+        let mut result = self.read_unaligned(aligned_start, end_pts)?;
+        if let Some(gap) = result.cut_after_gaps() {
+            warn!(
+                "Replay disk spill lost packets at pts {}..={}; spilled window starts after the gap",
+                gap.start_pts, gap.end_pts
+            );
+        }
+        debug!(
+            "Read {} spilled packets for window start_pts={} (aligned {}), end_pts={}",
+            result.packets.len(),
+            start_pts,
+            aligned_start,
+            end_pts
+        );
+        Ok(result)
+    }
+
+    /// Reads every spilled packet in `[start_pts, end_pts)` without keyframe alignment, along
+    /// with the gaps in that window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a segment that is still indexed cannot be read.
+    pub(crate) fn read_unaligned(&self, start_pts: i64, end_pts: i64) -> BufferResult<SpillRead> {
+        let (segments, gaps): (Vec<SpillSegment>, Vec<SpillGap>) = {
+            let index = self.index.lock();
+            let segments = index
+                .segments
+                .iter()
+                .filter(|segment| {
+                    segment.packet_count > 0
+                        && segment.last_pts >= start_pts
+                        && segment.first_pts < end_pts
+                })
+                .cloned()
+                .collect();
+            (segments, index.gaps_in(start_pts, end_pts))
+        };
+
+        let mut result = Vec::new();
+        for segment in &segments {
+            match read_segment_range(segment, start_pts, end_pts, &mut result) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    debug!(
+                        "Spill segment {:?} was evicted during snapshot; skipping",
+                        segment.path
+                    );
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(SpillRead {
+            packets: result,
+            gaps,
+        })
+    }
+
+    /// Picks where a chunked read of the window starting at `start_pts` begins: the last
+    /// spilled keyframe at or before `start_pts` (or the first one after it), moved past the
+    /// newest gap when the gap lies after that keyframe.
+    ///
+    /// Returns the keyframe's PTS and a cursor one GOP before it, so audio written ahead of
+    /// the keyframe is read too, or `None` when no usable keyframe is spilled.
+    pub(crate) fn chunk_start(&self, start_pts: i64) -> Option<(i64, SpillCursor)> {
+        let index = self.index.lock();
+        let keyframes: Vec<(u64, i64, u64)> = index
+            .segments
+            .iter()
+            .filter(|segment| segment.packet_count > 0)
+            .flat_map(|segment| {
+                segment
+                    .keyframes
+                    .iter()
+                    .map(move |&(pts, offset)| (segment.id, pts, offset))
+            })
+            .collect();
+
+        let mut pos = keyframes
+            .iter()
+            .rposition(|&(_, pts, _)| pts <= start_pts)
+            .unwrap_or(0);
+        if let Some(gap) = index.gaps.back() {
+            if keyframes.get(pos)?.1 <= gap.end_pts {
+                pos = keyframes
+                    .iter()
+                    .position(|&(_, pts, _)| pts > gap.end_pts)?;
+            }
+        }
+        let (segment_id, keyframe_pts, _) = *keyframes.get(pos)?;
+        let cursor = match pos.checked_sub(1).map(|prev| keyframes[prev]) {
+            Some((prev_segment_id, _, prev_offset)) => SpillCursor {
+                segment_id: prev_segment_id,
+                offset: prev_offset,
+            },
+            None => SpillCursor {
+                segment_id,
+                offset: SEGMENT_MAGIC.len() as u64,
+            },
+        };
+        Some((keyframe_pts, cursor))
+    }
+
+    /// Reads the spilled records after `cursor` in write order, keeping the packets `keep`
+    /// accepts (by stream and PTS, before their payload is read) and stopping before the
+    /// packet that would take the chunk past `max_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::ReadOverrun`] if the segment at `cursor` was deleted before the
+    /// read got to it, or an I/O error if a segment cannot be read.
+    pub(crate) fn read_chunk(
+        &self,
+        cursor: SpillCursor,
+        max_bytes: usize,
+        mut keep: impl FnMut(StreamType, i64) -> bool,
+    ) -> BufferResult<SpillChunk> {
+        let segments: Vec<SpillSegment> = {
+            let index = self.index.lock();
+            if index
+                .segments
+                .front()
+                .is_some_and(|front| front.id > cursor.segment_id)
+            {
+                // The packet count of deleted segments is no longer known.
+                return Err(BufferError::ReadOverrun { lost: 0 });
+            }
+            index
+                .segments
+                .iter()
+                .filter(|segment| segment.id >= cursor.segment_id)
+                .cloned()
+                .collect()
+        };
+
+        let mut packets = Vec::new();
+        let mut bytes = 0usize;
+        let mut cursor = cursor;
+        for segment in &segments {
+            if segment.id != cursor.segment_id {
+                cursor = SpillCursor {
+                    segment_id: segment.id,
+                    offset: SEGMENT_MAGIC.len() as u64,
+                };
+            }
+            let mut reader = match SegmentReader::open(segment, cursor.offset) {
+                Ok(reader) => reader,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(BufferError::ReadOverrun {
+                        lost: segment.packet_count,
+                    });
+                }
+                Err(err) => return Err(err.into()),
+            };
+            loop {
+                let record_offset = reader.offset;
+                let Some(header) = reader.next_header()? else {
+                    break;
+                };
+                if !keep(header.stream, header.pts) {
+                    reader.skip_payload(&header)?;
+                    continue;
+                }
+                if !packets.is_empty() && bytes.saturating_add(header.payload_len) > max_bytes {
+                    cursor.offset = record_offset;
+                    return Ok(SpillChunk {
+                        packets,
+                        cursor,
+                        end_reached: false,
+                    });
+                }
+                bytes += header.payload_len;
+                packets.push(reader.read_packet(header)?);
+            }
+            cursor.offset = reader.offset;
+        }
+        Ok(SpillChunk {
+            packets,
+            cursor,
+            end_reached: true,
+        })
+    }
+}
+
+impl Drop for DiskSpillTier {
+    fn drop(&mut self) {
+        // Closing the channel stops the writer after it drains the queue.
+        drop(self.tx.take());
+        if let Some(handle) = self.writer.take() {
+            let _ = handle.join();
+        }
+        if let Err(err) = fs::remove_dir_all(&self.session_dir) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "Failed to remove replay spill directory {:?}: {}",
+                    self.session_dir, err
+                );
+            }
+        }
+    }
+}
+
+struct SpillWriter {
+    session_dir: PathBuf,
+    index: Arc<parking_lot::Mutex<SpillIndex>>,
+    max_bytes: u64,
+    max_duration_qpc: i64,
+    segment_target_bytes: u64,
+    next_segment_id: u64,
+    current: Option<(BufWriter<File>, SpillSegment)>,
+    write_errors: usize,
+    last_write_failed: bool,
+}
+
+impl SpillWriter {
+    fn run(mut self, rx: Receiver<SpillCommand>) {
+        while let Ok(command) = rx.recv() {
+            let mut newest_pts = None;
+            let mut waiters = Vec::new();
+            self.handle(command, &mut newest_pts, &mut waiters);
+            // Drain what is already queued so one flush covers the whole batch.
+            for _ in 0..SPILL_QUEUE_CAPACITY {
+                let Ok(command) = rx.try_recv() else {
+                    break;
+                };
+                self.handle(command, &mut newest_pts, &mut waiters);
+            }
+            self.commit();
+            if let Some(newest_pts) = newest_pts {
+                self.enforce_limits(newest_pts);
+            }
+            for waiter in waiters {
+                let _ = waiter.send(());
+            }
+        }
+        self.commit();
+        self.current = None;
+    }
+
+    fn handle(
+        &mut self,
+        command: SpillCommand,
+        newest_pts: &mut Option<i64>,
+        waiters: &mut Vec<Sender<()>>,
+    ) {
+        match command {
+            SpillCommand::Packet {
+                packet,
+                newest_pts: newest,
+            } => {
+                *newest_pts = Some(newest_pts.map_or(newest, |current| current.max(newest)));
+                if let Err(err) = self.append(&packet) {
+                    self.index
+                        .lock()
+                        .record_gap(packet.pts, self.last_write_failed);
+                    self.last_write_failed = true;
+                    self.write_errors += 1;
+                    if self.write_errors % DROPPED_PACKET_WARN_INTERVAL == 1 {
+                        warn!(
+                            "Replay disk spill write failed ({} errors so far): {}",
+                            self.write_errors, err
+                        );
+                    }
+                    // Start a fresh segment on the next packet instead of appending after
+                    // a partially written record.
+                    self.current = None;
+                } else {
+                    self.last_write_failed = false;
+                }
+            }
+            SpillCommand::Clear => self.clear(),
+            SpillCommand::Sync(waiter) => waiters.push(waiter),
+            SpillCommand::SetMaxDuration(max_duration_qpc) => {
+                self.max_duration_qpc = max_duration_qpc;
+            }
+        }
+    }
+
+    fn append(&mut self, packet: &EncodedPacket) -> std::io::Result<()> {
+        let needs_rotation = self.current.as_ref().map_or(true, |(_, segment)| {
+            segment.committed_bytes >= self.segment_target_bytes
+        });
+        if needs_rotation {
+            self.commit();
+            self.current = None;
+            self.open_segment()?;
+        }
+
+        let Some((writer, segment)) = self.current.as_mut() else {
+            return Ok(());
+        };
+        writer.write_all(&encode_record_header(packet))?;
+        writer.write_all(packet.data.as_ref())?;
+
+        let record_offset = segment.committed_bytes;
+        let record_len = (RECORD_HEADER_LEN + packet.data.len()) as u64;
+        segment.committed_bytes += record_len;
+        segment.streams[stream_slot(packet.stream)].add(&SpillStreamUsage {
+            bytes: record_len,
+            packet_count: 1,
+            oldest_pts: Some(packet.pts),
+            newest_pts: Some(packet.pts),
+        });
+        segment.first_pts = segment.first_pts.min(packet.pts);
+        segment.last_pts = segment.last_pts.max(packet.pts);
+        segment.packet_count += 1;
+        if packet.is_keyframe && matches!(packet.stream, StreamType::Video) {
+            segment.keyframes.push((packet.pts, record_offset));
+        }
+        Ok(())
+    }
+
+    fn open_segment(&mut self) -> std::io::Result<()> {
+        let id = self.next_segment_id;
+        let path = self.session_dir.join(format!("segment-{:06}.spill", id));
+        self.next_segment_id += 1;
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(SEGMENT_MAGIC)?;
+
+        self.index
+            .lock()
+            .segments
+            .push_back(SpillSegment::new(id, path.clone()));
+
+        let mut segment = SpillSegment::new(id, path);
+        segment.committed_bytes = SEGMENT_MAGIC.len() as u64;
+        self.current = Some((writer, segment));
+        Ok(())
+    }
+
+    /// Flushes the current segment and publishes its metadata to readers.
+    fn commit(&mut self) {
+        let Some((writer, segment)) = self.current.as_mut() else {
+            return;
+        };
+        if let Err(err) = writer.flush() {
+            warn!("Replay disk spill flush failed: {}", err);
+            return;
+        }
+
+        let mut index = self.index.lock();
+        let index = &mut *index;
+        if let Some(published) = index
+            .segments
+            .iter_mut()
+            .rev()
+            .find(|published| published.id == segment.id)
+        {
+            index.total_bytes = index
+                .total_bytes
+                .saturating_sub(published.committed_bytes)
+                .saturating_add(segment.committed_bytes);
+            index.total_packets = index
+                .total_packets
+                .saturating_sub(published.packet_count)
+                .saturating_add(segment.packet_count);
+            *published = segment.clone();
+        }
+    }
+
+    fn enforce_limits(&mut self, newest_pts: i64) {
+        let cutoff_pts = if self.max_duration_qpc > 0 {
+            Some(newest_pts.saturating_sub(self.max_duration_qpc))
+        } else {
+            None
+        };
+
+        loop {
+            let victim = {
+                let mut index = self.index.lock();
+                let Some(front) = index.segments.front() else {
+                    break;
+                };
+                let over_budget = index.total_bytes > self.max_bytes;
+                let expired = front.packet_count > 0
+                    && cutoff_pts.is_some_and(|cutoff| front.last_pts < cutoff);
+                if !over_budget && !expired {
+                    break;
+                }
+                let Some(victim) = index.segments.pop_front() else {
+                    break;
+                };
+                index.total_bytes = index.total_bytes.saturating_sub(victim.committed_bytes);
+                index.total_packets = index.total_packets.saturating_sub(victim.packet_count);
+                victim
+            };
+
+            if self
+                .current
+                .as_ref()
+                .is_some_and(|(_, segment)| segment.id == victim.id)
+            {
+                self.current = None;
+            }
+            remove_segment_file(&victim.path);
+            debug!(
+                "Replay disk spill evicted segment {:?} ({:.1}MB, {} packets)",
+                victim.path,
+                victim.committed_bytes as f64 / 1_048_576.0,
+                victim.packet_count
+            );
+        }
+
+        // Gaps older than everything still spilled no longer affect any read.
+        let mut index = self.index.lock();
+        let floor = index
+            .segments
+            .iter()
+            .find(|segment| segment.packet_count > 0)
+            .map(|segment| segment.first_pts)
+            .or(cutoff_pts);
+        if let Some(floor) = floor {
+            index.gaps.retain(|gap| gap.end_pts >= floor);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.current = None;
+        let removed = {
+            let mut index = self.index.lock();
+            index.total_bytes = 0;
+            index.total_packets = 0;
+            index.gaps.clear();
+            std::mem::take(&mut index.segments)
+        };
+        for segment in &removed {
+            remove_segment_file(&segment.path);
+        }
+        // Segments evicted from the index by `DiskSpillTier::clear` are still on disk.
+        if let Ok(entries) = fs::read_dir(&self.session_dir) {
+            for entry in entries.flatten() {
+                remove_segment_file(&entry.path());
+            }
+        }
+    }
+}
+
+fn remove_segment_file(path: &Path) {
+    if let Err(err) = fs::remove_file(path) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove replay spill segment {:?}: {}", path, err);
+        }
+    }
+}
+
+/// Appends the packets of `segment` with PTS in `[start_pts, end_pts)` to `out`, reading
+/// only their payloads.
+fn read_segment_range(
+    segment: &SpillSegment,
+    start_pts: i64,
+    end_pts: i64,
+    out: &mut Vec<EncodedPacket>,
+) -> std::io::Result<()> {
+    let mut reader = SegmentReader::open(segment, segment.scan_offset(start_pts))?;
+    while let Some(header) = reader.next_header()? {
+        if header.pts >= start_pts && header.pts < end_pts {
+            out.push(reader.read_packet(header)?);
+        } else {
+            reader.skip_payload(&header)?;
+        }
+    }
+    Ok(())
+}
+
+/// Streams the committed records of one segment, loading only the payloads asked for.
+struct SegmentReader {
+    file: BufReader<File>,
+    /// Offset of the next unread byte.
+    offset: u64,
+    end: u64,
+}
+
+impl SegmentReader {
+    /// Opens `segment` at record offset `offset`; reads stop at its committed length.
+    fn open(segment: &SpillSegment, offset: u64) -> std::io::Result<Self> {
+        let offset = offset.max(SEGMENT_MAGIC.len() as u64);
+        let mut file = File::open(&segment.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Self {
+            file: BufReader::with_capacity(SEGMENT_READ_BUFFER_BYTES, file),
+            offset,
+            end: segment.committed_bytes,
+        })
+    }
+
+    /// Reads the next record header, or `None` at the end of the committed records.
+    fn next_header(&mut self) -> std::io::Result<Option<RecordHeader>> {
+        if self.offset + RECORD_HEADER_LEN as u64 > self.end {
+            return Ok(None);
+        }
+        let mut bytes = [0u8; RECORD_HEADER_LEN];
+        self.file.read_exact(&mut bytes)?;
+        let header = match RecordHeader::parse(&bytes) {
+            Some(header)
+                if self.offset + (RECORD_HEADER_LEN + header.payload_len) as u64 <= self.end =>
+            {
+                header
+            }
+            _ => {
+                self.offset = self.end;
+                return Ok(None);
+            }
+        };
+        self.offset += RECORD_HEADER_LEN as u64;
+        Ok(Some(header))
+    }
+
+    /// Reads the payload of the record whose header was returned last.
+    fn read_packet(&mut self, header: RecordHeader) -> std::io::Result<EncodedPacket> {
+        let mut data = vec![0u8; header.payload_len];
+        self.file.read_exact(&mut data)?;
+        self.offset += header.payload_len as u64;
+        Ok(header.into_packet(Bytes::from(data)))
+    }
+
+    /// Skips the payload of the record whose header was returned last.
+    fn skip_payload(&mut self, header: &RecordHeader) -> std::io::Result<()> {
+        self.file.seek_relative(header.payload_len as i64)?;
+        self.offset += header.payload_len as u64;
+        Ok(())
+    }
+}
+
+#[cfg(not(test))]
+fn spill_root() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("liteclip")
+        .join("replay-spill")
+}
+
+/// Unit tests must never touch (or clean up) the spill directory of a running app.
+#[cfg(test)]
+fn spill_root() -> PathBuf {
+    std::env::temp_dir()
+        .join("liteclip-tests")
+        .join("replay-spill")
+}
+
+fn session_prefix() -> String {
+    format!("session-{}-", std::process::id())
+}
+
+/// Removes session directories left behind by earlier processes (crash, kill, power loss).
+///
+/// A directory is only removed once the process that created it has exited, so the live spill
+/// of a second instance (or of a previous instance that is still shutting down) is left alone.
+fn remove_stale_sessions(root: &Path) {
+    let Ok(entries) = fs::read_dir(root) else {
+        return;
+    };
+    let own_prefix = session_prefix();
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with("session-") || name.starts_with(&own_prefix) {
+            continue;
+        }
+        let Some(owner_pid) = session_owner_pid(&name) else {
+            debug!(
+                "Skipping replay spill directory {:?} with no owner PID",
+                entry.path()
+            );
+            continue;
+        };
+        if process_is_running(owner_pid) {
+            debug!(
+                "Keeping replay spill session {:?} of running process {}",
+                entry.path(),
+                owner_pid
+            );
+            continue;
+        }
+        match fs::remove_dir_all(entry.path()) {
+            Ok(()) => debug!("Removed stale replay spill session {:?}", entry.path()),
+            Err(err) => warn!(
+                "Failed to remove stale replay spill session {:?}: {}",
+                entry.path(),
+                err
+            ),
+        }
+    }
+}
+
+/// PID encoded in a `session-<pid>-<n>` directory name.
+fn session_owner_pid(name: &str) -> Option<u32> {
+    name.strip_prefix("session-")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Whether process `pid` is still running. Errors other than "no such process" count as
+/// running, so a session is never removed on a failed check.
+#[cfg(windows)]
+fn process_is_running(pid: u32) -> bool {
+    use windows::Win32::Foundation::{CloseHandle, ERROR_INVALID_PARAMETER, STILL_ACTIVE};
+    use windows::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: the handle is only used while open and closed before returning.
+    unsafe {
+        let handle = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+            Ok(handle) => handle,
+            Err(err) => return err.code() != ERROR_INVALID_PARAMETER.to_hresult(),
+        };
+        let mut exit_code = 0u32;
+        let running = GetExitCodeProcess(handle, &mut exit_code).is_err()
+            || exit_code == STILL_ACTIVE.0 as u32;
+        let _ = CloseHandle(handle);
+        running
+    }
+}
+
+#[cfg(not(windows))]
+fn process_is_running(pid: u32) -> bool {
+    !Path::new("/proc").is_dir() || Path::new("/proc").join(pid.to_string()).exists()
+}
+
+fn stream_to_u8(stream: StreamType) -> u8 {
+    match stream {
+        StreamType::Video => 0,
+        StreamType::SystemAudio => 1,
+        StreamType::Microphone => 2,
+    }
+}
+
+fn stream_from_u8(value: u8) -> Option<StreamType> {
+    match value {
+        0 => Some(StreamType::Video),
+        1 => Some(StreamType::SystemAudio),
+        2 => Some(StreamType::Microphone),
+        _ => None,
+    }
+}
+
+fn codec_to_u8(codec: Option<VideoCodec>) -> u8 {
+    match codec {
+        None => 0,
+        Some(VideoCodec::H264) => 1,
+        Some(VideoCodec::Hevc) => 2,
+    }
+}
+
+fn codec_from_u8(value: u8) -> Option<VideoCodec> {
+    match value {
+        1 => Some(VideoCodec::H264),
+        2 => Some(VideoCodec::Hevc),
+        _ => None,
+    }
+}
+
+/// Serializes the fixed-size header that precedes a packet payload on disk.
+pub(crate) fn encode_record_header(packet: &EncodedPacket) -> [u8; RECORD_HEADER_LEN] {
+    encode_tagged_record_header(packet, 0)
+}
+
+/// Like [`encode_record_header`], with extra `RECORD_FLAG_*` bits OR-ed into the flags byte.
+pub(crate) fn encode_tagged_record_header(
+    packet: &EncodedPacket,
+    extra_flags: u8,
+) -> [u8; RECORD_HEADER_LEN] {
+    let mut header = [0u8; RECORD_HEADER_LEN];
+    let (width, height) = packet.resolution.unwrap_or((0, 0));
+    header[0..4].copy_from_slice(&(packet.data.len() as u32).to_le_bytes());
+    header[4..12].copy_from_slice(&packet.pts.to_le_bytes());
+    header[12..20].copy_from_slice(&packet.dts.to_le_bytes());
+    header[20] = stream_to_u8(packet.stream);
+    let keyframe_flag = if packet.is_keyframe {
+        RECORD_FLAG_KEYFRAME
+    } else {
+        0
+    };
+    header[21] = extra_flags | keyframe_flag;
+    header[22] = codec_to_u8(packet.codec);
+    header[24..28].copy_from_slice(&width.to_le_bytes());
+    header[28..32].copy_from_slice(&height.to_le_bytes());
+    header
+}
+
+/// Decodes packet records starting at `offset`, with each record's raw flags byte.
+///
+/// Payloads are zero-copy slices of `data`. Decoding stops at the first truncated or
+/// malformed record, so a partially written tail is silently discarded.
+pub(crate) fn decode_tagged_records(data: &Bytes, offset: usize) -> Vec<(EncodedPacket, u8)> {
+    let mut packets = Vec::new();
+    let mut pos = offset;
+    while pos + RECORD_HEADER_LEN <= data.len() {
+        let Some(header) = RecordHeader::parse(&data[pos..pos + RECORD_HEADER_LEN]) else {
+            break;
+        };
+        let payload_start = pos + RECORD_HEADER_LEN;
+        let payload_end = payload_start.saturating_add(header.payload_len);
+        if payload_end > data.len() {
+            break;
+        }
+
+        let flags = header.flags;
+        packets.push((
+            header.into_packet(data.slice(payload_start..payload_end)),
+            flags,
+        ));
+        pos = payload_end;
+    }
+    packets
+}
+
+/// A decoded record header.
+struct RecordHeader {
+    payload_len: usize,
+    pts: i64,
+    dts: i64,
+    stream: StreamType,
+    flags: u8,
+    codec: Option<VideoCodec>,
+    resolution: Option<(u32, u32)>,
+}
+
+impl RecordHeader {
+    /// Parses a [`RECORD_HEADER_LEN`]-byte header; `None` if it is malformed.
+    fn parse(header: &[u8]) -> Option<Self> {
+        let read_u32 = |at: usize| {
+            u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+        };
+        let read_i64 = |at: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&header[at..at + 8]);
+            i64::from_le_bytes(bytes)
+        };
+
+        let width = read_u32(24);
+        let height = read_u32(28);
+        Some(Self {
+            payload_len: read_u32(0) as usize,
+            pts: read_i64(4),
+            dts: read_i64(12),
+            stream: stream_from_u8(header[20])?,
+            flags: header[21],
+            codec: codec_from_u8(header[22]),
+            resolution: (width > 0 && height > 0).then_some((width, height)),
+        })
+    }
+
+    fn into_packet(self, data: Bytes) -> EncodedPacket {
+        EncodedPacket {
+            data,
+            pts: self.pts,
+            dts: self.dts,
+            resolution: self.resolution,
+            stream: self.stream,
+            is_keyframe: self.flags & RECORD_FLAG_KEYFRAME != 0,
+            codec: self.codec,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(pts: i64, is_keyframe: bool, stream: StreamType) -> EncodedPacket {
+        EncodedPacket {
+            data: Bytes::from(vec![(pts % 251) as u8; 64]),
+            pts,
+            dts: pts,
+            resolution: is_keyframe.then_some((1920, 1080)),
+            stream,
+            is_keyframe,
+            codec: matches!(stream, StreamType::Video).then_some(VideoCodec::Hevc),
+        }
+    }
+
+    #[test]
+    fn record_round_trip_and_truncated_tail() {
+        let original = [
+            packet(10, true, StreamType::Video),
+            packet(11, false, StreamType::SystemAudio),
+            packet(12, false, StreamType::Microphone),
+        ];
+        let mut encoded = SEGMENT_MAGIC.to_vec();
+        for p in &original {
+            encoded.extend_from_slice(&encode_record_header(p));
+            encoded.extend_from_slice(&p.data);
+        }
+        // Simulate a crash mid-record.
+        encoded.extend_from_slice(&encode_record_header(&original[0])[..10]);
+
+        let decoded = decode_tagged_records(&Bytes::from(encoded), SEGMENT_MAGIC.len());
+        assert_eq!(decoded.len(), original.len());
+        for ((a, _), b) in decoded.iter().zip(original.iter()) {
+            assert_eq!(a.pts, b.pts);
+            assert_eq!(a.stream, b.stream);
+            assert_eq!(a.is_keyframe, b.is_keyframe);
+            assert_eq!(a.resolution, b.resolution);
+            assert_eq!(a.codec, b.codec);
+            assert_eq!(a.data, b.data);
+        }
+    }
+
+    #[test]
+    fn stale_sessions_of_exited_processes_are_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let exited = root.path().join(format!("session-{}-0", u32::MAX));
+        let own = root.path().join(format!("{}7", session_prefix()));
+        let unowned = root.path().join("session-unknown");
+        for dir in [&exited, &own, &unowned] {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        remove_stale_sessions(root.path());
+
+        assert!(!exited.exists());
+        assert!(own.exists());
+        assert!(unowned.exists());
+        assert_eq!(session_owner_pid("session-1234-5"), Some(1234));
+    }
+
+    #[test]
+    fn read_range_aligns_to_spilled_keyframe() {
+        let root = tempfile::tempdir().unwrap();
+        let tier = DiskSpillTier::with_root(root.path(), BYTES_PER_GB, 0).unwrap();
+        for pts in 0..30 {
+            tier.offer(packet(pts, pts % 10 == 0, StreamType::Video), pts);
+        }
+        tier.sync();
+
+        assert_eq!(tier.oldest_pts(), Some(0));
+        assert_eq!(tier.usage().1, 30);
+
+        let range = tier.read_range(15, 25).unwrap().packets;
+        assert_eq!(range.first().map(|p| p.pts), Some(10));
+        assert!(range.first().is_some_and(|p| p.is_keyframe));
+        assert_eq!(range.last().map(|p| p.pts), Some(24));
+
+        tier.clear();
+        tier.sync();
+        assert_eq!(tier.usage(), (0, 0));
+        assert!(tier.read_range(0, 30).unwrap().packets.is_empty());
+    }
+
+    #[test]
+    fn read_chunk_resumes_at_cursor() {
+        let root = tempfile::tempdir().unwrap();
+        let tier = DiskSpillTier::with_root(root.path(), BYTES_PER_GB, 0).unwrap();
+        for pts in 0..30 {
+            tier.offer(packet(pts, pts % 10 == 0, StreamType::Video), pts);
+        }
+        tier.sync();
+
+        let (keyframe_pts, mut cursor) = tier.chunk_start(15).unwrap();
+        assert_eq!(keyframe_pts, 10);
+        let mut read = Vec::new();
+        loop {
+            let chunk = tier.read_chunk(cursor, 64 * 4, |_, pts| pts >= 10).unwrap();
+            assert!(chunk.packets.len() <= 4);
+            read.extend(chunk.packets.iter().map(|p| p.pts));
+            cursor = chunk.cursor;
+            if chunk.end_reached {
+                break;
+            }
+        }
+        assert_eq!(read, (10..30).collect::<Vec<_>>());
+
+        // Packets spilled later are read from the same cursor.
+        for pts in 30..35 {
+            tier.offer(packet(pts, false, StreamType::Video), pts);
+        }
+        tier.sync();
+        let chunk = tier.read_chunk(cursor, usize::MAX, |_, _| true).unwrap();
+        assert!(chunk.end_reached);
+        assert_eq!(
+            chunk.packets.iter().map(|p| p.pts).collect::<Vec<_>>(),
+            (30..35).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn read_range_starts_after_a_gap() {
+        let root = tempfile::tempdir().unwrap();
+        let tier = DiskSpillTier::with_root(root.path(), BYTES_PER_GB, 0).unwrap();
+        for pts in (0..30).filter(|pts| !(12..=13).contains(pts)) {
+            tier.offer(packet(pts, pts % 10 == 0, StreamType::Video), pts);
+        }
+        tier.sync();
+        // What `offer` records when the writer queue is full.
+        tier.index.lock().record_gap(12, false);
+        tier.index.lock().record_gap(13, true);
+
+        let read = tier.read_range(15, 25).unwrap();
+        assert_eq!(
+            read.gaps,
+            vec![SpillGap {
+                start_pts: 12,
+                end_pts: 13
+            }]
+        );
+        assert_eq!(read.packets.first().map(|p| p.pts), Some(20));
+        assert!(read.packets.first().is_some_and(|p| p.is_keyframe));
+
+        let unaligned = tier.read_unaligned(14, 30).unwrap();
+        assert!(unaligned.gaps.is_empty());
+        assert_eq!(unaligned.packets.len(), 16);
+    }
+}
@@ -22,6 +22,7 @@ pub fn qpc_frequency() -> i64 {
 #[cfg(test)]
 mod tests {
     use crate::buffer::ring::spmc_ring::LockFreeReplayBuffer;
+    use crate::buffer::ring::types::StreamStats;
     use crate::encode::{EncodedPacket, StreamType};
     use crate::media::nal;
     use bytes::Bytes;
@@ -583,4 +584,387 @@ mod tests {
             snapshot.len()
         );
     }
+
+    #[test]
+    fn test_disk_spill_extends_snapshot_past_ram() {
+        let mut config = make_config(120, 1);
+        config.general.replay_disk_limit_gb = 1;
+        let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+
+        // 100 x 50KB = ~5MB, far more than the 1MB RAM budget.
+        for i in 0..100 {
+            buffer.push(create_test_packet(i * 1_000_000, i % 10 == 0, 50_000));
+        }
+
+        let ram_stats = buffer.stats();
+        let snapshot = buffer.snapshot_from(0).unwrap();
+        let video: Vec<_> = snapshot
+            .iter()
+            .filter(|p| matches!(p.stream, StreamType::Video))
+            .collect();
+        assert!(
+            video.len() > ram_stats.packet_count,
+            "spilled packets should extend the snapshot beyond RAM ({} <= {})",
+            video.len(),
+            ram_stats.packet_count
+        );
+        assert_eq!(video.first().map(|p| p.pts), Some(0));
+        assert!(video.first().is_some_and(|p| p.is_keyframe));
+        assert!(video.windows(2).all(|w| w[0].pts < w[1].pts));
+
+        let stats = buffer.stats();
+        assert!(stats.disk_packet_count > 0);
+        assert!(stats.disk_bytes > 0);
+        assert_eq!(buffer.oldest_pts(), Some(0));
+
+        buffer.clear();
+        let stats = buffer.stats();
+        assert_eq!(stats.disk_bytes, 0);
+        assert_eq!(stats.disk_packet_count, 0);
+    }
+
+    #[test]
+    fn test_duration_eviction_feeds_disk_spill() {
+        let mut config = make_config(1, 512);
+        config.general.replay_disk_limit_gb = 1;
+        let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+        let step = super::qpc_frequency() / 100;
+
+        // 20s of packets against a 1s window: most leave RAM through duration eviction.
+        for i in 0..2000 {
+            buffer.push(create_test_packet(i * step, i % 10 == 0, 1000));
+        }
+
+        let snapshot = buffer.snapshot_range(0, i64::MAX).unwrap();
+        let pts: Vec<i64> = snapshot.iter().map(|p| p.pts).collect();
+        assert_eq!(pts, (0..2000).map(|i| i * step).collect::<Vec<_>>());
+        drop(snapshot);
+
+        let stats = buffer.stats();
+        assert_eq!(stats.disk_packet_count + stats.packet_count, 2000);
+    }
+
+    #[test]
+    fn test_snapshot_chunks_stream_spilled_window() {
+        let mut config = make_config(120, 1);
+        config.general.replay_disk_limit_gb = 1;
+        let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+        let push_second = |i: i64| {
+            buffer.push(create_test_packet(i * 1_000_000, i % 10 == 0, 50_000));
+            let mut audio = create_test_packet(i * 1_000_000 + 500, false, 1_000);
+            audio.stream = StreamType::SystemAudio;
+            buffer.push(audio);
+        };
+        for i in 0..100 {
+            push_second(i);
+        }
+        let end_pts = 99 * 1_000_000 + 500;
+
+        let chunks = buffer
+            .snapshot_chunks(0, Some(end_pts), 200_000)
+            .unwrap()
+            .expect("window starts in the disk spill");
+        assert!(chunks.reads_spill());
+        assert_eq!(chunks.start_pts(), 0);
+
+        let mut streamed = Vec::new();
+        for (n, chunk) in chunks.enumerate() {
+            let chunk = chunk.unwrap();
+            assert!(chunk.iter().map(|p| p.data.len()).sum::<usize>() <= 200_000);
+            streamed.extend(chunk.into_inner());
+            // Keep evicting while the read runs, so it has to follow packets into the spill.
+            push_second(100 + n as i64);
+        }
+        let snapshot = buffer.snapshot_range(0, end_pts).unwrap();
+        let pts = |packets: &[EncodedPacket]| packets.iter().map(|p| p.pts).collect::<Vec<_>>();
+        assert_eq!(pts(&streamed).len(), 200);
+        assert_eq!(pts(&streamed), pts(&snapshot));
+    }
+
+    #[test]
+    fn test_pinned_range_survives_eviction() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 1)).unwrap();
+        for i in 0..10 {
+            buffer.push(create_test_packet(i * 1_000_000, i % 10 == 0, 50_000));
+        }
+
+        // Aligned back to the keyframe at pts 0.
+        let pin = buffer.pin_range(2_000_000, 5_000_000).unwrap();
+        assert_eq!(pin.start_pts(), 0);
+
+        // ~5MB more pushes the pinned packets out of the 1MB ring.
+        for i in 10..110 {
+            buffer.push(create_test_packet(i * 1_000_000, i % 10 == 0, 50_000));
+        }
+        assert!(buffer.oldest_pts().is_some_and(|oldest| oldest > 5_000_000));
+
+        let stats = buffer.stats();
+        assert_eq!(stats.pinned_range_count, 1);
+        assert_eq!(stats.pinned_range_bytes, 6 * 50_000);
+        assert_eq!(pin.retained_bytes(), 6 * 50_000);
+
+        let snapshot = pin.snapshot().unwrap();
+        let pts: Vec<i64> = snapshot.iter().map(|p| p.pts).collect();
+        assert_eq!(pts, (0..=5).map(|i| i * 1_000_000).collect::<Vec<_>>());
+        assert!(snapshot[0].is_keyframe);
+        drop(snapshot);
+
+        drop(pin);
+        let stats = buffer.stats();
+        assert_eq!(stats.pinned_range_count, 0);
+        assert_eq!(stats.pinned_range_bytes, 0);
+    }
+
+    #[test]
+    fn test_pin_budget_caps_retained_bytes() {
+        let mut config = make_config(120, 1);
+        config.general.replay_pin_budget_mb = 1;
+        let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+
+        assert!(matches!(
+            buffer.pin_range(10, 5),
+            Err(crate::buffer::BufferError::InvalidRange { .. })
+        ));
+
+        let _pin = buffer.pin_range(0, i64::MAX).unwrap();
+        for i in 0..100 {
+            buffer.push(create_test_packet(i * 1_000_000, i % 10 == 0, 64 * 1024));
+        }
+
+        // 16 x 64KB fills the 1MB pin budget exactly; later evictions are dropped normally.
+        assert_eq!(buffer.stats().pinned_range_bytes, 1024 * 1024);
+        assert!(matches!(
+            buffer.pin_range(0, 1),
+            Err(crate::buffer::BufferError::PinBudgetExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_stats_break_down_per_stream() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 512)).unwrap();
+        let freq = super::qpc_frequency();
+        let frame = freq / 30;
+        let audio_period = freq / 50;
+
+        for i in 0..30 {
+            buffer.push(create_test_packet(i * frame, i % 10 == 0, 2048));
+        }
+        for i in 0..50 {
+            let mut system = create_test_packet(i * audio_period, false, 256);
+            system.stream = StreamType::SystemAudio;
+            buffer.push(system);
+            // The microphone drops out for half a second in the middle.
+            if !(20..45).contains(&i) {
+                let mut mic = create_test_packet(i * audio_period, false, 128);
+                mic.stream = StreamType::Microphone;
+                buffer.push(mic);
+            }
+        }
+
+        let stats = buffer.stats();
+        assert_eq!(stats.video.packet_count, 30);
+        assert_eq!(stats.video.bytes, 30 * 2048);
+        assert_eq!(stats.system_audio.bytes, 50 * 256);
+        assert_eq!(stats.stream(StreamType::Microphone).packet_count, 25);
+        assert_eq!(
+            stats.video.bytes + stats.system_audio.bytes + stats.microphone.bytes,
+            stats.total_bytes
+        );
+
+        assert!((stats.system_audio.span_secs - 0.98).abs() < 0.01);
+        assert_eq!(stats.microphone.oldest_pts, Some(0));
+        assert_eq!(stats.microphone.newest_pts, Some(49 * audio_period));
+
+        let video = buffer.stream_gaps(StreamType::Video);
+        let mic = buffer.stream_gaps(StreamType::Microphone);
+        assert_eq!(video.gap_count, 0);
+        assert_eq!(buffer.stream_gaps(StreamType::SystemAudio).gap_count, 0);
+        assert_eq!(mic.gap_count, 1);
+        // 25 periods of 20 ms are missing; the gap and the longest one agree.
+        assert!((mic.gap_secs - 0.5).abs() < 0.01);
+        assert!((mic.longest_gap_secs - 0.5).abs() < 0.01);
+        assert!((video.expected_interval_secs - 1.0 / 30.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_stream_stats_follow_eviction() {
+        let mut config = make_config(120, 1);
+        config.general.replay_disk_limit_gb = 0;
+        let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+        for i in 0..100 {
+            let mut packet = create_test_packet(i * 1_000, i % 10 == 0, 50_000);
+            if i % 2 == 1 {
+                packet.stream = StreamType::SystemAudio;
+            }
+            buffer.push(packet);
+        }
+
+        // The counters must agree with what a full walk of the surviving packets reports.
+        let stats = buffer.stats();
+        let snapshot = buffer.snapshot().unwrap();
+        for stream in [StreamType::Video, StreamType::SystemAudio] {
+            let packets: Vec<_> = snapshot.iter().filter(|p| p.stream == stream).collect();
+            let counted = stats.stream(stream);
+            assert_eq!(counted.packet_count, packets.len());
+            assert_eq!(
+                counted.bytes,
+                packets.iter().map(|p| p.data.len()).sum::<usize>()
+            );
+            assert_eq!(counted.oldest_pts, packets.iter().map(|p| p.pts).min());
+            assert_eq!(counted.newest_pts, packets.iter().map(|p| p.pts).max());
+        }
+        assert!(stats.video.oldest_pts > Some(0), "eviction should have run");
+        assert_eq!(stats.microphone, StreamStats::default());
+        assert_eq!(buffer.newest_stream_pts(StreamType::Video), Some(98_000));
+
+        buffer.clear();
+        assert_eq!(buffer.stats().video, StreamStats::default());
+    }
+
+    #[test]
+    fn test_subscription_starts_at_next_keyframe() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 512)).unwrap();
+        buffer.push(create_test_packet(0, true, 100));
+        let mut sub = buffer.subscribe();
+
+        for i in 1..4 {
+            buffer.push(create_test_packet(i, false, 100));
+        }
+        let batch = sub.try_recv();
+        assert!(batch.packets.is_empty());
+        assert!(!batch.is_overrun());
+        assert!(sub.is_resyncing());
+
+        for i in 4..7 {
+            buffer.push(create_test_packet(i, i == 4, 100));
+        }
+        let batch = sub.try_recv();
+        let pts: Vec<i64> = batch.packets.iter().map(|p| p.pts).collect();
+        assert_eq!(pts, vec![4, 5, 6]);
+        assert_eq!(batch.skipped, 0);
+
+        buffer.push(create_test_packet(7, false, 100));
+        assert_eq!(sub.try_recv().packets.len(), 1);
+        assert!(sub.try_recv().packets.is_empty());
+    }
+
+    #[test]
+    fn test_subscription_overrun_skips_to_next_keyframe() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 1)).unwrap();
+        let mut sub = buffer.subscribe();
+        buffer.push(create_test_packet(0, true, 1000));
+        assert_eq!(sub.try_recv().packets.len(), 1);
+
+        // ~5 MB against a 1 MB budget evicts packets the reader has not seen yet.
+        for i in 1..=100 {
+            buffer.push(create_test_packet(i, i % 10 == 5, 50_000));
+        }
+        let batch = sub.try_recv();
+        assert!(batch.is_overrun());
+        assert!(batch.packets[0].is_keyframe);
+        assert_eq!(batch.skipped + batch.packets.len(), 100);
+        assert!(!sub.is_resyncing());
+
+        buffer.push(create_test_packet(101, false, 1000));
+        let batch = sub.try_recv();
+        assert!(!batch.is_overrun());
+        assert_eq!(batch.packets.len(), 1);
+    }
+
+    #[test]
+    fn test_subscription_reports_restart_and_wakes_on_push() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 512)).unwrap();
+        let mut sub = buffer.subscribe();
+        buffer.push(create_test_packet(0, true, 100));
+        assert_eq!(sub.try_recv().packets.len(), 1);
+
+        buffer.restart();
+        buffer.push(create_test_packet(10, true, 100));
+        let batch = sub.try_recv();
+        assert!(batch.reset);
+        assert!(!batch.is_overrun());
+        assert_eq!(batch.packets.len(), 1);
+
+        let producer = buffer.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            producer.push(create_test_packet(11, false, 100));
+        });
+        let batch = sub.recv_timeout(std::time::Duration::from_secs(5));
+        handle.join().unwrap();
+        assert_eq!(batch.packets.len(), 1);
+        assert_eq!(batch.packets[0].pts, 11);
+    }
+
+    #[test]
+    fn test_resize_grow_keeps_buffered_packets() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(10, 512)).unwrap();
+        for i in 0..1500 {
+            buffer.push(create_test_packet(i, i % 100 == 0, 100));
+        }
+        buffer.resize(&make_config(120, 512));
+        for i in 1500..3000 {
+            buffer.push(create_test_packet(i, i % 100 == 0, 100));
+        }
+
+        let stats = buffer.stats();
+        assert_eq!(stats.packet_count, 3000);
+        assert_eq!(stats.total_bytes, 3000 * 100);
+        let snapshot = buffer.snapshot().unwrap();
+        assert_eq!(snapshot.first().unwrap().pts, 0);
+        assert_eq!(snapshot.last().unwrap().pts, 2999);
+    }
+
+    #[test]
+    fn test_resize_shrink_evicts_oldest_and_keeps_leading_keyframe() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 512)).unwrap();
+        for i in 0..100 {
+            buffer.push(create_test_packet(i, i % 10 == 5, 50_000));
+        }
+
+        // 1 MB holds the newest 20 packets (80..=99); the window then starts at keyframe 85.
+        buffer.resize(&make_config(120, 1));
+        let stats = buffer.stats();
+        assert_eq!(stats.packet_count, 15);
+        assert_eq!(stats.total_bytes, 15 * 50_000);
+        assert_eq!(buffer.oldest_pts(), Some(85));
+
+        let snapshot = buffer.snapshot().unwrap();
+        assert!(snapshot[0].is_keyframe);
+        assert_eq!(snapshot[0].pts, 85);
+
+        buffer.push(create_test_packet(100, false, 50_000));
+        assert!(buffer.stats().memory_usage_percent <= 100.0);
+    }
+
+    #[test]
+    fn test_snapshot_chunks_match_snapshot_from() {
+        let buffer = LockFreeReplayBuffer::new(&make_config(120, 512)).unwrap();
+        for i in 0..60 {
+            buffer.push(create_test_packet(i * 1000, i % 20 == 0, 10_000));
+            let mut audio = create_test_packet(i * 1000 + 500, false, 1_000);
+            audio.stream = StreamType::SystemAudio;
+            buffer.push(audio);
+        }
+
+        let chunks = buffer
+            .snapshot_chunks(25_000, None, 50_000)
+            .unwrap()
+            .expect("window is in RAM");
+        assert_eq!(chunks.start_pts(), 20_000);
+
+        let mut streamed = Vec::new();
+        for chunk in chunks {
+            let chunk = chunk.unwrap();
+            assert!(chunk.iter().map(|p| p.data.len()).sum::<usize>() <= 50_000);
+            streamed.extend(chunk.into_inner());
+        }
+        let snapshot = buffer.snapshot_from(25_000).unwrap();
+        let pts = |packets: &[EncodedPacket]| packets.iter().map(|p| p.pts).collect::<Vec<_>>();
+        assert_eq!(pts(&streamed), pts(&snapshot));
+        assert_eq!(
+            buffer.outstanding_snapshot_bytes(),
+            snapshot.iter().map(|p| p.data.len()).sum()
+        );
+    }
 }
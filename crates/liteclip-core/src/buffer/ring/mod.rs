@@ -15,7 +15,11 @@
 //! - [`ReplayBuffer`] - Main buffer handle (type alias for `SharedReplayBuffer`)
 //! - [`LockFreeReplayBuffer`] - Core ring implementation (`spmc_ring`)
 //! - [`SharedReplayBuffer`] - Thread-safe wrapper
-//! - [`BufferStats`] - Buffer statistics
+//! - [`BufferStats`] - Buffer statistics, with a per-stream [`StreamStats`] breakdown
+//! - [`StreamGaps`] - Discontinuities of one stream, walked on demand
+//! - [`PinHandle`] - Keeps a PTS range exempt from eviction (see [`pin`])
+//! - [`SnapshotChunks`] - Clip window read in bounded chunks (see [`chunks`])
+//! - [`Bookmark`] - A moment marked while recording, saved as a clip chapter
 //!
 //! # Memory Model
 //!
@@ -24,7 +28,8 @@
 //! - A power-of-two sized ring of packet slots
 //! - Atomic write index for the producer
 //! - Parameter set cache (SPS/PPS/VPS) for clip saving
-//! - Statistics for monitoring (duration, memory usage, keyframes)
+//! - Statistics for monitoring (duration, memory usage, keyframes, per-stream bytes and gaps)
+//! - An optional on-disk spill tier for packets evicted from RAM (see [`disk_spill`])
 //!
 //! # Eviction Policy
 //!
@@ -45,13 +50,23 @@
 //! println!("Buffer: {:.1}s, {} MB", stats.duration_secs, stats.total_bytes / 1024 / 1024);
 //! ```
 
+pub mod chunks;
+pub mod disk_spill;
 pub mod functions;
+pub mod pin;
 pub mod spmc_ring;
+pub mod tail;
 pub mod types;
 
+pub use chunks::SnapshotChunks;
 pub use functions::*;
+pub use pin::PinHandle;
 pub use spmc_ring::{LockFreeReplayBuffer, TrackedSnapshot};
-pub use types::{BufferStats, SharedReplayBuffer};
+pub use tail::{ReplaySubscription, TailBatch};
+pub use types::{
+    Bookmark, BufferStats, SavedClip, SharedReplayBuffer, StreamGaps, StreamStats, MAX_BOOKMARKS,
+    STREAM_GAP_FACTOR,
+};
 
 /// Main replay buffer type.
 ///
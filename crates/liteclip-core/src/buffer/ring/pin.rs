@@ -0,0 +1,231 @@
+//! Pinned replay ranges
+//!
+//! A [`PinHandle`] keeps a PTS window alive after it leaves the normal replay window. While
+//! the handle lives, packets in the window that the ring evicts (duration or memory eviction,
+//! ring wrap, clear/restart) move into the pin store instead of being dropped, and packets
+//! already in the disk spill tier are copied in when the pin is taken.
+//!
+//! Retained bytes count against `general.replay_pin_budget_mb`, which is separate from the
+//! ring's memory limit. Once the budget is used up, pins stop retaining packets (they are
+//! evicted as usual) and new pins are rejected with
+//! [`BufferError::PinBudgetExceeded`](crate::buffer::BufferError::PinBudgetExceeded).
+
+use crate::buffer::BufferResult;
+use crate::encode::{EncodedPacket, StreamType};
+
+use super::spmc_ring::{LockFreeReplayBuffer, TrackedSnapshot};
+
+/// One pinned window and the packets it has retained from eviction.
+pub(crate) struct PinnedRange {
+    id: u64,
+    start_pts: i64,
+    end_pts: i64,
+    packets: Vec<EncodedPacket>,
+    bytes: usize,
+    /// Set once the pin budget refused a packet, so the warning is logged once per pin.
+    pub(crate) truncated: bool,
+}
+
+impl PinnedRange {
+    fn contains(&self, pts: i64) -> bool {
+        pts >= self.start_pts && pts <= self.end_pts
+    }
+
+    pub(crate) fn push(&mut self, packet: EncodedPacket) {
+        self.bytes += packet.data.len();
+        self.packets.push(packet);
+    }
+}
+
+/// Live pinned ranges of a replay buffer.
+#[derive(Default)]
+pub(crate) struct PinStore {
+    next_id: u64,
+    ranges: Vec<PinnedRange>,
+}
+
+impl PinStore {
+    /// Registers `[start_pts, end_pts]` and returns its id.
+    pub(crate) fn insert(&mut self, start_pts: i64, end_pts: i64) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ranges.push(PinnedRange {
+            id,
+            start_pts,
+            end_pts,
+            packets: Vec::new(),
+            bytes: 0,
+            truncated: false,
+        });
+        id
+    }
+
+    pub(crate) fn range_mut(&mut self, id: u64) -> Option<&mut PinnedRange> {
+        self.ranges.iter_mut().find(|range| range.id == id)
+    }
+
+    /// The oldest live range covering `pts`.
+    pub(crate) fn covering_mut(&mut self, pts: i64) -> Option<&mut PinnedRange> {
+        self.ranges.iter_mut().find(|range| range.contains(pts))
+    }
+
+    /// Whether a range other than `id` covers `pts`.
+    pub(crate) fn is_covered_by_other(&self, id: u64, pts: i64) -> bool {
+        self.ranges
+            .iter()
+            .any(|range| range.id != id && range.contains(pts))
+    }
+
+    /// Removes range `id`, handing packets that other live ranges still cover over to them.
+    ///
+    /// Returns the number of bytes no longer retained by any range.
+    pub(crate) fn remove(&mut self, id: u64) -> usize {
+        let Some(pos) = self.ranges.iter().position(|range| range.id == id) else {
+            return 0;
+        };
+        let removed = self.ranges.remove(pos);
+        let mut released = 0;
+        for packet in removed.packets {
+            match self.covering_mut(packet.pts) {
+                Some(other) => other.push(packet),
+                None => released += packet.data.len(),
+            }
+        }
+        released
+    }
+
+    /// Clones the retained packets with PTS in `[start_pts, end_pts)`, across all ranges.
+    pub(crate) fn packets_in(&self, start_pts: i64, end_pts: i64) -> Vec<EncodedPacket> {
+        self.ranges
+            .iter()
+            .flat_map(|range| range.packets.iter())
+            .filter(|packet| packet.pts >= start_pts && packet.pts < end_pts)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether any range retains a packet with PTS in `[start_pts, end_pts]`.
+    pub(crate) fn has_packets_in(&self, start_pts: i64, end_pts: i64) -> bool {
+        self.ranges
+            .iter()
+            .flat_map(|range| range.packets.iter())
+            .any(|packet| packet.pts >= start_pts && packet.pts <= end_pts)
+    }
+
+    /// PTS of the last retained video keyframe at or before `pts`.
+    pub(crate) fn keyframe_at_or_before(&self, pts: i64) -> Option<i64> {
+        self.ranges
+            .iter()
+            .flat_map(|range| range.packets.iter())
+            .filter(|packet| {
+                packet.is_keyframe
+                    && matches!(packet.stream, StreamType::Video)
+                    && packet.pts <= pts
+            })
+            .map(|packet| packet.pts)
+            .max()
+    }
+
+    pub(crate) fn retained_bytes(&self, id: u64) -> usize {
+        self.ranges
+            .iter()
+            .find(|range| range.id == id)
+            .map_or(0, |range| range.bytes)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+/// Keeps a PTS range of the replay buffer exempt from eviction until dropped.
+///
+/// Created by [`SharedReplayBuffer::pin_range`](super::SharedReplayBuffer::pin_range). The
+/// range starts at the last keyframe at or before the requested start so a clip cut from it
+/// is decodable; its end may lie in the future, in which case packets pushed later are
+/// retained too.
+pub struct PinHandle {
+    buffer: LockFreeReplayBuffer,
+    id: u64,
+    start_pts: i64,
+    end_pts: i64,
+}
+
+impl PinHandle {
+    pub(super) fn new(buffer: LockFreeReplayBuffer, id: u64, start_pts: i64, end_pts: i64) -> Self {
+        Self {
+            buffer,
+            id,
+            start_pts,
+            end_pts,
+        }
+    }
+
+    /// Keyframe-aligned start of the pinned range.
+    #[must_use]
+    pub fn start_pts(&self) -> i64 {
+        self.start_pts
+    }
+
+    /// Inclusive end of the pinned range.
+    #[must_use]
+    pub fn end_pts(&self) -> i64 {
+        self.end_pts
+    }
+
+    /// Bytes this pin currently retains outside the ring (evicted or spilled packets).
+    #[must_use]
+    pub fn retained_bytes(&self) -> usize {
+        self.buffer.pin_retained_bytes(self.id)
+    }
+
+    /// Snapshots the pinned range from every tier of the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the outstanding snapshot limit is exceeded or spilled packets
+    /// cannot be read.
+    pub fn snapshot(&self) -> BufferResult<TrackedSnapshot> {
+        self.buffer.snapshot_range(self.start_pts, self.end_pts)
+    }
+}
+
+impl Drop for PinHandle {
+    fn drop(&mut self) {
+        self.buffer.release_pin(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn packet(pts: i64, size: usize) -> EncodedPacket {
+        EncodedPacket {
+            data: Bytes::from(vec![0u8; size]),
+            pts,
+            dts: pts,
+            stream: StreamType::Video,
+            is_keyframe: false,
+            resolution: None,
+            codec: None,
+        }
+    }
+
+    #[test]
+    fn removing_a_range_hands_overlapping_packets_to_the_survivor() {
+        let mut store = PinStore::default();
+        let first = store.insert(0, 100);
+        let second = store.insert(50, 200);
+
+        let range = store.range_mut(first).unwrap();
+        range.push(packet(10, 4));
+        range.push(packet(60, 8));
+
+        assert_eq!(store.remove(first), 4);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.retained_bytes(second), 8);
+        assert_eq!(store.packets_in(0, 201).len(), 1);
+    }
+}
@@ -22,6 +22,17 @@
 //! - Memory and duration-based eviction
 //! - Proactive eviction at 80% memory watermark to prevent mutex storms
 //! - Batch eviction to reduce lock contention
+//! - Optional disk spill tier: packets evicted from RAM are appended to segment files and
+//!   read back by `snapshot_from` when the requested window reaches past RAM (see
+//!   [`super::disk_spill`])
+//! - Pinned ranges: packets inside a live [`PinHandle`] window are moved into a pin store
+//!   instead of being dropped when they leave the ring (see [`super::pin`])
+//! - In-place resize: duration and memory limits can change at runtime. The slot array sits
+//!   behind an `RwLock` that every operation read-locks; [`LockFreeReplayBuffer::resize`]
+//!   takes it for writing only while it moves the live packets
+//! - Chunked reads: [`LockFreeReplayBuffer::snapshot_chunks`] yields a clip window, RAM and
+//!   disk spill alike, in bounded chunks so savers never hold the whole clip (see
+//!   [`super::chunks`])
 //!
 //! # Thread Safety
 //!
@@ -40,16 +51,20 @@
 //! let buffer = LockFreeReplayBuffer::new(&config).unwrap();
 //! ```
 
-use crate::buffer::BufferResult;
+use crate::buffer::{BufferError, BufferResult};
 use crate::encode::{EncodedPacket, StreamType};
 use bytes::Bytes;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, trace, warn};
 
+use super::chunks::{ChunkRead, SnapshotChunks};
+use super::disk_spill::{DiskSpillTier, SpillChunk, SpillCursor, SpillRead, BYTES_PER_GB};
 use super::functions::qpc_frequency;
-use super::types::BufferStats;
+use super::pin::{PinHandle, PinStore};
+use super::tail::{ReplaySubscription, TailRead};
+use super::types::{BufferStats, StreamGaps, StreamGapsAccumulator, StreamStats};
 use crate::media::nal::{h264_nal_type, hevc_nal_type};
 
 /// Memory usage percentage at which proactive eviction begins.
@@ -141,14 +156,14 @@ impl SnapshotBytes {
 
             // Warn if approaching limit
             #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
-            let warning_threshold = (inner.max_memory_bytes as f64
+            let warning_threshold = (inner.max_memory_bytes.load(Ordering::Relaxed) as f64
                 * OUTSTANDING_SNAPSHOT_WARNING_RATIO as f64)
                 as usize;
             if new_total > warning_threshold && current <= warning_threshold {
                 warn!(
                     "Snapshot memory approaching limit: {:.1}MB / {:.1}MB",
                     new_total as f64 / 1_048_576.0,
-                    inner.max_memory_bytes as f64 / 1_048_576.0
+                    inner.max_memory_bytes.load(Ordering::Relaxed) as f64 / 1_048_576.0
                 );
             }
         }
@@ -254,11 +269,10 @@ struct LockFreeInner {
     _pad1: [u8; 72],
 
     // ── Consumer-cold fields (read-only after init, rarely written, or mutex-protected) ──
-    slots: Box<[Slot]>,
-    capacity: usize,
-    mask: usize,
-    max_duration_qpc: i64,
-    max_memory_bytes: usize,
+    /// Slot array; replaced under the write lock only by [`LockFreeReplayBuffer::resize`].
+    ring: parking_lot::RwLock<SlotRing>,
+    max_duration_qpc: AtomicI64,
+    max_memory_bytes: AtomicUsize,
     restart_generation: AtomicUsize,
     outstanding_snapshot_bytes: AtomicUsize,
     param_cache: parking_lot::Mutex<ParameterCache>,
@@ -267,17 +281,98 @@ struct LockFreeInner {
     first_video_kind: AtomicU8,
     /// Write index at which the first video packet was stored (accessed only during snapshot).
     first_video_idx: AtomicUsize,
+    /// On-disk tier receiving packets evicted from RAM; `None` when disabled.
+    spill: Option<DiskSpillTier>,
+    /// Ranges kept alive by [`PinHandle`]s, with the packets they retained from eviction.
+    pins: parking_lot::Mutex<PinStore>,
+    /// Number of live pins; lets the eviction paths skip the `pins` lock when zero.
+    pin_count: AtomicUsize,
+    pinned_range_bytes: AtomicUsize,
+    max_pinned_bytes: usize,
+    /// Per-stream accounting of the packets in the ring, indexed by [`stream_slot`].
+    streams: [StreamCounters; 3],
+    /// Number of live [`ReplaySubscription`]s; lets the push path skip the wakeup when zero.
+    subscriber_count: AtomicUsize,
+    /// Paired with `tail_signal` so a subscriber cannot miss a push between its check and wait.
+    tail_lock: parking_lot::Mutex<()>,
+    tail_signal: parking_lot::Condvar,
+}
+
+impl LockFreeInner {
+    /// Records `pts` as the next PTS in the slot of the stream's newest packet. Called before
+    /// the new packet's slot is overwritten, which may hold that previous packet.
+    fn link_to_previous(&self, ring: &SlotRing, stream: StreamType, pts: i64) {
+        let counters = &self.streams[stream_slot(stream)];
+        if counters.packet_count.load(Ordering::Relaxed) > 0 {
+            let newest_idx = counters.newest_idx.load(Ordering::Relaxed);
+            ring.slots[newest_idx & ring.mask]
+                .next_pts
+                .store(pts, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts `packet`, stored at `write_idx`, in its stream.
+    fn account_pushed(&self, write_idx: usize, packet: &EncodedPacket) {
+        let counters = &self.streams[stream_slot(packet.stream)];
+        if counters.packet_count.load(Ordering::Relaxed) == 0 {
+            counters.oldest_pts.store(packet.pts, Ordering::Relaxed);
+        }
+        counters
+            .bytes
+            .fetch_add(packet.data.len(), Ordering::Relaxed);
+        counters.packet_count.fetch_add(1, Ordering::Relaxed);
+        counters.newest_pts.store(packet.pts, Ordering::Relaxed);
+        counters.newest_idx.store(write_idx, Ordering::Relaxed);
+    }
+
+    /// Removes `packet`, the oldest of its stream, taken out of `slot`.
+    fn account_evicted(&self, slot: &Slot, packet: &EncodedPacket) {
+        let counters = &self.streams[stream_slot(packet.stream)];
+        counters
+            .bytes
+            .fetch_sub(packet.data.len(), Ordering::Relaxed);
+        if counters.packet_count.fetch_sub(1, Ordering::Relaxed) <= 1 {
+            counters.oldest_pts.store(NO_PTS, Ordering::Relaxed);
+            counters.newest_pts.store(NO_PTS, Ordering::Relaxed);
+        } else {
+            counters
+                .oldest_pts
+                .store(slot.next_pts.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Removes `packet`, just pushed, that was dropped to hold the memory cap. The stream's
+    /// newest PTS keeps pointing at it until the next push.
+    fn account_dropped_newest(&self, packet: &EncodedPacket) {
+        let counters = &self.streams[stream_slot(packet.stream)];
+        counters
+            .bytes
+            .fetch_sub(packet.data.len(), Ordering::Relaxed);
+        if counters.packet_count.fetch_sub(1, Ordering::Relaxed) <= 1 {
+            counters.oldest_pts.store(NO_PTS, Ordering::Relaxed);
+            counters.newest_pts.store(NO_PTS, Ordering::Relaxed);
+        }
+    }
 }
 
 #[repr(align(64))]
 struct Slot {
     packet: parking_lot::Mutex<Option<EncodedPacket>>,
+    /// Write index + 1 of the packet last stored in this slot (0 = never written since the
+    /// last clear/restart). Updated under `packet`'s lock; lets tail readers tell a packet
+    /// that is still being written from one that was already overwritten.
+    seq: AtomicUsize,
+    /// PTS of the next packet of the same stream, [`NO_PTS`] until it is pushed. Lets
+    /// eviction move the stream's oldest PTS forward without looking for that packet.
+    next_pts: AtomicI64,
 }
 
 impl Slot {
     const fn new() -> Self {
         Self {
             packet: parking_lot::Mutex::new(None),
+            seq: AtomicUsize::new(0),
+            next_pts: AtomicI64::new(NO_PTS),
         }
     }
 
@@ -302,6 +397,138 @@ impl Slot {
     }
 }
 
+/// Position in the ring's write sequence, used by [`LockFreeReplayBuffer::packets_since`]
+/// and [`ReplaySubscription`] to tail newly pushed packets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RingCursor {
+    next_idx: usize,
+    generation: usize,
+}
+
+/// Snapshot ordering: PTS first, then video before system audio before microphone.
+pub(in crate::buffer) fn snapshot_sort_key(packet: &EncodedPacket) -> (i64, u8) {
+    (packet.pts, stream_slot(packet.stream) as u8)
+}
+
+/// Index of `stream` in per-stream arrays: video, system audio, microphone.
+pub(in crate::buffer) fn stream_slot(stream: StreamType) -> usize {
+    match stream {
+        StreamType::Video => 0,
+        StreamType::SystemAudio => 1,
+        StreamType::Microphone => 2,
+    }
+}
+
+/// Marks an unset PTS in [`StreamCounters`] and [`Slot::next_pts`].
+const NO_PTS: i64 = i64::MIN;
+
+/// Running accounting of one stream's packets in the ring, updated on push and eviction so
+/// [`LockFreeReplayBuffer::stats`] does not walk the slots. Written by the producer only.
+struct StreamCounters {
+    bytes: AtomicUsize,
+    packet_count: AtomicUsize,
+    /// PTS of the oldest and newest packet of the stream in the ring, [`NO_PTS`] when empty.
+    oldest_pts: AtomicI64,
+    newest_pts: AtomicI64,
+    /// Write index of the newest packet, whose slot learns the PTS of the next one.
+    newest_idx: AtomicUsize,
+}
+
+impl StreamCounters {
+    const fn new() -> Self {
+        Self {
+            bytes: AtomicUsize::new(0),
+            packet_count: AtomicUsize::new(0),
+            oldest_pts: AtomicI64::new(NO_PTS),
+            newest_pts: AtomicI64::new(NO_PTS),
+            newest_idx: AtomicUsize::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.bytes.store(0, Ordering::Release);
+        self.packet_count.store(0, Ordering::Release);
+        self.oldest_pts.store(NO_PTS, Ordering::Release);
+        self.newest_pts.store(NO_PTS, Ordering::Release);
+        self.newest_idx.store(0, Ordering::Release);
+    }
+
+    fn pts(value: i64) -> Option<i64> {
+        (value != NO_PTS).then_some(value)
+    }
+}
+
+/// Power-of-two slot array addressed by `write_idx & mask`.
+struct SlotRing {
+    slots: Box<[Slot]>,
+    capacity: usize,
+    mask: usize,
+}
+
+impl SlotRing {
+    fn new(capacity: usize) -> Self {
+        let mut slots = Vec::with_capacity(capacity);
+        for _ in 0..capacity {
+            slots.push(Slot::new());
+        }
+        Self {
+            slots: slots.into_boxed_slice(),
+            capacity,
+            mask: capacity - 1,
+        }
+    }
+}
+
+/// Ring size and eviction limits derived from the replay settings.
+struct RingLimits {
+    capacity: usize,
+    max_duration_qpc: i64,
+    max_memory_bytes: usize,
+}
+
+impl RingLimits {
+    fn duration_qpc(config: &crate::config::Config) -> i64 {
+        (config.general.replay_duration_secs.max(1) as i64).saturating_mul(qpc_frequency().max(1))
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn from_config(config: &crate::config::Config, spill_enabled: bool) -> Self {
+        let duration = Duration::from_secs(config.general.replay_duration_secs as u64 + 1);
+        let effective_memory_limit_mb = config.effective_replay_memory_limit_mb();
+        let max_memory_bytes = (effective_memory_limit_mb as usize).saturating_mul(1024 * 1024);
+
+        // With a spill tier the ring only needs slots for what fits in RAM (with headroom);
+        // older packets are overwritten into the spill instead of holding a slot each.
+        let ring_duration = if spill_enabled {
+            let bytes_per_sec =
+                (config.estimated_replay_storage_bytes() as u64 / duration.as_secs().max(1)).max(1);
+            let ram_secs = (max_memory_bytes as u64 / bytes_per_sec)
+                .saturating_mul(2)
+                .clamp(1, duration.as_secs());
+            Duration::from_secs(ram_secs)
+        } else {
+            duration
+        };
+
+        let video_packets_per_sec = config.video.framerate as f32;
+        let audio_streams =
+            (u8::from(config.audio.capture_system) + u8::from(config.audio.capture_mic)) as f32;
+        let audio_packets_per_sec = audio_streams * 50.0;
+        let packets_per_sec = video_packets_per_sec + audio_packets_per_sec;
+        let estimated_packets = (ring_duration.as_secs_f32() * packets_per_sec).max(100.0) as usize;
+
+        Self {
+            capacity: estimated_packets.next_power_of_two(),
+            max_duration_qpc: Self::duration_qpc(config),
+            max_memory_bytes,
+        }
+    }
+}
+
 impl LockFreeReplayBuffer {
     /// Creates a new lock-free replay buffer.
     ///
@@ -333,34 +560,33 @@ impl LockFreeReplayBuffer {
         clippy::cast_sign_loss
     )]
     pub fn new(config: &crate::config::Config) -> BufferResult<Self> {
-        let duration = Duration::from_secs(config.general.replay_duration_secs as u64 + 1);
-        let effective_memory_limit_mb = config.effective_replay_memory_limit_mb();
-        let max_memory_bytes = (effective_memory_limit_mb as usize).saturating_mul(1024 * 1024);
-        let qpc_freq = qpc_frequency().max(1);
-        let max_duration_qpc =
-            (config.general.replay_duration_secs.max(1) as i64).saturating_mul(qpc_freq);
-
-        let video_packets_per_sec = config.video.framerate as f32;
-        let audio_streams =
-            (u8::from(config.audio.capture_system) + u8::from(config.audio.capture_mic)) as f32;
-        let audio_packets_per_sec = audio_streams * 50.0;
-        let packets_per_sec = video_packets_per_sec + audio_packets_per_sec;
-        let estimated_packets = (duration.as_secs_f32() * packets_per_sec).max(100.0) as usize;
-        let capacity = estimated_packets.next_power_of_two();
-        let mask = capacity - 1;
-
-        let mut slots = Vec::with_capacity(capacity);
-        for _ in 0..capacity {
-            slots.push(Slot::new());
-        }
+        let max_pinned_bytes =
+            (config.general.replay_pin_budget_mb as usize).saturating_mul(1024 * 1024);
+        let max_duration_qpc = RingLimits::duration_qpc(config);
+
+        let spill = if config.general.replay_disk_limit_gb > 0 {
+            let max_disk_bytes =
+                u64::from(config.general.replay_disk_limit_gb).saturating_mul(BYTES_PER_GB);
+            match DiskSpillTier::new(max_disk_bytes, max_duration_qpc) {
+                Ok(tier) => Some(tier),
+                Err(e) => {
+                    warn!(
+                        "Replay disk spill unavailable, keeping replay in RAM only: {}",
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
+        let limits = RingLimits::from_config(config, spill.is_some());
         debug!(
-            "Creating LockFreeReplayBuffer: {} seconds, {} MB max, {} slots ({} video + {} audio pps)",
-            duration.as_secs(),
-            effective_memory_limit_mb,
-            capacity,
-            video_packets_per_sec as u32,
-            audio_packets_per_sec as u32
+            "Creating LockFreeReplayBuffer: {} seconds, {} MB max, {} slots",
+            config.general.replay_duration_secs,
+            limits.max_memory_bytes / (1024 * 1024),
+            limits.capacity
         );
 
         Ok(Self {
@@ -374,20 +600,144 @@ impl LockFreeReplayBuffer {
                 param_cache_complete: AtomicBool::new(false),
                 param_cache_pushes_since_complete: AtomicUsize::new(0),
                 _pad1: [0u8; 72],
-                slots: slots.into_boxed_slice(),
-                capacity,
-                mask,
-                max_duration_qpc,
-                max_memory_bytes,
+                ring: parking_lot::RwLock::new(SlotRing::new(limits.capacity)),
+                max_duration_qpc: AtomicI64::new(limits.max_duration_qpc),
+                max_memory_bytes: AtomicUsize::new(limits.max_memory_bytes),
                 restart_generation: AtomicUsize::new(0),
                 outstanding_snapshot_bytes: AtomicUsize::new(0),
                 param_cache: parking_lot::Mutex::new(ParameterCache::default()),
                 first_video_kind: AtomicU8::new(0),
                 first_video_idx: AtomicUsize::new(0),
+                spill,
+                pins: parking_lot::Mutex::new(PinStore::default()),
+                pin_count: AtomicUsize::new(0),
+                pinned_range_bytes: AtomicUsize::new(0),
+                max_pinned_bytes,
+                streams: [
+                    StreamCounters::new(),
+                    StreamCounters::new(),
+                    StreamCounters::new(),
+                ],
+                subscriber_count: AtomicUsize::new(0),
+                tail_lock: parking_lot::Mutex::new(()),
+                tail_signal: parking_lot::Condvar::new(),
             }),
         })
     }
 
+    /// Applies changed `replay_duration_secs` / memory limit settings without dropping the
+    /// buffered replay.
+    ///
+    /// When the derived slot count changes, the slot array is reallocated and the live
+    /// packets move to it at the same write indices, so subscriptions and pins keep working.
+    /// When the new limits hold less than is buffered, the oldest packets are evicted (into
+    /// pins and the disk spill like any other eviction) and the retained window is advanced
+    /// to the next video keyframe so it still starts decodable. Pushes and snapshots wait
+    /// while the slots are moved.
+    pub fn resize(&self, config: &crate::config::Config) {
+        let inner = &self.inner;
+        let limits = RingLimits::from_config(config, inner.spill.is_some());
+        let mut ring = inner.ring.write();
+        let old_capacity = ring.capacity;
+        let old_mask = ring.mask;
+
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let live_start = write_idx
+            .saturating_sub(old_capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire));
+        let newest_pts = inner.newest_pts.load(Ordering::Acquire);
+        let cutoff_pts = newest_pts.saturating_sub(limits.max_duration_qpc);
+
+        // Walk back from the newest packet while it still fits the new limits.
+        let mut keep_start = write_idx;
+        let mut kept_bytes = 0usize;
+        while keep_start > live_start && write_idx - (keep_start - 1) <= limits.capacity {
+            if let Some(packet) = ring.slots[(keep_start - 1) & old_mask].packet.get_mut() {
+                let over_memory = limits.max_memory_bytes > 0
+                    && kept_bytes + packet.data.len() > limits.max_memory_bytes;
+                let too_old = newest_pts > 0 && packet.pts < cutoff_pts;
+                if over_memory || too_old {
+                    break;
+                }
+                kept_bytes += packet.data.len();
+            }
+            keep_start -= 1;
+        }
+        if keep_start > live_start {
+            if let Some(keyframe_idx) = (keep_start..write_idx).find(|&idx| {
+                ring.slots[idx & old_mask]
+                    .packet
+                    .get_mut()
+                    .as_ref()
+                    .is_some_and(|p| p.is_keyframe && matches!(p.stream, StreamType::Video))
+            }) {
+                keep_start = keyframe_idx;
+            }
+        }
+
+        let mut evicted_packets = 0usize;
+        let mut evicted_bytes = 0usize;
+        for idx in live_start..keep_start {
+            let slot = &mut ring.slots[idx & old_mask];
+            let Some(old) = slot.packet.get_mut().take() else {
+                continue;
+            };
+            inner.account_evicted(slot, &old);
+            inner
+                .total_bytes
+                .fetch_sub(old.data.len(), Ordering::Relaxed);
+            if old.is_keyframe {
+                inner.keyframe_count.fetch_sub(1, Ordering::Relaxed);
+            }
+            evicted_packets += 1;
+            evicted_bytes += old.data.len();
+            self.dispose_evicted(old, newest_pts);
+        }
+        if keep_start > inner.evict_frontier.load(Ordering::Acquire) {
+            inner.evict_frontier.store(keep_start, Ordering::Release);
+        }
+
+        if limits.capacity != old_capacity {
+            let mut resized = SlotRing::new(limits.capacity);
+            for idx in keep_start..write_idx {
+                let old_slot = &mut ring.slots[idx & old_mask];
+                let packet = old_slot.packet.get_mut().take();
+                let seq = *old_slot.seq.get_mut();
+                let next_pts = *old_slot.next_pts.get_mut();
+                let new_slot = &mut resized.slots[idx & resized.mask];
+                *new_slot.packet.get_mut() = packet;
+                *new_slot.seq.get_mut() = seq;
+                *new_slot.next_pts.get_mut() = next_pts;
+            }
+            *ring = resized;
+        }
+        if write_idx >= limits.capacity {
+            inner.has_wrapped.store(true, Ordering::Release);
+        }
+
+        inner
+            .max_duration_qpc
+            .store(limits.max_duration_qpc, Ordering::Relaxed);
+        inner
+            .max_memory_bytes
+            .store(limits.max_memory_bytes, Ordering::Relaxed);
+        if let Some(spill) = inner.spill.as_ref() {
+            spill.set_max_duration_qpc(limits.max_duration_qpc);
+        }
+        drop(ring);
+        self.notify_subscribers();
+
+        info!(
+            "Replay buffer resized: {} -> {} slots, {:.1}MB limit, {}s window; evicted {} packets ({:.1}MB)",
+            old_capacity,
+            limits.capacity,
+            limits.max_memory_bytes as f64 / 1_048_576.0,
+            config.general.replay_duration_secs,
+            evicted_packets,
+            evicted_bytes as f64 / 1_048_576.0
+        );
+    }
+
     /// Pushes a batch of packets into the buffer.
     ///
     /// Thread-safe for single producer.
@@ -428,6 +778,8 @@ impl LockFreeReplayBuffer {
     /// would require coordinating the `write_idx` increment to prevent races.
     fn push_single(&self, packet: EncodedPacket) {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let max_memory_bytes = inner.max_memory_bytes.load(Ordering::Relaxed);
         let packet_size = packet.data.len();
         let packet_pts = packet.pts;
         let is_keyframe = packet.is_keyframe;
@@ -468,19 +820,27 @@ impl LockFreeReplayBuffer {
         }
 
         let write_idx = inner.write_idx.fetch_add(1, Ordering::Relaxed);
-        let slot_idx = write_idx & inner.mask;
-        let slot = &inner.slots[slot_idx];
+        let slot_idx = write_idx & ring.mask;
+        let slot = &ring.slots[slot_idx];
+        // Before the slot is overwritten: it may hold the stream's previous packet.
+        inner.link_to_previous(&ring, stream_type, packet_pts);
 
         // Load total_bytes_before for logging BEFORE the lock block where fetch_add happens
         let total_bytes_before = inner.total_bytes.load(Ordering::Relaxed);
 
         // Track old packet for memory accounting - now includes new packet accounting inside lock
-        let old_packet_size = {
+        let (old_packet_size, overwritten) = {
             let mut packet_guard = slot.packet.lock();
             let old = packet_guard.take();
             let old_size = old.as_ref().map_or(0, |p| p.data.len());
             let old_was_keyframe = old.as_ref().is_some_and(|p| p.is_keyframe);
+            if let Some(old) = old.as_ref() {
+                inner.account_evicted(slot, old);
+            }
+            slot.next_pts.store(NO_PTS, Ordering::Relaxed);
+            inner.account_pushed(write_idx, &packet);
             *packet_guard = Some(packet);
+            slot.seq.store(write_idx + 1, Ordering::Release);
             // Account for new packet bytes immediately, inside the lock.
             // The parking_lot::Mutex already provides sequential consistency for
             // the critical section, so Relaxed ordering is sufficient here.
@@ -495,10 +855,17 @@ impl LockFreeReplayBuffer {
             if is_keyframe {
                 inner.keyframe_count.fetch_add(1, Ordering::Relaxed);
             }
-            old_size
+            (old_size, old)
         };
 
         inner.newest_pts.store(packet_pts, Ordering::Release);
+        self.notify_subscribers();
+
+        // A packet overwritten at ring wrap is still inside the replay window as far as
+        // the spill tier is concerned; the spill writer applies its own duration cutoff.
+        if let Some(old) = overwritten {
+            self.dispose_evicted(old, packet_pts);
+        }
 
         // RC1: Only run duration-based eviction after the ring has wrapped at least once.
         // Before the first wrap, all packets are within the replay window.
@@ -511,8 +878,8 @@ impl LockFreeReplayBuffer {
         // The oldest valid packet is at (write_idx + 1) - capacity when buffer is full.
         // Without this, memory eviction would evict NEW packets instead of old ones.
         let next_write_idx = write_idx + 1;
-        if next_write_idx > inner.capacity {
-            let oldest_valid = next_write_idx - inner.capacity;
+        if next_write_idx > ring.capacity {
+            let oldest_valid = next_write_idx - ring.capacity;
             let current_frontier = inner.evict_frontier.load(Ordering::Relaxed);
             if current_frontier < oldest_valid {
                 inner.evict_frontier.store(oldest_valid, Ordering::Release);
@@ -520,7 +887,7 @@ impl LockFreeReplayBuffer {
                 trace!(
                     "Ring wrap: write_idx={}, capacity={}, evict_frontier {}->{}",
                     write_idx,
-                    inner.capacity,
+                    ring.capacity,
                     current_frontier,
                     oldest_valid
                 );
@@ -542,8 +909,8 @@ impl LockFreeReplayBuffer {
                 packet_size / 1024,
                 old_packet_size / 1024,
                 total_bytes_before as f64 / 1_048_576.0,
-                inner.max_memory_bytes as f64 / 1_048_576.0,
-                (total_bytes_before as f64 / inner.max_memory_bytes as f64 * 100.0).min(999.0),
+                max_memory_bytes as f64 / 1_048_576.0,
+                (total_bytes_before as f64 / max_memory_bytes as f64 * 100.0).min(999.0),
                 packet_count,
                 write_idx,
                 evict_frontier
@@ -571,12 +938,12 @@ impl LockFreeReplayBuffer {
         //
         // Batch eviction acquires multiple slot locks in a tight loop to reduce
         // contention compared to one-by-one eviction with full push path overhead.
-        if inner.max_memory_bytes > 0 {
+        if max_memory_bytes > 0 {
             // Use Relaxed ordering — the parking_lot::Mutex lock/unlock in the eviction
             // loop below provides the necessary happens-before synchronization. Exact
             // memory pressure tracking doesn't require sequential consistency.
             let current_total = inner.total_bytes.load(Ordering::Relaxed);
-            let memory_ratio = current_total as f32 / inner.max_memory_bytes as f32;
+            let memory_ratio = current_total as f32 / max_memory_bytes as f32;
 
             // Determine if we need eviction and how aggressively
             let needs_eviction = memory_ratio > PROACTIVE_EVICTION_WATERMARK;
@@ -596,7 +963,7 @@ impl LockFreeReplayBuffer {
                 } else {
                     PROACTIVE_EVICTION_WATERMARK - 0.05 // Proactive: evict to ~75%
                 };
-                let target_bytes = (inner.max_memory_bytes as f32 * target_ratio) as usize;
+                let target_bytes = (max_memory_bytes as f32 * target_ratio) as usize;
 
                 // Batch eviction loop — Relaxed ordering is sufficient because the
                 // parking_lot::Mutex lock/unlock inside each iteration provides
@@ -619,14 +986,15 @@ impl LockFreeReplayBuffer {
                             break;
                         }
 
-                        let evict_slot_idx = evict & inner.mask;
-                        let slot = &inner.slots[evict_slot_idx];
+                        let evict_slot_idx = evict & ring.mask;
+                        let slot = &ring.slots[evict_slot_idx];
 
                         // Acquire lock and evict packet
                         {
                             let mut guard = slot.packet.lock();
                             if let Some(old) = guard.take() {
                                 let old_len = old.data.len();
+                                inner.account_evicted(slot, &old);
                                 // The parking_lot::Mutex provides sequential consistency,
                                 // so Relaxed ordering is sufficient for counter updates.
                                 inner.total_bytes.fetch_sub(old_len, Ordering::Relaxed);
@@ -637,6 +1005,7 @@ impl LockFreeReplayBuffer {
                                 evicted_bytes += old_len;
                                 eviction_count += 1;
                                 batch_evicted += 1;
+                                self.dispose_evicted(old, packet_pts);
                             } else {
                                 trace!(
                                     "Eviction slot empty: evict_frontier={}, slot_idx={}",
@@ -675,21 +1044,20 @@ impl LockFreeReplayBuffer {
                         evicted_bytes as f64 / 1_048_576.0,
                         start_total as f64 / 1_048_576.0,
                         end_total as f64 / 1_048_576.0,
-                        inner.max_memory_bytes as f64 / 1_048_576.0,
-                        (start_total as f64 / inner.max_memory_bytes as f64 * 100.0),
-                        (end_total as f64 / inner.max_memory_bytes as f64 * 100.0)
+                        max_memory_bytes as f64 / 1_048_576.0,
+                        (start_total as f64 / max_memory_bytes as f64 * 100.0),
+                        (end_total as f64 / max_memory_bytes as f64 * 100.0)
                     );
                 }
 
                 // Oldest packets are gone but the packet we just wrote (or accounting skew) still exceeds the cap.
-                if stopped_at_head
-                    && inner.total_bytes.load(Ordering::Acquire) > inner.max_memory_bytes
-                {
-                    let slot_idx = write_idx & inner.mask;
-                    let slot = &inner.slots[slot_idx];
+                if stopped_at_head && inner.total_bytes.load(Ordering::Acquire) > max_memory_bytes {
+                    let slot_idx = write_idx & ring.mask;
+                    let slot = &ring.slots[slot_idx];
                     let mut guard = slot.packet.lock();
                     if let Some(removed) = guard.take() {
                         let rm = removed.data.len();
+                        inner.account_dropped_newest(&removed);
                         // The parking_lot::Mutex provides sequential consistency,
                         // so Relaxed ordering is sufficient for counter updates.
                         inner.total_bytes.fetch_sub(rm, Ordering::Relaxed);
@@ -699,7 +1067,7 @@ impl LockFreeReplayBuffer {
                         warn!(
                             "Buffer: dropped newest packet ({:.1}KB) to enforce memory cap {:.1}MB",
                             rm as f64 / 1024.0,
-                            inner.max_memory_bytes as f64 / 1_048_576.0
+                            max_memory_bytes as f64 / 1_048_576.0
                         );
                     }
                 }
@@ -709,14 +1077,13 @@ impl LockFreeReplayBuffer {
 
     fn evict_packets_older_than(&self, newest_pts: i64) {
         let inner = &self.inner;
-        if inner.max_duration_qpc <= 0
-            || newest_pts <= 0
-            || !inner.has_wrapped.load(Ordering::Relaxed)
-        {
+        let ring = inner.ring.read_recursive();
+        let max_duration_qpc = inner.max_duration_qpc.load(Ordering::Relaxed);
+        if max_duration_qpc <= 0 || newest_pts <= 0 || !inner.has_wrapped.load(Ordering::Relaxed) {
             return;
         }
 
-        let cutoff_pts = newest_pts.saturating_sub(inner.max_duration_qpc);
+        let cutoff_pts = newest_pts.saturating_sub(max_duration_qpc);
         let write_idx = inner.write_idx.load(Ordering::Acquire);
         let mut duration_evicted_packets = 0usize;
         let mut duration_evicted_bytes = 0usize;
@@ -728,8 +1095,8 @@ impl LockFreeReplayBuffer {
                 break;
             }
 
-            let slot_idx = evict & inner.mask;
-            let slot = &inner.slots[slot_idx];
+            let slot_idx = evict & ring.mask;
+            let slot = &ring.slots[slot_idx];
             let mut guard = slot.packet.lock();
 
             let should_evict = match guard.as_ref() {
@@ -743,6 +1110,7 @@ impl LockFreeReplayBuffer {
 
             if let Some(old) = guard.take() {
                 let old_len = old.data.len();
+                inner.account_evicted(slot, &old);
                 // The parking_lot::Mutex provides sequential consistency,
                 // so Relaxed ordering is sufficient for counter updates.
                 inner.total_bytes.fetch_sub(old_len, Ordering::Relaxed);
@@ -752,6 +1120,7 @@ impl LockFreeReplayBuffer {
                 }
                 duration_evicted_bytes += old_len;
                 duration_evicted_packets += 1;
+                self.dispose_evicted(old, newest_pts);
             }
             drop(guard);
             inner.evict_frontier.fetch_add(1, Ordering::Release);
@@ -765,7 +1134,7 @@ impl LockFreeReplayBuffer {
                 duration_evicted_bytes as f64 / 1_048_576.0,
                 cutoff_pts,
                 newest_pts,
-                inner.max_duration_qpc as f64 / qpc_frequency().max(1) as f64
+                max_duration_qpc as f64 / qpc_frequency().max(1) as f64
             );
         }
     }
@@ -925,13 +1294,14 @@ impl LockFreeReplayBuffer {
     #[allow(clippy::too_many_lines)]
     pub fn snapshot(&self) -> BufferResult<TrackedSnapshot> {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
         let write_idx = inner.write_idx.load(Ordering::Acquire);
 
         if write_idx == 0 {
             return Ok(TrackedSnapshot::new(vec![], Arc::clone(inner)));
         }
 
-        let capacity = inner.capacity;
+        let capacity = ring.capacity;
         let evict_frontier = inner.evict_frontier.load(Ordering::Acquire);
         let start_idx = write_idx.saturating_sub(capacity).max(evict_frontier);
         let count = write_idx - start_idx;
@@ -939,8 +1309,8 @@ impl LockFreeReplayBuffer {
         let mut result = Vec::with_capacity(count);
 
         for i in start_idx..write_idx {
-            let slot_idx = i & inner.mask;
-            let slot = &inner.slots[slot_idx];
+            let slot_idx = i & ring.mask;
+            let slot = &ring.slots[slot_idx];
 
             if let Some(packet_guard) = slot.try_lock_snapshot() {
                 if let Some(ref packet) = *packet_guard {
@@ -1091,6 +1461,7 @@ impl LockFreeReplayBuffer {
     /// Safe to call from multiple consumer threads.
     pub fn snapshot_from(&self, start_pts: i64) -> BufferResult<TrackedSnapshot> {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
         let write_idx = inner.write_idx.load(Ordering::Acquire);
 
         if write_idx == 0 {
@@ -1102,31 +1473,38 @@ impl LockFreeReplayBuffer {
         let gen_before = inner.restart_generation.load(Ordering::Acquire);
 
         // Check outstanding snapshot bytes limit before proceeding
-        let current_outstanding = inner.outstanding_snapshot_bytes.load(Ordering::Relaxed);
-        let max_outstanding = inner.max_memory_bytes.min(MAX_OUTSTANDING_SNAPSHOT_BYTES);
-        if current_outstanding >= max_outstanding {
-            warn!(
-                "snapshot_from rejected: outstanding snapshot bytes ({:.1}MB) exceeds limit ({:.1}MB)",
-                current_outstanding as f64 / 1_048_576.0,
-                max_outstanding as f64 / 1_048_576.0
-            );
-            return Err(crate::buffer::BufferError::SnapshotLimitExceeded {
-                current: current_outstanding,
-                limit: max_outstanding,
-            });
-        }
+        self.check_outstanding_snapshot_limit("snapshot_from")?;
 
-        let first_idx = write_idx.saturating_sub(inner.capacity);
+        let first_idx = write_idx.saturating_sub(ring.capacity);
         let evict_frontier = inner.evict_frontier.load(Ordering::Acquire);
         let first_idx = first_idx.max(evict_frontier);
 
+        // ── Spill tier: the window reaches past RAM, stitch disk + RAM packets ──
+        if let Some(spill) = inner.spill.as_ref() {
+            let reaches_past_ram = self
+                .ram_oldest_pts()
+                .map_or(true, |oldest| start_pts < oldest);
+            if reaches_past_ram {
+                if let Some(result) =
+                    self.collect_with_spill(spill, start_pts, first_idx, write_idx)?
+                {
+                    let result = self.prepend_parameter_sets(result);
+                    if inner.restart_generation.load(Ordering::Acquire) != gen_before {
+                        warn!("snapshot_from: buffer restarted during spill read; returning empty");
+                        return Ok(TrackedSnapshot::new(vec![], Arc::clone(inner)));
+                    }
+                    return Ok(TrackedSnapshot::new(result, Arc::clone(inner)));
+                }
+            }
+        }
+
         // ── Sub-pass 1: scan keyframe metadata only (no clones, no Vec<bool>, early-break) ──
         let mut last_keyframe_at_or_before: Option<usize> = None;
         let mut first_keyframe_at_or_after: Option<usize> = None;
 
         for i in first_idx..write_idx {
-            let slot_idx = i & inner.mask;
-            let slot = &inner.slots[slot_idx];
+            let slot_idx = i & ring.mask;
+            let slot = &ring.slots[slot_idx];
 
             if let Some(packet_guard) = slot.try_lock_snapshot() {
                 if let Some(ref packet) = *packet_guard {
@@ -1158,8 +1536,8 @@ impl LockFreeReplayBuffer {
         let mut found_first_video_pts: Option<i64> = None;
 
         for i in start_idx..write_idx {
-            let slot_idx = i & inner.mask;
-            let slot = &inner.slots[slot_idx];
+            let slot_idx = i & ring.mask;
+            let slot = &ring.slots[slot_idx];
 
             if let Some(packet_guard) = slot.try_lock_snapshot() {
                 if let Some(ref packet) = *packet_guard {
@@ -1186,8 +1564,8 @@ impl LockFreeReplayBuffer {
         //     first_video_pts, to prevent unbounded audio-only lead-in. ─────────────
         let first_video_pts = found_first_video_pts.unwrap_or(i64::MAX);
         for i in first_idx..start_idx {
-            let slot_idx = i & inner.mask;
-            let slot = &inner.slots[slot_idx];
+            let slot_idx = i & ring.mask;
+            let slot = &ring.slots[slot_idx];
 
             if let Some(packet_guard) = slot.try_lock_snapshot() {
                 if let Some(ref packet) = *packet_guard {
@@ -1212,104 +1590,10 @@ impl LockFreeReplayBuffer {
 
         // Sort by PTS, then by stream type for deterministic ordering
         // (audio lead-in packets may appear before the keyframe slice).
-        result.sort_by_key(|p| {
-            (
-                p.pts,
-                match p.stream {
-                    StreamType::Video => 0,
-                    StreamType::SystemAudio => 1,
-                    StreamType::Microphone => 2,
-                },
-            )
-        });
+        result.sort_by_key(snapshot_sort_key);
 
         // ── Prepend parameter sets if needed ──────────────────────────────
-        if !result.is_empty() {
-            let first_video = result
-                .iter()
-                .find(|p| matches!(p.stream, StreamType::Video));
-            if let Some(first_vid) = first_video {
-                let first_is_keyframe = first_vid.is_keyframe;
-                let first_data = first_vid.data.as_ref();
-                let first_nal_is_vps = hevc_nal_type(first_data) == Some(32);
-                let first_nal_is_sps = h264_nal_type(first_data) == Some(7);
-
-                if first_is_keyframe && !first_nal_is_vps && !first_nal_is_sps {
-                    let cache = self.inner.param_cache.lock();
-                    let first_pts = first_vid.pts;
-                    let mut prepend = Vec::new();
-
-                    match cache.codec_kind {
-                        CodecKind::H264 => {
-                            if let Some(ref sps_data) = cache.h264_sps {
-                                prepend.push(EncodedPacket {
-                                    data: sps_data.clone(),
-                                    pts: first_pts,
-                                    dts: first_pts,
-                                    stream: StreamType::Video,
-                                    is_keyframe: false,
-                                    resolution: None,
-                                    codec: Some(crate::encode::VideoCodec::H264),
-                                });
-                            }
-                            if let Some(ref pps_data) = cache.h264_pps {
-                                prepend.push(EncodedPacket {
-                                    data: pps_data.clone(),
-                                    pts: first_pts,
-                                    dts: first_pts,
-                                    stream: StreamType::Video,
-                                    is_keyframe: false,
-                                    resolution: None,
-                                    codec: Some(crate::encode::VideoCodec::H264),
-                                });
-                            }
-                        }
-                        CodecKind::Hevc => {
-                            if let Some(ref vps_data) = cache.hevc_vps {
-                                prepend.push(EncodedPacket {
-                                    data: vps_data.clone(),
-                                    pts: first_pts,
-                                    dts: first_pts,
-                                    stream: StreamType::Video,
-                                    is_keyframe: false,
-                                    resolution: None,
-                                    codec: Some(crate::encode::VideoCodec::Hevc),
-                                });
-                            }
-                            if let Some(ref sps_data) = cache.hevc_sps {
-                                prepend.push(EncodedPacket {
-                                    data: sps_data.clone(),
-                                    pts: first_pts,
-                                    dts: first_pts,
-                                    stream: StreamType::Video,
-                                    is_keyframe: false,
-                                    resolution: None,
-                                    codec: Some(crate::encode::VideoCodec::Hevc),
-                                });
-                            }
-                            if let Some(ref pps_data) = cache.hevc_pps {
-                                prepend.push(EncodedPacket {
-                                    data: pps_data.clone(),
-                                    pts: first_pts,
-                                    dts: first_pts,
-                                    stream: StreamType::Video,
-                                    is_keyframe: false,
-                                    resolution: None,
-                                    codec: Some(crate::encode::VideoCodec::Hevc),
-                                });
-                            }
-                        }
-                    }
-
-                    if !prepend.is_empty() {
-                        let mut final_result = Vec::with_capacity(prepend.len() + result.len());
-                        final_result.extend(prepend);
-                        final_result.extend(result);
-                        return Ok(TrackedSnapshot::new(final_result, Arc::clone(inner)));
-                    }
-                }
-            }
-        }
+        let result = self.prepend_parameter_sets(result);
 
         // H1: Check if buffer was restarted between scanning and cloning.
         let gen_after = inner.restart_generation.load(Ordering::Acquire);
@@ -1324,6 +1608,861 @@ impl LockFreeReplayBuffer {
         Ok(TrackedSnapshot::new(result, Arc::clone(inner)))
     }
 
+    /// Gets a snapshot of the packets in `[start_pts, end_pts]` from every tier: RAM, pinned
+    /// ranges and the disk spill.
+    ///
+    /// The window is extended back to the last keyframe at or before `start_pts`; packets
+    /// before the first video keyframe found are dropped and cached parameter sets are
+    /// prepended as in [`Self::snapshot_from`]. Unlike `snapshot_from`, a concurrent restart
+    /// does not empty the result, since pinned packets survive restarts. When the disk spill
+    /// lost packets inside the window, the snapshot starts at the first keyframe after the
+    /// newest gap.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is inverted, outstanding snapshot bytes exceed the limit,
+    /// or spilled packets cannot be read.
+    pub fn snapshot_range(&self, start_pts: i64, end_pts: i64) -> BufferResult<TrackedSnapshot> {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        if end_pts < start_pts {
+            return Err(BufferError::InvalidRange { start_pts, end_pts });
+        }
+        self.check_outstanding_snapshot_limit("snapshot_range")?;
+
+        let aligned_start = self.keyframe_at_or_before(start_pts).unwrap_or(start_pts);
+        let end_exclusive = end_pts.saturating_add(1);
+
+        // Tiers are read RAM -> pins -> spill. Evicted packets only move forward through that
+        // order, so a packet evicted mid-read is still found; copies held by two tiers (spilled
+        // packets copied into a pin) are removed by the dedup below.
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let first_idx = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire));
+        let mut result = Vec::new();
+        for i in first_idx..write_idx {
+            let slot = &ring.slots[i & ring.mask];
+            if let Some(guard) = slot.try_lock_snapshot() {
+                if let Some(ref packet) = *guard {
+                    if packet.pts >= aligned_start && packet.pts < end_exclusive {
+                        result.push(packet.clone());
+                    }
+                }
+            }
+        }
+        result.extend(inner.pins.lock().packets_in(aligned_start, end_exclusive));
+        let mut newest_gap = None;
+        if let Some(spill) = inner.spill.as_ref() {
+            spill.sync();
+            let spilled = spill.read_unaligned(aligned_start, end_exclusive)?;
+            newest_gap = spilled.gaps.last().copied();
+            result.extend(spilled.packets);
+        }
+
+        result.sort_by_key(snapshot_sort_key);
+        result.dedup_by(|a, b| {
+            snapshot_sort_key(a) == snapshot_sort_key(b) && a.data.len() == b.data.len()
+        });
+        // Never mux across packets the spill lost: start at the first keyframe after the gap.
+        if let Some(gap) = newest_gap {
+            warn!(
+                "snapshot_range: replay disk spill lost packets at pts {}..={}; clip starts after the gap",
+                gap.start_pts, gap.end_pts
+            );
+            result.retain(|p| p.pts > gap.end_pts);
+        }
+        match result
+            .iter()
+            .find(|p| p.is_keyframe && matches!(p.stream, StreamType::Video))
+        {
+            Some(keyframe) => {
+                let keyframe_pts = keyframe.pts;
+                result.retain(|p| p.pts >= keyframe_pts);
+            }
+            None => result.clear(),
+        }
+
+        debug!(
+            "snapshot_range: {} packets for {}..={} (aligned start {})",
+            result.len(),
+            start_pts,
+            end_pts,
+            aligned_start
+        );
+        let result = self.prepend_parameter_sets(result);
+        Ok(TrackedSnapshot::new(result, Arc::clone(inner)))
+    }
+
+    /// Pins the packets in `[start_pts, end_pts]`, extended back to the preceding keyframe,
+    /// so they are exempt from eviction while the returned handle lives.
+    ///
+    /// `end_pts` may lie in the future; packets pushed later are retained as they leave the
+    /// ring. Spilled packets in the range are copied into memory now, since the spill tier
+    /// deletes whole segments; when the spill lost packets in the range, only the packets from
+    /// the first keyframe after the newest gap are copied and the pin is marked truncated.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::InvalidRange`] if `end_pts < start_pts`,
+    /// [`BufferError::PinBudgetExceeded`] if pinned ranges already hold the whole pin budget,
+    /// or an I/O error if spilled packets in the range cannot be read.
+    pub fn pin_range(&self, start_pts: i64, end_pts: i64) -> BufferResult<PinHandle> {
+        let inner = &self.inner;
+        if end_pts < start_pts {
+            return Err(BufferError::InvalidRange { start_pts, end_pts });
+        }
+        let current = inner.pinned_range_bytes.load(Ordering::Relaxed);
+        if current >= inner.max_pinned_bytes {
+            warn!(
+                "pin_range rejected: pinned bytes ({:.1}MB) exhaust pin budget ({:.1}MB)",
+                current as f64 / 1_048_576.0,
+                inner.max_pinned_bytes as f64 / 1_048_576.0
+            );
+            return Err(BufferError::PinBudgetExceeded {
+                current,
+                limit: inner.max_pinned_bytes,
+            });
+        }
+
+        let aligned_start = self.keyframe_at_or_before(start_pts).unwrap_or(start_pts);
+        let mut spilled = match inner.spill.as_ref() {
+            Some(spill) => {
+                spill.sync();
+                spill.read_unaligned(aligned_start, end_pts.saturating_add(1))?
+            }
+            None => SpillRead::default(),
+        };
+        let newest_gap = spilled.cut_after_gaps();
+
+        let mut pins = inner.pins.lock();
+        let id = pins.insert(aligned_start, end_pts);
+        if let Some(gap) = newest_gap {
+            warn!(
+                "Replay disk spill lost packets at pts {}..={}; pinned range starts after the gap",
+                gap.start_pts, gap.end_pts
+            );
+            if let Some(range) = pins.range_mut(id) {
+                range.truncated = true;
+            }
+        }
+        for packet in spilled.packets {
+            // An older overlapping pin already copied or retained this packet.
+            if pins.is_covered_by_other(id, packet.pts) {
+                continue;
+            }
+            if !self.try_charge_pin_budget(packet.data.len()) {
+                warn!(
+                    "Pin budget ({:.1}MB) exhausted while copying spilled packets; pinned range starts late",
+                    inner.max_pinned_bytes as f64 / 1_048_576.0
+                );
+                if let Some(range) = pins.range_mut(id) {
+                    range.truncated = true;
+                }
+                break;
+            }
+            if let Some(range) = pins.range_mut(id) {
+                range.push(packet);
+            }
+        }
+        inner.pin_count.store(pins.len(), Ordering::Release);
+        drop(pins);
+
+        debug!(
+            "Pinned replay range {}..={} (requested start {}) as pin {}",
+            aligned_start, end_pts, start_pts, id
+        );
+        Ok(PinHandle::new(self.clone(), id, aligned_start, end_pts))
+    }
+
+    /// Releases pin `id`, freeing the bytes no other live pin still covers.
+    pub(super) fn release_pin(&self, id: u64) {
+        let inner = &self.inner;
+        let released = {
+            let mut pins = inner.pins.lock();
+            let released = pins.remove(id);
+            inner.pin_count.store(pins.len(), Ordering::Release);
+            released
+        };
+        inner
+            .pinned_range_bytes
+            .fetch_sub(released, Ordering::Relaxed);
+        debug!(
+            "Released pin {} ({:.1}MB freed)",
+            id,
+            released as f64 / 1_048_576.0
+        );
+    }
+
+    pub(super) fn pin_retained_bytes(&self, id: u64) -> usize {
+        self.inner.pins.lock().retained_bytes(id)
+    }
+
+    /// Hands a packet evicted from the ring to the pin covering it, or else to the disk spill.
+    ///
+    /// Every eviction path (memory, wrap, duration, resize) goes through here so the spilled
+    /// timeline has no holes other than the gaps the spill tier records itself.
+    fn dispose_evicted(&self, packet: EncodedPacket, newest_pts: i64) {
+        if let Some(packet) = self.retain_for_pins(packet) {
+            if let Some(spill) = self.inner.spill.as_ref() {
+                spill.offer(packet, newest_pts);
+            }
+        }
+    }
+
+    /// Moves a packet leaving the ring into the pin store when a live pin covers it.
+    ///
+    /// Returns the packet back when no pin wants it or the pin budget is exhausted.
+    fn retain_for_pins(&self, packet: EncodedPacket) -> Option<EncodedPacket> {
+        let inner = &self.inner;
+        if inner.pin_count.load(Ordering::Acquire) == 0 {
+            return Some(packet);
+        }
+        let mut pins = inner.pins.lock();
+        let Some(range) = pins.covering_mut(packet.pts) else {
+            return Some(packet);
+        };
+        if !self.try_charge_pin_budget(packet.data.len()) {
+            if !range.truncated {
+                range.truncated = true;
+                warn!(
+                    "Pin budget ({:.1}MB) exhausted; packets in pinned range are evicted normally",
+                    inner.max_pinned_bytes as f64 / 1_048_576.0
+                );
+            }
+            return Some(packet);
+        }
+        range.push(packet);
+        None
+    }
+
+    /// Adds `bytes` to the pinned total if it stays within the pin budget.
+    /// Callers hold the `pins` lock, so the check and the add cannot race.
+    fn try_charge_pin_budget(&self, bytes: usize) -> bool {
+        let inner = &self.inner;
+        let pinned = inner.pinned_range_bytes.load(Ordering::Relaxed);
+        if pinned.saturating_add(bytes) > inner.max_pinned_bytes {
+            return false;
+        }
+        inner.pinned_range_bytes.fetch_add(bytes, Ordering::Relaxed);
+        true
+    }
+
+    /// PTS of the last video keyframe at or before `pts` in any tier.
+    fn keyframe_at_or_before(&self, pts: i64) -> Option<i64> {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let first_idx = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire));
+
+        let mut best = inner.pins.lock().keyframe_at_or_before(pts);
+        for i in first_idx..write_idx {
+            let slot = &ring.slots[i & ring.mask];
+            if let Some(guard) = slot.try_lock_snapshot() {
+                if let Some(ref packet) = *guard {
+                    if packet.is_keyframe
+                        && matches!(packet.stream, StreamType::Video)
+                        && packet.pts <= pts
+                    {
+                        best = best.max(Some(packet.pts));
+                    }
+                }
+            }
+        }
+        let spilled = inner
+            .spill
+            .as_ref()
+            .and_then(|spill| spill.keyframe_at_or_before(pts));
+        best.max(spilled)
+    }
+
+    /// Rejects a new snapshot when in-flight snapshots already pin too many bytes.
+    fn check_outstanding_snapshot_limit(&self, caller: &str) -> BufferResult<()> {
+        let inner = &self.inner;
+        let max_memory_bytes = inner.max_memory_bytes.load(Ordering::Relaxed);
+        let current_outstanding = inner.outstanding_snapshot_bytes.load(Ordering::Relaxed);
+        let max_outstanding = max_memory_bytes.min(MAX_OUTSTANDING_SNAPSHOT_BYTES);
+        if current_outstanding >= max_outstanding {
+            warn!(
+                "{} rejected: outstanding snapshot bytes ({:.1}MB) exceeds limit ({:.1}MB)",
+                caller,
+                current_outstanding as f64 / 1_048_576.0,
+                max_outstanding as f64 / 1_048_576.0
+            );
+            return Err(BufferError::SnapshotLimitExceeded {
+                current: current_outstanding,
+                limit: max_outstanding,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the cached parameter sets (SPS/PPS or VPS/SPS/PPS) as standalone video packets
+    /// stamped with `pts`, in decoder order. Empty until the encoder has emitted them.
+    pub(crate) fn parameter_set_packets(&self, pts: i64) -> Vec<EncodedPacket> {
+        let cache = self.inner.param_cache.lock();
+        let (codec, param_sets) = match cache.codec_kind {
+            CodecKind::H264 => (
+                crate::encode::VideoCodec::H264,
+                [cache.h264_sps.as_ref(), cache.h264_pps.as_ref(), None],
+            ),
+            CodecKind::Hevc => (
+                crate::encode::VideoCodec::Hevc,
+                [
+                    cache.hevc_vps.as_ref(),
+                    cache.hevc_sps.as_ref(),
+                    cache.hevc_pps.as_ref(),
+                ],
+            ),
+        };
+        param_sets
+            .into_iter()
+            .flatten()
+            .map(|data| EncodedPacket {
+                data: data.clone(),
+                pts,
+                dts: pts,
+                stream: StreamType::Video,
+                is_keyframe: false,
+                resolution: None,
+                codec: Some(codec),
+            })
+            .collect()
+    }
+
+    /// Clones the packets written since `cursor` (a value previously returned by this method,
+    /// or 0), in write order, and returns them with the cursor for the next call.
+    ///
+    /// Packets already evicted or overwritten are skipped. When the ring has been cleared or
+    /// restarted since `cursor` was taken (the write index went backwards or the restart
+    /// generation changed), the third value is `true` and the packets start from the oldest
+    /// buffered one.
+    pub(crate) fn packets_since(
+        &self,
+        cursor: RingCursor,
+    ) -> (Vec<EncodedPacket>, RingCursor, bool) {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let generation = inner.restart_generation.load(Ordering::Acquire);
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let reset = generation != cursor.generation || write_idx < cursor.next_idx;
+        let from_idx = if reset { 0 } else { cursor.next_idx };
+
+        let first_idx = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire))
+            .max(from_idx);
+        let mut packets = Vec::with_capacity(write_idx.saturating_sub(first_idx));
+        for i in first_idx..write_idx {
+            let slot = &ring.slots[i & ring.mask];
+            if let Some(guard) = slot.try_lock_snapshot() {
+                if let Some(ref packet) = *guard {
+                    packets.push(packet.clone());
+                }
+            }
+        }
+
+        let next = RingCursor {
+            next_idx: write_idx,
+            generation,
+        };
+        (packets, next, reset)
+    }
+
+    /// Starts a chunked read of the packets in `[start_pts, end_pts]` (see [`super::chunks`]).
+    ///
+    /// The window starts at the last keyframe at or before `start_pts` (or the first one
+    /// after it) and, without `end_pts`, ends at the newest packet buffered now. Each chunk
+    /// holds at most `max_chunk_bytes` of packet data, or a single packet when one is larger.
+    ///
+    /// A window that starts before the oldest packet in RAM is read from the disk spill
+    /// first. Returns `None` when no tier that can be streamed holds a keyframe to start
+    /// from, or when the window reaches into pinned ranges; use [`Self::snapshot_range`] for
+    /// those.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::InvalidRange`] if `end_pts < start_pts`.
+    pub fn snapshot_chunks(
+        &self,
+        start_pts: i64,
+        end_pts: Option<i64>,
+        max_chunk_bytes: usize,
+    ) -> BufferResult<Option<SnapshotChunks>> {
+        let inner = &self.inner;
+        if let Some(end_pts) = end_pts {
+            if end_pts < start_pts {
+                return Err(BufferError::InvalidRange { start_pts, end_pts });
+            }
+        }
+        let end_pts = end_pts.unwrap_or(i64::MAX);
+
+        let ring = inner.ring.read_recursive();
+        let generation = inner.restart_generation.load(Ordering::Acquire);
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let first_idx = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire));
+        let ring_cursor = RingCursor {
+            next_idx: first_idx,
+            generation,
+        };
+
+        let mut last_keyframe_at_or_before: Option<(usize, i64)> = None;
+        let mut first_keyframe_at_or_after: Option<(usize, i64)> = None;
+        for i in first_idx..write_idx {
+            let slot = &ring.slots[i & ring.mask];
+            if let Some(guard) = slot.try_lock_snapshot() {
+                if let Some(ref packet) = *guard {
+                    if packet.is_keyframe && matches!(packet.stream, StreamType::Video) {
+                        if packet.pts <= start_pts {
+                            last_keyframe_at_or_before = Some((i, packet.pts));
+                        } else if first_keyframe_at_or_after.is_none() {
+                            first_keyframe_at_or_after = Some((i, packet.pts));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let ram_start = last_keyframe_at_or_before.or(first_keyframe_at_or_after);
+        let Some((start_idx, keyframe_pts)) = ram_start.filter(|&(_, keyframe_pts)| {
+            // Otherwise the RAM keyframe is after `start_pts`, but an older tier has one at
+            // or before it.
+            keyframe_pts <= start_pts || self.keyframe_at_or_before(start_pts).is_none()
+        }) else {
+            drop(ring);
+            return Ok(inner.spill.as_ref().and_then(|spill| {
+                self.spill_chunks(
+                    spill,
+                    start_pts,
+                    end_pts,
+                    max_chunk_bytes,
+                    ring_cursor,
+                    write_idx,
+                )
+            }));
+        };
+
+        // Audio pushed before the keyframe but timed after it belongs to the clip, as in
+        // `snapshot_from`.
+        let mut lead_in = Vec::new();
+        for i in first_idx..start_idx {
+            let slot = &ring.slots[i & ring.mask];
+            if let Some(guard) = slot.try_lock_snapshot() {
+                if let Some(ref packet) = *guard {
+                    if !matches!(packet.stream, StreamType::Video)
+                        && packet.pts >= keyframe_pts
+                        && packet.pts <= end_pts
+                    {
+                        lead_in.push(packet.clone());
+                    }
+                }
+            }
+        }
+
+        debug!(
+            "snapshot_chunks: indices {}..{} from keyframe pts {} (requested start {}), {} byte chunks",
+            start_idx, write_idx, keyframe_pts, start_pts, max_chunk_bytes
+        );
+        Ok(Some(SnapshotChunks::new(
+            self.clone(),
+            RingCursor {
+                next_idx: start_idx,
+                generation,
+            },
+            write_idx,
+            keyframe_pts,
+            end_pts,
+            max_chunk_bytes,
+            lead_in,
+        )))
+    }
+
+    /// Starts a chunked read of a window that begins in the disk spill (see
+    /// [`super::chunks`]).
+    ///
+    /// Returns `None` when the spill has no keyframe for the window, or a pinned range holds
+    /// packets of it: evicted packets of the window would then go to the pin instead of the
+    /// spill.
+    fn spill_chunks(
+        &self,
+        spill: &DiskSpillTier,
+        start_pts: i64,
+        end_pts: i64,
+        max_chunk_bytes: usize,
+        ring_cursor: RingCursor,
+        end_idx: usize,
+    ) -> Option<SnapshotChunks> {
+        spill.sync();
+        let (keyframe_pts, spill_cursor) = spill.chunk_start(start_pts)?;
+        if keyframe_pts > end_pts || self.pins_hold_packets_in(keyframe_pts, end_pts) {
+            return None;
+        }
+
+        debug!(
+            "snapshot_chunks: disk spill from keyframe pts {} (requested start {}), then indices {}..{}, {} byte chunks",
+            keyframe_pts, start_pts, ring_cursor.next_idx, end_idx, max_chunk_bytes
+        );
+        Some(SnapshotChunks::from_spill(
+            self.clone(),
+            spill_cursor,
+            ring_cursor,
+            end_idx,
+            keyframe_pts,
+            end_pts,
+            max_chunk_bytes,
+        ))
+    }
+
+    fn pins_hold_packets_in(&self, start_pts: i64, end_pts: i64) -> bool {
+        let inner = &self.inner;
+        inner.pin_count.load(Ordering::Acquire) > 0
+            && inner.pins.lock().has_packets_in(start_pts, end_pts)
+    }
+
+    /// Reads the next spilled chunk of a chunked read, keeping the packets `keep` accepts.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::ReadOverrun`] if the spill lost packets after `keyframe_pts` or
+    /// deleted the segment at `cursor`, or an I/O error if a segment cannot be read.
+    pub(super) fn read_spill_chunk(
+        &self,
+        cursor: SpillCursor,
+        keyframe_pts: i64,
+        max_bytes: usize,
+        keep: impl FnMut(StreamType, i64) -> bool,
+    ) -> BufferResult<SpillChunk> {
+        let Some(spill) = self.inner.spill.as_ref() else {
+            return Err(BufferError::ReadOverrun { lost: 0 });
+        };
+        let chunk = spill.read_chunk(cursor, max_bytes, keep)?;
+        if let Some(gap) = spill.newest_gap().filter(|gap| gap.end_pts >= keyframe_pts) {
+            warn!(
+                "Replay disk spill lost packets at pts {}..={} during a chunked read",
+                gap.start_pts, gap.end_pts
+            );
+            return Err(BufferError::ReadOverrun {
+                lost: chunk.packets.len(),
+            });
+        }
+        Ok(chunk)
+    }
+
+    /// Picks where a chunked read continues in the ring after its spill part: the oldest
+    /// packet still in RAM (or `cursor`, if that is newer), once the spill writer has drained
+    /// so every packet evicted before it can be read from the spill.
+    ///
+    /// The eviction frontier only moves after the evicted packet was handed to the spill, so
+    /// nothing before the returned cursor can still be on its way there.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::ReadOverrun`] if the buffer was cleared or restarted, a pinned
+    /// range took packets of the window, or the spill writer did not drain in time.
+    pub(super) fn spill_handoff(
+        &self,
+        cursor: RingCursor,
+        keyframe_pts: i64,
+        end_pts: i64,
+        end_idx: usize,
+    ) -> BufferResult<RingCursor> {
+        let inner = &self.inner;
+        let frontier = inner.evict_frontier.load(Ordering::Acquire);
+        if inner.restart_generation.load(Ordering::Acquire) != cursor.generation
+            || inner.write_idx.load(Ordering::Acquire) < cursor.next_idx
+        {
+            return Err(BufferError::ReadOverrun {
+                lost: end_idx.saturating_sub(cursor.next_idx),
+            });
+        }
+        if self.pins_hold_packets_in(keyframe_pts, end_pts) {
+            return Err(BufferError::ReadOverrun {
+                lost: frontier.saturating_sub(cursor.next_idx),
+            });
+        }
+        if !inner.spill.as_ref().is_some_and(DiskSpillTier::sync) {
+            return Err(BufferError::ReadOverrun {
+                lost: frontier.saturating_sub(cursor.next_idx),
+            });
+        }
+        Ok(RingCursor {
+            next_idx: frontier.max(cursor.next_idx),
+            generation: cursor.generation,
+        })
+    }
+
+    /// Clones the packets in `[start_pts, end_pts]` written between `cursor` and `end_idx`,
+    /// in write order, stopping before the packet that would take the chunk past
+    /// `max_bytes`.
+    ///
+    /// Unlike snapshots, every slot is locked with a blocking lock: a skipped packet would
+    /// leave a hole in the clip. A slot the producer has claimed but not yet filled ends the
+    /// window.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::ReadOverrun`] if a packet in the window was evicted or
+    /// overwritten before it was read, or the buffer was cleared or restarted.
+    pub(super) fn read_chunk(
+        &self,
+        cursor: RingCursor,
+        end_idx: usize,
+        start_pts: i64,
+        end_pts: i64,
+        max_bytes: usize,
+    ) -> BufferResult<ChunkRead> {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        if inner.restart_generation.load(Ordering::Acquire) != cursor.generation
+            || write_idx < cursor.next_idx
+        {
+            return Err(BufferError::ReadOverrun {
+                lost: end_idx.saturating_sub(cursor.next_idx),
+            });
+        }
+        let first_idx = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire));
+        if first_idx > cursor.next_idx {
+            return Err(BufferError::ReadOverrun {
+                lost: first_idx.min(end_idx) - cursor.next_idx,
+            });
+        }
+
+        let mut packets = Vec::new();
+        let mut bytes = 0usize;
+        let mut next_idx = cursor.next_idx;
+        let mut end_reached = false;
+        while next_idx < end_idx {
+            let slot = &ring.slots[next_idx & ring.mask];
+            let guard = slot.packet.lock();
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq <= next_idx {
+                end_reached = true;
+                break;
+            }
+            let Some(ref packet) = *guard else {
+                return Err(BufferError::ReadOverrun { lost: 1 });
+            };
+            if seq != next_idx + 1 {
+                return Err(BufferError::ReadOverrun { lost: 1 });
+            }
+            if packet.pts >= start_pts && packet.pts <= end_pts {
+                let len = packet.data.len();
+                if !packets.is_empty() && bytes.saturating_add(len) > max_bytes {
+                    break;
+                }
+                bytes += len;
+                packets.push(packet.clone());
+            }
+            next_idx += 1;
+        }
+        if next_idx >= end_idx {
+            end_reached = true;
+        }
+
+        Ok(ChunkRead {
+            packets,
+            cursor: RingCursor {
+                next_idx,
+                generation: cursor.generation,
+            },
+            end_reached,
+        })
+    }
+
+    /// Wraps `packets` so their bytes count as outstanding snapshot memory while held.
+    pub(super) fn track_snapshot(&self, packets: Vec<EncodedPacket>) -> TrackedSnapshot {
+        TrackedSnapshot::new(packets, Arc::clone(&self.inner))
+    }
+
+    /// Starts a live tail at the current write head (see [`super::tail`]).
+    pub fn subscribe(&self) -> ReplaySubscription {
+        self.inner.subscriber_count.fetch_add(1, Ordering::AcqRel);
+        let cursor = RingCursor {
+            next_idx: self.inner.write_idx.load(Ordering::Acquire),
+            generation: self.inner.restart_generation.load(Ordering::Acquire),
+        };
+        ReplaySubscription::new(self.clone(), cursor)
+    }
+
+    pub(super) fn release_subscriber(&self) {
+        self.inner.subscriber_count.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Clones the packets written at or after `cursor`, stopping at the first slot the
+    /// producer has claimed but not yet filled.
+    ///
+    /// Unlike [`Self::packets_since`], every index between the cursor and the returned cursor
+    /// is accounted for: a packet that was evicted or overwritten before it could be read is
+    /// counted in [`TailRead::lost`] instead of being skipped silently. After a clear or
+    /// restart the read starts from the oldest buffered packet and `lost` is 0.
+    pub(super) fn read_tail(&self, cursor: RingCursor) -> TailRead {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let generation = inner.restart_generation.load(Ordering::Acquire);
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let reset = generation != cursor.generation || write_idx < cursor.next_idx;
+        let from_idx = if reset { 0 } else { cursor.next_idx };
+
+        let first_idx = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire))
+            .max(from_idx);
+        let mut lost = if reset { 0 } else { first_idx - from_idx };
+        let mut packets = Vec::with_capacity(write_idx.saturating_sub(first_idx));
+        let mut next_idx = first_idx;
+        while next_idx < write_idx {
+            let slot = &ring.slots[next_idx & ring.mask];
+            // A held lock means the producer is filling or evicting this slot; retry next read.
+            let Some(guard) = slot.try_lock_snapshot() else {
+                break;
+            };
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq <= next_idx {
+                break;
+            }
+            match *guard {
+                Some(ref packet) if seq == next_idx + 1 => packets.push(packet.clone()),
+                _ => lost += 1,
+            }
+            next_idx += 1;
+        }
+
+        TailRead {
+            packets,
+            cursor: RingCursor {
+                next_idx,
+                generation,
+            },
+            lost,
+            reset,
+        }
+    }
+
+    /// Blocks until a packet at `cursor` has been written, the buffer was reset, or
+    /// `deadline` passes. Returns `false` on timeout.
+    pub(super) fn wait_for_tail(&self, cursor: RingCursor, deadline: Instant) -> bool {
+        let inner = &self.inner;
+        let mut guard = inner.tail_lock.lock();
+        loop {
+            if self.tail_ready(cursor) {
+                return true;
+            }
+            if inner
+                .tail_signal
+                .wait_until(&mut guard, deadline)
+                .timed_out()
+            {
+                return self.tail_ready(cursor);
+            }
+        }
+    }
+
+    fn tail_ready(&self, cursor: RingCursor) -> bool {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        inner.restart_generation.load(Ordering::Acquire) != cursor.generation
+            || inner.write_idx.load(Ordering::Acquire) < cursor.next_idx
+            || inner.evict_frontier.load(Ordering::Acquire) > cursor.next_idx
+            || ring.slots[cursor.next_idx & ring.mask]
+                .seq
+                .load(Ordering::Acquire)
+                > cursor.next_idx
+    }
+
+    /// Wakes subscribers blocked in [`Self::wait_for_tail`].
+    fn notify_subscribers(&self) {
+        let inner = &self.inner;
+        if inner.subscriber_count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let _guard = inner.tail_lock.lock();
+        inner.tail_signal.notify_all();
+    }
+
+    /// Prepends cached parameter sets (SPS/PPS or VPS/SPS/PPS) when the first video packet
+    /// of a sorted snapshot is a keyframe that does not carry them in-band.
+    pub(super) fn prepend_parameter_sets(&self, result: Vec<EncodedPacket>) -> Vec<EncodedPacket> {
+        let Some(first_vid) = result
+            .iter()
+            .find(|p| matches!(p.stream, StreamType::Video))
+        else {
+            return result;
+        };
+        let first_data = first_vid.data.as_ref();
+        let first_nal_is_vps = hevc_nal_type(first_data) == Some(32);
+        let first_nal_is_sps = h264_nal_type(first_data) == Some(7);
+        if !first_vid.is_keyframe || first_nal_is_vps || first_nal_is_sps {
+            return result;
+        }
+
+        let prepend = self.parameter_set_packets(first_vid.pts);
+        if prepend.is_empty() {
+            return result;
+        }
+        let mut final_result = Vec::with_capacity(prepend.len() + result.len());
+        final_result.extend(prepend);
+        final_result.extend(result);
+        final_result
+    }
+
+    /// Collects a snapshot whose window starts before the oldest packet still in RAM.
+    ///
+    /// RAM packets are cloned first, then the spill writer is drained and the spilled packets
+    /// older than the first RAM packet are read back. Any packet evicted between the two steps
+    /// either lands in the spill after `sync` or is filtered out by the exclusive end bound,
+    /// so nothing is duplicated.
+    ///
+    /// Returns `None` when the spill has no keyframe to start from, so the caller can fall
+    /// back to the RAM-only path.
+    fn collect_with_spill(
+        &self,
+        spill: &DiskSpillTier,
+        start_pts: i64,
+        first_idx: usize,
+        write_idx: usize,
+    ) -> BufferResult<Option<Vec<EncodedPacket>>> {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let mut ram_packets: Vec<EncodedPacket> =
+            Vec::with_capacity(write_idx.saturating_sub(first_idx));
+        for i in first_idx..write_idx {
+            let slot = &ring.slots[i & ring.mask];
+            if let Some(packet_guard) = slot.try_lock_snapshot() {
+                if let Some(ref packet) = *packet_guard {
+                    ram_packets.push(packet.clone());
+                }
+            }
+        }
+        let ram_first_pts = ram_packets.iter().map(|p| p.pts).min().unwrap_or(i64::MAX);
+
+        spill.sync();
+        let spilled = spill.read_range(start_pts, ram_first_pts)?.packets;
+        if !spilled
+            .iter()
+            .any(|p| p.is_keyframe && matches!(p.stream, StreamType::Video))
+        {
+            return Ok(None);
+        }
+
+        debug!(
+            "snapshot_from: {} spilled packets + {} RAM packets for start_pts={}",
+            spilled.len(),
+            ram_packets.len(),
+            start_pts
+        );
+        let mut result = spilled;
+        result.extend(ram_packets);
+        result.sort_by_key(snapshot_sort_key);
+        Ok(Some(result))
+    }
+
     /// Clears all packets from the buffer.
     ///
     /// Resets the write index and clears all packet slots.
@@ -1334,6 +2473,7 @@ impl LockFreeReplayBuffer {
     /// Should be called when no producer is actively pushing packets.
     pub fn clear(&self) {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
 
         // Log state before clear
         let bytes_before = inner.total_bytes.load(Ordering::Relaxed);
@@ -1348,12 +2488,16 @@ impl LockFreeReplayBuffer {
             bytes_before as f64 / 1_048_576.0
         );
 
-        for i in 0..inner.capacity {
-            let slot = &inner.slots[i];
+        for i in 0..ring.capacity {
+            let slot = &ring.slots[i];
             // Use blocking lock with poison recovery — try_lock could silently
             // skip slots if a snapshot consumer holds the lock, leaking packets.
             let mut packet_guard = slot.packet.lock();
-            *packet_guard = None;
+            slot.seq.store(0, Ordering::Release);
+            slot.next_pts.store(NO_PTS, Ordering::Release);
+            if let Some(old) = packet_guard.take() {
+                let _ = self.retain_for_pins(old);
+            }
         }
 
         inner.write_idx.store(0, Ordering::Release);
@@ -1361,6 +2505,9 @@ impl LockFreeReplayBuffer {
         inner.total_bytes.store(0, Ordering::Release);
         inner.keyframe_count.store(0, Ordering::Release);
         inner.newest_pts.store(0, Ordering::Release);
+        for counters in &inner.streams {
+            counters.reset();
+        }
         inner.has_wrapped.store(false, Ordering::Release);
         inner.restart_generation.store(0, Ordering::Release);
         inner.param_cache_complete.store(false, Ordering::Release);
@@ -1369,6 +2516,10 @@ impl LockFreeReplayBuffer {
             .store(0, Ordering::Release);
         inner.first_video_kind.store(0, Ordering::Release);
         inner.first_video_idx.store(0, Ordering::Release);
+        if let Some(spill) = inner.spill.as_ref() {
+            spill.clear();
+        }
+        self.notify_subscribers();
 
         let cache = inner.param_cache.lock();
         debug!(
@@ -1392,6 +2543,7 @@ impl LockFreeReplayBuffer {
     /// a fresh buffer).
     pub fn restart(&self) {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
 
         // Log state before restart
         let bytes_before = inner.total_bytes.load(Ordering::Relaxed);
@@ -1407,10 +2559,14 @@ impl LockFreeReplayBuffer {
         );
 
         // Clear all slots (blocking locks to ensure no packet remains)
-        for i in 0..inner.capacity {
-            let slot = &inner.slots[i];
+        for i in 0..ring.capacity {
+            let slot = &ring.slots[i];
             let mut packet_guard = slot.packet.lock();
-            *packet_guard = None;
+            slot.seq.store(0, Ordering::Release);
+            slot.next_pts.store(NO_PTS, Ordering::Release);
+            if let Some(old) = packet_guard.take() {
+                let _ = self.retain_for_pins(old);
+            }
         }
 
         // Reset indexes and stats
@@ -1419,6 +2575,9 @@ impl LockFreeReplayBuffer {
         inner.total_bytes.store(0, Ordering::Release);
         inner.keyframe_count.store(0, Ordering::Release);
         inner.newest_pts.store(0, Ordering::Release);
+        for counters in &inner.streams {
+            counters.reset();
+        }
         inner.has_wrapped.store(false, Ordering::Release);
         inner.restart_generation.fetch_add(1, Ordering::Release);
         inner.param_cache_complete.store(false, Ordering::Release);
@@ -1427,6 +2586,10 @@ impl LockFreeReplayBuffer {
             .store(0, Ordering::Release);
         inner.first_video_kind.store(0, Ordering::Release);
         inner.first_video_idx.store(0, Ordering::Release);
+        if let Some(spill) = inner.spill.as_ref() {
+            spill.clear();
+        }
+        self.notify_subscribers();
 
         // O1: Preserve param_cache during restart so the next keyframe immediately
         // produces save-able clips — no need to wait for parameter set collection again.
@@ -1491,18 +2654,22 @@ impl LockFreeReplayBuffer {
     /// - `packet_count`: Number of packets in buffer
     /// - `keyframe_count`: Number of keyframes
     /// - `memory_usage_percent`: Percentage of max memory used
+    /// - `disk_bytes` / `disk_packet_count`: Contents of the disk spill tier, if enabled
+    /// - `pinned_range_bytes` / `pinned_range_count`: Memory retained by live pins
     #[must_use]
     pub fn stats(&self) -> BufferStats {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let max_memory_bytes = inner.max_memory_bytes.load(Ordering::Relaxed);
         let write_idx = inner.write_idx.load(Ordering::Acquire);
         // Acquire ordering ensures we see latest counter values from producer thread
         let total_bytes = inner.total_bytes.load(Ordering::Acquire);
         let keyframe_count = inner.keyframe_count.load(Ordering::Acquire);
         let evict_frontier = inner.evict_frontier.load(Ordering::Acquire);
-        let actual_start = write_idx.saturating_sub(inner.capacity).max(evict_frontier);
+        let actual_start = write_idx.saturating_sub(ring.capacity).max(evict_frontier);
 
-        let memory_usage_percent = if inner.max_memory_bytes > 0 {
-            (total_bytes as f32 / inner.max_memory_bytes as f32) * 100.0
+        let memory_usage_percent = if max_memory_bytes > 0 {
+            (total_bytes as f32 / max_memory_bytes as f32) * 100.0
         } else {
             0.0
         };
@@ -1513,23 +2680,29 @@ impl LockFreeReplayBuffer {
                 "Buffer stats: write_idx={}, evict_frontier={}, capacity={}, packets={}, keyframes={}, bytes={:.1}MB/{:.1}MB ({:.0}%)",
                 write_idx,
                 evict_frontier,
-                inner.capacity,
+                ring.capacity,
                 write_idx.saturating_sub(actual_start),
                 keyframe_count,
                 total_bytes as f64 / 1_048_576.0,
-                inner.max_memory_bytes as f64 / 1_048_576.0,
+                max_memory_bytes as f64 / 1_048_576.0,
                 memory_usage_percent
             );
         }
 
+        let spill_oldest_pts = inner.spill.as_ref().and_then(DiskSpillTier::oldest_pts);
+        let (disk_bytes, disk_packet_count) =
+            inner.spill.as_ref().map_or((0, 0), DiskSpillTier::usage);
+
         let duration_secs = if write_idx >= 2 {
             // Read actual oldest packet's PTS from its slot (evict_frontier aware).
-            let oldest_slot = &inner.slots[actual_start & inner.mask];
-            let oldest_pts = if let Some(g) = oldest_slot.packet.try_lock() {
+            let oldest_slot = &ring.slots[actual_start & ring.mask];
+            let ram_oldest_pts = if let Some(g) = oldest_slot.packet.try_lock() {
                 g.as_ref().map_or(0, |p| p.pts)
             } else {
                 0
             };
+            let oldest_pts =
+                spill_oldest_pts.map_or(ram_oldest_pts, |spilled| spilled.min(ram_oldest_pts));
             let newest = inner.newest_pts.load(Ordering::Relaxed);
             let qpc_freq = qpc_frequency() as f64;
             if newest > oldest_pts && qpc_freq > 0.0 {
@@ -1543,45 +2716,122 @@ impl LockFreeReplayBuffer {
 
         let packet_count = write_idx.saturating_sub(actual_start);
 
+        let qpc_freq = qpc_frequency() as f64;
+
         BufferStats {
             duration_secs,
             total_bytes,
             packet_count,
             keyframe_count,
             memory_usage_percent: memory_usage_percent.min(100.0),
+            disk_bytes,
+            disk_packet_count,
+            pinned_range_bytes: inner.pinned_range_bytes.load(Ordering::Relaxed),
+            pinned_range_count: inner.pin_count.load(Ordering::Relaxed),
+            video: self.stream_stats(StreamType::Video, qpc_freq),
+            system_audio: self.stream_stats(StreamType::SystemAudio, qpc_freq),
+            microphone: self.stream_stats(StreamType::Microphone, qpc_freq),
+        }
+    }
+
+    /// Breakdown of `stream` from the counters kept on push and eviction and the spill index.
+    fn stream_stats(&self, stream: StreamType, qpc_freq: f64) -> StreamStats {
+        let counters = &self.inner.streams[stream_slot(stream)];
+        let spilled = self
+            .inner
+            .spill
+            .as_ref()
+            .map(|spill| spill.stream_usage(stream))
+            .unwrap_or_default();
+        let ram_oldest = StreamCounters::pts(counters.oldest_pts.load(Ordering::Acquire));
+        let ram_newest = StreamCounters::pts(counters.newest_pts.load(Ordering::Acquire));
+        // The spill only holds packets evicted from RAM, so it has the oldest ones.
+        let oldest_pts = spilled.oldest_pts.or(ram_oldest);
+        let newest_pts = ram_newest.or(spilled.newest_pts);
+        let span_secs = match (oldest_pts, newest_pts) {
+            (Some(oldest), Some(newest)) if newest > oldest && qpc_freq > 0.0 => {
+                (newest - oldest) as f64 / qpc_freq
+            }
+            _ => 0.0,
+        };
+        StreamStats {
+            bytes: counters.bytes.load(Ordering::Acquire),
+            packet_count: counters.packet_count.load(Ordering::Acquire),
+            disk_bytes: spilled.bytes,
+            disk_packet_count: spilled.packet_count,
+            oldest_pts,
+            newest_pts,
+            span_secs,
         }
     }
 
-    /// Returns the number of bytes currently pinned by in-flight snapshots.
+    /// Gaps of `stream` over the packets in RAM, walking the live slots without blocking
+    /// the producer. Slots being written or evicted concurrently are skipped.
+    pub fn stream_gaps(&self, stream: StreamType) -> StreamGaps {
+        let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
+        let write_idx = inner.write_idx.load(Ordering::Acquire);
+        let start = write_idx
+            .saturating_sub(ring.capacity)
+            .max(inner.evict_frontier.load(Ordering::Acquire));
+        let mut gaps = StreamGapsAccumulator::default();
+        for idx in start..write_idx {
+            let Some(guard) = ring.slots[idx & ring.mask].packet.try_lock() else {
+                continue;
+            };
+            if let Some(packet) = guard.as_ref().filter(|packet| packet.stream == stream) {
+                gaps.push(packet.pts);
+            }
+        }
+        gaps.finish(qpc_frequency() as f64)
+    }
+
+    /// Returns the number of bytes currently held by in-flight snapshots and chunks.
     ///
     /// This is memory that has been cloned from the ring but is still being
     /// processed (e.g., being encoded to disk). The ring's `total_bytes` doesn't
-    /// account for these pinned allocations, so this method provides visibility
-    /// into the actual RSS beyond the ring's configured budget.
+    /// account for these allocations, so this method provides visibility
+    /// into the actual RSS beyond the ring's configured budget. Packets kept alive by
+    /// pinned ranges are counted separately, in [`BufferStats::pinned_range_bytes`].
     ///
     /// **Note:** This uses `Bytes::len()` (logical slice length), not the backing
     /// allocation size. If packets are views into larger `BytesMut` pages, this
     /// will underreport actual RSS — it's a lower bound, not exact.
     #[must_use]
-    pub fn pinned_bytes(&self) -> usize {
+    pub fn outstanding_snapshot_bytes(&self) -> usize {
         self.inner
             .outstanding_snapshot_bytes
             .load(Ordering::Relaxed)
     }
 
+    /// Returns the PTS of the oldest buffered packet, including the disk spill tier.
     #[must_use]
     pub fn oldest_pts(&self) -> Option<i64> {
+        let ram_oldest = self.ram_oldest_pts();
+        let spill_oldest = self
+            .inner
+            .spill
+            .as_ref()
+            .and_then(DiskSpillTier::oldest_pts);
+        match (ram_oldest, spill_oldest) {
+            (Some(ram), Some(spilled)) => Some(ram.min(spilled)),
+            (ram, spilled) => ram.or(spilled),
+        }
+    }
+
+    fn ram_oldest_pts(&self) -> Option<i64> {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
         let write_idx = inner.write_idx.load(Ordering::Acquire);
         if write_idx == 0 {
             return None;
         }
 
-        let oldest_idx = write_idx.saturating_sub(inner.capacity);
+        let oldest_idx = write_idx.saturating_sub(ring.capacity);
         let evict_frontier = inner.evict_frontier.load(Ordering::Acquire);
         let oldest_idx = oldest_idx.max(evict_frontier);
-        let slot_idx = oldest_idx & inner.mask;
-        let slot = &inner.slots[slot_idx];
+        let slot_idx = oldest_idx & ring.mask;
+        let slot = &ring.slots[slot_idx];
 
         if let Some(packet_guard) = slot.packet.try_lock() {
             packet_guard.as_ref().map(|p| p.pts)
@@ -1590,17 +2840,28 @@ impl LockFreeReplayBuffer {
         }
     }
 
+    /// PTS of the newest packet of `stream` in RAM, from the counters kept on push.
+    #[must_use]
+    pub fn newest_stream_pts(&self, stream: StreamType) -> Option<i64> {
+        StreamCounters::pts(
+            self.inner.streams[stream_slot(stream)]
+                .newest_pts
+                .load(Ordering::Acquire),
+        )
+    }
+
     #[must_use]
     pub fn newest_pts(&self) -> Option<i64> {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
         let write_idx = inner.write_idx.load(Ordering::Acquire);
         if write_idx == 0 {
             return None;
         }
 
         let newest_idx = write_idx - 1;
-        let slot_idx = newest_idx & inner.mask;
-        let slot = &inner.slots[slot_idx];
+        let slot_idx = newest_idx & ring.mask;
+        let slot = &ring.slots[slot_idx];
 
         if let Some(packet_guard) = slot.packet.try_lock() {
             packet_guard.as_ref().map(|p| p.pts)
@@ -1612,16 +2873,17 @@ impl LockFreeReplayBuffer {
     #[must_use]
     pub fn first_packet_resolution(&self) -> Option<(u32, u32)> {
         let inner = &self.inner;
+        let ring = inner.ring.read_recursive();
         let write_idx = inner.write_idx.load(Ordering::Acquire);
         if write_idx == 0 {
             return None;
         }
 
-        let start_idx = write_idx.saturating_sub(inner.capacity);
+        let start_idx = write_idx.saturating_sub(ring.capacity);
         let evict_frontier = inner.evict_frontier.load(Ordering::Acquire);
         let start_idx = start_idx.max(evict_frontier);
-        let slot_idx = start_idx & inner.mask;
-        let slot = &inner.slots[slot_idx];
+        let slot_idx = start_idx & ring.mask;
+        let slot = &ring.slots[slot_idx];
 
         if let Some(packet_guard) = slot.packet.try_lock() {
             packet_guard.as_ref().and_then(|p| p.resolution)
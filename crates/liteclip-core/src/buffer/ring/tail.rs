@@ -0,0 +1,161 @@
+//! Live tail subscriptions
+//!
+//! A [`ReplaySubscription`] follows the write head of a replay buffer and yields packets as
+//! they are pushed, for live previews, streaming outputs and continuous recorders. Each
+//! subscription keeps its own cursor. The producer never waits for readers, so a reader
+//! that falls a ring's worth behind loses packets.
+//!
+//! # Overrun
+//!
+//! A read is an overrun when packets between the cursor and the oldest buffered packet were
+//! evicted or overwritten before the reader got to them. The subscription then drops
+//! everything up to the next video keyframe, so decoders resume on a clean GOP, and reports
+//! the number of packets lost (including those dropped while resynchronising) in
+//! [`TailBatch::skipped`]. A cleared or restarted buffer is reported in [`TailBatch::reset`]
+//! and resynchronised the same way.
+//!
+//! A new subscription starts at the write head and also waits for the next keyframe; those
+//! packets are not counted as skipped. Whenever delivery resumes at a keyframe that does not
+//! carry its parameter sets in-band, the cached SPS/PPS (or VPS/SPS/PPS) are prepended.
+
+use std::time::{Duration, Instant};
+
+use crate::encode::{EncodedPacket, StreamType};
+
+use super::spmc_ring::{LockFreeReplayBuffer, RingCursor};
+
+/// Result of [`LockFreeReplayBuffer::read_tail`].
+pub(crate) struct TailRead {
+    pub(crate) packets: Vec<EncodedPacket>,
+    pub(crate) cursor: RingCursor,
+    /// Indices between the old and new cursor whose packet was gone before it was read.
+    pub(crate) lost: usize,
+    pub(crate) reset: bool,
+}
+
+/// Packets delivered by one [`ReplaySubscription`] read.
+#[derive(Debug, Default)]
+pub struct TailBatch {
+    /// New packets in push order.
+    pub packets: Vec<EncodedPacket>,
+    /// Packets this reader missed by falling behind, plus those dropped while waiting for
+    /// the keyframe to resume from. Non-zero means this read was an overrun.
+    pub skipped: usize,
+    /// The buffer was cleared or restarted since the previous read.
+    pub reset: bool,
+}
+
+impl TailBatch {
+    /// Whether the reader fell behind and lost packets.
+    #[must_use]
+    pub fn is_overrun(&self) -> bool {
+        self.skipped > 0
+    }
+}
+
+/// Whether a subscription is delivering or waiting for a keyframe to resume from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TailState {
+    Live,
+    /// `count_skipped` is false only for the initial sync of a new subscription.
+    AwaitingKeyframe {
+        count_skipped: bool,
+    },
+}
+
+/// Cursor-based reader that yields packets as they are pushed into a replay buffer.
+///
+/// Created by [`SharedReplayBuffer::subscribe`](super::SharedReplayBuffer::subscribe). See the
+/// [module docs](self) for overrun semantics.
+pub struct ReplaySubscription {
+    buffer: LockFreeReplayBuffer,
+    cursor: RingCursor,
+    state: TailState,
+}
+
+impl ReplaySubscription {
+    pub(super) fn new(buffer: LockFreeReplayBuffer, cursor: RingCursor) -> Self {
+        Self {
+            buffer,
+            cursor,
+            state: TailState::AwaitingKeyframe {
+                count_skipped: false,
+            },
+        }
+    }
+
+    /// Returns whatever was pushed since the previous read, without blocking.
+    pub fn try_recv(&mut self) -> TailBatch {
+        let read = self.buffer.read_tail(self.cursor);
+        self.cursor = read.cursor;
+
+        let mut batch = TailBatch {
+            packets: read.packets,
+            skipped: read.lost,
+            reset: read.reset,
+        };
+        if batch.reset {
+            self.state = TailState::AwaitingKeyframe {
+                count_skipped: false,
+            };
+        } else if read.lost > 0 {
+            self.state = TailState::AwaitingKeyframe {
+                count_skipped: true,
+            };
+        }
+
+        if let TailState::AwaitingKeyframe { count_skipped } = self.state {
+            match batch
+                .packets
+                .iter()
+                .position(|p| p.is_keyframe && matches!(p.stream, StreamType::Video))
+            {
+                Some(keyframe) => {
+                    if count_skipped {
+                        batch.skipped += keyframe;
+                    }
+                    batch.packets.drain(..keyframe);
+                    batch.packets = self.buffer.prepend_parameter_sets(batch.packets);
+                    self.state = TailState::Live;
+                }
+                None => {
+                    if count_skipped {
+                        batch.skipped += batch.packets.len();
+                    }
+                    batch.packets.clear();
+                }
+            }
+        }
+        batch
+    }
+
+    /// Waits up to `timeout` for new packets, an overrun or a reset, then reads like
+    /// [`Self::try_recv`].
+    ///
+    /// The returned batch may still be empty on timeout, or while the subscription is
+    /// waiting for a keyframe.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> TailBatch {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let batch = self.try_recv();
+            if !batch.packets.is_empty() || batch.is_overrun() || batch.reset {
+                return batch;
+            }
+            if !self.buffer.wait_for_tail(self.cursor, deadline) {
+                return self.try_recv();
+            }
+        }
+    }
+
+    /// Whether the subscription is waiting for a keyframe before delivering packets.
+    #[must_use]
+    pub fn is_resyncing(&self) -> bool {
+        self.state != TailState::Live
+    }
+}
+
+impl Drop for ReplaySubscription {
+    fn drop(&mut self) {
+        self.buffer.release_subscriber();
+    }
+}
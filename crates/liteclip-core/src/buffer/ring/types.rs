@@ -3,20 +3,59 @@
 //! SharedReplayBuffer wraps LockFreeReplayBuffer for thread-safe access.
 
 use crate::buffer::BufferResult;
-use crate::encode::EncodedPacket;
+use crate::encode::{EncodedPacket, StreamType};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use super::spmc_ring::{LockFreeReplayBuffer, TrackedSnapshot};
+use super::chunks::SnapshotChunks;
+use super::pin::PinHandle;
+use super::spmc_ring::{LockFreeReplayBuffer, RingCursor, TrackedSnapshot};
+use super::tail::ReplaySubscription;
 
 /// Thread-safe wrapper around LockFreeReplayBuffer
 #[derive(Clone)]
 pub struct SharedReplayBuffer {
     inner: LockFreeReplayBuffer,
+    last_saved: Arc<parking_lot::Mutex<Option<SavedClip>>>,
+    /// FFmpeg name of the video encoder feeding the buffer, recorded in saved clips.
+    video_encoder: Arc<parking_lot::Mutex<Option<String>>>,
+    /// Bookmarks taken while recording, keyed by PTS.
+    bookmarks: Arc<parking_lot::Mutex<BTreeMap<i64, Bookmark>>>,
+}
+
+/// Most bookmarks kept at once; the oldest are dropped beyond this.
+pub const MAX_BOOKMARKS: usize = 256;
+
+/// The most recent clip saved from a buffer, used to detect overlapping saves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedClip {
+    /// File the clip was written to.
+    pub path: PathBuf,
+    /// First PTS of the saved window.
+    pub start_pts: i64,
+    /// Last PTS of the saved window.
+    pub end_pts: i64,
+}
+
+/// A moment marked while recording, written as a chapter into clips that contain it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    /// PTS of the newest packet when the bookmark was taken.
+    pub pts: i64,
+    /// Optional user-supplied label.
+    pub label: Option<String>,
 }
 
 impl SharedReplayBuffer {
     pub fn new(config: &crate::config::Config) -> BufferResult<Self> {
         let inner = LockFreeReplayBuffer::new(config)?;
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            last_saved: Arc::new(parking_lot::Mutex::new(None)),
+            video_encoder: Arc::new(parking_lot::Mutex::new(None)),
+            bookmarks: Arc::new(parking_lot::Mutex::new(BTreeMap::new())),
+        })
     }
 
     pub fn push_batch(&self, packets: impl IntoIterator<Item = EncodedPacket>) {
@@ -35,21 +74,120 @@ impl SharedReplayBuffer {
         self.inner.snapshot_from(start_pts)
     }
 
+    /// Snapshots `[start_pts, end_pts]` across RAM, pinned ranges and the disk spill.
+    pub fn snapshot_range(&self, start_pts: i64, end_pts: i64) -> BufferResult<TrackedSnapshot> {
+        self.inner.snapshot_range(start_pts, end_pts)
+    }
+
+    /// Reads `[start_pts, end_pts]` from RAM and the disk spill in chunks of at most
+    /// `max_chunk_bytes`; `None` when the window reaches into pinned ranges (see
+    /// [`LockFreeReplayBuffer::snapshot_chunks`]).
+    pub fn snapshot_chunks(
+        &self,
+        start_pts: i64,
+        end_pts: Option<i64>,
+        max_chunk_bytes: usize,
+    ) -> BufferResult<Option<SnapshotChunks>> {
+        self.inner
+            .snapshot_chunks(start_pts, end_pts, max_chunk_bytes)
+    }
+
+    /// Keeps `[start_pts, end_pts]` (plus the preceding keyframe) exempt from eviction while
+    /// the returned handle lives.
+    pub fn pin_range(&self, start_pts: i64, end_pts: i64) -> BufferResult<PinHandle> {
+        self.inner.pin_range(start_pts, end_pts)
+    }
+
+    /// Starts a live tail that yields packets as they are pushed, beginning at the next
+    /// video keyframe.
+    pub fn subscribe(&self) -> ReplaySubscription {
+        self.inner.subscribe()
+    }
+
+    /// Applies new replay duration / memory limit settings, keeping the buffered replay.
+    pub fn resize(&self, config: &crate::config::Config) {
+        self.inner.resize(config);
+    }
+
     pub fn clear(&self) {
         self.inner.clear();
+        *self.last_saved.lock() = None;
+        self.bookmarks.lock().clear();
     }
 
     /// Completely resets the replay buffer including parameter caches.
     pub fn restart(&self) {
         self.inner.restart();
+        *self.last_saved.lock() = None;
+        self.bookmarks.lock().clear();
+    }
+
+    /// The last clip recorded with [`Self::record_saved_clip`], cleared by restart/clear.
+    pub fn last_saved_clip(&self) -> Option<SavedClip> {
+        self.last_saved.lock().clone()
+    }
+
+    pub fn record_saved_clip(&self, clip: SavedClip) {
+        *self.last_saved.lock() = Some(clip);
+    }
+
+    /// The video encoder set with [`Self::set_video_encoder`]; kept across restarts.
+    pub fn video_encoder(&self) -> Option<String> {
+        self.video_encoder.lock().clone()
+    }
+
+    pub fn set_video_encoder(&self, encoder: Option<String>) {
+        *self.video_encoder.lock() = encoder;
+    }
+
+    /// Bookmarks the newest buffered packet; `None` while the buffer is empty.
+    ///
+    /// Bookmarks older than the buffered replay are dropped, and at most [`MAX_BOOKMARKS`]
+    /// are kept. A blank label is stored as no label.
+    pub fn add_bookmark(&self, label: Option<String>) -> Option<Bookmark> {
+        let pts = self.inner.newest_pts()?;
+        let label = label
+            .map(|label| label.trim().to_string())
+            .filter(|label| !label.is_empty());
+        let bookmark = Bookmark { pts, label };
+
+        let mut bookmarks = self.bookmarks.lock();
+        if let Some(oldest) = self.inner.oldest_pts() {
+            *bookmarks = bookmarks.split_off(&oldest);
+        }
+        bookmarks.insert(pts, bookmark.clone());
+        while bookmarks.len() > MAX_BOOKMARKS {
+            bookmarks.pop_first();
+        }
+        Some(bookmark)
+    }
+
+    /// Bookmarks within `[start_pts, end_pts]`, oldest first; cleared by restart/clear.
+    pub fn bookmarks_in(&self, start_pts: i64, end_pts: i64) -> Vec<Bookmark> {
+        if start_pts > end_pts {
+            return Vec::new();
+        }
+        self.bookmarks
+            .lock()
+            .range(start_pts..=end_pts)
+            .map(|(_, bookmark)| bookmark.clone())
+            .collect()
     }
 
     pub fn stats(&self) -> BufferStats {
         self.inner.stats()
     }
 
-    pub fn pinned_bytes(&self) -> usize {
-        self.inner.pinned_bytes()
+    /// Gaps of `stream` over the packets in RAM. Walks the ring, so unlike [`Self::stats`]
+    /// it is meant for occasional diagnostics rather than hot paths.
+    pub fn stream_gaps(&self, stream: StreamType) -> StreamGaps {
+        self.inner.stream_gaps(stream)
+    }
+
+    /// Bytes held by in-flight snapshots and chunks; see
+    /// [`LockFreeReplayBuffer::outstanding_snapshot_bytes`].
+    pub fn outstanding_snapshot_bytes(&self) -> usize {
+        self.inner.outstanding_snapshot_bytes()
     }
 
     pub fn oldest_pts(&self) -> Option<i64> {
@@ -60,6 +198,10 @@ impl SharedReplayBuffer {
         self.inner.newest_pts()
     }
 
+    pub fn newest_stream_pts(&self, stream: StreamType) -> Option<i64> {
+        self.inner.newest_stream_pts(stream)
+    }
+
     pub fn snapshot_first_packet_resolution(&self) -> Option<(u32, u32)> {
         self.inner.first_packet_resolution()
     }
@@ -67,6 +209,17 @@ impl SharedReplayBuffer {
     pub fn has_keyframe(&self) -> bool {
         self.inner.has_keyframe()
     }
+
+    pub(crate) fn packets_since(
+        &self,
+        cursor: RingCursor,
+    ) -> (Vec<EncodedPacket>, RingCursor, bool) {
+        self.inner.packets_since(cursor)
+    }
+
+    pub(crate) fn parameter_set_packets(&self, pts: i64) -> Vec<EncodedPacket> {
+        self.inner.parameter_set_packets(pts)
+    }
 }
 
 /// Statistics about the buffer state
@@ -82,4 +235,118 @@ pub struct BufferStats {
     pub keyframe_count: usize,
     /// Memory usage percentage (0-100)
     pub memory_usage_percent: f32,
+    /// Bytes held in the disk spill tier (0 when disabled)
+    pub disk_bytes: u64,
+    /// Packets held in the disk spill tier
+    pub disk_packet_count: usize,
+    /// Bytes retained by pinned ranges outside the ring (counted against the pin budget).
+    /// Unlike [`SharedReplayBuffer::outstanding_snapshot_bytes`], this excludes snapshots in
+    /// flight.
+    pub pinned_range_bytes: usize,
+    /// Number of live pinned ranges
+    pub pinned_range_count: usize,
+    /// Video packets held in RAM and the disk spill
+    pub video: StreamStats,
+    /// System audio packets held in RAM and the disk spill
+    pub system_audio: StreamStats,
+    /// Microphone packets held in RAM and the disk spill
+    pub microphone: StreamStats,
+}
+
+impl BufferStats {
+    /// Breakdown for one stream.
+    #[must_use]
+    pub fn stream(&self, stream: StreamType) -> &StreamStats {
+        match stream {
+            StreamType::Video => &self.video,
+            StreamType::SystemAudio => &self.system_audio,
+            StreamType::Microphone => &self.microphone,
+        }
+    }
+}
+
+/// Per-stream breakdown of the buffered packets, kept up to date as packets are pushed and
+/// evicted. The RAM and disk spill tiers are reported separately, like in [`BufferStats`];
+/// the PTS range and span cover both.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StreamStats {
+    /// Bytes held in RAM for this stream
+    pub bytes: usize,
+    /// Number of packets held in RAM for this stream
+    pub packet_count: usize,
+    /// Bytes of this stream's records in the disk spill tier
+    pub disk_bytes: u64,
+    /// Number of this stream's packets in the disk spill tier
+    pub disk_packet_count: usize,
+    /// Oldest buffered PTS of this stream
+    pub oldest_pts: Option<i64>,
+    /// Newest buffered PTS of this stream
+    pub newest_pts: Option<i64>,
+    /// Time between the oldest and newest packet, in seconds
+    pub span_secs: f64,
+}
+
+/// Discontinuities of one stream over the packets held in RAM, from
+/// [`SharedReplayBuffer::stream_gaps`].
+///
+/// A gap is an interval between consecutive packets of the stream that exceeds
+/// [`STREAM_GAP_FACTOR`] times the expected interval. The expected interval is the median
+/// interval over the buffered window, so it adapts to the frame rate and audio period
+/// actually delivered rather than the configured ones. The time a gap is missing is its
+/// interval minus the expected one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StreamGaps {
+    /// Median interval between consecutive packets, in seconds
+    pub expected_interval_secs: f64,
+    /// Number of gaps
+    pub gap_count: usize,
+    /// Time missing across all gaps, in seconds
+    pub gap_secs: f64,
+    /// Time missing in the longest gap, in seconds
+    pub longest_gap_secs: f64,
+}
+
+/// An interval counts as a gap once it exceeds this multiple of the expected interval.
+pub const STREAM_GAP_FACTOR: f64 = 1.5;
+
+/// Builds [`StreamGaps`] from one stream's packets in push order.
+#[derive(Default)]
+pub(crate) struct StreamGapsAccumulator {
+    last_pts: Option<i64>,
+    intervals: Vec<i64>,
+}
+
+impl StreamGapsAccumulator {
+    pub(crate) fn push(&mut self, pts: i64) {
+        if let Some(last) = self.last_pts {
+            // Out-of-order packets (e.g. B-frame PTS) carry no continuity information.
+            if pts > last {
+                self.intervals.push(pts - last);
+            }
+        }
+        self.last_pts = Some(pts);
+    }
+
+    pub(crate) fn finish(mut self, qpc_freq: f64) -> StreamGaps {
+        let mut gaps = StreamGaps::default();
+        if self.intervals.is_empty() || qpc_freq <= 0.0 {
+            return gaps;
+        }
+        let to_secs = |ticks: i64| ticks as f64 / qpc_freq;
+
+        let mid = self.intervals.len() / 2;
+        let expected = *self.intervals.select_nth_unstable(mid).1;
+        gaps.expected_interval_secs = to_secs(expected);
+
+        let threshold = expected as f64 * STREAM_GAP_FACTOR;
+        for &interval in &self.intervals {
+            if interval as f64 > threshold {
+                let missing = to_secs(interval - expected);
+                gaps.gap_count += 1;
+                gaps.gap_secs += missing;
+                gaps.longest_gap_secs = gaps.longest_gap_secs.max(missing);
+            }
+        }
+        gaps
+    }
 }
@@ -316,6 +316,16 @@ impl AudioMixer {
     ) {
         output.clear();
 
+        // Separate tracks are never paired: each stream keeps its own packets and timing.
+        if self.config.separate_tracks {
+            for packet in system_packet.iter().chain(mic_packet.iter()) {
+                if let Some(track_packet) = self.process_track_packet(packet) {
+                    output.push(track_packet);
+                }
+            }
+            return;
+        }
+
         // Add received packets to their respective buffers (sorted by PTS)
         if let Some(packet) = system_packet {
             Self::insert_sorted(&mut self.system_packets, packet, &mut self.evicted_packets);
@@ -477,16 +487,7 @@ impl AudioMixer {
         let mic_gain = mic_user_gain * mic_balance_gain;
         let _master_gain = (self.config.master_volume as f32 / 100.0).clamp(0.0, 2.0);
 
-        // Calculate balance (stereo only)
-        let (left_balance, right_balance) = if self.config.balance < 0 {
-            // Left bias
-            let bias = (self.config.balance as f32 / -100.0).clamp(0.0, 1.0);
-            (1.0, 1.0 - bias)
-        } else {
-            // Right bias
-            let bias = (self.config.balance as f32 / 100.0).clamp(0.0, 1.0);
-            (1.0 - bias, 1.0)
-        };
+        let (left_balance, right_balance) = self.balance_gains();
 
         // Fused SIMD mixing + quantization pass: process 4 samples (2 stereo frames)
         // per f32x4 lane, writing directly to output_buffer. This eliminates the
@@ -574,6 +575,58 @@ impl AudioMixer {
         ))
     }
 
+    /// Left/right gains for the configured stereo balance.
+    fn balance_gains(&self) -> (f32, f32) {
+        if self.config.balance < 0 {
+            // Left bias
+            let bias = (self.config.balance as f32 / -100.0).clamp(0.0, 1.0);
+            (1.0, 1.0 - bias)
+        } else {
+            // Right bias
+            let bias = (self.config.balance as f32 / 100.0).clamp(0.0, 1.0);
+            (1.0 - bias, 1.0)
+        }
+    }
+
+    /// Applies the stream's volume and the balance to a single packet without mixing it, for
+    /// `separate_tracks`. The output keeps the input's stream type and timestamps.
+    fn process_track_packet(&mut self, packet: &EncodedPacket) -> Option<EncodedPacket> {
+        decode_packet_into(packet, &mut self.system_decode_buf);
+        if self.system_decode_buf.is_empty() {
+            return None;
+        }
+
+        let gain = match packet.stream {
+            crate::encode::StreamType::Microphone => {
+                (self.config.mic_volume as f32 / 100.0).clamp(0.0, 4.0)
+            }
+            _ => (self.config.system_volume as f32 / 100.0).clamp(0.0, 2.0),
+        };
+        let (left_balance, right_balance) = self.balance_gains();
+
+        self.output_buffer.clear();
+        self.output_buffer.reserve(self.system_decode_buf.len() * 2);
+        for (idx, &sample) in self.system_decode_buf.iter().enumerate() {
+            let balance = if idx % 2 == 0 {
+                left_balance
+            } else {
+                right_balance
+            };
+            let scaled = (sample as f32 / PCM_SCALE) * gain * balance;
+            let quantized = (scaled.clamp(-1.0, 1.0) * (PCM_SCALE - 1.0)).round() as i16;
+            self.output_buffer
+                .extend_from_slice(&quantized.to_le_bytes());
+        }
+
+        Some(EncodedPacket::new(
+            self.output_buffer.split().freeze(),
+            packet.pts,
+            packet.pts,
+            false,
+            packet.stream,
+        ))
+    }
+
     /// Handle packets that have timed out waiting for a matching packet
     fn handle_timeouts(&mut self) {
         let current_pts = self
@@ -845,6 +898,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_separate_tracks_keep_streams_apart() {
+        let mut config = Config::default().audio;
+        config.separate_tracks = true;
+        config.system_volume = 50;
+        config.mic_volume = 100;
+        let mut mixer = AudioMixer::new(&config);
+
+        let mut system_data = BytesMut::with_capacity(4);
+        system_data.extend_from_slice(&8000i16.to_le_bytes());
+        system_data.extend_from_slice(&(-8000i16).to_le_bytes());
+        let system_packet = EncodedPacket::new(
+            system_data.freeze(),
+            100,
+            100,
+            false,
+            crate::encode::StreamType::SystemAudio,
+        );
+
+        let mut mic_data = BytesMut::with_capacity(4);
+        mic_data.extend_from_slice(&3000i16.to_le_bytes());
+        mic_data.extend_from_slice(&3000i16.to_le_bytes());
+        let mic_packet = EncodedPacket::new(
+            mic_data.freeze(),
+            120,
+            120,
+            false,
+            crate::encode::StreamType::Microphone,
+        );
+
+        let result = mixer.mix_packets(Some(system_packet), Some(mic_packet));
+        assert_eq!(result.len(), 2);
+        assert!(matches!(
+            result[0].stream,
+            crate::encode::StreamType::SystemAudio
+        ));
+        assert_eq!(result[0].pts, 100);
+        assert!(matches!(
+            result[1].stream,
+            crate::encode::StreamType::Microphone
+        ));
+        assert_eq!(result[1].pts, 120);
+
+        let samples = |packet: &EncodedPacket| -> Vec<i16> {
+            packet
+                .data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                .collect()
+        };
+        assert_eq!(samples(&result[0]), vec![4000, -4000]);
+        assert_eq!(samples(&result[1]), vec![3000, 3000]);
+        assert_eq!(mixer.pending_packet_counts(), (0, 0));
+    }
+
     #[test]
     fn test_normalization_prefers_boosting_quiet_source() {
         let mut config = Config::default().audio;
@@ -9,8 +9,10 @@
 //! 🤖 Generated with [SplitRS](https://github.com/cool-japan/splitrs)
 
 use super::functions::{
-    default_audio_normalization_enabled, default_audio_target_lufs, default_balance,
-    default_master_volume, default_mic_device, default_mic_volume, default_system_volume,
+    default_audio_normalization_enabled, default_audio_target_lufs, default_audio_track_language,
+    default_balance, default_buffer_audio_codec, default_clip_audio_bitrate_kbps,
+    default_clip_audio_codec, default_false, default_master_volume, default_mic_device,
+    default_mic_track_title, default_mic_volume, default_system_track_title, default_system_volume,
     default_true, default_true_peak_limit_dbtp, default_true_peak_limiter_enabled,
 };
 use super::types::AudioConfig;
@@ -30,6 +32,13 @@ impl Default for AudioConfig {
             target_lufs: default_audio_target_lufs(),
             true_peak_limiter_enabled: default_true_peak_limiter_enabled(),
             true_peak_limit_dbtp: default_true_peak_limit_dbtp(),
+            buffer_codec: default_buffer_audio_codec(),
+            clip_codec: default_clip_audio_codec(),
+            clip_bitrate_kbps: default_clip_audio_bitrate_kbps(),
+            separate_tracks: default_false(),
+            system_track_title: default_system_track_title(),
+            mic_track_title: default_mic_track_title(),
+            track_language: default_audio_track_language(),
         }
     }
 }
@@ -0,0 +1,27 @@
+//! # ExportConfig - Trait Implementations
+//!
+//! This module contains trait implementations for `ExportConfig`.
+//!
+//! ## Implemented Traits
+//!
+//! - `Default`
+//!
+//! 🤖 Generated with [SplitRS](https://github.com/cool-japan/splitrs)
+
+use super::functions::{
+    default_audio_target_lufs, default_true, default_true_peak_limit_dbtp,
+    default_true_peak_limiter_enabled,
+};
+use super::types::ExportConfig;
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            custom_presets: Vec::new(),
+            normalize_loudness: default_true(),
+            target_lufs: default_audio_target_lufs(),
+            true_peak_limiter_enabled: default_true_peak_limiter_enabled(),
+            true_peak_limit_dbtp: default_true_peak_limit_dbtp(),
+        }
+    }
+}
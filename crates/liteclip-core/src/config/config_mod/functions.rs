@@ -2,7 +2,10 @@
 //!
 //! 🤖 Generated with [SplitRS](https://github.com/cool-japan/splitrs)
 
-use super::types::{EncoderType, QualityPreset, RateControl, Resolution};
+use super::types::{
+    BufferAudioCodec, ClipAudioCodec, ClipSaveMode, EncoderType, QualityPreset, RateControl,
+    Resolution, SaveContainer,
+};
 
 pub const MAX_FRAMERATE: u32 = 240;
 pub const RECOMMENDED_BUFFER_HEADROOM_PERCENT: u64 = 135;
@@ -12,6 +15,13 @@ pub const ESTIMATED_MIC_AUDIO_BITRATE_BPS: u64 = 128_000;
 pub const REPLAY_MEMORY_LIMIT_AUTO_MB: u32 = 0;
 pub const MIN_REPLAY_MEMORY_LIMIT_MB: u32 = 1;
 pub const MAX_REPLAY_MEMORY_LIMIT_MB: u32 = 4096;
+/// Upper bound for the replay disk spill budget; 0 disables the spill tier.
+pub const MAX_REPLAY_DISK_LIMIT_GB: u32 = 512;
+/// Upper bound for the memory budget held by pinned replay ranges.
+pub const MAX_REPLAY_PIN_BUDGET_MB: u32 = 4096;
+/// Bounds for the AAC/Opus bitrate of saved clips.
+pub const MIN_CLIP_AUDIO_BITRATE_KBPS: u32 = 32;
+pub const MAX_CLIP_AUDIO_BITRATE_KBPS: u32 = 512;
 
 pub(crate) fn default_true() -> bool {
     true
@@ -24,6 +34,12 @@ pub(crate) fn default_false() -> bool {
 pub(super) fn default_replay_duration() -> u32 {
     30
 }
+pub(super) fn default_replay_disk_limit_gb() -> u32 {
+    0
+}
+pub(super) fn default_replay_pin_budget_mb() -> u32 {
+    256
+}
 pub(super) fn default_save_directory() -> String {
     dirs::video_dir()
         .map(|p| p.join("liteclip").to_string_lossy().to_string())
@@ -47,6 +63,21 @@ pub(super) fn default_bitrate() -> u32 {
 pub(super) fn default_encoder() -> EncoderType {
     EncoderType::Auto
 }
+pub(super) fn default_buffer_audio_codec() -> BufferAudioCodec {
+    BufferAudioCodec::Pcm
+}
+pub(super) fn default_clip_audio_codec() -> ClipAudioCodec {
+    ClipAudioCodec::Aac
+}
+pub(super) fn default_clip_audio_bitrate_kbps() -> u32 {
+    192
+}
+pub(super) fn default_clip_save_mode() -> ClipSaveMode {
+    ClipSaveMode::Restart
+}
+pub(super) fn default_save_container() -> SaveContainer {
+    SaveContainer::Mp4
+}
 pub(super) fn default_quality_preset() -> QualityPreset {
     QualityPreset::Performance
 }
@@ -90,6 +121,15 @@ pub(super) fn default_true_peak_limiter_enabled() -> bool {
 pub(super) fn default_true_peak_limit_dbtp() -> i8 {
     -1
 }
+pub(super) fn default_system_track_title() -> String {
+    "Game Audio".to_string()
+}
+pub(super) fn default_mic_track_title() -> String {
+    "Microphone".to_string()
+}
+pub(super) fn default_audio_track_language() -> String {
+    "und".to_string()
+}
 pub(super) fn default_hotkey_save() -> String {
     "Ctrl+Shift+S".to_string()
 }
@@ -99,6 +139,9 @@ pub(super) fn default_hotkey_toggle() -> String {
 pub(super) fn default_hotkey_gallery() -> String {
     "Ctrl+Shift+G".to_string()
 }
+pub(super) fn default_hotkey_bookmark() -> String {
+    "Ctrl+Shift+B".to_string()
+}
 pub(super) fn default_gpu_index() -> u32 {
     0
 }
@@ -129,6 +172,10 @@ mod tests {
         assert_eq!(config.audio.target_lufs, -16);
         assert!(config.audio.true_peak_limiter_enabled);
         assert_eq!(config.audio.true_peak_limit_dbtp, -1);
+        assert!(config.export.normalize_loudness);
+        assert_eq!(config.export.target_lufs, -16);
+        assert!(config.export.true_peak_limiter_enabled);
+        assert_eq!(config.export.true_peak_limit_dbtp, -1);
     }
     #[test]
     fn test_validate_quality_value_clamps() {
@@ -210,4 +257,16 @@ mod tests {
         config.validate();
         assert_eq!(config.advanced.memory_limit_mb, MAX_REPLAY_MEMORY_LIMIT_MB);
     }
+
+    #[test]
+    fn test_validate_disk_limit_clamps_upper_bound() {
+        let mut config = Config::default();
+        assert_eq!(config.general.replay_disk_limit_gb, 0);
+        config.general.replay_disk_limit_gb = MAX_REPLAY_DISK_LIMIT_GB + 1;
+        config.validate();
+        assert_eq!(
+            config.general.replay_disk_limit_gb,
+            MAX_REPLAY_DISK_LIMIT_GB
+        );
+    }
 }
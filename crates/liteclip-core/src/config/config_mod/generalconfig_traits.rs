@@ -9,7 +9,8 @@
 //! 🤖 Generated with [SplitRS](https://github.com/cool-japan/splitrs)
 
 use super::functions::{
-    default_false, default_replay_duration, default_save_directory, default_true,
+    default_clip_save_mode, default_false, default_replay_disk_limit_gb, default_replay_duration,
+    default_replay_pin_budget_mb, default_save_container, default_save_directory, default_true,
 };
 use super::types::GeneralConfig;
 
@@ -17,12 +18,18 @@ impl Default for GeneralConfig {
     fn default() -> Self {
         Self {
             replay_duration_secs: default_replay_duration(),
+            replay_disk_limit_gb: default_replay_disk_limit_gb(),
+            replay_pin_budget_mb: default_replay_pin_budget_mb(),
+            crash_recovery_enabled: default_false(),
+            clip_save_mode: default_clip_save_mode(),
+            save_container: default_save_container(),
             save_directory: default_save_directory(),
             auto_start_with_windows: default_true(),
             start_minimised: default_true(),
             notifications: default_true(),
             auto_detect_game: default_true(),
             generate_clip_thumbnail: default_true(),
+            write_metadata_sidecar: default_false(),
             use_software_encoder: default_false(),
         }
     }
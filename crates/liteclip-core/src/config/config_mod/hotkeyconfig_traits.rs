@@ -8,7 +8,9 @@
 //!
 //! 🤖 Generated with [SplitRS](https://github.com/cool-japan/splitrs)
 
-use super::functions::{default_hotkey_gallery, default_hotkey_save, default_hotkey_toggle};
+use super::functions::{
+    default_hotkey_bookmark, default_hotkey_gallery, default_hotkey_save, default_hotkey_toggle,
+};
 use super::types::HotkeyConfig;
 
 impl Default for HotkeyConfig {
@@ -17,6 +19,7 @@ impl Default for HotkeyConfig {
             save_clip: default_hotkey_save(),
             toggle_recording: default_hotkey_toggle(),
             open_gallery: default_hotkey_gallery(),
+            bookmark: default_hotkey_bookmark(),
         }
     }
 }
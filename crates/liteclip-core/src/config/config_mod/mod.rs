@@ -5,6 +5,7 @@
 
 pub mod advancedconfig_traits;
 pub mod audioconfig_traits;
+pub mod exportconfig_traits;
 pub(crate) mod functions;
 pub mod generalconfig_traits;
 pub mod hotkeyconfig_traits;
@@ -13,8 +14,10 @@ pub mod videoconfig_traits;
 
 // Embedder-facing API: configuration types and shared limits (not serde plumbing).
 pub use functions::{
-    ESTIMATED_MIC_AUDIO_BITRATE_BPS, ESTIMATED_SYSTEM_AUDIO_BITRATE_BPS, MAX_FRAMERATE,
-    MAX_REPLAY_MEMORY_LIMIT_MB, MIN_REPLAY_MEMORY_LIMIT_MB, RECOMMENDED_BUFFER_BASE_OVERHEAD_MB,
+    ESTIMATED_MIC_AUDIO_BITRATE_BPS, ESTIMATED_SYSTEM_AUDIO_BITRATE_BPS,
+    MAX_CLIP_AUDIO_BITRATE_KBPS, MAX_FRAMERATE, MAX_REPLAY_DISK_LIMIT_GB,
+    MAX_REPLAY_MEMORY_LIMIT_MB, MAX_REPLAY_PIN_BUDGET_MB, MIN_CLIP_AUDIO_BITRATE_KBPS,
+    MIN_REPLAY_MEMORY_LIMIT_MB, RECOMMENDED_BUFFER_BASE_OVERHEAD_MB,
     RECOMMENDED_BUFFER_HEADROOM_PERCENT, REPLAY_MEMORY_LIMIT_AUTO_MB,
 };
 pub use types::*;
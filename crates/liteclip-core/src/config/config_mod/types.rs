@@ -7,18 +7,24 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::output::ExportPreset;
 use crate::paths::AppDirs;
 
 use super::functions::{
-    default_audio_normalization_enabled, default_audio_target_lufs, default_balance,
-    default_bitrate, default_encoder, default_false, default_framerate, default_gpu_index,
-    default_hotkey_gallery, default_hotkey_save, default_hotkey_toggle, default_keyframe_interval,
-    default_master_volume, default_mic_device, default_mic_volume, default_quality_preset,
-    default_quality_value, default_quality_value_for_preset, default_rate_control,
-    default_replay_duration, default_resolution, default_save_directory, default_system_volume,
-    default_true, default_true_peak_limit_dbtp, default_true_peak_limiter_enabled,
-    ESTIMATED_MIC_AUDIO_BITRATE_BPS, ESTIMATED_SYSTEM_AUDIO_BITRATE_BPS, MAX_FRAMERATE,
-    MAX_REPLAY_MEMORY_LIMIT_MB, MIN_REPLAY_MEMORY_LIMIT_MB, RECOMMENDED_BUFFER_BASE_OVERHEAD_MB,
+    default_audio_normalization_enabled, default_audio_target_lufs, default_audio_track_language,
+    default_balance, default_bitrate, default_buffer_audio_codec, default_clip_audio_bitrate_kbps,
+    default_clip_audio_codec, default_clip_save_mode, default_encoder, default_false,
+    default_framerate, default_gpu_index, default_hotkey_gallery, default_hotkey_save,
+    default_hotkey_toggle, default_keyframe_interval, default_master_volume, default_mic_device,
+    default_mic_track_title, default_mic_volume, default_quality_preset, default_quality_value,
+    default_quality_value_for_preset, default_rate_control, default_replay_disk_limit_gb,
+    default_replay_duration, default_replay_pin_budget_mb, default_resolution,
+    default_save_container, default_save_directory, default_system_track_title,
+    default_system_volume, default_true, default_true_peak_limit_dbtp,
+    default_true_peak_limiter_enabled, ESTIMATED_MIC_AUDIO_BITRATE_BPS,
+    ESTIMATED_SYSTEM_AUDIO_BITRATE_BPS, MAX_CLIP_AUDIO_BITRATE_KBPS, MAX_FRAMERATE,
+    MAX_REPLAY_DISK_LIMIT_GB, MAX_REPLAY_MEMORY_LIMIT_MB, MAX_REPLAY_PIN_BUDGET_MB,
+    MIN_CLIP_AUDIO_BITRATE_KBPS, MIN_REPLAY_MEMORY_LIMIT_MB, RECOMMENDED_BUFFER_BASE_OVERHEAD_MB,
     RECOMMENDED_BUFFER_HEADROOM_PERCENT, REPLAY_MEMORY_LIMIT_AUTO_MB,
 };
 
@@ -117,6 +123,7 @@ pub enum Resolution {
 /// - Video settings (resolution, framerate, encoder, codec)
 /// - Audio settings (capture sources, volume levels)
 /// - Hotkey bindings
+/// - Export presets
 /// - Advanced settings
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
@@ -132,6 +139,9 @@ pub struct Config {
     /// Hotkey bindings.
     #[serde(default)]
     pub hotkeys: HotkeyConfig,
+    /// Saved export presets.
+    #[serde(default)]
+    pub export: ExportConfig,
     /// Advanced/developer settings.
     #[serde(default)]
     pub advanced: AdvancedConfig,
@@ -294,6 +304,61 @@ impl Config {
             );
             self.advanced.memory_limit_mb = MAX_REPLAY_MEMORY_LIMIT_MB;
         }
+        if self.general.replay_disk_limit_gb > MAX_REPLAY_DISK_LIMIT_GB {
+            warn!(
+                "Config: replay_disk_limit_gb was {}, clamping to {}",
+                self.general.replay_disk_limit_gb, MAX_REPLAY_DISK_LIMIT_GB
+            );
+            self.general.replay_disk_limit_gb = MAX_REPLAY_DISK_LIMIT_GB;
+        }
+        if self.general.replay_pin_budget_mb > MAX_REPLAY_PIN_BUDGET_MB {
+            warn!(
+                "Config: replay_pin_budget_mb was {}, clamping to {}",
+                self.general.replay_pin_budget_mb, MAX_REPLAY_PIN_BUDGET_MB
+            );
+            self.general.replay_pin_budget_mb = MAX_REPLAY_PIN_BUDGET_MB;
+        }
+        let clip_bitrate_kbps = self
+            .audio
+            .clip_bitrate_kbps
+            .clamp(MIN_CLIP_AUDIO_BITRATE_KBPS, MAX_CLIP_AUDIO_BITRATE_KBPS);
+        if clip_bitrate_kbps != self.audio.clip_bitrate_kbps {
+            warn!(
+                "Config: clip_bitrate_kbps was {}, clamping to {}",
+                self.audio.clip_bitrate_kbps, clip_bitrate_kbps
+            );
+            self.audio.clip_bitrate_kbps = clip_bitrate_kbps;
+        }
+        // Encoded buffer audio is stream-copied into clips, so it must already be in the clip
+        // codec; lossless clips need the buffer to keep PCM.
+        if self.audio.buffer_codec.is_compressed()
+            && self.audio.clip_codec.buffer_codec() != Some(self.audio.buffer_codec)
+        {
+            let buffer_codec = self
+                .audio
+                .clip_codec
+                .buffer_codec()
+                .unwrap_or(BufferAudioCodec::Pcm);
+            warn!(
+                "Config: buffer_codec {:?} cannot be copied into {} clips - buffering {:?} instead",
+                self.audio.buffer_codec,
+                self.audio.clip_codec.label(),
+                buffer_codec
+            );
+            self.audio.buffer_codec = buffer_codec;
+        }
+        if !self
+            .general
+            .save_container
+            .supports_audio_codec(self.audio.clip_codec)
+        {
+            warn!(
+                "Config: {} cannot hold {} audio - saving clips as MKV instead",
+                self.general.save_container.label(),
+                self.audio.clip_codec.label()
+            );
+            self.general.save_container = SaveContainer::Mkv;
+        }
         if !self.video.use_native_resolution && matches!(self.video.resolution, Resolution::Native)
         {
             warn!(
@@ -354,10 +419,23 @@ impl Config {
         self.audio.mic_volume = self.audio.mic_volume.clamp(0, 400);
         self.audio.target_lufs = self.audio.target_lufs.clamp(-23, -14);
         self.audio.true_peak_limit_dbtp = self.audio.true_peak_limit_dbtp.clamp(-3, 0);
+        self.export.target_lufs = self.export.target_lufs.clamp(-23, -14);
+        self.export.true_peak_limit_dbtp = self.export.true_peak_limit_dbtp.clamp(-3, 0);
+        let language = self.audio.track_language.trim().to_ascii_lowercase();
+        if language.len() == 3 && language.bytes().all(|b| b.is_ascii_lowercase()) {
+            self.audio.track_language = language;
+        } else {
+            warn!(
+                "Config: track_language '{}' is not an ISO 639-2 code, using 'und'",
+                self.audio.track_language
+            );
+            self.audio.track_language = super::functions::default_audio_track_language();
+        }
         // mic_noise_reduction is a simple on/off toggle, no per-parameter clamping required.
 
         // Validate save_directory for security and correctness
         self.validate_save_directory();
+        self.validate_export_presets();
 
         crate::hotkey_parse::validate_hotkey_config_strings(self);
     }
@@ -404,6 +482,26 @@ impl Config {
         }
     }
 
+    /// Clamps custom export presets and drops unnamed ones and ones whose name is taken.
+    fn validate_export_presets(&mut self) {
+        use tracing::warn;
+
+        let mut presets: Vec<ExportPreset> = Vec::new();
+        for mut preset in std::mem::take(&mut self.export.custom_presets) {
+            if !preset.sanitize() {
+                warn!("Config: dropping export preset without a name");
+            } else if preset.is_built_in() || presets.iter().any(|p| p.name == preset.name) {
+                warn!(
+                    "Config: dropping export preset '{}', the name is already taken",
+                    preset.name
+                );
+            } else {
+                presets.push(preset);
+            }
+        }
+        self.export.custom_presets = presets;
+    }
+
     pub fn estimated_replay_storage_bytes(&self) -> usize {
         let duration_secs = self.general.replay_duration_secs.max(1) as u64;
         let video_bps = (self.video.bitrate_mbps.max(1) as u64).saturating_mul(1_000_000);
@@ -473,10 +571,21 @@ impl Config {
             || self.audio.capture_mic != other.audio.capture_mic
             || self.audio.mic_device != other.audio.mic_device
             || self.audio.mic_noise_reduction != other.audio.mic_noise_reduction
+            || self.audio.buffer_codec != other.audio.buffer_codec
+            || (self.audio.buffer_codec.is_compressed()
+                && self.audio.clip_bitrate_kbps != other.audio.clip_bitrate_kbps)
+            || self.audio.separate_tracks != other.audio.separate_tracks
             || self.advanced.gpu_index != other.advanced.gpu_index
             || self.advanced.keyframe_interval_secs != other.advanced.keyframe_interval_secs
             || self.advanced.use_cpu_readback != other.advanced.use_cpu_readback
-            || self.general.replay_duration_secs != other.general.replay_duration_secs
+            || self.general.replay_disk_limit_gb != other.general.replay_disk_limit_gb
+            || self.general.replay_pin_budget_mb != other.general.replay_pin_budget_mb
+    }
+
+    /// Whether the replay buffer must be resized in place (see
+    /// [`LockFreeReplayBuffer::resize`](crate::buffer::ring::LockFreeReplayBuffer::resize)).
+    pub fn requires_buffer_resize(&self, other: &Config) -> bool {
+        self.general.replay_duration_secs != other.general.replay_duration_secs
             || self.advanced.memory_limit_mb != other.advanced.memory_limit_mb
     }
 
@@ -484,6 +593,7 @@ impl Config {
         self.hotkeys.save_clip != other.hotkeys.save_clip
             || self.hotkeys.toggle_recording != other.hotkeys.toggle_recording
             || self.hotkeys.open_gallery != other.hotkeys.open_gallery
+            || self.hotkeys.bookmark != other.hotkeys.bookmark
     }
 }
 
@@ -496,6 +606,176 @@ pub enum RateControl {
     Cq,
 }
 
+/// What happens to the replay buffer after a clip is saved.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipSaveMode {
+    /// Restart the buffer after each save, so consecutive clips never overlap.
+    Restart,
+    /// Keep the buffer; every save is a full-length independent clip, even when it overlaps
+    /// an earlier one.
+    Independent,
+    /// Keep the buffer; a save that overlaps the previous clip rewrites that file to cover
+    /// both windows instead of creating a new one.
+    Extend,
+}
+
+/// Container format saved clips are written in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SaveContainer {
+    /// MP4 with the index at the front (`+faststart`) for quick playback and web upload.
+    Mp4,
+    /// Fragmented MP4. Every keyframe starts a fragment, so a save cut short by a crash
+    /// still plays up to the last complete fragment.
+    FragmentedMp4,
+    /// Matroska. Written progressively, so a partially written clip stays playable.
+    Mkv,
+    /// QuickTime MOV, with the index at the front like MP4.
+    Mov,
+}
+
+impl SaveContainer {
+    /// All containers, in UI order.
+    pub const ALL: [Self; 4] = [Self::Mp4, Self::FragmentedMp4, Self::Mkv, Self::Mov];
+
+    /// File extension of saved clips (without leading dot).
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            SaveContainer::Mp4 | SaveContainer::FragmentedMp4 => "mp4",
+            SaveContainer::Mkv => "mkv",
+            SaveContainer::Mov => "mov",
+        }
+    }
+
+    /// Human-readable label for UI display.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            SaveContainer::Mp4 => "MP4",
+            SaveContainer::FragmentedMp4 => "MP4 (fragmented)",
+            SaveContainer::Mkv => "MKV",
+            SaveContainer::Mov => "MOV",
+        }
+    }
+
+    /// Whether the index can be moved to the front of the file (`+faststart`).
+    #[must_use]
+    pub fn supports_faststart(self) -> bool {
+        matches!(self, SaveContainer::Mp4 | SaveContainer::Mov)
+    }
+
+    /// Whether a save interrupted mid-write leaves a playable file.
+    #[must_use]
+    pub fn is_crash_tolerant(self) -> bool {
+        matches!(self, SaveContainer::FragmentedMp4 | SaveContainer::Mkv)
+    }
+
+    /// Whether clips in this container can carry `codec` audio.
+    ///
+    /// MKV holds every codec; MP4 has no mapping for raw PCM here and MOV none for Opus or FLAC.
+    #[must_use]
+    pub fn supports_audio_codec(self, codec: ClipAudioCodec) -> bool {
+        match self {
+            SaveContainer::Mp4 | SaveContainer::FragmentedMp4 => codec != ClipAudioCodec::Pcm,
+            SaveContainer::Mkv => true,
+            SaveContainer::Mov => matches!(codec, ClipAudioCodec::Aac | ClipAudioCodec::Pcm),
+        }
+    }
+
+    /// The container an existing clip was written in, judged by its extension.
+    ///
+    /// Plain and fragmented MP4 share an extension; `.mp4` maps to `preferred` when that is
+    /// an MP4 variant and to [`SaveContainer::Mp4`] otherwise.
+    #[must_use]
+    pub fn for_extension(extension: &str, preferred: SaveContainer) -> Option<Self> {
+        let extension = extension.to_ascii_lowercase();
+        if extension == preferred.extension() {
+            return Some(preferred);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|container| container.extension() == extension)
+    }
+}
+
+/// Audio codec of saved clips.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipAudioCodec {
+    /// AAC-LC at `clip_bitrate_kbps`.
+    Aac,
+    /// Opus (libopus) at `clip_bitrate_kbps`.
+    Opus,
+    /// Lossless FLAC.
+    Flac,
+    /// Uncompressed 16-bit PCM, for archival.
+    Pcm,
+}
+
+impl ClipAudioCodec {
+    /// All codecs, in UI order.
+    pub const ALL: [Self; 4] = [Self::Aac, Self::Opus, Self::Flac, Self::Pcm];
+
+    /// Human-readable label for UI display.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            ClipAudioCodec::Aac => "AAC",
+            ClipAudioCodec::Opus => "Opus",
+            ClipAudioCodec::Flac => "FLAC (lossless)",
+            ClipAudioCodec::Pcm => "PCM (uncompressed)",
+        }
+    }
+
+    /// Whether the configured bitrate applies (lossy codecs only).
+    #[must_use]
+    pub fn uses_bitrate(self) -> bool {
+        matches!(self, ClipAudioCodec::Aac | ClipAudioCodec::Opus)
+    }
+
+    /// The buffer codec whose packets can be stream-copied into clips of this codec.
+    #[must_use]
+    pub fn buffer_codec(self) -> Option<BufferAudioCodec> {
+        match self {
+            ClipAudioCodec::Aac => Some(BufferAudioCodec::Aac),
+            ClipAudioCodec::Opus => Some(BufferAudioCodec::Opus),
+            ClipAudioCodec::Flac | ClipAudioCodec::Pcm => None,
+        }
+    }
+}
+
+/// How captured audio is stored in the replay buffer.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BufferAudioCodec {
+    /// Raw 16-bit PCM; encoded to the clip codec when a clip is saved.
+    Pcm,
+    /// AAC packets, stream-copied into saved clips.
+    Aac,
+    /// Opus packets (libopus), stream-copied into saved clips.
+    Opus,
+}
+
+impl BufferAudioCodec {
+    /// Whether audio is encoded before it enters the buffer.
+    #[must_use]
+    pub fn is_compressed(self) -> bool {
+        !matches!(self, BufferAudioCodec::Pcm)
+    }
+
+    /// The clip codec the buffered packets already are, for compressed buffers.
+    #[must_use]
+    pub fn clip_codec(self) -> Option<ClipAudioCodec> {
+        match self {
+            BufferAudioCodec::Pcm => None,
+            BufferAudioCodec::Aac => Some(ClipAudioCodec::Aac),
+            BufferAudioCodec::Opus => Some(ClipAudioCodec::Opus),
+        }
+    }
+}
+
 /// Audio capture settings
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AudioConfig {
@@ -523,6 +803,26 @@ pub struct AudioConfig {
     pub true_peak_limiter_enabled: bool,
     #[serde(default = "default_true_peak_limit_dbtp")]
     pub true_peak_limit_dbtp: i8,
+    /// Encode mixed audio before it enters the replay buffer instead of storing PCM.
+    #[serde(default = "default_buffer_audio_codec")]
+    pub buffer_codec: BufferAudioCodec,
+    /// Audio codec of saved clips.
+    #[serde(default = "default_clip_audio_codec")]
+    pub clip_codec: ClipAudioCodec,
+    /// Bitrate of AAC/Opus audio, whether encoded into the buffer or at save time.
+    #[serde(default = "default_clip_audio_bitrate_kbps")]
+    pub clip_bitrate_kbps: u32,
+    /// Keep system audio and microphone as two labelled tracks in saved clips instead of
+    /// mixing them into one.
+    #[serde(default = "default_false")]
+    pub separate_tracks: bool,
+    #[serde(default = "default_system_track_title")]
+    pub system_track_title: String,
+    #[serde(default = "default_mic_track_title")]
+    pub mic_track_title: String,
+    /// ISO 639-2 language code written on each audio track (`und` when unknown).
+    #[serde(default = "default_audio_track_language")]
+    pub track_language: String,
 }
 /// Global hotkey bindings
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -533,6 +833,9 @@ pub struct HotkeyConfig {
     pub toggle_recording: String,
     #[serde(default = "default_hotkey_gallery")]
     pub open_gallery: String,
+    /// Marks the current moment; saved clips get a chapter there.
+    #[serde(default = "default_hotkey_bookmark")]
+    pub bookmark: String,
 }
 
 impl From<&Config> for HotkeyConfig {
@@ -597,6 +900,33 @@ impl VideoConfig {
         }
     }
 }
+/// Export settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportConfig {
+    /// Presets saved by the user, shown after [`ExportPreset::built_in`].
+    #[serde(default)]
+    pub custom_presets: Vec<ExportPreset>,
+    /// Bring exports to [`Self::target_lufs`] with two-pass loudness normalization, which
+    /// re-encodes the audio. Independent of the live normalization of captured audio
+    /// ([`AudioConfig::normalization_enabled`]).
+    #[serde(default = "default_true")]
+    pub normalize_loudness: bool,
+    /// Integrated loudness of normalized exports, in LUFS.
+    #[serde(default = "default_audio_target_lufs")]
+    pub target_lufs: i8,
+    /// Hold the true peak of normalized exports under [`Self::true_peak_limit_dbtp`].
+    #[serde(default = "default_true_peak_limiter_enabled")]
+    pub true_peak_limiter_enabled: bool,
+    #[serde(default = "default_true_peak_limit_dbtp")]
+    pub true_peak_limit_dbtp: i8,
+}
+
+impl ExportConfig {
+    /// Built-in presets followed by the custom ones.
+    pub fn presets(&self) -> Vec<ExportPreset> {
+        ExportPreset::all_with_custom(&self.custom_presets)
+    }
+}
 /// Advanced settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdvancedConfig {
@@ -615,6 +945,25 @@ pub struct AdvancedConfig {
 pub struct GeneralConfig {
     #[serde(default = "default_replay_duration")]
     pub replay_duration_secs: u32,
+    /// Disk budget (GB) for packets evicted from the in-memory replay ring. 0 keeps the
+    /// replay buffer RAM-only; otherwise evicted packets spill to the cache directory so
+    /// long replay windows don't need a matching memory limit.
+    #[serde(default = "default_replay_disk_limit_gb")]
+    pub replay_disk_limit_gb: u32,
+    /// Memory budget (MB) for packets kept alive by pinned replay ranges after they leave
+    /// the normal replay window. Pins that would exceed it stop retaining packets.
+    #[serde(default = "default_replay_pin_budget_mb")]
+    pub replay_pin_budget_mb: u32,
+    /// Periodically checkpoint the replay buffer to disk so it can be recovered into a clip
+    /// after a crash or power loss.
+    #[serde(default = "default_false")]
+    pub crash_recovery_enabled: bool,
+    /// Whether saving a clip restarts the replay buffer or keeps it for overlapping saves.
+    #[serde(default = "default_clip_save_mode")]
+    pub clip_save_mode: ClipSaveMode,
+    /// Container format (and file extension) of saved clips.
+    #[serde(default = "default_save_container")]
+    pub save_container: SaveContainer,
     #[serde(default = "default_save_directory")]
     pub save_directory: String,
     #[serde(default = "default_true")]
@@ -628,6 +977,10 @@ pub struct GeneralConfig {
     /// When false, skip post-save gallery thumbnail (linked libav decode); use for A/B memory diagnosis or `LITECLIP_SKIP_THUMBNAIL=1`.
     #[serde(default = "default_true")]
     pub generate_clip_thumbnail: bool,
+    /// Also write each clip's recording metadata as a JSON sidecar in `.cache`, next to its
+    /// thumbnail. The metadata is always embedded as container tags.
+    #[serde(default = "default_false")]
+    pub write_metadata_sidecar: bool,
     /// When true, use software encoder (libx265) for clip export instead of hardware encoders.
     /// Defaults to false (use hardware acceleration when available).
     #[serde(default = "default_false")]
@@ -697,6 +1050,134 @@ mod tests {
         assert!(config1.requires_pipeline_restart(&config2));
     }
 
+    #[test]
+    fn test_requires_pipeline_restart_buffer_audio_codec() {
+        let config1 = default_config();
+        let mut config2 = default_config();
+
+        config2.audio.buffer_codec = BufferAudioCodec::Opus;
+
+        assert!(config1.requires_pipeline_restart(&config2));
+    }
+
+    #[test]
+    fn test_requires_pipeline_restart_clip_bitrate_only_with_encoded_buffer() {
+        let mut config1 = default_config();
+        let mut config2 = default_config();
+
+        config2.audio.clip_bitrate_kbps = 96;
+        assert!(!config1.requires_pipeline_restart(&config2));
+
+        config1.audio.buffer_codec = BufferAudioCodec::Opus;
+        config2.audio.buffer_codec = BufferAudioCodec::Opus;
+        assert!(config1.requires_pipeline_restart(&config2));
+    }
+
+    #[test]
+    fn test_requires_pipeline_restart_separate_audio_tracks() {
+        let config1 = default_config();
+        let mut config2 = default_config();
+
+        config2.audio.separate_tracks = true;
+        assert!(config1.requires_pipeline_restart(&config2));
+
+        config2 = default_config();
+        config2.audio.mic_track_title = "Voice".to_string();
+        assert!(!config1.requires_pipeline_restart(&config2));
+    }
+
+    #[test]
+    fn test_validate_audio_track_language() {
+        let mut config = default_config();
+        config.audio.track_language = " ENG ".to_string();
+        config.validate();
+        assert_eq!(config.audio.track_language, "eng");
+
+        config.audio.track_language = "english".to_string();
+        config.validate();
+        assert_eq!(config.audio.track_language, "und");
+    }
+
+    #[test]
+    fn test_validate_mov_with_opus_audio_falls_back_to_mkv() {
+        let mut config = default_config();
+        config.general.save_container = SaveContainer::Mov;
+        config.audio.clip_codec = ClipAudioCodec::Opus;
+        config.audio.buffer_codec = BufferAudioCodec::Opus;
+        config.validate();
+        assert_eq!(config.general.save_container, SaveContainer::Mkv);
+
+        config.general.save_container = SaveContainer::Mov;
+        config.audio.clip_codec = ClipAudioCodec::Aac;
+        config.audio.buffer_codec = BufferAudioCodec::Aac;
+        config.validate();
+        assert_eq!(config.general.save_container, SaveContainer::Mov);
+    }
+
+    #[test]
+    fn test_validate_pcm_clip_audio_in_mp4_falls_back_to_mkv() {
+        let mut config = default_config();
+        config.audio.clip_codec = ClipAudioCodec::Pcm;
+        config.validate();
+        assert_eq!(config.general.save_container, SaveContainer::Mkv);
+
+        config.general.save_container = SaveContainer::Mp4;
+        config.audio.clip_codec = ClipAudioCodec::Flac;
+        config.validate();
+        assert_eq!(config.general.save_container, SaveContainer::Mp4);
+    }
+
+    #[test]
+    fn test_validate_buffer_codec_follows_clip_codec() {
+        let mut config = default_config();
+        config.audio.buffer_codec = BufferAudioCodec::Aac;
+        config.audio.clip_codec = ClipAudioCodec::Opus;
+        config.validate();
+        assert_eq!(config.audio.buffer_codec, BufferAudioCodec::Opus);
+
+        config.audio.clip_codec = ClipAudioCodec::Flac;
+        config.validate();
+        assert_eq!(config.audio.buffer_codec, BufferAudioCodec::Pcm);
+
+        // A PCM buffer can be encoded to any clip codec at save time.
+        config.audio.clip_codec = ClipAudioCodec::Aac;
+        config.validate();
+        assert_eq!(config.audio.buffer_codec, BufferAudioCodec::Pcm);
+    }
+
+    #[test]
+    fn test_validate_clamps_clip_audio_bitrate() {
+        let mut config = default_config();
+        config.audio.clip_bitrate_kbps = 8;
+        config.validate();
+        assert_eq!(config.audio.clip_bitrate_kbps, MIN_CLIP_AUDIO_BITRATE_KBPS);
+
+        config.audio.clip_bitrate_kbps = 4000;
+        config.validate();
+        assert_eq!(config.audio.clip_bitrate_kbps, MAX_CLIP_AUDIO_BITRATE_KBPS);
+    }
+
+    #[test]
+    fn test_save_container_for_extension() {
+        let fragmented = SaveContainer::FragmentedMp4;
+        assert_eq!(
+            SaveContainer::for_extension("mp4", fragmented),
+            Some(fragmented)
+        );
+        assert_eq!(
+            SaveContainer::for_extension("MP4", SaveContainer::Mkv),
+            Some(SaveContainer::Mp4)
+        );
+        assert_eq!(
+            SaveContainer::for_extension("mkv", SaveContainer::Mp4),
+            Some(SaveContainer::Mkv)
+        );
+        assert_eq!(
+            SaveContainer::for_extension("avi", SaveContainer::Mp4),
+            None
+        );
+    }
+
     #[test]
     fn test_requires_pipeline_restart_replay_duration() {
         let mut config1 = default_config();
@@ -705,6 +1186,31 @@ mod tests {
         config1.general.replay_duration_secs = 30;
         config2.general.replay_duration_secs = 60;
 
+        assert!(!config1.requires_pipeline_restart(&config2));
+        assert!(config1.requires_buffer_resize(&config2));
+    }
+
+    #[test]
+    fn test_requires_buffer_resize_memory_limit() {
+        let mut config1 = default_config();
+        let mut config2 = default_config();
+
+        config1.advanced.memory_limit_mb = 512;
+        config2.advanced.memory_limit_mb = 1024;
+
+        assert!(!config1.requires_pipeline_restart(&config2));
+        assert!(config1.requires_buffer_resize(&config2));
+        assert!(!config1.requires_buffer_resize(&config1.clone()));
+    }
+
+    #[test]
+    fn test_requires_pipeline_restart_replay_disk_limit() {
+        let mut config1 = default_config();
+        let mut config2 = default_config();
+
+        config1.general.replay_disk_limit_gb = 0;
+        config2.general.replay_disk_limit_gb = 8;
+
         assert!(config1.requires_pipeline_restart(&config2));
     }
 
@@ -768,5 +1274,34 @@ mod tests {
         config1.hotkeys.open_gallery = "Alt+F7".to_string();
         config2.hotkeys.open_gallery = "Alt+F8".to_string();
         assert!(config1.requires_hotkey_reregister(&config2));
+
+        config1 = default_config();
+        config2 = default_config();
+        config1.hotkeys.bookmark = "Alt+F5".to_string();
+        config2.hotkeys.bookmark = "Alt+F6".to_string();
+        assert!(config1.requires_hotkey_reregister(&config2));
+    }
+
+    #[test]
+    fn test_custom_export_presets_round_trip_and_validate() {
+        let mut config = default_config();
+        let mut shadowing = ExportPreset::built_in().remove(0);
+        shadowing.target_size_mb = Some(5);
+        let mut custom = shadowing.clone();
+        custom.name = " Clips for mum ".to_string();
+        custom.output_fps = Some(24.0);
+        config.export.custom_presets = vec![shadowing, custom];
+
+        let content = toml::to_string_pretty(&config).unwrap();
+        let mut loaded: Config = toml::from_str(&content).unwrap();
+        loaded.validate();
+
+        assert_eq!(loaded.export.custom_presets.len(), 1);
+        assert_eq!(loaded.export.custom_presets[0].name, "Clips for mum");
+        assert_eq!(loaded.export.custom_presets[0].output_fps, Some(24.0));
+        assert_eq!(
+            loaded.export.presets().len(),
+            ExportPreset::built_in().len() + 1
+        );
     }
 }
@@ -14,6 +14,7 @@
 //! - **Video**: Framerate, bitrate, encoder, codec, resolution
 //! - **Audio**: System/mic capture, volume levels
 //! - **Hotkeys**: Key bindings for save, toggle, gallery
+//! - **Export**: Custom export presets
 //! - **Advanced**: GPU selection, CPU readback, overlay
 //!
 //! # Key Types
@@ -23,6 +24,7 @@
 //! - [`VideoConfig`] - Video encoding settings
 //! - [`AudioConfig`] - Audio capture settings
 //! - [`HotkeyConfig`] - Hotkey bindings
+//! - [`ExportConfig`] - Saved export presets
 //! - [`AdvancedConfig`] - Advanced tuning options
 //!
 //! # Example
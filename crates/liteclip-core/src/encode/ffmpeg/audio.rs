@@ -0,0 +1,332 @@
+//! Audio encoding for the replay buffer and the clip muxer.
+//!
+//! [`BufferAudioEncoder`] turns the mixer's 16-bit PCM packets into AAC or Opus packets before
+//! they enter the replay buffer (see [`BufferAudioCodec`]). The buffer then holds the encoded
+//! bitrate instead of ~1.5 Mbit/s of PCM, and saving a clip only has to stream-copy the audio.
+//!
+//! Encoded packets keep QPC timestamps like every other [`EncodedPacket`]. The encoder runs on
+//! a continuous 48 kHz sample clock; small timestamp jitter is absorbed, short dropouts are
+//! filled with silence and larger discontinuities re-anchor the clock, so each output packet
+//! maps back to the QPC time of its first sample.
+
+use bytes::Bytes;
+use ffmpeg_next as ffmpeg;
+use std::collections::VecDeque;
+
+use crate::buffer::ring::qpc_frequency;
+use crate::config::{BufferAudioCodec, ClipAudioCodec};
+use crate::encode::{EncodeError, EncodeResult, EncodedPacket, StreamType};
+use crate::output::functions::{AUDIO_CHANNELS, AUDIO_SAMPLE_RATE};
+
+/// Timestamp deviation (in sample frames) absorbed without touching the sample clock (10 ms).
+const CLOCK_JITTER_TOLERANCE_FRAMES: i64 = AUDIO_SAMPLE_RATE as i64 / 100;
+/// Longest dropout (in sample frames) filled with silence instead of re-anchoring (1 s).
+const MAX_SILENCE_FILL_FRAMES: i64 = AUDIO_SAMPLE_RATE as i64;
+/// Frame size used when the encoder accepts variable-sized frames.
+const DEFAULT_FRAME_SIZE: usize = 1024;
+
+/// Opens a `codec` encoder for 48 kHz stereo.
+///
+/// `bitrate_bps` applies to AAC and Opus; FLAC and PCM ignore it. Set `global_header` when
+/// the codec configuration must go into the container (MP4, MKV) rather than in-band; the
+/// extradata is then available from the opened encoder.
+///
+/// # Errors
+///
+/// Returns an error if the encoder is missing from the linked FFmpeg build or fails to open.
+pub fn open_audio_encoder(
+    codec: ClipAudioCodec,
+    bitrate_bps: usize,
+    global_header: bool,
+) -> EncodeResult<ffmpeg::encoder::Audio> {
+    let (encoder, options) = match codec {
+        ClipAudioCodec::Aac => {
+            let mut options = ffmpeg::Dictionary::new();
+            options.set("aac_coder", "fast");
+            (ffmpeg::encoder::find(ffmpeg::codec::Id::AAC), options)
+        }
+        ClipAudioCodec::Opus => (
+            ffmpeg::encoder::find_by_name("libopus"),
+            ffmpeg::Dictionary::new(),
+        ),
+        ClipAudioCodec::Flac => (
+            ffmpeg::encoder::find(ffmpeg::codec::Id::FLAC),
+            ffmpeg::Dictionary::new(),
+        ),
+        ClipAudioCodec::Pcm => (
+            ffmpeg::encoder::find(ffmpeg::codec::Id::PCM_S16LE),
+            ffmpeg::Dictionary::new(),
+        ),
+    };
+    let encoder = encoder
+        .and_then(|encoder| encoder.audio().ok())
+        .ok_or_else(|| EncodeError::msg("Audio encoder not available in the linked FFmpeg"))?;
+    let sample_format = encoder
+        .formats()
+        .and_then(|mut formats| formats.next())
+        .ok_or_else(|| EncodeError::msg("Audio encoder did not report a sample format"))?;
+
+    let mut audio = ffmpeg::codec::context::Context::new_with_codec(*encoder)
+        .encoder()
+        .audio()?;
+    audio.set_rate(AUDIO_SAMPLE_RATE as i32);
+    audio.set_channel_layout(ffmpeg::channel_layout::ChannelLayout::STEREO);
+    audio.set_format(sample_format);
+    if codec.uses_bitrate() {
+        audio.set_bit_rate(bitrate_bps);
+        audio.set_max_bit_rate(bitrate_bps);
+    }
+    audio.set_time_base((1, AUDIO_SAMPLE_RATE as i32));
+    if global_header {
+        audio.set_flags(ffmpeg::codec::flag::Flags::GLOBAL_HEADER);
+    }
+
+    Ok(audio.open_as_with(encoder, options)?)
+}
+
+/// Encodes mixed PCM packets into AAC or Opus packets for the replay buffer.
+pub struct BufferAudioEncoder {
+    encoder: ffmpeg::encoder::Audio,
+    resampler: ffmpeg::software::resampling::Context,
+    frame_size: usize,
+    /// Interleaved samples waiting for a full encoder frame.
+    pending: Vec<i16>,
+    clock: SampleClock,
+    stream: StreamType,
+}
+
+impl BufferAudioEncoder {
+    /// Opens the encoder for `codec` at `bitrate_bps`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for [`BufferAudioCodec::Pcm`] or if the encoder cannot be opened.
+    pub fn new(codec: BufferAudioCodec, bitrate_bps: usize) -> EncodeResult<Self> {
+        let codec = codec
+            .clip_codec()
+            .ok_or_else(|| EncodeError::msg("PCM audio does not use an audio encoder"))?;
+        let encoder = open_audio_encoder(codec, bitrate_bps, true)?;
+        let resampler = ffmpeg::software::resampling::Context::get(
+            ffmpeg::format::Sample::I16(ffmpeg::format::sample::Type::Packed),
+            ffmpeg::channel_layout::ChannelLayout::STEREO,
+            AUDIO_SAMPLE_RATE,
+            encoder.format(),
+            encoder.channel_layout(),
+            encoder.rate(),
+        )?;
+        let frame_size = match encoder.frame_size() {
+            0 => DEFAULT_FRAME_SIZE,
+            size => size as usize,
+        };
+
+        Ok(Self {
+            encoder,
+            resampler,
+            frame_size,
+            pending: Vec::with_capacity(frame_size * AUDIO_CHANNELS as usize * 2),
+            clock: SampleClock::default(),
+            stream: StreamType::SystemAudio,
+        })
+    }
+
+    /// Sample frames per encoded packet.
+    #[must_use]
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Queues one PCM packet and appends any packets the encoder produced to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if resampling or encoding fails.
+    pub fn encode(
+        &mut self,
+        packet: &EncodedPacket,
+        out: &mut Vec<EncodedPacket>,
+    ) -> EncodeResult<()> {
+        let channels = AUDIO_CHANNELS as usize;
+        let frames = packet.data.len() / 2 / channels;
+        if frames == 0 {
+            return Ok(());
+        }
+
+        self.stream = packet.stream;
+        let silence = self.clock.place(packet.pts, frames as i64);
+        self.pending
+            .resize(self.pending.len() + silence as usize * channels, 0);
+        self.pending.extend(
+            packet.data[..frames * channels * 2]
+                .chunks_exact(2)
+                .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])),
+        );
+
+        let chunk = self.frame_size * channels;
+        let mut offset = 0;
+        while self.pending.len() - offset >= chunk {
+            self.send_frame(offset, chunk, out)?;
+            offset += chunk;
+        }
+        self.pending.drain(..offset);
+        Ok(())
+    }
+
+    /// Encodes any partial frame, drains the encoder and appends the final packets to `out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if encoding the remainder fails.
+    pub fn flush(&mut self, out: &mut Vec<EncodedPacket>) -> EncodeResult<()> {
+        if !self.pending.is_empty() {
+            self.send_frame(0, self.pending.len(), out)?;
+            self.pending.clear();
+        }
+        self.encoder.send_eof()?;
+        self.drain(out);
+        Ok(())
+    }
+
+    fn send_frame(
+        &mut self,
+        offset: usize,
+        len: usize,
+        out: &mut Vec<EncodedPacket>,
+    ) -> EncodeResult<()> {
+        let channels = AUDIO_CHANNELS as usize;
+        let pending_frames = (self.pending.len() / channels) as i64;
+        let first_sample = self.clock.next_sample - pending_frames + (offset / channels) as i64;
+
+        let mut input = ffmpeg::frame::Audio::new(
+            ffmpeg::format::Sample::I16(ffmpeg::format::sample::Type::Packed),
+            len / channels,
+            ffmpeg::channel_layout::ChannelLayout::STEREO,
+        );
+        input.set_rate(AUDIO_SAMPLE_RATE);
+        for (dst, src) in input
+            .plane_mut::<(i16, i16)>(0)
+            .iter_mut()
+            .zip(self.pending[offset..offset + len].chunks_exact(channels))
+        {
+            *dst = (src[0], src[1]);
+        }
+
+        let mut converted = ffmpeg::frame::Audio::empty();
+        self.resampler.run(&input, &mut converted)?;
+        converted.set_pts(Some(first_sample));
+        self.encoder.send_frame(&converted)?;
+        self.drain(out);
+        Ok(())
+    }
+
+    fn drain(&mut self, out: &mut Vec<EncodedPacket>) {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            if let (Some(pts), Some(data)) = (packet.pts(), packet.data()) {
+                let qpc = self.clock.to_qpc(pts);
+                out.push(EncodedPacket::new(
+                    Bytes::copy_from_slice(data),
+                    qpc,
+                    qpc,
+                    false,
+                    self.stream,
+                ));
+                self.clock.release_before(pts);
+            }
+            packet = ffmpeg::Packet::empty();
+        }
+    }
+}
+
+/// Maps the encoder's continuous sample clock back to QPC time.
+#[derive(Default)]
+struct SampleClock {
+    /// `(sample index, QPC)` pairs; samples from an anchor up to the next one are contiguous.
+    anchors: VecDeque<(i64, i64)>,
+    /// Sample index of the next frame to be queued.
+    next_sample: i64,
+}
+
+impl SampleClock {
+    /// Accounts for `frames` sample frames stamped `pts` and returns how many frames of
+    /// silence to queue before them.
+    fn place(&mut self, pts: i64, frames: i64) -> i64 {
+        let silence = if self.anchors.is_empty() {
+            self.anchors.push_back((0, pts));
+            0
+        } else {
+            let drift = qpc_to_frames(pts - self.to_qpc(self.next_sample));
+            if drift.abs() <= CLOCK_JITTER_TOLERANCE_FRAMES {
+                0
+            } else if drift > 0 && drift <= MAX_SILENCE_FILL_FRAMES {
+                drift
+            } else {
+                self.anchors.push_back((self.next_sample, pts));
+                0
+            }
+        };
+        self.next_sample += silence + frames;
+        silence
+    }
+
+    fn to_qpc(&self, sample: i64) -> i64 {
+        let (anchor_sample, anchor_qpc) = self
+            .anchors
+            .iter()
+            .rev()
+            .find(|(anchor_sample, _)| *anchor_sample <= sample)
+            .or_else(|| self.anchors.front())
+            .copied()
+            .unwrap_or_default();
+        anchor_qpc + frames_to_qpc(sample - anchor_sample)
+    }
+
+    /// Drops anchors that no sample at or after `sample` maps through.
+    fn release_before(&mut self, sample: i64) {
+        while self.anchors.len() > 1 && self.anchors[1].0 <= sample {
+            self.anchors.pop_front();
+        }
+    }
+}
+
+fn frames_to_qpc(frames: i64) -> i64 {
+    (frames as i128 * qpc_frequency() as i128 / AUDIO_SAMPLE_RATE as i128) as i64
+}
+
+fn qpc_to_frames(delta_qpc: i64) -> i64 {
+    let qpc_freq = qpc_frequency();
+    if qpc_freq <= 0 {
+        return 0;
+    }
+    (delta_qpc as i128 * AUDIO_SAMPLE_RATE as i128 / qpc_freq as i128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_clock_absorbs_jitter_fills_dropouts_and_reanchors() {
+        let mut clock = SampleClock::default();
+        let start = 1_000_000;
+
+        assert_eq!(clock.place(start, 480), 0);
+        // 2 ms late: within tolerance, stays on the sample clock.
+        assert_eq!(clock.place(start + frames_to_qpc(480 + 96), 480), 0);
+        assert_eq!(clock.to_qpc(960), start + frames_to_qpc(960));
+
+        // 100 ms dropout: filled with silence.
+        let silence = clock.place(start + frames_to_qpc(960 + 4800), 480);
+        assert!((silence - 4800).abs() <= 1);
+
+        // Jump back in time: re-anchored at the packet's timestamp.
+        let rewind = start + frames_to_qpc(100);
+        let anchor = clock.next_sample;
+        assert_eq!(clock.place(rewind, 480), 0);
+        assert_eq!(clock.to_qpc(anchor), rewind);
+        assert_eq!(clock.to_qpc(anchor - 1), start + frames_to_qpc(anchor - 1));
+
+        clock.release_before(anchor);
+        assert_eq!(clock.anchors.len(), 1);
+        assert_eq!(clock.to_qpc(anchor + 480), rewind + frames_to_qpc(480));
+    }
+}
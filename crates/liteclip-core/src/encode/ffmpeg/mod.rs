@@ -34,6 +34,7 @@
 //! on contributor or reviewer manual testing.
 
 pub mod amf;
+pub mod audio;
 pub mod context;
 pub mod nvenc;
 pub mod options;
@@ -11,6 +11,7 @@ use crate::app::{AppState, ClipManager};
 use crate::config::Config;
 use crate::error::Result;
 use crate::host::CoreHost;
+use crate::output::ClipWindow;
 use crate::paths::AppDirs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -135,6 +136,22 @@ impl ReplayEngine {
         let path = ClipManager::save_clip(&config, &buffer, game_name, host).await?;
         Ok(path)
     }
+
+    /// Save an arbitrary window of the replay buffer; see [`ClipManager::save_range`].
+    ///
+    /// Use [`ClipWindow::Last`] for "last N seconds", [`ClipWindow::PtsRange`] for explicit
+    /// PTS bounds, or [`ClipWindow::EndingAgo`] for "N seconds ending M seconds ago". The
+    /// buffer is not restarted, so several windows can be saved from the same replay.
+    pub async fn save_range(
+        &self,
+        window: ClipWindow,
+        game_name: Option<&str>,
+        host: Option<Arc<dyn CoreHost>>,
+    ) -> Result<PathBuf> {
+        let (config, buffer) = self.state.save_context();
+        let path = ClipManager::save_range(&config, &buffer, window, game_name, host).await?;
+        Ok(path)
+    }
 }
 
 #[cfg(test)]
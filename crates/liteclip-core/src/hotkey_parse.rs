@@ -76,6 +76,7 @@ pub fn validate_hotkey_config_strings(config: &crate::config::Config) {
         ("save_clip", config.hotkeys.save_clip.as_str()),
         ("toggle_recording", config.hotkeys.toggle_recording.as_str()),
         ("open_gallery", config.hotkeys.open_gallery.as_str()),
+        ("bookmark", config.hotkeys.bookmark.as_str()),
     ];
 
     for (name, value) in fields {
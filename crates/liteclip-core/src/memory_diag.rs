@@ -7,6 +7,7 @@
 //! AMF/D3D11/WASAPI/libav outside the ring buffer.
 
 use crate::buffer::ring::SharedReplayBuffer;
+use crate::encode::StreamType;
 use tracing::info;
 
 /// Working set and private usage in megabytes (Windows). `None` on non-Windows.
@@ -42,14 +43,16 @@ pub fn process_memory_mb() -> Option<(f64, f64)> {
     None
 }
 
-/// Logs ring usage ([`SharedReplayBuffer::stats`]), pinned snapshot bytes, and process memory.
+/// Logs ring usage ([`SharedReplayBuffer::stats`]), microphone gaps, outstanding snapshot
+/// bytes, and process memory.
 /// Intended for periodic calls from the encoder thread during recording.
 pub fn log_recording_memory(stage: &str, buffer: &SharedReplayBuffer) {
     let stats = buffer.stats();
-    let pinned = buffer.pinned_bytes();
+    let outstanding = buffer.outstanding_snapshot_bytes();
+    let mic_gaps = buffer.stream_gaps(StreamType::Microphone).gap_count;
     if let Some((working_set_mb, private_mb)) = process_memory_mb() {
         info!(
-            "Recording memory [{}]: process_working={:.1}MB, private={:.1}MB, buffer={:.1}MB ({}pkts, {}kf, mem={:.0}%), pinned_snapshots={:.1}MB",
+            "Recording memory [{}]: process_working={:.1}MB, private={:.1}MB, buffer={:.1}MB ({}pkts, {}kf, mem={:.0}%), video={:.1}MB, system_audio={:.1}MB, mic={:.1}MB ({} gaps), outstanding_snapshots={:.1}MB",
             stage,
             working_set_mb,
             private_mb,
@@ -57,17 +60,25 @@ pub fn log_recording_memory(stage: &str, buffer: &SharedReplayBuffer) {
             stats.packet_count,
             stats.keyframe_count,
             stats.memory_usage_percent,
-            pinned as f64 / 1_048_576.0,
+            stats.video.bytes as f64 / 1_048_576.0,
+            stats.system_audio.bytes as f64 / 1_048_576.0,
+            stats.microphone.bytes as f64 / 1_048_576.0,
+            mic_gaps,
+            outstanding as f64 / 1_048_576.0,
         );
     } else {
         info!(
-            "Recording memory [{}]: buffer={:.1}MB ({}pkts, {}kf, mem={:.0}%), pinned_snapshots={:.1}MB",
+            "Recording memory [{}]: buffer={:.1}MB ({}pkts, {}kf, mem={:.0}%), video={:.1}MB, system_audio={:.1}MB, mic={:.1}MB ({} gaps), outstanding_snapshots={:.1}MB",
             stage,
             stats.total_bytes as f64 / 1_048_576.0,
             stats.packet_count,
             stats.keyframe_count,
             stats.memory_usage_percent,
-            pinned as f64 / 1_048_576.0,
+            stats.video.bytes as f64 / 1_048_576.0,
+            stats.system_audio.bytes as f64 / 1_048_576.0,
+            stats.microphone.bytes as f64 / 1_048_576.0,
+            mic_gaps,
+            outstanding as f64 / 1_048_576.0,
         );
     }
 }
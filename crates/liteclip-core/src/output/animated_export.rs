@@ -0,0 +1,484 @@
+//! Animated image export (GIF, animated WebP, APNG).
+//!
+//! Each kept range is decoded, cropped and scaled to RGB at the attempt's size, resampled
+//! to its framerate and pushed through a filter graph that prepares frames for the image
+//! encoder. For GIF that graph builds one palette from the whole clip (`palettegen`) and
+//! dithers every frame against it (`paletteuse`), which looks far better than the encoder's
+//! fixed palette. Animated images have no audio, so audio streams are ignored.
+
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+    Arc,
+};
+use std::time::Instant;
+use tracing::info;
+
+use super::sdk_export::{
+    muxer_header_opts_for_container, scale_with_crop, seek_to_seconds, time_base_to_secs_per_tick,
+    OverlayFilterGraph,
+};
+use super::video_file::{
+    normalize_output_fps, AnimatedImageSettings, ClipExportPhase, ClipExportRequest,
+    ClipExportUpdate, ExportAttemptResult, ExportContainerFormat,
+};
+
+/// Lossy WebP quality (0-100).
+const WEBP_QUALITY: &str = "75";
+
+/// FFmpeg muxer, encoder input pixel format and filter chain for an animated image format.
+///
+/// The filter chain takes RGB24 frames; `stats_mode=diff` weights the GIF palette towards
+/// the parts of the picture that move, and `diff_mode=rectangle` only re-dithers the
+/// changed area of each frame, which keeps static backgrounds from shimmering.
+fn animated_format(
+    container: ExportContainerFormat,
+) -> Option<(&'static str, ffmpeg::format::Pixel, &'static str)> {
+    match container {
+        ExportContainerFormat::Gif => Some((
+            "gif",
+            ffmpeg::format::Pixel::PAL8,
+            "split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=sierra2_4a:diff_mode=rectangle",
+        )),
+        ExportContainerFormat::WebP => Some((
+            "webp",
+            ffmpeg::format::Pixel::YUV420P,
+            "format=yuv420p",
+        )),
+        ExportContainerFormat::Apng => Some(("apng", ffmpeg::format::Pixel::RGB24, "format=rgb24")),
+        ExportContainerFormat::Mp4
+        | ExportContainerFormat::Mkv
+        | ExportContainerFormat::Mov
+        | ExportContainerFormat::WebM
+        | ExportContainerFormat::M4a
+        | ExportContainerFormat::Opus
+        | ExportContainerFormat::Flac
+        | ExportContainerFormat::Wav => None,
+    }
+}
+
+/// Run one animated image export attempt at `settings`.
+///
+/// Returns `Ok(None)` if the export was cancelled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn attempt_animated_export(
+    request: &ClipExportRequest,
+    output_path: &Path,
+    settings: AnimatedImageSettings,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+    attempt_index: usize,
+    attempt_count: usize,
+    phase: ClipExportPhase,
+) -> Result<Option<ExportAttemptResult>> {
+    let (muxer_name, pixel_format, filter_spec) = animated_format(request.container_format)
+        .with_context(|| {
+            format!(
+                "{} is not an animated image format",
+                request.container_format.short_label()
+            )
+        })?;
+    let total_duration_secs = request.output_duration_secs().max(0.1);
+    let fps = settings.fps.round().max(1.0) as i32;
+    let frame_secs = 1.0 / f64::from(fps);
+    let source_fps = normalize_output_fps(request.metadata.fps, f64::from(fps));
+
+    let _ = progress_tx.send(ClipExportUpdate::Progress {
+        phase: ClipExportPhase::Preparing,
+        fraction: 0.0,
+        message: "Preparing export".to_string(),
+    });
+
+    info!(
+        "Exporting animated {} {:?} -> {:?} ({} kept ranges, {}x{} at {} fps)",
+        request.container_format.short_label(),
+        request.input_path,
+        output_path,
+        request.keep_ranges.len(),
+        settings.width,
+        settings.height,
+        fps,
+    );
+
+    let mut input_ctx = ffmpeg::format::input(&request.input_path)
+        .with_context(|| format!("Failed to open input: {:?}", request.input_path))?;
+    let video_stream_idx = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .map(|s| s.index())
+        .context("No video stream found")?;
+    let input_time_base = input_ctx
+        .stream(video_stream_idx)
+        .context("Missing video stream")?
+        .time_base();
+    let secs_per_input_tick =
+        time_base_to_secs_per_tick(input_time_base).context("Invalid video time base")?;
+
+    let mut output_ctx = ffmpeg::format::output_as(output_path, muxer_name)
+        .with_context(|| format!("Failed to create output: {:?}", output_path))?;
+
+    let codec_name = request.container_format.default_video_codec_name();
+    let codec = ffmpeg::encoder::find_by_name(codec_name)
+        .with_context(|| format!("Encoder {} not found", codec_name))?;
+    let mut video_enc = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .context("Failed to create video encoder")?;
+    video_enc.set_width(settings.width);
+    video_enc.set_height(settings.height);
+    video_enc.set_format(pixel_format);
+    video_enc.set_time_base(ffmpeg::Rational(1, fps));
+    video_enc.set_frame_rate(Some((fps, 1)));
+
+    let mut opts = ffmpeg::Dictionary::new();
+    match request.container_format {
+        ExportContainerFormat::WebP => {
+            opts.set("quality", WEBP_QUALITY);
+            opts.set("compression_level", "4");
+        }
+        ExportContainerFormat::Apng => {
+            opts.set("pred", "mixed");
+        }
+        _ => {}
+    }
+    let mut encoder = video_enc
+        .open_with(opts)
+        .context("Failed to open encoder")?;
+
+    let video_out_idx = {
+        let mut out_stream = output_ctx
+            .add_stream(codec)
+            .context("Failed to add video stream")?;
+        out_stream.set_time_base(ffmpeg::Rational(1, fps));
+        out_stream.set_avg_frame_rate((fps, 1));
+        out_stream.set_parameters(&encoder);
+        out_stream.index()
+    };
+
+    let header_opts = muxer_header_opts_for_container(request.container_format);
+    output_ctx
+        .write_header_with(header_opts)
+        .with_context(|| "Failed to write output header")?;
+    // The GIF muxer always uses centiseconds; read the time base only after the header.
+    let video_out_time_base = output_ctx
+        .stream(video_out_idx)
+        .context("Missing output video stream")?
+        .time_base();
+
+    let mut filter_graph =
+        AnimatedImageFilterGraph::new(settings.width, settings.height, fps, filter_spec)?;
+
+    let (source_format, source_width, source_height) = {
+        let v_stream = input_ctx
+            .stream(video_stream_idx)
+            .context("Failed to get video stream for scaler creation")?;
+        let v_ctx = ffmpeg::codec::context::Context::from_parameters(v_stream.parameters())?;
+        let v_decoder = v_ctx.decoder().video()?;
+        let (width, height) = request
+            .crop
+            .map_or((v_decoder.width(), v_decoder.height()), |c| {
+                (c.width, c.height)
+            });
+        (v_decoder.format(), width, height)
+    };
+    let mut scaler = ffmpeg::software::scaling::Context::get(
+        source_format,
+        source_width,
+        source_height,
+        ffmpeg::format::Pixel::RGB24,
+        settings.width,
+        settings.height,
+        ffmpeg::software::scaling::flag::Flags::LANCZOS,
+    )
+    .context("Failed to create video scaler for export")?;
+
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut scaled = ffmpeg::frame::Video::new(
+        ffmpeg::format::Pixel::RGB24,
+        settings.width,
+        settings.height,
+    );
+    let mut overlay_graph = OverlayFilterGraph::new(
+        &request.overlays,
+        settings.width,
+        settings.height,
+        fps,
+        ffmpeg::format::Pixel::RGB24,
+    )?;
+    let mut next_frame_index = 0i64;
+    let mut output_cursor_secs = 0.0f64;
+    let mut processed_duration = 0.0f64;
+    let started_at = Instant::now();
+    let mut last_progress_time = started_at;
+
+    for range in &request.keep_ranges {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let range_output_start_secs = output_cursor_secs;
+        output_cursor_secs += range.output_duration_secs();
+        let speed = range.effective_speed();
+        // In slow motion a source frame stays on screen for several output frames.
+        let hold_secs = if speed < 1.0 {
+            1.0 / (source_fps * speed)
+        } else {
+            0.0
+        };
+
+        seek_to_seconds(&mut input_ctx, range.start_secs);
+        let v_stream = input_ctx
+            .stream(video_stream_idx)
+            .with_context(|| format!("missing video stream {} after seek", video_stream_idx))?;
+        let v_ctx = ffmpeg::codec::context::Context::from_parameters(v_stream.parameters())?;
+        let mut decoder = v_ctx.decoder().video()?;
+        let mut found_first_keyframe = false;
+
+        // Keeps the frames of this range that land on the output frame grid.
+        let mut push_decoded = |decoded: &mut ffmpeg::frame::Video,
+                                next_frame_index: &mut i64,
+                                graph: &mut AnimatedImageFilterGraph|
+         -> Result<Option<f64>> {
+            let Some(ts) = decoded.timestamp() else {
+                return Ok(None);
+            };
+            let pts_secs = ts as f64 * secs_per_input_tick;
+            if pts_secs < range.start_secs || pts_secs >= range.end_secs {
+                return Ok(None);
+            }
+            let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs) / speed;
+            if output_pts_secs + 0.000_5 < *next_frame_index as f64 * frame_secs {
+                return Ok(None);
+            }
+            scale_with_crop(&mut scaler, decoded, &mut scaled, request.crop)
+                .context("Failed to scale video frame during export")?;
+            loop {
+                scaled.set_pts(Some(*next_frame_index));
+                *next_frame_index += 1;
+                match overlay_graph.as_mut() {
+                    Some(overlay_graph) => graph.send(overlay_graph.apply(&scaled)?)?,
+                    None => graph.send(&scaled)?,
+                }
+                if hold_secs <= 0.0
+                    || *next_frame_index as f64 * frame_secs + 0.000_5
+                        >= output_pts_secs + hold_secs
+                {
+                    break;
+                }
+            }
+            Ok(Some(output_pts_secs))
+        };
+
+        for (stream, packet) in input_ctx.packets() {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            if stream.index() != video_stream_idx {
+                continue;
+            }
+            if let Some(pts) = packet.pts() {
+                if pts as f64 * secs_per_input_tick > range.end_secs + 1.0 {
+                    break;
+                }
+            }
+            // Skip packets until we find a keyframe (for decodable output).
+            if !found_first_keyframe {
+                if !packet.is_key() {
+                    continue;
+                }
+                found_first_keyframe = true;
+            }
+
+            decoder.send_packet(&packet)?;
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                if let Some(secs) =
+                    push_decoded(&mut decoded, &mut next_frame_index, &mut filter_graph)?
+                {
+                    processed_duration = processed_duration.max(secs);
+                    encode_ready_frames(
+                        &mut filter_graph,
+                        &mut encoder,
+                        &mut output_ctx,
+                        video_out_idx,
+                        video_out_time_base,
+                    )?;
+                }
+            }
+
+            let now = Instant::now();
+            const MIN_PROGRESS_INTERVAL: std::time::Duration =
+                std::time::Duration::from_millis(100);
+            if now.duration_since(last_progress_time) >= MIN_PROGRESS_INTERVAL {
+                last_progress_time = now;
+                let progress = (processed_duration / total_duration_secs).min(1.0) as f32;
+                let _ = progress_tx.send(ClipExportUpdate::Progress {
+                    phase,
+                    fraction: progress,
+                    message: format!(
+                        "Attempt {}/{} - {:.1}s processed",
+                        attempt_index + 1,
+                        attempt_count,
+                        processed_duration
+                    ),
+                });
+            }
+        }
+
+        decoder.send_eof()?;
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            if let Some(secs) =
+                push_decoded(&mut decoded, &mut next_frame_index, &mut filter_graph)?
+            {
+                processed_duration = processed_duration.max(secs);
+            }
+        }
+        encode_ready_frames(
+            &mut filter_graph,
+            &mut encoder,
+            &mut output_ctx,
+            video_out_idx,
+            video_out_time_base,
+        )?;
+    }
+
+    // GIF palettes are only generated once every frame has been seen.
+    filter_graph.flush()?;
+    encode_ready_frames(
+        &mut filter_graph,
+        &mut encoder,
+        &mut output_ctx,
+        video_out_idx,
+        video_out_time_base,
+    )?;
+    encoder.send_eof()?;
+    write_packets(
+        &mut encoder,
+        &mut output_ctx,
+        video_out_idx,
+        video_out_time_base,
+    )?;
+
+    output_ctx
+        .write_trailer()
+        .with_context(|| "Failed to write output trailer")?;
+
+    let size_bytes = std::fs::metadata(output_path)
+        .with_context(|| format!("Failed to get size of export output file {:?}", output_path))?
+        .len();
+    info!(
+        elapsed_secs = started_at.elapsed().as_secs_f64(),
+        frames = next_frame_index,
+        size_bytes,
+        "Animated image export attempt finished"
+    );
+
+    Ok(Some(ExportAttemptResult {
+        output_path: output_path.to_path_buf(),
+        video_bitrate_kbps: (size_bytes as f64 * 8.0 / 1000.0 / total_duration_secs).round() as u32,
+        size_bytes,
+    }))
+}
+
+/// Encodes every frame the filter graph has ready and writes the packets.
+fn encode_ready_frames(
+    graph: &mut AnimatedImageFilterGraph,
+    encoder: &mut ffmpeg::encoder::Video,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    stream_index: usize,
+    out_time_base: ffmpeg::Rational,
+) -> Result<()> {
+    while graph.receive(|frame| {
+        frame.set_kind(ffmpeg::picture::Type::None);
+        encoder.send_frame(frame)?;
+        Ok(())
+    })? {}
+    write_packets(encoder, output_ctx, stream_index, out_time_base)
+}
+
+/// Writes the encoder's pending packets to the output stream.
+fn write_packets(
+    encoder: &mut ffmpeg::encoder::Video,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    stream_index: usize,
+    out_time_base: ffmpeg::Rational,
+) -> Result<()> {
+    let mut packet = ffmpeg::Packet::empty();
+    while encoder.receive_packet(&mut packet).is_ok() {
+        packet.rescale_ts(encoder.time_base(), out_time_base);
+        packet.set_stream(stream_index);
+        packet
+            .write_interleaved(output_ctx)
+            .context("Failed writing animated image packet")?;
+    }
+    Ok(())
+}
+
+/// Filter graph taking RGB24 frames at the output size and emitting frames in the image
+/// encoder's pixel format.
+struct AnimatedImageFilterGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+    filtered_frame: ffmpeg::frame::Video,
+}
+
+impl AnimatedImageFilterGraph {
+    fn new(width: u32, height: u32, fps: i32, spec: &str) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let args =
+            format!("video_size={width}x{height}:pix_fmt=rgb24:time_base=1/{fps}:pixel_aspect=1/1");
+        let source_filter = ffmpeg::filter::find("buffer").context("buffer filter not found")?;
+        let sink_filter =
+            ffmpeg::filter::find("buffersink").context("buffersink filter not found")?;
+        let source = graph
+            .add(&source_filter, "in", &args)
+            .context("Failed to add buffer filter to graph")?;
+        let sink = graph
+            .add(&sink_filter, "out", "")
+            .context("Failed to add buffersink filter to graph")?;
+        graph
+            .output("in", 0)
+            .and_then(|parser| parser.input("out", 0))
+            .and_then(|parser| parser.parse(spec))
+            .with_context(|| format!("Failed to build animated image filter graph: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate animated image filter graph")?;
+
+        Ok(Self {
+            graph,
+            source,
+            sink,
+            filtered_frame: ffmpeg::frame::Video::empty(),
+        })
+    }
+
+    fn send(&mut self, frame: &ffmpeg::frame::Video) -> Result<()> {
+        self.source
+            .source()
+            .add(frame)
+            .context("Failed to push frame into filter graph")
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.source
+            .source()
+            .flush()
+            .context("Failed to flush filter graph source")
+    }
+
+    /// Calls `callback` with the next ready frame; returns `false` when none is ready.
+    fn receive<F>(&mut self, callback: F) -> Result<bool>
+    where
+        F: FnOnce(&mut ffmpeg::frame::Video) -> Result<()>,
+    {
+        if self.sink.sink().frame(&mut self.filtered_frame).is_err() {
+            return Ok(false);
+        }
+        callback(&mut self.filtered_frame)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,384 @@
+//! Audio mixing settings of a clip export.
+//!
+//! [`ExportAudioMix`] sets a gain (or mute) per audio track of the clip, fades at the start
+//! and end of every kept range, and crossfades across the joins between ranges. A hard cut
+//! between two ranges jumps from one waveform to another mid-cycle, which is heard as a
+//! click; a short fade, or a crossfade that lets the cut-off audio of one range die away
+//! under the start of the next, hides the join. Anything but the default mix needs the audio
+//! re-encoded, so it rules out stream copy.
+//!
+//! [`LoudnessNormalization`] brings the export to a target loudness (EBU R128) in two
+//! passes: the first measures the integrated loudness and true peak of the kept ranges, the
+//! second applies the gain that reaches the target, with a limiter holding the true peak
+//! down when the gain would push it over the limit.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Quietest gain a track can be set to; mute it to silence it entirely.
+pub const MIN_TRACK_GAIN_DB: f32 = -30.0;
+/// Loudest gain a track can be set to.
+pub const MAX_TRACK_GAIN_DB: f32 = 20.0;
+/// Longest fade at a kept-range boundary.
+pub const MAX_FADE_SECS: f64 = 5.0;
+/// Longest crossfade between joined ranges. The audio that fades out is read past the end of
+/// its range, and the export reads no further ahead than this.
+pub const MAX_CROSSFADE_SECS: f64 = 0.5;
+
+/// Most gain loudness normalization applies, so near-silent clips are not boosted into noise.
+pub const MAX_NORMALIZATION_GAIN_DB: f64 = 20.0;
+
+/// Gain and mute of one audio track.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct AudioSourceMix {
+    /// Gain in dB, between [`MIN_TRACK_GAIN_DB`] and [`MAX_TRACK_GAIN_DB`].
+    #[serde(default)]
+    pub gain_db: f32,
+    #[serde(default)]
+    pub muted: bool,
+}
+
+impl AudioSourceMix {
+    /// Whether the track plays unchanged.
+    pub fn is_unity(&self) -> bool {
+        !self.muted && self.gain_db == 0.0
+    }
+
+    /// `volume` filter for the track, or `None` when it plays unchanged.
+    fn volume_filter(&self) -> Option<String> {
+        if self.muted {
+            Some("volume=0".to_string())
+        } else if self.gain_db != 0.0 {
+            Some(format!("volume={:.2}dB", self.gain_db))
+        } else {
+            None
+        }
+    }
+}
+
+/// Per-track gain and the fades of an export's audio.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportAudioMix {
+    /// Gain and mute of each audio track of the clip, in track order. Tracks past the end of
+    /// the list play unchanged.
+    #[serde(default)]
+    pub sources: Vec<AudioSourceMix>,
+    /// Fade in at the start of every kept range.
+    #[serde(default)]
+    pub fade_in_secs: f64,
+    /// Fade out at the end of every kept range.
+    #[serde(default)]
+    pub fade_out_secs: f64,
+    /// Crossfade at every join: the audio cut off after a range fades out under the start of
+    /// the next one, which fades in. Keeps the output length and A/V sync.
+    #[serde(default)]
+    pub crossfade_secs: f64,
+}
+
+impl ExportAudioMix {
+    /// Gain and mute of track `index`.
+    pub fn source(&self, index: usize) -> AudioSourceMix {
+        self.sources.get(index).copied().unwrap_or_default()
+    }
+
+    /// Whether the audio is exported as it is, so stream copy stays possible.
+    pub fn is_passthrough(&self) -> bool {
+        self.sources.iter().all(AudioSourceMix::is_unity)
+            && self.fade_in_secs <= 0.0
+            && self.fade_out_secs <= 0.0
+            && self.crossfade_secs <= 0.0
+    }
+
+    /// Checks that gains and fade lengths are within their limits.
+    pub fn validate(&self) -> Result<()> {
+        for (index, source) in self.sources.iter().enumerate() {
+            if !(MIN_TRACK_GAIN_DB..=MAX_TRACK_GAIN_DB).contains(&source.gain_db) {
+                bail!(
+                    "Audio track {} gain {} dB is outside {MIN_TRACK_GAIN_DB}..={MAX_TRACK_GAIN_DB} dB",
+                    index + 1,
+                    source.gain_db
+                );
+            }
+        }
+        for (label, secs, max) in [
+            ("Fade-in", self.fade_in_secs, MAX_FADE_SECS),
+            ("Fade-out", self.fade_out_secs, MAX_FADE_SECS),
+            ("Crossfade", self.crossfade_secs, MAX_CROSSFADE_SECS),
+        ] {
+            if !(0.0..=max).contains(&secs) {
+                bail!("{label} of {secs:.2}s is outside 0..={max}s");
+            }
+        }
+        Ok(())
+    }
+
+    /// Crossfade length in use, or `None` without crossfades.
+    pub(crate) fn crossfade(&self) -> Option<f64> {
+        (self.crossfade_secs > 0.0).then(|| self.crossfade_secs.min(MAX_CROSSFADE_SECS))
+    }
+
+    /// Fade-in and fade-out lengths over a kept range that lasts `range_secs` in the output.
+    /// With `tail`, the range fades in for at least the crossfade.
+    pub(crate) fn range_fades(&self, range_secs: f64, tail: bool) -> (f64, f64) {
+        let crossfade = self.crossfade().filter(|_| tail);
+        let fade_in = self
+            .fade_in_secs
+            .max(crossfade.unwrap_or(0.0))
+            .min(range_secs / 2.0);
+        (fade_in, self.fade_out_secs.min(range_secs / 2.0))
+    }
+
+    /// Filter graph description applying the mix to track `source` over a kept range that
+    /// lasts `range_secs` in the output, or `None` when the range plays unchanged.
+    ///
+    /// Reads the range from `in` and, with `tail`, the audio cut off after the previous
+    /// range from `tail`; writes `out` in `sample_format`.
+    pub(crate) fn range_filter_spec(
+        &self,
+        source: usize,
+        range_secs: f64,
+        tail: bool,
+        sample_format: &str,
+    ) -> Option<String> {
+        let volume = self.source(source).volume_filter();
+        let crossfade = self.crossfade().filter(|_| tail);
+        let (fade_in, fade_out) = self.range_fades(range_secs, tail);
+
+        let mut filters: Vec<String> = volume.iter().cloned().collect();
+        if fade_in > 0.0 {
+            filters.push(format!("afade=t=in:st=0:d={fade_in:.3}"));
+        }
+        if fade_out > 0.0 {
+            filters.push(format!(
+                "afade=t=out:st={:.3}:d={fade_out:.3}",
+                range_secs - fade_out
+            ));
+        }
+        if filters.is_empty() && crossfade.is_none() {
+            return None;
+        }
+        let format = format!("aformat=sample_fmts={sample_format}");
+
+        match crossfade {
+            Some(crossfade) => {
+                let mut tail_filters: Vec<String> = volume.into_iter().collect();
+                tail_filters.push(format!("afade=t=out:st=0:d={crossfade:.3}"));
+                Some(format!(
+                    "[in]{}[main];[tail]{}[faded];[main][faded]amix=inputs=2:duration=first:normalize=0,{format}[out]",
+                    filters.join(","),
+                    tail_filters.join(","),
+                ))
+            }
+            None => Some(format!("[in]{},{format}[out]", filters.join(","))),
+        }
+    }
+}
+
+/// Loudness target of an export, and the measurement of its first pass.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessNormalization {
+    /// Integrated loudness to reach, in LUFS.
+    pub target_lufs: f64,
+    /// Ceiling of the true peak after the gain, in dBTP.
+    pub true_peak_limit_dbtp: f64,
+    /// Hold the true peak under [`Self::true_peak_limit_dbtp`] with a limiter when the gain
+    /// would push it over. When off, the gain is applied as is.
+    #[serde(default = "default_limiter_enabled")]
+    pub limiter_enabled: bool,
+    /// Integrated loudness of the kept ranges, filled in by the first pass. `None` before it,
+    /// or when the audio is too quiet to measure.
+    #[serde(default)]
+    pub measured_lufs: Option<f64>,
+    /// True peak of the kept ranges in dBTP, filled in by the first pass.
+    #[serde(default)]
+    pub measured_true_peak_dbtp: Option<f64>,
+}
+
+impl LoudnessNormalization {
+    /// The target set in the export settings, or `None` when export normalization
+    /// (`export.normalize_loudness`) is turned off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.export.normalize_loudness.then(|| Self {
+            target_lufs: f64::from(config.export.target_lufs),
+            true_peak_limit_dbtp: f64::from(config.export.true_peak_limit_dbtp),
+            limiter_enabled: config.export.true_peak_limiter_enabled,
+            measured_lufs: None,
+            measured_true_peak_dbtp: None,
+        })
+    }
+
+    /// Gain that brings the measured loudness to the target, or `None` before the first pass
+    /// or for audio too quiet to measure.
+    pub fn gain_db(&self) -> Option<f64> {
+        let measured = self.measured_lufs.filter(|lufs| lufs.is_finite())?;
+        Some(
+            (self.target_lufs - measured)
+                .clamp(-MAX_NORMALIZATION_GAIN_DB, MAX_NORMALIZATION_GAIN_DB),
+        )
+    }
+
+    /// Whether the gain would push the true peak over the limit, so the limiter is needed.
+    /// Assumed when the peak was not measured.
+    pub fn needs_limiter(&self) -> bool {
+        match (self.gain_db(), self.measured_true_peak_dbtp) {
+            (Some(gain), Some(peak)) => peak + gain > self.true_peak_limit_dbtp,
+            _ => true,
+        }
+    }
+
+    /// Filter chain of the second pass (`volume` and, when enabled and needed, `alimiter`),
+    /// or `None` when there is nothing to apply.
+    pub(crate) fn filter_chain(&self) -> Option<String> {
+        let gain = self.gain_db()?;
+        let mut filters = vec![format!("volume={gain:.2}dB")];
+        if self.limiter_enabled && self.needs_limiter() {
+            let limit = 10f64
+                .powf(self.true_peak_limit_dbtp / 20.0)
+                .clamp(0.0625, 1.0);
+            filters.push(format!(
+                "alimiter=limit={limit:.4}:attack=5:release=50:level=0"
+            ));
+        }
+        Some(filters.join(","))
+    }
+}
+
+fn default_limiter_enabled() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization_gain_reaches_the_target_within_limits() {
+        let config = Config::default();
+        let mut loudness =
+            LoudnessNormalization::from_config(&config).expect("export normalization is on");
+        assert!(loudness.gain_db().is_none());
+        assert!(loudness.filter_chain().is_none());
+
+        loudness.target_lufs = -16.0;
+        loudness.true_peak_limit_dbtp = -1.0;
+        loudness.measured_lufs = Some(-22.5);
+        loudness.measured_true_peak_dbtp = Some(-9.0);
+        assert_eq!(loudness.gain_db(), Some(6.5));
+        assert!(!loudness.needs_limiter());
+        assert_eq!(loudness.filter_chain().as_deref(), Some("volume=6.50dB"));
+
+        loudness.measured_true_peak_dbtp = Some(-3.0);
+        assert!(loudness.needs_limiter());
+        assert!(loudness
+            .filter_chain()
+            .unwrap()
+            .starts_with("volume=6.50dB,alimiter=limit=0.8913:"));
+        loudness.limiter_enabled = false;
+        assert_eq!(loudness.filter_chain().as_deref(), Some("volume=6.50dB"));
+
+        loudness.measured_lufs = Some(-70.0);
+        assert_eq!(loudness.gain_db(), Some(MAX_NORMALIZATION_GAIN_DB));
+    }
+
+    #[test]
+    fn normalization_follows_the_export_settings_only() {
+        let mut config = Config::default();
+        let loudness = LoudnessNormalization::from_config(&config).expect("on by default");
+        assert_eq!(loudness.target_lufs, -16.0);
+        assert_eq!(loudness.true_peak_limit_dbtp, -1.0);
+        assert!(loudness.limiter_enabled);
+
+        config.audio.normalization_enabled = false;
+        config.audio.target_lufs = -23;
+        config.audio.true_peak_limiter_enabled = false;
+        config.export.target_lufs = -14;
+        config.export.true_peak_limiter_enabled = false;
+        let loudness = LoudnessNormalization::from_config(&config).expect("export switch is on");
+        assert_eq!(loudness.target_lufs, -14.0);
+        assert!(!loudness.limiter_enabled);
+
+        config.audio.normalization_enabled = true;
+        config.export.normalize_loudness = false;
+        assert!(LoudnessNormalization::from_config(&config).is_none());
+    }
+
+    #[test]
+    fn default_mix_passes_audio_through() {
+        let mix = ExportAudioMix::default();
+        assert!(mix.is_passthrough());
+        assert!(mix.range_filter_spec(0, 10.0, false, "fltp").is_none());
+        assert!(mix.validate().is_ok());
+
+        let muted = ExportAudioMix {
+            sources: vec![
+                AudioSourceMix::default(),
+                AudioSourceMix {
+                    gain_db: 0.0,
+                    muted: true,
+                },
+            ],
+            ..ExportAudioMix::default()
+        };
+        assert!(!muted.is_passthrough());
+        assert!(muted.range_filter_spec(0, 10.0, false, "fltp").is_none());
+        assert_eq!(
+            muted.range_filter_spec(1, 10.0, false, "fltp").as_deref(),
+            Some("[in]volume=0,aformat=sample_fmts=fltp[out]")
+        );
+    }
+
+    #[test]
+    fn fades_are_clamped_to_half_the_range() {
+        let mix = ExportAudioMix {
+            sources: vec![AudioSourceMix {
+                gain_db: -6.0,
+                muted: false,
+            }],
+            fade_in_secs: 0.5,
+            fade_out_secs: 2.0,
+            ..ExportAudioMix::default()
+        };
+        assert_eq!(
+            mix.range_filter_spec(0, 3.0, false, "s16").as_deref(),
+            Some("[in]volume=-6.00dB,afade=t=in:st=0:d=0.500,afade=t=out:st=1.500:d=1.500,aformat=sample_fmts=s16[out]")
+        );
+    }
+
+    #[test]
+    fn crossfade_mixes_the_previous_tail_under_the_fade_in() {
+        let mix = ExportAudioMix {
+            fade_in_secs: 0.01,
+            crossfade_secs: 0.2,
+            ..ExportAudioMix::default()
+        };
+        let spec = mix.range_filter_spec(0, 5.0, true, "fltp").unwrap();
+        assert_eq!(
+            spec,
+            "[in]afade=t=in:st=0:d=0.200[main];[tail]afade=t=out:st=0:d=0.200[faded];[main][faded]amix=inputs=2:duration=first:normalize=0,aformat=sample_fmts=fltp[out]"
+        );
+        // The first range has no tail to fade out.
+        assert_eq!(
+            mix.range_filter_spec(0, 5.0, false, "fltp").as_deref(),
+            Some("[in]afade=t=in:st=0:d=0.010,aformat=sample_fmts=fltp[out]")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_settings() {
+        let mut mix = ExportAudioMix {
+            crossfade_secs: 1.0,
+            ..ExportAudioMix::default()
+        };
+        assert!(mix.validate().is_err());
+        mix.crossfade_secs = 0.1;
+        mix.sources.push(AudioSourceMix {
+            gain_db: 40.0,
+            muted: false,
+        });
+        assert!(mix.validate().is_err());
+        mix.sources[0].gain_db = 6.0;
+        assert!(mix.validate().is_ok());
+    }
+}
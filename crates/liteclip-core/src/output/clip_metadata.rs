@@ -0,0 +1,221 @@
+//! Recording context stored with saved clips.
+//!
+//! [`ClipMetadata`] records what produced a clip: the game, the encoder actually used after
+//! auto-selection and fallback, configured vs. measured framerate, the replay window and the
+//! frames missing from the clip. The muxer writes it as container tags (see
+//! [`ClipMetadata::tags`]); it can also be written as a JSON sidecar at
+//! [`metadata_sidecar_path`](super::companion_cache::metadata_sidecar_path), keyed like the
+//! thumbnail cache.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const TAG_GAME: &str = "liteclip_game";
+const TAG_ENCODER: &str = "liteclip_encoder";
+const TAG_CONFIGURED_FPS: &str = "liteclip_configured_fps";
+const TAG_ACTUAL_FPS: &str = "liteclip_actual_fps";
+const TAG_REPLAY_WINDOW_SECS: &str = "liteclip_replay_window_secs";
+const TAG_DROPPED_FRAMES: &str = "liteclip_dropped_frames";
+
+/// Recording context of a saved clip. Every field is optional so partial tags and sidecars
+/// from older versions still read back.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClipMetadata {
+    /// Game the clip was recorded in, if one was detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    /// FFmpeg name of the video encoder that produced the clip (e.g. `hevc_nvenc`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoder: Option<String>,
+    /// Framerate requested in the settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub configured_fps: Option<u32>,
+    /// Framerate measured over the clip's video.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_fps: Option<f64>,
+    /// Replay duration setting at save time, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_window_secs: Option<u32>,
+    /// Frames missing from the clip relative to the configured framerate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dropped_frames: Option<u64>,
+}
+
+impl ClipMetadata {
+    /// Builds the metadata for a save from the replay settings.
+    ///
+    /// The actual framerate and dropped-frame count depend on the packets the save writes;
+    /// the saver fills them in with [`Self::with_video_timing`].
+    #[must_use]
+    pub fn from_replay(
+        configured_fps: u32,
+        replay_window_secs: u32,
+        game: Option<&str>,
+        encoder: Option<String>,
+    ) -> Self {
+        Self {
+            game: game.filter(|game| !game.is_empty()).map(str::to_string),
+            encoder,
+            configured_fps: Some(configured_fps),
+            replay_window_secs: Some(replay_window_secs),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the actual framerate and dropped-frame count from the clip's video: `frames`
+    /// frames, `span_secs` apart from the first to the last. Both stay unset for clips too
+    /// short to measure; dropped frames also need the configured framerate.
+    #[must_use]
+    pub fn with_video_timing(mut self, frames: usize, span_secs: f64) -> Self {
+        if frames > 1 && span_secs > 0.0 {
+            // N frames span N - 1 frame intervals.
+            let intervals = (frames - 1) as f64;
+            self.actual_fps = Some(intervals / span_secs);
+            self.dropped_frames = self.configured_fps.map(|fps| {
+                let expected = (span_secs * f64::from(fps)).round();
+                (expected - intervals).max(0.0) as u64
+            });
+        } else {
+            self.actual_fps = None;
+            self.dropped_frames = None;
+        }
+        self
+    }
+
+    /// Container tags for the set fields.
+    #[must_use]
+    pub fn tags(&self) -> Vec<(&'static str, String)> {
+        let mut tags = Vec::new();
+        if let Some(game) = &self.game {
+            tags.push((TAG_GAME, game.clone()));
+        }
+        if let Some(encoder) = &self.encoder {
+            tags.push((TAG_ENCODER, encoder.clone()));
+        }
+        if let Some(fps) = self.configured_fps {
+            tags.push((TAG_CONFIGURED_FPS, fps.to_string()));
+        }
+        if let Some(fps) = self.actual_fps {
+            tags.push((TAG_ACTUAL_FPS, format!("{fps:.2}")));
+        }
+        if let Some(secs) = self.replay_window_secs {
+            tags.push((TAG_REPLAY_WINDOW_SECS, secs.to_string()));
+        }
+        if let Some(frames) = self.dropped_frames {
+            tags.push((TAG_DROPPED_FRAMES, frames.to_string()));
+        }
+        tags
+    }
+
+    /// Reads the metadata back from container tags; `None` when none of our tags are present.
+    ///
+    /// Keys match case-insensitively, since some containers change the case of tag names.
+    #[must_use]
+    pub fn from_tags<'a>(tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<Self> {
+        let mut metadata = Self::default();
+        let mut found = false;
+        for (key, value) in tags {
+            let value = value.trim();
+            let key = key.to_ascii_lowercase();
+            match key.as_str() {
+                TAG_GAME => metadata.game = Some(value.to_string()),
+                TAG_ENCODER => metadata.encoder = Some(value.to_string()),
+                TAG_CONFIGURED_FPS => metadata.configured_fps = value.parse().ok(),
+                TAG_ACTUAL_FPS => metadata.actual_fps = value.parse().ok(),
+                TAG_REPLAY_WINDOW_SECS => metadata.replay_window_secs = value.parse().ok(),
+                TAG_DROPPED_FRAMES => metadata.dropped_frames = value.parse().ok(),
+                _ => continue,
+            }
+            found = true;
+        }
+        found.then_some(metadata)
+    }
+
+    /// Writes the metadata as pretty-printed JSON to `path`, creating its directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or file cannot be written.
+    pub fn write_sidecar(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create sidecar directory: {:?}", parent))?;
+        }
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize metadata")?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write metadata sidecar {:?}", path))
+    }
+
+    /// Reads a sidecar written by [`Self::write_sidecar`]; `None` if it is missing or invalid.
+    #[must_use]
+    pub fn read_sidecar(path: &Path) -> Option<Self> {
+        let json = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_timing_measures_framerate_and_missing_frames() {
+        // 10 s at 60 fps should hold 601 frames; 541 means 60 are missing.
+        let metadata =
+            ClipMetadata::from_replay(60, 30, Some("Valorant"), None).with_video_timing(541, 10.0);
+        assert_eq!(metadata.game.as_deref(), Some("Valorant"));
+        assert_eq!(metadata.configured_fps, Some(60));
+        assert_eq!(metadata.replay_window_secs, Some(30));
+        assert_eq!(metadata.dropped_frames, Some(60));
+        assert!((metadata.actual_fps.unwrap() - 54.0).abs() < 1e-9);
+
+        let empty = ClipMetadata::from_replay(60, 30, Some(""), None).with_video_timing(1, 0.0);
+        assert_eq!(empty.game, None);
+        assert_eq!(empty.actual_fps, None);
+        assert_eq!(empty.dropped_frames, None);
+
+        let unconfigured = ClipMetadata::default().with_video_timing(31, 1.0);
+        assert!((unconfigured.actual_fps.unwrap() - 30.0).abs() < 1e-9);
+        assert_eq!(unconfigured.dropped_frames, None);
+    }
+
+    #[test]
+    fn tags_round_trip() {
+        let metadata = ClipMetadata {
+            game: Some("Apex Legends".to_string()),
+            encoder: Some("hevc_nvenc".to_string()),
+            configured_fps: Some(60),
+            actual_fps: Some(59.5),
+            replay_window_secs: Some(30),
+            dropped_frames: Some(12),
+        };
+        let tags = metadata.tags();
+        let parsed = ClipMetadata::from_tags(tags.iter().map(|(k, v)| (*k, v.as_str())));
+        assert_eq!(parsed, Some(metadata));
+
+        let upper = [("LITECLIP_ENCODER", "libx265"), ("encoder", "Lavf61")];
+        assert_eq!(
+            ClipMetadata::from_tags(upper).and_then(|m| m.encoder),
+            Some("libx265".to_string())
+        );
+        assert_eq!(ClipMetadata::from_tags([("encoder", "Lavf61")]), None);
+    }
+
+    #[test]
+    fn sidecar_round_trip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join(".cache").join("clip.json");
+        let metadata = ClipMetadata {
+            encoder: Some("hevc_amf".to_string()),
+            configured_fps: Some(144),
+            ..ClipMetadata::default()
+        };
+        metadata.write_sidecar(&path).unwrap();
+        assert_eq!(ClipMetadata::read_sidecar(&path), Some(metadata));
+        assert_eq!(
+            ClipMetadata::read_sidecar(&temp.path().join("missing.json")),
+            None
+        );
+    }
+}
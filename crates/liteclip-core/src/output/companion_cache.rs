@@ -2,7 +2,7 @@
 
 use rustc_hash::FxHasher;
 use std::hash::{Hash, Hasher};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Stable hash for a main video path (same algorithm as thumbnail cache).
 pub fn hash_main_video_path(video_path: &Path) -> u64 {
@@ -11,6 +11,14 @@ pub fn hash_main_video_path(video_path: &Path) -> u64 {
     hasher.finish()
 }
 
+/// JSON metadata sidecar for `video_path`: `<save_directory>/.cache/<hash>.json`, next to
+/// the thumbnail.
+pub fn metadata_sidecar_path(save_directory: &Path, video_path: &Path) -> PathBuf {
+    save_directory
+        .join(".cache")
+        .join(format!("{:016x}.json", hash_main_video_path(video_path)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -30,6 +38,17 @@ mod tests {
         assert_ne!(hash_main_video_path(path1), hash_main_video_path(path2));
     }
 
+    #[test]
+    fn sidecar_path_is_keyed_by_video_hash() {
+        let video = Path::new("C:/Videos/clips/test.mp4");
+        let sidecar = metadata_sidecar_path(Path::new("C:/Videos"), video);
+        assert_eq!(
+            sidecar,
+            Path::new("C:/Videos/.cache")
+                .join(format!("{:016x}.json", hash_main_video_path(video)))
+        );
+    }
+
     #[test]
     fn case_sensitive_paths() {
         let path1 = Path::new("C:/Videos/Clip.mp4");
@@ -0,0 +1,994 @@
+//! Audio side of the re-encoding clip export
+//!
+//! [`ExportAudio`] decodes the kept ranges of a clip's audio tracks, resamples them to the
+//! encoder format and encodes them into the output file. Which input tracks are read, and
+//! whether each becomes its own output stream or all are mixed into one with `amix`, follows
+//! the request's [`ExportAudioTracks`]. Containers that hold a single audio stream always
+//! get the mix.
+//!
+//! Like the video side of [`attempt_export`](super::sdk_export::attempt_export), decoders are
+//! recreated for every kept range (after the seek) while resamplers, the mixer and encoders
+//! live for the whole export. Ranges that play at another speed also get an
+//! [`AudioRetimeGraph`] per track, between the resampler and the mixer or encoder, and
+//! ranges the request's [`ExportAudioMix`] changes get an [`AudioEditGraph`] per track
+//! after that for the gain and fades. For crossfades, the audio decoded just past the end of
+//! a range is kept and faded out under the start of the next one. With loudness
+//! normalization, every output runs its frames through an [`AudioLoudnessGraph`] applying
+//! the gain measured by the first pass (see [`loudness`](super::loudness)) before encoding.
+
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+use std::time::Instant;
+
+use super::audio_mix::{ExportAudioMix, LoudnessNormalization};
+use super::retime::AudioRetimeGraph;
+use super::sdk_export::{
+    audio_codec_for_container, audio_frame_samples_for_container, INVALID_DURATION,
+};
+use super::video_file::{ClipExportRequest, ExportAudioTracks, ExportContainerFormat, TimeRange};
+
+/// Sample rate of every exported audio stream.
+pub(super) const EXPORT_AUDIO_RATE: i32 = 48_000;
+/// Minimum bitrate given to each exported audio stream.
+pub(super) const MIN_TRACK_BITRATE_KBPS: u32 = 48;
+
+/// An audio stream of the input file that is being exported.
+struct AudioInput {
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    /// Recreated for every kept range, after the seek.
+    decoder: Option<ffmpeg::decoder::Audio>,
+    resampler: ffmpeg::software::resampling::Context,
+    /// Output stream this input is encoded into, or mixer input it feeds when mixing.
+    target: usize,
+    /// A packet past the end of the current range has been read.
+    past_range_end: bool,
+    /// Set while the current range plays at another speed.
+    retime: Option<AudioRetimeGraph>,
+    /// Samples the retime graph has produced in the current range.
+    retimed_samples: i64,
+    /// Set while the current range has its gain or fades changed.
+    edit: Option<AudioEditGraph>,
+    /// Samples the edit graph has produced in the current range.
+    edited_samples: i64,
+    /// Whether the audio past the end of the current range is kept for a crossfade.
+    collect_tail: bool,
+    /// Resampled audio from just past the end of the current range, stamped from the range
+    /// end, which fades out under the start of the next range.
+    tail: Vec<ffmpeg::frame::Audio>,
+}
+
+/// An encoded audio stream of the output file.
+pub(super) struct AudioOutput {
+    encoder: ffmpeg::encoder::Audio,
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    default_duration: i64,
+    next_pts: i64,
+    next_dts: i64,
+    /// Applies the loudness normalization gain and limiter, when normalizing.
+    loudness: Option<AudioLoudnessGraph>,
+    /// Cuts frames to the encoder's frame size. Unset when mixing without normalizing (the
+    /// mixer does it) or when the encoder takes frames of any size.
+    framer: Option<AudioFramer>,
+}
+
+impl AudioOutput {
+    /// Opens an audio encoder for `container` and adds its output stream. Unless `framed`
+    /// (frames already come in the encoder's frame size), a framer is set up when the
+    /// encoder needs one. `loudness_chain` is the second pass of loudness normalization,
+    /// whose output is always framed.
+    pub(super) fn open(
+        output_ctx: &mut ffmpeg::format::context::Output,
+        container: ExportContainerFormat,
+        bitrate_kbps: u32,
+        framed: bool,
+        loudness_chain: Option<&str>,
+    ) -> Result<Self> {
+        let (encoder, stream_index) = open_output_stream(output_ctx, container, bitrate_kbps)?;
+        let loudness = loudness_chain
+            .map(|chain| AudioLoudnessGraph::new(chain, &encoder))
+            .transpose()?;
+        let framer = if framed && loudness.is_none() {
+            None
+        } else {
+            AudioFramer::new(&encoder)?
+        };
+        Ok(Self {
+            encoder,
+            stream_index,
+            time_base: ffmpeg::Rational(1, EXPORT_AUDIO_RATE),
+            default_duration: audio_frame_samples_for_container(container),
+            next_pts: 0,
+            next_dts: 0,
+            loudness,
+            framer,
+        })
+    }
+
+    pub(super) fn encoder(&self) -> &ffmpeg::encoder::Audio {
+        &self.encoder
+    }
+
+    /// Re-reads the output time base, which the muxer may change when writing the header.
+    pub(super) fn after_header(
+        &mut self,
+        output_ctx: &ffmpeg::format::context::Output,
+        container: ExportContainerFormat,
+    ) -> Result<()> {
+        let time_base = output_ctx
+            .stream(self.stream_index)
+            .context("Missing output audio stream")?
+            .time_base();
+        let ticks_per_second =
+            f64::from(time_base.denominator()) / f64::from(time_base.numerator().max(1));
+        self.time_base = time_base;
+        self.default_duration = ((audio_frame_samples_for_container(container) as f64)
+            * (ticks_per_second / f64::from(EXPORT_AUDIO_RATE)))
+        .round()
+        .max(1.0) as i64;
+        Ok(())
+    }
+
+    /// Sends `frame` (or end of stream for `None`) to the encoder, through the loudness
+    /// graph and the framer if there are any, and writes what it returns.
+    pub(super) fn encode(
+        &mut self,
+        frame: Option<&ffmpeg::frame::Audio>,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let Some(mut loudness) = self.loudness.take() else {
+            return self.encode_framed(frame, output_ctx);
+        };
+        let result = loudness.push(frame).and_then(|()| {
+            while loudness.sink.sink().frame(&mut loudness.normalized).is_ok() {
+                self.encode_framed(Some(&loudness.normalized), output_ctx)?;
+            }
+            match frame {
+                Some(_) => Ok(()),
+                None => self.encode_framed(None, output_ctx),
+            }
+        });
+        self.loudness = Some(loudness);
+        result
+    }
+
+    /// Sends `frame` (or end of stream for `None`) to the encoder, through the framer if
+    /// there is one, and writes what it returns.
+    fn encode_framed(
+        &mut self,
+        frame: Option<&ffmpeg::frame::Audio>,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let Some(mut framer) = self.framer.take() else {
+            return self.encode_frame(frame, output_ctx);
+        };
+        let result = framer.push(frame).and_then(|()| {
+            while framer.sink.sink().frame(&mut framer.framed).is_ok() {
+                self.encode_frame(Some(&framer.framed), output_ctx)?;
+            }
+            match frame {
+                Some(_) => Ok(()),
+                None => self.encode_frame(None, output_ctx),
+            }
+        });
+        self.framer = Some(framer);
+        result
+    }
+
+    fn encode_frame(
+        &mut self,
+        frame: Option<&ffmpeg::frame::Audio>,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        match frame {
+            Some(frame) => self.encoder.send_frame(frame)?,
+            None => self.encoder.send_eof()?,
+        }
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            packet.rescale_ts(self.encoder.time_base(), self.time_base);
+            if packet.pts().is_none() {
+                packet.set_pts(Some(self.next_pts));
+            }
+            if packet.dts().is_none() {
+                packet.set_dts(Some(self.next_dts));
+            }
+            if packet.duration() <= 0 || packet.duration() == INVALID_DURATION {
+                packet.set_duration(self.default_duration);
+            }
+            let fixed_dts = packet.dts().unwrap_or(self.next_dts).max(self.next_dts);
+            let fixed_pts = packet.pts().unwrap_or(fixed_dts).max(fixed_dts);
+            packet.set_dts(Some(fixed_dts));
+            packet.set_pts(Some(fixed_pts));
+            packet.set_stream(self.stream_index);
+            packet.write_interleaved(output_ctx)?;
+            self.next_dts = fixed_dts.saturating_add(packet.duration().max(1));
+            self.next_pts = fixed_pts.saturating_add(packet.duration().max(1));
+        }
+        Ok(())
+    }
+}
+
+/// Filter graph regrouping the samples of one resampled track into frames of the encoder's
+/// fixed frame size (`abuffer → abuffersink`).
+///
+/// Decoded frames only happen to match the encoder when source and output codec share a
+/// frame size, which is not the case for Opus or FLAC output.
+struct AudioFramer {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+    framed: ffmpeg::frame::Audio,
+}
+
+impl AudioFramer {
+    /// Returns `None` when the encoder takes frames of any size.
+    fn new(encoder: &ffmpeg::encoder::Audio) -> Result<Option<Self>> {
+        if encoder.frame_size() == 0 {
+            return Ok(None);
+        }
+        let mut graph = ffmpeg::filter::Graph::new();
+        let abuffer = ffmpeg::filter::find("abuffer").context("abuffer filter not found")?;
+        let abuffersink =
+            ffmpeg::filter::find("abuffersink").context("abuffersink filter not found")?;
+        let mut source = graph
+            .add(&abuffer, "in", &abuffer_args(encoder))
+            .context("Failed to add abuffer filter to graph")?;
+        let mut sink = graph
+            .add(&abuffersink, "out", "")
+            .context("Failed to add abuffersink filter to graph")?;
+        source.link(0, &mut sink, 0);
+        graph
+            .validate()
+            .context("Failed to validate audio framing filter graph")?;
+        sink.sink().set_frame_size(encoder.frame_size());
+
+        Ok(Some(Self {
+            graph,
+            source,
+            sink,
+            framed: ffmpeg::frame::Audio::empty(),
+        }))
+    }
+
+    /// Pushes `frame` into the graph, or flushes it for `None`.
+    fn push(&mut self, frame: Option<&ffmpeg::frame::Audio>) -> Result<()> {
+        match frame {
+            Some(frame) => self
+                .source
+                .source()
+                .add(frame)
+                .context("Failed to push audio into framing graph"),
+            None => self
+                .source
+                .source()
+                .flush()
+                .context("Failed to flush audio framing graph"),
+        }
+    }
+}
+
+/// Second pass of loudness normalization for one output (`abuffer → volume [→ alimiter] →
+/// aformat → abuffersink`), in the encoder's sample format.
+struct AudioLoudnessGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+    normalized: ffmpeg::frame::Audio,
+}
+
+impl AudioLoudnessGraph {
+    /// `chain` is [`LoudnessNormalization::filter_chain`].
+    fn new(chain: &str, encoder: &ffmpeg::encoder::Audio) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let abuffer = ffmpeg::filter::find("abuffer").context("abuffer filter not found")?;
+        let abuffersink =
+            ffmpeg::filter::find("abuffersink").context("abuffersink filter not found")?;
+        let source = graph
+            .add(&abuffer, "in", &abuffer_args(encoder))
+            .context("Failed to add abuffer filter to graph")?;
+        let sink = graph
+            .add(&abuffersink, "out", "")
+            .context("Failed to add abuffersink filter to graph")?;
+        let spec = format!("{chain},aformat=sample_fmts={}", encoder.format().name());
+        graph
+            .output("in", 0)?
+            .input("out", 0)?
+            .parse(&spec)
+            .with_context(|| format!("Failed to parse loudness filter: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate loudness filter graph")?;
+
+        Ok(Self {
+            graph,
+            source,
+            sink,
+            normalized: ffmpeg::frame::Audio::empty(),
+        })
+    }
+
+    /// Pushes `frame` into the graph, or flushes it for `None`.
+    fn push(&mut self, frame: Option<&ffmpeg::frame::Audio>) -> Result<()> {
+        match frame {
+            Some(frame) => self
+                .source
+                .source()
+                .add(frame)
+                .context("Failed to push audio into loudness graph"),
+            None => self
+                .source
+                .source()
+                .flush()
+                .context("Failed to flush loudness graph"),
+        }
+    }
+}
+
+/// Filter graph mixing several resampled tracks into one.
+///
+/// `abuffer × N → amix → abuffersink`, with the sink cutting frames to the encoder's frame
+/// size.
+struct AudioMixGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    sources: Vec<ffmpeg::filter::Context>,
+    sink: ffmpeg::filter::Context,
+}
+
+impl AudioMixGraph {
+    fn new(inputs: usize, encoder: &ffmpeg::encoder::Audio) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let abuffer = ffmpeg::filter::find("abuffer").context("abuffer filter not found")?;
+        let amix = ffmpeg::filter::find("amix").context("amix filter not found")?;
+        let abuffersink =
+            ffmpeg::filter::find("abuffersink").context("abuffersink filter not found")?;
+
+        let source_args = abuffer_args(encoder);
+        let mut mix = graph
+            .add(
+                &amix,
+                "mix",
+                &format!("inputs={inputs}:duration=longest:normalize=0"),
+            )
+            .context("Failed to add amix filter")?;
+        let mut sources = Vec::with_capacity(inputs);
+        for index in 0..inputs {
+            let mut source = graph
+                .add(&abuffer, &format!("in{index}"), &source_args)
+                .context("Failed to add abuffer filter to graph")?;
+            source.link(0, &mut mix, index as u32);
+            sources.push(source);
+        }
+        let mut sink = graph
+            .add(&abuffersink, "out", "")
+            .context("Failed to add abuffersink filter to graph")?;
+        mix.link(0, &mut sink, 0);
+
+        graph
+            .validate()
+            .context("Failed to validate audio mix filter graph")?;
+        if encoder.frame_size() > 0 {
+            sink.sink().set_frame_size(encoder.frame_size());
+        }
+
+        Ok(Self {
+            graph,
+            sources,
+            sink,
+        })
+    }
+
+    /// Encodes every frame the mixer has ready into `output`.
+    fn drain_into(
+        &mut self,
+        frame: &mut ffmpeg::frame::Audio,
+        output: &mut AudioOutput,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        while self.sink.sink().frame(frame).is_ok() {
+            output.encode(Some(frame), output_ctx)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the gain and fades of an [`ExportAudioMix`] to one track over one kept range
+/// (`abuffer [+ abuffer for the previous range's tail] → volume / afade… [→ amix] →
+/// aformat → abuffersink`), in the encoder's sample format.
+struct AudioEditGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+}
+
+impl AudioEditGraph {
+    /// Builds the graph for `spec` (see [`ExportAudioMix::range_filter_spec`]). `tail`
+    /// frames are pushed into the graph straight away.
+    fn new(spec: &str, source_args: &str, tail: Vec<ffmpeg::frame::Audio>) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let abuffer = ffmpeg::filter::find("abuffer").context("abuffer filter not found")?;
+        let abuffersink =
+            ffmpeg::filter::find("abuffersink").context("abuffersink filter not found")?;
+        let source = graph
+            .add(&abuffer, "in", source_args)
+            .context("Failed to add abuffer filter to graph")?;
+        let mut tail_source = if tail.is_empty() {
+            None
+        } else {
+            Some(
+                graph
+                    .add(&abuffer, "tail", source_args)
+                    .context("Failed to add abuffer filter to graph")?,
+            )
+        };
+        let sink = graph
+            .add(&abuffersink, "out", "")
+            .context("Failed to add abuffersink filter to graph")?;
+        let mut parser = graph.output("in", 0)?;
+        if tail_source.is_some() {
+            parser = parser.output("tail", 0)?;
+        }
+        parser
+            .input("out", 0)?
+            .parse(spec)
+            .with_context(|| format!("Failed to parse audio edit filter: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate audio edit filter graph")?;
+
+        if let Some(tail_source) = tail_source.as_mut() {
+            for frame in &tail {
+                tail_source
+                    .source()
+                    .add(frame)
+                    .context("Failed to push crossfade audio into edit graph")?;
+            }
+            tail_source
+                .source()
+                .flush()
+                .context("Failed to flush crossfade audio source")?;
+        }
+
+        Ok(Self {
+            graph,
+            source,
+            sink,
+        })
+    }
+
+    /// Pushes a frame, or flushes the graph at the end of the range for `None`.
+    fn push(&mut self, frame: Option<&ffmpeg::frame::Audio>) -> Result<()> {
+        match frame {
+            Some(frame) => self
+                .source
+                .source()
+                .add(frame)
+                .context("Failed to push audio into edit graph"),
+            None => self
+                .source
+                .source()
+                .flush()
+                .context("Failed to flush audio edit graph"),
+        }
+    }
+
+    /// Takes the next edited frame, if one is ready.
+    fn pull(&mut self, frame: &mut ffmpeg::frame::Audio) -> bool {
+        self.sink.sink().frame(frame).is_ok()
+    }
+}
+
+/// Decodes, resamples, optionally mixes and encodes the audio of a re-encoding export.
+pub(super) struct ExportAudio {
+    inputs: Vec<AudioInput>,
+    outputs: Vec<AudioOutput>,
+    mixer: Option<AudioMixGraph>,
+    mix: ExportAudioMix,
+    /// Number of kept ranges, and how many have been started.
+    range_count: usize,
+    ranges_started: usize,
+    decoded: ffmpeg::frame::Audio,
+    mixed: ffmpeg::frame::Audio,
+    retimed: ffmpeg::frame::Audio,
+    edited: ffmpeg::frame::Audio,
+    /// Time spent resampling, mixing and encoding.
+    pub(super) encode_elapsed_secs: f64,
+}
+
+impl ExportAudio {
+    /// Opens the audio encoders and adds their streams to `output_ctx`.
+    ///
+    /// Returns `None` when the request has no audio to export. `audio_bitrate_kbps` is the
+    /// total audio budget, split evenly across the output streams.
+    pub(super) fn new(
+        input_ctx: &ffmpeg::format::context::Input,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        request: &ClipExportRequest,
+        audio_bitrate_kbps: u32,
+    ) -> Result<Option<Self>> {
+        if !request.metadata.has_audio {
+            return Ok(None);
+        }
+        let mut streams: Vec<_> = input_ctx
+            .streams()
+            .filter(|stream| stream.parameters().medium() == ffmpeg::media::Type::Audio)
+            .collect();
+        if request.audio_tracks == ExportAudioTracks::PrimaryOnly {
+            streams.truncate(1);
+        }
+        if streams.is_empty() {
+            return Ok(None);
+        }
+
+        let mix = streams.len() > 1
+            && (request.audio_tracks == ExportAudioTracks::Mix
+                || !request.container_format.supports_multiple_audio_tracks());
+        let output_count = if mix { 1 } else { streams.len() };
+        let bitrate_kbps = (audio_bitrate_kbps / output_count as u32).max(MIN_TRACK_BITRATE_KBPS);
+        let loudness_chain = request
+            .loudness
+            .as_ref()
+            .and_then(LoudnessNormalization::filter_chain);
+
+        let mut outputs = Vec::with_capacity(output_count);
+        for stream in streams.iter().take(output_count) {
+            // The mixer already cuts frames to the encoder's frame size.
+            let output = AudioOutput::open(
+                output_ctx,
+                request.container_format,
+                bitrate_kbps,
+                mix,
+                loudness_chain.as_deref(),
+            )?;
+            if !mix {
+                // Track title and language.
+                let mut out_stream = output_ctx
+                    .stream_mut(output.stream_index)
+                    .context("Missing output audio stream")?;
+                out_stream.set_metadata(stream.metadata().to_owned());
+            }
+            outputs.push(output);
+        }
+
+        let encoder = &outputs[0].encoder;
+        let mut inputs = Vec::with_capacity(streams.len());
+        for (index, stream) in streams.iter().enumerate() {
+            let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+            let decoder = context.decoder().audio()?;
+            let resampler = ffmpeg::software::resampling::Context::get(
+                decoder.format(),
+                decoder.channel_layout(),
+                decoder.rate(),
+                encoder.format(),
+                encoder.channel_layout(),
+                encoder.rate(),
+            )?;
+            inputs.push(AudioInput {
+                stream_index: stream.index(),
+                time_base: stream.time_base(),
+                decoder: None,
+                resampler,
+                target: index,
+                past_range_end: false,
+                retime: None,
+                retimed_samples: 0,
+                edit: None,
+                edited_samples: 0,
+                collect_tail: false,
+                tail: Vec::new(),
+            });
+        }
+        let mixer = if mix {
+            Some(AudioMixGraph::new(inputs.len(), encoder)?)
+        } else {
+            None
+        };
+
+        Ok(Some(Self {
+            inputs,
+            outputs,
+            mixer,
+            mix: request.audio.clone(),
+            range_count: request.keep_ranges.len(),
+            ranges_started: 0,
+            decoded: ffmpeg::frame::Audio::empty(),
+            mixed: ffmpeg::frame::Audio::empty(),
+            retimed: ffmpeg::frame::Audio::empty(),
+            edited: ffmpeg::frame::Audio::empty(),
+            encode_elapsed_secs: 0.0,
+        }))
+    }
+
+    /// Re-reads the output time bases, which the muxer may change when writing the header.
+    pub(super) fn after_header(
+        &mut self,
+        output_ctx: &ffmpeg::format::context::Output,
+        container: ExportContainerFormat,
+    ) -> Result<()> {
+        for output in &mut self.outputs {
+            output.after_header(output_ctx, container)?;
+        }
+        Ok(())
+    }
+
+    /// Whether packets of input stream `stream_index` belong to the export.
+    pub(super) fn handles(&self, stream_index: usize) -> bool {
+        self.inputs
+            .iter()
+            .any(|input| input.stream_index == stream_index)
+    }
+
+    /// Whether every exported track has been read past the end of the current range.
+    pub(super) fn past_range_end(&self) -> bool {
+        self.inputs.iter().all(|input| input.past_range_end)
+    }
+
+    /// Recreates the decoders after seeking to the start of `range`, and sets up retiming
+    /// when the range plays at another speed and the gain and fades of the audio mix.
+    ///
+    /// Ranges must be started in order: crossfades join each range to the one before.
+    pub(super) fn start_range(
+        &mut self,
+        input_ctx: &ffmpeg::format::context::Input,
+        range: &TimeRange,
+    ) -> Result<()> {
+        let retime_args = abuffer_args(&self.outputs[0].encoder);
+        let sample_format = self.outputs[0].encoder.format().name();
+        self.ranges_started += 1;
+        let is_last_range = self.ranges_started >= self.range_count;
+        for (source, input) in self.inputs.iter_mut().enumerate() {
+            let stream = input_ctx.stream(input.stream_index).with_context(|| {
+                format!("missing audio stream {} after seek", input.stream_index)
+            })?;
+            let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+            input.decoder = Some(context.decoder().audio()?);
+            input.past_range_end = false;
+            input.retime = if range.is_retimed() {
+                Some(AudioRetimeGraph::new(
+                    range.effective_speed(),
+                    &retime_args,
+                )?)
+            } else {
+                None
+            };
+            input.retimed_samples = 0;
+
+            // Audio cut off after retimed ranges is not crossfaded: it would play at
+            // normal speed under the next range.
+            input.collect_tail =
+                self.mix.crossfade().is_some() && !is_last_range && !range.is_retimed();
+            let tail = std::mem::take(&mut input.tail);
+            input.edit = match self.mix.range_filter_spec(
+                source,
+                range.output_duration_secs(),
+                !tail.is_empty(),
+                sample_format,
+            ) {
+                Some(spec) => Some(AudioEditGraph::new(&spec, &retime_args, tail)?),
+                None => None,
+            };
+            input.edited_samples = 0;
+        }
+        Ok(())
+    }
+
+    /// Decodes a packet of input stream `stream_index` and encodes the samples inside `range`.
+    pub(super) fn send_packet(
+        &mut self,
+        stream_index: usize,
+        packet: &ffmpeg::Packet,
+        range: &TimeRange,
+        range_output_start_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let Some(index) = self
+            .inputs
+            .iter()
+            .position(|input| input.stream_index == stream_index)
+        else {
+            return Ok(());
+        };
+        let input = &mut self.inputs[index];
+        if let Some(pts) = packet.pts() {
+            if timestamp_secs(pts, input.time_base) > range.end_secs + 0.5 {
+                input.past_range_end = true;
+            }
+        }
+        if let Some(decoder) = input.decoder.as_mut() {
+            decoder.send_packet(packet)?;
+        }
+        self.receive_frames(index, range, range_output_start_secs, output_ctx)
+    }
+
+    /// Drains the decoders at the end of a kept range.
+    pub(super) fn finish_range(
+        &mut self,
+        range: &TimeRange,
+        range_output_start_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        for index in 0..self.inputs.len() {
+            if let Some(decoder) = self.inputs[index].decoder.as_mut() {
+                decoder.send_eof()?;
+            }
+            self.receive_frames(index, range, range_output_start_secs, output_ctx)?;
+            self.inputs[index].decoder = None;
+            if let Some(retime) = self.inputs[index].retime.as_mut() {
+                retime.push(None)?;
+                self.deliver_retimed(index, range_output_start_secs, output_ctx)?;
+                self.inputs[index].retime = None;
+            }
+            if let Some(edit) = self.inputs[index].edit.as_mut() {
+                edit.push(None)?;
+                self.deliver_edited(index, range_output_start_secs, output_ctx)?;
+                self.inputs[index].edit = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the mixer and the encoders after the last range.
+    pub(super) fn finish(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        if let Some(mixer) = self.mixer.as_mut() {
+            for source in &mut mixer.sources {
+                source
+                    .source()
+                    .flush()
+                    .context("Failed to flush audio mix source")?;
+            }
+            mixer.drain_into(&mut self.mixed, &mut self.outputs[0], output_ctx)?;
+        }
+        for output in &mut self.outputs {
+            output.encode(None, output_ctx)?;
+        }
+        Ok(())
+    }
+
+    fn receive_frames(
+        &mut self,
+        index: usize,
+        range: &TimeRange,
+        range_output_start_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        loop {
+            let Some(decoder) = self.inputs[index].decoder.as_mut() else {
+                return Ok(());
+            };
+            if decoder.receive_frame(&mut self.decoded).is_err() {
+                return Ok(());
+            }
+            self.encode_decoded(index, range, range_output_start_secs, output_ctx)?;
+        }
+    }
+
+    /// Resamples the frame in `self.decoded` and encodes (or mixes) it if it lies in `range`,
+    /// retimed first when the range plays at another speed and edited when the mix changes
+    /// it. Frames just past the end of the range are kept for the crossfade instead.
+    fn encode_decoded(
+        &mut self,
+        index: usize,
+        range: &TimeRange,
+        range_output_start_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let input = &mut self.inputs[index];
+        let pts_secs = self
+            .decoded
+            .timestamp()
+            .map(|ts| timestamp_secs(ts, input.time_base))
+            .unwrap_or(0.0);
+        if pts_secs >= range.end_secs {
+            let crossfade = self.mix.crossfade().unwrap_or(0.0);
+            if input.collect_tail && pts_secs < range.end_secs + crossfade {
+                let mut resampled = ffmpeg::frame::Audio::empty();
+                input.resampler.run(&self.decoded, &mut resampled)?;
+                resampled.set_pts(Some(
+                    ((pts_secs - range.end_secs) * f64::from(EXPORT_AUDIO_RATE)) as i64,
+                ));
+                input.tail.push(resampled);
+            }
+            return Ok(());
+        }
+        if pts_secs < range.start_secs {
+            return Ok(());
+        }
+        let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs);
+
+        let encode_started_at = Instant::now();
+        let mut resampled = ffmpeg::frame::Audio::empty();
+        input.resampler.run(&self.decoded, &mut resampled)?;
+        let target = input.target;
+        if let Some(retime) = input.retime.as_mut() {
+            // Timestamps within the range; the retimed output is restamped.
+            resampled.set_pts(Some(
+                ((pts_secs - range.start_secs) * f64::from(EXPORT_AUDIO_RATE)) as i64,
+            ));
+            retime.push(Some(&resampled))?;
+            self.deliver_retimed(index, range_output_start_secs, output_ctx)?;
+        } else if let Some(edit) = input.edit.as_mut() {
+            resampled.set_pts(Some(
+                ((pts_secs - range.start_secs) * f64::from(EXPORT_AUDIO_RATE)) as i64,
+            ));
+            edit.push(Some(&resampled))?;
+            self.deliver_edited(index, range_output_start_secs, output_ctx)?;
+        } else {
+            resampled.set_pts(Some(
+                (output_pts_secs * f64::from(EXPORT_AUDIO_RATE)) as i64,
+            ));
+            deliver(
+                &resampled,
+                target,
+                self.mixer.as_mut(),
+                &mut self.mixed,
+                &mut self.outputs,
+                output_ctx,
+            )?;
+        }
+        self.encode_elapsed_secs += encode_started_at.elapsed().as_secs_f64();
+        Ok(())
+    }
+
+    /// Encodes (or mixes) every frame the retime graph of input `index` has ready, stamped
+    /// back to back from the start of the range in the output.
+    fn deliver_retimed(
+        &mut self,
+        index: usize,
+        range_output_start_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let range_start_sample =
+            (range_output_start_secs * f64::from(EXPORT_AUDIO_RATE)).round() as i64;
+        loop {
+            let input = &mut self.inputs[index];
+            let Some(retime) = input.retime.as_mut() else {
+                return Ok(());
+            };
+            if !retime.pull(&mut self.retimed) {
+                return Ok(());
+            }
+            if let Some(edit) = input.edit.as_mut() {
+                // The edit graph takes timestamps within the range.
+                self.retimed.set_pts(Some(input.retimed_samples));
+                input.retimed_samples += self.retimed.samples() as i64;
+                edit.push(Some(&self.retimed))?;
+                self.deliver_edited(index, range_output_start_secs, output_ctx)?;
+                continue;
+            }
+            self.retimed
+                .set_pts(Some(range_start_sample + input.retimed_samples));
+            input.retimed_samples += self.retimed.samples() as i64;
+            deliver(
+                &self.retimed,
+                input.target,
+                self.mixer.as_mut(),
+                &mut self.mixed,
+                &mut self.outputs,
+                output_ctx,
+            )?;
+        }
+    }
+
+    /// Encodes (or mixes) every frame the edit graph of input `index` has ready, stamped
+    /// back to back from the start of the range in the output.
+    fn deliver_edited(
+        &mut self,
+        index: usize,
+        range_output_start_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let range_start_sample =
+            (range_output_start_secs * f64::from(EXPORT_AUDIO_RATE)).round() as i64;
+        loop {
+            let input = &mut self.inputs[index];
+            let Some(edit) = input.edit.as_mut() else {
+                return Ok(());
+            };
+            if !edit.pull(&mut self.edited) {
+                return Ok(());
+            }
+            self.edited
+                .set_pts(Some(range_start_sample + input.edited_samples));
+            input.edited_samples += self.edited.samples() as i64;
+            deliver(
+                &self.edited,
+                input.target,
+                self.mixer.as_mut(),
+                &mut self.mixed,
+                &mut self.outputs,
+                output_ctx,
+            )?;
+        }
+    }
+}
+
+/// Pushes `frame` into mixer input `target`, or encodes it into output `target` when not
+/// mixing.
+fn deliver(
+    frame: &ffmpeg::frame::Audio,
+    target: usize,
+    mixer: Option<&mut AudioMixGraph>,
+    mixed: &mut ffmpeg::frame::Audio,
+    outputs: &mut [AudioOutput],
+    output_ctx: &mut ffmpeg::format::context::Output,
+) -> Result<()> {
+    match mixer {
+        Some(mixer) => {
+            mixer.sources[target]
+                .source()
+                .add(frame)
+                .context("Failed to push audio into mix graph")?;
+            mixer.drain_into(mixed, &mut outputs[0], output_ctx)
+        }
+        None => outputs[target].encode(Some(frame), output_ctx),
+    }
+}
+
+/// Opens an audio encoder for `container` and adds its output stream.
+fn open_output_stream(
+    output_ctx: &mut ffmpeg::format::context::Output,
+    container: ExportContainerFormat,
+    bitrate_kbps: u32,
+) -> Result<(ffmpeg::encoder::Audio, usize)> {
+    let (codec_id, codec_label) = audio_codec_for_container(container)
+        .context("No audio codec configured for this container format")?;
+    let codec = ffmpeg::encoder::find(codec_id)
+        .with_context(|| format!("{} encoder not found", codec_label))?;
+
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .audio()
+        .context("Failed to create audio encoder")?;
+    encoder.set_time_base((1, EXPORT_AUDIO_RATE));
+    encoder.set_bit_rate((bitrate_kbps * 1000) as usize);
+    encoder.set_rate(EXPORT_AUDIO_RATE);
+    encoder.set_channel_layout(ffmpeg::channel_layout::ChannelLayout::STEREO);
+    encoder.set_format(encoder_sample_format(codec));
+    let encoder = encoder.open().context("Failed to open audio encoder")?;
+
+    let mut stream = output_ctx
+        .add_stream(codec)
+        .context("Failed to add audio stream")?;
+    stream.set_time_base((1, EXPORT_AUDIO_RATE));
+    stream.set_parameters(&encoder);
+    Ok((encoder, stream.index()))
+}
+
+/// Picks planar float when the encoder supports it, else the encoder's first supported
+/// sample format (libopus wants packed samples, FLAC and PCM want integers).
+fn encoder_sample_format(codec: ffmpeg::Codec) -> ffmpeg::format::Sample {
+    const PREFERRED: ffmpeg::format::Sample =
+        ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar);
+    let supported: Vec<_> = codec
+        .audio()
+        .ok()
+        .and_then(|audio| audio.formats())
+        .map(|formats| formats.collect())
+        .unwrap_or_default();
+    if supported.is_empty() || supported.contains(&PREFERRED) {
+        PREFERRED
+    } else {
+        supported[0]
+    }
+}
+
+/// `abuffer` arguments for frames in the encoder's sample format.
+fn abuffer_args(encoder: &ffmpeg::encoder::Audio) -> String {
+    format!(
+        "time_base=1/{rate}:sample_rate={rate}:sample_fmt={format}:channel_layout=stereo",
+        rate = EXPORT_AUDIO_RATE,
+        format = encoder.format().name(),
+    )
+}
+
+fn timestamp_secs(ts: i64, time_base: ffmpeg::Rational) -> f64 {
+    ts as f64 * f64::from(time_base.numerator()) / f64::from(time_base.denominator())
+}
@@ -0,0 +1,357 @@
+//! Named, serializable export settings.
+//!
+//! An [`ExportPreset`] bundles the settings of a clip export that do not depend on the clip
+//! being exported: target size, audio bitrate, output height and framerate, container,
+//! audio track handling and overlays such as a watermark. [`ExportPreset::built_in`] lists
+//! the presets shipped with LiteClip; user presets are stored in
+//! [`crate::config::ExportConfig::custom_presets`].
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::EncoderType;
+
+use super::audio_mix::ExportAudioMix;
+use super::overlay::ExportOverlay;
+use super::video_file::{
+    ClipExportRequest, ExportAudioTracks, ExportContainerFormat, TimeRange, VideoFileMetadata,
+};
+
+/// Lowest audio bitrate a preset may ask for.
+pub const MIN_PRESET_AUDIO_BITRATE_KBPS: u32 = 48;
+/// Highest audio bitrate a preset may ask for.
+pub const MAX_PRESET_AUDIO_BITRATE_KBPS: u32 = 320;
+/// Highest output framerate a preset may ask for.
+const MAX_PRESET_FPS: f64 = 240.0;
+
+/// Named export settings that turn into a [`ClipExportRequest`] for any clip.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportPreset {
+    /// Name shown in the UI; unique among all presets.
+    pub name: String,
+    /// Re-encode to fit this size. `None` keeps the original quality: the kept ranges are
+    /// stream-copied when the container allows it, and re-encoded at the source's size
+    /// otherwise.
+    #[serde(default)]
+    pub target_size_mb: Option<u32>,
+    #[serde(default = "default_preset_audio_bitrate_kbps")]
+    pub audio_bitrate_kbps: u32,
+    /// Output height; the width follows the source's aspect ratio. Clips are never
+    /// upscaled. `None` keeps the source resolution.
+    #[serde(default)]
+    pub output_height: Option<u32>,
+    /// Output frame rate. `None` keeps the source frame rate.
+    #[serde(default)]
+    pub output_fps: Option<f64>,
+    #[serde(default)]
+    pub container_format: ExportContainerFormat,
+    #[serde(default = "default_preset_hardware_acceleration")]
+    pub use_hardware_acceleration: bool,
+    #[serde(default)]
+    pub audio_tracks: ExportAudioTracks,
+    /// Drawn over every clip exported with the preset; any overlay forces a re-encode.
+    #[serde(default)]
+    pub overlays: Vec<ExportOverlay>,
+}
+
+fn default_preset_audio_bitrate_kbps() -> u32 {
+    128
+}
+
+fn default_preset_hardware_acceleration() -> bool {
+    true
+}
+
+impl ExportPreset {
+    /// Presets shipped with LiteClip, in UI order.
+    pub fn built_in() -> Vec<Self> {
+        vec![
+            Self {
+                name: "Discord 10 MB".to_string(),
+                target_size_mb: Some(10),
+                audio_bitrate_kbps: 96,
+                output_height: Some(720),
+                output_fps: Some(30.0),
+                container_format: ExportContainerFormat::Mp4,
+                use_hardware_acceleration: true,
+                audio_tracks: ExportAudioTracks::Mix,
+                overlays: Vec::new(),
+            },
+            Self {
+                name: "Discord 50 MB".to_string(),
+                target_size_mb: Some(50),
+                audio_bitrate_kbps: 128,
+                output_height: Some(1080),
+                output_fps: Some(60.0),
+                container_format: ExportContainerFormat::Mp4,
+                use_hardware_acceleration: true,
+                audio_tracks: ExportAudioTracks::Mix,
+                overlays: Vec::new(),
+            },
+            Self {
+                name: "720p60 share".to_string(),
+                target_size_mb: Some(100),
+                audio_bitrate_kbps: 160,
+                output_height: Some(720),
+                output_fps: Some(60.0),
+                container_format: ExportContainerFormat::Mp4,
+                use_hardware_acceleration: true,
+                audio_tracks: ExportAudioTracks::Mix,
+                overlays: Vec::new(),
+            },
+            Self {
+                name: "Archive lossless".to_string(),
+                target_size_mb: None,
+                audio_bitrate_kbps: MAX_PRESET_AUDIO_BITRATE_KBPS,
+                output_height: None,
+                output_fps: None,
+                container_format: ExportContainerFormat::Mkv,
+                use_hardware_acceleration: true,
+                audio_tracks: ExportAudioTracks::Keep,
+                overlays: Vec::new(),
+            },
+        ]
+    }
+
+    /// Built-in presets followed by `custom` ones, skipping custom presets whose name is
+    /// already taken.
+    pub fn all_with_custom(custom: &[Self]) -> Vec<Self> {
+        let mut presets = Self::built_in();
+        for preset in custom {
+            if presets.iter().all(|existing| existing.name != preset.name) {
+                presets.push(preset.clone());
+            }
+        }
+        presets
+    }
+
+    /// Whether this is one of [`Self::built_in`] (by name).
+    pub fn is_built_in(&self) -> bool {
+        Self::built_in()
+            .iter()
+            .any(|preset| preset.name == self.name)
+    }
+
+    /// Clamps the settings to values the exporter accepts. Returns false if the preset is
+    /// unusable (it has no name).
+    pub fn sanitize(&mut self) -> bool {
+        self.name = self.name.trim().to_string();
+        if let Some(target_size_mb) = self.target_size_mb.as_mut() {
+            *target_size_mb = (*target_size_mb).max(1);
+        }
+        self.audio_bitrate_kbps = self
+            .audio_bitrate_kbps
+            .clamp(MIN_PRESET_AUDIO_BITRATE_KBPS, MAX_PRESET_AUDIO_BITRATE_KBPS);
+        self.output_height = self
+            .output_height
+            .filter(|&height| height >= 2)
+            .map(|height| height & !1);
+        self.output_fps = self
+            .output_fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0 && *fps <= MAX_PRESET_FPS);
+        !self.name.is_empty()
+    }
+
+    /// Output size for a source of `width` x `height`, or `None` to keep the source size.
+    pub fn output_dimensions(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let target_height = self.output_height?;
+        if width == 0 || height == 0 || target_height >= height {
+            return None;
+        }
+        let target_width =
+            (f64::from(width) * f64::from(target_height) / f64::from(height)).round() as u32;
+        Some(((target_width & !1).max(2), target_height))
+    }
+
+    /// Builds the export request for `keep_ranges` of the clip at `input_path`. The
+    /// extension of `output_path` is replaced with the container's.
+    pub fn to_clip_export_request(
+        &self,
+        input_path: PathBuf,
+        output_path: &Path,
+        keep_ranges: Vec<TimeRange>,
+        metadata: VideoFileMetadata,
+        preferred_encoder: EncoderType,
+    ) -> ClipExportRequest {
+        let stream_copy = self.target_size_mb.is_none()
+            && self.output_height.is_none()
+            && self.output_fps.is_none()
+            && self.overlays.is_empty()
+            && self.container_format.supports_stream_copy();
+        let target_size_mb = self
+            .target_size_mb
+            .unwrap_or_else(|| original_size_target_mb(&input_path, &metadata, &keep_ranges));
+        let output_size = self.output_dimensions(metadata.width, metadata.height);
+        ClipExportRequest {
+            output_path: output_path.with_extension(self.container_format.extension()),
+            input_path,
+            keep_ranges,
+            target_size_mb,
+            audio_bitrate_kbps: self.audio_bitrate_kbps,
+            use_hardware_acceleration: self.use_hardware_acceleration,
+            preferred_encoder,
+            metadata,
+            stream_copy,
+            smart_cut: false,
+            output_width: output_size.map(|(width, _)| width),
+            output_height: output_size.map(|(_, height)| height),
+            output_fps: self.output_fps,
+            crop: None,
+            post_process_filters: false,
+            container_format: self.container_format,
+            audio_tracks: self.audio_tracks,
+            overlays: self.overlays.clone(),
+            audio: ExportAudioMix::default(),
+            loudness: None,
+        }
+    }
+}
+
+/// Size of the kept part of the source file, for presets that keep the original quality
+/// but need a re-encode.
+fn original_size_target_mb(
+    input_path: &Path,
+    metadata: &VideoFileMetadata,
+    keep_ranges: &[TimeRange],
+) -> u32 {
+    let Ok(file) = std::fs::metadata(input_path) else {
+        return 1;
+    };
+    let kept_secs: f64 = keep_ranges.iter().map(|range| range.duration_secs()).sum();
+    let kept_fraction = if metadata.duration_secs > 0.0 {
+        (kept_secs / metadata.duration_secs).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    (file.len() as f64 * kept_fraction / (1024.0 * 1024.0))
+        .ceil()
+        .max(1.0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> VideoFileMetadata {
+        VideoFileMetadata {
+            duration_secs: 30.0,
+            width: 2560,
+            height: 1440,
+            has_audio: true,
+            audio_track_count: 2,
+            fps: 60.0,
+            clip_metadata: None,
+            chapters: Vec::new(),
+        }
+    }
+
+    fn preset(name: &str) -> ExportPreset {
+        ExportPreset::built_in()
+            .into_iter()
+            .find(|preset| preset.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn discord_preset_scales_and_reencodes() {
+        let request = preset("Discord 10 MB").to_clip_export_request(
+            PathBuf::from("clip.mkv"),
+            Path::new("out/clip_clipped.mkv"),
+            vec![TimeRange {
+                start_secs: 2.0,
+                end_secs: 12.0,
+                speed: 1.0,
+            }],
+            metadata(),
+            EncoderType::Auto,
+        );
+        assert_eq!(request.output_path, PathBuf::from("out/clip_clipped.mp4"));
+        assert_eq!(request.target_size_mb, 10);
+        assert!(!request.stream_copy);
+        assert_eq!(request.output_width, Some(1280));
+        assert_eq!(request.output_height, Some(720));
+        assert_eq!(request.output_fps, Some(30.0));
+        assert_eq!(request.audio_tracks, ExportAudioTracks::Mix);
+    }
+
+    #[test]
+    fn archive_preset_stream_copies() {
+        let request = preset("Archive lossless").to_clip_export_request(
+            PathBuf::from("missing.mp4"),
+            Path::new("clip.mp4"),
+            vec![TimeRange {
+                start_secs: 0.0,
+                end_secs: 30.0,
+                speed: 1.0,
+            }],
+            metadata(),
+            EncoderType::Auto,
+        );
+        assert!(request.stream_copy);
+        assert_eq!(request.output_path, PathBuf::from("clip.mkv"));
+        assert_eq!(request.output_width, None);
+        assert_eq!(request.output_fps, None);
+        assert_eq!(request.audio_tracks, ExportAudioTracks::Keep);
+    }
+
+    #[test]
+    fn overlays_rule_out_stream_copy() {
+        let mut branded = preset("Archive lossless");
+        branded.overlays.push(ExportOverlay::text("LiteClip"));
+        let request = branded.to_clip_export_request(
+            PathBuf::from("missing.mp4"),
+            Path::new("clip.mp4"),
+            vec![TimeRange::new(0.0, 30.0)],
+            metadata(),
+            EncoderType::Auto,
+        );
+        assert!(!request.stream_copy);
+        assert_eq!(request.overlays, branded.overlays);
+    }
+
+    #[test]
+    fn output_dimensions_never_upscale() {
+        let preset = preset("Discord 50 MB");
+        assert_eq!(preset.output_dimensions(2560, 1440), Some((1920, 1080)));
+        assert_eq!(preset.output_dimensions(1280, 720), None);
+        assert_eq!(preset.output_dimensions(1366, 1366), Some((1080, 1080)));
+    }
+
+    #[test]
+    fn sanitize_clamps_settings() {
+        let mut preset = ExportPreset {
+            name: "  Tiny ".to_string(),
+            target_size_mb: Some(0),
+            audio_bitrate_kbps: 8,
+            output_height: Some(721),
+            output_fps: Some(f64::NAN),
+            container_format: ExportContainerFormat::WebM,
+            use_hardware_acceleration: false,
+            audio_tracks: ExportAudioTracks::PrimaryOnly,
+            overlays: Vec::new(),
+        };
+        assert!(preset.sanitize());
+        assert_eq!(preset.name, "Tiny");
+        assert_eq!(preset.target_size_mb, Some(1));
+        assert_eq!(preset.audio_bitrate_kbps, MIN_PRESET_AUDIO_BITRATE_KBPS);
+        assert_eq!(preset.output_height, Some(720));
+        assert_eq!(preset.output_fps, None);
+
+        preset.name = "   ".to_string();
+        assert!(!preset.sanitize());
+    }
+
+    #[test]
+    fn custom_presets_cannot_shadow_built_ins() {
+        let mut custom = preset("Discord 10 MB");
+        custom.target_size_mb = Some(8);
+        let mut mine = custom.clone();
+        mine.name = "Mine".to_string();
+
+        let all = ExportPreset::all_with_custom(&[custom, mine]);
+        assert_eq!(all.len(), ExportPreset::built_in().len() + 1);
+        assert_eq!(all[0].target_size_mb, Some(10));
+        assert_eq!(all.last().unwrap().name, "Mine");
+        assert!(!all.last().unwrap().is_built_in());
+    }
+}
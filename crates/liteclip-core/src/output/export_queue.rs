@@ -0,0 +1,572 @@
+//! Batch export queue.
+//!
+//! [`ExportQueue`] holds many [`ClipExportRequest`]s and runs them through
+//! [`spawn_clip_export`], one at a time or a few at once. The owner calls
+//! [`ExportQueue::poll`] regularly (every frame in the gallery); it collects each job's
+//! [`ClipExportUpdate`]s and starts the next jobs when slots free up.
+//!
+//! A queue opened with [`ExportQueue::open`] keeps its unfinished jobs in a JSON file, so
+//! they resume after a restart. Jobs still running when the queue is dropped are cancelled
+//! and run again from the start next time.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender, TryRecvError},
+    Arc,
+};
+use tracing::{info, warn};
+
+use super::video_file::{spawn_clip_export, ClipExportPhase, ClipExportRequest, ClipExportUpdate};
+
+/// Identifies a job within its queue; stays the same across restarts.
+pub type ExportJobId = u64;
+
+/// Where a queued job is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportJobState {
+    /// Waiting for a free slot.
+    Pending,
+    /// Exporting; carries the latest progress update.
+    Running {
+        phase: ClipExportPhase,
+        fraction: f32,
+        message: String,
+    },
+    Finished(PathBuf),
+    Failed(String),
+    Cancelled,
+}
+
+impl ExportJobState {
+    /// Whether the job is over, successfully or not.
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            ExportJobState::Finished(_) | ExportJobState::Failed(_) | ExportJobState::Cancelled
+        )
+    }
+
+    /// Progress of the job from 0.0 to 1.0; jobs that are over count as complete.
+    pub fn fraction(&self) -> f32 {
+        match self {
+            ExportJobState::Pending => 0.0,
+            ExportJobState::Running { fraction, .. } => fraction.clamp(0.0, 1.0),
+            _ => 1.0,
+        }
+    }
+}
+
+/// A queued export.
+#[derive(Debug, Clone)]
+pub struct ExportJob {
+    pub id: ExportJobId,
+    pub request: ClipExportRequest,
+    pub state: ExportJobState,
+}
+
+/// Progress over every job in the queue.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportQueueProgress {
+    /// Jobs that are over, including failed and cancelled ones.
+    pub done: usize,
+    pub running: usize,
+    pub total: usize,
+    /// Overall progress from 0.0 to 1.0, each job weighted equally.
+    pub fraction: f32,
+}
+
+/// A job as written to the queue file.
+#[derive(Serialize, Deserialize)]
+struct PersistedJob {
+    id: ExportJobId,
+    request: ClipExportRequest,
+}
+
+/// Channel and cancel flag of a running job.
+struct RunningJob {
+    progress_rx: Receiver<ClipExportUpdate>,
+    cancel_flag: Arc<AtomicBool>,
+}
+
+/// Clip exports run in order, at most `max_concurrent` at a time.
+pub struct ExportQueue {
+    jobs: Vec<ExportJob>,
+    running: HashMap<ExportJobId, RunningJob>,
+    next_id: ExportJobId,
+    max_concurrent: usize,
+    /// Queue file for unfinished jobs; `None` keeps the queue in memory only.
+    state_path: Option<PathBuf>,
+}
+
+impl ExportQueue {
+    /// A queue that is not persisted.
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            jobs: Vec::new(),
+            running: HashMap::new(),
+            next_id: 1,
+            max_concurrent: max_concurrent.max(1),
+            state_path: None,
+        }
+    }
+
+    /// Opens the queue persisted at `state_path`, resuming its unfinished jobs as pending.
+    /// A missing file gives an empty queue.
+    pub fn open(state_path: impl Into<PathBuf>, max_concurrent: usize) -> Result<Self> {
+        let state_path = state_path.into();
+        let mut queue = Self::new(max_concurrent);
+        if state_path.exists() {
+            let json = std::fs::read_to_string(&state_path)
+                .with_context(|| format!("Failed to read export queue from {:?}", state_path))?;
+            let persisted: Vec<PersistedJob> = serde_json::from_str(&json)
+                .with_context(|| format!("Failed to parse export queue from {:?}", state_path))?;
+            for job in persisted {
+                queue.next_id = queue.next_id.max(job.id + 1);
+                queue.jobs.push(ExportJob {
+                    id: job.id,
+                    request: job.request,
+                    state: ExportJobState::Pending,
+                });
+            }
+            if !queue.jobs.is_empty() {
+                info!(
+                    "Resuming {} queued exports from {:?}",
+                    queue.jobs.len(),
+                    state_path
+                );
+            }
+        }
+        queue.state_path = Some(state_path);
+        Ok(queue)
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Changes how many jobs may run at once. Running jobs are not stopped.
+    pub fn set_max_concurrent(&mut self, max_concurrent: usize) {
+        self.max_concurrent = max_concurrent.max(1);
+    }
+
+    /// Adds a job at the end of the queue. It starts on a later [`Self::poll`].
+    pub fn push(&mut self, request: ClipExportRequest) -> ExportJobId {
+        let id = self.add_job(request);
+        self.persist();
+        id
+    }
+
+    /// Adds several jobs at the end of the queue, in order.
+    pub fn extend(
+        &mut self,
+        requests: impl IntoIterator<Item = ClipExportRequest>,
+    ) -> Vec<ExportJobId> {
+        let ids = requests
+            .into_iter()
+            .map(|request| self.add_job(request))
+            .collect();
+        self.persist();
+        ids
+    }
+
+    fn add_job(&mut self, request: ClipExportRequest) -> ExportJobId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push(ExportJob {
+            id,
+            request,
+            state: ExportJobState::Pending,
+        });
+        id
+    }
+
+    /// All jobs in queue order, including finished ones until [`Self::clear_done`].
+    pub fn jobs(&self) -> &[ExportJob] {
+        &self.jobs
+    }
+
+    pub fn job(&self, id: ExportJobId) -> Option<&ExportJob> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// Whether no job is pending or running.
+    pub fn is_idle(&self) -> bool {
+        self.jobs.iter().all(|job| job.state.is_done())
+    }
+
+    pub fn progress(&self) -> ExportQueueProgress {
+        let total = self.jobs.len();
+        let done = self.jobs.iter().filter(|job| job.state.is_done()).count();
+        let fraction = if total == 0 {
+            1.0
+        } else {
+            self.jobs
+                .iter()
+                .map(|job| job.state.fraction())
+                .sum::<f32>()
+                / total as f32
+        };
+        ExportQueueProgress {
+            done,
+            running: self.running.len(),
+            total,
+            fraction,
+        }
+    }
+
+    /// Cancels a job. A pending job is cancelled at once; a running one once its export
+    /// notices. Returns false if the job is unknown or already over.
+    pub fn cancel(&mut self, id: ExportJobId) -> bool {
+        if let Some(running) = self.running.get(&id) {
+            running.cancel_flag.store(true, Ordering::Relaxed);
+            return true;
+        }
+        let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+            return false;
+        };
+        if job.state.is_done() {
+            return false;
+        }
+        job.state = ExportJobState::Cancelled;
+        self.persist();
+        true
+    }
+
+    /// Cancels every pending and running job.
+    pub fn cancel_all(&mut self) {
+        let ids: Vec<ExportJobId> = self
+            .jobs
+            .iter()
+            .filter(|job| !job.state.is_done())
+            .map(|job| job.id)
+            .collect();
+        for id in ids {
+            self.cancel(id);
+        }
+    }
+
+    /// Removes the jobs that are over.
+    pub fn clear_done(&mut self) {
+        self.jobs.retain(|job| !job.state.is_done());
+    }
+
+    /// Collects progress from running jobs and starts pending ones while slots are free.
+    /// Returns every update received, tagged with its job.
+    pub fn poll(&mut self) -> Vec<(ExportJobId, ClipExportUpdate)> {
+        let updates = self.collect_updates();
+        self.start_ready_jobs(spawn_clip_export);
+        updates
+    }
+
+    fn collect_updates(&mut self) -> Vec<(ExportJobId, ClipExportUpdate)> {
+        let mut updates = Vec::new();
+        let mut ended = Vec::new();
+        for (&id, running) in &self.running {
+            loop {
+                match running.progress_rx.try_recv() {
+                    Ok(update) => updates.push((id, update)),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        ended.push(id);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut any_done = false;
+        for (id, update) in &updates {
+            any_done |= self.apply_update(*id, update.clone());
+        }
+        for id in ended {
+            self.running.remove(&id);
+            if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                if !job.state.is_done() {
+                    warn!("Export job {} stopped without reporting a result", id);
+                    job.state = ExportJobState::Failed("Export stopped unexpectedly".to_string());
+                    any_done = true;
+                }
+            }
+        }
+        if any_done {
+            self.persist();
+        }
+        updates
+    }
+
+    /// Records `update` on job `id`. Returns true if it ended the job.
+    fn apply_update(&mut self, id: ExportJobId, update: ClipExportUpdate) -> bool {
+        let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+            return false;
+        };
+        job.state = match update {
+            ClipExportUpdate::Progress {
+                phase,
+                fraction,
+                message,
+            } => ExportJobState::Running {
+                phase,
+                fraction,
+                message,
+            },
+            ClipExportUpdate::Finished(path) => ExportJobState::Finished(path),
+            ClipExportUpdate::Failed(err) => ExportJobState::Failed(err),
+            ClipExportUpdate::Cancelled => ExportJobState::Cancelled,
+        };
+        if job.state.is_done() {
+            self.running.remove(&id);
+            return true;
+        }
+        false
+    }
+
+    fn start_ready_jobs(
+        &mut self,
+        mut spawn: impl FnMut(ClipExportRequest, Sender<ClipExportUpdate>, Arc<AtomicBool>),
+    ) {
+        for job in &mut self.jobs {
+            if self.running.len() >= self.max_concurrent {
+                break;
+            }
+            if job.state != ExportJobState::Pending {
+                continue;
+            }
+            let (progress_tx, progress_rx) = mpsc::channel();
+            let cancel_flag = Arc::new(AtomicBool::new(false));
+            info!(
+                job_id = job.id,
+                input_path = ?job.request.input_path,
+                output_path = ?job.request.output_path,
+                "Starting queued export"
+            );
+            spawn(job.request.clone(), progress_tx, cancel_flag.clone());
+            job.state = ExportJobState::Running {
+                phase: ClipExportPhase::Preparing,
+                fraction: 0.0,
+                message: "Preparing export".to_string(),
+            };
+            self.running.insert(
+                job.id,
+                RunningJob {
+                    progress_rx,
+                    cancel_flag,
+                },
+            );
+        }
+    }
+
+    /// Writes the unfinished jobs to the queue file, if there is one.
+    fn persist(&self) {
+        let Some(state_path) = self.state_path.as_deref() else {
+            return;
+        };
+        if let Err(err) = write_queue_file(state_path, &self.jobs) {
+            warn!("Failed to save export queue to {:?}: {:#}", state_path, err);
+        }
+    }
+}
+
+impl Drop for ExportQueue {
+    fn drop(&mut self) {
+        // Running jobs stay in the queue file and restart from scratch next time.
+        for running in self.running.values() {
+            running.cancel_flag.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Writes through a temporary file so a crash never leaves a half-written queue.
+fn write_queue_file(state_path: &Path, jobs: &[ExportJob]) -> Result<()> {
+    let persisted: Vec<PersistedJob> = jobs
+        .iter()
+        .filter(|job| !job.state.is_done())
+        .map(|job| PersistedJob {
+            id: job.id,
+            request: job.request.clone(),
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&persisted).context("Failed to serialize queue")?;
+    if let Some(parent) = state_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+    let tmp_path = state_path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json).with_context(|| format!("Failed to write {:?}", tmp_path))?;
+    std::fs::rename(&tmp_path, state_path)
+        .with_context(|| format!("Failed to replace {:?}", state_path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EncoderType;
+    use crate::output::video_file::{
+        ExportAudioTracks, ExportContainerFormat, TimeRange, VideoFileMetadata,
+    };
+
+    fn request(name: &str) -> ClipExportRequest {
+        ClipExportRequest {
+            input_path: PathBuf::from(format!("{name}.mp4")),
+            output_path: PathBuf::from(format!("{name}_clipped.mp4")),
+            keep_ranges: vec![TimeRange {
+                start_secs: 0.0,
+                end_secs: 10.0,
+                speed: 1.0,
+            }],
+            target_size_mb: 10,
+            audio_bitrate_kbps: 96,
+            use_hardware_acceleration: false,
+            preferred_encoder: EncoderType::Auto,
+            metadata: VideoFileMetadata {
+                duration_secs: 10.0,
+                width: 1920,
+                height: 1080,
+                has_audio: true,
+                audio_track_count: 1,
+                fps: 60.0,
+                clip_metadata: None,
+                chapters: Vec::new(),
+            },
+            stream_copy: false,
+            smart_cut: false,
+            output_width: None,
+            output_height: None,
+            output_fps: None,
+            crop: None,
+            post_process_filters: false,
+            container_format: ExportContainerFormat::Mp4,
+            audio_tracks: ExportAudioTracks::Mix,
+            overlays: Vec::new(),
+            audio: Default::default(),
+            loudness: None,
+        }
+    }
+
+    /// Starts ready jobs without exporting, keeping the channels so tests can report.
+    fn start_fake(queue: &mut ExportQueue) -> Vec<(PathBuf, Sender<ClipExportUpdate>)> {
+        let mut started = Vec::new();
+        queue.start_ready_jobs(|request, progress_tx, _cancel_flag| {
+            started.push((request.input_path, progress_tx));
+        });
+        started
+    }
+
+    fn temp_queue_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "liteclip_export_queue_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("export_queue.json")
+    }
+
+    #[test]
+    fn runs_at_most_max_concurrent_jobs_in_order() {
+        let mut queue = ExportQueue::new(2);
+        queue.extend(["a", "b", "c"].map(request));
+
+        let started = start_fake(&mut queue);
+        assert_eq!(
+            started.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(),
+            [PathBuf::from("a.mp4"), PathBuf::from("b.mp4")]
+        );
+        assert!(start_fake(&mut queue).is_empty());
+
+        started[0]
+            .1
+            .send(ClipExportUpdate::Finished(PathBuf::from("a_clipped.mp4")))
+            .unwrap();
+        queue.collect_updates();
+        let started_next = start_fake(&mut queue);
+        assert_eq!(started_next.len(), 1);
+        assert_eq!(started_next[0].0, PathBuf::from("c.mp4"));
+    }
+
+    #[test]
+    fn progress_aggregates_job_updates() {
+        let mut queue = ExportQueue::new(1);
+        queue.extend(["a", "b"].map(request));
+        let started = start_fake(&mut queue);
+        started[0]
+            .1
+            .send(ClipExportUpdate::Progress {
+                phase: ClipExportPhase::FirstPass,
+                fraction: 0.5,
+                message: "Attempt 1/3".to_string(),
+            })
+            .unwrap();
+        let updates = queue.collect_updates();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].0, 1);
+
+        let progress = queue.progress();
+        assert_eq!(progress.done, 0);
+        assert_eq!(progress.running, 1);
+        assert_eq!(progress.total, 2);
+        assert!((progress.fraction - 0.25).abs() < 1e-6);
+
+        drop(started);
+        queue.collect_updates();
+        assert!(matches!(
+            queue.job(1).unwrap().state,
+            ExportJobState::Failed(_)
+        ));
+        assert_eq!(queue.progress().done, 1);
+    }
+
+    #[test]
+    fn cancelling_pending_job_skips_it() {
+        let mut queue = ExportQueue::new(1);
+        let ids = queue.extend(["a", "b"].map(request));
+        assert!(queue.cancel(ids[1]));
+        assert!(!queue.cancel(ids[1]));
+
+        let started = start_fake(&mut queue);
+        started[0].1.send(ClipExportUpdate::Cancelled).unwrap();
+        queue.collect_updates();
+        assert!(start_fake(&mut queue).is_empty());
+        assert!(queue.is_idle());
+
+        queue.clear_done();
+        assert!(queue.jobs().is_empty());
+    }
+
+    #[test]
+    fn unfinished_jobs_resume_after_reopen() {
+        let path = temp_queue_path("resume");
+        {
+            let mut queue = ExportQueue::open(&path, 1).unwrap();
+            queue.extend(["a", "b", "c"].map(request));
+            let started = start_fake(&mut queue);
+            started[0]
+                .1
+                .send(ClipExportUpdate::Finished(PathBuf::from("a_clipped.mp4")))
+                .unwrap();
+            queue.collect_updates();
+            // "b" is running when the queue goes away.
+            let _running = start_fake(&mut queue);
+        }
+
+        let queue = ExportQueue::open(&path, 1).unwrap();
+        let resumed: Vec<_> = queue
+            .jobs()
+            .iter()
+            .map(|job| (job.id, job.request.input_path.clone(), job.state.clone()))
+            .collect();
+        assert_eq!(
+            resumed,
+            [
+                (2, PathBuf::from("b.mp4"), ExportJobState::Pending),
+                (3, PathBuf::from("c.mp4"), ExportJobState::Pending),
+            ]
+        );
+        assert_eq!(queue.next_id, 4);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::SaveContainer;
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use tracing::debug;
@@ -65,10 +66,14 @@ pub fn calculate_clip_start_pts(
 ///
 /// # Returns
 ///
-/// Filename string in format "YYYY-MM-DD_HH-MM-SSS.mp4".
-fn generate_output_filename() -> String {
+/// Filename string in format "YYYY-MM-DD_HH-MM-SSS.<ext>", with the container's extension.
+fn generate_output_filename(container: SaveContainer) -> String {
     let timestamp = chrono::Local::now();
-    format!("{}.mp4", timestamp.format("%Y-%m-%d_%H-%M-%S_%3f"))
+    format!(
+        "{}.{}",
+        timestamp.format("%Y-%m-%d_%H-%M-%S_%3f"),
+        container.extension()
+    )
 }
 
 /// Generates an output path with optional game subdirectory.
@@ -79,12 +84,17 @@ fn generate_output_filename() -> String {
 ///
 /// * `base_dir` - Base save directory.
 /// * `game_name` - Optional game name for subdirectory organization.
+/// * `container` - Container the clip will be written in; picks the file extension.
 ///
 /// # Returns
 ///
 /// Complete path to the output file.
-pub fn generate_output_path(base_dir: &Path, game_name: Option<&str>) -> Result<PathBuf> {
-    let filename = generate_output_filename();
+pub fn generate_output_path(
+    base_dir: &Path,
+    game_name: Option<&str>,
+    container: SaveContainer,
+) -> Result<PathBuf> {
+    let filename = generate_output_filename(container);
 
     let output_dir = if let Some(game) = game_name {
         if game.is_empty() {
@@ -129,7 +139,7 @@ mod tests {
     #[test]
     fn generate_output_path_with_game() {
         let temp = tempfile::TempDir::new().unwrap();
-        let path = generate_output_path(temp.path(), Some("MyGame")).unwrap();
+        let path = generate_output_path(temp.path(), Some("MyGame"), SaveContainer::Mp4).unwrap();
         assert!(path.to_string_lossy().contains("MyGame"));
         assert!(path.parent().unwrap().exists());
     }
@@ -137,7 +147,7 @@ mod tests {
     #[test]
     fn generate_output_path_without_game() {
         let temp = tempfile::TempDir::new().unwrap();
-        let path = generate_output_path(temp.path(), None).unwrap();
+        let path = generate_output_path(temp.path(), None, SaveContainer::Mp4).unwrap();
         assert!(path.to_string_lossy().contains("Desktop"));
         assert!(path.parent().unwrap().exists());
     }
@@ -145,7 +155,14 @@ mod tests {
     #[test]
     fn generate_output_path_empty_game() {
         let temp = tempfile::TempDir::new().unwrap();
-        let path = generate_output_path(temp.path(), Some("")).unwrap();
+        let path = generate_output_path(temp.path(), Some(""), SaveContainer::Mp4).unwrap();
         assert_eq!(path.parent().unwrap(), temp.path());
     }
+
+    #[test]
+    fn generate_output_path_uses_container_extension() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = generate_output_path(temp.path(), None, SaveContainer::Mkv).unwrap();
+        assert_eq!(path.extension().unwrap(), "mkv");
+    }
 }
@@ -0,0 +1,762 @@
+//! First pass of the export's loudness normalization.
+//!
+//! [`measure_request_loudness`] decodes the kept ranges of the exported audio tracks,
+//! applies the request's per-track gain, mutes, fades and crossfades, mixes them unless they
+//! are kept as separate streams, and measures the result with a [`LoudnessMeter`]: integrated
+//! loudness per ITU-R BS.1770 / EBU R128 (K-weighting, 400 ms blocks every 100 ms, absolute
+//! gate at -70 LUFS and relative gate 10 LU below the mean) and the true peak, from 4×
+//! oversampling. The second pass is the `volume`/`alimiter` chain of
+//! [`LoudnessNormalization::filter_chain`], which
+//! [`ExportAudio`](super::export_audio::ExportAudio) runs in front of every audio encoder.
+
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+    Arc,
+};
+use std::time::Instant;
+use tracing::info;
+
+use super::audio_mix::{ExportAudioMix, LoudnessNormalization};
+use super::export_audio::EXPORT_AUDIO_RATE;
+use super::retime::is_audible_speed;
+use super::sdk_export::seek_to_seconds;
+use super::video_file::{
+    ClipExportPhase, ClipExportRequest, ClipExportUpdate, ExportAudioTracks, TimeRange,
+};
+
+/// Samples in one 100 ms step of the gating blocks.
+const STEP_SAMPLES: usize = EXPORT_AUDIO_RATE as usize / 10;
+/// Steps in one 400 ms gating block.
+const STEPS_PER_BLOCK: usize = 4;
+/// Blocks quieter than this are left out of the integrated loudness.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this far below the mean of the blocks above the absolute gate are left out too.
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// Taps of each interpolation phase of the true-peak oversampler.
+const TRUE_PEAK_TAPS: usize = 12;
+/// Oversampling factor of the true-peak measurement.
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Biquad filter in direct form I.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    const fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// K-weighting of one channel at 48 kHz: the BS.1770 high shelf, then its high-pass.
+#[derive(Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new() -> Self {
+        Self {
+            shelf: Biquad::new(
+                [
+                    1.535_124_859_586_97,
+                    -2.691_696_189_406_38,
+                    1.198_392_810_852_85,
+                ],
+                [-1.690_659_293_182_41, 0.732_480_774_215_85],
+            ),
+            high_pass: Biquad::new(
+                [1.0, -2.0, 1.0],
+                [-1.990_047_454_833_98, 0.990_072_250_366_21],
+            ),
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(input))
+    }
+}
+
+/// Interpolates one channel at 4× its rate to find peaks between samples.
+struct TruePeak {
+    /// Windowed-sinc taps of the three in-between phases.
+    phases: [[f64; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING - 1],
+    history: VecDeque<f64>,
+    peak: f64,
+}
+
+impl TruePeak {
+    fn new() -> Self {
+        let mut phases = [[0.0; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING - 1];
+        let half_width = TRUE_PEAK_TAPS as f64 / 2.0 + 0.5;
+        for (phase, taps) in phases.iter_mut().enumerate() {
+            let fraction = (phase + 1) as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+            for (index, tap) in taps.iter_mut().enumerate() {
+                // Distance from the point between history[5] and history[6] being computed.
+                let t = index as f64 - (TRUE_PEAK_TAPS / 2 - 1) as f64 - fraction;
+                let sinc = if t.abs() < 1e-9 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t)
+                };
+                let window = 0.5 * (1.0 + (std::f64::consts::PI * t / half_width).cos());
+                *tap = sinc * window;
+            }
+            let sum: f64 = taps.iter().sum();
+            for tap in taps.iter_mut() {
+                *tap /= sum;
+            }
+        }
+        Self {
+            phases,
+            history: VecDeque::from(vec![0.0; TRUE_PEAK_TAPS]),
+            peak: 0.0,
+        }
+    }
+
+    fn process(&mut self, sample: f64) {
+        self.history.pop_front();
+        self.history.push_back(sample);
+        self.peak = self.peak.max(sample.abs());
+        for taps in &self.phases {
+            let interpolated: f64 = taps
+                .iter()
+                .zip(self.history.iter())
+                .map(|(tap, sample)| tap * sample)
+                .sum();
+            self.peak = self.peak.max(interpolated.abs());
+        }
+    }
+}
+
+/// Integrated loudness and true peak of 48 kHz stereo audio.
+pub(super) struct LoudnessMeter {
+    weighting: [KWeighting; 2],
+    true_peak: [TruePeak; 2],
+    /// Weighted energy summed over the channels in the current step.
+    step_energy: f64,
+    step_samples: usize,
+    /// Mean weighted energy of the last steps, newest last.
+    recent_steps: VecDeque<f64>,
+    /// Mean weighted energy of every 400 ms block, and how much the block counts.
+    blocks: Vec<(f64, f64)>,
+    /// How much the blocks completed from now on count.
+    block_weight: f64,
+}
+
+impl LoudnessMeter {
+    pub(super) fn new() -> Self {
+        Self {
+            weighting: [KWeighting::new(), KWeighting::new()],
+            true_peak: [TruePeak::new(), TruePeak::new()],
+            step_energy: 0.0,
+            step_samples: 0,
+            recent_steps: VecDeque::with_capacity(STEPS_PER_BLOCK),
+            blocks: Vec::new(),
+            block_weight: 1.0,
+        }
+    }
+
+    /// Makes the blocks completed from now on count `weight` times, for audio that plays
+    /// for longer or shorter than it was measured.
+    pub(super) fn set_block_weight(&mut self, weight: f64) {
+        self.block_weight = weight;
+    }
+
+    pub(super) fn push(&mut self, samples: &[(f32, f32)]) {
+        for &(left, right) in samples {
+            let mut energy = 0.0;
+            for (channel, sample) in [f64::from(left), f64::from(right)].into_iter().enumerate() {
+                let weighted = self.weighting[channel].process(sample);
+                energy += weighted * weighted;
+                self.true_peak[channel].process(sample);
+            }
+            self.step_energy += energy;
+            self.step_samples += 1;
+            if self.step_samples == STEP_SAMPLES {
+                self.finish_step();
+            }
+        }
+    }
+
+    fn finish_step(&mut self) {
+        if self.recent_steps.len() == STEPS_PER_BLOCK {
+            self.recent_steps.pop_front();
+        }
+        self.recent_steps
+            .push_back(self.step_energy / STEP_SAMPLES as f64);
+        self.step_energy = 0.0;
+        self.step_samples = 0;
+        if self.recent_steps.len() == STEPS_PER_BLOCK {
+            self.blocks.push((
+                self.recent_steps.iter().sum::<f64>() / STEPS_PER_BLOCK as f64,
+                self.block_weight,
+            ));
+        }
+    }
+
+    /// Integrated loudness in LUFS, or `None` when no block is above the absolute gate.
+    pub(super) fn integrated_lufs(&self) -> Option<f64> {
+        let mean_loudness = |blocks: &mut dyn Iterator<Item = (f64, f64)>| {
+            let (sum, weight) = blocks.fold((0.0, 0.0), |(sum, total), (energy, weight)| {
+                (sum + energy * weight, total + weight)
+            });
+            (weight > 0.0).then(|| energy_to_lufs(sum / weight))
+        };
+        let above_absolute = || {
+            self.blocks
+                .iter()
+                .copied()
+                .filter(|&(energy, _)| energy_to_lufs(energy) > ABSOLUTE_GATE_LUFS)
+        };
+        let relative_gate = mean_loudness(&mut above_absolute())? - RELATIVE_GATE_LU;
+        mean_loudness(
+            &mut above_absolute().filter(|&(energy, _)| energy_to_lufs(energy) > relative_gate),
+        )
+    }
+
+    /// True peak over both channels in dBTP, or `None` for digital silence.
+    pub(super) fn true_peak_dbtp(&self) -> Option<f64> {
+        let peak = self.true_peak[0].peak.max(self.true_peak[1].peak);
+        (peak > 0.0).then(|| 20.0 * peak.log10())
+    }
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.max(1e-20).log10()
+}
+
+/// Gain and fades of one track over a kept range, applied to its samples as the export's
+/// edit graph would (see [`ExportAudioMix::range_filter_spec`]). Samples come in at the
+/// source speed and are placed at their output time.
+struct RangeEdit {
+    gain: f32,
+    /// Output seconds per source sample.
+    sample_secs: f64,
+    output_secs: f64,
+    fade_in_secs: f64,
+    fade_out_secs: f64,
+    /// Audio cut off after the previous range, fading out under the start of this one.
+    tail: Vec<(f32, f32)>,
+    crossfade_secs: f64,
+    /// Samples edited so far.
+    position: usize,
+}
+
+impl RangeEdit {
+    fn new(mix: &ExportAudioMix, source: usize, range: &TimeRange, tail: Vec<(f32, f32)>) -> Self {
+        let crossfade = mix.crossfade().filter(|_| !tail.is_empty());
+        let output_secs = range.output_duration_secs();
+        let (fade_in_secs, fade_out_secs) = mix.range_fades(output_secs, crossfade.is_some());
+        Self {
+            gain: 10f32.powf(mix.source(source).gain_db / 20.0),
+            sample_secs: 1.0 / (f64::from(EXPORT_AUDIO_RATE) * range.effective_speed()),
+            output_secs,
+            fade_in_secs,
+            fade_out_secs,
+            tail: if crossfade.is_some() {
+                tail
+            } else {
+                Vec::new()
+            },
+            crossfade_secs: crossfade.unwrap_or(0.0),
+            position: 0,
+        }
+    }
+
+    fn apply(&mut self, (left, right): (f32, f32)) -> (f32, f32) {
+        let secs = self.position as f64 * self.sample_secs;
+        self.position += 1;
+        let mut envelope = 1.0;
+        if self.fade_in_secs > 0.0 {
+            envelope *= (secs / self.fade_in_secs).min(1.0);
+        }
+        if self.fade_out_secs > 0.0 {
+            envelope *= ((self.output_secs - secs) / self.fade_out_secs).clamp(0.0, 1.0);
+        }
+        let (mut left, mut right) = (left * envelope as f32, right * envelope as f32);
+        if secs < self.crossfade_secs {
+            let index = (secs * f64::from(EXPORT_AUDIO_RATE)) as usize;
+            if let Some(&(tail_left, tail_right)) = self.tail.get(index) {
+                let fade = (1.0 - secs / self.crossfade_secs) as f32;
+                left += tail_left * fade;
+                right += tail_right * fade;
+            }
+        }
+        (left * self.gain, right * self.gain)
+    }
+}
+
+/// Whether `range` is measured. Ranges too fast or too slow to keep their audio are silent
+/// in the export.
+fn is_measured_range(range: &TimeRange) -> bool {
+    is_audible_speed(range.effective_speed())
+}
+
+/// An exported audio track being decoded for the measurement.
+struct MeasuredTrack {
+    /// Index of the track among the clip's audio tracks.
+    source: usize,
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    decoder: Option<ffmpeg::decoder::Audio>,
+    resampler: ffmpeg::software::resampling::Context,
+    /// Gain and fades of the current range.
+    edit: Option<RangeEdit>,
+    past_range_end: bool,
+    /// Whether the audio past the end of the current range is kept for a crossfade.
+    collect_tail: bool,
+    /// Resampled audio from just past the end of the current range.
+    tail: Vec<(f32, f32)>,
+    /// Edited samples not yet mixed with the other tracks.
+    pending: VecDeque<(f32, f32)>,
+}
+
+impl MeasuredTrack {
+    /// Resamples and edits the decoded frames inside `range` into `pending`, keeping those
+    /// within `crossfade_secs` past its end for the crossfade.
+    fn receive_frames(
+        &mut self,
+        decoded: &mut ffmpeg::frame::Audio,
+        resampled: &mut ffmpeg::frame::Audio,
+        range: &TimeRange,
+        crossfade_secs: f64,
+    ) -> Result<()> {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return Ok(());
+        };
+        while decoder.receive_frame(decoded).is_ok() {
+            let pts_secs = decoded
+                .timestamp()
+                .map(|ts| {
+                    ts as f64 * f64::from(self.time_base.numerator())
+                        / f64::from(self.time_base.denominator())
+                })
+                .unwrap_or(0.0);
+            if pts_secs >= range.end_secs {
+                if self.collect_tail && pts_secs < range.end_secs + crossfade_secs {
+                    self.resampler.run(decoded, resampled)?;
+                    self.tail
+                        .extend_from_slice(resampled.plane::<(f32, f32)>(0));
+                }
+                continue;
+            }
+            if pts_secs < range.start_secs {
+                continue;
+            }
+            self.resampler.run(decoded, resampled)?;
+            if let Some(edit) = self.edit.as_mut() {
+                self.pending.extend(
+                    resampled
+                        .plane::<(f32, f32)>(0)
+                        .iter()
+                        .map(|&sample| edit.apply(sample)),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Feeds the samples every track has ready into `meters`: summed into the only meter when the
+/// tracks are mixed, or each track into its own. With `drain`, the mix takes all of them,
+/// silence standing in for the shorter tracks.
+fn meter_pending(
+    tracks: &mut [MeasuredTrack],
+    mixed: &mut Vec<(f32, f32)>,
+    meters: &mut [LoudnessMeter],
+    drain: bool,
+) {
+    if meters.len() > 1 {
+        for (track, meter) in tracks.iter_mut().zip(meters.iter_mut()) {
+            mixed.clear();
+            mixed.extend(track.pending.drain(..));
+            meter.push(mixed);
+        }
+        return;
+    }
+    let ready = tracks.iter().map(|track| track.pending.len());
+    let count = if drain { ready.max() } else { ready.min() }.unwrap_or(0);
+    if count == 0 {
+        return;
+    }
+    mixed.clear();
+    mixed.resize(count, (0.0, 0.0));
+    for track in tracks.iter_mut() {
+        let take = count.min(track.pending.len());
+        for (mixed, (left, right)) in mixed.iter_mut().zip(track.pending.drain(..take)) {
+            mixed.0 += left;
+            mixed.1 += right;
+        }
+    }
+    meters[0].push(mixed);
+}
+
+/// First pass of loudness normalization: measures the kept ranges of `request` as the export
+/// will play them and fills in the measurement of `request.loudness`.
+///
+/// Ranges are weighted by their length in the output, and those whose speed mutes their
+/// audio are left out. When the tracks are kept as separate streams, each one is measured on
+/// its own and the loudest sets the measurement, since the same gain applies to all of them.
+///
+/// Does nothing without a loudness target, when the measurement is already there or when
+/// the clip has no audio. Returns `false` when cancelled.
+pub(super) fn measure_request_loudness(
+    request: &mut ClipExportRequest,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+) -> Result<bool> {
+    let Some(loudness) = request.loudness else {
+        return Ok(true);
+    };
+    if loudness.measured_lufs.is_some() || !request.metadata.has_audio {
+        return Ok(true);
+    }
+
+    let _ = progress_tx.send(ClipExportUpdate::Progress {
+        phase: ClipExportPhase::Preparing,
+        fraction: 0.0,
+        message: "Measuring loudness".to_string(),
+    });
+    let started_at = Instant::now();
+
+    let mut input_ctx = ffmpeg::format::input(&request.input_path)
+        .with_context(|| format!("Failed to open input: {:?}", request.input_path))?;
+    let mut streams: Vec<_> = input_ctx
+        .streams()
+        .filter(|stream| stream.parameters().medium() == ffmpeg::media::Type::Audio)
+        .map(|stream| (stream.index(), stream.time_base()))
+        .collect();
+    if request.audio_tracks == ExportAudioTracks::PrimaryOnly {
+        streams.truncate(1);
+    }
+
+    let mut tracks = Vec::with_capacity(streams.len());
+    for (source, (stream_index, time_base)) in streams.into_iter().enumerate() {
+        if request.audio.source(source).muted {
+            continue;
+        }
+        let stream = input_ctx
+            .stream(stream_index)
+            .with_context(|| format!("missing audio stream {stream_index}"))?;
+        let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+            .decoder()
+            .audio()?;
+        let resampler = ffmpeg::software::resampling::Context::get(
+            decoder.format(),
+            decoder.channel_layout(),
+            decoder.rate(),
+            ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Packed),
+            ffmpeg::channel_layout::ChannelLayout::STEREO,
+            EXPORT_AUDIO_RATE as u32,
+        )?;
+        tracks.push(MeasuredTrack {
+            source,
+            stream_index,
+            time_base,
+            decoder: None,
+            resampler,
+            edit: None,
+            past_range_end: false,
+            collect_tail: false,
+            tail: Vec::new(),
+            pending: VecDeque::new(),
+        });
+    }
+    if tracks.is_empty() {
+        return Ok(true);
+    }
+
+    let separate_tracks = request.audio_tracks == ExportAudioTracks::Keep
+        && request.container_format.supports_multiple_audio_tracks();
+    let meter_count = if separate_tracks { tracks.len() } else { 1 };
+    let mut meters: Vec<_> = (0..meter_count).map(|_| LoudnessMeter::new()).collect();
+    let crossfade = request.audio.crossfade();
+    let total_secs: f64 = request
+        .keep_ranges
+        .iter()
+        .filter(|range| is_measured_range(range))
+        .map(|range| range.output_duration_secs())
+        .sum::<f64>()
+        .max(0.1);
+    let mut measured_secs = 0.0;
+    let mut decoded = ffmpeg::frame::Audio::empty();
+    let mut resampled = ffmpeg::frame::Audio::empty();
+    let mut mixed = Vec::new();
+
+    let range_count = request.keep_ranges.len();
+    for (index, range) in request.keep_ranges.iter().enumerate() {
+        if !is_measured_range(range) {
+            for track in &mut tracks {
+                track.tail.clear();
+            }
+            continue;
+        }
+        for meter in &mut meters {
+            meter.set_block_weight(1.0 / range.effective_speed());
+        }
+        seek_to_seconds(&mut input_ctx, range.start_secs);
+        for track in &mut tracks {
+            let stream = input_ctx.stream(track.stream_index).with_context(|| {
+                format!("missing audio stream {} after seek", track.stream_index)
+            })?;
+            track.decoder = Some(
+                ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+                    .decoder()
+                    .audio()?,
+            );
+            track.past_range_end = false;
+            let tail = std::mem::take(&mut track.tail);
+            track.edit = Some(RangeEdit::new(&request.audio, track.source, range, tail));
+            // Audio cut off after retimed ranges is not crossfaded, as in the export.
+            track.collect_tail =
+                crossfade.is_some() && index + 1 < range_count && !range.is_retimed();
+        }
+
+        for (stream, packet) in input_ctx.packets() {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Ok(false);
+            }
+            let Some(track) = tracks
+                .iter_mut()
+                .find(|track| track.stream_index == stream.index())
+            else {
+                continue;
+            };
+            if let Some(pts) = packet.pts() {
+                let pts_secs = pts as f64 * f64::from(track.time_base.numerator())
+                    / f64::from(track.time_base.denominator());
+                if pts_secs > range.end_secs + 0.5 {
+                    track.past_range_end = true;
+                }
+            }
+            if let Some(decoder) = track.decoder.as_mut() {
+                decoder.send_packet(&packet)?;
+            }
+            track.receive_frames(
+                &mut decoded,
+                &mut resampled,
+                range,
+                crossfade.unwrap_or(0.0),
+            )?;
+            meter_pending(&mut tracks, &mut mixed, &mut meters, false);
+            if tracks.iter().all(|track| track.past_range_end) {
+                break;
+            }
+        }
+
+        for track in &mut tracks {
+            if let Some(decoder) = track.decoder.as_mut() {
+                decoder.send_eof()?;
+            }
+            track.receive_frames(
+                &mut decoded,
+                &mut resampled,
+                range,
+                crossfade.unwrap_or(0.0),
+            )?;
+            track.decoder = None;
+            track.edit = None;
+        }
+        meter_pending(&mut tracks, &mut mixed, &mut meters, true);
+
+        measured_secs += range.output_duration_secs();
+        let _ = progress_tx.send(ClipExportUpdate::Progress {
+            phase: ClipExportPhase::Preparing,
+            fraction: (measured_secs / total_secs).min(1.0) as f32,
+            message: "Measuring loudness".to_string(),
+        });
+    }
+
+    let measured_lufs = meters
+        .iter()
+        .filter_map(LoudnessMeter::integrated_lufs)
+        .reduce(f64::max);
+    let measured_true_peak_dbtp = meters
+        .iter()
+        .filter_map(LoudnessMeter::true_peak_dbtp)
+        .reduce(f64::max);
+    request.loudness = Some(LoudnessNormalization {
+        measured_lufs,
+        measured_true_peak_dbtp,
+        ..loudness
+    });
+    info!(
+        measured_lufs = ?measured_lufs,
+        measured_true_peak_dbtp = ?measured_true_peak_dbtp,
+        target_lufs = loudness.target_lufs,
+        elapsed_secs = format!("{:.2}", started_at.elapsed().as_secs_f64()),
+        "Measured export loudness"
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::audio_mix::AudioSourceMix;
+
+    fn stereo_sine(frequency: f64, amplitude: f64, phase: f64, secs: f64) -> Vec<(f32, f32)> {
+        let rate = f64::from(EXPORT_AUDIO_RATE);
+        (0..(secs * rate) as usize)
+            .map(|index| {
+                let sample = amplitude
+                    * (2.0 * std::f64::consts::PI * frequency * index as f64 / rate + phase).sin();
+                (sample as f32, sample as f32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stereo_sine_reads_its_level_in_lufs() {
+        // A 1 kHz sine on both channels reads its peak level in dBFS.
+        let mut meter = LoudnessMeter::new();
+        meter.push(&stereo_sine(997.0, 0.1, 0.0, 5.0));
+        let lufs = meter.integrated_lufs().unwrap();
+        assert!((lufs + 20.0).abs() < 0.3, "measured {lufs:.2} LUFS");
+    }
+
+    /// Measures `ranges` of the sample lists next to them the way
+    /// [`measure_request_loudness`] measures decoded tracks.
+    fn measure_ranges(ranges: &[(TimeRange, Vec<(f32, f32)>)], mix: &ExportAudioMix) -> f64 {
+        let mut meter = LoudnessMeter::new();
+        for (range, samples) in ranges {
+            if !is_measured_range(range) {
+                continue;
+            }
+            meter.set_block_weight(1.0 / range.effective_speed());
+            let mut edit = RangeEdit::new(mix, 0, range, Vec::new());
+            let edited: Vec<_> = samples.iter().map(|&sample| edit.apply(sample)).collect();
+            meter.push(&edited);
+        }
+        meter.integrated_lufs().unwrap()
+    }
+
+    fn sine_range(range: TimeRange, amplitude: f64) -> (TimeRange, Vec<(f32, f32)>) {
+        (
+            range,
+            stereo_sine(997.0, amplitude, 0.0, range.duration_secs()),
+        )
+    }
+
+    #[test]
+    fn muted_fast_range_does_not_change_the_loudness() {
+        let mix = ExportAudioMix::default();
+        let quiet = sine_range(TimeRange::new(0.0, 5.0), 0.1);
+        let loud_fast = sine_range(
+            TimeRange {
+                speed: 4.0,
+                ..TimeRange::new(5.0, 25.0)
+            },
+            0.9,
+        );
+        let alone = measure_ranges(std::slice::from_ref(&quiet), &mix);
+        let with_fast = measure_ranges(&[quiet, loud_fast], &mix);
+        assert!(
+            (with_fast - alone).abs() < 0.01,
+            "{with_fast:.2} vs {alone:.2}"
+        );
+    }
+
+    #[test]
+    fn ranges_count_for_their_output_length() {
+        // 2 s at -20 LUFS slowed to 4 s against 4 s at -30 LUFS: the loud range is half
+        // the output, so the mean energy is (0.01 + 0.001) / 2, or about -22.6 LUFS.
+        let mix = ExportAudioMix::default();
+        let slow = sine_range(
+            TimeRange {
+                speed: 0.5,
+                ..TimeRange::new(0.0, 2.0)
+            },
+            0.1,
+        );
+        let normal = sine_range(TimeRange::new(2.0, 6.0), 0.1 / 10f64.sqrt());
+        let lufs = measure_ranges(&[slow, normal], &mix);
+        assert!((lufs + 22.6).abs() < 0.3, "measured {lufs:.2} LUFS");
+    }
+
+    #[test]
+    fn fades_and_gain_are_measured() {
+        let range = sine_range(TimeRange::new(0.0, 4.0), 0.1);
+        let plain = measure_ranges(std::slice::from_ref(&range), &ExportAudioMix::default());
+        let faded = measure_ranges(
+            std::slice::from_ref(&range),
+            &ExportAudioMix {
+                fade_in_secs: 2.0,
+                fade_out_secs: 2.0,
+                ..ExportAudioMix::default()
+            },
+        );
+        let boosted = measure_ranges(
+            &[range],
+            &ExportAudioMix {
+                sources: vec![AudioSourceMix {
+                    gain_db: 6.0,
+                    muted: false,
+                }],
+                ..ExportAudioMix::default()
+            },
+        );
+        assert!(faded < plain - 2.0, "faded {faded:.2} vs {plain:.2}");
+        assert!(
+            (boosted - plain - 6.0).abs() < 0.1,
+            "boosted {boosted:.2} vs {plain:.2}"
+        );
+    }
+
+    #[test]
+    fn silence_is_gated_out() {
+        let mut meter = LoudnessMeter::new();
+        meter.push(&vec![(0.0, 0.0); EXPORT_AUDIO_RATE as usize * 2]);
+        assert!(meter.integrated_lufs().is_none());
+        assert!(meter.true_peak_dbtp().is_none());
+    }
+
+    #[test]
+    fn true_peak_finds_peaks_between_samples() {
+        // At a quarter of the sample rate and 45° off, every sample misses the crest by 3 dB.
+        let mut meter = LoudnessMeter::new();
+        let samples = stereo_sine(
+            f64::from(EXPORT_AUDIO_RATE) / 4.0,
+            0.5,
+            std::f64::consts::FRAC_PI_4,
+            1.0,
+        );
+        let sample_peak_dbtp = 20.0
+            * samples
+                .iter()
+                .map(|&(left, _)| f64::from(left.abs()))
+                .fold(0.0, f64::max)
+                .log10();
+        meter.push(&samples);
+        let true_peak = meter.true_peak_dbtp().unwrap();
+        assert!((sample_peak_dbtp + 9.03).abs() < 0.1);
+        assert!(
+            (true_peak + 6.02).abs() < 0.5,
+            "measured {true_peak:.2} dBTP"
+        );
+    }
+}
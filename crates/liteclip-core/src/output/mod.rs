@@ -13,12 +13,23 @@
 //! # Key Types
 //!
 //! - [`Muxer`] - FFmpeg-based MP4 muxer
+//! - [`ClipStreamMuxer`] - Muxes a clip chunk by chunk
 //! - [`MuxerConfig`] - Muxer configuration
+//! - [`AudioTrackLayout`] - Mixed or separate system/microphone audio tracks
+//! - [`ClipWindow`] - Portion of the replay buffer to save
+//! - [`ClipMetadata`] - Recording context written as tags and an optional JSON sidecar
+//! - [`MontageExportRequest`] - Several clips joined into one video with transitions
+//! - [`ExportPreset`] - Named export settings, built in or saved in the config
+//! - [`ExportQueue`] - Batch clip exports that resume after a restart
+//! - [`ExportOverlay`] - Watermark image or text drawn over an export
+//! - [`ExportAudioMix`] - Per-track gain and mute, fades and crossfades of an export's audio
+//! - [`LoudnessNormalization`] - Two-pass EBU R128 loudness target of an export
 //! - [`OutputError`] - Output-specific errors
 //!
 //! # Key Functions
 //!
 //! - [`spawn_clip_saver`] - Spawn a background task to save a clip
+//! - [`spawn_montage_export`] - Spawn a background montage export
 //! - [`generate_output_path`] - Generate a timestamped output file path
 //! - [`generate_thumbnail`] - Create a thumbnail from encoded video
 //! - [`h264_nal_type`] / [`hevc_nal_type`] - Parse NAL unit types
@@ -26,39 +37,73 @@
 //! # Example
 //!
 //! ```no_run
+//! use liteclip_core::config::SaveContainer;
 //! use liteclip_core::output::{MuxerConfig, generate_output_path};
 //! use std::path::Path;
 //!
 //! // Generate output path
-//! let output_path = generate_output_path(Path::new("C:/Videos"), Some("game_name")).unwrap();
+//! let output_path =
+//!     generate_output_path(Path::new("C:/Videos"), Some("game_name"), SaveContainer::Mp4)
+//!         .unwrap();
 //!
 //! // Configure muxer
-//! let muxer_config = MuxerConfig::new(1920, 1080, 60.0, output_path);
+//! let muxer_config =
+//!     MuxerConfig::new(1920, 1080, 60.0, output_path).with_container(SaveContainer::Mp4);
 //! ```
 
+#[cfg(feature = "ffmpeg")]
+mod animated_export;
+pub mod audio_mix;
+pub mod clip_metadata;
 pub mod companion_cache;
 pub mod error;
+#[cfg(feature = "ffmpeg")]
+mod export_audio;
+pub mod export_preset;
+pub mod export_queue;
 pub mod functions;
 #[cfg(feature = "ffmpeg")]
+mod loudness;
+pub mod montage;
+#[cfg(feature = "ffmpeg")]
+mod montage_export;
+#[cfg(feature = "ffmpeg")]
 pub mod mp4;
+pub mod overlay;
+#[cfg(feature = "ffmpeg")]
+mod retime;
 pub mod saver;
 #[cfg(feature = "ffmpeg")]
 pub mod sdk_export;
 #[cfg(feature = "ffmpeg")]
 pub mod sdk_ffmpeg_output;
+#[cfg(feature = "ffmpeg")]
+mod smart_cut;
 pub mod types;
 pub mod video_file;
 
-pub use companion_cache::hash_main_video_path;
+pub use audio_mix::{
+    AudioSourceMix, ExportAudioMix, LoudnessNormalization, MAX_CROSSFADE_SECS, MAX_FADE_SECS,
+    MAX_NORMALIZATION_GAIN_DB, MAX_TRACK_GAIN_DB, MIN_TRACK_GAIN_DB,
+};
+pub use clip_metadata::ClipMetadata;
+pub use companion_cache::{hash_main_video_path, metadata_sidecar_path};
 pub use error::{OutputError, OutputResult};
+pub use export_preset::ExportPreset;
+pub use export_queue::{ExportJob, ExportJobId, ExportJobState, ExportQueue, ExportQueueProgress};
 pub use functions::{
     calculate_clip_start_pts, ffmpeg_executable_path, generate_output_path, generate_thumbnail,
     h264_nal_type, hevc_nal_type,
 };
+pub use montage::{MontageClip, MontageExportRequest, MontageTransition};
+pub use overlay::{ExportOverlay, OverlayAnchor, OverlayContent};
 pub use saver::{spawn_clip_saver, SKIP_THUMBNAIL_ENV};
-pub use types::{Muxer, MuxerConfig};
+#[cfg(feature = "ffmpeg")]
+pub use types::ClipStreamMuxer;
+pub use types::{AudioTrackLabel, AudioTrackLayout, ClipWindow, Muxer, MuxerConfig};
 pub use video_file::{
     estimate_export_bitrates, extract_preview_frame, probe_video_file, spawn_clip_export,
-    ClipExportPhase, ClipExportRequest, ClipExportUpdate, CropRect, ExportBitrateEstimate,
-    ExportContainerFormat, TimeRange, VideoFileMetadata,
+    spawn_montage_export, ClipChapter, ClipExportPhase, ClipExportRequest, ClipExportUpdate,
+    CropRect, ExportAudioTracks, ExportBitrateEstimate, ExportContainerFormat, TimeRange,
+    VideoFileMetadata, MAX_RANGE_SPEED, MIN_RANGE_SPEED,
 };
@@ -0,0 +1,389 @@
+//! Montage export: several clips joined into one video.
+//!
+//! A [`MontageExportRequest`] lists clips in playback order, each with its own kept ranges.
+//! The kept ranges of one clip are joined with hard cuts, and consecutive clips are joined
+//! with the request's [`MontageTransition`]. Crossfades and fades through black overlap the
+//! end of one clip with the start of the next, so every transition shortens the montage by
+//! its duration.
+
+use std::path::PathBuf;
+
+use crate::config::EncoderType;
+
+use super::audio_mix::ExportAudioMix;
+use super::video_file::{
+    normalize_output_fps, slice_keep_ranges_for_output_window, ClipExportRequest,
+    ExportAudioTracks, ExportContainerFormat, TimeRange, VideoFileMetadata, FALLBACK_EXPORT_FPS,
+};
+
+/// Transitions shorter than this are made as cuts.
+const MIN_TRANSITION_SECS: f64 = 0.05;
+
+/// How consecutive clips of a montage are joined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MontageTransition {
+    /// Hard cut from one clip to the next (default).
+    #[default]
+    Cut,
+    /// The end of one clip blends into the start of the next; the audio crossfades.
+    Crossfade,
+    /// The picture fades out to black and back in on the next clip; the audio crossfades.
+    FadeToBlack,
+}
+
+impl MontageTransition {
+    /// All transitions, in UI order.
+    pub const ALL: [Self; 3] = [Self::Cut, Self::Crossfade, Self::FadeToBlack];
+
+    /// Human-readable label for UI display.
+    pub fn label(self) -> &'static str {
+        match self {
+            MontageTransition::Cut => "Cut",
+            MontageTransition::Crossfade => "Crossfade",
+            MontageTransition::FadeToBlack => "Fade to black",
+        }
+    }
+}
+
+/// One clip of a montage.
+#[derive(Debug, Clone)]
+pub struct MontageClip {
+    pub input_path: PathBuf,
+    /// Parts of the clip to keep, in source time and playback order.
+    pub keep_ranges: Vec<TimeRange>,
+    /// Probed metadata of `input_path`.
+    pub metadata: VideoFileMetadata,
+}
+
+impl MontageClip {
+    /// Kept duration of the clip, before transitions.
+    pub fn duration_secs(&self) -> f64 {
+        self.keep_ranges
+            .iter()
+            .map(|range| range.duration_secs())
+            .sum()
+    }
+}
+
+/// Export of several clips, joined in order, into one video file.
+///
+/// Every clip is scaled (letterboxed if its aspect ratio differs) to the output resolution,
+/// resampled to the output frame rate, and has its audio tracks mixed into one stereo track.
+/// The size budget and bitrate search are the same as for a single clip.
+#[derive(Debug, Clone)]
+pub struct MontageExportRequest {
+    /// Clips in playback order.
+    pub clips: Vec<MontageClip>,
+    pub output_path: PathBuf,
+    pub transition: MontageTransition,
+    /// Duration of each transition. Clamped to half of the shorter clip it joins; ignored
+    /// for cuts.
+    pub transition_secs: f64,
+    pub target_size_mb: u32,
+    pub audio_bitrate_kbps: u32,
+    pub use_hardware_acceleration: bool,
+    pub preferred_encoder: EncoderType,
+    /// Output resolution. None means the first clip's resolution.
+    pub output_width: Option<u32>,
+    pub output_height: Option<u32>,
+    /// Output frame rate. None means the first clip's frame rate.
+    pub output_fps: Option<f64>,
+    /// Output container format. Only the video containers (MP4, MKV, MOV, WebM) are
+    /// supported.
+    pub container_format: ExportContainerFormat,
+}
+
+impl MontageExportRequest {
+    /// Whether any clip has audio. Clips without audio then contribute silence.
+    pub fn has_audio(&self) -> bool {
+        self.clips.iter().any(|clip| clip.metadata.has_audio)
+    }
+
+    /// Duration of the transition between clip `index` and the next one, or 0 after the
+    /// last clip and for cuts (including transitions too short to show).
+    pub fn transition_overlap_secs(&self, index: usize) -> f64 {
+        if self.transition == MontageTransition::Cut || index + 1 >= self.clips.len() {
+            return 0.0;
+        }
+        let shorter_clip_secs = self.clips[index]
+            .duration_secs()
+            .min(self.clips[index + 1].duration_secs());
+        let overlap_secs = self.transition_secs.min(shorter_clip_secs / 2.0);
+        if overlap_secs < MIN_TRANSITION_SECS {
+            0.0
+        } else {
+            overlap_secs
+        }
+    }
+
+    /// Start of every clip on the montage timeline.
+    pub fn clip_start_secs(&self) -> Vec<f64> {
+        let mut cursor = 0.0;
+        self.clips
+            .iter()
+            .enumerate()
+            .map(|(index, clip)| {
+                let start = cursor;
+                cursor += clip.duration_secs() - self.transition_overlap_secs(index);
+                start
+            })
+            .collect()
+    }
+
+    /// Duration of the exported montage, transitions included.
+    pub fn output_duration_secs(&self) -> f64 {
+        self.clips
+            .iter()
+            .enumerate()
+            .map(|(index, clip)| clip.duration_secs() - self.transition_overlap_secs(index))
+            .sum::<f64>()
+            .max(0.0)
+    }
+
+    /// Output resolution, rounded down to even numbers.
+    pub fn output_dimensions(&self) -> (u32, u32) {
+        let (width, height) = self
+            .clips
+            .first()
+            .map_or((2, 2), |clip| (clip.metadata.width, clip.metadata.height));
+        (
+            (self.output_width.unwrap_or(width) & !1).max(2),
+            (self.output_height.unwrap_or(height) & !1).max(2),
+        )
+    }
+
+    /// Output frame rate.
+    pub fn output_fps(&self) -> f64 {
+        let first_clip_fps = self.clips.first().map_or(FALLBACK_EXPORT_FPS, |clip| {
+            normalize_output_fps(clip.metadata.fps, FALLBACK_EXPORT_FPS)
+        });
+        normalize_output_fps(self.output_fps.unwrap_or(first_clip_fps), first_clip_fps)
+    }
+
+    /// The montage timeline as a single-clip request, so it can share the single-clip size
+    /// budget, calibration and bitrate search. Its one kept range spans the whole timeline;
+    /// calibration narrows it to sample windows, which [`Self::for_timeline_ranges`] maps back
+    /// onto the clips.
+    pub(crate) fn timeline_request(&self) -> ClipExportRequest {
+        let (width, height) = self.output_dimensions();
+        let fps = self.output_fps();
+        let duration_secs = self.output_duration_secs();
+        ClipExportRequest {
+            input_path: self
+                .clips
+                .first()
+                .map(|clip| clip.input_path.clone())
+                .unwrap_or_default(),
+            output_path: self.output_path.clone(),
+            keep_ranges: vec![TimeRange {
+                start_secs: 0.0,
+                end_secs: duration_secs,
+                speed: 1.0,
+            }],
+            target_size_mb: self.target_size_mb,
+            audio_bitrate_kbps: self.audio_bitrate_kbps,
+            use_hardware_acceleration: self.use_hardware_acceleration,
+            preferred_encoder: self.preferred_encoder,
+            metadata: VideoFileMetadata {
+                duration_secs,
+                width,
+                height,
+                has_audio: self.has_audio(),
+                audio_track_count: usize::from(self.has_audio()),
+                fps,
+                clip_metadata: None,
+                chapters: Vec::new(),
+            },
+            stream_copy: false,
+            smart_cut: false,
+            output_width: Some(width),
+            output_height: Some(height),
+            output_fps: Some(fps),
+            crop: None,
+            post_process_filters: false,
+            container_format: self.container_format,
+            audio_tracks: ExportAudioTracks::Mix,
+            overlays: Vec::new(),
+            audio: ExportAudioMix::default(),
+            loudness: None,
+        }
+    }
+
+    /// The part of the montage covering `timeline_ranges` of its timeline.
+    ///
+    /// Ranges spanning the whole timeline return the montage unchanged. Anything shorter
+    /// (the calibration samples) is cut together without transitions; the time a transition
+    /// overlaps two clips is taken from the earlier one.
+    pub(crate) fn for_timeline_ranges(&self, timeline_ranges: &[TimeRange]) -> Self {
+        let duration_secs = self.output_duration_secs();
+        if let [range] = timeline_ranges {
+            if range.start_secs <= 0.0 && range.end_secs >= duration_secs {
+                return self.clone();
+            }
+        }
+
+        let starts = self.clip_start_secs();
+        let mut clips = Vec::new();
+        for (index, clip) in self.clips.iter().enumerate() {
+            let clip_start = starts[index];
+            let clip_end = starts.get(index + 1).copied().unwrap_or(duration_secs);
+            let keep_ranges: Vec<TimeRange> = timeline_ranges
+                .iter()
+                .filter_map(|range| {
+                    let start = range.start_secs.max(clip_start);
+                    let end = range.end_secs.min(clip_end);
+                    (end > start).then_some((start - clip_start, end - start))
+                })
+                .flat_map(|(window_start, window_secs)| {
+                    slice_keep_ranges_for_output_window(
+                        &clip.keep_ranges,
+                        window_start,
+                        window_secs,
+                    )
+                })
+                .collect();
+            if !keep_ranges.is_empty() {
+                clips.push(MontageClip {
+                    keep_ranges,
+                    ..clip.clone()
+                });
+            }
+        }
+
+        Self {
+            clips,
+            transition: MontageTransition::Cut,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(path: &str, ranges: &[(f64, f64)]) -> MontageClip {
+        MontageClip {
+            input_path: PathBuf::from(path),
+            keep_ranges: ranges
+                .iter()
+                .map(|&(start_secs, end_secs)| TimeRange {
+                    start_secs,
+                    end_secs,
+                    speed: 1.0,
+                })
+                .collect(),
+            metadata: VideoFileMetadata {
+                duration_secs: 60.0,
+                width: 1920,
+                height: 1080,
+                has_audio: true,
+                audio_track_count: 1,
+                fps: 60.0,
+                clip_metadata: None,
+                chapters: Vec::new(),
+            },
+        }
+    }
+
+    fn montage(transition: MontageTransition) -> MontageExportRequest {
+        MontageExportRequest {
+            clips: vec![
+                clip("a.mp4", &[(0.0, 10.0)]),
+                clip("b.mp4", &[(5.0, 7.0), (20.0, 22.0)]),
+                clip("c.mp4", &[(0.0, 8.0)]),
+            ],
+            output_path: PathBuf::from("montage.mp4"),
+            transition,
+            transition_secs: 1.0,
+            target_size_mb: 25,
+            audio_bitrate_kbps: 128,
+            use_hardware_acceleration: false,
+            preferred_encoder: EncoderType::Auto,
+            output_width: None,
+            output_height: None,
+            output_fps: None,
+            container_format: ExportContainerFormat::Mp4,
+        }
+    }
+
+    #[test]
+    fn transitions_overlap_clips_on_the_timeline() {
+        let cut = montage(MontageTransition::Cut);
+        assert_eq!(cut.output_duration_secs(), 22.0);
+        assert_eq!(cut.clip_start_secs(), vec![0.0, 10.0, 14.0]);
+
+        let fade = montage(MontageTransition::Crossfade);
+        assert_eq!(fade.transition_overlap_secs(0), 1.0);
+        assert_eq!(fade.transition_overlap_secs(2), 0.0);
+        assert_eq!(fade.output_duration_secs(), 20.0);
+        assert_eq!(fade.clip_start_secs(), vec![0.0, 9.0, 12.0]);
+    }
+
+    #[test]
+    fn transition_is_clamped_to_half_the_shorter_clip() {
+        let mut fade = montage(MontageTransition::FadeToBlack);
+        fade.transition_secs = 5.0;
+        // Clip b keeps 4 s, so both of its transitions are capped at 2 s.
+        assert_eq!(fade.transition_overlap_secs(0), 2.0);
+        assert_eq!(fade.transition_overlap_secs(1), 2.0);
+
+        fade.transition_secs = 0.0;
+        assert_eq!(fade.transition_overlap_secs(0), 0.0);
+        assert_eq!(fade.output_duration_secs(), 22.0);
+    }
+
+    #[test]
+    fn full_timeline_keeps_the_montage() {
+        let fade = montage(MontageTransition::Crossfade);
+        let full = fade.for_timeline_ranges(&fade.timeline_request().keep_ranges);
+        assert_eq!(full.transition, MontageTransition::Crossfade);
+        assert_eq!(full.clips.len(), 3);
+    }
+
+    #[test]
+    fn timeline_windows_map_back_onto_clips() {
+        let fade = montage(MontageTransition::Crossfade);
+        let sample = fade.for_timeline_ranges(&[
+            TimeRange {
+                start_secs: 8.0,
+                end_secs: 11.0,
+                speed: 1.0,
+            },
+            TimeRange {
+                start_secs: 18.0,
+                end_secs: 20.0,
+                speed: 1.0,
+            },
+        ]);
+        assert_eq!(sample.transition, MontageTransition::Cut);
+        assert_eq!(sample.clips.len(), 3);
+        // 8..9 s is the end of clip a; 9..11 s is the first 2 s of clip b.
+        assert_eq!(
+            sample.clips[0].keep_ranges,
+            vec![TimeRange {
+                start_secs: 8.0,
+                end_secs: 9.0,
+                speed: 1.0
+            }]
+        );
+        assert_eq!(
+            sample.clips[1].keep_ranges,
+            vec![TimeRange {
+                start_secs: 5.0,
+                end_secs: 7.0,
+                speed: 1.0
+            }]
+        );
+        // Clip c starts at 12 s on the timeline.
+        assert_eq!(
+            sample.clips[2].keep_ranges,
+            vec![TimeRange {
+                start_secs: 6.0,
+                end_secs: 8.0,
+                speed: 1.0
+            }]
+        );
+        assert_eq!(sample.output_duration_secs(), 5.0);
+    }
+}
@@ -0,0 +1,869 @@
+//! Montage export (several clips joined into one video).
+//!
+//! All clips go through one filter graph. Every clip has a `buffer` source, plus an
+//! `abuffer` source per audio track, feeding its own chain: the picture is scaled and
+//! letterboxed to the output size, resampled to the output framerate and padded or trimmed
+//! to the clip's kept duration, and the audio tracks are mixed, resampled to 48 kHz stereo
+//! and padded or trimmed the same way. Clips without audio get silence. The clips are then
+//! joined with `concat` (cuts) or a chain of `xfade` and `acrossfade` filters (transitions).
+//!
+//! Clips are decoded one after another. A transition only needs the tail of the previous
+//! clip, which the graph holds until the next clip's frames arrive.
+
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+    Arc,
+};
+use std::time::Instant;
+use tracing::info;
+
+use super::export_audio::{AudioOutput, EXPORT_AUDIO_RATE, MIN_TRACK_BITRATE_KBPS};
+use super::montage::{MontageClip, MontageExportRequest, MontageTransition};
+use super::sdk_export::{
+    export_encoder_pixel_format_for_container, muxer_header_opts_for_container,
+    open_export_video_encoder, seek_to_seconds, time_base_to_secs_per_tick,
+};
+use super::video_file::{
+    ClipExportPhase, ClipExportUpdate, ExportAttemptResult, ExportVideoEncoder, TimeRange,
+};
+
+/// Run one montage export attempt.
+///
+/// Returns `Ok(None)` if the export was cancelled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn attempt_montage_export(
+    montage: &MontageExportRequest,
+    output_path: &Path,
+    video_bitrate_kbps: u32,
+    audio_bitrate_kbps: u32,
+    video_encoder: ExportVideoEncoder,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+    attempt_index: usize,
+    attempt_count: usize,
+    phase: ClipExportPhase,
+) -> Result<Option<ExportAttemptResult>> {
+    let total_duration_secs = montage.output_duration_secs().max(0.1);
+    let (output_width, output_height) = montage.output_dimensions();
+    let output_fps = montage.output_fps().round().max(1.0) as i32;
+
+    let _ = progress_tx.send(ClipExportUpdate::Progress {
+        phase: ClipExportPhase::Preparing,
+        fraction: 0.0,
+        message: "Preparing export".to_string(),
+    });
+
+    info!(
+        "Exporting montage of {} clips -> {:?} ({:?} transitions, {}x{} at {} fps, video bitrate={} kbps, codec={}, container={})",
+        montage.clips.len(),
+        output_path,
+        montage.transition,
+        output_width,
+        output_height,
+        output_fps,
+        video_bitrate_kbps,
+        video_encoder.ffmpeg_name_for_container(montage.container_format),
+        montage.container_format.short_label(),
+    );
+
+    let mut inputs = montage
+        .clips
+        .iter()
+        .map(MontageInput::open)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut output_ctx = ffmpeg::format::output(output_path)
+        .with_context(|| format!("Failed to create output: {:?}", output_path))?;
+    let (video, video_out_idx) = open_export_video_encoder(
+        &mut output_ctx,
+        montage.container_format,
+        video_encoder,
+        output_width,
+        output_height,
+        output_fps,
+        video_bitrate_kbps,
+    )?;
+    // The audio sink of the graph already cuts frames to the encoder's frame size.
+    let mut audio = if montage.has_audio() {
+        Some(AudioOutput::open(
+            &mut output_ctx,
+            montage.container_format,
+            audio_bitrate_kbps.max(MIN_TRACK_BITRATE_KBPS),
+            true,
+            None,
+        )?)
+    } else {
+        None
+    };
+
+    let mut graph = MontageFilterGraph::new(
+        montage,
+        &inputs,
+        export_encoder_pixel_format_for_container(video_encoder, montage.container_format),
+        audio.as_ref().map(AudioOutput::encoder),
+    )?;
+
+    let header_opts = muxer_header_opts_for_container(montage.container_format);
+    output_ctx
+        .write_header_with(header_opts)
+        .with_context(|| "Failed to write output header")?;
+    if let Some(audio) = audio.as_mut() {
+        audio.after_header(&output_ctx, montage.container_format)?;
+    }
+    let video_out_time_base = output_ctx
+        .stream(video_out_idx)
+        .context("Missing output video stream")?
+        .time_base();
+
+    let mut encoders = MontageEncoders {
+        video,
+        video_out_idx,
+        video_out_time_base,
+        next_video_pts: 0,
+        audio,
+        filtered_video: ffmpeg::frame::Video::empty(),
+        filtered_audio: ffmpeg::frame::Audio::empty(),
+    };
+
+    let clip_starts = montage.clip_start_secs();
+    let started_at = Instant::now();
+    let mut last_progress_time = started_at;
+    let mut processed_duration = 0.0f64;
+    let mut decoded_video = ffmpeg::frame::Video::empty();
+    let mut decoded_audio = ffmpeg::frame::Audio::empty();
+
+    for (clip_index, (clip, input)) in montage.clips.iter().zip(inputs.iter_mut()).enumerate() {
+        let video_stream_idx = input.video_stream_idx;
+        let video_secs_per_tick = time_base_to_secs_per_tick(
+            input
+                .ctx
+                .stream(video_stream_idx)
+                .context("Missing video stream")?
+                .time_base(),
+        )
+        .context("Invalid video time base")?;
+        let mut clip_cursor_secs = 0.0f64;
+
+        for range in &clip.keep_ranges {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            let range_clip_start_secs = clip_cursor_secs;
+            clip_cursor_secs += range.duration_secs();
+
+            seek_to_seconds(&mut input.ctx, range.start_secs);
+            let mut video_decoder = open_decoder(&input.ctx, video_stream_idx)?.video()?;
+            let mut audio_tracks = if graph.audio_sink.is_some() {
+                input
+                    .audio_stream_indices
+                    .iter()
+                    .map(|&stream_index| MontageAudioTrack::open(&input.ctx, stream_index))
+                    .collect::<Result<Vec<_>>>()?
+            } else {
+                Vec::new()
+            };
+
+            // After seeking, skip to the first keyframe so every decoded frame is complete.
+            let mut found_first_keyframe = false;
+            let mut stop_video = false;
+
+            for (stream, packet) in input.ctx.packets() {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+
+                let stream_idx = stream.index();
+                if stream_idx == video_stream_idx {
+                    if let Some(pts) = packet.pts() {
+                        if pts as f64 * video_secs_per_tick > range.end_secs + 1.0 {
+                            stop_video = true;
+                        }
+                    }
+                    if !found_first_keyframe {
+                        if !packet.is_key() {
+                            continue;
+                        }
+                        found_first_keyframe = true;
+                    }
+
+                    video_decoder.send_packet(&packet)?;
+                    while video_decoder.receive_frame(&mut decoded_video).is_ok() {
+                        if let Some(clip_secs) = push_video_frame(
+                            &mut decoded_video,
+                            &mut graph.video_sources[clip_index],
+                            video_secs_per_tick,
+                            range,
+                            range_clip_start_secs,
+                        )? {
+                            processed_duration =
+                                processed_duration.max(clip_starts[clip_index] + clip_secs);
+                        }
+                    }
+                } else if let Some(track_index) = audio_tracks
+                    .iter()
+                    .position(|track| track.stream_index == stream_idx)
+                {
+                    let track = &mut audio_tracks[track_index];
+                    if let Some(pts) = packet.pts() {
+                        if pts as f64 * track.secs_per_tick > range.end_secs + 0.5 {
+                            track.past_range_end = true;
+                        }
+                    }
+                    track.decoder.send_packet(&packet)?;
+                    track.receive_frames(
+                        &mut decoded_audio,
+                        &mut graph.audio_sources[clip_index][track_index],
+                        range,
+                        range_clip_start_secs,
+                    )?;
+                }
+
+                encoders.drain(&mut graph, &mut output_ctx)?;
+
+                if stop_video && audio_tracks.iter().all(|track| track.past_range_end) {
+                    break;
+                }
+
+                let now = Instant::now();
+                const MIN_PROGRESS_INTERVAL: std::time::Duration =
+                    std::time::Duration::from_millis(100);
+                if now.duration_since(last_progress_time) >= MIN_PROGRESS_INTERVAL {
+                    last_progress_time = now;
+                    let progress = (processed_duration / total_duration_secs).min(1.0) as f32;
+                    let _ = progress_tx.send(ClipExportUpdate::Progress {
+                        phase,
+                        fraction: progress,
+                        message: format!(
+                            "Attempt {}/{} - clip {}/{} - {:.1}s processed",
+                            attempt_index + 1,
+                            attempt_count,
+                            clip_index + 1,
+                            montage.clips.len(),
+                            processed_duration
+                        ),
+                    });
+                }
+            }
+
+            // Flush decoders for this range.
+            video_decoder.send_eof()?;
+            while video_decoder.receive_frame(&mut decoded_video).is_ok() {
+                push_video_frame(
+                    &mut decoded_video,
+                    &mut graph.video_sources[clip_index],
+                    video_secs_per_tick,
+                    range,
+                    range_clip_start_secs,
+                )?;
+            }
+            for (track_index, track) in audio_tracks.iter_mut().enumerate() {
+                track.decoder.send_eof()?;
+                track.receive_frames(
+                    &mut decoded_audio,
+                    &mut graph.audio_sources[clip_index][track_index],
+                    range,
+                    range_clip_start_secs,
+                )?;
+            }
+            encoders.drain(&mut graph, &mut output_ctx)?;
+        }
+
+        // End this clip's chains so its trims and the next transition can complete.
+        graph.finish_clip(clip_index)?;
+        encoders.drain(&mut graph, &mut output_ctx)?;
+        info!(
+            clip_index,
+            clip_duration_secs = clip.duration_secs(),
+            elapsed_secs = started_at.elapsed().as_secs_f64(),
+            "Montage clip processed"
+        );
+    }
+
+    encoders.finish(&mut output_ctx)?;
+    output_ctx
+        .write_trailer()
+        .with_context(|| "Failed to write output trailer")?;
+    drop(graph);
+    drop(inputs);
+
+    let size_bytes = std::fs::metadata(output_path)
+        .with_context(|| format!("Failed to get size of export output file {:?}", output_path))?
+        .len();
+    info!(
+        elapsed_secs = started_at.elapsed().as_secs_f64(),
+        frames = encoders.next_video_pts,
+        size_bytes,
+        "Montage export attempt finished"
+    );
+
+    Ok(Some(ExportAttemptResult {
+        output_path: output_path.to_path_buf(),
+        video_bitrate_kbps,
+        size_bytes,
+    }))
+}
+
+/// An opened montage clip and the streams of it that are exported.
+struct MontageInput {
+    ctx: ffmpeg::format::context::Input,
+    video_stream_idx: usize,
+    /// Every audio track, mixed into one in the graph.
+    audio_stream_indices: Vec<usize>,
+}
+
+impl MontageInput {
+    fn open(clip: &MontageClip) -> Result<Self> {
+        let ctx = ffmpeg::format::input(&clip.input_path)
+            .with_context(|| format!("Failed to open input: {:?}", clip.input_path))?;
+        let video_stream_idx = ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .map(|stream| stream.index())
+            .with_context(|| format!("No video stream found in {:?}", clip.input_path))?;
+        let audio_stream_indices = ctx
+            .streams()
+            .filter(|stream| stream.parameters().medium() == ffmpeg::media::Type::Audio)
+            .map(|stream| stream.index())
+            .collect();
+        Ok(Self {
+            ctx,
+            video_stream_idx,
+            audio_stream_indices,
+        })
+    }
+}
+
+/// Decoder state of one audio track for the kept range being read.
+struct MontageAudioTrack {
+    stream_index: usize,
+    decoder: ffmpeg::decoder::Audio,
+    secs_per_tick: f64,
+    /// A packet past the end of the current range has been read.
+    past_range_end: bool,
+}
+
+impl MontageAudioTrack {
+    fn open(input_ctx: &ffmpeg::format::context::Input, stream_index: usize) -> Result<Self> {
+        let time_base = input_ctx
+            .stream(stream_index)
+            .with_context(|| format!("missing audio stream {} after seek", stream_index))?
+            .time_base();
+        Ok(Self {
+            stream_index,
+            decoder: open_decoder(input_ctx, stream_index)?.audio()?,
+            secs_per_tick: time_base_to_secs_per_tick(time_base)
+                .context("Invalid audio time base")?,
+            past_range_end: false,
+        })
+    }
+
+    /// Pushes every decoded frame inside `range` into `source`, timed on the clip's
+    /// timeline.
+    fn receive_frames(
+        &mut self,
+        frame: &mut ffmpeg::frame::Audio,
+        source: &mut ffmpeg::filter::Context,
+        range: &TimeRange,
+        range_clip_start_secs: f64,
+    ) -> Result<()> {
+        while self.decoder.receive_frame(frame).is_ok() {
+            let Some(ts) = frame.timestamp() else {
+                continue;
+            };
+            let pts_secs = ts as f64 * self.secs_per_tick;
+            if pts_secs < range.start_secs || pts_secs >= range.end_secs {
+                continue;
+            }
+            let clip_secs = range_clip_start_secs + (pts_secs - range.start_secs);
+            frame.set_pts(Some((clip_secs * f64::from(frame.rate())).round() as i64));
+            source
+                .source()
+                .add(frame)
+                .context("Failed to push audio into montage filter graph")?;
+        }
+        Ok(())
+    }
+}
+
+fn open_decoder(
+    input_ctx: &ffmpeg::format::context::Input,
+    stream_index: usize,
+) -> Result<ffmpeg::decoder::Decoder> {
+    let stream = input_ctx
+        .stream(stream_index)
+        .with_context(|| format!("missing stream {} after seek", stream_index))?;
+    let context = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?;
+    Ok(context.decoder())
+}
+
+/// Pushes `frame` into `source`, timed on the clip's timeline, if it lies inside `range`.
+/// Returns the frame's time on the clip's timeline.
+fn push_video_frame(
+    frame: &mut ffmpeg::frame::Video,
+    source: &mut ffmpeg::filter::Context,
+    secs_per_tick: f64,
+    range: &TimeRange,
+    range_clip_start_secs: f64,
+) -> Result<Option<f64>> {
+    let Some(ts) = frame.timestamp() else {
+        return Ok(None);
+    };
+    let pts_secs = ts as f64 * secs_per_tick;
+    if pts_secs < range.start_secs || pts_secs >= range.end_secs {
+        return Ok(None);
+    }
+    let clip_secs = range_clip_start_secs + (pts_secs - range.start_secs);
+    frame.set_pts(Some((clip_secs / secs_per_tick).round() as i64));
+    source
+        .source()
+        .add(frame)
+        .context("Failed to push video frame into montage filter graph")?;
+    Ok(Some(clip_secs))
+}
+
+/// Encoders of the montage output, fed from the filter graph's sinks.
+struct MontageEncoders {
+    video: ffmpeg::encoder::Video,
+    video_out_idx: usize,
+    video_out_time_base: ffmpeg::Rational,
+    next_video_pts: i64,
+    audio: Option<AudioOutput>,
+    filtered_video: ffmpeg::frame::Video,
+    filtered_audio: ffmpeg::frame::Audio,
+}
+
+impl MontageEncoders {
+    /// Encodes every frame the graph has ready.
+    fn drain(
+        &mut self,
+        graph: &mut MontageFilterGraph,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        while graph
+            .video_sink
+            .sink()
+            .frame(&mut self.filtered_video)
+            .is_ok()
+        {
+            self.filtered_video.set_pts(Some(self.next_video_pts));
+            self.next_video_pts += 1;
+            self.filtered_video.set_kind(ffmpeg::picture::Type::None);
+            self.video.send_frame(&self.filtered_video)?;
+            self.write_video_packets(output_ctx)?;
+        }
+        if let (Some(sink), Some(audio)) = (graph.audio_sink.as_mut(), self.audio.as_mut()) {
+            while sink.sink().frame(&mut self.filtered_audio).is_ok() {
+                audio.encode(Some(&self.filtered_audio), output_ctx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the encoders.
+    fn finish(&mut self, output_ctx: &mut ffmpeg::format::context::Output) -> Result<()> {
+        self.video.send_eof()?;
+        self.write_video_packets(output_ctx)?;
+        if let Some(audio) = self.audio.as_mut() {
+            audio.encode(None, output_ctx)?;
+        }
+        Ok(())
+    }
+
+    fn write_video_packets(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.video.receive_packet(&mut packet).is_ok() {
+            packet.rescale_ts(self.video.time_base(), self.video_out_time_base);
+            packet.set_stream(self.video_out_idx);
+            packet
+                .write_interleaved(output_ctx)
+                .context("Failed writing montage video packet")?;
+        }
+        Ok(())
+    }
+}
+
+/// The filter graph normalising and joining all clips of a montage.
+struct MontageFilterGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    /// `buffer` source of every clip.
+    video_sources: Vec<ffmpeg::filter::Context>,
+    /// `abuffer` sources of every clip, one per audio track. Empty without audio output.
+    audio_sources: Vec<Vec<ffmpeg::filter::Context>>,
+    video_sink: ffmpeg::filter::Context,
+    audio_sink: Option<ffmpeg::filter::Context>,
+}
+
+impl MontageFilterGraph {
+    fn new(
+        montage: &MontageExportRequest,
+        inputs: &[MontageInput],
+        pixel_format: ffmpeg::format::Pixel,
+        audio_encoder: Option<&ffmpeg::encoder::Audio>,
+    ) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let buffer = ffmpeg::filter::find("buffer").context("buffer filter not found")?;
+        let abuffer = ffmpeg::filter::find("abuffer").context("abuffer filter not found")?;
+        let buffersink =
+            ffmpeg::filter::find("buffersink").context("buffersink filter not found")?;
+        let abuffersink =
+            ffmpeg::filter::find("abuffersink").context("abuffersink filter not found")?;
+
+        let mut source_names = Vec::new();
+        let mut video_sources = Vec::with_capacity(inputs.len());
+        let mut audio_sources = Vec::with_capacity(inputs.len());
+        for (index, input) in inputs.iter().enumerate() {
+            let name = format!("vin{index}");
+            let args = video_source_args(&input.ctx, input.video_stream_idx)?;
+            video_sources.push(
+                graph
+                    .add(&buffer, &name, &args)
+                    .context("Failed to add buffer filter to graph")?,
+            );
+            source_names.push(name);
+
+            let mut tracks = Vec::new();
+            if audio_encoder.is_some() {
+                for (track, &stream_index) in input.audio_stream_indices.iter().enumerate() {
+                    let name = format!("ain{index}_{track}");
+                    let args = audio_source_args(&input.ctx, stream_index)?;
+                    tracks.push(
+                        graph
+                            .add(&abuffer, &name, &args)
+                            .context("Failed to add abuffer filter to graph")?,
+                    );
+                    source_names.push(name);
+                }
+            }
+            audio_sources.push(tracks);
+        }
+
+        let video_sink = graph
+            .add(&buffersink, "vout", "")
+            .context("Failed to add buffersink filter to graph")?;
+        let mut audio_sink = match audio_encoder {
+            Some(_) => Some(
+                graph
+                    .add(&abuffersink, "aout", "")
+                    .context("Failed to add abuffersink filter to graph")?,
+            ),
+            None => None,
+        };
+
+        let pixel_format_name = pixel_format
+            .descriptor()
+            .context("Unknown encoder pixel format")?
+            .name();
+        let audio_tracks: Vec<usize> = audio_sources.iter().map(Vec::len).collect();
+        let spec = montage_filter_spec(
+            montage,
+            &audio_tracks,
+            pixel_format_name,
+            audio_encoder.map(|encoder| encoder.format().name()),
+        );
+
+        let mut parser = graph.output(&source_names[0], 0)?;
+        for name in &source_names[1..] {
+            parser = parser.output(name, 0)?;
+        }
+        parser = parser.input("vout", 0)?;
+        if audio_sink.is_some() {
+            parser = parser.input("aout", 0)?;
+        }
+        parser
+            .parse(&spec)
+            .with_context(|| format!("Failed to build montage filter graph: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate montage filter graph")?;
+
+        if let (Some(sink), Some(encoder)) = (audio_sink.as_mut(), audio_encoder) {
+            if encoder.frame_size() > 0 {
+                sink.sink().set_frame_size(encoder.frame_size());
+            }
+        }
+
+        Ok(Self {
+            graph,
+            video_sources,
+            audio_sources,
+            video_sink,
+            audio_sink,
+        })
+    }
+
+    /// Ends the sources of clip `index` after its last frame.
+    fn finish_clip(&mut self, index: usize) -> Result<()> {
+        self.video_sources[index]
+            .source()
+            .flush()
+            .context("Failed to flush montage video source")?;
+        for source in &mut self.audio_sources[index] {
+            source
+                .source()
+                .flush()
+                .context("Failed to flush montage audio source")?;
+        }
+        Ok(())
+    }
+}
+
+/// `buffer` arguments for the video stream of a clip.
+fn video_source_args(
+    input_ctx: &ffmpeg::format::context::Input,
+    stream_index: usize,
+) -> Result<String> {
+    let stream = input_ctx
+        .stream(stream_index)
+        .context("Missing video stream")?;
+    let time_base = stream.time_base();
+    let decoder = open_decoder(input_ctx, stream_index)?.video()?;
+    let aspect = decoder.aspect_ratio();
+    let (aspect_num, aspect_den) = if aspect.numerator() > 0 && aspect.denominator() > 0 {
+        (aspect.numerator(), aspect.denominator())
+    } else {
+        (1, 1)
+    };
+    Ok(format!(
+        "video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::ffi::AVPixelFormat::from(decoder.format()) as i32,
+        time_base.numerator(),
+        time_base.denominator(),
+        aspect_num,
+        aspect_den,
+    ))
+}
+
+/// `abuffer` arguments for an audio track of a clip. Frames are pushed with timestamps in
+/// samples.
+fn audio_source_args(
+    input_ctx: &ffmpeg::format::context::Input,
+    stream_index: usize,
+) -> Result<String> {
+    let decoder = open_decoder(input_ctx, stream_index)?.audio()?;
+    let layout = if decoder.channel_layout().is_empty() {
+        ffmpeg::ChannelLayout::default(i32::from(decoder.channels()))
+    } else {
+        decoder.channel_layout()
+    };
+    Ok(format!(
+        "time_base=1/{rate}:sample_rate={rate}:sample_fmt={format}:channel_layout=0x{layout:x}",
+        rate = decoder.rate(),
+        format = decoder.format().name(),
+        layout = layout.bits(),
+    ))
+}
+
+/// Filter graph description joining the clips of `montage`.
+///
+/// Reads the sources `vin{clip}` and `ain{clip}_{track}` (`audio_tracks[clip]` of them) and
+/// writes `vout`, plus `aout` when `audio_sample_format` is set.
+fn montage_filter_spec(
+    montage: &MontageExportRequest,
+    audio_tracks: &[usize],
+    pixel_format: &str,
+    audio_sample_format: Option<&str>,
+) -> String {
+    let (width, height) = montage.output_dimensions();
+    let fps = montage.output_fps().round().max(1.0) as i32;
+    let rate = EXPORT_AUDIO_RATE;
+    let mut chains = Vec::new();
+
+    for (index, clip) in montage.clips.iter().enumerate() {
+        let duration = clip.duration_secs();
+        // tpad covers frames missing at the end of a range so every clip lasts exactly its
+        // kept duration, which the transition offsets rely on.
+        chains.push(format!(
+            "[vin{index}]scale={width}:{height}:force_original_aspect_ratio=decrease:force_divisible_by=2,\
+             pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps},format={pixel_format},\
+             tpad=stop_mode=clone:stop_duration=1,trim=duration={duration:.6},setpts=PTS-STARTPTS[v{index}]"
+        ));
+
+        let Some(sample_format) = audio_sample_format else {
+            continue;
+        };
+        let aformat = format!(
+            "aformat=sample_fmts={sample_format}:sample_rates={rate}:channel_layouts=stereo"
+        );
+        let chain = match audio_tracks.get(index).copied().unwrap_or(0) {
+            0 => format!(
+                "anullsrc=channel_layout=stereo:sample_rate={rate},atrim=duration={duration:.6},{aformat}"
+            ),
+            tracks => {
+                let labels: String = (0..tracks)
+                    .map(|track| format!("[ain{index}_{track}]"))
+                    .collect();
+                let mix = if tracks > 1 {
+                    format!("amix=inputs={tracks}:duration=longest:normalize=0,")
+                } else {
+                    String::new()
+                };
+                format!(
+                    "{labels}{mix}aresample={rate}:async=1:first_pts=0,{aformat},apad,\
+                     atrim=duration={duration:.6},asetpts=PTS-STARTPTS"
+                )
+            }
+        };
+        chains.push(format!("{chain}[a{index}]"));
+    }
+
+    let has_audio = audio_sample_format.is_some();
+    let count = montage.clips.len();
+    let xfade = match montage.transition {
+        MontageTransition::Cut => None,
+        MontageTransition::Crossfade => Some("fade"),
+        MontageTransition::FadeToBlack => Some("fadeblack"),
+    };
+    match xfade {
+        Some(xfade) if count > 1 => {
+            let clip_starts = montage.clip_start_secs();
+            let mut video = "v0".to_string();
+            let mut audio = "a0".to_string();
+            for index in 1..count {
+                let (next_video, next_audio) = if index + 1 == count {
+                    ("vout".to_string(), "aout".to_string())
+                } else {
+                    (format!("vx{index}"), format!("ax{index}"))
+                };
+                let overlap = montage.transition_overlap_secs(index - 1);
+                if overlap > 0.0 {
+                    chains.push(format!(
+                        "[{video}][v{index}]xfade=transition={xfade}:duration={overlap:.6}:offset={:.6}[{next_video}]",
+                        clip_starts[index]
+                    ));
+                    if has_audio {
+                        chains.push(format!(
+                            "[{audio}][a{index}]acrossfade=d={overlap:.6}[{next_audio}]"
+                        ));
+                    }
+                } else {
+                    chains.push(format!(
+                        "[{video}][v{index}]concat=n=2:v=1:a=0[{next_video}]"
+                    ));
+                    if has_audio {
+                        chains.push(format!(
+                            "[{audio}][a{index}]concat=n=2:v=0:a=1[{next_audio}]"
+                        ));
+                    }
+                }
+                video = next_video;
+                audio = next_audio;
+            }
+        }
+        _ => {
+            let labels: String = (0..count)
+                .map(|index| {
+                    if has_audio {
+                        format!("[v{index}][a{index}]")
+                    } else {
+                        format!("[v{index}]")
+                    }
+                })
+                .collect();
+            let outputs = if has_audio { "[vout][aout]" } else { "[vout]" };
+            chains.push(format!(
+                "{labels}concat=n={count}:v=1:a={}{outputs}",
+                u8::from(has_audio)
+            ));
+        }
+    }
+
+    chains.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EncoderType;
+    use crate::output::video_file::{ExportContainerFormat, VideoFileMetadata};
+    use std::path::PathBuf;
+
+    fn montage(transition: MontageTransition) -> MontageExportRequest {
+        let clip = |path: &str, end_secs: f64| MontageClip {
+            input_path: PathBuf::from(path),
+            keep_ranges: vec![TimeRange {
+                start_secs: 0.0,
+                end_secs,
+                speed: 1.0,
+            }],
+            metadata: VideoFileMetadata {
+                duration_secs: end_secs,
+                width: 1920,
+                height: 1080,
+                has_audio: true,
+                audio_track_count: 1,
+                fps: 60.0,
+                clip_metadata: None,
+                chapters: Vec::new(),
+            },
+        };
+        MontageExportRequest {
+            clips: vec![clip("a.mp4", 10.0), clip("b.mp4", 6.0), clip("c.mp4", 8.0)],
+            output_path: PathBuf::from("montage.mp4"),
+            transition,
+            transition_secs: 1.0,
+            target_size_mb: 25,
+            audio_bitrate_kbps: 128,
+            use_hardware_acceleration: false,
+            preferred_encoder: EncoderType::Auto,
+            output_width: Some(1280),
+            output_height: Some(720),
+            output_fps: Some(30.0),
+            container_format: ExportContainerFormat::Mp4,
+        }
+    }
+
+    #[test]
+    fn cuts_concatenate_every_clip() {
+        let spec = montage_filter_spec(
+            &montage(MontageTransition::Cut),
+            &[1, 2, 0],
+            "yuv420p",
+            Some("fltp"),
+        );
+        assert!(spec.contains("scale=1280:720:force_original_aspect_ratio=decrease"));
+        assert!(spec.contains("fps=30,format=yuv420p"));
+        assert!(spec.contains("[ain1_0][ain1_1]amix=inputs=2"));
+        assert!(spec.contains("anullsrc=channel_layout=stereo:sample_rate=48000"));
+        assert!(spec.ends_with("[v0][a0][v1][a1][v2][a2]concat=n=3:v=1:a=1[vout][aout]"));
+        assert!(!spec.contains("xfade"));
+    }
+
+    #[test]
+    fn transitions_chain_xfade_at_clip_starts() {
+        let spec = montage_filter_spec(
+            &montage(MontageTransition::FadeToBlack),
+            &[1, 1, 1],
+            "nv12",
+            Some("fltp"),
+        );
+        assert!(spec
+            .contains("[v0][v1]xfade=transition=fadeblack:duration=1.000000:offset=9.000000[vx1]"));
+        assert!(spec.contains(
+            "[vx1][v2]xfade=transition=fadeblack:duration=1.000000:offset=14.000000[vout]"
+        ));
+        assert!(spec.contains("[ax1][a2]acrossfade=d=1.000000[aout]"));
+    }
+
+    #[test]
+    fn video_only_montage_has_no_audio_chains() {
+        let spec = montage_filter_spec(
+            &montage(MontageTransition::Crossfade),
+            &[0, 0, 0],
+            "yuv420p",
+            None,
+        );
+        assert!(spec.contains("xfade=transition=fade:"));
+        assert!(!spec.contains("[a0]"));
+        assert!(!spec.contains("aout"));
+    }
+}
@@ -1,18 +1,24 @@
 #![allow(clippy::similar_names)]
-use crate::encode::EncodedPacket;
+use crate::buffer::Bookmark;
+use crate::config::SaveContainer;
+use crate::encode::ffmpeg::audio::open_audio_encoder;
+use crate::encode::{EncodedPacket, StreamType};
 use anyhow::{Context, Result};
 use ffmpeg_next as ffmpeg;
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 use tracing::info;
 
 use super::{
     functions::{AUDIO_CHANNELS, AUDIO_SAMPLE_RATE},
-    MuxerConfig,
+    AudioTrackLayout, ClipMetadata, MuxerConfig,
 };
 
-const AUDIO_BITRATE_BPS: usize = 192_000;
 const PCM_BYTES_PER_SAMPLE: usize = 2;
 const AUDIO_PACKET_JITTER_TOLERANCE_FRAMES: usize = 8;
+/// How far (in interleaved samples, 1 s) one audio stream may run ahead of another before
+/// the mixer stops waiting for the slower one.
+const MIX_STREAM_SKEW_SAMPLES: usize = AUDIO_SAMPLE_RATE as usize * AUDIO_CHANNELS as usize;
 
 struct EncodedAudioPacket {
     data: bytes::Bytes,
@@ -23,14 +29,118 @@ struct EncodedAudioPacket {
 pub struct FfmpegMuxer {
     format_context: ffmpeg::format::context::Output,
     video_stream_index: usize,
-    audio_stream_index: Option<usize>,
-    audio_encoder: Option<ffmpeg::encoder::Audio>,
+    /// One entry per output audio stream, in stream order.
+    audio_tracks: Vec<AudioTrack>,
     output_path: PathBuf,
     video_time_base: (i32, i32),
     video_frame_rate: i32,
     audio_time_base: (i32, i32),
-    expect_audio: bool,
-    faststart: bool,
+    /// `movflags` written with the header, if any.
+    movflags: Option<String>,
+    /// Bookmarks written as chapters with the header.
+    bookmarks: Vec<Bookmark>,
+    /// Set by the first [`Self::write_packets`] call, which also writes the header.
+    progress: Option<MuxProgress>,
+}
+
+/// Clip timeline and counters carried between [`FfmpegMuxer::write_packets`] calls.
+struct MuxProgress {
+    base_qpc: i64,
+    last_video_dts: i64,
+    video_count: usize,
+    audio_count: usize,
+    keyframe_count: usize,
+    min_video_pts: i64,
+    max_video_pts: i64,
+}
+
+/// How buffered audio reaches the audio stream.
+enum AudioTimeline {
+    Copied(CopiedAudioPlacer),
+    Mixed(PcmAudioMixer),
+}
+
+/// Which buffered audio streams feed an output audio track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AudioTrackSource {
+    /// System audio and microphone, mixed.
+    All,
+    System,
+    Microphone,
+}
+
+impl AudioTrackSource {
+    fn accepts(self, packet: &EncodedPacket) -> bool {
+        match self {
+            Self::All => true,
+            Self::System => matches!(packet.stream, StreamType::SystemAudio),
+            Self::Microphone => matches!(packet.stream, StreamType::Microphone),
+        }
+    }
+}
+
+/// One audio stream of the output file and the buffered audio on its way there.
+struct AudioTrack {
+    stream_index: usize,
+    source: AudioTrackSource,
+    /// Encoder for buffered PCM; `None` when buffered audio is stream-copied.
+    encoder: Option<ffmpeg::encoder::Audio>,
+    /// Samples per packet when buffered audio is already encoded and stream-copied.
+    copied_frame_size: Option<i64>,
+    /// Set up by the first [`FfmpegMuxer::write_packets`] call.
+    timeline: Option<AudioTimeline>,
+    /// Encoded audio not yet written because no video packet at or after it has been.
+    pending: VecDeque<EncodedAudioPacket>,
+}
+
+impl AudioTrack {
+    fn start(&mut self, base_qpc: i64) -> Result<()> {
+        self.timeline = match (self.copied_frame_size, self.encoder.as_ref()) {
+            (Some(frame_size), _) => Some(AudioTimeline::Copied(CopiedAudioPlacer::new(
+                base_qpc, frame_size,
+            ))),
+            (None, Some(encoder)) => {
+                Some(AudioTimeline::Mixed(PcmAudioMixer::new(encoder, base_qpc)?))
+            }
+            (None, None) => None,
+        };
+        Ok(())
+    }
+
+    /// Places (or mixes and encodes) this track's share of `audio_packets` into `pending`.
+    fn push(&mut self, audio_packets: &[&EncodedPacket]) -> Result<()> {
+        let packets: Vec<&EncodedPacket> = audio_packets
+            .iter()
+            .copied()
+            .filter(|packet| self.source.accepts(packet))
+            .collect();
+        match self.timeline.as_mut() {
+            Some(AudioTimeline::Copied(placer)) => {
+                placer.place(&packets, i64::MAX, &mut self.pending);
+            }
+            Some(AudioTimeline::Mixed(mixer)) => {
+                if let Some(encoder) = self.encoder.as_mut() {
+                    mixer.push(&packets);
+                    mixer.encode_ready(encoder, &mut self.pending)?;
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Encodes the rest of a mixed track and trims the track to end with the video.
+    fn finish(&mut self, base_qpc: i64, video_end_qpc: i64) -> Result<()> {
+        if let (Some(AudioTimeline::Mixed(mixer)), Some(encoder)) =
+            (self.timeline.as_mut(), self.encoder.as_mut())
+        {
+            mixer.finish(encoder, video_end_qpc, &mut self.pending)?;
+        }
+        // Audio placed before the video end was known may run past it.
+        let end_sample = qpc_to_sample_index(video_end_qpc.saturating_sub(base_qpc)) as i64;
+        self.pending.retain(|packet| packet.pts < end_sample);
+        Ok(())
+    }
 }
 
 impl FfmpegMuxer {
@@ -43,8 +153,11 @@ impl FfmpegMuxer {
         config: &MuxerConfig,
     ) -> Result<Self> {
         crate::output::saver::log_save_memory("FfmpegMuxer::new_entry", None, None);
-        let mut format_context = ffmpeg::format::output(&output_path)
-            .context("Failed to create output format context")?;
+        // Pick the muxer from the configured container, not the extension, so temporary
+        // paths (such as `.extending.mkv`) are written in the right format.
+        let mut format_context =
+            ffmpeg::format::output_as(&output_path, container_format_name(config.container))
+                .context("Failed to create output format context")?;
 
         let rounded_fps = fps.round().clamp(1.0, i32::MAX as f64) as i32;
         let video_time_base = (1, 90_000);
@@ -83,46 +196,55 @@ impl FfmpegMuxer {
             stream_index
         };
 
-        let mut audio_stream_index = None;
-        let mut audio_encoder = None;
+        let mut audio_tracks = Vec::new();
         if config.expect_audio {
-            let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
-                .context("Failed to find AAC encoder for muxer")?
-                .audio()
-                .context("Failed to use AAC encoder for audio stream")?;
-            let sample_format = codec
-                .formats()
-                .and_then(|mut formats| formats.next())
-                .context("AAC encoder did not report a supported sample format")?;
-
-            let mut stream = format_context.add_stream(codec)?;
-            let stream_index = stream.index();
+            // Buffered PCM is encoded to the clip codec here; already-encoded buffer audio is
+            // stream-copied, using an identically configured encoder only for the stream
+            // parameters.
+            let buffered_codec = config.buffer_audio_codec.clip_codec();
+            let copy_audio = buffered_codec.is_some();
+            let codec = buffered_codec.unwrap_or(config.audio_codec);
+            let bitrate_bps = config.audio_bitrate_kbps as usize * 1000;
+            let sources = match &config.audio_tracks {
+                AudioTrackLayout::Mixed => vec![(AudioTrackSource::All, None)],
+                AudioTrackLayout::Separate { system, microphone } => vec![
+                    (AudioTrackSource::System, Some(system)),
+                    (AudioTrackSource::Microphone, Some(microphone)),
+                ],
+            };
+            for (source, label) in sources {
+                let audio = open_audio_encoder(codec, bitrate_bps, global_header)
+                    .with_context(|| format!("Failed to open {:?} encoder for muxer", codec))?;
+
+                let mut stream = format_context.add_stream(audio.codec())?;
+                let stream_index = stream.index();
+                stream.set_time_base(audio_time_base);
+                stream.set_parameters(&audio);
+                if let Some(label) = label {
+                    let mut metadata = ffmpeg::Dictionary::new();
+                    metadata.set("title", &label.title);
+                    metadata.set("language", &label.language);
+                    stream.set_metadata(metadata);
+                }
 
-            let mut audio = ffmpeg::codec::context::Context::new_with_codec(*codec)
-                .encoder()
-                .audio()?;
-            audio.set_rate(AUDIO_SAMPLE_RATE as i32);
-            audio.set_channel_layout(ffmpeg::channel_layout::ChannelLayout::STEREO);
-            audio.set_format(sample_format);
-            audio.set_bit_rate(AUDIO_BITRATE_BPS);
-            audio.set_max_bit_rate(AUDIO_BITRATE_BPS);
-            audio.set_time_base(audio_time_base);
-            if global_header {
-                audio.set_flags(ffmpeg::codec::flag::Flags::GLOBAL_HEADER);
+                let (encoder, copied_frame_size) = if copy_audio {
+                    (None, Some(i64::from(audio.frame_size()).max(1)))
+                } else {
+                    (Some(audio), None)
+                };
+                audio_tracks.push(AudioTrack {
+                    stream_index,
+                    source,
+                    encoder,
+                    copied_frame_size,
+                    timeline: None,
+                    pending: VecDeque::new(),
+                });
             }
+        }
 
-            let mut aac_opts = ffmpeg::Dictionary::new();
-            aac_opts.set("aac_coder", "fast");
-
-            let audio = audio
-                .open_as_with(codec, aac_opts)
-                .context("Failed to open AAC encoder")?;
-
-            stream.set_time_base(audio_time_base);
-            stream.set_parameters(&audio);
-
-            audio_stream_index = Some(stream_index);
-            audio_encoder = Some(audio);
+        if let Some(metadata) = &config.metadata {
+            format_context.set_metadata(metadata_tags(metadata));
         }
 
         info!("Created native muxer for {:?}", output_path);
@@ -130,24 +252,33 @@ impl FfmpegMuxer {
         Ok(Self {
             format_context,
             video_stream_index,
-            audio_stream_index,
-            audio_encoder,
+            audio_tracks,
             output_path: output_path.to_path_buf(),
             video_time_base,
             video_frame_rate: rounded_fps,
             audio_time_base,
-            expect_audio: config.expect_audio,
-            faststart: config.faststart,
+            movflags: container_movflags(
+                config.container,
+                config.faststart,
+                config.metadata.is_some(),
+            ),
+            bookmarks: config.bookmarks.clone(),
+            progress: None,
         })
     }
 
     /// Writes encoded video and audio packets to the MP4 file.
     ///
-    /// This method handles:
-    /// 1. Calculating a common base timestamp (QPC-based) to normalize all packets to start at 0.
-    /// 2. Normalizing and interleaved writing of video and audio packets.
-    /// 3. Normalizing PTS/DTS values for the target MP4 timebase.
-    /// 4. Handling fragmented MP4 (+frag_keyframe) to minimize memory usage during save.
+    /// May be called repeatedly with consecutive chunks of a clip, each stream continuing in
+    /// decode order; [`Self::finish`] then writes the trailer. The first call:
+    /// 1. Calculates a common base timestamp (QPC-based) to normalize all packets to start at 0.
+    /// 2. Writes the header (with `+faststart` when configured and the container has one,
+    ///    or fragmenting at keyframes for fragmented MP4).
+    ///
+    /// Every call then places its audio on the clip timeline (mixing and encoding buffered
+    /// PCM as it becomes final) and interleaves it with the video by decode time, converting
+    /// PTS/DTS to the MP4 time bases. Audio ahead of the last video packet is held until the
+    /// next call or [`Self::finish`].
     ///
     /// # Arguments
     ///
@@ -156,97 +287,29 @@ impl FfmpegMuxer {
     ///
     /// # Returns
     ///
-    /// Tuple of (video_count, audio_count) written.
+    /// Tuple of (video_count, audio_count) written by this call.
     pub fn write_packets(
         &mut self,
         video_packets: &[&EncodedPacket],
         audio_packets: &[&EncodedPacket],
     ) -> Result<(usize, usize)> {
-        if video_packets.is_empty() {
-            anyhow::bail!("No video packets to write");
+        if self.progress.is_none() {
+            if video_packets.is_empty() {
+                anyhow::bail!("No video packets to write");
+            }
+            self.begin(video_packets, audio_packets)?;
         }
-
-        let qpc_freq = crate::buffer::ring::qpc_frequency().max(1);
-
-        let video_start_qpc = video_packets
-            .iter()
-            .filter(|packet| !is_parameter_set_payload(packet.data.as_ref()))
-            .map(|packet| packet.dts)
-            .min()
-            .or_else(|| video_packets.iter().map(|packet| packet.dts).min())
-            .unwrap_or(0);
-
-        let video_end_qpc = {
-            let max_video_pts = video_packets
-                .iter()
-                .map(|packet| packet.pts)
-                .max()
-                .unwrap_or(0);
-            let frame_duration = default_video_frame_qpc(self.video_frame_rate);
-            max_video_pts.saturating_add(frame_duration)
+        let Some(base_qpc) = self.progress.as_ref().map(|progress| progress.base_qpc) else {
+            anyhow::bail!("Muxer timeline was not initialized");
         };
+        let qpc_freq = crate::buffer::ring::qpc_frequency().max(1);
 
-        let min_audio_qpc = audio_packets.iter().map(|packet| packet.pts).min();
-
-        let base_qpc = std::iter::once(video_start_qpc)
-            .chain(min_audio_qpc)
-            .min()
-            .unwrap_or(0);
-
-        let mut options = ffmpeg::Dictionary::new();
-        if self.faststart {
-            options.set("movflags", "+faststart");
-        }
-        self.format_context
-            .write_header_with(options)
-            .context("Failed to write MP4 header")?;
-
-        let encoded_audio: Vec<EncodedAudioPacket> = if let (Some(_), Some(audio_encoder)) =
-            (self.audio_stream_index, self.audio_encoder.as_mut())
-        {
-            if audio_packets.is_empty() && !self.expect_audio {
-                vec![]
-            } else {
-                mix_and_encode_audio_chunks(audio_encoder, audio_packets, base_qpc, video_end_qpc)?
-            }
-        } else {
-            vec![]
-        };
-
-        // Log video stream info.
-        {
-            let qpc_freq = crate::buffer::ring::qpc_frequency();
-            let min_pts = video_packets.iter().map(|p| p.pts).min().unwrap_or(0);
-            let max_pts = video_packets.iter().map(|p| p.pts).max().unwrap_or(0);
-            let duration_ms = if qpc_freq > 0 {
-                max_pts.saturating_sub(min_pts) * 1000 / qpc_freq
-            } else {
-                0
-            };
-            let keyframe_count = video_packets.iter().filter(|p| p.is_keyframe).count();
-            let actual_fps = if duration_ms > 0 {
-                (video_packets.len() as i64 * 1000 / duration_ms) as i32
-            } else {
-                0
-            };
-            info!(
-                "Writing {} video packets: duration={}ms, keyframes={}, expected_fps={}, actual_fps={}",
-                video_packets.len(),
-                duration_ms,
-                keyframe_count,
-                self.video_frame_rate,
-                actual_fps
-            );
+        for track in &mut self.audio_tracks {
+            track.push(audio_packets)?;
         }
 
         // Merge-sort video and audio by decode time (DTS) and write each packet.
         // This aligns with FFmpeg's expectations for fragmented MP4 output.
-        let mut aac_iter = encoded_audio.iter().peekable();
-        let audio_stream_idx = self.audio_stream_index;
-        let audio_time_base = self.audio_time_base;
-        let mut video_count = 0usize;
-        let mut audio_count = 0usize;
-
         let mut video_packets_ordered: Vec<&EncodedPacket> = video_packets.to_vec();
         video_packets_ordered.sort_by_key(|pkt| (pkt.dts, pkt.pts));
 
@@ -256,33 +319,12 @@ impl FfmpegMuxer {
         )
         .max(1);
 
-        let mut last_dts: i64 = -1;
-
+        let mut video_count = 0usize;
+        let mut audio_count = 0usize;
         for pkt in &video_packets_ordered {
-            // Flush AAC packets whose PTS/DTS is at or before this video packet.
+            // Flush audio packets whose PTS/DTS is at or before this video packet.
             let video_us = pkt.dts.saturating_sub(base_qpc).saturating_mul(1_000_000) / qpc_freq;
-
-            while let Some(audio_packet) = aac_iter.peek() {
-                let audio_us =
-                    audio_packet.pts.saturating_mul(1_000_000) / AUDIO_SAMPLE_RATE as i64;
-                if audio_us > video_us {
-                    break;
-                }
-                let Some(audio_packet) = aac_iter.next() else {
-                    break;
-                };
-                if let Some(aidx) = audio_stream_idx {
-                    write_audio_frame_direct(
-                        &mut self.format_context,
-                        aidx,
-                        &audio_packet.data,
-                        audio_packet.pts,
-                        audio_packet.duration,
-                        audio_time_base,
-                    )?;
-                    audio_count += 1;
-                }
-            }
+            audio_count += self.write_pending_audio(video_us)?;
 
             // Write video packet.
             let pts = qpc_to_time_base(
@@ -294,14 +336,21 @@ impl FfmpegMuxer {
                 self.video_time_base.1 as i64,
             );
 
+            let Some(progress) = self.progress.as_mut() else {
+                break;
+            };
             // Enforce strictly monotonically increasing DTS to prevent
             // FFmpeg rejecting the mux with "non monotonically increasing dts".
             // Integer division in qpc_to_time_base can map two close QPC ticks to
             // the same time-base value.
-            let dts = dts.max(last_dts + 1).max(0);
+            let dts = dts.max(progress.last_video_dts + 1).max(0);
             let pts = pts.max(dts);
 
-            last_dts = dts;
+            progress.last_video_dts = dts;
+            progress.video_count += 1;
+            progress.keyframe_count += usize::from(pkt.is_keyframe);
+            progress.min_video_pts = progress.min_video_pts.min(pkt.pts);
+            progress.max_video_pts = progress.max_video_pts.max(pkt.pts);
 
             write_borrowed_video_packet(
                 &mut self.format_context,
@@ -314,40 +363,198 @@ impl FfmpegMuxer {
             video_count += 1;
         }
 
-        // Flush any remaining AAC packets that come after the last video frame.
-        for audio_packet in aac_iter {
-            if let Some(aidx) = audio_stream_idx {
-                write_audio_frame_direct(
-                    &mut self.format_context,
-                    aidx,
-                    &audio_packet.data,
-                    audio_packet.pts,
-                    audio_packet.duration,
-                    audio_time_base,
-                )?;
-                audio_count += 1;
-            }
+        Ok((video_count, audio_count))
+    }
+
+    /// Replaces the clip's metadata tags.
+    ///
+    /// Containers that write their tags with the trailer (MP4 and MOV) get the new tags even
+    /// after packets were written; fragmented MP4 and MKV keep the tags from the header.
+    pub fn set_metadata(&mut self, metadata: &ClipMetadata) {
+        self.format_context.set_metadata(metadata_tags(metadata));
+    }
+
+    /// Writes the audio still held back and the trailer, and releases the audio encoders.
+    ///
+    /// Audio is trimmed (or padded with silence when mixed from PCM) to end with the last
+    /// video frame.
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (video_count, audio_count) written over all [`Self::write_packets`] calls.
+    pub fn finish(&mut self) -> Result<(usize, usize)> {
+        let Some(progress) = self.progress.as_ref() else {
+            anyhow::bail!("No video packets to write");
+        };
+        let base_qpc = progress.base_qpc;
+        let video_end_qpc = progress
+            .max_video_pts
+            .saturating_add(default_video_frame_qpc(self.video_frame_rate));
+
+        // Log video stream info.
+        {
+            let qpc_freq = crate::buffer::ring::qpc_frequency();
+            let duration_ms = if qpc_freq > 0 {
+                progress
+                    .max_video_pts
+                    .saturating_sub(progress.min_video_pts)
+                    * 1000
+                    / qpc_freq
+            } else {
+                0
+            };
+            let actual_fps = if duration_ms > 0 {
+                (progress.video_count as i64 * 1000 / duration_ms) as i32
+            } else {
+                0
+            };
+            info!(
+                "Wrote {} video packets: duration={}ms, keyframes={}, expected_fps={}, actual_fps={}",
+                progress.video_count,
+                duration_ms,
+                progress.keyframe_count,
+                self.video_frame_rate,
+                actual_fps
+            );
         }
-        // aac_iter is consumed by the for loop above.
-        // encoded_audio goes out of scope after video_packets_ordered is dropped below.
 
-        // Release video packet reference Vec — all data has been written to the file.
-        drop(video_packets_ordered);
+        for track in &mut self.audio_tracks {
+            track.finish(base_qpc, video_end_qpc)?;
+        }
+
+        // Flush any remaining audio packets that come after the last video frame.
+        self.write_pending_audio(i64::MAX)?;
+        for track in &mut self.audio_tracks {
+            track.timeline = None;
+        }
+
+        // The last chapter was left open in `begin`; close it at the end of the clip.
+        // Containers that write chapters with the header keep its zero length.
+        let chapter_count = self.format_context.nb_chapters() as usize;
+        if let Some(mut last) = chapter_count
+            .checked_sub(1)
+            .and_then(|index| self.format_context.chapter_mut(index))
+        {
+            let end = qpc_to_time_base(
+                video_end_qpc.saturating_sub(base_qpc),
+                self.video_time_base.1 as i64,
+            );
+            if end > last.start() {
+                last.set_end(end);
+            }
+        }
 
         crate::output::saver::log_save_memory("before write_trailer", None, None);
         self.format_context.write_trailer()?;
         crate::output::saver::log_save_memory("after write_trailer", None, None);
 
-        // Explicitly drop audio encoder to free FFmpeg resources early
-        let _ = self.audio_encoder.take();
+        // Explicitly drop audio encoders to free FFmpeg resources early
+        for track in &mut self.audio_tracks {
+            let _ = track.encoder.take();
+        }
         crate::output::saver::log_save_memory("after audio_encoder drop", None, None);
 
+        let Some(progress) = self.progress.as_ref() else {
+            anyhow::bail!("Muxer timeline was not initialized");
+        };
         info!(
             "Muxed {} video packets and {} audio packets to {:?}",
-            video_count, audio_count, self.output_path
+            progress.video_count, progress.audio_count, self.output_path
         );
 
-        Ok((video_count, audio_count))
+        Ok((progress.video_count, progress.audio_count))
+    }
+
+    /// Fixes the clip timeline from the first chunk, writes the header and sets up audio.
+    fn begin(
+        &mut self,
+        video_packets: &[&EncodedPacket],
+        audio_packets: &[&EncodedPacket],
+    ) -> Result<()> {
+        let video_start_qpc = video_packets
+            .iter()
+            .filter(|packet| !is_parameter_set_payload(packet.data.as_ref()))
+            .map(|packet| packet.dts)
+            .min()
+            .or_else(|| video_packets.iter().map(|packet| packet.dts).min())
+            .unwrap_or(0);
+
+        let min_audio_qpc = audio_packets.iter().map(|packet| packet.pts).min();
+
+        let base_qpc = std::iter::once(video_start_qpc)
+            .chain(min_audio_qpc)
+            .min()
+            .unwrap_or(0);
+
+        for (index, chapter) in chapter_spans(&self.bookmarks, base_qpc)
+            .into_iter()
+            .enumerate()
+        {
+            let time_base_den = self.video_time_base.1 as i64;
+            self.format_context
+                .add_chapter(
+                    index as i64,
+                    self.video_time_base,
+                    qpc_to_time_base(chapter.start_qpc, time_base_den),
+                    qpc_to_time_base(chapter.end_qpc, time_base_den),
+                    &chapter.title,
+                )
+                .context("Failed to add bookmark chapter")?;
+        }
+
+        let mut options = ffmpeg::Dictionary::new();
+        if let Some(movflags) = &self.movflags {
+            options.set("movflags", movflags);
+        }
+        self.format_context
+            .write_header_with(options)
+            .context("Failed to write clip header")?;
+
+        for track in &mut self.audio_tracks {
+            track.start(base_qpc)?;
+        }
+
+        self.progress = Some(MuxProgress {
+            base_qpc,
+            last_video_dts: -1,
+            video_count: 0,
+            audio_count: 0,
+            keyframe_count: 0,
+            min_video_pts: i64::MAX,
+            max_video_pts: i64::MIN,
+        });
+        Ok(())
+    }
+
+    /// Writes held-back audio of every track up to `until_us` on the clip timeline and
+    /// returns the count.
+    fn write_pending_audio(&mut self, until_us: i64) -> Result<usize> {
+        let mut written = 0usize;
+        for track in &mut self.audio_tracks {
+            while let Some(audio_packet) = track.pending.front() {
+                let audio_us =
+                    audio_packet.pts.saturating_mul(1_000_000) / AUDIO_SAMPLE_RATE as i64;
+                if audio_us > until_us {
+                    break;
+                }
+                let Some(audio_packet) = track.pending.pop_front() else {
+                    break;
+                };
+                write_audio_frame_direct(
+                    &mut self.format_context,
+                    track.stream_index,
+                    &audio_packet.data,
+                    audio_packet.pts,
+                    audio_packet.duration,
+                    self.audio_time_base,
+                )?;
+                written += 1;
+            }
+        }
+        if let Some(progress) = self.progress.as_mut() {
+            progress.audio_count += written;
+        }
+        Ok(written)
     }
 }
 
@@ -393,7 +600,6 @@ fn write_borrowed_video_packet(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encode::StreamType;
 
     fn qpc_for_frame_index(target_frame_index: usize) -> i64 {
         let qpc_freq = crate::buffer::ring::qpc_frequency().max(1);
@@ -526,6 +732,155 @@ mod tests {
         );
     }
 
+    #[test]
+    fn copied_audio_packets_snap_jitter_and_drop_overlaps() {
+        let packet_at = |frame: usize| {
+            EncodedPacket::new(
+                vec![0u8; 16],
+                qpc_for_frame_index(frame),
+                qpc_for_frame_index(frame),
+                false,
+                StreamType::SystemAudio,
+            )
+        };
+        let first = packet_at(0);
+        let jittered = packet_at(1024 + 3);
+        let overlapping = packet_at(1024 + 512);
+        let after_gap = packet_at(8192);
+        let past_video = packet_at(20_000);
+        let audio_packets = vec![&first, &jittered, &overlapping, &after_gap, &past_video];
+
+        let placed =
+            place_copied_audio_packets(&audio_packets, 0, qpc_for_frame_index(16_000), 1024);
+
+        let pts: Vec<i64> = placed.iter().map(|packet| packet.pts).collect();
+        assert_eq!(pts, vec![0, 1024, 8192]);
+        assert!(placed.iter().all(|packet| packet.duration == 1024));
+    }
+
+    #[test]
+    fn copied_audio_placement_continues_across_chunks() {
+        let packet_at = |frame: usize| {
+            EncodedPacket::new(
+                vec![0u8; 16],
+                qpc_for_frame_index(frame),
+                qpc_for_frame_index(frame),
+                false,
+                StreamType::SystemAudio,
+            )
+        };
+        let first_chunk = [packet_at(0), packet_at(1024)];
+        let second_chunk = [packet_at(2048 + 5), packet_at(2048 + 512), packet_at(3072)];
+
+        let mut placer = CopiedAudioPlacer::new(0, 1024);
+        let mut placed = VecDeque::new();
+        placer.place(
+            &first_chunk.iter().collect::<Vec<_>>(),
+            i64::MAX,
+            &mut placed,
+        );
+        placer.place(
+            &second_chunk.iter().collect::<Vec<_>>(),
+            i64::MAX,
+            &mut placed,
+        );
+
+        let pts: Vec<i64> = placed.iter().map(|packet| packet.pts).collect();
+        assert_eq!(pts, vec![0, 1024, 2048, 3072]);
+    }
+
+    #[test]
+    fn container_movflags_follow_container() {
+        assert_eq!(
+            container_movflags(SaveContainer::Mp4, true, false).as_deref(),
+            Some("+faststart")
+        );
+        assert_eq!(container_movflags(SaveContainer::Mov, false, false), None);
+        assert_eq!(container_movflags(SaveContainer::Mkv, true, true), None);
+        assert_eq!(
+            container_movflags(SaveContainer::FragmentedMp4, true, false).as_deref(),
+            Some("+frag_keyframe+empty_moov+default_base_moof")
+        );
+        assert_eq!(
+            container_movflags(SaveContainer::Mov, false, true).as_deref(),
+            Some("+use_metadata_tags")
+        );
+        assert_eq!(
+            container_movflags(SaveContainer::Mp4, true, true).as_deref(),
+            Some("+faststart+use_metadata_tags")
+        );
+    }
+
+    #[test]
+    fn chapter_spans_start_at_bookmarks_inside_the_clip() {
+        let bookmark = |pts: i64, label: Option<&str>| Bookmark {
+            pts,
+            label: label.map(str::to_string),
+        };
+        let bookmarks = [
+            bookmark(3_000, None),
+            bookmark(500, Some("before the clip")),
+            bookmark(1_500, Some("Ace")),
+            bookmark(3_000, Some("duplicate")),
+        ];
+
+        let spans = chapter_spans(&bookmarks, 1_000);
+        assert_eq!(
+            spans,
+            vec![
+                ChapterSpan {
+                    start_qpc: 500,
+                    end_qpc: 2_000,
+                    title: "Ace".to_string(),
+                },
+                ChapterSpan {
+                    start_qpc: 2_000,
+                    end_qpc: 2_000,
+                    title: "Bookmark 2".to_string(),
+                },
+            ]
+        );
+        assert!(chapter_spans(&bookmarks, 4_000).is_empty());
+    }
+
+    #[test]
+    fn separate_audio_tracks_take_only_their_stream() {
+        let packet_at = |frame: usize, stream: StreamType| {
+            EncodedPacket::new(
+                vec![0u8; 16],
+                qpc_for_frame_index(frame),
+                qpc_for_frame_index(frame),
+                false,
+                stream,
+            )
+        };
+        let packets = [
+            packet_at(0, StreamType::SystemAudio),
+            packet_at(0, StreamType::Microphone),
+            packet_at(1024, StreamType::SystemAudio),
+        ];
+        let packets: Vec<&EncodedPacket> = packets.iter().collect();
+
+        let track = |source| AudioTrack {
+            stream_index: 1,
+            source,
+            encoder: None,
+            copied_frame_size: Some(1024),
+            timeline: None,
+            pending: VecDeque::new(),
+        };
+        let mut system = track(AudioTrackSource::System);
+        let mut microphone = track(AudioTrackSource::Microphone);
+        for track in [&mut system, &mut microphone] {
+            track.start(0).unwrap();
+            track.push(&packets).unwrap();
+        }
+
+        let pts = |track: &AudioTrack| track.pending.iter().map(|p| p.pts).collect::<Vec<_>>();
+        assert_eq!(pts(&system), vec![0, 1024]);
+        assert_eq!(pts(&microphone), vec![0]);
+    }
+
     #[test]
     fn mix_audio_packets_pads_when_audio_starts_after_video() {
         let audio_start_frame = 100;
@@ -547,6 +902,88 @@ mod tests {
     }
 }
 
+/// libavformat muxer name for `container`.
+fn container_format_name(container: SaveContainer) -> &'static str {
+    match container {
+        SaveContainer::Mp4 | SaveContainer::FragmentedMp4 => "mp4",
+        SaveContainer::Mkv => "matroska",
+        SaveContainer::Mov => "mov",
+    }
+}
+
+/// Container tags for `metadata`.
+fn metadata_tags(metadata: &ClipMetadata) -> ffmpeg::Dictionary<'static> {
+    let mut tags = ffmpeg::Dictionary::new();
+    for (key, value) in metadata.tags() {
+        tags.set(key, &value);
+    }
+    tags
+}
+
+/// `movflags` header option for `container`.
+///
+/// Fragmented MP4 starts a self-contained fragment at every keyframe (with an empty `moov`
+/// up front), so it never needs the trailer and ignores `faststart`.
+/// MP4 and MOV only store tags outside their fixed set with `+use_metadata_tags`, which
+/// `custom_tags` adds.
+fn container_movflags(
+    container: SaveContainer,
+    faststart: bool,
+    custom_tags: bool,
+) -> Option<String> {
+    let mut flags = match container {
+        SaveContainer::Mkv => return None,
+        SaveContainer::Mp4 | SaveContainer::Mov if faststart => String::from("+faststart"),
+        SaveContainer::Mp4 | SaveContainer::Mov => String::new(),
+        SaveContainer::FragmentedMp4 => String::from("+frag_keyframe+empty_moov+default_base_moof"),
+    };
+    if custom_tags {
+        flags.push_str("+use_metadata_tags");
+    }
+    (!flags.is_empty()).then_some(flags)
+}
+
+/// A chapter on the clip timeline, in QPC ticks from the clip start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChapterSpan {
+    start_qpc: i64,
+    end_qpc: i64,
+    title: String,
+}
+
+/// Chapters for the bookmarks at or after `base_qpc`, in order.
+///
+/// Each chapter runs to the next bookmark; the last ends where it starts until
+/// [`FfmpegMuxer::finish`] closes it at the end of the clip. Unlabelled bookmarks are titled
+/// by their position in the clip.
+fn chapter_spans(bookmarks: &[Bookmark], base_qpc: i64) -> Vec<ChapterSpan> {
+    let mut starts: Vec<&Bookmark> = bookmarks
+        .iter()
+        .filter(|bookmark| bookmark.pts >= base_qpc)
+        .collect();
+    starts.sort_by_key(|bookmark| bookmark.pts);
+    starts.dedup_by_key(|bookmark| bookmark.pts);
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, bookmark)| {
+            let start_qpc = bookmark.pts - base_qpc;
+            let end_qpc = starts
+                .get(index + 1)
+                .map_or(start_qpc, |next| next.pts - base_qpc);
+            ChapterSpan {
+                start_qpc,
+                end_qpc,
+                title: bookmark
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| format!("Bookmark {}", index + 1)),
+            }
+        })
+        .collect()
+}
+
 fn default_video_frame_qpc(video_frame_rate: i32) -> i64 {
     let qpc_freq = crate::buffer::ring::qpc_frequency();
     (qpc_freq / video_frame_rate.max(1) as i64).max(1)
@@ -589,12 +1026,15 @@ struct AudioPacketPlacement<'a> {
     start_index: usize,
 }
 
+/// Places PCM packets on the interleaved sample timeline starting at `base_qpc`.
+///
+/// `stream_next_indices` holds where each stream's previous packet ended, so placement
+/// continues across calls for the same clip.
 fn compute_audio_placements<'a>(
     audio_packets: &[&'a EncodedPacket],
     base_qpc: i64,
+    stream_next_indices: &mut HashMap<u8, usize>,
 ) -> Vec<AudioPacketPlacement<'a>> {
-    let mut stream_next_indices: std::collections::HashMap<u8, usize> =
-        std::collections::HashMap::with_capacity(2);
     let mut ordered_audio_packets: Vec<&EncodedPacket> = audio_packets.to_vec();
     ordered_audio_packets.sort_by_key(|packet| (audio_stream_id(packet), packet.pts));
 
@@ -639,12 +1079,8 @@ fn mix_audio_packets_to_pcm(
     base_qpc: i64,
     video_end_qpc: i64,
 ) -> Vec<i16> {
-    let placements = compute_audio_placements(audio_packets, base_qpc);
-
-    let video_duration_qpc = video_end_qpc.saturating_sub(base_qpc);
-    let video_required_samples =
-        qpc_to_sample_index(video_duration_qpc).saturating_mul(AUDIO_CHANNELS as usize);
-    let final_len = video_required_samples.max(1);
+    let placements = compute_audio_placements(audio_packets, base_qpc, &mut HashMap::new());
+    let final_len = clip_audio_len(base_qpc, video_end_qpc);
 
     let mut mixed = vec![0_i32; final_len];
     for p in placements {
@@ -663,21 +1099,91 @@ fn mix_audio_packets_to_pcm(
         }
     }
 
-    mixed
-        .into_iter()
-        .map(|sample| {
-            let limit = 24000.0;
-            let sample_f32 = sample as f32;
-            let clipped = if sample_f32 > limit {
-                limit + (sample_f32 - limit) / (1.0 + (sample_f32 - limit) / (32767.0 - limit))
-            } else if sample_f32 < -limit {
-                -limit + (sample_f32 + limit) / (1.0 - (sample_f32 + limit) / (32768.0 - limit))
-            } else {
-                sample_f32
+    mixed.into_iter().map(soft_clip).collect()
+}
+
+/// Interleaved samples from `base_qpc` to the end of the last video frame.
+fn clip_audio_len(base_qpc: i64, video_end_qpc: i64) -> usize {
+    let video_duration_qpc = video_end_qpc.saturating_sub(base_qpc);
+    qpc_to_sample_index(video_duration_qpc)
+        .saturating_mul(AUDIO_CHANNELS as usize)
+        .max(1)
+}
+
+/// Soft-limits a summed sample above ±24000 instead of hard clipping it.
+fn soft_clip(sample: i32) -> i16 {
+    let limit = 24000.0;
+    let sample_f32 = sample as f32;
+    let clipped = if sample_f32 > limit {
+        limit + (sample_f32 - limit) / (1.0 + (sample_f32 - limit) / (32767.0 - limit))
+    } else if sample_f32 < -limit {
+        -limit + (sample_f32 + limit) / (1.0 - (sample_f32 + limit) / (32768.0 - limit))
+    } else {
+        sample_f32
+    };
+    clipped.clamp(-32768.0, 32767.0).round() as i16
+}
+
+/// Places already-encoded (stream-copied) audio packets on the clip's sample timeline.
+///
+/// Packets within the jitter tolerance of the previous packet's end are butted against it,
+/// packets overlapping audio already placed are dropped, and packets from `video_end_qpc`
+/// on are trimmed. Placement continues across calls for the same clip.
+struct CopiedAudioPlacer {
+    base_qpc: i64,
+    frame_size: i64,
+    next_pts: Option<i64>,
+}
+
+impl CopiedAudioPlacer {
+    fn new(base_qpc: i64, frame_size: i64) -> Self {
+        Self {
+            base_qpc,
+            frame_size,
+            next_pts: None,
+        }
+    }
+
+    fn place(
+        &mut self,
+        audio_packets: &[&EncodedPacket],
+        video_end_qpc: i64,
+        placed: &mut VecDeque<EncodedAudioPacket>,
+    ) {
+        for packet in audio_packets {
+            if packet.pts < self.base_qpc || packet.pts >= video_end_qpc {
+                continue;
+            }
+            let nominal = qpc_to_sample_index(packet.pts - self.base_qpc) as i64;
+            let pts = match self.next_pts {
+                Some(next)
+                    if nominal.abs_diff(next) <= AUDIO_PACKET_JITTER_TOLERANCE_FRAMES as u64 =>
+                {
+                    next
+                }
+                Some(next) if nominal < next => continue,
+                _ => nominal,
             };
-            clipped.clamp(-32768.0, 32767.0).round() as i16
-        })
-        .collect()
+            placed.push_back(EncodedAudioPacket {
+                data: packet.data.clone(),
+                pts,
+                duration: self.frame_size,
+            });
+            self.next_pts = Some(pts + self.frame_size);
+        }
+    }
+}
+
+#[cfg(test)]
+fn place_copied_audio_packets(
+    audio_packets: &[&EncodedPacket],
+    base_qpc: i64,
+    video_end_qpc: i64,
+    frame_size: i64,
+) -> Vec<EncodedAudioPacket> {
+    let mut placed = VecDeque::with_capacity(audio_packets.len());
+    CopiedAudioPlacer::new(base_qpc, frame_size).place(audio_packets, video_end_qpc, &mut placed);
+    placed.into()
 }
 
 fn copy_pcm_into_frame(
@@ -761,200 +1267,207 @@ fn write_f32_plane(dst: &mut [u8], samples: &[f32]) {
     }
 }
 
-fn mix_and_encode_audio_chunks(
-    encoder: &mut ffmpeg::encoder::Audio,
-    audio_packets: &[&EncodedPacket],
+/// Mixes buffered PCM from every audio stream onto the clip timeline and encodes it to the
+/// clip codec.
+///
+/// Packets are pushed chunk by chunk. Mixed samples are encoded once every stream has
+/// delivered them (or one stream runs [`MIX_STREAM_SKEW_SAMPLES`] ahead of a silent one), so
+/// the accumulator only spans the skew between streams plus one chunk, not the whole clip.
+struct PcmAudioMixer {
     base_qpc: i64,
-    video_end_qpc: i64,
-) -> Result<Vec<EncodedAudioPacket>> {
-    let placements = compute_audio_placements(audio_packets, base_qpc);
+    stream_next_indices: HashMap<u8, usize>,
+    /// Summed interleaved samples; `mixed[0]` is sample index `mixed_start`.
+    mixed: VecDeque<i32>,
+    mixed_start: usize,
+    /// Soft-clipped samples waiting for a full encoder frame.
+    pcm_buffer: Vec<i16>,
+    resampler: ffmpeg::software::resampling::Context,
+    samples_per_frame: usize,
+    next_pts: i64,
+    planar_i16_left: Vec<i16>,
+    planar_i16_right: Vec<i16>,
+    planar_f32_left: Vec<f32>,
+    planar_f32_right: Vec<f32>,
+}
 
-    let video_duration_qpc = video_end_qpc.saturating_sub(base_qpc);
-    let video_required_samples =
-        qpc_to_sample_index(video_duration_qpc).saturating_mul(AUDIO_CHANNELS as usize);
-    let final_len = video_required_samples.max(1);
-
-    let mut result = Vec::new();
-
-    let input_format = ffmpeg::format::Sample::I16(ffmpeg::format::sample::Type::Packed);
-    let channel_layout = ffmpeg::channel_layout::ChannelLayout::STEREO;
-    let mut resampler = ffmpeg::software::resampling::Context::get(
-        input_format,
-        channel_layout,
-        AUDIO_SAMPLE_RATE,
-        encoder.format(),
-        encoder.channel_layout(),
-        encoder.rate(),
-    )
-    .context("Failed to create audio resampler")?;
-
-    let frame_size = encoder.frame_size().max(1024) as usize;
-    let samples_per_chunk = frame_size.saturating_mul(AUDIO_CHANNELS as usize);
-    let mut next_pts = 0i64;
-
-    // Process in ~1-second chunks to bound working set memory
-    let chunk_samples = AUDIO_SAMPLE_RATE as usize * AUDIO_CHANNELS as usize; // 1 second
-    let mut chunk_start = 0;
-    let mut search_idx = 0;
-
-    let mut mixed_i32 = vec![0_i32; chunk_samples];
-    let mut mixed_i16 = vec![0_i16; chunk_samples];
-    let mut pcm_buffer = Vec::with_capacity(samples_per_chunk * 2);
-    let planar_capacity = samples_per_chunk / AUDIO_CHANNELS as usize;
-    let mut planar_i16_left = Vec::with_capacity(planar_capacity);
-    let mut planar_i16_right = Vec::with_capacity(planar_capacity);
-    let mut planar_f32_left = Vec::with_capacity(planar_capacity);
-    let mut planar_f32_right = Vec::with_capacity(planar_capacity);
-
-    while chunk_start < final_len {
-        let chunk_end = (chunk_start + chunk_samples).min(final_len);
-        let current_chunk_len = chunk_end - chunk_start;
-
-        mixed_i32[..current_chunk_len].fill(0);
-
-        while search_idx < placements.len() {
-            let p = &placements[search_idx];
-            let p_len = p.packet.data.len() / 2;
-            if p.start_index + p_len <= chunk_start {
-                search_idx += 1;
-            } else {
-                break;
-            }
-        }
+impl PcmAudioMixer {
+    const INPUT_FORMAT: ffmpeg::format::Sample =
+        ffmpeg::format::Sample::I16(ffmpeg::format::sample::Type::Packed);
+    const CHANNEL_LAYOUT: ffmpeg::channel_layout::ChannelLayout =
+        ffmpeg::channel_layout::ChannelLayout::STEREO;
+
+    fn new(encoder: &ffmpeg::encoder::Audio, base_qpc: i64) -> Result<Self> {
+        let resampler = ffmpeg::software::resampling::Context::get(
+            Self::INPUT_FORMAT,
+            Self::CHANNEL_LAYOUT,
+            AUDIO_SAMPLE_RATE,
+            encoder.format(),
+            encoder.channel_layout(),
+            encoder.rate(),
+        )
+        .context("Failed to create audio resampler")?;
 
-        for p in placements.iter().skip(search_idx) {
-            let p_start = p.start_index;
-            if p_start >= chunk_end {
-                break;
-            }
+        let frame_size = encoder.frame_size().max(1024) as usize;
+        let samples_per_frame = frame_size.saturating_mul(AUDIO_CHANNELS as usize);
+        let planar_capacity = samples_per_frame / AUDIO_CHANNELS as usize;
+        Ok(Self {
+            base_qpc,
+            stream_next_indices: HashMap::with_capacity(2),
+            mixed: VecDeque::new(),
+            mixed_start: 0,
+            pcm_buffer: Vec::with_capacity(samples_per_frame * 2),
+            resampler,
+            samples_per_frame,
+            next_pts: 0,
+            planar_i16_left: Vec::with_capacity(planar_capacity),
+            planar_i16_right: Vec::with_capacity(planar_capacity),
+            planar_f32_left: Vec::with_capacity(planar_capacity),
+            planar_f32_right: Vec::with_capacity(planar_capacity),
+        })
+    }
 
-            let p_len = p.packet.data.len() / 2;
-            let p_end = p_start + p_len;
-            if p_end <= chunk_start {
+    /// Adds `audio_packets` to the mix. Samples before the already-encoded point are dropped.
+    fn push(&mut self, audio_packets: &[&EncodedPacket]) {
+        let placements =
+            compute_audio_placements(audio_packets, self.base_qpc, &mut self.stream_next_indices);
+        for p in placements {
+            let data = p.packet.data.as_ref();
+            let p_len = data.len() / 2;
+            let p_end = p.start_index + p_len;
+            if p_end <= self.mixed_start {
                 continue;
             }
-
-            let overlap_start = p_start.max(chunk_start);
-            let overlap_end = p_end.min(chunk_end);
-
-            let packet_offset = overlap_start - p_start;
-            let chunk_offset = overlap_start - chunk_start;
-            let len = overlap_end - overlap_start;
-
-            let data = p.packet.data.as_ref();
-            for j in 0..len {
-                let data_idx = (packet_offset + j) * 2;
+            let needed = p_end - self.mixed_start;
+            if self.mixed.len() < needed {
+                self.mixed.resize(needed, 0);
+            }
+            let first = self.mixed_start.saturating_sub(p.start_index);
+            for j in first..p_len {
+                let data_idx = j * 2;
                 let sample = i16::from_le_bytes([data[data_idx], data[data_idx + 1]]) as i32;
-                mixed_i32[chunk_offset + j] = mixed_i32[chunk_offset + j].saturating_add(sample);
+                let mixed = &mut self.mixed[p.start_index + j - self.mixed_start];
+                *mixed = mixed.saturating_add(sample);
             }
         }
+    }
 
-        for (i, &sample) in mixed_i32[..current_chunk_len].iter().enumerate() {
-            let limit = 24000.0;
-            let sample_f32 = sample as f32;
-            let clipped = if sample_f32 > limit {
-                limit + (sample_f32 - limit) / (1.0 + (sample_f32 - limit) / (32767.0 - limit))
-            } else if sample_f32 < -limit {
-                -limit + (sample_f32 + limit) / (1.0 - (sample_f32 + limit) / (32768.0 - limit))
-            } else {
-                sample_f32
-            };
-            mixed_i16[i] = clipped.clamp(-32768.0, 32767.0).round() as i16;
-        }
+    /// Encodes the samples every stream has delivered.
+    fn encode_ready(
+        &mut self,
+        encoder: &mut ffmpeg::encoder::Audio,
+        result: &mut VecDeque<EncodedAudioPacket>,
+    ) -> Result<()> {
+        let newest = self.stream_next_indices.values().copied().max();
+        let oldest = self.stream_next_indices.values().copied().min();
+        let (Some(newest), Some(oldest)) = (newest, oldest) else {
+            return Ok(());
+        };
+        // A stream that stopped delivering must not hold back the others indefinitely.
+        let ready = oldest.max(newest.saturating_sub(MIX_STREAM_SKEW_SAMPLES));
+        self.encode_until(ready, encoder, result)
+    }
 
-        pcm_buffer.extend_from_slice(&mixed_i16[..current_chunk_len]);
+    /// Encodes the rest of the mix up to the end of the last video frame, padding with
+    /// silence, and flushes the encoder.
+    fn finish(
+        &mut self,
+        encoder: &mut ffmpeg::encoder::Audio,
+        video_end_qpc: i64,
+        result: &mut VecDeque<EncodedAudioPacket>,
+    ) -> Result<()> {
+        let final_len = clip_audio_len(self.base_qpc, video_end_qpc);
+        self.encode_until(final_len, encoder, result)?;
+        self.mixed = VecDeque::new();
+
+        if !self.pcm_buffer.is_empty() {
+            let len = self.pcm_buffer.len();
+            self.encode_frame(0..len, encoder, result)?;
+            self.pcm_buffer = Vec::new();
+        }
 
-        let mut offset = 0usize;
-        while offset + samples_per_chunk <= pcm_buffer.len() {
-            let chunk = &pcm_buffer[offset..offset + samples_per_chunk];
-            offset += samples_per_chunk;
-
-            let samples_in_frame = (chunk.len() / AUDIO_CHANNELS as usize).max(1);
-            let mut input =
-                ffmpeg::frame::Audio::new(input_format, samples_in_frame, channel_layout);
-            input.set_rate(AUDIO_SAMPLE_RATE);
-            input.set_pts(Some(next_pts));
-            copy_pcm_into_frame(
-                &mut input,
-                chunk,
-                &mut planar_i16_left,
-                &mut planar_i16_right,
-                &mut planar_f32_left,
-                &mut planar_f32_right,
-            );
+        encoder.send_eof().ok();
+        drain_encoder_into(encoder, result);
+        Ok(())
+    }
 
-            let mut converted = ffmpeg::frame::Audio::empty();
-            resampler
-                .run(&input, &mut converted)
-                .context("Failed to resample audio frame")?;
-            converted.set_pts(Some(next_pts));
-            next_pts = next_pts.saturating_add(converted.samples() as i64);
+    /// Soft-clips the mix up to sample index `end_index` and encodes every full frame.
+    fn encode_until(
+        &mut self,
+        end_index: usize,
+        encoder: &mut ffmpeg::encoder::Audio,
+        result: &mut VecDeque<EncodedAudioPacket>,
+    ) -> Result<()> {
+        if end_index <= self.mixed_start {
+            return Ok(());
+        }
+        let count = end_index - self.mixed_start;
+        if self.mixed.len() < count {
+            self.mixed.resize(count, 0);
+        }
+        self.pcm_buffer
+            .extend(self.mixed.drain(..count).map(soft_clip));
+        self.mixed_start = end_index;
 
-            encoder
-                .send_frame(&converted)
-                .context("Failed to send audio frame to encoder")?;
-            drain_encoder_to_vec(encoder, &mut result);
+        let mut offset = 0usize;
+        while offset + self.samples_per_frame <= self.pcm_buffer.len() {
+            self.encode_frame(offset..offset + self.samples_per_frame, encoder, result)?;
+            offset += self.samples_per_frame;
         }
 
         if offset > 0 {
-            pcm_buffer.drain(..offset);
+            self.pcm_buffer.drain(..offset);
             // Aggressively release capacity when it significantly exceeds current length.
             // This prevents the allocator from holding onto large blocks across chunks.
-            if pcm_buffer.capacity() > pcm_buffer.len() * 4 && pcm_buffer.capacity() > 16384 {
-                pcm_buffer.shrink_to_fit();
+            if self.pcm_buffer.capacity() > self.pcm_buffer.len() * 4
+                && self.pcm_buffer.capacity() > 16384
+            {
+                self.pcm_buffer.shrink_to_fit();
             }
         }
-
-        chunk_start += current_chunk_len;
+        Ok(())
     }
 
-    if !pcm_buffer.is_empty() {
-        let chunk = &pcm_buffer;
+    fn encode_frame(
+        &mut self,
+        range: std::ops::Range<usize>,
+        encoder: &mut ffmpeg::encoder::Audio,
+        result: &mut VecDeque<EncodedAudioPacket>,
+    ) -> Result<()> {
+        let chunk = &self.pcm_buffer[range];
         let samples_in_frame = (chunk.len() / AUDIO_CHANNELS as usize).max(1);
-        let mut input = ffmpeg::frame::Audio::new(input_format, samples_in_frame, channel_layout);
+        let mut input =
+            ffmpeg::frame::Audio::new(Self::INPUT_FORMAT, samples_in_frame, Self::CHANNEL_LAYOUT);
         input.set_rate(AUDIO_SAMPLE_RATE);
-        input.set_pts(Some(next_pts));
+        input.set_pts(Some(self.next_pts));
         copy_pcm_into_frame(
             &mut input,
             chunk,
-            &mut planar_i16_left,
-            &mut planar_i16_right,
-            &mut planar_f32_left,
-            &mut planar_f32_right,
+            &mut self.planar_i16_left,
+            &mut self.planar_i16_right,
+            &mut self.planar_f32_left,
+            &mut self.planar_f32_right,
         );
 
         let mut converted = ffmpeg::frame::Audio::empty();
-        resampler
+        self.resampler
             .run(&input, &mut converted)
             .context("Failed to resample audio frame")?;
-        converted.set_pts(Some(next_pts));
+        converted.set_pts(Some(self.next_pts));
+        self.next_pts = self.next_pts.saturating_add(converted.samples() as i64);
 
         encoder
             .send_frame(&converted)
             .context("Failed to send audio frame to encoder")?;
-        drain_encoder_to_vec(encoder, &mut result);
+        drain_encoder_into(encoder, result);
+        Ok(())
     }
-
-    encoder.send_eof().ok();
-    drain_encoder_to_vec(encoder, &mut result);
-
-    // Release working set buffers — they are no longer needed.
-    drop(pcm_buffer);
-    drop(mixed_i16);
-    drop(mixed_i32);
-    drop(placements);
-
-    // Shrink result to exact size — no extra capacity after encoding completes.
-    result.shrink_to_fit();
-
-    Ok(result)
 }
 
-/// Drains encoded AAC packets from `encoder` into `result`.
+/// Drains encoded audio packets from `encoder` into `result`.
 /// Negative-PTS priming frames (AAC encoder delay artifact) are skipped.
-fn drain_encoder_to_vec(
+fn drain_encoder_into(
     encoder: &mut ffmpeg::encoder::Audio,
-    result: &mut Vec<EncodedAudioPacket>,
+    result: &mut VecDeque<EncodedAudioPacket>,
 ) {
     let mut packet = ffmpeg::Packet::empty();
     while encoder.receive_packet(&mut packet).is_ok() {
@@ -962,7 +1475,7 @@ fn drain_encoder_to_vec(
             if pts >= 0 {
                 let data = bytes::Bytes::copy_from_slice(packet.data().unwrap_or(&[]));
                 let duration = packet.duration().max(encoder.frame_size() as i64).max(1);
-                result.push(EncodedAudioPacket {
+                result.push_back(EncodedAudioPacket {
                     data,
                     pts,
                     duration,
@@ -973,7 +1486,7 @@ fn drain_encoder_to_vec(
     }
 }
 
-/// Writes a single raw AAC packet to the muxer via av_write_frame.
+/// Writes a single raw encoded audio packet to the muxer via av_write_frame.
 /// `pts` is in audio sample units (audio time base denominator = AUDIO_SAMPLE_RATE).
 fn write_audio_frame_direct(
     format_context: &mut ffmpeg::format::context::Output,
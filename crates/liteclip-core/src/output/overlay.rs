@@ -0,0 +1,489 @@
+//! Text and image overlays burned into an export.
+//!
+//! An [`ExportOverlay`] is a watermark image (typically a PNG logo) or a line of text,
+//! anchored to a corner or the centre of the frame and optionally limited to part of the
+//! export. Sizes and margins are given for a 1080-line frame and scale with the output
+//! height, so one overlay looks the same at every export resolution.
+//!
+//! This module describes overlays and builds the filter arguments for them; the export
+//! renders them with `movie`/`overlay` and `drawtext` in its filter graph. Any overlay
+//! forces a re-encode.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Font file in the Windows font directory used for text overlays without a font file.
+const DEFAULT_OVERLAY_FONT_FILE: &str = "arial.ttf";
+/// Frame height that overlay sizes and margins are given for.
+const OVERLAY_REFERENCE_HEIGHT: f64 = 1080.0;
+
+/// Corner (or centre) of the frame an overlay is placed against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+impl OverlayAnchor {
+    pub const ALL: [Self; 5] = [
+        Self::TopLeft,
+        Self::TopRight,
+        Self::BottomLeft,
+        Self::BottomRight,
+        Self::Center,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::TopLeft => "Top left",
+            Self::TopRight => "Top right",
+            Self::BottomLeft => "Bottom left",
+            Self::BottomRight => "Bottom right",
+            Self::Center => "Center",
+        }
+    }
+
+    /// `x` and `y` filter expressions placing an item of `item_w` x `item_h` in a frame of
+    /// `frame_w` x `frame_h`, `margin` pixels from the anchored edges. The arguments are the
+    /// variable names the filter uses for those sizes.
+    fn position_exprs(
+        self,
+        frame_w: &str,
+        frame_h: &str,
+        item_w: &str,
+        item_h: &str,
+        margin: u32,
+    ) -> (String, String) {
+        let left = margin.to_string();
+        let top = margin.to_string();
+        let right = format!("{frame_w}-{item_w}-{margin}");
+        let bottom = format!("{frame_h}-{item_h}-{margin}");
+        match self {
+            Self::TopLeft => (left, top),
+            Self::TopRight => (right, top),
+            Self::BottomLeft => (left, bottom),
+            Self::BottomRight => (right, bottom),
+            Self::Center => (
+                format!("({frame_w}-{item_w})/2"),
+                format!("({frame_h}-{item_h})/2"),
+            ),
+        }
+    }
+}
+
+/// What an overlay shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OverlayContent {
+    /// An image file, drawn with its alpha channel.
+    Image {
+        path: PathBuf,
+        /// Width on a 1080-line frame; the height follows the image's aspect ratio. `None`
+        /// draws the image at its own size.
+        #[serde(default)]
+        width: Option<u32>,
+    },
+    /// A single line of text with a thin dark outline.
+    Text {
+        text: String,
+        /// TrueType/OpenType font file; [`default_overlay_font`] when unset.
+        #[serde(default)]
+        font_path: Option<PathBuf>,
+        /// Font size on a 1080-line frame.
+        #[serde(default = "default_overlay_font_size")]
+        font_size: u32,
+        /// RGB text colour.
+        #[serde(default = "default_overlay_color")]
+        color: [u8; 3],
+    },
+}
+
+fn default_overlay_font_size() -> u32 {
+    48
+}
+
+fn default_overlay_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+fn default_overlay_margin() -> u32 {
+    24
+}
+
+fn default_overlay_opacity() -> f32 {
+    1.0
+}
+
+/// An image or text drawn over the exported video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportOverlay {
+    pub content: OverlayContent,
+    #[serde(default)]
+    pub anchor: OverlayAnchor,
+    /// Distance from the anchored edges on a 1080-line frame.
+    #[serde(default = "default_overlay_margin")]
+    pub margin: u32,
+    /// From 0.0 (invisible) to 1.0 (opaque).
+    #[serde(default = "default_overlay_opacity")]
+    pub opacity: f32,
+    /// Export time the overlay appears at, in seconds; `None` shows it from the start.
+    #[serde(default)]
+    pub start_secs: Option<f64>,
+    /// Export time the overlay disappears at, in seconds; `None` shows it to the end.
+    #[serde(default)]
+    pub end_secs: Option<f64>,
+}
+
+impl ExportOverlay {
+    /// An image shown for the whole export in the bottom-right corner.
+    pub fn image(path: impl Into<PathBuf>) -> Self {
+        Self::new(OverlayContent::Image {
+            path: path.into(),
+            width: None,
+        })
+    }
+
+    /// White text shown for the whole export in the bottom-right corner.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(OverlayContent::Text {
+            text: text.into(),
+            font_path: None,
+            font_size: default_overlay_font_size(),
+            color: default_overlay_color(),
+        })
+    }
+
+    fn new(content: OverlayContent) -> Self {
+        Self {
+            content,
+            anchor: OverlayAnchor::default(),
+            margin: default_overlay_margin(),
+            opacity: default_overlay_opacity(),
+            start_secs: None,
+            end_secs: None,
+        }
+    }
+
+    /// Checks that the overlay can be rendered: its files exist, the text is not empty and
+    /// the time range is not reversed.
+    pub fn validate(&self) -> Result<()> {
+        match &self.content {
+            OverlayContent::Image { path, .. } => {
+                if !path.is_file() {
+                    bail!("Overlay image {:?} does not exist", path);
+                }
+            }
+            OverlayContent::Text {
+                text, font_path, ..
+            } => {
+                if text.trim().is_empty() {
+                    bail!("Text overlays need some text");
+                }
+                match font_path {
+                    Some(font_path) if !font_path.is_file() => {
+                        bail!("Overlay font {:?} does not exist", font_path);
+                    }
+                    Some(_) => {}
+                    None => {
+                        default_overlay_font()?;
+                    }
+                }
+            }
+        }
+        if let (Some(start), Some(end)) = (self.start_secs, self.end_secs) {
+            if end <= start {
+                bail!("Overlay ends at {end:.2}s, before it starts at {start:.2}s");
+            }
+        }
+        Ok(())
+    }
+
+    /// Timeline `enable` expression limiting the overlay to its time range, or `None` when
+    /// it is shown throughout.
+    fn enable_expr(&self) -> Option<String> {
+        match (self.start_secs, self.end_secs) {
+            (Some(start), Some(end)) => Some(format!("between(t,{start:.3},{end:.3})")),
+            (Some(start), None) => Some(format!("gte(t,{start:.3})")),
+            (None, Some(end)) => Some(format!("lt(t,{end:.3})")),
+            (None, None) => None,
+        }
+    }
+
+    fn opacity(&self) -> f32 {
+        if self.opacity.is_finite() {
+            self.opacity.clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// `drawtext` arguments for a text overlay on a frame `output_height` pixels high, or
+    /// `None` for image overlays.
+    fn drawtext_args(&self, output_height: u32) -> Option<String> {
+        let OverlayContent::Text {
+            text,
+            font_path,
+            font_size,
+            color,
+        } = &self.content
+        else {
+            return None;
+        };
+        let font = font_path
+            .clone()
+            .unwrap_or_else(default_overlay_font_path)
+            .to_string_lossy()
+            .into_owned();
+        let (x, y) = self.anchor.position_exprs(
+            "w",
+            "h",
+            "text_w",
+            "text_h",
+            scaled_to_output(self.margin, output_height),
+        );
+        let opacity = self.opacity();
+        let mut args = format!(
+            "fontfile={}:text={}:expansion=none:fontsize={}:fontcolor=0x{:02x}{:02x}{:02x}@{:.2}:borderw=2:bordercolor=black@{:.2}:x={}:y={}",
+            quote_filter_value(&font),
+            quote_filter_value(text),
+            scaled_to_output(*font_size, output_height).max(1),
+            color[0],
+            color[1],
+            color[2],
+            opacity,
+            opacity * 0.6,
+            quote_filter_value(&x),
+            quote_filter_value(&y),
+        );
+        if let Some(enable) = self.enable_expr() {
+            args.push_str(&format!(":enable={}", quote_filter_value(&enable)));
+        }
+        Some(args)
+    }
+
+    /// Filter chain preparing an image overlay's picture (`movie` source, size and
+    /// opacity), as `(filter, args)` pairs, or `None` for text overlays.
+    fn image_source_filters(&self, output_height: u32) -> Option<Vec<(&'static str, String)>> {
+        let OverlayContent::Image { path, width } = &self.content else {
+            return None;
+        };
+        let mut filters = vec![(
+            "movie",
+            format!("filename={}", quote_filter_value(&path.to_string_lossy())),
+        )];
+        if let Some(width) = width {
+            let width = (scaled_to_output(*width, output_height) & !1).max(2);
+            filters.push(("scale", format!("w={width}:h=-2")));
+        }
+        filters.push(("format", "pix_fmts=rgba".to_string()));
+        let opacity = self.opacity();
+        if opacity < 1.0 {
+            filters.push(("colorchannelmixer", format!("aa={opacity:.3}")));
+        }
+        Some(filters)
+    }
+
+    /// `overlay` arguments placing an image overlay on a frame `output_height` pixels high.
+    fn overlay_args(&self, output_height: u32) -> String {
+        let (x, y) = self.anchor.position_exprs(
+            "W",
+            "H",
+            "w",
+            "h",
+            scaled_to_output(self.margin, output_height),
+        );
+        let mut args = format!(
+            "x={}:y={}:eof_action=repeat",
+            quote_filter_value(&x),
+            quote_filter_value(&y)
+        );
+        if let Some(enable) = self.enable_expr() {
+            args.push_str(&format!(":enable={}", quote_filter_value(&enable)));
+        }
+        args
+    }
+}
+
+/// Font used for text overlays without a font file: Arial from `%WINDIR%\Fonts`.
+///
+/// # Errors
+///
+/// Returns an error if the font is not installed.
+pub fn default_overlay_font() -> Result<PathBuf> {
+    let path = default_overlay_font_path();
+    if !path.is_file() {
+        bail!(
+            "Default overlay font {:?} does not exist; choose a font file for the text overlay",
+            path
+        );
+    }
+    Ok(path)
+}
+
+/// Where [`default_overlay_font`] is looked for, whether or not it exists.
+fn default_overlay_font_path() -> PathBuf {
+    let windows_dir = std::env::var_os("WINDIR")
+        .or_else(|| std::env::var_os("SystemRoot"))
+        .map_or_else(|| PathBuf::from(r"C:\Windows"), PathBuf::from);
+    windows_dir.join("Fonts").join(DEFAULT_OVERLAY_FONT_FILE)
+}
+
+/// Filter graph description drawing `overlays`, in order, on frames `output_height` pixels
+/// high.
+///
+/// Reads `in` and writes `out`, converting back to `pixel_format` (the overlay filter
+/// works in planar YUV or RGB).
+pub(crate) fn overlay_filter_spec(
+    overlays: &[ExportOverlay],
+    output_height: u32,
+    pixel_format: &str,
+) -> String {
+    let mut chains = Vec::new();
+    let mut current = "in".to_string();
+    for (index, overlay) in overlays.iter().enumerate() {
+        let next = format!("v{index}");
+        if let Some(args) = overlay.drawtext_args(output_height) {
+            chains.push(format!(
+                "[{current}]drawtext={}[{next}]",
+                escape_graph_args(&args)
+            ));
+        } else if let Some(filters) = overlay.image_source_filters(output_height) {
+            let source = filters
+                .iter()
+                .map(|(name, args)| format!("{name}={}", escape_graph_args(args)))
+                .collect::<Vec<_>>()
+                .join(",");
+            chains.push(format!("{source}[img{index}]"));
+            chains.push(format!(
+                "[{current}][img{index}]overlay={}[{next}]",
+                escape_graph_args(&overlay.overlay_args(output_height))
+            ));
+        }
+        current = next;
+    }
+    chains.push(format!("[{current}]format=pix_fmts={pixel_format}[out]"));
+    chains.join(";")
+}
+
+/// Scales a size given for a 1080-line frame to a frame `output_height` pixels high.
+fn scaled_to_output(size: u32, output_height: u32) -> u32 {
+    (f64::from(size) * f64::from(output_height) / OVERLAY_REFERENCE_HEIGHT).round() as u32
+}
+
+/// Quotes a filter option value so `:`, `,`, `\` and spaces in it are taken literally.
+fn quote_filter_value(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Escapes filter arguments for a filter graph description, which strips one level of
+/// quoting and escaping before the filter parses its options.
+fn escape_graph_args(args: &str) -> String {
+    let mut escaped = String::with_capacity(args.len());
+    for c in args.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_overlay_scales_and_quotes_its_arguments() {
+        let mut overlay = ExportOverlay::text("GG: it's over");
+        overlay.anchor = OverlayAnchor::TopLeft;
+        overlay.start_secs = Some(1.0);
+        overlay.end_secs = Some(4.5);
+
+        let args = overlay.drawtext_args(720).unwrap();
+        assert!(args.contains(r"text='GG: it'\''s over'"));
+        assert!(args.contains("fontsize=32:"));
+        assert!(args.contains("x='16':y='16'"));
+        assert!(args.ends_with(":enable='between(t,1.000,4.500)'"));
+        assert!(overlay.overlay_args(720).starts_with("x='16'"));
+        assert!(overlay.image_source_filters(720).is_none());
+    }
+
+    #[test]
+    fn image_overlay_builds_a_sized_translucent_source() {
+        let overlay = ExportOverlay {
+            content: OverlayContent::Image {
+                path: PathBuf::from("C:/Brand/logo.png"),
+                width: Some(300),
+            },
+            opacity: 0.5,
+            ..ExportOverlay::image("unused.png")
+        };
+
+        let filters = overlay.image_source_filters(1080).unwrap();
+        assert_eq!(
+            filters[0],
+            ("movie", "filename='C:/Brand/logo.png'".to_string())
+        );
+        assert_eq!(filters[1], ("scale", "w=300:h=-2".to_string()));
+        assert_eq!(filters[3], ("colorchannelmixer", "aa=0.500".to_string()));
+        assert_eq!(
+            overlay.overlay_args(1080),
+            "x='W-w-24':y='H-h-24':eof_action=repeat"
+        );
+        assert!(overlay.drawtext_args(1080).is_none());
+    }
+
+    #[test]
+    fn filter_spec_chains_overlays_in_order() {
+        let overlays = [ExportOverlay::image("logo.png"), ExportOverlay::text("a,b")];
+        let spec = overlay_filter_spec(&overlays, 1080, "nv12");
+        let chains: Vec<&str> = spec.split(';').collect();
+        assert_eq!(chains.len(), 4);
+        assert!(chains[0].starts_with(r"movie=filename=\'logo.png\',format="));
+        assert!(chains[0].ends_with("[img0]"));
+        assert!(chains[1].starts_with("[in][img0]overlay=") && chains[1].ends_with("[v0]"));
+        assert!(chains[2].starts_with("[v0]drawtext=") && chains[2].contains(r"text=\'a\,b\'"));
+        assert_eq!(chains[3], "[v1]format=pix_fmts=nv12[out]");
+    }
+
+    #[test]
+    fn validate_rejects_unusable_overlays() {
+        assert!(ExportOverlay::text("  ").validate().is_err());
+        assert!(ExportOverlay::image("missing/logo.png").validate().is_err());
+
+        let font = tempfile::NamedTempFile::new().unwrap();
+        let mut overlay = ExportOverlay::text("LiteClip");
+        if let OverlayContent::Text { font_path, .. } = &mut overlay.content {
+            *font_path = Some(PathBuf::from("missing/font.ttf"));
+        }
+        assert!(overlay.validate().is_err());
+        if let OverlayContent::Text { font_path, .. } = &mut overlay.content {
+            *font_path = Some(font.path().to_path_buf());
+        }
+        assert!(overlay.validate().is_ok());
+        overlay.start_secs = Some(5.0);
+        overlay.end_secs = Some(2.0);
+        assert!(overlay.validate().is_err());
+    }
+
+    #[test]
+    fn default_font_is_looked_up_in_the_windows_font_directory() {
+        let path = default_overlay_font_path();
+        assert!(path.ends_with("Fonts/arial.ttf"));
+        assert_eq!(
+            ExportOverlay::text("LiteClip").validate().is_ok(),
+            path.is_file()
+        );
+        let args = ExportOverlay::text("LiteClip").drawtext_args(1080).unwrap();
+        assert!(args.starts_with(&format!(
+            "fontfile={}:",
+            quote_filter_value(&path.to_string_lossy())
+        )));
+    }
+}
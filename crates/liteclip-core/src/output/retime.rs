@@ -0,0 +1,225 @@
+//! Filter graphs retiming kept ranges that play at another speed.
+//!
+//! A [`TimeRange`](super::video_file::TimeRange) with a speed other than 1.0 gets its own
+//! pair of graphs in the re-encoding export. [`VideoRetimeGraph`] stretches the frame
+//! timestamps with `setpts` and puts the frames back on the output frame grid with `fps`,
+//! or with `framerate` (which blends neighbouring frames) for slow motion when the encoder
+//! takes YUV420P. [`AudioRetimeGraph`] changes the tempo with `atempo` without changing
+//! the pitch, and mutes ranges whose speed is too far from normal to sound right.
+
+use anyhow::{Context, Result};
+use ffmpeg_next as ffmpeg;
+
+/// Slowest speed whose audio is kept; slower ranges are muted.
+const MIN_AUDIBLE_SPEED: f64 = 0.5;
+/// Fastest speed whose audio is kept; faster ranges are muted.
+const MAX_AUDIBLE_SPEED: f64 = 2.0;
+/// Time base of the frames pushed into [`VideoRetimeGraph`] (microseconds).
+pub(super) const VIDEO_RETIME_TIME_BASE: i32 = 1_000_000;
+
+/// Whether the audio of a range played at `speed` is kept.
+pub(super) fn is_audible_speed(speed: f64) -> bool {
+    (MIN_AUDIBLE_SPEED..=MAX_AUDIBLE_SPEED).contains(&speed)
+}
+
+/// Filter chain retiming video to `speed` at `fps` output frames per second.
+fn video_retime_filter(speed: f64, fps: f64, pixel_format: ffmpeg::format::Pixel) -> String {
+    let resample = if speed < 1.0 && pixel_format == ffmpeg::format::Pixel::YUV420P {
+        "framerate"
+    } else {
+        "fps"
+    };
+    format!("setpts=(PTS-STARTPTS)/{speed:.4},{resample}=fps={fps:.3}")
+}
+
+/// Chain of `atempo` filters for `speed`. Each `atempo` is kept within 0.5..=2.0, where it
+/// sounds best, so speeds beyond that are reached by chaining several.
+fn atempo_chain(speed: f64) -> String {
+    let mut remaining = speed;
+    let mut stages = Vec::new();
+    while remaining > 2.0 + 1e-9 {
+        stages.push("atempo=2.0".to_string());
+        remaining /= 2.0;
+    }
+    while remaining < 0.5 - 1e-9 {
+        stages.push("atempo=0.5".to_string());
+        remaining /= 0.5;
+    }
+    stages.push(format!("atempo={remaining:.4}"));
+    stages.join(",")
+}
+
+/// Filter chain retiming audio to `speed`; silent outside the audible speeds.
+fn audio_retime_filter(speed: f64) -> String {
+    let chain = atempo_chain(speed);
+    if is_audible_speed(speed) {
+        chain
+    } else {
+        format!("{chain},volume=0")
+    }
+}
+
+/// Retimes the scaled frames of one kept range (`buffer → setpts → fps/framerate →
+/// buffersink`).
+///
+/// Frames go in with timestamps in [`VIDEO_RETIME_TIME_BASE`] relative to the range start
+/// and come out one per output frame; their timestamps are left to the caller.
+pub(super) struct VideoRetimeGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+}
+
+impl VideoRetimeGraph {
+    pub(super) fn new(
+        speed: f64,
+        fps: f64,
+        width: u32,
+        height: u32,
+        pixel_format: ffmpeg::format::Pixel,
+    ) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let buffer = ffmpeg::filter::find("buffer").context("buffer filter not found")?;
+        let buffersink =
+            ffmpeg::filter::find("buffersink").context("buffersink filter not found")?;
+        let args = format!(
+            "video_size={width}x{height}:pix_fmt={}:time_base=1/{VIDEO_RETIME_TIME_BASE}:pixel_aspect=1/1",
+            ffmpeg::ffi::AVPixelFormat::from(pixel_format) as i32,
+        );
+        let source = graph
+            .add(&buffer, "in", &args)
+            .context("Failed to add buffer filter to graph")?;
+        let sink = graph
+            .add(&buffersink, "out", "")
+            .context("Failed to add buffersink filter to graph")?;
+        let spec = video_retime_filter(speed, fps, pixel_format);
+        graph
+            .output("in", 0)?
+            .input("out", 0)?
+            .parse(&spec)
+            .with_context(|| format!("Failed to parse video retime filter: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate video retime filter graph")?;
+
+        Ok(Self {
+            graph,
+            source,
+            sink,
+        })
+    }
+
+    /// Pushes a frame, or flushes the graph at the end of the range for `None`.
+    pub(super) fn push(&mut self, frame: Option<&ffmpeg::frame::Video>) -> Result<()> {
+        match frame {
+            Some(frame) => self
+                .source
+                .source()
+                .add(frame)
+                .context("Failed to push frame into retime graph"),
+            None => self
+                .source
+                .source()
+                .flush()
+                .context("Failed to flush retime graph"),
+        }
+    }
+
+    /// Takes the next retimed frame, if one is ready.
+    pub(super) fn pull(&mut self, frame: &mut ffmpeg::frame::Video) -> bool {
+        self.sink.sink().frame(frame).is_ok()
+    }
+}
+
+/// Retimes the resampled audio of one track over one kept range (`abuffer → atempo… →
+/// abuffersink`), in the encoder's sample format.
+pub(super) struct AudioRetimeGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+}
+
+impl AudioRetimeGraph {
+    /// `source_args` are the `abuffer` arguments of the resampled frames.
+    pub(super) fn new(speed: f64, source_args: &str) -> Result<Self> {
+        let mut graph = ffmpeg::filter::Graph::new();
+        let abuffer = ffmpeg::filter::find("abuffer").context("abuffer filter not found")?;
+        let abuffersink =
+            ffmpeg::filter::find("abuffersink").context("abuffersink filter not found")?;
+        let source = graph
+            .add(&abuffer, "in", source_args)
+            .context("Failed to add abuffer filter to graph")?;
+        let sink = graph
+            .add(&abuffersink, "out", "")
+            .context("Failed to add abuffersink filter to graph")?;
+        let spec = audio_retime_filter(speed);
+        graph
+            .output("in", 0)?
+            .input("out", 0)?
+            .parse(&spec)
+            .with_context(|| format!("Failed to parse audio retime filter: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate audio retime filter graph")?;
+
+        Ok(Self {
+            graph,
+            source,
+            sink,
+        })
+    }
+
+    /// Pushes a frame, or flushes the graph at the end of the range for `None`.
+    pub(super) fn push(&mut self, frame: Option<&ffmpeg::frame::Audio>) -> Result<()> {
+        match frame {
+            Some(frame) => self
+                .source
+                .source()
+                .add(frame)
+                .context("Failed to push audio into retime graph"),
+            None => self
+                .source
+                .source()
+                .flush()
+                .context("Failed to flush audio retime graph"),
+        }
+    }
+
+    /// Takes the next retimed frame, if one is ready.
+    pub(super) fn pull(&mut self, frame: &mut ffmpeg::frame::Audio) -> bool {
+        self.sink.sink().frame(frame).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atempo_chain_stays_within_single_filter_limits() {
+        assert_eq!(atempo_chain(1.5), "atempo=1.5000");
+        assert_eq!(atempo_chain(4.0), "atempo=2.0,atempo=2.0000");
+        assert_eq!(atempo_chain(3.0), "atempo=2.0,atempo=1.5000");
+        assert_eq!(atempo_chain(0.25), "atempo=0.5,atempo=0.5000");
+    }
+
+    #[test]
+    fn extreme_speeds_are_muted() {
+        assert!(!audio_retime_filter(1.5).contains("volume"));
+        assert!(audio_retime_filter(0.25).ends_with("volume=0"));
+        assert!(audio_retime_filter(4.0).ends_with("volume=0"));
+        assert!(is_audible_speed(0.5) && is_audible_speed(2.0));
+    }
+
+    #[test]
+    fn slow_motion_interpolates_yuv420p_only() {
+        assert_eq!(
+            video_retime_filter(0.5, 60.0, ffmpeg::format::Pixel::YUV420P),
+            "setpts=(PTS-STARTPTS)/0.5000,framerate=fps=60.000"
+        );
+        assert!(video_retime_filter(0.5, 60.0, ffmpeg::format::Pixel::NV12).contains(",fps="));
+        assert!(video_retime_filter(2.0, 30.0, ffmpeg::format::Pixel::YUV420P).contains(",fps="));
+    }
+}
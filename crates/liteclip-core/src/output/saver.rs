@@ -1,18 +1,23 @@
 use crate::buffer::ring::{SharedReplayBuffer, TrackedSnapshot};
-use crate::encode::EncodedPacket;
+use crate::buffer::BufferError;
+use crate::encode::{EncodedPacket, StreamType};
 use anyhow::{bail, Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::Duration;
 use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
 
+use super::types::is_parameter_set_packet;
 use super::{
-    calculate_clip_start_pts, generate_thumbnail, h264_nal_type, hevc_nal_type, Muxer, MuxerConfig,
+    generate_thumbnail, h264_nal_type, hevc_nal_type, ClipMetadata, ClipStreamMuxer, ClipWindow,
+    Muxer, MuxerConfig,
 };
 
 const CLIP_VIDEO_CATCH_UP_RETRY_LIMIT: usize = 8;
 const CLIP_VIDEO_CATCH_UP_SLEEP: Duration = Duration::from_millis(125);
+/// Packet bytes a streaming save reads from the ring at a time.
+const CLIP_SAVE_CHUNK_BYTES: usize = 8 * 1024 * 1024;
 
 /// Aggressively drops all packet data and forces memory release.
 /// Uses std::mem::replace to ensure the old allocation is fully freed before
@@ -23,25 +28,30 @@ fn aggressively_drop_packets(packets: TrackedSnapshot) {
     drop(packets);
 }
 
-/// Spawns a background task to extract packets from the replay buffer and save them to an MP4 file.
+/// Spawns a background task to extract packets from the replay buffer and save them to a clip file
+/// in the configured container (`config.container`).
 ///
 /// This function coordinates the following:
-/// 1. Snapshotting: Atomsically clones the relevant range of packets from the lock-free `SharedReplayBuffer`.
+/// 1. Reading: Streams the window out of the `SharedReplayBuffer` in bounded chunks, so the save
+///    never holds a copy of the whole clip. Windows reaching past RAM are snapshotted instead.
 /// 2. Keyframe Seeking: Ensures the clip starts on a decodable keyframe (IDR frame) to avoid green/corrupt frames.
-/// 3. Muxing: Uses `FfmpegMuxer` to interleave video and audio streams into a valid MP4 container.
+/// 3. Muxing: Feeds each chunk to `FfmpegMuxer` to interleave video and audio streams into the
+///    configured container.
+///    Bookmarks taken inside the window replace `config.bookmarks` and are written as chapters.
 /// 4. Thumbnail Generation: Spawns a side task to create a JPG preview for the gallery.
 ///
 /// # Arguments
 ///
 /// * `buffer` - The ring buffer containing encoded packets.
-/// * `duration` - Requested duration of the clip in seconds.
-/// * `output_path` - Target file path for the MP4.
+/// * `window` - Portion of the buffer to save; a plain [`Duration`] saves the last `N` seconds.
+/// * `output_path` - Target file path for the clip.
 /// * `config` - Muxing parameters (bitrate, flags).
 /// * `save_directory` - Root directory for clips (used for thumbnail placement).
 ///
 /// # Returns
 ///
-/// A `JoinHandle` representing the background operation. It resolves to the `PathBuf` of the saved file.
+/// A `JoinHandle` representing the background operation. It resolves to the `PathBuf` of the saved
+/// file and `config.metadata` with the framerate and dropped frames measured over the saved video.
 /// If set to `1` or `true`, skips gallery thumbnail generation after save (A/B memory diagnosis).
 pub const SKIP_THUMBNAIL_ENV: &str = "LITECLIP_SKIP_THUMBNAIL";
 
@@ -57,178 +67,50 @@ fn thumbnail_enabled_after_save(requested: bool) -> bool {
 
 pub fn spawn_clip_saver(
     buffer: SharedReplayBuffer,
-    duration: Duration,
+    window: impl Into<ClipWindow>,
     output_path: PathBuf,
     config: MuxerConfig,
     save_directory: PathBuf,
     generate_thumbnail_after_save: bool,
-) -> JoinHandle<Result<PathBuf>> {
+) -> JoinHandle<Result<(PathBuf, Option<ClipMetadata>)>> {
     let generate_thumbnail_after_save = thumbnail_enabled_after_save(generate_thumbnail_after_save);
+    let window = window.into();
     tokio::task::spawn_blocking(move || {
         log_save_memory("start", Some(&buffer), None);
         info!(
-            "Clip saver started: duration={}s, output={:?}",
-            duration.as_secs(),
-            output_path
+            "Clip saver started: window={:?}, output={:?}",
+            window, output_path
         );
 
-        let mut newest_pts = buffer
+        let newest_pts = buffer
             .newest_pts()
             .context("No packets in buffer to save")?;
-        let mut oldest_pts = buffer.oldest_pts();
+        let oldest_pts = buffer.oldest_pts();
 
-        let mut start_pts = calculate_clip_start_pts(newest_pts, duration, oldest_pts);
+        let (start_pts, end_pts) = window.resolve(newest_pts, oldest_pts)?;
+        // Bookmarks taken inside the window become chapters of the clip.
+        let config =
+            config.with_bookmarks(buffer.bookmarks_in(start_pts, end_pts.unwrap_or(newest_pts)));
 
         debug!(
-            "Clip window: {} to {} (duration: {}s)",
+            "Clip window: {} to {} ({:?})",
             start_pts,
-            newest_pts,
-            duration.as_secs()
+            end_pts.unwrap_or(newest_pts),
+            window
         );
 
-        let has_decodable_video_frame = |packets: &[crate::encode::EncodedPacket]| {
-            packets.iter().any(|packet| {
-                if !matches!(packet.stream, crate::encode::StreamType::Video) {
-                    return false;
+        // ── Phase 1+2: Read and mux ──
+        // Stream the window from RAM in bounded chunks; fall back to a full snapshot when the
+        // window reaches into pinned ranges or the disk spill.
+        let (final_path, counts) =
+            match stream_clip(&buffer, window, start_pts, end_pts, &output_path, &config)? {
+                Some(saved) => saved,
+                None => {
+                    save_from_snapshot(&buffer, window, start_pts, end_pts, &output_path, &config)?
                 }
-                if matches!(h264_nal_type(packet.data.as_ref()), Some(1 | 5 | 7 | 8)) {
-                    return true;
-                }
-                if matches!(
-                    hevc_nal_type(packet.data.as_ref()),
-                    Some(19 | 20 | 32 | 33 | 34)
-                ) {
-                    return true;
-                }
-                false
-            })
-        };
-
-        let max_video_tail_lag_qpc = (crate::buffer::ring::qpc_frequency().max(1) / 2).max(1);
-
-        // ── Phase 1: Take snapshot with aggressive retry memory cleanup ──
-        // Keep as TrackedSnapshot to track pinned bytes until after mux
-        let mut snapshot = buffer
-            .snapshot_from(start_pts)
-            .context("Failed to get packets from buffer")?;
-
-        // Video tail catch-up retries — each retry must drop old snapshot before allocating new
-        for attempt in 1..=CLIP_VIDEO_CATCH_UP_RETRY_LIMIT {
-            let Some(video_tail_lag_qpc) = clip_video_tail_lag_qpc(&snapshot) else {
-                break;
             };
-
-            if video_tail_lag_qpc <= max_video_tail_lag_qpc {
-                break;
-            }
-
-            warn!(
-                "Clip snapshot video tail is behind newest buffered packet by {}ms; retrying catch-up {}/{}",
-                video_tail_lag_qpc.saturating_mul(1000) / crate::buffer::ring::qpc_frequency().max(1),
-                attempt,
-                CLIP_VIDEO_CATCH_UP_RETRY_LIMIT
-            );
-
-            // AGGRESSIVE: drop old snapshot BEFORE sleeping and allocating new one
-            aggressively_drop_packets(snapshot);
-
-            thread::sleep(CLIP_VIDEO_CATCH_UP_SLEEP);
-            newest_pts = buffer.newest_pts().unwrap_or(newest_pts);
-            oldest_pts = buffer.oldest_pts().or(oldest_pts);
-            start_pts = calculate_clip_start_pts(newest_pts, duration, oldest_pts);
-            snapshot = buffer
-                .snapshot_from(start_pts)
-                .context("Failed to refresh packets from buffer during video catch-up")?;
-        }
-
-        // Decodable frame retries
-        if !has_decodable_video_frame(&snapshot) {
-            warn!("Clip snapshot does not yet contain a decodable video frame; retrying briefly");
-            for attempt in 1..=5 {
-                // AGGRESSIVE: drop old snapshot before retry
-                aggressively_drop_packets(snapshot);
-
-                thread::sleep(Duration::from_millis(150));
-                snapshot = buffer
-                    .snapshot_from(start_pts)
-                    .context("Failed to refresh packets from buffer")?;
-                if has_decodable_video_frame(&snapshot) {
-                    info!(
-                        "Found decodable video frame after clip snapshot retry {}/5",
-                        attempt
-                    );
-                    break;
-                }
-            }
-        }
-
-        // Log first video packet info before moving snapshot out
-        {
-            let video_packets: Vec<_> = snapshot
-                .iter()
-                .filter(|p| matches!(p.stream, crate::encode::StreamType::Video))
-                .collect();
-
-            if !video_packets.is_empty() {
-                let first_vid = video_packets[0];
-                let first_20_bytes: Vec<String> = first_vid
-                    .data
-                    .iter()
-                    .take(20)
-                    .map(|b| format!("{:02x}", b))
-                    .collect();
-                info!(
-                    "First video packet: {}B, keyframe={}, first20=[{}]",
-                    first_vid.data.len(),
-                    first_vid.is_keyframe,
-                    first_20_bytes.join(" ")
-                );
-
-                let nal_type = hevc_nal_type(first_vid.data.as_ref());
-                info!("First video packet HEVC NAL type: {:?}", nal_type);
-            }
-        }
-
-        let keyframe_count = snapshot.iter().filter(|p| p.is_keyframe).count();
-        if keyframe_count == 0 {
-            warn!("No keyframes in clip range - video may not be playable");
-        }
-
-        let video_count = snapshot
-            .iter()
-            .filter(|packet| matches!(packet.stream, crate::encode::StreamType::Video))
-            .count();
-        let system_audio_count = snapshot
-            .iter()
-            .filter(|packet| matches!(packet.stream, crate::encode::StreamType::SystemAudio))
-            .count();
-        let mic_audio_count = snapshot
-            .iter()
-            .filter(|packet| matches!(packet.stream, crate::encode::StreamType::Microphone))
-            .count();
-        let audio_count = system_audio_count + mic_audio_count;
-
-        info!(
-            "Prepared clip packet set: {} video packets, {} audio packets ({} system + {} mic)",
-            video_count, audio_count, system_audio_count, mic_audio_count
-        );
-
-        if video_count == 0 {
-            bail!("No video packets in selected clip range");
-        }
-
-        let clip_span_secs = clip_pts_span_seconds(snapshot.as_slice());
-
-        // ── Phase 2: Mux with aggressive cleanup ──
-        log_save_memory("before mux", None, Some(snapshot.as_slice()));
-        let final_path = Muxer::mux_clip(&output_path, &config, snapshot.as_slice())
-            .context("Failed to finalize MP4")?;
-        log_save_memory("after mux", None, Some(snapshot.as_slice()));
-
-        // AGGRESSIVE: explicitly free all packet data immediately after mux
-        // This drops the TrackedSnapshot, decrementing outstanding_snapshot_bytes
-        aggressively_drop_packets(snapshot);
         log_save_memory("after packet release", None, None);
+        let metadata = counts.measured_metadata(&config);
 
         // Release the buffer clone NOW — all needed packets are in the muxed file.
         drop(buffer);
@@ -236,11 +118,11 @@ pub fn spawn_clip_saver(
         info!(
             "Clip saved successfully: {:?} ({} video packets, {} audio packets [{} system + {} mic], ~{:.1}s)",
             final_path,
-            video_count,
-            audio_count,
-            system_audio_count,
-            mic_audio_count,
-            clip_span_secs.unwrap_or(duration.as_secs_f64())
+            counts.video,
+            counts.audio(),
+            counts.system_audio,
+            counts.microphone,
+            counts.span_secs().unwrap_or_default()
         );
 
         // Clean up any leftover fragmented MP4s from prior failed saves.
@@ -278,10 +160,367 @@ pub fn spawn_clip_saver(
             );
         }
 
-        Ok(final_path)
+        Ok((final_path, metadata))
     })
 }
 
+/// Packet counts of a saved clip, for logging and the clip metadata.
+#[derive(Debug, Default)]
+struct ClipPacketCounts {
+    video: usize,
+    system_audio: usize,
+    microphone: usize,
+    keyframes: usize,
+    min_pts: Option<i64>,
+    max_pts: Option<i64>,
+    /// Video packets holding a frame, i.e. not standalone parameter sets, and their PTS range.
+    video_frames: usize,
+    first_frame_pts: Option<i64>,
+    last_frame_pts: Option<i64>,
+}
+
+impl ClipPacketCounts {
+    fn add(&mut self, packets: &[EncodedPacket]) {
+        for packet in packets {
+            match packet.stream {
+                StreamType::Video => self.video += 1,
+                StreamType::SystemAudio => self.system_audio += 1,
+                StreamType::Microphone => self.microphone += 1,
+            }
+            if packet.stream == StreamType::Video && !is_parameter_set_packet(packet) {
+                self.video_frames += 1;
+                self.first_frame_pts = Some(
+                    self.first_frame_pts
+                        .map_or(packet.pts, |pts| pts.min(packet.pts)),
+                );
+                self.last_frame_pts = Some(
+                    self.last_frame_pts
+                        .map_or(packet.pts, |pts| pts.max(packet.pts)),
+                );
+            }
+            self.keyframes += usize::from(packet.is_keyframe);
+            self.min_pts = Some(self.min_pts.map_or(packet.pts, |pts| pts.min(packet.pts)));
+            self.max_pts = Some(self.max_pts.map_or(packet.pts, |pts| pts.max(packet.pts)));
+        }
+    }
+
+    fn audio(&self) -> usize {
+        self.system_audio + self.microphone
+    }
+
+    fn span_secs(&self) -> Option<f64> {
+        let (min_pts, max_pts) = (self.min_pts?, self.max_pts?);
+        Some(max_pts.saturating_sub(min_pts) as f64 / QPC_TICKS_PER_SEC)
+    }
+
+    /// `config.metadata` with the framerate and dropped frames measured over these packets.
+    fn measured_metadata(&self, config: &MuxerConfig) -> Option<ClipMetadata> {
+        let span_secs = match (self.first_frame_pts, self.last_frame_pts) {
+            (Some(first), Some(last)) => last.saturating_sub(first) as f64 / QPC_TICKS_PER_SEC,
+            _ => 0.0,
+        };
+        let metadata = config.metadata.clone()?;
+        Some(metadata.with_video_timing(self.video_frames, span_secs))
+    }
+}
+
+/// Muxes the window straight from the ring, [`CLIP_SAVE_CHUNK_BYTES`] at a time, so the save
+/// never holds a copy of the whole clip.
+///
+/// Windows that start in the disk spill are streamed from it too. Returns `None` without
+/// writing anything when the window cannot be streamed: it reaches into pinned ranges, or its
+/// first chunk has no decodable video frame yet. If the ring overtakes the reader mid-save,
+/// the clip is re-muxed from a snapshot of the range, which the pin taken here keeps complete
+/// for RAM-only windows (spill-backed reads follow evictions into the spill instead).
+fn stream_clip(
+    buffer: &SharedReplayBuffer,
+    window: ClipWindow,
+    mut start_pts: i64,
+    end_pts: Option<i64>,
+    output_path: &Path,
+    config: &MuxerConfig,
+) -> Result<Option<(PathBuf, ClipPacketCounts)>> {
+    // Open-ended windows follow the newest packet: give the video tail a moment to catch up
+    // with audio first, as the snapshot path does.
+    if end_pts.is_none() {
+        let max_video_tail_lag_qpc = (crate::buffer::ring::qpc_frequency().max(1) / 2).max(1);
+        for attempt in 1..=CLIP_VIDEO_CATCH_UP_RETRY_LIMIT {
+            let (Some(newest_pts), Some(newest_video_pts)) = (
+                buffer.newest_pts(),
+                buffer.newest_stream_pts(StreamType::Video),
+            ) else {
+                break;
+            };
+            let video_tail_lag_qpc = newest_pts.saturating_sub(newest_video_pts);
+            if video_tail_lag_qpc <= max_video_tail_lag_qpc {
+                break;
+            }
+            warn!(
+                "Clip video tail is behind newest buffered packet by {}ms; waiting for catch-up {}/{}",
+                video_tail_lag_qpc.saturating_mul(1000) / crate::buffer::ring::qpc_frequency().max(1),
+                attempt,
+                CLIP_VIDEO_CATCH_UP_RETRY_LIMIT
+            );
+            thread::sleep(CLIP_VIDEO_CATCH_UP_SLEEP);
+            let newest_pts = buffer.newest_pts().unwrap_or(newest_pts);
+            start_pts = window.resolve(newest_pts, buffer.oldest_pts())?.0;
+        }
+    }
+
+    let Some(window_end) = end_pts.or_else(|| buffer.newest_pts()) else {
+        return Ok(None);
+    };
+    let Some(chunks) = buffer
+        .snapshot_chunks(start_pts, end_pts, CLIP_SAVE_CHUNK_BYTES)
+        .context("Failed to start reading packets from buffer")?
+    else {
+        debug!("Clip window cannot be streamed; saving from a full snapshot");
+        return Ok(None);
+    };
+    let clip_start_pts = chunks.start_pts();
+    // Packets evicted while the save runs stay readable for the overrun fallback. Reads that
+    // start in the spill need those evictions to reach the spill, so they are not pinned.
+    let pin = if chunks.reads_spill() {
+        None
+    } else {
+        match buffer.pin_range(clip_start_pts, window_end) {
+            Ok(pin) => Some(pin),
+            Err(err) => {
+                debug!("Streaming clip save without a pin: {}", err);
+                None
+            }
+        }
+    };
+
+    let mut stream = ClipStreamMuxer::new(output_path, config);
+    let mut counts = ClipPacketCounts::default();
+    let mut chunk_count = 0usize;
+    for chunk in chunks {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(BufferError::ReadOverrun { lost }) => {
+                warn!(
+                    "Clip save fell behind the replay buffer ({} packets left it unread); re-muxing from a snapshot",
+                    lost
+                );
+                drop(stream);
+                let snapshot = buffer
+                    .snapshot_range(clip_start_pts, window_end)
+                    .context("Failed to get packets from buffer")?;
+                drop(pin);
+                return mux_snapshot(snapshot, output_path, config).map(Some);
+            }
+            Err(err) => return Err(err).context("Failed to read packets from buffer"),
+        };
+
+        if chunk_count == 0 {
+            if !has_decodable_video_frame(&chunk) {
+                debug!("First clip chunk has no decodable video frame; saving from a snapshot");
+                return Ok(None);
+            }
+            log_first_video_packet(&chunk);
+            log_save_memory("before mux", None, Some(chunk.as_slice()));
+        }
+        chunk_count += 1;
+        counts.add(&chunk);
+        stream
+            .write_chunk(chunk.as_slice())
+            .context("Failed to write clip chunk")?;
+        // The chunk is dropped here, releasing its outstanding snapshot bytes.
+    }
+    if chunk_count == 0 {
+        return Ok(None);
+    }
+
+    // The header went out with the first chunk; containers that write their tags with the
+    // trailer still get the timing measured over the whole clip.
+    if let Some(metadata) = counts.measured_metadata(config) {
+        stream.set_metadata(metadata);
+    }
+    let final_path = stream.finish().context("Failed to finalize clip file")?;
+    drop(pin);
+    log_save_memory("after mux", None, None);
+    info!(
+        "Streamed clip packet set in {} chunks of up to {:.1}MB: {} video packets, {} audio packets ({} system + {} mic)",
+        chunk_count,
+        CLIP_SAVE_CHUNK_BYTES as f64 / 1_048_576.0,
+        counts.video,
+        counts.audio(),
+        counts.system_audio,
+        counts.microphone
+    );
+    Ok(Some((final_path, counts)))
+}
+
+/// Saves the window from a full snapshot taken from every buffer tier.
+fn save_from_snapshot(
+    buffer: &SharedReplayBuffer,
+    window: ClipWindow,
+    mut start_pts: i64,
+    end_pts: Option<i64>,
+    output_path: &Path,
+    config: &MuxerConfig,
+) -> Result<(PathBuf, ClipPacketCounts)> {
+    let mut newest_pts = buffer
+        .newest_pts()
+        .context("No packets in buffer to save")?;
+    let mut oldest_pts = buffer.oldest_pts();
+
+    // Windows with a fixed end are read from every buffer tier (RAM, pinned ranges, disk
+    // spill); open-ended windows follow the newest packet.
+    let take_snapshot = |start_pts: i64| match end_pts {
+        Some(end_pts) => buffer.snapshot_range(start_pts, end_pts),
+        None => buffer.snapshot_from(start_pts),
+    };
+
+    let max_video_tail_lag_qpc = (crate::buffer::ring::qpc_frequency().max(1) / 2).max(1);
+
+    // Keep as TrackedSnapshot to track pinned bytes until after mux
+    let mut snapshot = take_snapshot(start_pts).context("Failed to get packets from buffer")?;
+
+    // Video tail catch-up retries — each retry must drop old snapshot before allocating new.
+    // Only open-ended windows chase the newest packet.
+    for attempt in 1..=CLIP_VIDEO_CATCH_UP_RETRY_LIMIT {
+        if end_pts.is_some() {
+            break;
+        }
+        let Some(video_tail_lag_qpc) = clip_video_tail_lag_qpc(&snapshot) else {
+            break;
+        };
+
+        if video_tail_lag_qpc <= max_video_tail_lag_qpc {
+            break;
+        }
+
+        warn!(
+            "Clip snapshot video tail is behind newest buffered packet by {}ms; retrying catch-up {}/{}",
+            video_tail_lag_qpc.saturating_mul(1000) / crate::buffer::ring::qpc_frequency().max(1),
+            attempt,
+            CLIP_VIDEO_CATCH_UP_RETRY_LIMIT
+        );
+
+        // AGGRESSIVE: drop old snapshot BEFORE sleeping and allocating new one
+        aggressively_drop_packets(snapshot);
+
+        thread::sleep(CLIP_VIDEO_CATCH_UP_SLEEP);
+        newest_pts = buffer.newest_pts().unwrap_or(newest_pts);
+        oldest_pts = buffer.oldest_pts().or(oldest_pts);
+        start_pts = window.resolve(newest_pts, oldest_pts)?.0;
+        snapshot = take_snapshot(start_pts)
+            .context("Failed to refresh packets from buffer during video catch-up")?;
+    }
+
+    // Decodable frame retries
+    if !has_decodable_video_frame(&snapshot) {
+        warn!("Clip snapshot does not yet contain a decodable video frame; retrying briefly");
+        for attempt in 1..=5 {
+            // AGGRESSIVE: drop old snapshot before retry
+            aggressively_drop_packets(snapshot);
+
+            thread::sleep(Duration::from_millis(150));
+            snapshot = take_snapshot(start_pts).context("Failed to refresh packets from buffer")?;
+            if has_decodable_video_frame(&snapshot) {
+                info!(
+                    "Found decodable video frame after clip snapshot retry {}/5",
+                    attempt
+                );
+                break;
+            }
+        }
+    }
+
+    mux_snapshot(snapshot, output_path, config)
+}
+
+/// Muxes a complete snapshot and releases it.
+fn mux_snapshot(
+    snapshot: TrackedSnapshot,
+    output_path: &Path,
+    config: &MuxerConfig,
+) -> Result<(PathBuf, ClipPacketCounts)> {
+    log_first_video_packet(&snapshot);
+
+    let mut counts = ClipPacketCounts::default();
+    counts.add(&snapshot);
+    if counts.keyframes == 0 {
+        warn!("No keyframes in clip range - video may not be playable");
+    }
+
+    info!(
+        "Prepared clip packet set: {} video packets, {} audio packets ({} system + {} mic)",
+        counts.video,
+        counts.audio(),
+        counts.system_audio,
+        counts.microphone
+    );
+
+    if counts.video == 0 {
+        bail!("No video packets in selected clip range");
+    }
+    let measured_config;
+    let config = match counts.measured_metadata(config) {
+        Some(metadata) => {
+            measured_config = config.clone().with_metadata(metadata);
+            &measured_config
+        }
+        None => config,
+    };
+
+    // ── Phase 2: Mux with aggressive cleanup ──
+    log_save_memory("before mux", None, Some(snapshot.as_slice()));
+    let final_path = Muxer::mux_clip(output_path, config, snapshot.as_slice())
+        .context("Failed to finalize clip file")?;
+    log_save_memory("after mux", None, Some(snapshot.as_slice()));
+
+    // AGGRESSIVE: explicitly free all packet data immediately after mux
+    // This drops the TrackedSnapshot, decrementing outstanding_snapshot_bytes
+    aggressively_drop_packets(snapshot);
+
+    Ok((final_path, counts))
+}
+
+fn has_decodable_video_frame(packets: &[EncodedPacket]) -> bool {
+    packets.iter().any(|packet| {
+        if !matches!(packet.stream, StreamType::Video) {
+            return false;
+        }
+        if matches!(h264_nal_type(packet.data.as_ref()), Some(1 | 5 | 7 | 8)) {
+            return true;
+        }
+        if matches!(
+            hevc_nal_type(packet.data.as_ref()),
+            Some(19 | 20 | 32 | 33 | 34)
+        ) {
+            return true;
+        }
+        false
+    })
+}
+
+fn log_first_video_packet(packets: &[EncodedPacket]) {
+    let Some(first_vid) = packets
+        .iter()
+        .find(|p| matches!(p.stream, StreamType::Video))
+    else {
+        return;
+    };
+    let first_20_bytes: Vec<String> = first_vid
+        .data
+        .iter()
+        .take(20)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    info!(
+        "First video packet: {}B, keyframe={}, first20=[{}]",
+        first_vid.data.len(),
+        first_vid.is_keyframe,
+        first_20_bytes.join(" ")
+    );
+
+    let nal_type = hevc_nal_type(first_vid.data.as_ref());
+    info!("First video packet HEVC NAL type: {:?}", nal_type);
+}
+
 /// Presentation timestamps use QPC ticks at ~10 MHz (`EncodedPacket::pts`).
 const QPC_TICKS_PER_SEC: f64 = 10_000_000.0;
 
@@ -295,15 +534,6 @@ fn clip_video_tail_lag_qpc(packets: &[EncodedPacket]) -> Option<i64> {
     Some(newest_packet_pts.saturating_sub(newest_video_pts))
 }
 
-fn clip_pts_span_seconds(packets: &[EncodedPacket]) -> Option<f64> {
-    if packets.is_empty() {
-        return None;
-    }
-    let min_pts = packets.iter().map(|p| p.pts).min()?;
-    let max_pts = packets.iter().map(|p| p.pts).max()?;
-    Some(max_pts.saturating_sub(min_pts) as f64 / QPC_TICKS_PER_SEC)
-}
-
 pub fn log_save_memory(
     stage: &str,
     buffer: Option<&SharedReplayBuffer>,
@@ -12,24 +12,34 @@ use std::sync::{
 use std::time::Instant;
 use tracing::{info, warn};
 
+use super::export_audio::ExportAudio;
+use super::overlay::{overlay_filter_spec, ExportOverlay};
+use super::retime::{VideoRetimeGraph, VIDEO_RETIME_TIME_BASE};
+use super::smart_cut::{self, CutSegment, SmartCutSource};
 use super::video_file::{
     ClipExportPhase, ClipExportRequest, ClipExportUpdate, CropRect, ExportAttemptResult,
-    ExportContainerFormat, ExportOutcome, ExportVideoEncoder,
+    ExportAudioTracks, ExportContainerFormat, ExportOutcome, ExportVideoEncoder,
 };
 
 /// Number of audio samples per frame for AAC encoding at 48 kHz.
 const AAC_FRAME_SAMPLES: i64 = 1024;
 /// Number of audio samples per frame for Opus encoding at 48 kHz (20 ms frames).
 const OPUS_FRAME_SAMPLES: i64 = 960;
-const INVALID_DURATION: i64 = i64::MIN;
+pub(super) const INVALID_DURATION: i64 = i64::MIN;
 
 /// Returns the audio frame sample count for the given container format.
-fn audio_frame_samples_for_container(container: ExportContainerFormat) -> i64 {
+pub(super) fn audio_frame_samples_for_container(container: ExportContainerFormat) -> i64 {
     match container {
-        ExportContainerFormat::WebM => OPUS_FRAME_SAMPLES,
-        ExportContainerFormat::Mp4 | ExportContainerFormat::Mkv | ExportContainerFormat::Mov => {
-            AAC_FRAME_SAMPLES
-        }
+        ExportContainerFormat::WebM | ExportContainerFormat::Opus => OPUS_FRAME_SAMPLES,
+        ExportContainerFormat::Mp4
+        | ExportContainerFormat::Mkv
+        | ExportContainerFormat::Mov
+        | ExportContainerFormat::Gif
+        | ExportContainerFormat::WebP
+        | ExportContainerFormat::Apng
+        | ExportContainerFormat::M4a
+        | ExportContainerFormat::Flac
+        | ExportContainerFormat::Wav => AAC_FRAME_SAMPLES,
     }
 }
 
@@ -327,6 +337,92 @@ impl PostProcessFilterGraph {
     }
 }
 
+/// Draws the request's overlays on every scaled frame (`buffer → drawtext / overlay… →
+/// format → buffersink`).
+///
+/// Frames go in with timestamps in output frames (`1/fps`), which is what the overlays'
+/// time ranges are evaluated against, and come out in the encoder's pixel format.
+pub(super) struct OverlayFilterGraph {
+    #[allow(dead_code)]
+    graph: ffmpeg::filter::Graph,
+    source: ffmpeg::filter::Context,
+    sink: ffmpeg::filter::Context,
+    overlaid_frame: ffmpeg::frame::Video,
+}
+
+impl OverlayFilterGraph {
+    /// Builds the graph, or returns `None` when there is nothing to draw.
+    pub(super) fn new(
+        overlays: &[ExportOverlay],
+        width: u32,
+        height: u32,
+        fps: i32,
+        pixel_format: ffmpeg::format::Pixel,
+    ) -> Result<Option<Self>> {
+        if overlays.is_empty() {
+            return Ok(None);
+        }
+
+        let mut graph = ffmpeg::filter::Graph::new();
+        let buffer = ffmpeg::filter::find("buffer").context("buffer filter not found")?;
+        let buffersink =
+            ffmpeg::filter::find("buffersink").context("buffersink filter not found")?;
+        let args = format!(
+            "video_size={width}x{height}:pix_fmt={}:time_base=1/{}:pixel_aspect=1/1",
+            ffmpeg::ffi::AVPixelFormat::from(pixel_format) as i32,
+            fps.max(1),
+        );
+        let source = graph
+            .add(&buffer, "in", &args)
+            .context("Failed to add buffer filter to graph")?;
+        let sink = graph
+            .add(&buffersink, "out", "")
+            .context("Failed to add buffersink filter to graph")?;
+
+        let pixel_format_name = pixel_format
+            .descriptor()
+            .context("Unknown encoder pixel format")?
+            .name();
+        let spec = overlay_filter_spec(overlays, height, pixel_format_name);
+        graph
+            .output("in", 0)?
+            .input("out", 0)?
+            .parse(&spec)
+            .with_context(|| format!("Failed to parse overlay filter: {spec}"))?;
+        graph
+            .validate()
+            .context("Failed to validate overlay filter graph")?;
+
+        info!(
+            overlays = overlays.len(),
+            width, height, "Overlay filter graph created"
+        );
+
+        Ok(Some(Self {
+            graph,
+            source,
+            sink,
+            overlaid_frame: ffmpeg::frame::Video::empty(),
+        }))
+    }
+
+    /// Draws the overlays on `frame`, keeping its pts.
+    pub(super) fn apply(
+        &mut self,
+        frame: &ffmpeg::frame::Video,
+    ) -> Result<&mut ffmpeg::frame::Video> {
+        self.source
+            .source()
+            .add(frame)
+            .context("Failed to push frame into overlay graph")?;
+        if self.sink.sink().frame(&mut self.overlaid_frame).is_err() {
+            anyhow::bail!("Overlay filter graph produced no frame");
+        }
+        self.overlaid_frame.set_pts(frame.pts());
+        Ok(&mut self.overlaid_frame)
+    }
+}
+
 fn export_encoder_pixel_format(encoder: ExportVideoEncoder) -> ffmpeg::format::Pixel {
     match encoder {
         ExportVideoEncoder::SoftwareHevc => ffmpeg::format::Pixel::YUV420P,
@@ -338,7 +434,7 @@ fn export_encoder_pixel_format(encoder: ExportVideoEncoder) -> ffmpeg::format::P
 
 /// Pixel format used for a given video encoder in the specified container.
 /// For WebM (VP9) we always use YUV420P (software only).
-fn export_encoder_pixel_format_for_container(
+pub(super) fn export_encoder_pixel_format_for_container(
     encoder: ExportVideoEncoder,
     container: ExportContainerFormat,
 ) -> ffmpeg::format::Pixel {
@@ -351,30 +447,49 @@ fn export_encoder_pixel_format_for_container(
 }
 
 /// Returns the container-appropriate muxer header options.
-fn muxer_header_opts_for_container(
+pub(super) fn muxer_header_opts_for_container(
     container: ExportContainerFormat,
 ) -> ffmpeg::Dictionary<'static> {
     let mut opts = ffmpeg::Dictionary::new();
     match container {
-        ExportContainerFormat::Mp4 | ExportContainerFormat::Mov => {
+        ExportContainerFormat::Mp4 | ExportContainerFormat::Mov | ExportContainerFormat::M4a => {
             opts.set("movflags", "+faststart");
         }
         ExportContainerFormat::Mkv | ExportContainerFormat::WebM => {
             // MKV and WebM don't need moov atom relocation
         }
+        ExportContainerFormat::Opus | ExportContainerFormat::Flac | ExportContainerFormat::Wav => {
+            // Plain audio muxers have no relevant header options
+        }
+        ExportContainerFormat::Gif | ExportContainerFormat::WebP => {
+            // Loop forever, like the clips they stand in for in chats.
+            opts.set("loop", "0");
+        }
+        ExportContainerFormat::Apng => {
+            opts.set("plays", "0");
+        }
     }
     opts
 }
 
 /// Returns the container-appropriate audio codec ID.
-/// WebM uses Opus; all others use AAC.
-fn audio_codec_for_container(
+/// WebM and Opus use Opus, FLAC and WAV use their lossless codecs, the animated image
+/// formats have no audio, and all others use AAC.
+pub(super) fn audio_codec_for_container(
     container: ExportContainerFormat,
 ) -> Option<(ffmpeg::codec::Id, &'static str)> {
     match container {
-        ExportContainerFormat::WebM => Some((ffmpeg::codec::Id::OPUS, "libopus")),
-        ExportContainerFormat::Mp4 | ExportContainerFormat::Mkv | ExportContainerFormat::Mov => {
-            Some((ffmpeg::codec::Id::AAC, "AAC"))
+        ExportContainerFormat::WebM | ExportContainerFormat::Opus => {
+            Some((ffmpeg::codec::Id::OPUS, "libopus"))
+        }
+        ExportContainerFormat::Mp4
+        | ExportContainerFormat::Mkv
+        | ExportContainerFormat::Mov
+        | ExportContainerFormat::M4a => Some((ffmpeg::codec::Id::AAC, "AAC")),
+        ExportContainerFormat::Flac => Some((ffmpeg::codec::Id::FLAC, "FLAC")),
+        ExportContainerFormat::Wav => Some((ffmpeg::codec::Id::PCM_S16LE, "PCM")),
+        ExportContainerFormat::Gif | ExportContainerFormat::WebP | ExportContainerFormat::Apng => {
+            None
         }
     }
 }
@@ -431,7 +546,7 @@ impl PostProcessFilterGraph {
 /// When `crop` is `Some`, only the specified rectangle from `input` is extracted
 /// and scaled to fill `output`. This uses `sws_scale` directly so we can pass
 /// adjusted source data pointers for the horizontal crop offset.
-fn scale_with_crop(
+pub(super) fn scale_with_crop(
     scaler: &mut ffmpeg::software::scaling::context::Context,
     input: &ffmpeg::util::frame::video::Video,
     output: &mut ffmpeg::util::frame::video::Video,
@@ -514,12 +629,12 @@ fn scale_with_crop(
     Ok(())
 }
 
-fn seek_to_seconds(input_ctx: &mut ffmpeg::format::context::Input, position_secs: f64) {
+pub(super) fn seek_to_seconds(input_ctx: &mut ffmpeg::format::context::Input, position_secs: f64) {
     let ts = (position_secs.max(0.0) * f64::from(ffmpeg::ffi::AV_TIME_BASE)).round() as i64;
     let _ = input_ctx.seek(ts, ..);
 }
 
-fn time_base_to_secs_per_tick(time_base: ffmpeg::Rational) -> Option<f64> {
+pub(super) fn time_base_to_secs_per_tick(time_base: ffmpeg::Rational) -> Option<f64> {
     let num = f64::from(time_base.numerator());
     let den = f64::from(time_base.denominator());
     if den <= 0.0 {
@@ -529,6 +644,9 @@ fn time_base_to_secs_per_tick(time_base: ffmpeg::Rational) -> Option<f64> {
 }
 
 /// Run stream copy export using ffmpeg-next (trim and concat without re-encoding).
+///
+/// With [`ClipExportRequest::smart_cut`] on an HEVC source, the partial GOPs at the edges of
+/// each kept range are re-encoded so cuts are frame-accurate.
 pub fn run_stream_copy_export_sdk(
     request: &ClipExportRequest,
     progress_tx: &Sender<ClipExportUpdate>,
@@ -573,6 +691,13 @@ pub fn run_stream_copy_export_sdk(
     )
     .round() as i32;
 
+    // `Mix` never reaches stream copy (see `ExportAudioTracks::requires_reencode`), so all
+    // audio tracks are copied unless only the first is kept.
+    let max_audio_tracks = match request.audio_tracks {
+        ExportAudioTracks::PrimaryOnly => 1,
+        ExportAudioTracks::Keep | ExportAudioTracks::Mix => usize::MAX,
+    };
+    let mut audio_tracks_copied = 0usize;
     let mut stream_mapping: Vec<(usize, usize, bool)> = vec![];
     for (stream_index, stream) in input_ctx.streams().enumerate() {
         let codec_params = stream.parameters();
@@ -597,7 +722,9 @@ pub fn run_stream_copy_export_sdk(
                 }
                 stream_mapping.push((stream_index, out_stream.index(), true));
             }
-            ffmpeg::media::Type::Audio if request.metadata.has_audio => {
+            ffmpeg::media::Type::Audio
+                if request.metadata.has_audio && audio_tracks_copied < max_audio_tracks =>
+            {
                 let mut out_stream = output_ctx
                     .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))
                     .context("Failed to add audio stream")?;
@@ -610,7 +737,10 @@ pub fn run_stream_copy_export_sdk(
                 }
                 out_stream.set_parameters(codec_params);
                 out_stream.set_time_base(stream.time_base());
+                // Track title and language.
+                out_stream.set_metadata(stream.metadata().to_owned());
                 stream_mapping.push((stream_index, out_stream.index(), true));
+                audio_tracks_copied += 1;
             }
             _ => {
                 stream_mapping.push((stream_index, usize::MAX, false));
@@ -620,14 +750,6 @@ pub fn run_stream_copy_export_sdk(
 
     // Build stream lookup Vec once so packet processing doesn't repeatedly scan stream mappings.
     // Uses dense Vec indexed by input stream index for O(1) lookup instead of HashMap.
-    #[derive(Clone, Copy)]
-    struct StreamCopyRoute {
-        out_idx: usize,
-        in_time_base: ffmpeg::Rational,
-        out_time_base: ffmpeg::Rational,
-        is_video: bool,
-    }
-
     let input_stream_count = input_ctx.nb_streams() as usize;
     let mut stream_routes: Vec<Option<StreamCopyRoute>> = vec![None; input_stream_count];
     let mut output_stream_count = 0usize;
@@ -639,10 +761,6 @@ pub fn run_stream_copy_export_sdk(
             .stream(*in_idx)
             .with_context(|| format!("Missing input stream {}", in_idx))?
             .time_base();
-        let out_time_base = output_ctx
-            .stream(*out_idx)
-            .with_context(|| format!("Missing output stream {}", out_idx))?
-            .time_base();
         let is_video = input_ctx
             .stream(*in_idx)
             .map(|s| s.parameters().medium() == ffmpeg::media::Type::Video)
@@ -650,7 +768,7 @@ pub fn run_stream_copy_export_sdk(
         stream_routes[*in_idx] = Some(StreamCopyRoute {
             out_idx: *out_idx,
             in_time_base,
-            out_time_base,
+            out_time_base: in_time_base,
             is_video,
         });
         output_stream_count = output_stream_count.max(out_idx + 1);
@@ -662,15 +780,37 @@ pub fn run_stream_copy_export_sdk(
         .write_header_with(header_opts)
         .with_context(|| "Failed to write output header for stream copy")?;
 
+    // The muxer may adjust stream time bases when the header is written (Matroska always
+    // uses milliseconds), so read the output time bases only now.
+    for route in stream_routes.iter_mut().flatten() {
+        route.out_time_base = output_ctx
+            .stream(route.out_idx)
+            .with_context(|| format!("Missing output stream {}", route.out_idx))?
+            .time_base();
+    }
+
+    let smart_cut_source = if request.smart_cut {
+        let video_index = stream_routes
+            .iter()
+            .position(|route| route.is_some_and(|r| r.is_video));
+        match video_index
+            .map(|index| SmartCutSource::probe(&mut input_ctx, index, request.metadata.fps))
+        {
+            Some(Ok(source)) => Some(source),
+            Some(Err(e)) => {
+                warn!("Smart cut unavailable, cutting at keyframes: {:#}", e);
+                None
+            }
+            None => None,
+        }
+    } else {
+        None
+    };
+
     // Read packets by output range (seek per range), so stream-copy work scales with kept duration.
     let start_time = Instant::now();
-    let mut last_progress_time = start_time;
-    let mut processed_duration: f64 = 0.0;
     let mut output_cursor_secs = 0.0f64;
-
-    // Use dense Vecs indexed by output stream index for O(1) lookup instead of HashMap.
-    let mut last_out_dts_by_stream: Vec<Option<i64>> = vec![None; output_stream_count];
-    let mut last_out_pts_by_stream: Vec<Option<i64>> = vec![None; output_stream_count];
+    let mut writer = StreamCopyWriter::new(output_stream_count, total_duration_secs, progress_tx);
     let route_count = stream_routes.iter().filter(|r| r.is_some()).count();
 
     // Maximum duration of a single frame in seconds, used to extend the end boundary
@@ -686,163 +826,160 @@ pub fn run_stream_copy_export_sdk(
         let range_started_at = Instant::now();
         let range_output_start_secs = output_cursor_secs;
         output_cursor_secs += range.duration_secs();
-        seek_to_seconds(&mut input_ctx, range.start_secs);
-
-        // Dense Vec<bool> indexed by input stream index instead of HashSet.
-        let mut streams_past_range_end: Vec<bool> = vec![false; input_stream_count];
-        let mut streams_past_range_end_count = 0usize;
-
-        // Scan forward until we hit a keyframe at/after the requested range start
-        // on each video stream. We must not "unlock" on a keyframe that is before
-        // range.start_secs and then drop pre-start packets, because that leaves the
-        // first in-range frames without their reference chain and can produce
-        // white/corrupt leading frames in stream-copy output.
-        let mut pending_video_keyframes: Vec<bool> = vec![false; input_stream_count];
-        let mut pending_video_keyframe_count = 0usize;
-        for (idx, route) in stream_routes.iter().enumerate() {
-            if let Some(r) = route {
-                if r.is_video {
-                    pending_video_keyframes[idx] = true;
-                    pending_video_keyframe_count += 1;
-                }
-            }
-        }
-        let mut keyframe_scan_done = pending_video_keyframe_count == 0;
-
-        for (stream, mut packet) in input_ctx.packets() {
-            if cancel_flag.load(Ordering::Relaxed) {
-                return Ok(ExportOutcome::Cancelled);
-            }
-            let stream_index = stream.index();
-            let route = match stream_routes.get(stream_index).copied().flatten() {
-                Some(r) => r,
-                None => continue,
-            };
+        let output_offset_secs = range_output_start_secs - range.start_secs;
+
+        let segments = match &smart_cut_source {
+            Some(source) => source.plan(*range),
+            None => vec![CutSegment::Copy {
+                start_secs: range.start_secs,
+                end_secs: range.end_secs,
+            }],
+        };
+        let mut after_reencode = false;
 
-            let secs_per_in_tick = match time_base_to_secs_per_tick(route.in_time_base) {
-                Some(v) => v,
-                None => continue,
-            };
-            let secs_per_out_tick = match time_base_to_secs_per_tick(route.out_time_base) {
-                Some(v) => v,
-                None => continue,
+        for (segment_index, segment) in segments.iter().copied().enumerate() {
+            // Include one extra frame past end_secs so the last frame doesn't hang.
+            let keep_until_secs = if segment_index + 1 == segments.len() {
+                segment.end_secs() + max_frame_secs
+            } else {
+                segment.end_secs()
             };
 
-            let pts_in = match packet.pts().or(packet.dts()) {
-                Some(ts) => ts,
-                None => continue,
-            };
-            let pts_secs = pts_in as f64 * secs_per_in_tick;
-
-            // While finding start keyframes, drop only non-key video packets.
-            // Audio (and other non-video) packets pass through so they start at
-            // the correct position and don't lag behind the first video frame.
-            if !keyframe_scan_done {
-                if route.is_video && packet.is_key() && pts_secs >= range.start_secs {
-                    if pending_video_keyframes[stream_index] {
-                        pending_video_keyframes[stream_index] = false;
-                        pending_video_keyframe_count -= 1;
-                    }
-                    if pending_video_keyframe_count == 0 {
-                        keyframe_scan_done = true;
-                    }
-                }
-                if !keyframe_scan_done && route.is_video {
-                    // Drop non-key video packets during scan — they depend on the
-                    // future keyframe's reference chain and can't be decoded yet.
-                    continue;
+            if let (CutSegment::Reencode { .. }, Some(source)) = (segment, &smart_cut_source) {
+                let finished = smart_cut::reencode_segment(
+                    &mut input_ctx,
+                    &mut output_ctx,
+                    &mut writer,
+                    &stream_routes,
+                    source,
+                    segment,
+                    keep_until_secs,
+                    output_offset_secs,
+                    cancel_flag,
+                )?;
+                if !finished {
+                    return Ok(ExportOutcome::Cancelled);
                 }
-                // Non-video streams (audio, subtitles) pass through so they start
-                // at the trim boundary in sync with the first video frame.
+                after_reencode = true;
+                continue;
             }
 
-            // Once all copied streams are comfortably past the range end, stop this range.
-            let end_boundary = range.end_secs + end_grace_secs;
-            if pts_secs >= end_boundary {
-                if !streams_past_range_end[stream_index] {
-                    streams_past_range_end[stream_index] = true;
-                    streams_past_range_end_count += 1;
-                }
-                if streams_past_range_end_count >= route_count {
-                    break;
+            seek_to_seconds(&mut input_ctx, segment.start_secs());
+
+            // Dense Vec<bool> indexed by input stream index instead of HashSet.
+            let mut streams_past_range_end: Vec<bool> = vec![false; input_stream_count];
+            let mut streams_past_range_end_count = 0usize;
+
+            // Scan forward until we hit a keyframe at/after the segment start
+            // on each video stream. We must not "unlock" on a keyframe that is before
+            // the segment start and then drop pre-start packets, because that leaves the
+            // first in-range frames without their reference chain and can produce
+            // white/corrupt leading frames in stream-copy output.
+            let mut pending_video_keyframes: Vec<bool> = vec![false; input_stream_count];
+            let mut pending_video_keyframe_count = 0usize;
+            for (idx, route) in stream_routes.iter().enumerate() {
+                if let Some(r) = route {
+                    if r.is_video {
+                        pending_video_keyframes[idx] = true;
+                        pending_video_keyframe_count += 1;
+                    }
                 }
-                continue;
-            }
-            // Skip packets before the requested start (they were captured to satisfy
-            // keyframe references but are outside the user's trim window).
-            // Include one extra frame past end_secs so the last frame doesn't hang.
-            let max_end_pts = range.end_secs + max_frame_secs;
-            if pts_secs < range.start_secs || pts_secs >= max_end_pts {
-                continue;
             }
+            let mut keyframe_scan_done = pending_video_keyframe_count == 0;
 
-            let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs);
-            let adjusted_pts = (output_pts_secs / secs_per_out_tick).round() as i64;
-            let pts_offset_secs = output_pts_secs - pts_secs;
-            let adjusted_dts = packet.dts().or(packet.pts()).map(|dts_in| {
-                let dts_secs = dts_in as f64 * secs_per_in_tick;
-                let output_dts_secs = dts_secs + pts_offset_secs;
-                (output_dts_secs / secs_per_out_tick).round() as i64
-            });
+            for (stream, packet) in input_ctx.packets() {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return Ok(ExportOutcome::Cancelled);
+                }
+                let stream_index = stream.index();
+                let route = match stream_routes.get(stream_index).copied().flatten() {
+                    Some(r) => r,
+                    None => continue,
+                };
 
-            // Rescale timestamps to the output timebase before applying final PTS/DTS.
-            packet.rescale_ts(route.in_time_base, route.out_time_base);
-            packet.set_position(-1);
-            packet.set_stream(route.out_idx);
+                let secs_per_in_tick = match time_base_to_secs_per_tick(route.in_time_base) {
+                    Some(v) => v,
+                    None => continue,
+                };
 
-            let mut fixed_pts = adjusted_pts.max(0);
-            let mut fixed_dts = adjusted_dts.unwrap_or(fixed_pts).max(0);
-            if fixed_pts < fixed_dts {
-                fixed_pts = fixed_dts;
-            }
+                let pts_in = match packet.pts().or(packet.dts()) {
+                    Some(ts) => ts,
+                    None => continue,
+                };
+                let pts_secs = pts_in as f64 * secs_per_in_tick;
+
+                // While finding start keyframes, drop only non-key video packets.
+                // Audio (and other non-video) packets pass through so they start at
+                // the correct position and don't lag behind the first video frame.
+                let mut first_keyframe = false;
+                if !keyframe_scan_done {
+                    if route.is_video && packet.is_key() && pts_secs >= segment.start_secs() {
+                        if pending_video_keyframes[stream_index] {
+                            pending_video_keyframes[stream_index] = false;
+                            pending_video_keyframe_count -= 1;
+                            first_keyframe = true;
+                        }
+                        if pending_video_keyframe_count == 0 {
+                            keyframe_scan_done = true;
+                        }
+                    }
+                    if !keyframe_scan_done && route.is_video {
+                        // Drop non-key video packets during scan — they depend on the
+                        // future keyframe's reference chain and can't be decoded yet.
+                        continue;
+                    }
+                    // Non-video streams (audio, subtitles) pass through so they start
+                    // at the trim boundary in sync with the first video frame.
+                }
 
-            // Monotonicity enforcement using dense Vec lookup (O(1) instead of HashMap).
-            if let Some(last) = last_out_dts_by_stream.get(route.out_idx).copied().flatten() {
-                if fixed_dts < last {
-                    fixed_dts = last;
+                // Once all copied streams are comfortably past the range end, stop this range.
+                let end_boundary = keep_until_secs + end_grace_secs;
+                if pts_secs >= end_boundary {
+                    if !streams_past_range_end[stream_index] {
+                        streams_past_range_end[stream_index] = true;
+                        streams_past_range_end_count += 1;
+                    }
+                    if streams_past_range_end_count >= route_count {
+                        break;
+                    }
+                    continue;
                 }
-            }
-            if let Some(last) = last_out_pts_by_stream.get(route.out_idx).copied().flatten() {
-                if fixed_pts < last {
-                    fixed_pts = last;
+                // Skip packets before the requested start (they were captured to satisfy
+                // keyframe references but are outside the user's trim window).
+                if pts_secs < segment.start_secs() || pts_secs >= keep_until_secs {
+                    continue;
                 }
-            }
-
-            packet.set_dts(Some(fixed_dts));
-            packet.set_pts(Some(fixed_pts));
-
-            last_out_dts_by_stream[route.out_idx] = Some(fixed_dts);
-            last_out_pts_by_stream[route.out_idx] = Some(fixed_pts);
 
-            processed_duration = processed_duration.max(output_pts_secs);
-
-            packet
-                .write_interleaved(&mut output_ctx)
-                .with_context(|| "Failed writing packet during stream copy")?;
+                let output_pts_secs = pts_secs + output_offset_secs;
+                let pts_offset_secs = output_pts_secs - pts_secs;
+                let output_dts_secs = packet
+                    .dts()
+                    .map(|dts_in| dts_in as f64 * secs_per_in_tick + pts_offset_secs);
 
-            // Progress reporting - use structured logging to avoid format! allocations in hot path
-            let now = Instant::now();
-            const MIN_PROGRESS_INTERVAL: std::time::Duration =
-                std::time::Duration::from_millis(100);
-            if now.duration_since(last_progress_time) >= MIN_PROGRESS_INTERVAL {
-                last_progress_time = now;
-                let progress = (processed_duration / total_duration_secs).min(1.0) as f32;
-                tracing::debug!(
-                    processed_duration_secs = processed_duration,
-                    progress_pct = progress * 100.0,
-                    "Stream copy progress"
-                );
-                let _ = progress_tx.send(ClipExportUpdate::Progress {
-                    phase: ClipExportPhase::Preparing,
-                    fraction: progress,
-                    message: format!("Stream copy: {:.1}s processed", processed_duration),
-                });
+                // Decoders picked up the re-encoded segment's in-band parameter sets;
+                // hand them the source's again on the first copied keyframe.
+                let packet = match &smart_cut_source {
+                    Some(source) if first_keyframe && after_reencode => {
+                        source.with_parameter_sets(&packet)
+                    }
+                    _ => packet,
+                };
+                writer.write(
+                    &mut output_ctx,
+                    packet,
+                    route,
+                    route.in_time_base,
+                    output_pts_secs,
+                    output_dts_secs,
+                )?;
             }
+            after_reencode = false;
         }
 
         info!(
             range_index = range_index + 1,
             range_count = request.keep_ranges.len(),
+            range_segments = segments.len(),
             range_elapsed_secs = range_started_at.elapsed().as_secs_f64(),
             range_duration_secs = range.duration_secs(),
             "Stream copy range completed"
@@ -856,97 +993,153 @@ pub fn run_stream_copy_export_sdk(
     info!(
         elapsed_secs = start_time.elapsed().as_secs_f64(),
         output = ?request.output_path,
+        smart_cut = smart_cut_source.is_some(),
         "Stream copy export complete"
     );
     Ok(ExportOutcome::Finished(request.output_path.clone()))
 }
 
-/// Run export attempt using ffmpeg-next filter graphs.
-#[allow(clippy::too_many_arguments)]
-pub(crate) fn attempt_export(
-    request: &ClipExportRequest,
-    output_path: &Path,
-    video_bitrate_kbps: u32,
-    audio_bitrate_kbps: u32,
-    video_encoder: ExportVideoEncoder,
-    progress_tx: &Sender<ClipExportUpdate>,
-    cancel_flag: &Arc<AtomicBool>,
-    attempt_index: usize,
-    attempt_count: usize,
-    single_pass_phase: ClipExportPhase,
-) -> Result<Option<ExportAttemptResult>> {
-    let total_duration_secs = request.output_duration_secs().max(0.1);
+/// Where a copied input stream goes in a stream copy export.
+#[derive(Clone, Copy)]
+pub(super) struct StreamCopyRoute {
+    pub(super) out_idx: usize,
+    pub(super) in_time_base: ffmpeg::Rational,
+    pub(super) out_time_base: ffmpeg::Rational,
+    pub(super) is_video: bool,
+}
 
-    let _ = progress_tx.send(ClipExportUpdate::Progress {
-        phase: ClipExportPhase::Preparing,
-        fraction: 0.0,
-        message: "Preparing export".to_string(),
-    });
+/// Writes stream copy packets at their output timeline position, keeping timestamps
+/// monotonic per output stream and reporting progress.
+pub(super) struct StreamCopyWriter<'a> {
+    // Dense Vecs indexed by output stream index for O(1) lookup instead of HashMap.
+    last_out_dts_by_stream: Vec<Option<i64>>,
+    last_out_pts_by_stream: Vec<Option<i64>>,
+    processed_duration: f64,
+    total_duration_secs: f64,
+    last_progress_time: Instant,
+    progress_tx: &'a Sender<ClipExportUpdate>,
+}
 
-    info!(
-        "Exporting clipped video {:?} -> {:?} ({} kept ranges, target={} MB, video bitrate={} kbps, codec={}, container={})",
-        request.input_path,
-        output_path,
-        request.keep_ranges.len(),
-        request.target_size_mb,
-        video_bitrate_kbps,
-        video_encoder.ffmpeg_name_for_container(request.container_format),
-        request.container_format.short_label(),
-    );
+impl<'a> StreamCopyWriter<'a> {
+    fn new(
+        output_stream_count: usize,
+        total_duration_secs: f64,
+        progress_tx: &'a Sender<ClipExportUpdate>,
+    ) -> Self {
+        Self {
+            last_out_dts_by_stream: vec![None; output_stream_count],
+            last_out_pts_by_stream: vec![None; output_stream_count],
+            processed_duration: 0.0,
+            total_duration_secs,
+            last_progress_time: Instant::now(),
+            progress_tx,
+        }
+    }
 
-    // Open input
-    let mut input_ctx = ffmpeg::format::input(&request.input_path)
-        .with_context(|| format!("Failed to open input: {:?}", request.input_path))?;
+    /// Writes `packet`, whose timestamps are in `packet_time_base`, at `output_pts_secs`
+    /// on the output timeline.
+    pub(super) fn write(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        mut packet: ffmpeg::Packet,
+        route: StreamCopyRoute,
+        packet_time_base: ffmpeg::Rational,
+        output_pts_secs: f64,
+        output_dts_secs: Option<f64>,
+    ) -> Result<()> {
+        let secs_per_out_tick = match time_base_to_secs_per_tick(route.out_time_base) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let adjusted_pts = (output_pts_secs / secs_per_out_tick).round() as i64;
+        let adjusted_dts =
+            output_dts_secs.map(|dts_secs| (dts_secs / secs_per_out_tick).round() as i64);
+
+        // Rescale timestamps to the output timebase before applying final PTS/DTS.
+        packet.rescale_ts(packet_time_base, route.out_time_base);
+        packet.set_position(-1);
+        packet.set_stream(route.out_idx);
+
+        let mut fixed_pts = adjusted_pts.max(0);
+        let mut fixed_dts = adjusted_dts.unwrap_or(fixed_pts).max(0);
+        if fixed_pts < fixed_dts {
+            fixed_pts = fixed_dts;
+        }
 
-    // Find video and audio streams
-    let video_stream_idx = input_ctx
-        .streams()
-        .best(ffmpeg::media::Type::Video)
-        .map(|s| s.index())
-        .context("No video stream found")?;
+        // Monotonicity enforcement using dense Vec lookup (O(1) instead of HashMap).
+        if let Some(last) = self
+            .last_out_dts_by_stream
+            .get(route.out_idx)
+            .copied()
+            .flatten()
+        {
+            if fixed_dts < last {
+                fixed_dts = last;
+            }
+        }
+        if let Some(last) = self
+            .last_out_pts_by_stream
+            .get(route.out_idx)
+            .copied()
+            .flatten()
+        {
+            if fixed_pts < last {
+                fixed_pts = last;
+            }
+        }
 
-    let audio_stream_idx = if request.metadata.has_audio {
-        input_ctx
-            .streams()
-            .best(ffmpeg::media::Type::Audio)
-            .map(|s| s.index())
-    } else {
-        None
-    };
+        packet.set_dts(Some(fixed_dts));
+        packet.set_pts(Some(fixed_pts));
 
-    let video_stream = input_ctx
-        .stream(video_stream_idx)
-        .context("Missing video stream")?;
-    let input_time_base = video_stream.time_base();
+        self.last_out_dts_by_stream[route.out_idx] = Some(fixed_dts);
+        self.last_out_pts_by_stream[route.out_idx] = Some(fixed_pts);
 
-    // Store audio time base separately (Bug 4 fix: audio has different time base than video)
-    let audio_input_time_base = if let Some(audio_idx) = audio_stream_idx {
-        input_ctx.stream(audio_idx).map(|s| s.time_base())
-    } else {
-        None
-    };
+        self.processed_duration = self.processed_duration.max(output_pts_secs);
 
-    // Create output
-    let mut output_ctx = ffmpeg::format::output(output_path)
-        .with_context(|| format!("Failed to create output: {:?}", output_path))?;
+        packet
+            .write_interleaved(output_ctx)
+            .with_context(|| "Failed writing packet during stream copy")?;
 
-    // Add video stream with encoder - use encoder name to get hardware encoder if needed
-    // The codec name is resolved per-container (e.g., VP9 for WebM, H.265 for others).
-    let codec_name = video_encoder.ffmpeg_name_for_container(request.container_format);
+        // Progress reporting - use structured logging to avoid format! allocations in hot path
+        let now = Instant::now();
+        const MIN_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+        if now.duration_since(self.last_progress_time) >= MIN_PROGRESS_INTERVAL {
+            self.last_progress_time = now;
+            let progress = (self.processed_duration / self.total_duration_secs).min(1.0) as f32;
+            tracing::debug!(
+                processed_duration_secs = self.processed_duration,
+                progress_pct = progress * 100.0,
+                "Stream copy progress"
+            );
+            let _ = self.progress_tx.send(ClipExportUpdate::Progress {
+                phase: ClipExportPhase::Preparing,
+                fraction: progress,
+                message: format!("Stream copy: {:.1}s processed", self.processed_duration),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Opens the video encoder for an export attempt and adds its output stream.
+///
+/// The codec is resolved per container (VP9 for WebM, H.265 for the others); returns the
+/// opened encoder and the index of its output stream.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn open_export_video_encoder(
+    output_ctx: &mut ffmpeg::format::context::Output,
+    container: ExportContainerFormat,
+    video_encoder: ExportVideoEncoder,
+    output_width: u32,
+    output_height: u32,
+    output_fps_i32: i32,
+    video_bitrate_kbps: u32,
+) -> Result<(ffmpeg::encoder::Video, usize)> {
+    // Use encoder name to get hardware encoder if needed
+    let codec_name = video_encoder.ffmpeg_name_for_container(container);
     let codec = ffmpeg::encoder::find_by_name(codec_name)
         .with_context(|| format!("Encoder {} not found", codec_name))?;
-    let encoder_pixel_format =
-        export_encoder_pixel_format_for_container(video_encoder, request.container_format);
-
-    let output_width = request.output_width.unwrap_or(request.metadata.width);
-    let output_height = request.output_height.unwrap_or(request.metadata.height);
-    let crop = request.crop;
-
-    let output_fps_i32 = super::video_file::normalize_output_fps(
-        request.output_fps.unwrap_or(request.metadata.fps),
-        request.metadata.fps,
-    )
-    .round() as i32;
+    let encoder_pixel_format = export_encoder_pixel_format_for_container(video_encoder, container);
 
     // Create encoder context and configure it
     let encoder_ctx = ffmpeg::codec::context::Context::new_with_codec(codec);
@@ -966,7 +1159,7 @@ pub(crate) fn attempt_export(
     // Set codec-specific options
     let mut opts = ffmpeg::Dictionary::new();
     // For WebM (VP9), we override with VP9-specific options regardless of the encoder enum.
-    if request.container_format == ExportContainerFormat::WebM {
+    if container == ExportContainerFormat::WebM {
         opts.set("cpu-used", "2"); // Balanced speed/quality (0=best, 5=fastest)
         opts.set("deadline", "good"); // Good quality encoding
         opts.set("pix_fmt", "yuv420p");
@@ -993,7 +1186,7 @@ pub(crate) fn attempt_export(
     }
 
     // Open the encoder - this consumes the ffmpeg_video_enc and returns an Encoder
-    let mut opened_video_encoder = ffmpeg_video_enc
+    let opened_video_encoder = ffmpeg_video_enc
         .open_with(opts)
         .context("Failed to open encoder")?;
 
@@ -1008,48 +1201,286 @@ pub(crate) fn attempt_export(
         video_out_stream.index()
     };
 
-    // Add audio stream if present
-    let mut opened_audio_encoder = None;
-    let mut audio_out_time_base = None;
-    let audio_out_idx = if audio_stream_idx.is_some() {
-        let (audio_codec_id, codec_label) = audio_codec_for_container(request.container_format)
-            .context("No audio codec configured for this container format")?;
-        let audio_codec = ffmpeg::encoder::find(audio_codec_id)
-            .with_context(|| format!("{} encoder not found", codec_label))?;
-
-        let audio_enc_ctx = ffmpeg::codec::context::Context::new_with_codec(audio_codec);
-        let mut audio_encoder = audio_enc_ctx
-            .encoder()
-            .audio()
-            .context("Failed to create audio encoder")?;
-        audio_encoder.set_time_base((1, 48_000));
-        audio_encoder.set_bit_rate((audio_bitrate_kbps.max(48) * 1000) as usize);
-        audio_encoder.set_rate(48000);
-        audio_encoder.set_channel_layout(ffmpeg::channel_layout::ChannelLayout::STEREO);
-        audio_encoder.set_format(ffmpeg::format::Sample::F32(
-            ffmpeg::format::sample::Type::Planar,
+    Ok((opened_video_encoder, video_out_idx))
+}
+
+/// A [`VideoRetimeGraph`] for the current range and the frame it pulls into.
+struct RetimeStage {
+    graph: VideoRetimeGraph,
+    frame: ffmpeg::frame::Video,
+}
+
+/// Video encoder of an export attempt and the per-frame stages in front of it.
+///
+/// Every frame goes through the same path: the range's retime graph when it plays at
+/// another speed, then the output pts stamp, the overlays, the post-processing graph and
+/// the encoder, whose packets are written to the output stream.
+struct ExportVideoEncodeState {
+    encoder: ffmpeg::encoder::Video,
+    out_idx: usize,
+    out_time_base: ffmpeg::Rational,
+    default_duration: i64,
+    ticks_per_frame: i64,
+    secs_per_tick: f64,
+    overlay_graph: Option<OverlayFilterGraph>,
+    post_process_graph: Option<PostProcessFilterGraph>,
+    /// Set for ranges at another speed; puts their frames on the output frame grid.
+    retime: Option<RetimeStage>,
+    /// The next frame starts a range and is encoded as a keyframe.
+    force_keyframe: bool,
+    next_frame_pts: i64,
+    next_pts: i64,
+    next_dts: i64,
+}
+
+impl ExportVideoEncodeState {
+    fn new(
+        encoder: ffmpeg::encoder::Video,
+        out_idx: usize,
+        out_time_base: ffmpeg::Rational,
+        output_fps: f64,
+        overlay_graph: Option<OverlayFilterGraph>,
+        post_process_graph: Option<PostProcessFilterGraph>,
+    ) -> Self {
+        let secs_per_tick = time_base_to_secs_per_tick(encoder.time_base())
+            .unwrap_or_else(|| 1.0 / output_fps.max(1.0));
+        let ticks_per_frame = ((1.0 / output_fps) / secs_per_tick).round().max(1.0) as i64;
+        let out_ticks_per_second =
+            f64::from(out_time_base.denominator()) / f64::from(out_time_base.numerator().max(1));
+        let default_duration = (out_ticks_per_second / output_fps).round().max(1.0) as i64;
+        Self {
+            encoder,
+            out_idx,
+            out_time_base,
+            default_duration,
+            ticks_per_frame,
+            secs_per_tick,
+            overlay_graph,
+            post_process_graph,
+            retime: None,
+            force_keyframe: false,
+            next_frame_pts: 0,
+            next_pts: 0,
+            next_dts: 0,
+        }
+    }
+
+    /// Starts a range; `retime` is its graph when it plays at another speed.
+    fn start_range(&mut self, retime: Option<VideoRetimeGraph>) {
+        self.retime = retime.map(|graph| RetimeStage {
+            graph,
+            frame: ffmpeg::frame::Video::empty(),
+        });
+        // Force a keyframe at the start of each range so the output is decodable across
+        // range boundaries.
+        self.force_keyframe = true;
+    }
+
+    /// Whether a decoded frame at `output_pts_secs` is needed. Without a retime graph, frames
+    /// before the next output frame slot are dropped (before they are scaled).
+    fn wants_frame(&self, output_pts_secs: f64) -> bool {
+        self.retime.is_some()
+            || output_pts_secs + 0.000_5 >= self.next_frame_pts as f64 * self.secs_per_tick
+    }
+
+    /// Encodes a scaled frame that is `range_offset_secs` into its source range.
+    fn encode_frame(
+        &mut self,
+        frame: &mut ffmpeg::frame::Video,
+        range_offset_secs: f64,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        let Some(mut retime) = self.retime.take() else {
+            return self.encode_output_frame(frame, output_ctx);
+        };
+        frame.set_pts(Some(
+            (range_offset_secs * f64::from(VIDEO_RETIME_TIME_BASE)) as i64,
         ));
-        let opened = audio_encoder
-            .open()
-            .context("Failed to open audio encoder")?;
-
-        let stream_codec = ffmpeg::encoder::find(audio_codec_id)
-            .context("Failed to find audio codec for output stream")?;
-        let (audio_idx_out, audio_tb_out) = {
-            let mut audio_out_stream = output_ctx
-                .add_stream(stream_codec)
-                .context("Failed to add audio stream")?;
-            audio_out_stream.set_time_base((1, 48_000));
-            audio_out_stream.set_parameters(&opened);
-            (audio_out_stream.index(), audio_out_stream.time_base())
+        retime.graph.push(Some(&*frame))?;
+        while retime.graph.pull(&mut retime.frame) {
+            self.encode_output_frame(&mut retime.frame, output_ctx)?;
+        }
+        self.retime = Some(retime);
+        Ok(())
+    }
+
+    /// Ends the current range; the retime graph holds the tail of a retimed range until it
+    /// is flushed.
+    fn finish_range(&mut self, output_ctx: &mut ffmpeg::format::context::Output) -> Result<()> {
+        let Some(mut retime) = self.retime.take() else {
+            return Ok(());
         };
+        retime.graph.push(None)?;
+        while retime.graph.pull(&mut retime.frame) {
+            self.encode_output_frame(&mut retime.frame, output_ctx)?;
+        }
+        Ok(())
+    }
 
-        opened_audio_encoder = Some(opened);
-        audio_out_time_base = Some(audio_tb_out);
-        Some(audio_idx_out)
-    } else {
-        None
-    };
+    /// Drains the post-processing graph and the encoder after the last range.
+    fn finish(&mut self, output_ctx: &mut ffmpeg::format::context::Output) -> Result<()> {
+        // Temporal filters hold back frames across ranges; the post-processing graph is only
+        // flushed here, once.
+        if let Some(mut filter_graph) = self.post_process_graph.take() {
+            if let Err(err) = filter_graph.flush(|filtered| {
+                filtered.set_pts(Some(self.next_frame_pts));
+                self.next_frame_pts = self.next_frame_pts.saturating_add(self.ticks_per_frame);
+                self.encoder.send_frame(filtered)?;
+                self.write_packets(output_ctx)
+            }) {
+                warn!("Failed to flush post-processing filter graph: {err:#}");
+            }
+        }
+
+        self.encoder.send_eof()?;
+        self.write_packets(output_ctx)
+    }
+
+    /// Stamps `frame` with the next output frame pts, draws the overlays, runs it through
+    /// the post-processing graph when there is one, and encodes it.
+    fn encode_output_frame(
+        &mut self,
+        frame: &mut ffmpeg::frame::Video,
+        output_ctx: &mut ffmpeg::format::context::Output,
+    ) -> Result<()> {
+        frame.set_pts(Some(self.next_frame_pts));
+        let frame = match self.overlay_graph.as_mut() {
+            Some(overlay_graph) => overlay_graph.apply(frame)?,
+            None => frame,
+        };
+        if std::mem::take(&mut self.force_keyframe) {
+            unsafe {
+                (*frame.as_mut_ptr()).pict_type = ffmpeg::picture::Type::I.into();
+                (*frame.as_mut_ptr()).key_frame = 1;
+            }
+        }
+
+        // Temporal filters (e.g. hqdn3d) may output zero or more frames per push, some of
+        // them held back from earlier frames or ranges; each is stamped as it comes out.
+        match self.post_process_graph.as_mut() {
+            Some(filter_graph) => {
+                let encoder = &mut self.encoder;
+                let next_frame_pts = &mut self.next_frame_pts;
+                let ticks_per_frame = self.ticks_per_frame;
+                filter_graph.process_into(frame, |filtered| {
+                    filtered.set_pts(Some(*next_frame_pts));
+                    *next_frame_pts = next_frame_pts.saturating_add(ticks_per_frame);
+                    encoder.send_frame(filtered)?;
+                    Ok(())
+                })?;
+            }
+            None => {
+                self.next_frame_pts = self.next_frame_pts.saturating_add(self.ticks_per_frame);
+                self.encoder.send_frame(frame)?;
+            }
+        }
+        self.write_packets(output_ctx)
+    }
+
+    /// Writes the packets the encoder has ready, keeping timestamps monotonic.
+    fn write_packets(&mut self, output_ctx: &mut ffmpeg::format::context::Output) -> Result<()> {
+        let mut pkt = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut pkt).is_ok() {
+            pkt.rescale_ts(self.encoder.time_base(), self.out_time_base);
+            if pkt.pts().is_none() {
+                pkt.set_pts(Some(self.next_pts));
+            }
+            if pkt.dts().is_none() {
+                pkt.set_dts(Some(self.next_dts));
+            }
+            pkt.set_duration(self.default_duration);
+            let fixed_dts = pkt.dts().unwrap_or(self.next_dts).max(self.next_dts);
+            let fixed_pts = pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
+            pkt.set_dts(Some(fixed_dts));
+            pkt.set_pts(Some(fixed_pts));
+            pkt.set_stream(self.out_idx);
+            pkt.write_interleaved(output_ctx)?;
+            self.next_dts = fixed_dts.saturating_add(pkt.duration().max(1));
+            self.next_pts = fixed_pts.saturating_add(pkt.duration().max(1));
+        }
+        Ok(())
+    }
+}
+
+/// Run export attempt using ffmpeg-next filter graphs.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn attempt_export(
+    request: &ClipExportRequest,
+    output_path: &Path,
+    video_bitrate_kbps: u32,
+    audio_bitrate_kbps: u32,
+    video_encoder: ExportVideoEncoder,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+    attempt_index: usize,
+    attempt_count: usize,
+    single_pass_phase: ClipExportPhase,
+) -> Result<Option<ExportAttemptResult>> {
+    let total_duration_secs = request.output_duration_secs().max(0.1);
+
+    let _ = progress_tx.send(ClipExportUpdate::Progress {
+        phase: ClipExportPhase::Preparing,
+        fraction: 0.0,
+        message: "Preparing export".to_string(),
+    });
+
+    info!(
+        "Exporting clipped video {:?} -> {:?} ({} kept ranges, target={} MB, video bitrate={} kbps, codec={}, container={})",
+        request.input_path,
+        output_path,
+        request.keep_ranges.len(),
+        request.target_size_mb,
+        video_bitrate_kbps,
+        video_encoder.ffmpeg_name_for_container(request.container_format),
+        request.container_format.short_label(),
+    );
+
+    // Open input
+    let mut input_ctx = ffmpeg::format::input(&request.input_path)
+        .with_context(|| format!("Failed to open input: {:?}", request.input_path))?;
+
+    // Find the video stream (audio streams are picked by ExportAudio)
+    let video_stream_idx = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .map(|s| s.index())
+        .context("No video stream found")?;
+
+    let video_stream = input_ctx
+        .stream(video_stream_idx)
+        .context("Missing video stream")?;
+    let input_time_base = video_stream.time_base();
+
+    // Create output
+    let mut output_ctx = ffmpeg::format::output(output_path)
+        .with_context(|| format!("Failed to create output: {:?}", output_path))?;
+
+    let encoder_pixel_format =
+        export_encoder_pixel_format_for_container(video_encoder, request.container_format);
+
+    let output_width = request.output_width.unwrap_or(request.metadata.width);
+    let output_height = request.output_height.unwrap_or(request.metadata.height);
+    let crop = request.crop;
+
+    let output_fps_i32 = super::video_file::normalize_output_fps(
+        request.output_fps.unwrap_or(request.metadata.fps),
+        request.metadata.fps,
+    )
+    .round() as i32;
+
+    let (opened_video_encoder, video_out_idx) = open_export_video_encoder(
+        &mut output_ctx,
+        request.container_format,
+        video_encoder,
+        output_width,
+        output_height,
+        output_fps_i32,
+        video_bitrate_kbps,
+    )?;
+
+    // Add audio streams if present
+    let mut export_audio =
+        ExportAudio::new(&input_ctx, &mut output_ctx, request, audio_bitrate_kbps)?;
 
     // Write header with container-appropriate muxer options
     let header_opts = muxer_header_opts_for_container(request.container_format);
@@ -1063,13 +1494,8 @@ pub(crate) fn attempt_export(
         .stream(video_out_idx)
         .context("Missing output video stream")?
         .time_base();
-    if let Some(audio_idx) = audio_out_idx {
-        audio_out_time_base = Some(
-            output_ctx
-                .stream(audio_idx)
-                .context("Missing output audio stream")?
-                .time_base(),
-        );
+    if let Some(audio) = export_audio.as_mut() {
+        audio.after_header(&output_ctx, request.container_format)?;
     }
 
     // We'll decode only the kept ranges by seeking for each range, instead of decoding the
@@ -1082,33 +1508,6 @@ pub(crate) fn attempt_export(
         request.metadata.fps,
     );
 
-    let video_enc_time_base = opened_video_encoder.time_base();
-    let video_enc_secs_per_tick = time_base_to_secs_per_tick(video_enc_time_base)
-        .unwrap_or_else(|| 1.0 / output_fps.max(1.0));
-    let video_enc_ticks_per_frame = ((1.0 / output_fps) / video_enc_secs_per_tick)
-        .round()
-        .max(1.0) as i64;
-
-    let video_ticks_per_second = f64::from(video_out_time_base.denominator())
-        / f64::from(video_out_time_base.numerator().max(1));
-    let video_default_duration = (video_ticks_per_second / output_fps).round().max(1.0) as i64;
-    let audio_default_duration = if let Some(audio_tb) = audio_out_time_base {
-        let audio_ticks_per_second =
-            f64::from(audio_tb.denominator()) / f64::from(audio_tb.numerator().max(1));
-        let frame_samples = audio_frame_samples_for_container(request.container_format);
-        ((frame_samples as f64) * (audio_ticks_per_second / 48_000.0))
-            .round()
-            .max(1.0) as i64
-    } else {
-        audio_frame_samples_for_container(request.container_format)
-    };
-
-    let mut next_video_pts = 0i64;
-    let mut next_video_dts = 0i64;
-    let mut next_audio_pts = 0i64;
-    let mut next_audio_dts = 0i64;
-    let mut next_video_frame_pts = 0i64;
-
     let start_time = Instant::now();
     let mut last_progress_time = start_time;
     let mut processed_duration: f64 = 0.0;
@@ -1117,7 +1516,6 @@ pub(crate) fn attempt_export(
     let mut decoder_setup_elapsed_secs = 0.0f64;
     let mut scale_elapsed_secs = 0.0f64;
     let mut video_encode_elapsed_secs = 0.0f64;
-    let mut audio_encode_elapsed_secs = 0.0f64;
     let mut seek_elapsed_secs = 0.0f64;
     let mut range_init_overhead_elapsed_secs = 0.0f64;
     let mut range_process_elapsed_secs = 0.0f64;
@@ -1125,11 +1523,10 @@ pub(crate) fn attempt_export(
     let mut decoded_video = ffmpeg::util::frame::video::Video::empty();
     let mut scaled_video =
         ffmpeg::util::frame::video::Video::new(encoder_pixel_format, output_width, output_height);
-    let mut decoded_audio = ffmpeg::util::frame::audio::Audio::empty();
     let mut output_cursor_secs = 0.0f64;
 
     // ── Hoisted resources: created once before the range loop and reused across ranges ──
-    // The video scaler, audio resampler, and post-process filter graph are independent
+    // The video scaler, audio resamplers, and post-process filter graph are independent
     // of per-range decoder state (same pixel format, resolution, and encoder format
     // across all ranges). Only the decoders must be recreated after each seek.
     let (src_w, src_h) = {
@@ -1205,31 +1602,29 @@ pub(crate) fn attempt_export(
         );
     }
 
-    // Hoist audio resampler: sample format/rate/channels are constant across ranges.
-    let mut audio_resampler: Option<ffmpeg::software::resampling::context::Context> = None;
-    if let Some(audio_idx) = audio_stream_idx {
-        let a_stream = input_ctx.stream(audio_idx).with_context(|| {
-            format!("missing audio stream {} for resampler creation", audio_idx)
-        })?;
-        let a_ctx = ffmpeg::codec::context::Context::from_parameters(a_stream.parameters())?;
-        let temp_decoder = a_ctx.decoder().audio()?;
-        if let Some(ref encoder) = opened_audio_encoder {
-            let resampler = ffmpeg::software::resampling::Context::get(
-                temp_decoder.format(),
-                temp_decoder.channel_layout(),
-                temp_decoder.rate(),
-                encoder.format(),
-                encoder.channel_layout(),
-                encoder.rate(),
-            )?;
-            audio_resampler = Some(resampler);
-        }
-    }
+    // Overlays are drawn after scaling, on the output frame grid, so they land in the same
+    // place and at the same output time in every range.
+    let overlay_graph = OverlayFilterGraph::new(
+        &request.overlays,
+        output_width,
+        output_height,
+        output_fps_i32,
+        encoder_pixel_format,
+    )?;
+    let mut video_encode = ExportVideoEncodeState::new(
+        opened_video_encoder,
+        video_out_idx,
+        video_out_time_base,
+        output_fps,
+        overlay_graph,
+        post_process_graph,
+    );
 
     for range in &request.keep_ranges {
         let range_started_at = Instant::now();
         let range_output_start_secs = output_cursor_secs;
-        output_cursor_secs += range.duration_secs();
+        output_cursor_secs += range.output_duration_secs();
+        let speed = range.effective_speed();
 
         if cancel_flag.load(Ordering::Relaxed) {
             return Ok(None);
@@ -1248,27 +1643,33 @@ pub(crate) fn attempt_export(
         let v_ctx = ffmpeg::codec::context::Context::from_parameters(v_stream.parameters())?;
         let mut video_decoder = v_ctx.decoder().video()?;
 
-        let mut audio_decoder = None;
-        if let Some(audio_idx) = audio_stream_idx {
-            let a_stream = input_ctx
-                .stream(audio_idx)
-                .with_context(|| format!("missing audio stream {} after seek", audio_idx))?;
-            let a_ctx = ffmpeg::codec::context::Context::from_parameters(a_stream.parameters())?;
-            audio_decoder = Some(a_ctx.decoder().audio()?);
+        if let Some(audio) = export_audio.as_mut() {
+            audio.start_range(&input_ctx, range)?;
         }
+        // Ranges at another speed go through their own retime graph, which puts the frames
+        // on the output frame grid itself.
+        let video_retime = if range.is_retimed() {
+            Some(VideoRetimeGraph::new(
+                speed,
+                output_fps,
+                output_width,
+                output_height,
+                encoder_pixel_format,
+            )?)
+        } else {
+            None
+        };
+        video_encode.start_range(video_retime);
         decoder_setup_elapsed_secs += decoder_setup_started_at.elapsed().as_secs_f64();
 
         range_init_overhead_elapsed_secs += range_started_at.elapsed().as_secs_f64();
 
         let mut stop_video = false;
-        let mut stop_audio = audio_stream_idx.is_none();
 
         // Track whether we've found the first keyframe for this range.
         // After seeking, FFmpeg may land on a P/B frame whose reference frames
         // are outside the kept range. We must skip frames until we hit a keyframe.
         let mut found_first_keyframe = false;
-        // Track if the first frame of this range has been encoded (to force a keyframe).
-        let mut first_frame_encoded = false;
 
         // Read packets until we're safely beyond the range end, then flush decoders.
         for (stream, packet) in input_ctx.packets() {
@@ -1313,11 +1714,10 @@ pub(crate) fn attempt_export(
                         continue;
                     }
 
-                    let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs);
+                    let output_pts_secs =
+                        range_output_start_secs + (pts_secs - range.start_secs) / speed;
 
-                    let next_frame_time_secs =
-                        (next_video_frame_pts as f64) * video_enc_secs_per_tick;
-                    if output_pts_secs + 0.000_5 < next_frame_time_secs {
+                    if !video_encode.wants_frame(output_pts_secs) {
                         continue;
                     }
 
@@ -1325,158 +1725,35 @@ pub(crate) fn attempt_export(
                     scale_with_crop(&mut video_scaler, &decoded_video, &mut scaled_video, crop)
                         .context("Failed to scale video frame during export")?;
                     scale_elapsed_secs += scale_started_at.elapsed().as_secs_f64();
-                    scaled_video.set_pts(Some(next_video_frame_pts));
-                    next_video_frame_pts =
-                        next_video_frame_pts.saturating_add(video_enc_ticks_per_frame.max(1));
-
-                    // Force a keyframe at the start of each range to ensure
-                    // the output is decodable when concatenating segments.
-                    // This prevents artifacts at segment boundaries.
-                    if !first_frame_encoded {
-                        unsafe {
-                            (*scaled_video.as_mut_ptr()).pict_type =
-                                ffmpeg::picture::Type::I.into();
-                            (*scaled_video.as_mut_ptr()).key_frame = 1;
-                        }
-                        first_frame_encoded = true;
-                    }
 
                     let video_encode_started_at = Instant::now();
-
-                    // Apply post-processing filters if enabled, otherwise encode
-                    // the scaled frame directly.  process_into() drains all frames
-                    // the filter makes available (temporal filters may output 0 or
-                    // more frames per push), so no frames are ever silently lost.
-                    // We override PTS on each output frame to handle any buffered
-                    // frames from temporal filters (e.g. hqdn3d) that may carry
-                    // stale timestamps from a previous range.
-                    if let Some(ref mut filter_graph) = post_process_graph {
-                        filter_graph.process_into(&scaled_video, |filtered| {
-                            filtered.set_pts(Some(next_video_frame_pts));
-                            next_video_frame_pts = next_video_frame_pts
-                                .saturating_add(video_enc_ticks_per_frame.max(1));
-                            opened_video_encoder.send_frame(filtered)?;
-                            Ok(())
-                        })?;
-                    } else {
-                        opened_video_encoder.send_frame(&scaled_video)?;
-                    }
-
-                    let mut pkt = ffmpeg::Packet::empty();
-                    while opened_video_encoder.receive_packet(&mut pkt).is_ok() {
-                        pkt.rescale_ts(opened_video_encoder.time_base(), video_out_time_base);
-                        if pkt.pts().is_none() {
-                            pkt.set_pts(Some(next_video_pts));
-                        }
-                        if pkt.dts().is_none() {
-                            pkt.set_dts(Some(next_video_dts));
-                        }
-                        pkt.set_duration(video_default_duration);
-                        let fixed_dts = pkt.dts().unwrap_or(next_video_dts).max(next_video_dts);
-                        let fixed_pts = pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-                        pkt.set_dts(Some(fixed_dts));
-                        pkt.set_pts(Some(fixed_pts));
-                        pkt.set_stream(video_out_idx);
-                        pkt.write_interleaved(&mut output_ctx)?;
-                        next_video_dts = fixed_dts.saturating_add(pkt.duration().max(1));
-                        next_video_pts = fixed_pts.saturating_add(pkt.duration().max(1));
-                    }
+                    video_encode.encode_frame(
+                        &mut scaled_video,
+                        pts_secs - range.start_secs,
+                        &mut output_ctx,
+                    )?;
                     video_encode_elapsed_secs += video_encode_started_at.elapsed().as_secs_f64();
 
                     processed_duration = processed_duration.max(output_pts_secs);
                 }
-            } else if Some(stream_idx) == audio_stream_idx {
-                if let Some(pts) = packet.pts() {
-                    let audio_tb = audio_input_time_base.unwrap_or(input_time_base);
-                    let pts_secs = pts as f64 * f64::from(audio_tb.numerator())
-                        / f64::from(audio_tb.denominator());
-                    if pts_secs > range.end_secs + 0.5 {
-                        stop_audio = true;
-                    }
-                }
-
-                if let Some(ref mut decoder) = audio_decoder {
-                    decoder.send_packet(&packet)?;
-                    while decoder.receive_frame(&mut decoded_audio).is_ok() {
-                        let audio_tb = audio_input_time_base.unwrap_or(input_time_base);
-                        let pts_secs = decoded_audio
-                            .timestamp()
-                            .map(|ts| {
-                                ts as f64 * f64::from(audio_tb.numerator())
-                                    / f64::from(audio_tb.denominator())
-                            })
-                            .unwrap_or(0.0);
-
-                        if pts_secs >= range.end_secs {
-                            continue;
-                        }
-                        if pts_secs < range.start_secs {
-                            continue;
-                        }
-
-                        let output_pts_secs =
-                            range_output_start_secs + (pts_secs - range.start_secs);
-
-                        if let Some(ref encoder) = opened_audio_encoder {
-                            let output_time_base = encoder.time_base();
-                            let output_pts = (output_pts_secs
-                                * f64::from(output_time_base.denominator())
-                                / f64::from(output_time_base.numerator()))
-                                as i64;
-                            decoded_audio.set_pts(Some(output_pts));
-                        }
-
-                        if let Some(ref mut encoder) = opened_audio_encoder {
-                            let audio_encode_started_at = Instant::now();
-                            if let Some(ref mut resampler) = audio_resampler {
-                                let mut resampled = ffmpeg::util::frame::audio::Audio::empty();
-                                resampler.run(&decoded_audio, &mut resampled)?;
-                                resampled.set_pts(decoded_audio.pts());
-                                encoder.send_frame(&resampled)?;
-                            } else {
-                                encoder.send_frame(&decoded_audio)?;
-                            }
-                            let mut audio_pkt = ffmpeg::Packet::empty();
-                            while encoder.receive_packet(&mut audio_pkt).is_ok() {
-                                if let (Some(audio_idx), Some(audio_tb)) =
-                                    (audio_out_idx, audio_out_time_base)
-                                {
-                                    audio_pkt.rescale_ts(encoder.time_base(), audio_tb);
-                                    if audio_pkt.pts().is_none() {
-                                        audio_pkt.set_pts(Some(next_audio_pts));
-                                    }
-                                    if audio_pkt.dts().is_none() {
-                                        audio_pkt.set_dts(Some(next_audio_dts));
-                                    }
-                                    if audio_pkt.duration() <= 0
-                                        || audio_pkt.duration() == INVALID_DURATION
-                                    {
-                                        audio_pkt.set_duration(audio_default_duration);
-                                    }
-                                    let fixed_dts = audio_pkt
-                                        .dts()
-                                        .unwrap_or(next_audio_dts)
-                                        .max(next_audio_dts);
-                                    let fixed_pts =
-                                        audio_pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-                                    audio_pkt.set_dts(Some(fixed_dts));
-                                    audio_pkt.set_pts(Some(fixed_pts));
-                                    audio_pkt.set_stream(audio_idx);
-                                    audio_pkt.write_interleaved(&mut output_ctx)?;
-                                    next_audio_dts =
-                                        fixed_dts.saturating_add(audio_pkt.duration().max(1));
-                                    next_audio_pts =
-                                        fixed_pts.saturating_add(audio_pkt.duration().max(1));
-                                }
-                            }
-                            audio_encode_elapsed_secs +=
-                                audio_encode_started_at.elapsed().as_secs_f64();
-                        }
-                    }
-                }
+            } else if let Some(audio) = export_audio
+                .as_mut()
+                .filter(|audio| audio.handles(stream_idx))
+            {
+                audio.send_packet(
+                    stream_idx,
+                    &packet,
+                    range,
+                    range_output_start_secs,
+                    &mut output_ctx,
+                )?;
             }
 
-            if stop_video && stop_audio {
+            if stop_video
+                && export_audio
+                    .as_ref()
+                    .map_or(true, ExportAudio::past_range_end)
+            {
                 break;
             }
 
@@ -1518,53 +1795,31 @@ pub(crate) fn attempt_export(
             if !(pts_secs >= range.start_secs && pts_secs < range.end_secs) {
                 continue;
             }
-            let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs);
+            let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs) / speed;
 
-            let next_frame_time_secs = (next_video_frame_pts as f64) * video_enc_secs_per_tick;
-            if output_pts_secs + 0.000_5 < next_frame_time_secs {
+            if !video_encode.wants_frame(output_pts_secs) {
                 continue;
             }
             let scale_started_at = Instant::now();
             scale_with_crop(&mut video_scaler, &decoded_video, &mut scaled_video, crop)
                 .context("Failed to scale video frame during export")?;
             scale_elapsed_secs += scale_started_at.elapsed().as_secs_f64();
-            scaled_video.set_pts(Some(next_video_frame_pts));
-            next_video_frame_pts =
-                next_video_frame_pts.saturating_add(video_enc_ticks_per_frame.max(1));
             let video_encode_started_at = Instant::now();
-            if let Some(ref mut filter_graph) = post_process_graph {
-                filter_graph
-                    .process_into(&scaled_video, |filtered| {
-                        opened_video_encoder.send_frame(filtered)?;
-                        Ok(())
-                    })
-                    .context("Post-processing filter failed during flush")?;
-            } else {
-                opened_video_encoder.send_frame(&scaled_video)?;
-            }
-            let mut pkt = ffmpeg::Packet::empty();
-            while opened_video_encoder.receive_packet(&mut pkt).is_ok() {
-                pkt.rescale_ts(opened_video_encoder.time_base(), video_out_time_base);
-                if pkt.pts().is_none() {
-                    pkt.set_pts(Some(next_video_pts));
-                }
-                if pkt.dts().is_none() {
-                    pkt.set_dts(Some(next_video_dts));
-                }
-                pkt.set_duration(video_default_duration);
-                let fixed_dts = pkt.dts().unwrap_or(next_video_dts).max(next_video_dts);
-                let fixed_pts = pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-                pkt.set_dts(Some(fixed_dts));
-                pkt.set_pts(Some(fixed_pts));
-                pkt.set_stream(video_out_idx);
-                pkt.write_interleaved(&mut output_ctx)?;
-                next_video_dts = fixed_dts.saturating_add(pkt.duration().max(1));
-                next_video_pts = fixed_pts.saturating_add(pkt.duration().max(1));
-            }
+            video_encode
+                .encode_frame(
+                    &mut scaled_video,
+                    pts_secs - range.start_secs,
+                    &mut output_ctx,
+                )
+                .context("Failed to encode video frame during flush")?;
             video_encode_elapsed_secs += video_encode_started_at.elapsed().as_secs_f64();
             processed_duration = processed_duration.max(output_pts_secs);
         }
 
+        let video_encode_started_at = Instant::now();
+        video_encode.finish_range(&mut output_ctx)?;
+        video_encode_elapsed_secs += video_encode_started_at.elapsed().as_secs_f64();
+
         // Note: The post-processing filter graph is NOT flushed at range boundaries.
         // Flushing would send EOF to the filter source, preventing reuse across ranges.
         // Instead, temporal filter state from the previous range may influence the first
@@ -1572,70 +1827,8 @@ pub(crate) fn attempt_export(
         // limited to one frame per boundary and is visually negligible for post-processing.
         // A final flush occurs after all ranges are processed (see below).
 
-        if let Some(ref mut decoder) = audio_decoder {
-            decoder.send_eof()?;
-            while decoder.receive_frame(&mut decoded_audio).is_ok() {
-                let audio_tb = audio_input_time_base.unwrap_or(input_time_base);
-                let pts_secs = decoded_audio
-                    .timestamp()
-                    .map(|ts| {
-                        ts as f64 * f64::from(audio_tb.numerator())
-                            / f64::from(audio_tb.denominator())
-                    })
-                    .unwrap_or(0.0);
-                if !(pts_secs >= range.start_secs && pts_secs < range.end_secs) {
-                    continue;
-                }
-                let output_pts_secs = range_output_start_secs + (pts_secs - range.start_secs);
-                if let Some(ref encoder) = opened_audio_encoder {
-                    let output_time_base = encoder.time_base();
-                    let output_pts = (output_pts_secs * f64::from(output_time_base.denominator())
-                        / f64::from(output_time_base.numerator()))
-                        as i64;
-                    decoded_audio.set_pts(Some(output_pts));
-                }
-                if let Some(ref mut encoder) = opened_audio_encoder {
-                    let audio_encode_started_at = Instant::now();
-                    if let Some(ref mut resampler) = audio_resampler {
-                        let mut resampled = ffmpeg::util::frame::audio::Audio::empty();
-                        resampler.run(&decoded_audio, &mut resampled)?;
-                        resampled.set_pts(decoded_audio.pts());
-                        encoder.send_frame(&resampled)?;
-                    } else {
-                        encoder.send_frame(&decoded_audio)?;
-                    }
-                    let mut audio_pkt = ffmpeg::Packet::empty();
-                    while encoder.receive_packet(&mut audio_pkt).is_ok() {
-                        if let (Some(audio_idx), Some(audio_tb)) =
-                            (audio_out_idx, audio_out_time_base)
-                        {
-                            audio_pkt.rescale_ts(encoder.time_base(), audio_tb);
-                            if audio_pkt.pts().is_none() {
-                                audio_pkt.set_pts(Some(next_audio_pts));
-                            }
-                            if audio_pkt.dts().is_none() {
-                                audio_pkt.set_dts(Some(next_audio_dts));
-                            }
-                            if audio_pkt.duration() <= 0 || audio_pkt.duration() == INVALID_DURATION
-                            {
-                                audio_pkt.set_duration(audio_default_duration);
-                            }
-                            let fixed_dts = audio_pkt
-                                .dts()
-                                .unwrap_or(next_audio_dts)
-                                .max(next_audio_dts);
-                            let fixed_pts = audio_pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-                            audio_pkt.set_dts(Some(fixed_dts));
-                            audio_pkt.set_pts(Some(fixed_pts));
-                            audio_pkt.set_stream(audio_idx);
-                            audio_pkt.write_interleaved(&mut output_ctx)?;
-                            next_audio_dts = fixed_dts.saturating_add(audio_pkt.duration().max(1));
-                            next_audio_pts = fixed_pts.saturating_add(audio_pkt.duration().max(1));
-                        }
-                    }
-                    audio_encode_elapsed_secs += audio_encode_started_at.elapsed().as_secs_f64();
-                }
-            }
+        if let Some(audio) = export_audio.as_mut() {
+            audio.finish_range(range, range_output_start_secs, &mut output_ctx)?;
         }
         let range_elapsed_secs = range_started_at.elapsed().as_secs_f64();
         range_process_elapsed_secs += range_elapsed_secs;
@@ -1643,99 +1836,20 @@ pub(crate) fn attempt_export(
             range_start_secs = range.start_secs,
             range_end_secs = range.end_secs,
             range_duration_secs = range.duration_secs(),
+            range_speed = speed,
             range_elapsed_secs,
             "Export range processing completed"
         );
     }
 
-    // Final flush of the post-processing filter graph (if any) to drain any
-    // remaining buffered frames from temporal filters across all ranges.
-    if let Some(ref mut filter_graph) = post_process_graph {
-        if let Err(err) = filter_graph.flush(|filtered| {
-            filtered.set_pts(Some(next_video_frame_pts));
-            next_video_frame_pts =
-                next_video_frame_pts.saturating_add(video_enc_ticks_per_frame.max(1));
-            let video_encode_started_at = Instant::now();
-            opened_video_encoder.send_frame(filtered)?;
-            let mut pkt = ffmpeg::Packet::empty();
-            while opened_video_encoder.receive_packet(&mut pkt).is_ok() {
-                pkt.rescale_ts(opened_video_encoder.time_base(), video_out_time_base);
-                if pkt.pts().is_none() {
-                    pkt.set_pts(Some(next_video_pts));
-                }
-                if pkt.dts().is_none() {
-                    pkt.set_dts(Some(next_video_dts));
-                }
-                if pkt.duration() <= 0 || pkt.duration() == INVALID_DURATION {
-                    pkt.set_duration(video_default_duration);
-                }
-                let fixed_dts = pkt.dts().unwrap_or(next_video_dts).max(next_video_dts);
-                let fixed_pts = pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-                pkt.set_dts(Some(fixed_dts));
-                pkt.set_pts(Some(fixed_pts));
-                pkt.set_stream(video_out_idx);
-                pkt.write_interleaved(&mut output_ctx)?;
-                next_video_dts = fixed_dts.saturating_add(pkt.duration().max(1));
-                next_video_pts = fixed_pts.saturating_add(pkt.duration().max(1));
-            }
-            video_encode_elapsed_secs += video_encode_started_at.elapsed().as_secs_f64();
-            Ok(())
-        }) {
-            warn!("Failed to flush post-processing filter graph: {err:#}");
-        }
-    }
-
-    // Flush audio encoder
-    if let Some(ref mut encoder) = opened_audio_encoder {
-        encoder.send_eof()?;
-        let mut audio_pkt = ffmpeg::Packet::empty();
-        while encoder.receive_packet(&mut audio_pkt).is_ok() {
-            if let (Some(audio_idx), Some(audio_tb)) = (audio_out_idx, audio_out_time_base) {
-                audio_pkt.rescale_ts(encoder.time_base(), audio_tb);
-                if audio_pkt.pts().is_none() {
-                    audio_pkt.set_pts(Some(next_audio_pts));
-                }
-                if audio_pkt.dts().is_none() {
-                    audio_pkt.set_dts(Some(next_audio_dts));
-                }
-                if audio_pkt.duration() <= 0 || audio_pkt.duration() == INVALID_DURATION {
-                    audio_pkt.set_duration(audio_default_duration);
-                }
-                let fixed_dts = audio_pkt
-                    .dts()
-                    .unwrap_or(next_audio_dts)
-                    .max(next_audio_dts);
-                let fixed_pts = audio_pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-                audio_pkt.set_dts(Some(fixed_dts));
-                audio_pkt.set_pts(Some(fixed_pts));
-                audio_pkt.set_stream(audio_idx);
-                audio_pkt.write_interleaved(&mut output_ctx)?;
-                next_audio_dts = fixed_dts.saturating_add(audio_pkt.duration().max(1));
-                next_audio_pts = fixed_pts.saturating_add(audio_pkt.duration().max(1));
-            }
-        }
-    }
+    // Drain the post-processing graph and the video encoder.
+    let video_encode_started_at = Instant::now();
+    video_encode.finish(&mut output_ctx)?;
+    video_encode_elapsed_secs += video_encode_started_at.elapsed().as_secs_f64();
 
-    // Flush video encoder
-    opened_video_encoder.send_eof()?;
-    let mut pkt = ffmpeg::Packet::empty();
-    while opened_video_encoder.receive_packet(&mut pkt).is_ok() {
-        pkt.rescale_ts(opened_video_encoder.time_base(), video_out_time_base);
-        if pkt.pts().is_none() {
-            pkt.set_pts(Some(next_video_pts));
-        }
-        if pkt.dts().is_none() {
-            pkt.set_dts(Some(next_video_dts));
-        }
-        pkt.set_duration(video_default_duration);
-        let fixed_dts = pkt.dts().unwrap_or(next_video_dts).max(next_video_dts);
-        let fixed_pts = pkt.pts().unwrap_or(fixed_dts).max(fixed_dts);
-        pkt.set_dts(Some(fixed_dts));
-        pkt.set_pts(Some(fixed_pts));
-        pkt.set_stream(video_out_idx);
-        pkt.write_interleaved(&mut output_ctx)?;
-        next_video_dts = fixed_dts.saturating_add(pkt.duration().max(1));
-        next_video_pts = fixed_pts.saturating_add(pkt.duration().max(1));
+    // Flush the audio mixer and encoders
+    if let Some(audio) = export_audio.as_mut() {
+        audio.finish(&mut output_ctx)?;
     }
 
     output_ctx
@@ -1749,14 +1863,17 @@ pub(crate) fn attempt_export(
         range_process_elapsed_secs,
         scale_elapsed_secs,
         video_encode_elapsed_secs,
-        audio_encode_elapsed_secs,
+        audio_encode_elapsed_secs = export_audio
+            .as_ref()
+            .map_or(0.0, |audio| audio.encode_elapsed_secs),
         "Export attempt stage timings"
     );
 
     // Explicitly release frame buffers to free memory pools
     drop(decoded_video);
     drop(scaled_video);
-    drop(decoded_audio);
+    drop(video_encode);
+    drop(export_audio);
 
     // Flush input context to release any buffered packets
     unsafe {
@@ -1774,6 +1891,141 @@ pub(crate) fn attempt_export(
     }))
 }
 
+/// Run an audio-only export attempt: the kept ranges of the audio tracks, encoded into one
+/// of the audio-only containers at `audio_bitrate_kbps`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn attempt_audio_export(
+    request: &ClipExportRequest,
+    output_path: &Path,
+    audio_bitrate_kbps: u32,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+    attempt_index: usize,
+    attempt_count: usize,
+    phase: ClipExportPhase,
+) -> Result<Option<ExportAttemptResult>> {
+    let total_duration_secs = request.output_duration_secs().max(0.1);
+
+    let _ = progress_tx.send(ClipExportUpdate::Progress {
+        phase: ClipExportPhase::Preparing,
+        fraction: 0.0,
+        message: "Preparing export".to_string(),
+    });
+
+    info!(
+        "Exporting clip audio {:?} -> {:?} ({} kept ranges, target={} MB, audio bitrate={} kbps, container={})",
+        request.input_path,
+        output_path,
+        request.keep_ranges.len(),
+        request.target_size_mb,
+        audio_bitrate_kbps,
+        request.container_format.short_label(),
+    );
+
+    let mut input_ctx = ffmpeg::format::input(&request.input_path)
+        .with_context(|| format!("Failed to open input: {:?}", request.input_path))?;
+    let mut output_ctx = ffmpeg::format::output(output_path)
+        .with_context(|| format!("Failed to create output: {:?}", output_path))?;
+
+    let mut export_audio =
+        ExportAudio::new(&input_ctx, &mut output_ctx, request, audio_bitrate_kbps)?
+            .context("This clip has no audio to export")?;
+
+    let header_opts = muxer_header_opts_for_container(request.container_format);
+    output_ctx
+        .write_header_with(header_opts)
+        .with_context(|| "Failed to write output header")?;
+    export_audio.after_header(&output_ctx, request.container_format)?;
+
+    let start_time = Instant::now();
+    let mut last_progress_time = start_time;
+    let mut processed_duration: f64 = 0.0;
+    let mut output_cursor_secs = 0.0f64;
+
+    for range in &request.keep_ranges {
+        let range_output_start_secs = output_cursor_secs;
+        output_cursor_secs += range.output_duration_secs();
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        seek_to_seconds(&mut input_ctx, range.start_secs);
+        export_audio.start_range(&input_ctx, range)?;
+
+        for (stream, packet) in input_ctx.packets() {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+
+            let stream_idx = stream.index();
+            if !export_audio.handles(stream_idx) {
+                continue;
+            }
+            export_audio.send_packet(
+                stream_idx,
+                &packet,
+                range,
+                range_output_start_secs,
+                &mut output_ctx,
+            )?;
+            if export_audio.past_range_end() {
+                break;
+            }
+
+            if let (Some(pts), Some(secs_per_tick)) =
+                (packet.pts(), time_base_to_secs_per_tick(stream.time_base()))
+            {
+                let pts_secs = (pts as f64 * secs_per_tick).clamp(range.start_secs, range.end_secs);
+                processed_duration = processed_duration.max(
+                    range_output_start_secs
+                        + (pts_secs - range.start_secs) / range.effective_speed(),
+                );
+            }
+
+            let now = Instant::now();
+            const MIN_PROGRESS_INTERVAL: std::time::Duration =
+                std::time::Duration::from_millis(100);
+            if now.duration_since(last_progress_time) >= MIN_PROGRESS_INTERVAL {
+                last_progress_time = now;
+                let _ = progress_tx.send(ClipExportUpdate::Progress {
+                    phase,
+                    fraction: (processed_duration / total_duration_secs).min(1.0) as f32,
+                    message: format!(
+                        "Attempt {}/{} - {:.1}s processed",
+                        attempt_index + 1,
+                        attempt_count,
+                        processed_duration
+                    ),
+                });
+            }
+        }
+
+        export_audio.finish_range(range, range_output_start_secs, &mut output_ctx)?;
+    }
+
+    export_audio.finish(&mut output_ctx)?;
+    output_ctx
+        .write_trailer()
+        .with_context(|| "Failed to write output trailer")?;
+    info!(
+        elapsed_secs = start_time.elapsed().as_secs_f64(),
+        audio_encode_elapsed_secs = export_audio.encode_elapsed_secs,
+        "Audio export attempt completed"
+    );
+    drop(export_audio);
+
+    let size_bytes = std::fs::metadata(output_path)
+        .with_context(|| format!("Failed to get size of export output file {:?}", output_path))?
+        .len();
+
+    Ok(Some(ExportAttemptResult {
+        output_path: output_path.to_path_buf(),
+        video_bitrate_kbps: 0,
+        size_bytes,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
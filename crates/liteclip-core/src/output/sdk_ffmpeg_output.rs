@@ -122,7 +122,7 @@ pub fn remux_fragmented_mp4(input_path: &Path, output_path: &Path, faststart: bo
     Ok(())
 }
 
-/// Probe duration, resolution, fps, and audio presence using libavformat.
+/// Probe duration, resolution, fps, and audio tracks using libavformat.
 pub fn probe_video_file(path: &Path) -> Result<VideoFileMetadata> {
     let ictx = ffmpeg::format::input(path)
         .with_context(|| format!("failed to open {:?} for probe", path.display()))?;
@@ -152,14 +152,42 @@ pub fn probe_video_file(path: &Path) -> Result<VideoFileMetadata> {
             "Ignoring unreasonable FPS reported by container"
         );
     }
-    let has_audio = ictx.streams().best(ffmpeg::media::Type::Audio).is_some();
+    let audio_track_count = ictx
+        .streams()
+        .filter(|stream| stream.parameters().medium() == ffmpeg::media::Type::Audio)
+        .count();
+    let clip_metadata = super::ClipMetadata::from_tags(ictx.metadata().iter());
+    let mut chapters: Vec<super::ClipChapter> = ictx
+        .chapters()
+        .map(|chapter| {
+            let time_base = chapter.time_base();
+            let start_secs = if time_base.denominator() > 0 {
+                chapter.start() as f64 * f64::from(time_base.numerator())
+                    / f64::from(time_base.denominator())
+            } else {
+                0.0
+            };
+            super::ClipChapter {
+                start_secs,
+                title: chapter
+                    .metadata()
+                    .get("title")
+                    .unwrap_or_default()
+                    .to_string(),
+            }
+        })
+        .collect();
+    chapters.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
 
     Ok(VideoFileMetadata {
         duration_secs,
         width,
         height,
-        has_audio,
+        has_audio: audio_track_count > 0,
+        audio_track_count,
         fps,
+        clip_metadata,
+        chapters,
     })
 }
 
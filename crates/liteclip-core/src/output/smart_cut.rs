@@ -0,0 +1,735 @@
+//! Smart cut: frame-accurate stream copy trims.
+//!
+//! A stream copy can only start at a keyframe. With [`ClipExportRequest::smart_cut`], each
+//! kept range is split by [`plan_smart_cut`] into a stream-copied middle that starts on a
+//! keyframe and re-encoded segments for the partial GOPs at its edges. Re-encoded segments
+//! use libx265 at the source's size, pixel format and colour properties with in-band
+//! parameter sets, rewritten to the source's NAL framing so copied and re-encoded packets
+//! share one HEVC track.
+//!
+//! [`ClipExportRequest::smart_cut`]: super::video_file::ClipExportRequest::smart_cut
+
+use anyhow::{bail, Context, Result};
+use ffmpeg_next as ffmpeg;
+use libc::c_int;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tracing::warn;
+
+use super::sdk_export::{StreamCopyRoute, StreamCopyWriter};
+use super::video_file::TimeRange;
+
+/// Encoder used for the re-encoded segments; it must produce the source's codec.
+const SMART_CUT_ENCODER: &str = "libx265";
+/// Visually lossless quality for the few frames around each cut.
+const SMART_CUT_CRF: &str = "16";
+/// In-band parameter sets on every keyframe; no B-frames and closed GOPs, so a segment
+/// decodes on its own and never references the copied packets after it.
+const SMART_CUT_X265_PARAMS: &str = "repeat-headers=1:bframes=0:open-gop=0:log-level=error";
+
+/// Part of a kept range in a stream copy export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum CutSegment {
+    /// Copy packets in `[start_secs, end_secs)`; video starts at the first keyframe at or
+    /// after `start_secs`.
+    Copy { start_secs: f64, end_secs: f64 },
+    /// Decode video from the keyframe at `decode_from_secs` and re-encode the frames in
+    /// `[start_secs, end_secs)`. Other streams are copied over the same window.
+    Reencode {
+        decode_from_secs: f64,
+        start_secs: f64,
+        end_secs: f64,
+    },
+}
+
+impl CutSegment {
+    pub(super) fn start_secs(self) -> f64 {
+        match self {
+            Self::Copy { start_secs, .. } | Self::Reencode { start_secs, .. } => start_secs,
+        }
+    }
+
+    pub(super) fn end_secs(self) -> f64 {
+        match self {
+            Self::Copy { end_secs, .. } | Self::Reencode { end_secs, .. } => end_secs,
+        }
+    }
+}
+
+/// Splits `range` into re-encoded edges and a stream-copied middle.
+///
+/// `keyframes` are the source's keyframe times in seconds, sorted. Segment boundaries sit half
+/// a frame before each keyframe so a frame lands in exactly one segment even when container
+/// timestamps are rounded. With `reencode_tail`, used for streams that reorder frames, the
+/// last GOP is re-encoded as well, because copying it would keep frames that reference
+/// pictures past the cut.
+pub(super) fn plan_smart_cut(
+    range: TimeRange,
+    keyframes: &[f64],
+    frame_secs: f64,
+    reencode_tail: bool,
+) -> Vec<CutSegment> {
+    let tolerance = frame_secs.max(0.0) / 2.0;
+    let decode_from = |secs: f64| {
+        keyframes
+            .iter()
+            .rev()
+            .find(|&&keyframe| keyframe <= secs + tolerance)
+            .or(keyframes.first())
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let whole_range = CutSegment::Reencode {
+        decode_from_secs: decode_from(range.start_secs),
+        start_secs: range.start_secs,
+        end_secs: range.end_secs,
+    };
+
+    let copy_from = match keyframes
+        .iter()
+        .find(|&&keyframe| keyframe >= range.start_secs - tolerance)
+    {
+        Some(&keyframe) if keyframe < range.end_secs - tolerance => keyframe,
+        _ => return vec![whole_range],
+    };
+    let copy_until = if reencode_tail {
+        let last = keyframes
+            .iter()
+            .rev()
+            .find(|&&keyframe| keyframe < range.end_secs - tolerance)
+            .copied()
+            .unwrap_or(copy_from);
+        if last <= copy_from {
+            return vec![whole_range];
+        }
+        Some(last)
+    } else {
+        None
+    };
+
+    let mut segments = Vec::with_capacity(3);
+    let copy_start = copy_from - tolerance;
+    if copy_from > range.start_secs + tolerance {
+        segments.push(CutSegment::Reencode {
+            decode_from_secs: decode_from(range.start_secs),
+            start_secs: range.start_secs,
+            end_secs: copy_start,
+        });
+    }
+    match copy_until {
+        Some(keyframe) => {
+            segments.push(CutSegment::Copy {
+                start_secs: copy_start,
+                end_secs: keyframe - tolerance,
+            });
+            segments.push(CutSegment::Reencode {
+                decode_from_secs: keyframe,
+                start_secs: keyframe - tolerance,
+                end_secs: range.end_secs,
+            });
+        }
+        None => segments.push(CutSegment::Copy {
+            start_secs: copy_start,
+            end_secs: range.end_secs,
+        }),
+    }
+    segments
+}
+
+/// Video stream facts needed to smart-cut one input.
+pub(super) struct SmartCutSource {
+    pub(super) video_index: usize,
+    /// Keyframe presentation times in seconds, sorted.
+    keyframes: Vec<f64>,
+    frame_secs: f64,
+    fps: f64,
+    /// Whether the stream reorders frames (B-frames).
+    reorders: bool,
+    /// NAL length field size for length-prefixed (`hvcC`) sources; `None` for Annex B.
+    nal_length_size: Option<usize>,
+    /// The source's parameter sets in its own NAL framing, prepended to the first copied
+    /// keyframe after a re-encoded segment so decoders switch back to them.
+    parameter_sets: Vec<u8>,
+}
+
+impl SmartCutSource {
+    /// Checks that `video_index` can be smart-cut and indexes its keyframes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream is not HEVC, libx265 is unavailable, or the source
+    /// extradata cannot be parsed.
+    pub(super) fn probe(
+        input_ctx: &mut ffmpeg::format::context::Input,
+        video_index: usize,
+        fps: f64,
+    ) -> Result<Self> {
+        let stream = input_ctx
+            .stream(video_index)
+            .with_context(|| format!("Missing input stream {}", video_index))?;
+        let parameters = stream.parameters();
+        if parameters.id() != ffmpeg::codec::Id::HEVC {
+            bail!("source video is {:?}, not HEVC", parameters.id());
+        }
+        if ffmpeg::encoder::find_by_name(SMART_CUT_ENCODER).is_none() {
+            bail!("{} encoder is not available", SMART_CUT_ENCODER);
+        }
+
+        // SAFETY: `parameters` borrows the stream's codec parameters, which outlive this
+        // block; extradata is either null or `extradata_size` readable bytes.
+        let (extradata, video_delay) = unsafe {
+            let params = parameters.as_ptr();
+            let extradata = if (*params).extradata.is_null() || (*params).extradata_size <= 0 {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts((*params).extradata, (*params).extradata_size as usize)
+                    .to_vec()
+            };
+            (extradata, (*params).video_delay)
+        };
+        let (nal_length_size, parameter_sets) = if extradata.first() == Some(&1) {
+            let length_size = usize::from(extradata.get(21).context("truncated hvcC")? & 3) + 1;
+            let units = hvcc_parameter_sets(&extradata).context("malformed hvcC")?;
+            (Some(length_size), length_prefixed(&units, length_size))
+        } else {
+            (None, extradata)
+        };
+
+        let reorders = video_delay > 0;
+        // Index entries carry decode times, which match presentation times only when the
+        // stream does not reorder frames.
+        let mut keyframes = if reorders {
+            Vec::new()
+        } else {
+            indexed_keyframe_secs(input_ctx, video_index)
+        };
+        if keyframes.is_empty() {
+            keyframes = scanned_keyframe_secs(input_ctx, video_index);
+        }
+        keyframes.sort_by(f64::total_cmp);
+        keyframes.dedup();
+        if keyframes.is_empty() {
+            bail!("no keyframes found");
+        }
+
+        let fps = fps.max(1.0);
+        Ok(Self {
+            video_index,
+            keyframes,
+            frame_secs: 1.0 / fps,
+            fps,
+            reorders,
+            nal_length_size,
+            parameter_sets,
+        })
+    }
+
+    /// Segments for one kept range; see [`plan_smart_cut`].
+    pub(super) fn plan(&self, range: TimeRange) -> Vec<CutSegment> {
+        plan_smart_cut(range, &self.keyframes, self.frame_secs, self.reorders)
+    }
+
+    /// Copy of `packet` with the source parameter sets in front of its data.
+    pub(super) fn with_parameter_sets(&self, packet: &ffmpeg::Packet) -> ffmpeg::Packet {
+        let data = packet.data().unwrap_or_default();
+        let mut bytes = Vec::with_capacity(self.parameter_sets.len() + data.len());
+        bytes.extend_from_slice(&self.parameter_sets);
+        bytes.extend_from_slice(data);
+        let mut prefixed = ffmpeg::Packet::copy(&bytes);
+        prefixed.set_pts(packet.pts());
+        prefixed.set_dts(packet.dts());
+        prefixed.set_duration(packet.duration());
+        prefixed.set_flags(packet.flags());
+        prefixed
+    }
+}
+
+/// Re-encodes the video frames of one [`CutSegment::Reencode`] and copies the other streams
+/// over the same window.
+///
+/// Source times map to the output as `secs + output_offset_secs`. Frames and packets up to
+/// `keep_until_secs` are kept, which is past `end_secs` only for the last segment of a range.
+/// Returns `false` if the export was cancelled.
+///
+/// # Errors
+///
+/// Returns an error if decoding, encoding or writing fails.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn reencode_segment(
+    input_ctx: &mut ffmpeg::format::context::Input,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    writer: &mut StreamCopyWriter<'_>,
+    routes: &[Option<StreamCopyRoute>],
+    source: &SmartCutSource,
+    segment: CutSegment,
+    keep_until_secs: f64,
+    output_offset_secs: f64,
+    cancel_flag: &Arc<AtomicBool>,
+) -> Result<bool> {
+    let CutSegment::Reencode {
+        decode_from_secs,
+        start_secs,
+        ..
+    } = segment
+    else {
+        return Ok(true);
+    };
+    let video_route = routes
+        .get(source.video_index)
+        .copied()
+        .flatten()
+        .context("Smart cut video stream is not mapped")?;
+    let video_time_base = video_route.in_time_base;
+    let secs_per_video_tick = super::sdk_export::time_base_to_secs_per_tick(video_time_base)
+        .context("Invalid video time base")?;
+
+    // Land on the keyframe at or before the GOP start; half a frame of slack covers
+    // rounded index timestamps.
+    let seek_secs = (decode_from_secs + source.frame_secs / 2.0).max(0.0);
+    let seek_ts = (seek_secs * f64::from(ffmpeg::ffi::AV_TIME_BASE)).round() as i64;
+    input_ctx
+        .seek(seek_ts, ..=seek_ts)
+        .context("Failed to seek for smart cut")?;
+
+    let video_stream = input_ctx
+        .stream(source.video_index)
+        .context("Missing smart cut video stream")?;
+    let decoder_ctx = ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())?;
+    let mut decoder = decoder_ctx.decoder().video()?;
+    let mut encoder = open_segment_encoder(&decoder, video_time_base, source.fps)?;
+
+    let end_grace_secs = 0.75_f64.max(source.frame_secs);
+    let route_count = routes.iter().filter(|r| r.is_some()).count();
+    let mut streams_past_end: Vec<bool> = vec![false; routes.len()];
+    let mut streams_past_end_count = 0usize;
+    let mut decoded = ffmpeg::frame::Video::empty();
+
+    for (stream, packet) in input_ctx.packets() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        let stream_index = stream.index();
+        let route = match routes.get(stream_index).copied().flatten() {
+            Some(r) => r,
+            None => continue,
+        };
+        let secs_per_in_tick =
+            match super::sdk_export::time_base_to_secs_per_tick(route.in_time_base) {
+                Some(v) => v,
+                None => continue,
+            };
+        let pts_secs = match packet.pts().or(packet.dts()) {
+            Some(ts) => ts as f64 * secs_per_in_tick,
+            None => continue,
+        };
+
+        if pts_secs >= keep_until_secs + end_grace_secs {
+            if !streams_past_end[stream_index] {
+                streams_past_end[stream_index] = true;
+                streams_past_end_count += 1;
+            }
+            if streams_past_end_count >= route_count {
+                break;
+            }
+            continue;
+        }
+
+        if stream_index == source.video_index {
+            decoder.send_packet(&packet)?;
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                encode_frame(
+                    &mut encoder,
+                    &mut decoded,
+                    secs_per_video_tick,
+                    start_secs,
+                    keep_until_secs,
+                    output_offset_secs,
+                )?;
+                write_encoded(&mut encoder, output_ctx, writer, video_route, source)?;
+            }
+        } else if pts_secs >= start_secs && pts_secs < keep_until_secs {
+            let output_dts_secs = packet
+                .dts()
+                .map(|dts| dts as f64 * secs_per_in_tick + output_offset_secs);
+            writer.write(
+                output_ctx,
+                packet,
+                route,
+                route.in_time_base,
+                pts_secs + output_offset_secs,
+                output_dts_secs,
+            )?;
+        }
+    }
+
+    decoder.send_eof()?;
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        encode_frame(
+            &mut encoder,
+            &mut decoded,
+            secs_per_video_tick,
+            start_secs,
+            keep_until_secs,
+            output_offset_secs,
+        )?;
+        write_encoded(&mut encoder, output_ctx, writer, video_route, source)?;
+    }
+    encoder.send_eof()?;
+    write_encoded(&mut encoder, output_ctx, writer, video_route, source)?;
+    Ok(true)
+}
+
+/// Opens a libx265 encoder matching the decoded source frames.
+fn open_segment_encoder(
+    decoder: &ffmpeg::decoder::Video,
+    time_base: ffmpeg::Rational,
+    fps: f64,
+) -> Result<ffmpeg::encoder::Video> {
+    let codec = ffmpeg::encoder::find_by_name(SMART_CUT_ENCODER)
+        .with_context(|| format!("Encoder {} not found", SMART_CUT_ENCODER))?;
+    let mut video_enc = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .context("Failed to create smart cut encoder")?;
+    video_enc.set_width(decoder.width());
+    video_enc.set_height(decoder.height());
+    video_enc.set_format(decoder.format());
+    video_enc.set_time_base(time_base);
+    video_enc.set_frame_rate(Some(ffmpeg::Rational::from(fps)));
+    video_enc.set_max_b_frames(0);
+    video_enc.set_colorspace(decoder.color_space());
+    video_enc.set_color_range(decoder.color_range());
+    // SAFETY: both pointers are valid codec contexts owned by this function's caller and
+    // the encoder under construction; only plain enum fields are copied.
+    unsafe {
+        let src = decoder.as_ptr();
+        let dst = video_enc.as_mut_ptr();
+        (*dst).color_primaries = (*src).color_primaries;
+        (*dst).color_trc = (*src).color_trc;
+        (*dst).chroma_sample_location = (*src).chroma_sample_location;
+    }
+
+    let mut opts = ffmpeg::Dictionary::new();
+    opts.set("preset", "medium");
+    opts.set("crf", SMART_CUT_CRF);
+    opts.set("x265-params", SMART_CUT_X265_PARAMS);
+    video_enc
+        .open_with(opts)
+        .context("Failed to open smart cut encoder")
+}
+
+/// Sends `frame` to the encoder if it falls inside the segment, retimed to the output.
+fn encode_frame(
+    encoder: &mut ffmpeg::encoder::Video,
+    frame: &mut ffmpeg::frame::Video,
+    secs_per_tick: f64,
+    start_secs: f64,
+    keep_until_secs: f64,
+    output_offset_secs: f64,
+) -> Result<()> {
+    let Some(ts) = frame.timestamp().or(frame.pts()) else {
+        return Ok(());
+    };
+    let secs = ts as f64 * secs_per_tick;
+    if secs < start_secs || secs >= keep_until_secs {
+        return Ok(());
+    }
+    let output_secs = (secs + output_offset_secs).max(0.0);
+    frame.set_pts(Some((output_secs / secs_per_tick).round() as i64));
+    frame.set_kind(ffmpeg::picture::Type::None);
+    encoder.send_frame(frame)?;
+    Ok(())
+}
+
+/// Writes the encoder's pending packets in the source's NAL framing.
+fn write_encoded(
+    encoder: &mut ffmpeg::encoder::Video,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    writer: &mut StreamCopyWriter<'_>,
+    route: StreamCopyRoute,
+    source: &SmartCutSource,
+) -> Result<()> {
+    let time_base = encoder.time_base();
+    let secs_per_tick = super::sdk_export::time_base_to_secs_per_tick(time_base)
+        .context("Invalid smart cut encoder time base")?;
+    let mut encoded = ffmpeg::Packet::empty();
+    while encoder.receive_packet(&mut encoded).is_ok() {
+        let Some(pts) = encoded.pts() else {
+            continue;
+        };
+        let mut packet = match source.nal_length_size {
+            Some(length_size) => {
+                let data =
+                    annexb_to_length_prefixed(encoded.data().unwrap_or_default(), length_size);
+                let mut packet = ffmpeg::Packet::copy(&data);
+                packet.set_pts(encoded.pts());
+                packet.set_dts(encoded.dts());
+                packet.set_duration(encoded.duration());
+                packet.set_flags(encoded.flags());
+                packet
+            }
+            None => encoded.clone(),
+        };
+        if packet.duration() <= 0 {
+            packet.set_duration((source.frame_secs / secs_per_tick).round() as i64);
+        }
+        let output_dts_secs = encoded.dts().map(|dts| dts as f64 * secs_per_tick);
+        writer.write(
+            output_ctx,
+            packet,
+            route,
+            time_base,
+            pts as f64 * secs_per_tick,
+            output_dts_secs,
+        )?;
+    }
+    Ok(())
+}
+
+/// Keyframe times from the demuxer's seek index.
+fn indexed_keyframe_secs(
+    input_ctx: &mut ffmpeg::format::context::Input,
+    video_index: usize,
+) -> Vec<f64> {
+    // Matroska loads its cues on the first seek.
+    let _ = input_ctx.seek(0, ..);
+    let Some(stream) = input_ctx.stream(video_index) else {
+        return Vec::new();
+    };
+    let Some(secs_per_tick) = super::sdk_export::time_base_to_secs_per_tick(stream.time_base())
+    else {
+        return Vec::new();
+    };
+    let mut keyframes = Vec::new();
+    // SAFETY: the stream pointer is valid while `input_ctx` is borrowed, and index entries
+    // are only read between the count and entry calls, with no demuxing in between.
+    unsafe {
+        let stream_ptr = stream.as_ptr();
+        let count = ffmpeg::ffi::avformat_index_get_entries_count(stream_ptr);
+        for index in 0..count {
+            let entry = ffmpeg::ffi::avformat_index_get_entry(stream_ptr as *mut _, index);
+            if entry.is_null() {
+                continue;
+            }
+            if (*entry).flags() & ffmpeg::ffi::AVINDEX_KEYFRAME as c_int != 0 {
+                keyframes.push((*entry).timestamp as f64 * secs_per_tick);
+            }
+        }
+    }
+    keyframes
+}
+
+/// Keyframe times from reading every video packet; used when the container has no index.
+fn scanned_keyframe_secs(
+    input_ctx: &mut ffmpeg::format::context::Input,
+    video_index: usize,
+) -> Vec<f64> {
+    let _ = input_ctx.seek(0, ..);
+    let secs_per_tick = input_ctx
+        .stream(video_index)
+        .and_then(|stream| super::sdk_export::time_base_to_secs_per_tick(stream.time_base()));
+    let Some(secs_per_tick) = secs_per_tick else {
+        return Vec::new();
+    };
+    let mut keyframes = Vec::new();
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != video_index || !packet.is_key() {
+            continue;
+        }
+        if let Some(pts) = packet.pts().or(packet.dts()) {
+            keyframes.push(pts as f64 * secs_per_tick);
+        }
+    }
+    if keyframes.is_empty() {
+        warn!("Smart cut found no keyframes in stream {}", video_index);
+    }
+    keyframes
+}
+
+/// NAL units of an Annex B byte stream, without start codes or trailing zero bytes.
+fn annexb_nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut units = Vec::new();
+    let mut unit_start = None;
+    let mut i = 0;
+    while i + 2 < data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            if let Some(start) = unit_start {
+                units.push(&data[start..i]);
+            }
+            i += 3;
+            unit_start = Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    if let Some(start) = unit_start {
+        units.push(&data[start..]);
+    }
+    units
+        .into_iter()
+        .map(|unit| {
+            let len = unit
+                .iter()
+                .rposition(|&b| b != 0)
+                .map_or(0, |last| last + 1);
+            &unit[..len]
+        })
+        .filter(|unit| !unit.is_empty())
+        .collect()
+}
+
+/// Frames each NAL unit with a big-endian length field of `length_size` bytes.
+fn length_prefixed(units: &[&[u8]], length_size: usize) -> Vec<u8> {
+    let length_size = length_size.clamp(1, 4);
+    let mut out = Vec::with_capacity(units.iter().map(|u| u.len() + length_size).sum());
+    for unit in units {
+        let len = (unit.len() as u32).to_be_bytes();
+        out.extend_from_slice(&len[4 - length_size..]);
+        out.extend_from_slice(unit);
+    }
+    out
+}
+
+/// Converts an Annex B access unit to length-prefixed NAL units, as stored in MP4/MKV.
+pub(super) fn annexb_to_length_prefixed(data: &[u8], length_size: usize) -> Vec<u8> {
+    length_prefixed(&annexb_nal_units(data), length_size)
+}
+
+/// VPS/SPS/PPS (and any SEI) NAL units stored in an `hvcC` decoder configuration record.
+pub(super) fn hvcc_parameter_sets(hvcc: &[u8]) -> Option<Vec<&[u8]>> {
+    let array_count = usize::from(*hvcc.get(22)?);
+    let mut pos = 23;
+    let mut units = Vec::new();
+    for _ in 0..array_count {
+        // Skip the completeness flag / NAL type byte.
+        let nal_count = usize::from(u16::from_be_bytes([
+            *hvcc.get(pos + 1)?,
+            *hvcc.get(pos + 2)?,
+        ]));
+        pos += 3;
+        for _ in 0..nal_count {
+            let len = usize::from(u16::from_be_bytes([*hvcc.get(pos)?, *hvcc.get(pos + 1)?]));
+            pos += 2;
+            units.push(hvcc.get(pos..pos + len)?);
+            pos += len;
+        }
+    }
+    Some(units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f64 = 1.0 / 60.0;
+
+    fn range(start_secs: f64, end_secs: f64) -> TimeRange {
+        TimeRange {
+            start_secs,
+            end_secs,
+            speed: 1.0,
+        }
+    }
+
+    #[test]
+    fn plan_reencodes_head_and_copies_to_the_end() {
+        let keyframes = [0.0, 2.0, 4.0, 6.0];
+        let plan = plan_smart_cut(range(1.5, 5.5), &keyframes, FRAME, false);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(
+            plan[0],
+            CutSegment::Reencode {
+                decode_from_secs: 0.0,
+                start_secs: 1.5,
+                end_secs: 2.0 - FRAME / 2.0,
+            }
+        );
+        assert_eq!(
+            plan[1],
+            CutSegment::Copy {
+                start_secs: 2.0 - FRAME / 2.0,
+                end_secs: 5.5,
+            }
+        );
+    }
+
+    #[test]
+    fn plan_copies_ranges_starting_on_a_keyframe() {
+        let keyframes = [0.0, 2.0, 4.0];
+        let plan = plan_smart_cut(range(2.0, 3.0), &keyframes, FRAME, false);
+        assert_eq!(plan.len(), 1);
+        assert!(matches!(plan[0], CutSegment::Copy { .. }));
+        assert!(plan[0].start_secs() <= 2.0);
+        assert_eq!(plan[0].end_secs(), 3.0);
+    }
+
+    #[test]
+    fn plan_reencodes_ranges_inside_one_gop() {
+        let keyframes = [0.0, 2.0, 4.0];
+        let plan = plan_smart_cut(range(2.5, 3.5), &keyframes, FRAME, false);
+        assert_eq!(
+            plan,
+            vec![CutSegment::Reencode {
+                decode_from_secs: 2.0,
+                start_secs: 2.5,
+                end_secs: 3.5,
+            }]
+        );
+    }
+
+    #[test]
+    fn plan_reencodes_tail_for_reordered_streams() {
+        let keyframes = [0.0, 2.0, 4.0, 6.0];
+        let plan = plan_smart_cut(range(1.0, 5.0), &keyframes, FRAME, true);
+        assert_eq!(plan.len(), 3);
+        assert!(matches!(plan[0], CutSegment::Reencode { .. }));
+        assert_eq!(
+            plan[1],
+            CutSegment::Copy {
+                start_secs: 2.0 - FRAME / 2.0,
+                end_secs: 4.0 - FRAME / 2.0,
+            }
+        );
+        assert_eq!(
+            plan[2],
+            CutSegment::Reencode {
+                decode_from_secs: 4.0,
+                start_secs: 4.0 - FRAME / 2.0,
+                end_secs: 5.0,
+            }
+        );
+        // Segments tile the range without gaps.
+        for pair in plan.windows(2) {
+            assert_eq!(pair[0].end_secs(), pair[1].start_secs());
+        }
+    }
+
+    #[test]
+    fn annexb_converts_to_length_prefixed() {
+        let annexb = [
+            0, 0, 0, 1, 0x40, 0x01, 0xAA, 0, 0, 1, 0x26, 0x01, 0xBB, 0xCC,
+        ];
+        assert_eq!(
+            annexb_to_length_prefixed(&annexb, 4),
+            vec![0, 0, 0, 3, 0x40, 0x01, 0xAA, 0, 0, 0, 4, 0x26, 0x01, 0xBB, 0xCC]
+        );
+        assert_eq!(
+            annexb_to_length_prefixed(&annexb, 2),
+            vec![0, 3, 0x40, 0x01, 0xAA, 0, 4, 0x26, 0x01, 0xBB, 0xCC]
+        );
+    }
+
+    #[test]
+    fn hvcc_parameter_sets_reads_every_array() {
+        let mut hvcc = vec![1u8; 22];
+        hvcc[21] = 0xFF;
+        hvcc.push(2); // numOfArrays
+        hvcc.extend_from_slice(&[0x20, 0, 1, 0, 2, 0x40, 0x01]); // VPS
+        hvcc.extend_from_slice(&[0x21, 0, 1, 0, 3, 0x42, 0x01, 0x07]); // SPS
+        let units = hvcc_parameter_sets(&hvcc).unwrap();
+        assert_eq!(units, vec![&[0x40, 0x01][..], &[0x42, 0x01, 0x07][..]]);
+        assert_eq!(hvcc_parameter_sets(&hvcc[..hvcc.len() - 1]), None);
+    }
+}
@@ -1,9 +1,14 @@
+use super::clip_metadata::ClipMetadata;
+use super::functions::calculate_clip_start_pts;
 #[cfg(feature = "ffmpeg")]
 use super::functions::{h264_nal_type, hevc_nal_type};
 use super::mp4::FfmpegMuxer;
+use crate::buffer::Bookmark;
+use crate::config::{AudioConfig, BufferAudioCodec, ClipAudioCodec, SaveContainer};
 use crate::encode::{EncodedPacket, StreamType};
 use anyhow::{bail, Result};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::{info, warn};
 
 pub struct Muxer;
@@ -39,6 +44,9 @@ impl Muxer {
         fallback.to_string()
     }
 
+    /// Muxes a complete, in-memory packet set into an MP4 file.
+    ///
+    /// This is the single-chunk case of [`ClipStreamMuxer`].
     #[cfg(feature = "ffmpeg")]
     pub fn mux_clip(
         output_path: &Path,
@@ -47,9 +55,52 @@ impl Muxer {
     ) -> Result<PathBuf> {
         crate::output::saver::log_save_memory("Muxer::mux_clip_entry", None, Some(packets));
 
+        let mut stream = ClipStreamMuxer::new(output_path, config);
+        stream.write_chunk(packets)?;
+        stream.finish()
+    }
+}
+
+/// Muxes a clip from consecutive packet chunks, such as those yielded by
+/// [`SnapshotChunks`](crate::buffer::SnapshotChunks).
+///
+/// Each chunk is partitioned, normalized and handed to [`FfmpegMuxer::write_packets`] before
+/// the next one is read, so the caller only ever holds one chunk. Within each stream, chunks
+/// must continue in decode order. The muxer (and the output file) is created with the first
+/// chunk, which must contain video.
+#[cfg(feature = "ffmpeg")]
+pub struct ClipStreamMuxer {
+    output_path: PathBuf,
+    config: MuxerConfig,
+    muxer: Option<FfmpegMuxer>,
+    /// Standalone parameter sets ending the previous chunk, merged into the next frame.
+    pending_param_sets: Vec<EncodedPacket>,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl ClipStreamMuxer {
+    /// Creates a streaming muxer writing to `output_path`.
+    pub fn new(output_path: &Path, config: &MuxerConfig) -> Self {
+        Self {
+            output_path: output_path.to_path_buf(),
+            config: config.clone(),
+            muxer: None,
+            pending_param_sets: Vec::new(),
+        }
+    }
+
+    /// Writes the next chunk of the clip.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the first chunk has no muxable video, or the muxer fails.
+    pub fn write_chunk(&mut self, packets: &[EncodedPacket]) -> Result<()> {
         // Single-pass partition: separate video and audio by stream type in one
-        // iteration instead of two filter+collect passes over the full packet list.
-        let mut raw_video_packets = Vec::with_capacity(packets.len() / 2);
+        // iteration instead of two filter+collect passes over the chunk.
+        let pending_param_sets = std::mem::take(&mut self.pending_param_sets);
+        let mut raw_video_packets: Vec<&EncodedPacket> =
+            Vec::with_capacity(pending_param_sets.len() + packets.len() / 2);
+        raw_video_packets.extend(pending_param_sets.iter());
         let mut audio_packets = Vec::with_capacity(packets.len() / 4);
         for packet in packets {
             match packet.stream {
@@ -57,13 +108,25 @@ impl Muxer {
                 StreamType::SystemAudio | StreamType::Microphone => audio_packets.push(packet),
             }
         }
-        if raw_video_packets.is_empty() {
+        if raw_video_packets.is_empty() && self.muxer.is_none() {
             bail!("No video packets available for MP4 generation");
         }
 
+        // Stable sort: carried parameter sets stay ahead of the frame they share a PTS with.
         raw_video_packets.sort_by_key(|packet| packet.pts);
         audio_packets.sort_by_key(|packet| packet.pts);
 
+        // Parameter sets at the end of the chunk belong to the first frame of the next one.
+        let muxable_len = raw_video_packets
+            .iter()
+            .rposition(|p| !is_parameter_set_packet(p))
+            .map_or(0, |last_frame| last_frame + 1);
+        let carried: Vec<EncodedPacket> = raw_video_packets[muxable_len..]
+            .iter()
+            .map(|p| (*p).clone())
+            .collect();
+        raw_video_packets.truncate(muxable_len);
+
         // Check if any standalone parameter set packets need merging.
         // If none, we can avoid the deep-copy normalization entirely.
         let needs_normalization = raw_video_packets.iter().any(|p| is_parameter_set_packet(p));
@@ -84,37 +147,71 @@ impl Muxer {
             video_refs = raw_video_packets;
         }
 
-        if video_refs.is_empty() {
+        if self.muxer.is_none() {
+            if video_refs.is_empty() {
+                bail!("No muxable video packets available for MP4 generation");
+            }
+            let config = &self.config;
+            let detected_video_codec = Muxer::detect_video_codec(&video_refs, &config.video_codec);
+            if detected_video_codec != config.video_codec {
+                warn!(
+                    "Muxer video codec override: configured={}, detected={} from buffered packets",
+                    config.video_codec, detected_video_codec
+                );
+            }
+
+            info!("Writing MP4 to {:?}", self.output_path);
+
+            self.muxer = Some(FfmpegMuxer::new(
+                &self.output_path,
+                &detected_video_codec,
+                config.width,
+                config.height,
+                config.fps,
+                config,
+            )?);
+        }
+        let Some(muxer) = self.muxer.as_mut() else {
             bail!("No muxable video packets available for MP4 generation");
+        };
+        muxer.write_packets(&video_refs, &audio_packets)?;
+        self.pending_param_sets = carried;
+        Ok(())
+    }
+
+    /// Replaces the clip metadata, e.g. once the whole clip has been measured. Tags already
+    /// written with the header are only replaced in containers that write them with the
+    /// trailer; see [`FfmpegMuxer::set_metadata`].
+    pub fn set_metadata(&mut self, metadata: ClipMetadata) {
+        if let Some(muxer) = self.muxer.as_mut() {
+            muxer.set_metadata(&metadata);
         }
+        self.config.metadata = Some(metadata);
+    }
 
-        let detected_video_codec = Self::detect_video_codec(&video_refs, &config.video_codec);
-        if detected_video_codec != config.video_codec {
+    /// Writes the trailer and returns the output path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no chunk was written or the trailer cannot be written.
+    pub fn finish(mut self) -> Result<PathBuf> {
+        if !self.pending_param_sets.is_empty() {
             warn!(
-                "Muxer video codec override: configured={}, detected={} from buffered packets",
-                config.video_codec, detected_video_codec
+                "Dropping {} trailing standalone parameter-set packets with no following video frame",
+                self.pending_param_sets.len()
             );
         }
-
-        info!("Writing MP4 to {:?}", output_path);
-
-        let mut muxer = FfmpegMuxer::new(
-            output_path,
-            &detected_video_codec,
-            config.width,
-            config.height,
-            config.fps,
-            config,
-        )?;
-
-        let (video_count, audio_count) = muxer.write_packets(&video_refs, &audio_packets)?;
+        let Some(mut muxer) = self.muxer.take() else {
+            bail!("No video packets available for MP4 generation");
+        };
+        let (video_count, audio_count) = muxer.finish()?;
         drop(muxer);
 
         info!(
             "MP4 finalized natively: {:?} ({} video packets, {} audio packets)",
-            output_path, video_count, audio_count
+            self.output_path, video_count, audio_count
         );
-        Ok(output_path.to_path_buf())
+        Ok(self.output_path)
     }
 }
 
@@ -210,7 +307,7 @@ fn normalize_video_packets_for_mp4(video_packets: &[&EncodedPacket]) -> Vec<Enco
 /// by scanning all NAL units in the packet: if any VCL (coded slice) NAL appears,
 /// the packet is a complete frame and must not be split off.
 #[cfg(feature = "ffmpeg")]
-fn is_parameter_set_packet(packet: &EncodedPacket) -> bool {
+pub(super) fn is_parameter_set_packet(packet: &EncodedPacket) -> bool {
     if !matches!(packet.stream, StreamType::Video) {
         return false;
     }
@@ -302,10 +399,76 @@ pub struct MuxerConfig {
     pub fps: f64,
     /// Output file path.
     pub output_path: PathBuf,
-    /// Whether to enable faststart for web streaming.
+    /// Container format written; the output path's extension is not consulted.
+    pub container: SaveContainer,
+    /// Whether to enable faststart for web streaming (MP4 and MOV only).
     pub faststart: bool,
     /// Whether to expect audio streams.
     pub expect_audio: bool,
+    /// Format of the buffered audio packets; compressed audio is stream-copied.
+    pub buffer_audio_codec: BufferAudioCodec,
+    /// Codec buffered PCM is encoded to; ignored when buffered audio is stream-copied.
+    pub audio_codec: ClipAudioCodec,
+    /// Bitrate for AAC/Opus audio encoded at save time.
+    pub audio_bitrate_kbps: u32,
+    /// Whether system audio and microphone share one track or get one each.
+    pub audio_tracks: AudioTrackLayout,
+    /// Recording context written as container tags.
+    pub metadata: Option<ClipMetadata>,
+    /// Bookmarks written as chapters; those before the clip start are skipped.
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// Title and ISO 639-2 language tag written on an audio track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioTrackLabel {
+    pub title: String,
+    pub language: String,
+}
+
+impl AudioTrackLabel {
+    /// Creates a label.
+    pub fn new(title: impl Into<String>, language: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            language: language.into(),
+        }
+    }
+}
+
+/// How buffered system and microphone audio are laid out in a muxed clip.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AudioTrackLayout {
+    /// Both streams mixed into a single track.
+    #[default]
+    Mixed,
+    /// System audio on the first track and the microphone on the second, each labelled.
+    Separate {
+        system: AudioTrackLabel,
+        microphone: AudioTrackLabel,
+    },
+}
+
+impl AudioTrackLayout {
+    /// The layout `config` asks for.
+    ///
+    /// Separate tracks are only used when both streams are captured; with a single source
+    /// there is nothing to separate.
+    pub fn from_audio_config(config: &AudioConfig) -> Self {
+        if config.separate_tracks && config.capture_system && config.capture_mic {
+            Self::separate(config)
+        } else {
+            Self::Mixed
+        }
+    }
+
+    /// Separate tracks labelled with the titles and language in `config`.
+    pub fn separate(config: &AudioConfig) -> Self {
+        Self::Separate {
+            system: AudioTrackLabel::new(&config.system_track_title, &config.track_language),
+            microphone: AudioTrackLabel::new(&config.mic_track_title, &config.track_language),
+        }
+    }
 }
 
 impl MuxerConfig {
@@ -317,8 +480,15 @@ impl MuxerConfig {
             video_codec: "h264".to_string(),
             fps,
             output_path: output_path.as_ref().to_path_buf(),
+            container: SaveContainer::Mp4,
             faststart: true,
             expect_audio: false,
+            buffer_audio_codec: BufferAudioCodec::Pcm,
+            audio_codec: ClipAudioCodec::Aac,
+            audio_bitrate_kbps: 192,
+            audio_tracks: AudioTrackLayout::Mixed,
+            metadata: None,
+            bookmarks: Vec::new(),
         }
     }
 
@@ -328,6 +498,12 @@ impl MuxerConfig {
         self
     }
 
+    /// Sets the container format.
+    pub fn with_container(mut self, container: SaveContainer) -> Self {
+        self.container = container;
+        self
+    }
+
     /// Sets the faststart option.
     pub fn with_faststart(mut self, faststart: bool) -> Self {
         self.faststart = faststart;
@@ -339,4 +515,108 @@ impl MuxerConfig {
         self.expect_audio = expect_audio;
         self
     }
+
+    /// Sets the format of the buffered audio packets.
+    pub fn with_buffer_audio_codec(mut self, codec: BufferAudioCodec) -> Self {
+        self.buffer_audio_codec = codec;
+        self
+    }
+
+    /// Sets the codec and bitrate that buffered PCM is encoded to.
+    pub fn with_audio_codec(mut self, codec: ClipAudioCodec, bitrate_kbps: u32) -> Self {
+        self.audio_codec = codec;
+        self.audio_bitrate_kbps = bitrate_kbps;
+        self
+    }
+
+    /// Sets the recording context written as container tags.
+    pub fn with_metadata(mut self, metadata: ClipMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Sets the bookmarks written as chapters.
+    pub fn with_bookmarks(mut self, bookmarks: Vec<Bookmark>) -> Self {
+        self.bookmarks = bookmarks;
+        self
+    }
+
+    /// Sets how system audio and microphone are laid out in tracks.
+    pub fn with_audio_tracks(mut self, layout: AudioTrackLayout) -> Self {
+        self.audio_tracks = layout;
+        self
+    }
+}
+
+/// Portion of the replay buffer to save as a clip (see
+/// [`spawn_clip_saver`](super::spawn_clip_saver)).
+///
+/// PTS values are QPC ticks, like [`EncodedPacket::pts`]. Every window is extended back to the
+/// last keyframe at or before its start so the clip is decodable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipWindow {
+    /// The last `N` seconds, ending at the newest buffered packet.
+    Last(Duration),
+    /// Packets with PTS in `start_pts..=end_pts`.
+    PtsRange { start_pts: i64, end_pts: i64 },
+    /// `duration` of replay ending `ago` before the newest buffered packet.
+    EndingAgo { duration: Duration, ago: Duration },
+}
+
+impl ClipWindow {
+    /// Resolves the window against the buffer's current PTS bounds.
+    ///
+    /// Returns the start PTS and, for windows with a fixed end, the inclusive end PTS.
+    /// [`ClipWindow::Last`] has no fixed end: it runs to the newest packet at snapshot time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is inverted or ends before the oldest buffered packet.
+    pub fn resolve(&self, newest_pts: i64, oldest_pts: Option<i64>) -> Result<(i64, Option<i64>)> {
+        let (start_pts, end_pts) = match *self {
+            Self::Last(duration) => {
+                return Ok((
+                    calculate_clip_start_pts(newest_pts, duration, oldest_pts),
+                    None,
+                ));
+            }
+            Self::PtsRange { start_pts, end_pts } => {
+                if end_pts < start_pts {
+                    bail!(
+                        "Clip window end ({}) precedes its start ({})",
+                        end_pts,
+                        start_pts
+                    );
+                }
+                (start_pts, end_pts)
+            }
+            Self::EndingAgo { duration, ago } => {
+                let end_pts = newest_pts.saturating_sub(duration_to_qpc(ago));
+                let start_pts = end_pts.saturating_sub(duration_to_qpc(duration)).max(0);
+                (start_pts, end_pts)
+            }
+        };
+
+        if let Some(oldest) = oldest_pts {
+            if end_pts < oldest {
+                bail!(
+                    "Clip window ends at pts {} but the oldest buffered packet is at {}",
+                    end_pts,
+                    oldest
+                );
+            }
+        }
+        Ok((start_pts, Some(end_pts)))
+    }
+}
+
+impl From<Duration> for ClipWindow {
+    fn from(duration: Duration) -> Self {
+        Self::Last(duration)
+    }
+}
+
+fn duration_to_qpc(duration: Duration) -> i64 {
+    let qpc_freq = crate::buffer::ring::functions::qpc_frequency();
+    (duration.as_secs_f64() * qpc_freq as f64) as i64
 }
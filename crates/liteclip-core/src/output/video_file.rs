@@ -1,8 +1,12 @@
+use super::audio_mix::{ExportAudioMix, LoudnessNormalization};
+use super::montage::MontageExportRequest;
+use super::overlay::ExportOverlay;
 use crate::config::EncoderType;
 use crate::encode::{resolve_effective_encoder_config, EncoderConfig};
 use crate::quality_contracts::{validate_export_validity, ExportValidationInput};
 use anyhow::{bail, Context, Result};
 use image::RgbaImage;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{
@@ -13,10 +17,13 @@ use std::sync::{
 
 /// Output container format for clip export.
 ///
-/// Determines the file container and (for WebM) the video codec used.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Determines the file container and (for WebM and the animated image formats) the video
+/// codec used. The audio-only formats drop the video stream entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExportContainerFormat {
     /// MP4 container with H.265/HEVC video + AAC audio (default).
+    #[default]
     Mp4,
     /// Matroska container with H.265/HEVC video + AAC audio.
     Mkv,
@@ -24,6 +31,20 @@ pub enum ExportContainerFormat {
     Mov,
     /// WebM container with VP9 video + Opus audio.
     WebM,
+    /// Animated GIF with one palette for the whole clip. No audio.
+    Gif,
+    /// Animated WebP (lossy). No audio.
+    WebP,
+    /// Animated PNG. No audio.
+    Apng,
+    /// Audio only: AAC in an MP4 audio container.
+    M4a,
+    /// Audio only: Opus in an Ogg container.
+    Opus,
+    /// Audio only: lossless FLAC.
+    Flac,
+    /// Audio only: uncompressed 16-bit PCM WAV.
+    Wav,
 }
 
 impl ExportContainerFormat {
@@ -34,6 +55,14 @@ impl ExportContainerFormat {
             ExportContainerFormat::Mkv => "mkv",
             ExportContainerFormat::Mov => "mov",
             ExportContainerFormat::WebM => "webm",
+            ExportContainerFormat::Gif => "gif",
+            ExportContainerFormat::WebP => "webp",
+            // Most viewers only play APNG under the plain PNG extension.
+            ExportContainerFormat::Apng => "png",
+            ExportContainerFormat::M4a => "m4a",
+            ExportContainerFormat::Opus => "opus",
+            ExportContainerFormat::Flac => "flac",
+            ExportContainerFormat::Wav => "wav",
         }
     }
 
@@ -44,6 +73,13 @@ impl ExportContainerFormat {
             ExportContainerFormat::Mkv => "MKV (H.265)",
             ExportContainerFormat::Mov => "MOV (H.265)",
             ExportContainerFormat::WebM => "WebM (VP9)",
+            ExportContainerFormat::Gif => "GIF (animated)",
+            ExportContainerFormat::WebP => "WebP (animated)",
+            ExportContainerFormat::Apng => "APNG (animated)",
+            ExportContainerFormat::M4a => "M4A (AAC audio)",
+            ExportContainerFormat::Opus => "Opus (audio)",
+            ExportContainerFormat::Flac => "FLAC (lossless audio)",
+            ExportContainerFormat::Wav => "WAV (PCM audio)",
         }
     }
 
@@ -56,16 +92,68 @@ impl ExportContainerFormat {
         )
     }
 
+    /// Returns true for the animated image formats (GIF, WebP, APNG). They carry no audio
+    /// and are sized to the target by lowering framerate and resolution instead of bitrate.
+    pub fn is_animated_image(self) -> bool {
+        matches!(
+            self,
+            ExportContainerFormat::Gif | ExportContainerFormat::WebP | ExportContainerFormat::Apng
+        )
+    }
+
+    /// Returns true for the audio-only formats (M4A, Opus, FLAC, WAV). They carry no video
+    /// and are sized to the target by lowering the audio bitrate only.
+    pub fn is_audio_only(self) -> bool {
+        matches!(
+            self,
+            ExportContainerFormat::M4a
+                | ExportContainerFormat::Opus
+                | ExportContainerFormat::Flac
+                | ExportContainerFormat::Wav
+        )
+    }
+
+    /// Returns true for the audio-only formats whose size is fixed by the source audio
+    /// (FLAC, WAV). The audio bitrate setting and the target size have no effect on them.
+    pub fn is_lossless_audio(self) -> bool {
+        matches!(
+            self,
+            ExportContainerFormat::Flac | ExportContainerFormat::Wav
+        )
+    }
+
+    /// Returns true if this container can hold more than one audio stream. Exports to the
+    /// other formats mix multiple tracks into one instead of keeping them separate.
+    pub fn supports_multiple_audio_tracks(self) -> bool {
+        matches!(
+            self,
+            ExportContainerFormat::Mp4
+                | ExportContainerFormat::Mkv
+                | ExportContainerFormat::Mov
+                | ExportContainerFormat::WebM
+                | ExportContainerFormat::M4a
+        )
+    }
+
     /// Returns the FFmpeg codec name for video encoding in this container.
-    /// For WebM we use libvpx-vp9 (VP9), for all others we use H.265.
+    /// For WebM we use libvpx-vp9 (VP9), the animated image formats use their own
+    /// encoders, and all others use H.265. The audio-only formats never encode video and
+    /// report H.265 only so that encoder selection has a valid fallback.
     /// Hardware encoders are handled separately via ExportVideoEncoder;
     /// this returns the fallback software encoder name.
     pub fn default_video_codec_name(self) -> &'static str {
         match self {
             ExportContainerFormat::WebM => "libvpx-vp9",
+            ExportContainerFormat::Gif => "gif",
+            ExportContainerFormat::WebP => "libwebp_anim",
+            ExportContainerFormat::Apng => "apng",
             ExportContainerFormat::Mp4
             | ExportContainerFormat::Mkv
-            | ExportContainerFormat::Mov => "libx265",
+            | ExportContainerFormat::Mov
+            | ExportContainerFormat::M4a
+            | ExportContainerFormat::Opus
+            | ExportContainerFormat::Flac
+            | ExportContainerFormat::Wav => "libx265",
         }
     }
 
@@ -76,39 +164,137 @@ impl ExportContainerFormat {
             ExportContainerFormat::Mkv => "MKV",
             ExportContainerFormat::Mov => "MOV",
             ExportContainerFormat::WebM => "WebM",
+            ExportContainerFormat::Gif => "GIF",
+            ExportContainerFormat::WebP => "WebP",
+            ExportContainerFormat::Apng => "APNG",
+            ExportContainerFormat::M4a => "M4A",
+            ExportContainerFormat::Opus => "Opus",
+            ExportContainerFormat::Flac => "FLAC",
+            ExportContainerFormat::Wav => "WAV",
+        }
+    }
+}
+
+/// What clip export does with the audio tracks of a clip saved with separate system audio
+/// and microphone tracks.
+///
+/// Files with a single audio track export the same way under every option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportAudioTracks {
+    /// Keep every audio track as its own stream, with its title and language (default).
+    #[default]
+    Keep,
+    /// Keep only the first track (game audio) and drop the rest.
+    PrimaryOnly,
+    /// Mix all tracks into one. Requires re-encoding the audio.
+    Mix,
+}
+
+impl ExportAudioTracks {
+    /// All options, in UI order.
+    pub const ALL: [Self; 3] = [Self::Keep, Self::PrimaryOnly, Self::Mix];
+
+    /// Human-readable label for UI display.
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportAudioTracks::Keep => "Keep separate tracks",
+            ExportAudioTracks::PrimaryOnly => "Game audio only",
+            ExportAudioTracks::Mix => "Mix into one track",
         }
     }
+
+    /// Whether exporting a file with `track_count` audio tracks needs an audio re-encode.
+    pub fn requires_reencode(self, track_count: usize) -> bool {
+        self == ExportAudioTracks::Mix && track_count > 1
+    }
 }
 use std::thread;
 use std::time::Instant;
 use tracing::{error, info, warn};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoFileMetadata {
     pub duration_secs: f64,
     pub width: u32,
     pub height: u32,
     pub has_audio: bool,
+    /// Number of audio streams; more than one for clips saved with separate tracks.
+    pub audio_track_count: usize,
     pub fps: f64,
+    /// Recording context from the container tags of clips saved by LiteClip.
+    pub clip_metadata: Option<super::ClipMetadata>,
+    /// Chapters in start order, such as bookmarks taken while recording.
+    pub chapters: Vec<ClipChapter>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// A chapter read from a video file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipChapter {
+    pub start_secs: f64,
+    pub title: String,
+}
+
+/// Slowest playback speed of a kept range.
+pub const MIN_RANGE_SPEED: f64 = 0.25;
+/// Fastest playback speed of a kept range.
+pub const MAX_RANGE_SPEED: f64 = 4.0;
+
+/// A kept range of the source, in source seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct TimeRange {
     pub start_secs: f64,
     pub end_secs: f64,
+    /// Playback speed of the range in the export, from [`MIN_RANGE_SPEED`] (slow motion) to
+    /// [`MAX_RANGE_SPEED`]. Anything but 1.0 requires re-encoding.
+    #[serde(default = "default_range_speed")]
+    pub speed: f64,
+}
+
+fn default_range_speed() -> f64 {
+    1.0
 }
 
 impl TimeRange {
+    /// A range played at normal speed.
+    pub fn new(start_secs: f64, end_secs: f64) -> Self {
+        Self {
+            start_secs,
+            end_secs,
+            speed: 1.0,
+        }
+    }
+
+    /// Length of the range in the source.
     pub fn duration_secs(self) -> f64 {
         (self.end_secs - self.start_secs).max(0.0)
     }
+
+    /// Length of the range in the export, after retiming to its speed.
+    pub fn output_duration_secs(self) -> f64 {
+        self.duration_secs() / self.effective_speed()
+    }
+
+    /// The speed clamped to [`MIN_RANGE_SPEED`]..=[`MAX_RANGE_SPEED`]; 1.0 if not finite.
+    pub fn effective_speed(self) -> f64 {
+        if self.speed.is_finite() {
+            self.speed.clamp(MIN_RANGE_SPEED, MAX_RANGE_SPEED)
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether the range plays at anything but normal speed.
+    pub fn is_retimed(self) -> bool {
+        (self.effective_speed() - 1.0).abs() > 1e-6
+    }
 }
 
 /// Rectangular crop region in source video pixel coordinates.
 ///
 /// All values are in the original video's pixel space (before any scaling).
 /// Width and height must be even numbers (divisible by 2) for H.265 encoding.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct CropRect {
     /// Horizontal offset from left edge in pixels.
     pub x: u32,
@@ -162,7 +348,7 @@ pub enum ClipExportUpdate {
     Cancelled,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipExportRequest {
     pub input_path: PathBuf,
     pub output_path: PathBuf,
@@ -175,6 +361,10 @@ pub struct ClipExportRequest {
     /// If true, use stream copy (no re-encoding) for fastest export preserving original quality.
     /// Used when user hasn't manually adjusted the target size.
     pub stream_copy: bool,
+    /// With `stream_copy`, cut frame-accurately by re-encoding only the partial GOPs at the
+    /// start and end of each kept range and stream-copying the rest. Requires an HEVC source
+    /// and libx265; otherwise the export falls back to cutting at keyframes.
+    pub smart_cut: bool,
     /// Output resolution. None means original resolution.
     pub output_width: Option<u32>,
     pub output_height: Option<u32>,
@@ -186,18 +376,38 @@ pub struct ClipExportRequest {
     /// Enable adaptive post-processing filters (deblocking, sharpening, contrast) during export.
     /// Filter strengths are computed automatically from bitrate/resolution.
     pub post_process_filters: bool,
-    /// Output container format (MP4, MKV, MOV, WebM).
-    /// Determines the file extension and (for WebM) the video codec.
+    /// Output container format (MP4, MKV, MOV, WebM) or animated image format (GIF, WebP,
+    /// APNG). Determines the file extension and (for WebM and the image formats) the codec.
     pub container_format: ExportContainerFormat,
+    /// Keep, drop or mix the audio tracks of clips with more than one.
+    pub audio_tracks: ExportAudioTracks,
+    /// Images and text drawn over the video, in drawing order. Like `crop`, any overlay
+    /// forces a re-encode.
+    #[serde(default)]
+    pub overlays: Vec<ExportOverlay>,
+    /// Per-track gain and mute, and the fades at and across the kept-range boundaries.
+    /// Anything but the default mix forces the audio to be re-encoded.
+    #[serde(default)]
+    pub audio: ExportAudioMix,
+    /// Loudness target of two-pass normalization. Like a non-default `audio` mix, it forces
+    /// the audio to be re-encoded.
+    #[serde(default)]
+    pub loudness: Option<LoudnessNormalization>,
 }
 
 impl ClipExportRequest {
+    /// Length of the export: the kept ranges, each retimed to its speed.
     pub fn output_duration_secs(&self) -> f64 {
         self.keep_ranges
             .iter()
-            .map(|range| range.duration_secs())
+            .map(|range| range.output_duration_secs())
             .sum()
     }
+
+    /// Whether any kept range plays at anything but normal speed.
+    pub fn has_retimed_ranges(&self) -> bool {
+        self.keep_ranges.iter().any(|range| range.is_retimed())
+    }
 }
 
 pub enum ExportOutcome {
@@ -277,7 +487,7 @@ const MIN_VIDEO_BITRATE_KBPS: u32 = 300;
 const MAX_VIDEO_BITRATE_KBPS: u32 = 400_000;
 const MIN_REASONABLE_FPS: f64 = 1.0;
 const MAX_REASONABLE_FPS: f64 = 240.0;
-const FALLBACK_EXPORT_FPS: f64 = 60.0;
+pub(super) const FALLBACK_EXPORT_FPS: f64 = 60.0;
 const TARGET_FILL_MIN_RATIO: f64 = 0.90;
 const TARGET_FILL_MAX_RATIO: f64 = 1.00;
 const INITIAL_TARGET_FILL_RATIO: f64 = 0.96;
@@ -295,6 +505,20 @@ const MAX_EXPORT_ATTEMPTS: usize = 8;
 const BITRATE_NUDGE_KBPS: u32 = 24;
 const COMPLEXITY_RATIO_MIN: f64 = 0.10;
 const COMPLEXITY_RATIO_MAX: f64 = 2.0;
+/// Share of the target size animated image exports aim for when shrinking.
+const ANIMATED_IMAGE_TARGET_FILL_RATIO: f64 = 0.92;
+/// Animated image exports drop framerate down to this before only shrinking resolution.
+const MIN_ANIMATED_IMAGE_FPS: f64 = 10.0;
+/// Animated image exports never shrink the shorter side below this many pixels.
+const MIN_ANIMATED_IMAGE_SIDE: u32 = 144;
+/// GIF frame delays are whole centiseconds, so faster rates play back wrong.
+const MAX_GIF_FPS: f64 = 50.0;
+/// Share of the target size lossy audio-only exports aim for.
+const AUDIO_ONLY_TARGET_FILL_RATIO: f64 = 0.95;
+/// Lossy audio-only exports never go below this bitrate.
+const MIN_AUDIO_ONLY_BITRATE_KBPS: u32 = 48;
+/// Smallest bitrate reduction between audio-only export attempts.
+const AUDIO_ONLY_BITRATE_STEP_KBPS: u32 = 8;
 /// Multiplier applied to the initial bitrate estimate for short clips
 /// that skip calibration, ensuring a conservative (higher) bitrate
 /// for better quality when file size is less of a concern.
@@ -439,6 +663,101 @@ pub fn spawn_clip_export(
         }
     });
 }
+/// Exports a montage on a background thread, reporting progress like [`spawn_clip_export`].
+pub fn spawn_montage_export(
+    request: MontageExportRequest,
+    progress_tx: Sender<ClipExportUpdate>,
+    cancel_flag: Arc<AtomicBool>,
+) {
+    thread::spawn(move || {
+        let result = run_montage_export(&request, &progress_tx, &cancel_flag);
+        match result {
+            Ok(ExportOutcome::Finished(path)) => {
+                let _ = progress_tx.send(ClipExportUpdate::Finished(path));
+            }
+            Ok(ExportOutcome::Cancelled) => {
+                let _ = progress_tx.send(ClipExportUpdate::Cancelled);
+            }
+            Err(err) => {
+                error!(
+                    clips = request.clips.len(),
+                    output_path = ?request.output_path,
+                    target_size_mb = request.target_size_mb,
+                    "Montage export failed: {err:#}"
+                );
+                let _ = progress_tx.send(ClipExportUpdate::Failed(format!("{err:#}")));
+            }
+        }
+    });
+}
+
+fn run_montage_export(
+    montage: &MontageExportRequest,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+) -> Result<ExportOutcome> {
+    if montage.clips.is_empty() {
+        bail!("Cannot export a montage with no clips");
+    }
+    if montage.clips.iter().any(|clip| clip.duration_secs() <= 0.0) {
+        bail!("Every montage clip needs at least one kept range");
+    }
+    if montage
+        .clips
+        .iter()
+        .flat_map(|clip| &clip.keep_ranges)
+        .any(|range| range.is_retimed())
+    {
+        bail!("Montage clips can't change playback speed; export retimed clips on their own first");
+    }
+    if montage.container_format.is_animated_image() || montage.container_format.is_audio_only() {
+        bail!(
+            "Montages can only be exported to video formats, not {}",
+            montage.container_format.short_label()
+        );
+    }
+
+    std::fs::create_dir_all(
+        montage
+            .output_path
+            .parent()
+            .context("Output path is missing a parent directory")?,
+    )
+    .with_context(|| {
+        format!(
+            "Failed to create output directory for {:?}",
+            montage.output_path
+        )
+    })?;
+
+    let export_started_at = Instant::now();
+    let request = montage.timeline_request();
+    let (output_width, output_height) = montage.output_dimensions();
+    info!(
+        clips = montage.clips.len(),
+        output = ?montage.output_path,
+        target_size_mb = montage.target_size_mb,
+        output_duration_secs = request.output_duration_secs(),
+        output_width,
+        output_height,
+        output_fps = format!("{:.2}", montage.output_fps()),
+        transition = ?montage.transition,
+        "Starting montage export"
+    );
+
+    let export_work_dir = create_export_work_dir()?;
+    let _work_dir_guard = WorkDirGuard::new(export_work_dir.clone());
+
+    run_reencode_export(
+        &request,
+        Some(montage),
+        &export_work_dir,
+        export_started_at,
+        progress_tx,
+        cancel_flag,
+    )
+}
+
 fn run_clip_export(
     request: &ClipExportRequest,
     progress_tx: &Sender<ClipExportUpdate>,
@@ -447,6 +766,12 @@ fn run_clip_export(
     if request.keep_ranges.is_empty() {
         bail!("Cannot export a clip with no kept ranges");
     }
+    if !request.container_format.is_audio_only() {
+        for overlay in &request.overlays {
+            overlay.validate()?;
+        }
+    }
+    request.audio.validate()?;
 
     std::fs::create_dir_all(
         request
@@ -461,7 +786,17 @@ fn run_clip_export(
         )
     })?;
 
-    if request.stream_copy && request.crop.is_none() {
+    if request.stream_copy
+        && request.container_format.supports_stream_copy()
+        && request.crop.is_none()
+        && request.overlays.is_empty()
+        && request.audio.is_passthrough()
+        && request.loudness.is_none()
+        && !request.has_retimed_ranges()
+        && !request
+            .audio_tracks
+            .requires_reencode(request.metadata.audio_track_count)
+    {
         #[cfg(feature = "ffmpeg")]
         {
             return super::sdk_export::run_stream_copy_export_sdk(
@@ -494,19 +829,64 @@ fn run_clip_export(
         "Starting clip export"
     );
 
-    let export_work_dir = std::env::temp_dir().join(format!(
-        "liteclip-export-{}-{}",
-        std::process::id(),
-        chrono::Utc::now().timestamp_millis()
-    ));
-    std::fs::create_dir_all(&export_work_dir).with_context(|| {
-        format!(
-            "Failed to create temporary export directory {:?}",
-            export_work_dir
-        )
-    })?;
+    let export_work_dir = create_export_work_dir()?;
     let _work_dir_guard = WorkDirGuard::new(export_work_dir.clone());
 
+    if request.container_format.is_animated_image() {
+        #[cfg(feature = "ffmpeg")]
+        {
+            return run_animated_image_export(request, &export_work_dir, progress_tx, cancel_flag);
+        }
+        #[cfg(not(feature = "ffmpeg"))]
+        {
+            anyhow::bail!("ffmpeg feature is required for animated image export");
+        }
+    }
+
+    // First pass of loudness normalization, shared by every attempt below.
+    #[cfg(feature = "ffmpeg")]
+    let request = &{
+        let mut request = request.clone();
+        if !super::loudness::measure_request_loudness(&mut request, progress_tx, cancel_flag)? {
+            return Ok(ExportOutcome::Cancelled);
+        }
+        request
+    };
+
+    if request.container_format.is_audio_only() {
+        #[cfg(feature = "ffmpeg")]
+        {
+            return run_audio_only_export(request, &export_work_dir, progress_tx, cancel_flag);
+        }
+        #[cfg(not(feature = "ffmpeg"))]
+        {
+            anyhow::bail!("ffmpeg feature is required for audio-only export");
+        }
+    }
+
+    run_reencode_export(
+        request,
+        None,
+        &export_work_dir,
+        export_started_at,
+        progress_tx,
+        cancel_flag,
+    )
+}
+
+/// Re-encoding export: calibrates, then searches for the video bitrate that fits the target
+/// size, falling back to the software encoder when a hardware encoder fails.
+///
+/// For a montage, `request` is its [`MontageExportRequest::timeline_request`].
+fn run_reencode_export(
+    request: &ClipExportRequest,
+    montage: Option<&MontageExportRequest>,
+    export_work_dir: &Path,
+    export_started_at: Instant,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+) -> Result<ExportOutcome> {
+    let output_duration_secs = request.output_duration_secs().max(0.1);
     let mut selected_encoder = select_export_video_encoder(request)
         .with_context(|| "Unable to resolve an export video encoder")?;
 
@@ -518,9 +898,10 @@ fn run_clip_export(
         let budget = SizeBudget::from_request(request, selected_encoder);
         let initial_video_bitrate_kbps = match calibrate_initial_bitrate(
             request,
+            montage,
             &budget,
             selected_encoder,
-            &export_work_dir,
+            export_work_dir,
             progress_tx,
             cancel_flag,
         ) {
@@ -542,10 +923,11 @@ fn run_clip_export(
 
         match run_bitrate_search(
             request,
+            montage,
             &budget,
             selected_encoder,
             initial_video_bitrate_kbps,
-            &export_work_dir,
+            export_work_dir,
             progress_tx,
             cancel_flag,
         ) {
@@ -613,13 +995,357 @@ fn run_clip_export(
     }
 }
 
+/// Frame size and rate of one animated image export attempt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AnimatedImageSettings {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) fps: f64,
+}
+
+impl AnimatedImageSettings {
+    /// The requested output size and framerate, capped to what the format can play.
+    fn from_request(request: &ClipExportRequest) -> Self {
+        let (width, height) = resolved_output_dimensions(request);
+        let mut fps = resolved_output_fps_for_request(request);
+        if request.container_format == ExportContainerFormat::Gif {
+            fps = fps.min(MAX_GIF_FPS);
+        }
+        Self { width, height, fps }
+    }
+}
+
+/// Smaller settings for an animated image export that came out at `size_bytes`, or `None`
+/// if it cannot shrink further.
+///
+/// Animated images have no bitrate to tune, so the size is brought down by lowering the
+/// framerate and the resolution, splitting the reduction evenly between them until the
+/// framerate reaches [`MIN_ANIMATED_IMAGE_FPS`].
+fn next_animated_image_settings(
+    current: AnimatedImageSettings,
+    size_bytes: u64,
+    target_size_bytes: u64,
+) -> Option<AnimatedImageSettings> {
+    let ratio = (target_size_bytes as f64 * ANIMATED_IMAGE_TARGET_FILL_RATIO
+        / size_bytes.max(1) as f64)
+        .clamp(0.01, 1.0);
+
+    let min_fps = MIN_ANIMATED_IMAGE_FPS.min(current.fps);
+    let fps = (current.fps * ratio.sqrt()).round().max(min_fps);
+    let area_ratio = (ratio * current.fps / fps).min(1.0);
+
+    let shorter_side = current.width.min(current.height);
+    let min_scale = (f64::from(MIN_ANIMATED_IMAGE_SIDE) / f64::from(shorter_side)).min(1.0);
+    let scale = area_ratio.sqrt().max(min_scale);
+    let even = |side: u32| (((f64::from(side) * scale).round() as u32) & !1).max(2);
+
+    let next = AnimatedImageSettings {
+        width: even(current.width),
+        height: even(current.height),
+        fps,
+    };
+    (next != current).then_some(next)
+}
+
+/// Animated image export: one attempt at the requested size and framerate, shrinking both
+/// until the file fits the target size. Long clips first measure a sample, like the bitrate
+/// calibration, to skip attempts that are bound to be too large.
+#[cfg(feature = "ffmpeg")]
+fn run_animated_image_export(
+    request: &ClipExportRequest,
+    export_work_dir: &Path,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+) -> Result<ExportOutcome> {
+    let export_started_at = Instant::now();
+    let target_size_bytes = target_size_bytes(request.target_size_mb);
+    let extension = request.container_format.extension();
+    let mut settings = AnimatedImageSettings::from_request(request);
+
+    if request.output_duration_secs() >= CALIBRATION_MIN_CLIP_DURATION_SECS {
+        let sample_request = build_calibration_sample_request(
+            request,
+            export_work_dir.join(format!("calibration-sample.{extension}")),
+        );
+        let sample_path = export_work_dir.join(format!("cal-sample.{extension}"));
+        let sample = match super::animated_export::attempt_animated_export(
+            &sample_request,
+            &sample_path,
+            settings,
+            progress_tx,
+            cancel_flag,
+            0,
+            1,
+            ClipExportPhase::Calibration,
+        )? {
+            Some(result) => result,
+            None => return Ok(ExportOutcome::Cancelled),
+        };
+        let _ = std::fs::remove_file(&sample_path);
+
+        let projected_size_bytes = (sample.size_bytes as f64 * request.output_duration_secs()
+            / sample_request.output_duration_secs().max(0.1))
+        .round() as u64;
+        if projected_size_bytes > target_size_bytes {
+            if let Some(next) =
+                next_animated_image_settings(settings, projected_size_bytes, target_size_bytes)
+            {
+                settings = next;
+            }
+        }
+        info!(
+            projected_size_bytes,
+            width = settings.width,
+            height = settings.height,
+            fps = settings.fps,
+            "Animated image calibration completed"
+        );
+    }
+
+    for attempt_index in 0..MAX_EXPORT_ATTEMPTS {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(ExportOutcome::Cancelled);
+        }
+
+        let output_path =
+            export_work_dir.join(format!("attempt-{}.{extension}", attempt_index + 1));
+        let attempt = match super::animated_export::attempt_animated_export(
+            request,
+            &output_path,
+            settings,
+            progress_tx,
+            cancel_flag,
+            attempt_index,
+            MAX_EXPORT_ATTEMPTS,
+            ClipExportPhase::SecondPass,
+        )? {
+            Some(result) => result,
+            None => return Ok(ExportOutcome::Cancelled),
+        };
+        info!(
+            attempt_index = attempt_index + 1,
+            width = settings.width,
+            height = settings.height,
+            fps = settings.fps,
+            size_bytes = attempt.size_bytes,
+            "Completed animated image export attempt"
+        );
+
+        if attempt.size_bytes <= target_size_bytes {
+            move_or_copy_file(&attempt.output_path, &request.output_path).with_context(|| {
+                format!(
+                    "Failed to move export output from {:?} to {:?}",
+                    attempt.output_path, request.output_path
+                )
+            })?;
+            info!(
+                elapsed_secs = export_started_at.elapsed().as_secs_f64(),
+                final_size_bytes = attempt.size_bytes,
+                target_size_bytes,
+                "Animated image export completed"
+            );
+            return Ok(ExportOutcome::Finished(request.output_path.clone()));
+        }
+        let _ = std::fs::remove_file(&attempt.output_path);
+
+        match next_animated_image_settings(settings, attempt.size_bytes, target_size_bytes) {
+            Some(next) => settings = next,
+            None => break,
+        }
+    }
+
+    bail!(
+        "Unable to fit the {} export in {} MB, even at {}x{} and {:.0} fps. Shorten the clip or raise the target size.",
+        request.container_format.short_label(),
+        request.target_size_mb,
+        settings.width,
+        settings.height,
+        settings.fps
+    );
+}
+
+/// Bitrate for the first attempt of a lossy audio-only export: the requested audio bitrate,
+/// lowered to what fits the target size over the whole output duration.
+fn audio_only_bitrate_kbps(request: &ClipExportRequest) -> u32 {
+    let duration_secs = request.output_duration_secs().max(0.1);
+    let overhead_bytes =
+        estimate_container_overhead_bytes(duration_secs, request.keep_ranges.len());
+    let audio_bytes = (target_size_bytes(request.target_size_mb) as f64
+        * AUDIO_ONLY_TARGET_FILL_RATIO)
+        .round() as u64;
+    let audio_bytes = audio_bytes.saturating_sub(overhead_bytes);
+    let fitting_kbps = (audio_bytes as f64 * 8.0 / duration_secs / 1000.0).floor() as u32;
+    request
+        .audio_bitrate_kbps
+        .min(fitting_kbps)
+        .max(MIN_AUDIO_ONLY_BITRATE_KBPS)
+}
+
+/// Lower bitrate for a lossy audio-only export that came out at `size_bytes`, or `None` if
+/// it is already at [`MIN_AUDIO_ONLY_BITRATE_KBPS`].
+fn next_audio_only_bitrate_kbps(
+    current_kbps: u32,
+    size_bytes: u64,
+    target_size_bytes: u64,
+) -> Option<u32> {
+    if current_kbps <= MIN_AUDIO_ONLY_BITRATE_KBPS {
+        return None;
+    }
+    let scaled = (f64::from(current_kbps) * target_size_bytes as f64 * AUDIO_ONLY_TARGET_FILL_RATIO
+        / size_bytes.max(1) as f64)
+        .floor() as u32;
+    let next = scaled
+        .min(current_kbps.saturating_sub(AUDIO_ONLY_BITRATE_STEP_KBPS))
+        .max(MIN_AUDIO_ONLY_BITRATE_KBPS);
+    Some(next)
+}
+
+/// Audio-only export. The target size only limits the audio bitrate of the lossy formats,
+/// which is lowered and retried while the file comes out too large. FLAC and WAV are
+/// exported once, whatever their size.
+#[cfg(feature = "ffmpeg")]
+fn run_audio_only_export(
+    request: &ClipExportRequest,
+    export_work_dir: &Path,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+) -> Result<ExportOutcome> {
+    if !request.metadata.has_audio {
+        bail!("This clip has no audio to export");
+    }
+
+    let export_started_at = Instant::now();
+    let target_size_bytes = target_size_bytes(request.target_size_mb);
+    let extension = request.container_format.extension();
+    let lossless = request.container_format.is_lossless_audio();
+    let attempt_count = if lossless { 1 } else { MAX_EXPORT_ATTEMPTS };
+    let mut audio_bitrate_kbps = audio_only_bitrate_kbps(request);
+
+    for attempt_index in 0..attempt_count {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Ok(ExportOutcome::Cancelled);
+        }
+
+        let output_path =
+            export_work_dir.join(format!("attempt-{}.{extension}", attempt_index + 1));
+        let attempt = match super::sdk_export::attempt_audio_export(
+            request,
+            &output_path,
+            audio_bitrate_kbps,
+            progress_tx,
+            cancel_flag,
+            attempt_index,
+            attempt_count,
+            ClipExportPhase::SecondPass,
+        )? {
+            Some(result) => result,
+            None => return Ok(ExportOutcome::Cancelled),
+        };
+        info!(
+            attempt_index = attempt_index + 1,
+            audio_bitrate_kbps,
+            size_bytes = attempt.size_bytes,
+            "Completed audio-only export attempt"
+        );
+
+        if attempt.size_bytes > target_size_bytes && !lossless {
+            let _ = std::fs::remove_file(&attempt.output_path);
+            match next_audio_only_bitrate_kbps(
+                audio_bitrate_kbps,
+                attempt.size_bytes,
+                target_size_bytes,
+            ) {
+                Some(next) => {
+                    audio_bitrate_kbps = next;
+                    continue;
+                }
+                None => break,
+            }
+        }
+        if attempt.size_bytes > target_size_bytes {
+            warn!(
+                size_bytes = attempt.size_bytes,
+                target_size_bytes,
+                "{} export exceeds the target size; lossless audio cannot be shrunk",
+                request.container_format.short_label()
+            );
+        }
+
+        move_or_copy_file(&attempt.output_path, &request.output_path).with_context(|| {
+            format!(
+                "Failed to move export output from {:?} to {:?}",
+                attempt.output_path, request.output_path
+            )
+        })?;
+        info!(
+            elapsed_secs = export_started_at.elapsed().as_secs_f64(),
+            final_size_bytes = attempt.size_bytes,
+            target_size_bytes,
+            "Audio-only export completed"
+        );
+        return Ok(ExportOutcome::Finished(request.output_path.clone()));
+    }
+
+    bail!(
+        "Unable to fit the {} export in {} MB, even at {} kbps. Shorten the clip or raise the target size.",
+        request.container_format.short_label(),
+        request.target_size_mb,
+        audio_bitrate_kbps
+    );
+}
+
 enum SearchOutcome {
     Selected(ExportAttemptResult),
     Cancelled,
 }
 
+/// Runs one re-encoding attempt of `request`, or, for a montage, of the part of the montage
+/// that the kept ranges of `request` cover on its timeline.
+#[allow(clippy::too_many_arguments)]
+fn attempt_reencode_export(
+    request: &ClipExportRequest,
+    montage: Option<&MontageExportRequest>,
+    output_path: &Path,
+    video_bitrate_kbps: u32,
+    audio_bitrate_kbps: u32,
+    encoder: ExportVideoEncoder,
+    progress_tx: &Sender<ClipExportUpdate>,
+    cancel_flag: &Arc<AtomicBool>,
+    attempt_index: usize,
+    attempt_count: usize,
+    phase: ClipExportPhase,
+) -> Result<Option<ExportAttemptResult>> {
+    match montage {
+        Some(montage) => super::montage_export::attempt_montage_export(
+            &montage.for_timeline_ranges(&request.keep_ranges),
+            output_path,
+            video_bitrate_kbps,
+            audio_bitrate_kbps,
+            encoder,
+            progress_tx,
+            cancel_flag,
+            attempt_index,
+            attempt_count,
+            phase,
+        ),
+        None => super::sdk_export::attempt_export(
+            request,
+            output_path,
+            video_bitrate_kbps,
+            audio_bitrate_kbps,
+            encoder,
+            progress_tx,
+            cancel_flag,
+            attempt_index,
+            attempt_count,
+            phase,
+        ),
+    }
+}
+
 fn calibrate_initial_bitrate(
     request: &ClipExportRequest,
+    montage: Option<&MontageExportRequest>,
     budget: &SizeBudget,
     encoder: ExportVideoEncoder,
     export_work_dir: &Path,
@@ -647,23 +1373,24 @@ fn calibrate_initial_bitrate(
     }
 
     // Build cache key from export parameters so re-exports of the same clip
-    // with identical settings skip redundant calibration.
+    // with identical settings skip redundant calibration. Montages are not cached:
+    // their timeline is not identified by a single input path.
     let (output_width, output_height) = resolved_output_dimensions(request);
     let output_fps = resolved_output_fps_for_request(request);
-    let cache_key = CalibrationCacheKey {
+    let cache_key = montage.is_none().then(|| CalibrationCacheKey {
         input_path: request.input_path.clone(),
         encoder,
         output_width,
         output_height,
         output_fps_bits: output_fps.to_bits(),
-    };
+    });
 
     // Check cache before running expensive calibration
-    {
+    if let Some(cache_key) = cache_key.as_ref() {
         let cache = calibration_cache()
             .lock()
             .expect("Calibration cache lock poisoned");
-        if let Some(&cached_bitrate) = cache.get(&cache_key) {
+        if let Some(&cached_bitrate) = cache.get(cache_key) {
             info!(
                 encoder = encoder.ffmpeg_name(),
                 cached_bitrate_kbps = cached_bitrate,
@@ -699,8 +1426,9 @@ fn calibrate_initial_bitrate(
         "Starting export calibration"
     );
 
-    let low_result = match super::sdk_export::attempt_export(
+    let low_result = match attempt_reencode_export(
         &sample_request,
+        montage,
         &export_work_dir.join("cal-low.mp4"),
         low_bitrate_kbps,
         budget.audio_bitrate_kbps,
@@ -721,8 +1449,9 @@ fn calibrate_initial_bitrate(
     // Release FFmpeg internal memory pools before the high calibration run
     super::sdk_export::release_ffmpeg_frame_pools();
 
-    let high_result = match super::sdk_export::attempt_export(
+    let high_result = match attempt_reencode_export(
         &sample_request,
+        montage,
         &export_work_dir.join("cal-high.mp4"),
         high_bitrate_kbps,
         budget.audio_bitrate_kbps,
@@ -779,7 +1508,7 @@ fn calibrate_initial_bitrate(
     .unwrap_or(budget.initial_video_bitrate_kbps);
 
     // Store the calibrated result in cache for future reuse
-    {
+    if let Some(cache_key) = cache_key {
         let mut cache = calibration_cache()
             .lock()
             .expect("Calibration cache lock poisoned");
@@ -798,8 +1527,10 @@ fn calibrate_initial_bitrate(
     Ok(Some(calibrated))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_bitrate_search(
     request: &ClipExportRequest,
+    montage: Option<&MontageExportRequest>,
     budget: &SizeBudget,
     encoder: ExportVideoEncoder,
     initial_video_bitrate_kbps: u32,
@@ -819,8 +1550,9 @@ fn run_bitrate_search(
 
         let output_path = export_work_dir.join(format!("attempt-{}.mp4", attempt_index + 1));
         let attempt_started_at = Instant::now();
-        let attempt_result = match super::sdk_export::attempt_export(
+        let attempt_result = match attempt_reencode_export(
             request,
+            montage,
             &output_path,
             current_video_bitrate_kbps,
             budget.audio_bitrate_kbps,
@@ -1072,7 +1804,7 @@ fn build_calibration_sample_request(
     sample_request
 }
 
-fn slice_keep_ranges_for_output_window(
+pub(super) fn slice_keep_ranges_for_output_window(
     keep_ranges: &[TimeRange],
     window_start_secs: f64,
     window_duration_secs: f64,
@@ -1084,9 +1816,9 @@ fn slice_keep_ranges_for_output_window(
     let mut output_cursor = 0.0;
 
     for range in keep_ranges {
-        let range_duration = range.duration_secs();
+        let speed = range.effective_speed();
         let range_start = output_cursor;
-        let range_end = output_cursor + range_duration;
+        let range_end = output_cursor + range.output_duration_secs();
 
         if range_end <= window_start_secs {
             output_cursor = range_end;
@@ -1100,8 +1832,9 @@ fn slice_keep_ranges_for_output_window(
         let overlap_end = window_end_secs.min(range_end);
         if overlap_end > overlap_start {
             sliced.push(TimeRange {
-                start_secs: range.start_secs + (overlap_start - range_start),
-                end_secs: range.start_secs + (overlap_end - range_start),
+                start_secs: range.start_secs + (overlap_start - range_start) * speed,
+                end_secs: range.start_secs + (overlap_end - range_start) * speed,
+                speed: range.speed,
             });
         }
 
@@ -1262,10 +1995,12 @@ impl ExportVideoEncoder {
     }
 
     /// Returns the codec name for this encoder when used with the given container format.
-    /// For WebM containers, overrides to VP9 software encoder since H.265 is not supported.
+    /// For WebM containers, overrides to VP9 software encoder since H.265 is not supported;
+    /// the animated image formats always use their own software encoder.
     pub fn ffmpeg_name_for_container(self, container: ExportContainerFormat) -> &'static str {
         match container {
             ExportContainerFormat::WebM => "libvpx-vp9",
+            _ if container.is_animated_image() => container.default_video_codec_name(),
             _ => self.ffmpeg_name(),
         }
     }
@@ -1305,7 +2040,7 @@ fn ordered_encoders_for_container(
     preferred: EncoderType,
     container: ExportContainerFormat,
 ) -> Vec<ExportVideoEncoder> {
-    if container == ExportContainerFormat::WebM {
+    if container == ExportContainerFormat::WebM || container.is_animated_image() {
         // WebM uses VP9 — hardware encoders are not commonly available,
         // so we always fall back to software libvpx-vp9 via SoftwareHevc
         // (which gets remapped to libvpx-vp9 at codec selection time).
@@ -1394,6 +2129,22 @@ fn cleanup_export_work_dir(work_dir: &Path) {
     let _ = std::fs::remove_dir_all(work_dir);
 }
 
+/// Creates a fresh temporary directory for the files of one export.
+fn create_export_work_dir() -> Result<PathBuf> {
+    let export_work_dir = std::env::temp_dir().join(format!(
+        "liteclip-export-{}-{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis()
+    ));
+    std::fs::create_dir_all(&export_work_dir).with_context(|| {
+        format!(
+            "Failed to create temporary export directory {:?}",
+            export_work_dir
+        )
+    })?;
+    Ok(export_work_dir)
+}
+
 struct WorkDirGuard {
     path: Option<PathBuf>,
 }
@@ -1423,6 +2174,7 @@ mod tests {
             keep_ranges: vec![TimeRange {
                 start_secs: 0.0,
                 end_secs: 120.0,
+                speed: 1.0,
             }],
             target_size_mb: 8,
             audio_bitrate_kbps: 128,
@@ -1433,18 +2185,148 @@ mod tests {
                 width: 2560,
                 height: 1440,
                 has_audio: true,
+                audio_track_count: 1,
                 fps: 60.0,
+                clip_metadata: None,
+                chapters: Vec::new(),
             },
             stream_copy: false,
+            smart_cut: false,
             output_width: None,
             output_height: None,
             output_fps: None,
             crop: None,
             post_process_filters: true,
             container_format: ExportContainerFormat::Mp4,
+            audio_tracks: ExportAudioTracks::Keep,
+            overlays: Vec::new(),
+            audio: ExportAudioMix::default(),
+            loudness: None,
         }
     }
 
+    #[test]
+    fn only_mixing_several_audio_tracks_requires_reencode() {
+        assert!(ExportAudioTracks::Mix.requires_reencode(2));
+        assert!(!ExportAudioTracks::Mix.requires_reencode(1));
+        assert!(!ExportAudioTracks::Keep.requires_reencode(2));
+        assert!(!ExportAudioTracks::PrimaryOnly.requires_reencode(2));
+    }
+
+    #[test]
+    fn animated_image_settings_respect_request_and_gif_limit() {
+        let mut request = base_request();
+        request.container_format = ExportContainerFormat::Gif;
+        request.output_width = Some(640);
+        request.output_height = Some(360);
+        assert_eq!(
+            AnimatedImageSettings::from_request(&request),
+            AnimatedImageSettings {
+                width: 640,
+                height: 360,
+                fps: MAX_GIF_FPS,
+            }
+        );
+
+        request.container_format = ExportContainerFormat::Apng;
+        assert_eq!(AnimatedImageSettings::from_request(&request).fps, 60.0);
+        assert!(!request.container_format.supports_stream_copy());
+    }
+
+    #[test]
+    fn animated_image_search_lowers_fps_and_resolution() {
+        let current = AnimatedImageSettings {
+            width: 1280,
+            height: 720,
+            fps: 30.0,
+        };
+        let next = next_animated_image_settings(current, 40 * 1024 * 1024, 10 * 1024 * 1024)
+            .expect("settings should shrink");
+        assert!(next.fps < current.fps && next.fps >= MIN_ANIMATED_IMAGE_FPS);
+        assert!(next.width < current.width && next.height < current.height);
+        assert_eq!(next.width % 2, 0);
+        // Aspect ratio is preserved within rounding.
+        let aspect = f64::from(next.width) / f64::from(next.height);
+        assert!((aspect - 16.0 / 9.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn audio_only_bitrate_fits_the_target_size() {
+        let mut request = base_request();
+        request.container_format = ExportContainerFormat::M4a;
+        assert_eq!(audio_only_bitrate_kbps(&request), 128);
+
+        request.target_size_mb = 1;
+        let fitted = audio_only_bitrate_kbps(&request);
+        assert!(fitted < 128 && fitted > MIN_AUDIO_ONLY_BITRATE_KBPS);
+        let projected_bytes = f64::from(fitted) * 1000.0 / 8.0 * 120.0;
+        assert!(projected_bytes < target_size_bytes(1) as f64);
+
+        request.keep_ranges[0].end_secs = 1200.0;
+        request.metadata.duration_secs = 1200.0;
+        assert_eq!(
+            audio_only_bitrate_kbps(&request),
+            MIN_AUDIO_ONLY_BITRATE_KBPS
+        );
+    }
+
+    #[test]
+    fn audio_only_retry_lowers_bitrate_until_the_floor() {
+        let target = 4 * 1024 * 1024;
+        let next = next_audio_only_bitrate_kbps(128, 2 * target, target).unwrap();
+        assert!(next <= 64 && next >= MIN_AUDIO_ONLY_BITRATE_KBPS);
+
+        // Barely over target still steps down.
+        let next = next_audio_only_bitrate_kbps(128, target + 1, target).unwrap();
+        assert!(next <= 128 - AUDIO_ONLY_BITRATE_STEP_KBPS);
+
+        assert_eq!(
+            next_audio_only_bitrate_kbps(MIN_AUDIO_ONLY_BITRATE_KBPS, 2 * target, target),
+            None
+        );
+    }
+
+    #[test]
+    fn audio_only_formats_skip_video_paths() {
+        for format in [
+            ExportContainerFormat::M4a,
+            ExportContainerFormat::Opus,
+            ExportContainerFormat::Flac,
+            ExportContainerFormat::Wav,
+        ] {
+            assert!(format.is_audio_only());
+            assert!(!format.is_animated_image());
+            assert!(!format.supports_stream_copy());
+        }
+        assert!(ExportContainerFormat::Flac.is_lossless_audio());
+        assert!(!ExportContainerFormat::Opus.is_lossless_audio());
+        assert!(ExportContainerFormat::M4a.supports_multiple_audio_tracks());
+        assert!(!ExportContainerFormat::Wav.supports_multiple_audio_tracks());
+    }
+
+    #[test]
+    fn animated_image_search_stops_at_the_floors() {
+        let floor = AnimatedImageSettings {
+            width: 256,
+            height: MIN_ANIMATED_IMAGE_SIDE,
+            fps: MIN_ANIMATED_IMAGE_FPS,
+        };
+        assert_eq!(
+            next_animated_image_settings(floor, 100 * 1024 * 1024, 1024 * 1024),
+            None
+        );
+
+        // At the framerate floor only the resolution shrinks.
+        let slow = AnimatedImageSettings {
+            width: 1920,
+            height: 1080,
+            fps: MIN_ANIMATED_IMAGE_FPS,
+        };
+        let next = next_animated_image_settings(slow, 4 * 1024 * 1024, 1024 * 1024).unwrap();
+        assert_eq!(next.fps, MIN_ANIMATED_IMAGE_FPS);
+        assert!(next.height >= MIN_ANIMATED_IMAGE_SIDE && next.height < 1080);
+    }
+
     #[test]
     fn estimate_export_bitrates_scales_audio_down_for_small_budgets() {
         let estimate = estimate_export_bitrates(1, 20.0, true, 128, 2, false);
@@ -1471,10 +2353,12 @@ mod tests {
             TimeRange {
                 start_secs: 0.0,
                 end_secs: 5.0,
+                speed: 1.0,
             },
             TimeRange {
                 start_secs: 10.0,
                 end_secs: 20.0,
+                speed: 1.0,
             },
         ];
 
@@ -1483,6 +2367,55 @@ mod tests {
         assert!(sample.output_duration_secs() <= CALIBRATION_MAX_SAMPLE_SECS);
     }
 
+    #[test]
+    fn retimed_ranges_set_output_duration_and_size_budget() {
+        let mut request = base_request();
+        request.keep_ranges = vec![
+            TimeRange {
+                start_secs: 0.0,
+                end_secs: 10.0,
+                speed: 0.5,
+            },
+            TimeRange {
+                start_secs: 20.0,
+                end_secs: 30.0,
+                speed: 2.0,
+            },
+            TimeRange {
+                start_secs: 40.0,
+                end_secs: 44.0,
+                speed: 100.0,
+            },
+        ];
+        assert!(request.has_retimed_ranges());
+        assert!((request.output_duration_secs() - 26.0).abs() < 1e-9);
+
+        let budget = SizeBudget::from_request(&request, ExportVideoEncoder::SoftwareHevc);
+        assert!((budget.output_duration_secs - 26.0).abs() < 1e-9);
+
+        assert!(!TimeRange::new(0.0, 1.0).is_retimed());
+        let nan = TimeRange {
+            speed: f64::NAN,
+            ..TimeRange::new(0.0, 1.0)
+        };
+        assert_eq!(nan.effective_speed(), 1.0);
+    }
+
+    #[test]
+    fn output_window_slices_retimed_ranges_in_source_time() {
+        let ranges = [TimeRange {
+            start_secs: 10.0,
+            end_secs: 20.0,
+            speed: 0.5,
+        }];
+        // The range lasts 20 s in the output; output 4..8 s is source 12..14 s.
+        let sliced = slice_keep_ranges_for_output_window(&ranges, 4.0, 4.0);
+        assert_eq!(sliced.len(), 1);
+        assert!((sliced[0].start_secs - 12.0).abs() < 1e-9);
+        assert!((sliced[0].end_secs - 14.0).abs() < 1e-9);
+        assert_eq!(sliced[0].speed, 0.5);
+    }
+
     #[test]
     fn solve_power_bitrate_matches_linear_curve() {
         let solved = solve_power_bitrate(200, 25_000.0, 400, 50_000.0, 37_500.0, 100, 1_000)
@@ -63,6 +63,11 @@ impl AppDirs {
         })
     }
 
+    /// File holding unfinished batch exports (see [`crate::output::ExportQueue::open`]).
+    pub fn export_queue_file(&self) -> PathBuf {
+        self.config_dir.join("export_queue.json")
+    }
+
     /// Default `general.save_directory` string matching historic [`crate::config`] defaults, but using [`Self::clips_folder_name`].
     pub fn default_save_directory_string(&self) -> String {
         dirs::video_dir()
@@ -584,7 +584,10 @@ mod tests {
             width: 1920,
             height: 1080,
             has_audio: false,
+            audio_track_count: 0,
             fps: 60.0,
+            clip_metadata: None,
+            chapters: Vec::new(),
         };
         let violations = validate_export_validity(ExportValidationInput {
             expected_duration_secs: 10.0,
@@ -606,7 +609,10 @@ mod tests {
             width: 1920,
             height: 1080,
             has_audio: true,
+            audio_track_count: 1,
             fps: 59.94,
+            clip_metadata: None,
+            chapters: Vec::new(),
         };
         let violations = validate_export_validity(ExportValidationInput {
             expected_duration_secs: 60.0,
@@ -13,8 +13,14 @@ mod common;
 use bytes::Bytes;
 use common::builders::ConfigBuilder;
 use common::fixtures::make_packet_sequence;
-use liteclip_core::buffer::ring::LockFreeReplayBuffer;
+use liteclip_core::app::{ClipManager, ClipSavePlan};
+use liteclip_core::buffer::ring::{
+    qpc_frequency, LockFreeReplayBuffer, SavedClip, SharedReplayBuffer,
+};
+use liteclip_core::config::ClipSaveMode;
 use liteclip_core::encode::{EncodedPacket, StreamType};
+use liteclip_core::output::ClipWindow;
+use std::time::Duration;
 use tempfile::TempDir;
 
 /// Test: Buffer snapshot can be written to a byte stream.
@@ -336,6 +342,393 @@ fn clip_metadata_tracking() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Test: Clip windows resolve to keyframe-aligned PTS ranges.
+///
+/// "N seconds ending M seconds ago" maps to a fixed PTS range, and the range
+/// snapshot starts at the keyframe preceding the window start.
+#[test]
+fn clip_window_range_is_keyframe_aligned() -> anyhow::Result<()> {
+    let config = ConfigBuilder::new()
+        .with_replay_duration(60)
+        .with_memory_limit(512)
+        .build();
+
+    let buffer = LockFreeReplayBuffer::new(&config)?;
+
+    // 20 seconds at 10 packets/s with a keyframe every second.
+    let qpc_freq = qpc_frequency();
+    let interval = qpc_freq / 10;
+    for packet in make_packet_sequence(200, interval, 10) {
+        buffer.push(packet);
+    }
+    let newest = buffer.newest_pts().expect("buffer has packets");
+    let oldest = buffer.oldest_pts();
+
+    let window = ClipWindow::EndingAgo {
+        duration: Duration::from_secs(5),
+        ago: Duration::from_secs(10),
+    };
+    let (start, end) = window.resolve(newest, oldest)?;
+    let end = end.expect("EndingAgo has a fixed end");
+    assert_eq!(end, newest - 10 * qpc_freq);
+    assert_eq!(start, end - 5 * qpc_freq);
+
+    // Start mid-GOP: the snapshot must reach back to the preceding keyframe.
+    let mid_gop_start = start + interval / 2;
+    let snapshot = buffer.snapshot_range(mid_gop_start, end)?;
+    let first = snapshot.first().expect("range has packets");
+    assert!(
+        first.is_keyframe,
+        "Range snapshot should start on a keyframe"
+    );
+    assert!(first.pts <= mid_gop_start);
+    assert!(snapshot.iter().all(|p| p.pts <= end));
+
+    // Inverted ranges and windows that have left the buffer are rejected.
+    let inverted = ClipWindow::PtsRange {
+        start_pts: 10,
+        end_pts: 5,
+    };
+    assert!(inverted.resolve(newest, oldest).is_err());
+    let expired = ClipWindow::EndingAgo {
+        duration: Duration::from_secs(1),
+        ago: Duration::from_secs(60),
+    };
+    assert!(expired.resolve(newest, oldest).is_err());
+
+    Ok(())
+}
+
+/// Creates a previously saved clip file covering `start_pts..=end_pts`.
+fn saved_clip_at(dir: &TempDir, start_pts: i64, end_pts: i64) -> anyhow::Result<SavedClip> {
+    let path = dir.path().join("previous.mp4");
+    std::fs::write(&path, b"clip")?;
+    Ok(SavedClip {
+        path,
+        start_pts,
+        end_pts,
+    })
+}
+
+/// Test: Saves restart the buffer unless another mode is configured.
+#[test]
+fn clip_save_mode_defaults_to_restart() {
+    let config = ConfigBuilder::new().build();
+    assert_eq!(config.general.clip_save_mode, ClipSaveMode::Restart);
+
+    let config = ConfigBuilder::new()
+        .with_clip_save_mode(ClipSaveMode::Extend)
+        .build();
+    assert_eq!(config.general.clip_save_mode, ClipSaveMode::Extend);
+}
+
+/// Test: Overlapping saves produce full independent clips outside extend mode.
+#[test]
+fn overlapping_save_is_independent_clip() -> anyhow::Result<()> {
+    let temp = TempDir::new()?;
+    let previous = saved_clip_at(&temp, 1_000, 5_000)?;
+
+    for mode in [ClipSaveMode::Restart, ClipSaveMode::Independent] {
+        let plan = ClipManager::plan_save(mode, Some(&previous), 3_000, Some(0));
+        assert_eq!(plan, ClipSavePlan::NewClip, "{:?} should not extend", mode);
+    }
+    assert_eq!(
+        ClipManager::plan_save(ClipSaveMode::Extend, None, 3_000, Some(0)),
+        ClipSavePlan::NewClip
+    );
+
+    Ok(())
+}
+
+/// Test: In extend mode an overlapping save rewrites the previous clip from its start.
+///
+/// Falls back to a new clip when the windows do not overlap, the previous start
+/// has been evicted, or the previous file is gone.
+#[test]
+fn overlapping_save_extends_previous_clip() -> anyhow::Result<()> {
+    let temp = TempDir::new()?;
+    let previous = saved_clip_at(&temp, 1_000, 5_000)?;
+
+    let plan = ClipManager::plan_save(ClipSaveMode::Extend, Some(&previous), 3_000, Some(0));
+    assert_eq!(
+        plan,
+        ClipSavePlan::ExtendPrevious {
+            path: previous.path.clone(),
+            start_pts: 1_000,
+        }
+    );
+
+    let gap = ClipManager::plan_save(ClipSaveMode::Extend, Some(&previous), 6_000, Some(0));
+    assert_eq!(gap, ClipSavePlan::NewClip);
+
+    let evicted = ClipManager::plan_save(ClipSaveMode::Extend, Some(&previous), 3_000, Some(2_000));
+    assert_eq!(evicted, ClipSavePlan::NewClip);
+
+    std::fs::remove_file(&previous.path)?;
+    let missing = ClipManager::plan_save(ClipSaveMode::Extend, Some(&previous), 3_000, Some(0));
+    assert_eq!(missing, ClipSavePlan::NewClip);
+
+    Ok(())
+}
+
+/// Test: Recording a saved window leaves the buffer contents intact.
+///
+/// Non-destructive modes rely on the buffer still holding the saved window so
+/// the next save can overlap it; only a restart forgets the previous save.
+#[test]
+fn saved_clip_record_keeps_buffer_until_restart() -> anyhow::Result<()> {
+    let temp = TempDir::new()?;
+    let config = ConfigBuilder::new()
+        .with_replay_duration(30)
+        .with_memory_limit(256)
+        .with_clip_save_mode(ClipSaveMode::Independent)
+        .build();
+
+    let buffer = SharedReplayBuffer::new(&config)?;
+    for packet in make_packet_sequence(60, 1_000_000 / 30, 30) {
+        buffer.push(packet);
+    }
+
+    let previous = saved_clip_at(&temp, 0, buffer.newest_pts().unwrap_or_default())?;
+    buffer.record_saved_clip(previous.clone());
+    assert_eq!(buffer.last_saved_clip(), Some(previous));
+    assert_eq!(buffer.snapshot()?.len(), 60);
+
+    buffer.restart();
+    assert_eq!(buffer.last_saved_clip(), None);
+    assert_eq!(buffer.stats().packet_count, 0);
+
+    Ok(())
+}
+
+/// Encodes `frames` synthetic 30 fps frames starting at frame `first` with libx265 and
+/// pushes the packets into `buffer`.
+///
+/// Each call flushes its own encoder, so every batch starts on a keyframe.
+#[cfg(feature = "ffmpeg")]
+fn encode_frames(buffer: &SharedReplayBuffer, first: i64, frames: i64) -> anyhow::Result<()> {
+    use liteclip_core::config::{QualityPreset, RateControl};
+    use liteclip_core::encode::{
+        ffmpeg::FfmpegEncoder, Encoder, ResolvedEncoderConfig, ResolvedEncoderType,
+    };
+    use liteclip_core::media::CapturedFrame;
+
+    let enc_cfg = ResolvedEncoderConfig {
+        bitrate_mbps: 2,
+        framerate: 30,
+        resolution: (320, 180),
+        use_native_resolution: false,
+        encoder_type: ResolvedEncoderType::Software,
+        quality_preset: QualityPreset::Performance,
+        rate_control: RateControl::Cbr,
+        quality_value: None,
+        keyframe_interval_secs: 1,
+        use_cpu_readback: true,
+        output_index: 0,
+    };
+    let mut encoder = FfmpegEncoder::new(&enc_cfg)?;
+    encoder.init(&enc_cfg)?;
+
+    let frame_interval = qpc_frequency().max(1) / 30;
+    for i in first..first + frames {
+        let shade = (i * 4 % 256) as u8;
+        let frame = CapturedFrame {
+            bgra: Bytes::from(vec![shade; 320 * 180 * 4]),
+            #[cfg(windows)]
+            d3d11: None,
+            timestamp: i * frame_interval,
+            resolution: (320, 180),
+        };
+        encoder.encode_frame(&frame)?;
+        while let Ok(packet) = encoder.packet_rx().try_recv() {
+            buffer.push(packet);
+        }
+    }
+    for packet in encoder.flush()? {
+        buffer.push(packet);
+    }
+    while let Ok(packet) = encoder.packet_rx().try_recv() {
+        buffer.push(packet);
+    }
+    Ok(())
+}
+
+/// Config saving into `temp` with `mode`, without audio or thumbnails.
+#[cfg(feature = "ffmpeg")]
+fn save_mode_config(temp: &TempDir, mode: ClipSaveMode) -> liteclip_core::config::Config {
+    let mut config = ConfigBuilder::new()
+        .with_replay_duration(5)
+        .with_framerate(30)
+        .with_memory_limit(256)
+        .with_save_dir(temp.path().join("clips"))
+        .with_clip_save_mode(mode)
+        .build();
+    config.general.generate_clip_thumbnail = false;
+    config.general.write_metadata_sidecar = false;
+    config.audio.capture_system = false;
+    config.audio.capture_mic = false;
+    config
+}
+
+/// Clip files (any container) saved under `dir`, recursively.
+#[cfg(feature = "ffmpeg")]
+fn clip_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(clip_files(&path));
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("mp4" | "mkv")
+        ) {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Test: Independent and extend saves keep the replay buffer and record the saved window.
+#[cfg(feature = "ffmpeg")]
+#[test]
+fn save_clip_keeps_buffer_outside_restart_mode() -> anyhow::Result<()> {
+    ffmpeg_next::init().ok();
+    let rt = tokio::runtime::Runtime::new()?;
+
+    for mode in [ClipSaveMode::Independent, ClipSaveMode::Extend] {
+        let temp = TempDir::new()?;
+        let config = save_mode_config(&temp, mode);
+        let buffer = SharedReplayBuffer::new(&config)?;
+        encode_frames(&buffer, 0, 60)?;
+        let before = buffer.stats();
+        let newest = buffer.newest_pts();
+
+        let path = rt.block_on(ClipManager::save_clip(&config, &buffer, None, None))?;
+
+        assert!(path.exists(), "{:?} save should write a clip", mode);
+        let after = buffer.stats();
+        assert_eq!(after.packet_count, before.packet_count, "{:?}", mode);
+        assert_eq!(after.total_bytes, before.total_bytes, "{:?}", mode);
+        assert_eq!(buffer.newest_pts(), newest, "{:?}", mode);
+        let saved = buffer
+            .last_saved_clip()
+            .expect("saved window should be recorded");
+        assert_eq!(saved.path, path, "{:?}", mode);
+        assert_eq!(Some(saved.end_pts), newest, "{:?}", mode);
+    }
+
+    Ok(())
+}
+
+/// Test: A restart save clears the replay buffer and forgets the saved window.
+#[cfg(feature = "ffmpeg")]
+#[test]
+fn save_clip_restart_clears_buffer() -> anyhow::Result<()> {
+    ffmpeg_next::init().ok();
+    let rt = tokio::runtime::Runtime::new()?;
+    let temp = TempDir::new()?;
+    let config = save_mode_config(&temp, ClipSaveMode::Restart);
+    let buffer = SharedReplayBuffer::new(&config)?;
+    encode_frames(&buffer, 0, 60)?;
+    assert!(buffer.stats().packet_count > 0);
+
+    let path = rt.block_on(ClipManager::save_clip(&config, &buffer, None, None))?;
+
+    assert!(path.exists());
+    assert_eq!(buffer.stats().packet_count, 0);
+    assert_eq!(buffer.newest_pts(), None);
+    assert_eq!(buffer.last_saved_clip(), None);
+
+    Ok(())
+}
+
+/// Test: An overlapping save in extend mode rewrites the previous clip in place.
+///
+/// The second save returns the first clip's path, leaves no second clip or temporary file
+/// behind, and the rewritten clip runs from the first save's start to the newest packet.
+#[cfg(feature = "ffmpeg")]
+#[test]
+fn extend_save_replaces_previous_clip_in_place() -> anyhow::Result<()> {
+    use liteclip_core::output::video_file::probe_video_file;
+
+    ffmpeg_next::init().ok();
+    let rt = tokio::runtime::Runtime::new()?;
+    let temp = TempDir::new()?;
+    let config = save_mode_config(&temp, ClipSaveMode::Extend);
+    let buffer = SharedReplayBuffer::new(&config)?;
+
+    encode_frames(&buffer, 0, 45)?;
+    let first = rt.block_on(ClipManager::save_clip(&config, &buffer, None, None))?;
+    let first_saved = buffer.last_saved_clip().expect("first save recorded");
+    let first_duration = probe_video_file(&first)?.duration_secs;
+
+    encode_frames(&buffer, 45, 45)?;
+    let second = rt.block_on(ClipManager::save_clip(&config, &buffer, None, None))?;
+
+    assert_eq!(second, first, "extend save should reuse the previous path");
+    assert_eq!(clip_files(&temp.path().join("clips")), vec![first.clone()]);
+    let extended = buffer.last_saved_clip().expect("extended save recorded");
+    assert_eq!(extended.start_pts, first_saved.start_pts);
+    assert_eq!(Some(extended.end_pts), buffer.newest_pts());
+    assert!(extended.end_pts > first_saved.end_pts);
+
+    let extended_duration = probe_video_file(&second)?.duration_secs;
+    assert!(
+        extended_duration > first_duration + 1.0,
+        "extended clip should be ~1.5 s longer ({:.2} s -> {:.2} s)",
+        first_duration,
+        extended_duration
+    );
+
+    Ok(())
+}
+
+/// Test: Bookmarks are keyed by the newest buffered PTS and filtered by window.
+///
+/// The saver asks for the bookmarks inside the clip window; a restart forgets
+/// them along with the replay.
+#[test]
+fn bookmarks_follow_buffered_replay() -> anyhow::Result<()> {
+    let config = ConfigBuilder::new()
+        .with_replay_duration(30)
+        .with_memory_limit(256)
+        .build();
+
+    let buffer = SharedReplayBuffer::new(&config)?;
+    assert_eq!(buffer.add_bookmark(None), None);
+
+    let packets = make_packet_sequence(60, 1_000_000 / 30, 30);
+    let (first_half, second_half) = packets.split_at(30);
+    for packet in first_half {
+        buffer.push(packet.clone());
+    }
+    let early = buffer
+        .add_bookmark(Some("  clutch  ".to_string()))
+        .expect("bookmark while buffered");
+    assert_eq!(early.pts, first_half[29].pts);
+    assert_eq!(early.label.as_deref(), Some("clutch"));
+
+    for packet in second_half {
+        buffer.push(packet.clone());
+    }
+    let late = buffer
+        .add_bookmark(Some(" ".to_string()))
+        .expect("bookmark while buffered");
+    assert_eq!(late.label, None);
+
+    assert_eq!(
+        buffer.bookmarks_in(0, late.pts),
+        vec![early.clone(), late.clone()]
+    );
+    assert_eq!(buffer.bookmarks_in(early.pts + 1, late.pts), vec![late]);
+    assert!(buffer.bookmarks_in(late.pts, early.pts).is_empty());
+
+    buffer.restart();
+    assert!(buffer.bookmarks_in(i64::MIN, i64::MAX).is_empty());
+
+    Ok(())
+}
+
 /// Helper function to create packet sequence with resolution
 fn make_packet_sequence_with_resolution(
     count: usize,
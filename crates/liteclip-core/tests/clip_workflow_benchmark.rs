@@ -21,8 +21,8 @@ mod common;
 
 use liteclip_core::config::EncoderType;
 use liteclip_core::output::video_file::{
-    ClipExportPhase, ClipExportRequest, ClipExportUpdate, ExportContainerFormat, TimeRange,
-    VideoFileMetadata,
+    ClipExportPhase, ClipExportRequest, ClipExportUpdate, ExportAudioTracks, ExportContainerFormat,
+    TimeRange, VideoFileMetadata,
 };
 
 /// Target input size for the test clip (4MB in bytes)
@@ -101,10 +101,12 @@ fn benchmark_4mb_clip_split_and_reencode_to_1mb() -> anyhow::Result<()> {
     let first_segment = TimeRange {
         start_secs: 0.0,
         end_secs: midpoint,
+        speed: 1.0,
     };
     let second_segment = TimeRange {
         start_secs: midpoint,
         end_secs: metadata.duration_secs,
+        speed: 1.0,
     };
     println!(
         "  Segment 1: {:.2}s - {:.2}s (duration: {:.2}s)",
@@ -237,12 +239,17 @@ fn run_clip_export_benchmark(
         preferred_encoder: EncoderType::Auto,
         metadata,
         stream_copy: false,
+        smart_cut: false,
         output_width: None,
         output_height: None,
         output_fps: None,
         crop: None,
         post_process_filters: true,
         container_format: ExportContainerFormat::Mp4,
+        audio_tracks: ExportAudioTracks::Keep,
+        overlays: Vec::new(),
+        audio: Default::default(),
+        loudness: None,
     };
 
     // Spawn progress monitor
@@ -449,7 +456,10 @@ fn create_test_video_file(
         width,
         height,
         has_audio: false,
+        audio_track_count: 0,
         fps: fps as f64,
+        clip_metadata: None,
+        chapters: Vec::new(),
     })
 }
 
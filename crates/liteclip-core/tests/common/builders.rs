@@ -4,7 +4,7 @@
 //! specific settings. Builders make tests more readable and maintainable by
 //! clearly expressing the intent of each test configuration.
 
-use liteclip_core::config::{Config, EncoderType, QualityPreset, Resolution};
+use liteclip_core::config::{ClipSaveMode, Config, EncoderType, QualityPreset, Resolution};
 
 /// Builder for creating test configs with a fluent API.
 ///
@@ -101,6 +101,12 @@ impl ConfigBuilder {
         self
     }
 
+    /// Set what happens to the replay buffer after a clip is saved.
+    pub fn with_clip_save_mode(mut self, mode: ClipSaveMode) -> Self {
+        self.config.general.clip_save_mode = mode;
+        self
+    }
+
     /// Build the final Config instance.
     ///
     /// Consumes the builder and returns the configured Config.
@@ -2,7 +2,8 @@
 //!
 //! Tests the ring buffer's eviction behaviour under memory constraints,
 //! verifying that the 80% watermark eviction, 512 MB snapshot cap, and
-//! batched eviction work correctly.
+//! batched eviction work correctly, and that chunked reads for streaming clip
+//! saves stay within their per-chunk memory bound.
 //!
 //! ## Categories
 //!
@@ -293,3 +294,113 @@ fn interleaved_push_snapshot_under_pressure() {
         }
     }
 }
+
+// ===========================================================================
+// Chunked reads for streaming clip saves
+// ===========================================================================
+
+/// Reading a long window in chunks must never hold more than one chunk's worth of
+/// snapshot bytes, however large the window.
+#[test]
+#[cfg_attr(not(feature = "test-slow"), ignore)]
+fn chunked_read_bounds_outstanding_snapshot_bytes() {
+    const CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+    let config = ConfigBuilder::new()
+        .with_replay_duration(300)
+        .with_memory_limit(512)
+        .build();
+    let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+
+    // 1000 x 200 KB = 200 MB, far more than one chunk
+    for i in 0..1000 {
+        buffer.push(make_packet(i as i64 * 1_000_000, 200_000, i % 30 == 0));
+    }
+
+    let chunks = buffer
+        .snapshot_chunks(0, None, CHUNK_BYTES)
+        .expect("Chunked read should start")
+        .expect("Window is held in RAM");
+
+    let mut packet_count = 0;
+    let mut peak_outstanding = 0;
+    let mut last_pts = i64::MIN;
+    for chunk in chunks {
+        let chunk = chunk.expect("No overrun without concurrent pushes");
+        let chunk_bytes: usize = chunk.iter().map(|p| p.data.len()).sum();
+        assert!(
+            chunk_bytes <= CHUNK_BYTES,
+            "Chunk holds {} bytes, cap is {}",
+            chunk_bytes,
+            CHUNK_BYTES,
+        );
+        peak_outstanding = peak_outstanding.max(buffer.outstanding_snapshot_bytes());
+        for packet in chunk.iter() {
+            assert!(packet.pts > last_pts, "Chunks must continue in PTS order");
+            last_pts = packet.pts;
+        }
+        packet_count += chunk.len();
+    }
+
+    assert!(
+        peak_outstanding <= CHUNK_BYTES,
+        "Peak outstanding snapshot bytes {} exceed one chunk ({})",
+        peak_outstanding,
+        CHUNK_BYTES,
+    );
+    assert_eq!(
+        buffer.outstanding_snapshot_bytes(),
+        0,
+        "Dropped chunks must release bytes"
+    );
+    assert_eq!(packet_count, buffer.snapshot().unwrap().len());
+
+    eprintln!(
+        "Chunked read: {} packets (200 MB) with peak {:.1} MB outstanding",
+        packet_count,
+        peak_outstanding as f64 / 1_048_576.0,
+    );
+}
+
+/// A reader that falls behind eviction must report an overrun instead of yielding a clip
+/// with a hole in it.
+#[test]
+#[cfg_attr(not(feature = "test-slow"), ignore)]
+fn chunked_read_reports_overrun_when_ring_overtakes_reader() {
+    let config = ConfigBuilder::new()
+        .with_replay_duration(300)
+        .with_memory_limit(8)
+        .build();
+    let buffer = LockFreeReplayBuffer::new(&config).unwrap();
+
+    for i in 0..100 {
+        buffer.push(make_packet(i as i64 * 1_000_000, 50_000, i % 10 == 0));
+    }
+
+    let mut chunks = buffer
+        .snapshot_chunks(0, None, 500_000)
+        .expect("Chunked read should start")
+        .expect("Window is held in RAM");
+    let first = chunks.next().expect("First chunk").expect("No overrun yet");
+    assert!(first[0].is_keyframe);
+    drop(first);
+
+    // 20 MB through an 8 MB ring evicts the rest of the window before it is read
+    for i in 100..500 {
+        buffer.push(make_packet(i as i64 * 1_000_000, 50_000, i % 10 == 0));
+    }
+
+    match chunks.next() {
+        Some(Err(liteclip_core::buffer::BufferError::ReadOverrun { lost })) => {
+            assert!(lost > 0);
+        }
+        other => panic!(
+            "Expected an overrun, got {:?}",
+            other.map(|r| r.map(|c| c.len()))
+        ),
+    }
+    assert!(
+        chunks.next().is_none(),
+        "Iterator must end after an overrun"
+    );
+}
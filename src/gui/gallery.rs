@@ -20,11 +20,13 @@ mod utils;
 
 use decode_pipeline::PlaybackController;
 
-use crate::config::{Config, EncoderType};
+use crate::config::{AppDirs, Config, EncoderType};
 use crate::gui::manager::{show_toast, ToastKind};
 use crate::output::{
-    generate_thumbnail, probe_video_file, spawn_clip_export, ClipExportRequest, ClipExportUpdate,
-    CropRect, ExportContainerFormat, TimeRange, VideoFileMetadata,
+    generate_thumbnail, metadata_sidecar_path, probe_video_file, spawn_clip_export,
+    ClipExportRequest, ClipExportUpdate, ClipMetadata, CropRect, ExportAudioMix, ExportAudioTracks,
+    ExportContainerFormat, ExportJobState, ExportOverlay, ExportPreset, ExportQueue,
+    LoudnessNormalization, TimeRange, VideoFileMetadata,
 };
 use crate::platform::AppEvent;
 
@@ -189,6 +191,8 @@ struct VideoEntry {
     size_mb: f64,
     modified: SystemTime,
     metadata: VideoFileMetadata,
+    /// Recording context: the embedded tags, or the JSON sidecar when the file has none.
+    clip_metadata: Option<ClipMetadata>,
     is_clipped: bool,
 }
 
@@ -240,11 +244,15 @@ pub(super) struct EditorState {
     last_tick: Instant,
     cut_points: Vec<f64>,
     snippet_enabled: Vec<bool>,
+    /// Export playback speed of each snippet, parallel to `snippet_enabled`.
+    snippet_speeds: Vec<f64>,
     selected_cut_point: Option<usize>,
     target_size_mb: u32,
     /// Whether target_size_mb was manually changed by the user.
     /// If false, export will use stream copy (no re-encoding).
     target_size_manually_adjusted: bool,
+    /// In stream copy mode, cut frame-accurately by re-encoding only the cut edges.
+    smart_cut: bool,
     audio_bitrate_kbps: u32,
     use_hardware_acceleration: bool,
     preferred_encoder: EncoderType,
@@ -272,17 +280,39 @@ pub(super) struct EditorState {
     cached_snippets: Vec<SnippetSegment>,
     cached_kept_ranges: Vec<TimeRange>,
     cached_kept_duration_secs: f64,
+    /// Kept duration after retiming each snippet to its speed.
+    cached_output_duration_secs: f64,
     snippets_dirty: bool,
     /// Spatial crop rectangle in source video pixel coordinates. None = no crop.
     crop: Option<CropRect>,
     /// Whether the visual crop overlay is visible on the preview.
     crop_editor_visible: bool,
+    /// Watermark images and text drawn over the export. Any overlay forces re-encoding.
+    overlays: Vec<ExportOverlay>,
+    /// Per-track gain and mute and the fades at the snippet joins. Anything but the default
+    /// forces re-encoding.
+    audio_mix: ExportAudioMix,
+    /// Loudness target of exports, `None` unless export normalization is on in the settings.
+    /// Forces the audio to be re-encoded.
+    loudness: Option<LoudnessNormalization>,
     /// Output container format (MP4, MKV, MOV, WebM).
     container_format: ExportContainerFormat,
+    /// Keep, drop or mix the audio tracks of clips saved with separate tracks.
+    audio_tracks: ExportAudioTracks,
+    /// Built-in and saved export presets, in UI order.
+    export_presets: Vec<ExportPreset>,
+    /// Name typed in for saving the current settings as a preset.
+    new_preset_name: String,
 }
 
 impl EditorState {
-    fn new(video: VideoEntry, preferred_encoder: EncoderType, use_software_encoder: bool) -> Self {
+    fn new(
+        video: VideoEntry,
+        preferred_encoder: EncoderType,
+        use_software_encoder: bool,
+        export_presets: Vec<ExportPreset>,
+        loudness: Option<LoudnessNormalization>,
+    ) -> Self {
         let target_size_mb = DEFAULT_TARGET_SIZE_MB
             .max(video.size_mb.round() as u32 / 2)
             .min(video.size_mb.ceil().max(1.0) as u32);
@@ -298,9 +328,11 @@ impl EditorState {
             last_tick: Instant::now(),
             cut_points: Vec::new(),
             snippet_enabled: vec![true],
+            snippet_speeds: vec![1.0],
             selected_cut_point: None,
             target_size_mb,
             target_size_manually_adjusted: false,
+            smart_cut: false,
             audio_bitrate_kbps: DEFAULT_AUDIO_BITRATE_KBPS,
             use_hardware_acceleration: !use_software_encoder,
             preferred_encoder,
@@ -325,10 +357,17 @@ impl EditorState {
             cached_snippets: Vec::new(),
             cached_kept_ranges: Vec::new(),
             cached_kept_duration_secs: 0.0,
+            cached_output_duration_secs: 0.0,
             snippets_dirty: true,
             crop: None,
             crop_editor_visible: false,
+            overlays: Vec::new(),
+            audio_mix: ExportAudioMix::default(),
+            loudness,
             container_format: ExportContainerFormat::Mp4,
+            audio_tracks: ExportAudioTracks::Keep,
+            export_presets,
+            new_preset_name: String::new(),
         }
     }
 
@@ -349,12 +388,18 @@ impl EditorState {
             self.duration_secs(),
             &self.cut_points,
             &self.snippet_enabled,
+            &self.snippet_speeds,
         );
         self.cached_kept_duration_secs = self
             .cached_kept_ranges
             .iter()
             .map(|range| range.duration_secs())
             .sum();
+        self.cached_output_duration_secs = self
+            .cached_kept_ranges
+            .iter()
+            .map(|range| range.output_duration_secs())
+            .sum();
         self.snippets_dirty = false;
     }
 
@@ -370,6 +415,18 @@ impl EditorState {
         self.cached_kept_duration_secs
     }
 
+    /// Length of the export: the kept duration with each snippet retimed to its speed.
+    fn output_duration_secs(&self) -> f64 {
+        self.cached_output_duration_secs
+    }
+
+    /// Whether a kept snippet plays at another speed, which rules out stream copy.
+    fn has_retimed_snippets(&self) -> bool {
+        self.cached_kept_ranges
+            .iter()
+            .any(|range| range.is_retimed())
+    }
+
     /// Maximum output size in MB based on proportion of enabled segments.
     /// If half the video is disabled, max output is half the original size.
     fn max_output_size_mb(&self) -> u32 {
@@ -460,6 +517,65 @@ impl EditorState {
         }
     }
 
+    /// Target size used in stream copy mode: the kept share of the source file.
+    fn auto_target_size_mb(&self) -> u32 {
+        DEFAULT_TARGET_SIZE_MB
+            .max(self.video.size_mb.round() as u32 / 2)
+            .min(self.video.size_mb.ceil().max(1.0) as u32)
+            .min(self.max_output_size_mb())
+    }
+
+    /// Replaces the export settings with those of `preset`.
+    fn apply_export_preset(&mut self, preset: &ExportPreset) {
+        match preset.target_size_mb {
+            Some(target_size_mb) => {
+                self.target_size_mb = target_size_mb.min(self.max_output_size_mb());
+                self.target_size_manually_adjusted = true;
+            }
+            None => {
+                self.target_size_mb = self.auto_target_size_mb();
+                // Formats without stream copy re-encode at the source's size instead.
+                self.target_size_manually_adjusted =
+                    !preset.container_format.supports_stream_copy();
+            }
+        }
+        let (width, height) = match self.crop {
+            Some(crop) => (crop.width, crop.height),
+            None => (self.video.metadata.width, self.video.metadata.height),
+        };
+        let output_size = preset.output_dimensions(width, height);
+        self.use_auto_resolution = false;
+        self.output_width = output_size.map(|(width, _)| width);
+        self.output_height = output_size.map(|(_, height)| height);
+        self.output_fps = preset.output_fps;
+        self.audio_bitrate_kbps = preset.audio_bitrate_kbps;
+        self.use_hardware_acceleration = preset.use_hardware_acceleration;
+        self.container_format = preset.container_format;
+        self.audio_tracks = preset.audio_tracks;
+        self.overlays = preset.overlays.clone();
+    }
+
+    /// The current export settings as a preset named `name`.
+    fn export_preset_from_settings(&self, name: &str) -> ExportPreset {
+        ExportPreset {
+            name: name.trim().to_string(),
+            target_size_mb: self
+                .target_size_manually_adjusted
+                .then_some(self.target_size_mb),
+            audio_bitrate_kbps: self.audio_bitrate_kbps,
+            output_height: if self.use_auto_resolution {
+                None
+            } else {
+                self.output_height
+            },
+            output_fps: self.output_fps,
+            container_format: self.container_format,
+            use_hardware_acceleration: self.use_hardware_acceleration,
+            audio_tracks: self.audio_tracks,
+            overlays: self.overlays.clone(),
+        }
+    }
+
     /// Get effective output FPS. Returns manual setting or original.
     fn effective_output_fps(&self) -> f64 {
         let fps = self.output_fps.unwrap_or(self.video.metadata.fps);
@@ -505,6 +621,15 @@ pub struct ClipCompressApp {
     delete_hold_started_at: Option<Instant>,
     preferred_export_encoder: EncoderType,
     use_software_encoder: bool,
+    /// Built-in and saved export presets offered by the editor.
+    export_presets: Vec<ExportPreset>,
+    /// Loudness normalization of exports, `None` unless turned on in the settings.
+    export_loudness: Option<LoudnessNormalization>,
+    /// Preset used when queueing exports of the selected videos.
+    batch_preset_index: usize,
+    /// Batch exports; unfinished jobs resume when the gallery opens again.
+    export_queue: ExportQueue,
+    event_tx: TokioSender<AppEvent>,
     last_thumbnail_check: Instant,
     filtered_cache: Option<browser::FilteredCache>,
 }
@@ -512,11 +637,20 @@ pub struct ClipCompressApp {
 pub type GalleryApp = ClipCompressApp;
 
 impl ClipCompressApp {
-    pub fn new(config: &Config, _event_tx: TokioSender<AppEvent>) -> Self {
+    pub fn new(config: &Config, event_tx: TokioSender<AppEvent>) -> Self {
         let save_directory = PathBuf::from(&config.general.save_directory);
         let cache_directory = save_directory.join(".cache");
         let (thumbnail_tx, thumbnail_rx) = mpsc::channel();
         let (dialog_tx, dialog_rx) = mpsc::channel();
+        let export_queue = AppDirs::liteclip()
+            .and_then(|dirs| ExportQueue::open(dirs.export_queue_file(), 1))
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to open export queue, batch exports won't persist: {:#}",
+                    e
+                );
+                ExportQueue::new(1)
+            });
 
         Self {
             save_directory,
@@ -552,6 +686,11 @@ impl ClipCompressApp {
             delete_hold_started_at: None,
             preferred_export_encoder: config.video.encoder,
             use_software_encoder: config.general.use_software_encoder,
+            export_presets: config.export.presets(),
+            export_loudness: LoudnessNormalization::from_config(config),
+            batch_preset_index: 0,
+            export_queue,
+            event_tx,
             last_thumbnail_check: Instant::now(),
             filtered_cache: None,
         }
@@ -656,6 +795,7 @@ impl ClipCompressApp {
         // Normalize game name by stripping "Clipped-" prefix to consolidate sections
         let game = Self::normalize_game_name(&raw_game);
         let is_clipped = raw_game.starts_with("Clipped-");
+        let clip_metadata = Self::read_clip_metadata(base_dir, &path, &video_metadata);
 
         Ok(VideoEntry {
             path,
@@ -668,10 +808,22 @@ impl ClipCompressApp {
             size_mb: metadata.len() as f64 / (1024.0 * 1024.0),
             modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
             metadata: video_metadata,
+            clip_metadata,
             is_clipped,
         })
     }
 
+    fn read_clip_metadata(
+        save_root: &Path,
+        path: &Path,
+        video_metadata: &VideoFileMetadata,
+    ) -> Option<ClipMetadata> {
+        video_metadata
+            .clip_metadata
+            .clone()
+            .or_else(|| ClipMetadata::read_sidecar(&metadata_sidecar_path(save_root, path)))
+    }
+
     fn normalize_game_name(game: &str) -> String {
         game.strip_prefix("Clipped-")
             .map(|s| s.to_string())
@@ -688,6 +840,7 @@ impl ClipCompressApp {
             .file_name()
             .map(|name| name.to_string_lossy().to_string())
             .unwrap_or_else(|| "video.mp4".to_string());
+        let clip_metadata = Self::read_clip_metadata(save_root, path, &video_metadata);
 
         Ok(VideoEntry {
             path: path.to_path_buf(),
@@ -700,6 +853,7 @@ impl ClipCompressApp {
             size_mb: metadata.len() as f64 / (1024.0 * 1024.0),
             modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
             metadata: video_metadata,
+            clip_metadata,
             is_clipped: false,
         })
     }
@@ -1014,6 +1168,8 @@ impl ClipCompressApp {
             self.check_for_new_thumbnails(ctx);
         }
 
+        self.poll_export_queue(ctx);
+
         while let Ok(result) = self.thumbnail_rx.try_recv() {
             self.thumbnails_generating.remove(&result.video_path);
             if let Some(image) = result.image {
@@ -1173,6 +1329,14 @@ impl ClipCompressApp {
             self.request_save_output_dialog(output_path);
         }
 
+        if !browser_outcome.videos_to_queue.is_empty() {
+            self.queue_batch_exports(browser_outcome.videos_to_queue);
+        }
+
+        if let Some(preset) = editor_outcome.save_export_preset {
+            self.save_export_preset(preset);
+        }
+
         if let Some(video) = browser_outcome.selected_video {
             self.open_editor(video);
             requested_preview = Some(0.0);
@@ -1277,9 +1441,144 @@ impl ClipCompressApp {
             video,
             self.preferred_export_encoder,
             self.use_software_encoder,
+            self.export_presets.clone(),
+            self.export_loudness,
         ));
     }
 
+    /// Queues exports of whole `videos` with the selected batch preset.
+    fn queue_batch_exports(&mut self, videos: Vec<VideoEntry>) {
+        let Some(preset) = self.export_presets.get(self.batch_preset_index).cloned() else {
+            return;
+        };
+        let requests: Vec<ClipExportRequest> = videos
+            .into_iter()
+            .map(|video| {
+                let keep_ranges = vec![TimeRange {
+                    start_secs: 0.0,
+                    end_secs: video.metadata.duration_secs,
+                    speed: 1.0,
+                }];
+                let mut request = preset.to_clip_export_request(
+                    video.path.clone(),
+                    &build_clipped_output_path(&video),
+                    keep_ranges,
+                    video.metadata,
+                    self.preferred_export_encoder,
+                );
+                request.loudness = self.export_loudness;
+                request
+            })
+            .collect();
+        let count = requests.len();
+        self.export_queue.extend(requests);
+        info!("Queued {} exports with preset '{}'", count, preset.name);
+        show_toast(
+            ToastKind::Info,
+            format!("Queued {} exports ({})", count, preset.name),
+        );
+        self.selected_videos.clear();
+        self.selection_mode = false;
+    }
+
+    fn poll_export_queue(&mut self, ctx: &egui::Context) {
+        let was_idle = self.export_queue.is_idle();
+        let mut any_finished = false;
+        for (job_id, update) in self.export_queue.poll() {
+            match update {
+                ClipExportUpdate::Finished(path) => {
+                    info!("Queued export {} finished: {:?}", job_id, path);
+                    any_finished = true;
+                }
+                ClipExportUpdate::Failed(err) => {
+                    warn!("Queued export {} failed: {}", job_id, err);
+                }
+                ClipExportUpdate::Progress { .. } | ClipExportUpdate::Cancelled => {}
+            }
+        }
+        if any_finished {
+            self.refresh();
+        }
+        if was_idle {
+            return;
+        }
+        if self.export_queue.is_idle() {
+            let failed = self
+                .export_queue
+                .jobs()
+                .iter()
+                .filter(|job| matches!(job.state, ExportJobState::Failed(_)))
+                .count();
+            let cancelled = self
+                .export_queue
+                .jobs()
+                .iter()
+                .any(|job| job.state == ExportJobState::Cancelled);
+            if cancelled {
+                show_toast(ToastKind::Info, "Batch export cancelled");
+            } else if failed == 0 {
+                show_toast(ToastKind::Success, "Batch export completed");
+            } else {
+                show_toast(
+                    ToastKind::Warning,
+                    format!("Batch export completed, {} failed", failed),
+                );
+            }
+            self.export_queue.clear_done();
+        } else {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    /// Saves `preset` to the config, replacing a custom preset with the same name.
+    fn save_export_preset(&mut self, preset: ExportPreset) {
+        let mut config = match Config::load_sync() {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Failed to load config for saving export preset: {:#}", e);
+                show_toast(ToastKind::Error, "Failed to save export preset");
+                return;
+            }
+        };
+        config
+            .export
+            .custom_presets
+            .retain(|existing| existing.name != preset.name);
+        config.export.custom_presets.push(preset.clone());
+        config.validate();
+        if !config
+            .export
+            .custom_presets
+            .iter()
+            .any(|saved| saved.name == preset.name)
+        {
+            show_toast(ToastKind::Error, "A built-in preset already uses that name");
+            return;
+        }
+
+        self.export_presets = config.export.presets();
+        if let Some(editor) = self.editor.as_mut() {
+            editor.export_presets = self.export_presets.clone();
+            editor.new_preset_name.clear();
+        }
+        match self
+            .event_tx
+            .try_send(AppEvent::ConfigUpdated(Arc::new(config)))
+        {
+            Ok(()) => {
+                info!("Saved export preset '{}'", preset.name);
+                show_toast(
+                    ToastKind::Success,
+                    format!("Saved preset '{}'", preset.name),
+                );
+            }
+            Err(e) => {
+                error!("Failed to send config with new export preset: {}", e);
+                show_toast(ToastKind::Error, "Failed to save export preset");
+            }
+        }
+    }
+
     fn render_browser(&mut self, ui: &mut egui::Ui) -> BrowserUiOutcome {
         browser::render_browser_ui(self, ui)
     }
@@ -1341,6 +1640,7 @@ struct BrowserUiOutcome {
     selected_video: Option<VideoEntry>,
     videos_to_delete: Vec<VideoEntry>,
     video_to_open: Option<VideoEntry>,
+    videos_to_queue: Vec<VideoEntry>,
     request_import_video_dialog: bool,
     refresh_requested: bool,
 }
@@ -1350,6 +1650,7 @@ struct EditorUiOutcome {
     preview_request: Option<f64>,
     fast_preview_request: Option<f64>,
     request_save_output_dialog: Option<PathBuf>,
+    save_export_preset: Option<ExportPreset>,
     back_to_browser: bool,
     refresh_browser: bool,
 }
@@ -1434,11 +1735,17 @@ fn start_export(editor: &mut EditorState) {
             use_hardware_acceleration: editor.use_hardware_acceleration,
             preferred_encoder: editor.preferred_encoder,
             metadata: editor.video.metadata.clone(),
-            // Cropping requires re-encoding, so force stream_copy off when crop is active.
+            // Cropping, overlays, audio mixing and loudness normalization require
+            // re-encoding, so force stream_copy off when any is active.
             // WebM also requires re-encoding (H.265 source can't be stream-copied to VP9).
             stream_copy: !editor.target_size_manually_adjusted
                 && editor.crop.is_none()
+                && editor.overlays.is_empty()
+                && editor.audio_mix.is_passthrough()
+                && editor.loudness.is_none()
+                && !editor.has_retimed_snippets()
                 && editor.container_format.supports_stream_copy(),
+            smart_cut: editor.smart_cut,
             output_width: if editor.use_auto_resolution {
                 let (w, _h) = editor.effective_output_resolution();
                 Some(w)
@@ -1455,6 +1762,10 @@ fn start_export(editor: &mut EditorState) {
             crop: editor.crop,
             post_process_filters: editor.use_auto_resolution,
             container_format: editor.container_format,
+            audio_tracks: editor.audio_tracks,
+            overlays: editor.overlays.clone(),
+            audio: editor.audio_mix.clone(),
+            loudness: editor.loudness,
         },
         progress_tx,
         cancel_flag.clone(),
@@ -1635,8 +1946,9 @@ fn enabled_time_ranges(
     duration_secs: f64,
     cut_points: &[f64],
     snippet_enabled: &[bool],
+    snippet_speeds: &[f64],
 ) -> Vec<TimeRange> {
-    utils::enabled_time_ranges_impl(duration_secs, cut_points, snippet_enabled)
+    utils::enabled_time_ranges_impl(duration_secs, cut_points, snippet_enabled, snippet_speeds)
 }
 
 fn clamp_to_enabled_playback_time(
@@ -1724,12 +2036,22 @@ mod tests {
 
     #[test]
     fn enabled_ranges_skip_disabled_snippets() {
-        let kept = enabled_time_ranges(30.0, &[5.0, 20.0], &[true, false, true]);
+        let kept = enabled_time_ranges(30.0, &[5.0, 20.0], &[true, false, true], &[1.0; 3]);
         assert_eq!(kept.len(), 2);
         assert!((kept[0].duration_secs() - 5.0).abs() < 0.001);
         assert!((kept[1].start_secs - 20.0).abs() < 0.001);
     }
 
+    #[test]
+    fn enabled_ranges_carry_snippet_speeds() {
+        let kept = enabled_time_ranges(30.0, &[5.0, 20.0], &[true, false, true], &[0.5, 2.0]);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].speed, 0.5);
+        assert!((kept[0].output_duration_secs() - 10.0).abs() < 0.001);
+        // Snippets without a recorded speed play at normal speed.
+        assert_eq!(kept[1].speed, 1.0);
+    }
+
     #[test]
     fn playback_clamps_to_next_enabled_snippet() {
         let next_time =
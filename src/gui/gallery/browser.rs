@@ -222,6 +222,7 @@ pub(super) fn render_browser_ui(app: &mut ClipCompressApp, ui: &mut egui::Ui) ->
     ui.horizontal(|ui| {
         ui.heading("Clip & Compress");
         ui.label(format!("({} videos)", filtered_count));
+        render_export_queue_status(app, ui);
 
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
             let size_btn_text = match app.card_size {
@@ -320,6 +321,23 @@ pub(super) fn render_browser_ui(app: &mut ClipCompressApp, ui: &mut egui::Ui) ->
     outcome
 }
 
+/// Progress of the batch export queue, with a button to cancel it.
+fn render_export_queue_status(app: &mut ClipCompressApp, ui: &mut egui::Ui) {
+    if app.export_queue.is_idle() {
+        return;
+    }
+    let progress = app.export_queue.progress();
+    ui.separator();
+    ui.add(
+        egui::ProgressBar::new(progress.fraction)
+            .desired_width(160.0)
+            .text(format!("Exporting {}/{}", progress.done, progress.total)),
+    );
+    if ui.small_button("Cancel all").clicked() {
+        app.export_queue.cancel_all();
+    }
+}
+
 fn render_filter_bar(app: &mut ClipCompressApp, ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
         let _search_response = ui.add(
@@ -473,6 +491,8 @@ fn render_delete_panel(
                 ui.label(
                     egui::RichText::new(format!("{} selected", app.selected_videos.len())).strong(),
                 );
+                ui.add_space(12.0);
+                render_batch_export_controls(app, ui, outcome);
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.add_space(20.0);
@@ -507,6 +527,41 @@ fn render_delete_panel(
         });
 }
 
+/// Preset picker and button queueing exports of the selected videos.
+fn render_batch_export_controls(
+    app: &mut ClipCompressApp,
+    ui: &mut egui::Ui,
+    outcome: &mut BrowserUiOutcome,
+) {
+    if app.export_presets.is_empty() {
+        return;
+    }
+    app.batch_preset_index = app.batch_preset_index.min(app.export_presets.len() - 1);
+    egui::ComboBox::from_id_salt("batch_export_preset")
+        .selected_text(app.export_presets[app.batch_preset_index].name.as_str())
+        .width(140.0)
+        .show_ui(ui, |ui| {
+            for (index, preset) in app.export_presets.iter().enumerate() {
+                ui.selectable_value(&mut app.batch_preset_index, index, preset.name.as_str());
+            }
+        });
+    if ui
+        .button("Export selected")
+        .on_hover_text(
+            "Queue exports of the whole selected clips; the queue resumes after a restart",
+        )
+        .clicked()
+    {
+        outcome.videos_to_queue = app
+            .videos_by_game
+            .iter()
+            .flat_map(|(_, videos)| videos)
+            .filter(|video| app.selected_videos.contains(&video.path))
+            .cloned()
+            .collect();
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn render_game_section(
     app: &mut ClipCompressApp,
@@ -8,7 +8,11 @@ use super::{
     SCRUB_SAMPLE_MIN_DT_SECS,
 };
 
-use crate::output::{CropRect, ExportContainerFormat};
+use crate::output::{
+    AudioSourceMix, CropRect, ExportAudioTracks, ExportContainerFormat, ExportOverlay,
+    OverlayAnchor, OverlayContent, MAX_CROSSFADE_SECS, MAX_FADE_SECS, MAX_RANGE_SPEED,
+    MAX_TRACK_GAIN_DB, MIN_RANGE_SPEED, MIN_TRACK_GAIN_DB,
+};
 
 const CROP_HANDLE_SIZE: f32 = 10.0;
 const CROP_MIN_PIXELS: u32 = 64;
@@ -119,7 +123,7 @@ fn render_editor_stats(ui: &mut egui::Ui, editor: &EditorState) {
         ui.separator();
         ui.label(format!(
             "Output Duration: {}",
-            format_compact_duration(editor.kept_duration_secs())
+            format_compact_duration(editor.output_duration_secs())
         ));
         ui.separator();
         ui.label(format!(
@@ -130,6 +134,20 @@ fn render_editor_stats(ui: &mut egui::Ui, editor: &EditorState) {
             ui.separator();
             ui.label("Audio: included");
         }
+        if let Some(clip) = &editor.video.clip_metadata {
+            if let Some(encoder) = &clip.encoder {
+                ui.separator();
+                ui.label(format!("Encoder: {encoder}"));
+            }
+            if let (Some(configured), Some(actual)) = (clip.configured_fps, clip.actual_fps) {
+                ui.separator();
+                ui.label(format!("Recorded FPS: {actual:.1} / {configured}"));
+            }
+            if let Some(dropped) = clip.dropped_frames.filter(|&dropped| dropped > 0) {
+                ui.separator();
+                ui.label(format!("Dropped Frames: {dropped}"));
+            }
+        }
         ui.separator();
         let (cache_entries, cache_mb) = editor.playback.cache_stats();
         ui.label(format!(
@@ -236,6 +254,38 @@ fn render_timeline(ui: &mut egui::Ui, editor: &mut EditorState, outcome: &mut Ed
         );
     }
 
+    // Bookmarks saved as chapters: a flag above the track and a faint line through it.
+    let bookmark_color = egui::Color32::from_rgb(86, 196, 140);
+    for chapter in &editor.video.metadata.chapters {
+        let x = super::time_to_x(track_rect, chapter.start_secs, editor.duration_secs());
+        painter.line_segment(
+            [
+                egui::pos2(x, track_rect.top()),
+                egui::pos2(x, track_rect.bottom()),
+            ],
+            egui::Stroke::new(1.0, bookmark_color.gamma_multiply(0.6)),
+        );
+        painter.add(egui::Shape::convex_polygon(
+            vec![
+                egui::pos2(x - 5.0, rect.top()),
+                egui::pos2(x + 5.0, rect.top()),
+                egui::pos2(x, track_rect.top()),
+            ],
+            bookmark_color,
+            egui::Stroke::NONE,
+        ));
+    }
+    let hovered_title = response.hover_pos().and_then(|pointer| {
+        editor.video.metadata.chapters.iter().find_map(|chapter| {
+            let x = super::time_to_x(track_rect, chapter.start_secs, editor.duration_secs());
+            ((x - pointer.x).abs() <= 5.0).then(|| chapter.title.clone())
+        })
+    });
+    let response = match hovered_title {
+        Some(title) => response.on_hover_text_at_pointer(title),
+        None => response,
+    };
+
     let playhead_x = super::time_to_x(track_rect, editor.current_time_secs, editor.duration_secs());
     painter.line_segment(
         [
@@ -395,10 +445,16 @@ fn render_editor_sidebar(
         |ui: &mut egui::Ui, editor: &mut EditorState, outcome: &mut EditorUiOutcome| {
             render_snippet_list(ui, editor, outcome);
             ui.add_space(10.0);
-            render_size_section(ui, editor);
+            render_size_section(ui, editor, outcome);
             ui.add_space(10.0);
             render_crop_section_impl(ui, editor);
             ui.add_space(10.0);
+            render_overlay_section(ui, editor);
+            ui.add_space(10.0);
+            if editor.video.metadata.has_audio {
+                render_audio_mix_section(ui, editor);
+                ui.add_space(10.0);
+            }
             render_action_section(ui, editor, outcome);
         };
 
@@ -508,6 +564,23 @@ fn render_snippet_list(ui: &mut egui::Ui, editor: &mut EditorState, outcome: &mu
                                     format_timestamp_precise(snippet.end_secs),
                                     format_compact_duration(snippet.duration_secs()),
                                 ));
+                                let mut speed = editor.snippet_speeds.get(index).copied().unwrap_or(1.0);
+                                let speed_response = ui
+                                    .add(
+                                        egui::DragValue::new(&mut speed)
+                                            .range(MIN_RANGE_SPEED..=MAX_RANGE_SPEED)
+                                            .suffix("x")
+                                            .speed(0.05)
+                                            .max_decimals(2),
+                                    )
+                                    .on_hover_text("Playback speed in the export. Slow motion below 1x; audio is muted below 0.5x and above 2x.");
+                                if speed_response.changed() {
+                                    editor.selected_snippet_index = Some(index);
+                                    if let Some(value) = editor.snippet_speeds.get_mut(index) {
+                                        *value = speed;
+                                    }
+                                    editor.invalidate_snippet_cache();
+                                }
                                 if index < editor.cut_points.len() && ui.button("Remove following cut").clicked() {
                                     editor.selected_snippet_index = Some(index);
                                     remove_cut_point(editor, index);
@@ -521,8 +594,9 @@ fn render_snippet_list(ui: &mut egui::Ui, editor: &mut EditorState, outcome: &mu
     });
 }
 
-fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
+fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState, outcome: &mut EditorUiOutcome) {
     let kept_duration = editor.kept_duration_secs();
+    let output_duration = editor.output_duration_secs();
     let total_duration = editor.duration_secs();
     let kept_proportion = if total_duration > 0.0 {
         (kept_duration / total_duration).clamp(0.0, 1.0)
@@ -534,12 +608,19 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
     egui::Frame::group(ui.style()).show(ui, |ui| {
         ui.label(egui::RichText::new("Export Settings").strong());
         ui.add_space(6.0);
+        render_preset_picker(ui, editor, outcome);
+        ui.add_space(6.0);
 
         // When the user hasn't manually adjusted the target size and no crop is active,
         // we use stream copy (no re-encoding). In this mode, target_size_mb and bitrate
         // estimates are irrelevant — the output preserves the original encoded data.
         // Skip all clamping and estimation to avoid any side-effects on the export path.
-        if !editor.target_size_manually_adjusted && editor.crop.is_none() {
+        if !editor.target_size_manually_adjusted
+            && editor.crop.is_none()
+            && editor.overlays.is_empty()
+            && editor.audio_mix.is_passthrough()
+            && !editor.has_retimed_snippets()
+        {
             ui.horizontal_wrapped(|ui| {
                 ui.label("Output Size:");
                 ui.label(
@@ -552,6 +633,14 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
                 )
                 .color(egui::Color32::from_rgb(100, 200, 100)),
             );
+            ui.checkbox(
+                &mut editor.smart_cut,
+                "Frame-accurate cuts (re-encode only the cut edges)",
+            )
+            .on_hover_text(
+                "Without this, stream copy cuts snap to the nearest keyframe. \
+                 Needs an H.265 clip; other clips are cut at keyframes.",
+            );
             if ui.button("Adjust size to enable compression").clicked() {
                 editor.target_size_manually_adjusted = true;
             }
@@ -567,7 +656,7 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
             // Compute bitrate and quality estimates for display in compression mode
             let (video_kbps, total_kbps) = estimate_export_bitrates_from_editor(
                 editor.target_size_mb,
-                kept_duration,
+                output_duration,
                 editor.video.metadata.has_audio,
                 editor.audio_bitrate_kbps,
                 kept_ranges_len,
@@ -585,6 +674,24 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
                         .color(egui::Color32::from_rgb(255, 180, 80)),
                 );
             }
+            if editor.has_retimed_snippets() {
+                ui.label(
+                    egui::RichText::new("Re-encoding required (snippet speed changed)")
+                        .color(egui::Color32::from_rgb(255, 180, 80)),
+                );
+            }
+            if !editor.overlays.is_empty() {
+                ui.label(
+                    egui::RichText::new("Re-encoding required (overlays)")
+                        .color(egui::Color32::from_rgb(255, 180, 80)),
+                );
+            }
+            if !editor.audio_mix.is_passthrough() {
+                ui.label(
+                    egui::RichText::new("Re-encoding required (audio mix)")
+                        .color(egui::Color32::from_rgb(255, 180, 80)),
+                );
+            }
 
             ui.horizontal_wrapped(|ui| {
                 ui.label("Target Output Size:");
@@ -649,13 +756,21 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
                             ExportContainerFormat::Mkv,
                             ExportContainerFormat::Mov,
                             ExportContainerFormat::WebM,
+                            ExportContainerFormat::Gif,
+                            ExportContainerFormat::WebP,
+                            ExportContainerFormat::Apng,
+                            ExportContainerFormat::M4a,
+                            ExportContainerFormat::Opus,
+                            ExportContainerFormat::Flac,
+                            ExportContainerFormat::Wav,
                         ] {
                             if ui
                                 .selectable_value(&mut editor.container_format, fmt, fmt.label())
                                 .clicked()
                             {
-                                // WebM doesn't support stream copy, so if user switches
-                                // to WebM from auto/stream-copy mode we must force re-encode.
+                                // WebM, the animated image and the audio-only formats don't
+                                // support stream copy, so switching to them from auto/stream-copy mode
+                                // must force re-encode.
                                 if !fmt.supports_stream_copy()
                                     && !editor.target_size_manually_adjusted
                                 {
@@ -666,6 +781,54 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
                     });
             });
 
+            if editor.container_format.is_animated_image() {
+                ui.label(
+                    egui::RichText::new(
+                        "Animated images have no audio. Framerate and resolution are \
+                         lowered as needed to fit the target size.",
+                    )
+                    .small()
+                    .weak(),
+                );
+            } else if editor.container_format.is_lossless_audio() {
+                ui.label(
+                    egui::RichText::new(
+                        "Audio only, lossless. The target size and audio bitrate don't apply.",
+                    )
+                    .small()
+                    .weak(),
+                );
+            } else if editor.container_format.is_audio_only() {
+                ui.label(
+                    egui::RichText::new(
+                        "Audio only. The bitrate is lowered as needed to fit the target size.",
+                    )
+                    .small()
+                    .weak(),
+                );
+            }
+
+            // Audio track handling, only for clips saved with separate tracks
+            if editor.video.metadata.audio_track_count > 1
+                && !editor.container_format.is_animated_image()
+            {
+                ui.horizontal(|ui| {
+                    ui.label("Audio Tracks:");
+                    egui::ComboBox::from_id_salt("export_audio_tracks")
+                        .selected_text(editor.audio_tracks.label())
+                        .width(160.0)
+                        .show_ui(ui, |ui| {
+                            for tracks in ExportAudioTracks::ALL {
+                                ui.selectable_value(
+                                    &mut editor.audio_tracks,
+                                    tracks,
+                                    tracks.label(),
+                                );
+                            }
+                        });
+                });
+            }
+
             ui.add_space(8.0);
             ui.separator();
             ui.add_space(4.0);
@@ -738,6 +901,48 @@ fn render_size_section(ui: &mut egui::Ui, editor: &mut EditorState) {
 }
 
 /// Render crop controls in the editor sidebar.
+fn render_preset_picker(
+    ui: &mut egui::Ui,
+    editor: &mut EditorState,
+    outcome: &mut EditorUiOutcome,
+) {
+    let mut chosen = None;
+    ui.horizontal(|ui| {
+        ui.label("Preset:");
+        egui::ComboBox::from_id_salt("export_preset")
+            .selected_text("Apply preset...")
+            .width(160.0)
+            .show_ui(ui, |ui| {
+                for (index, preset) in editor.export_presets.iter().enumerate() {
+                    if ui.selectable_label(false, preset.name.as_str()).clicked() {
+                        chosen = Some(index);
+                    }
+                }
+            });
+    });
+    if let Some(index) = chosen {
+        let preset = editor.export_presets[index].clone();
+        editor.apply_export_preset(&preset);
+    }
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut editor.new_preset_name)
+                .hint_text("Preset name")
+                .desired_width(140.0),
+        );
+        let can_save = !editor.new_preset_name.trim().is_empty();
+        if ui
+            .add_enabled(can_save, egui::Button::new("Save as preset"))
+            .on_hover_text("Save the current export settings; an existing custom preset with this name is replaced")
+            .clicked()
+        {
+            outcome.save_export_preset =
+                Some(editor.export_preset_from_settings(&editor.new_preset_name));
+        }
+    });
+}
+
 pub(super) fn render_crop_section_impl(ui: &mut egui::Ui, editor: &mut EditorState) {
     egui::Frame::group(ui.style()).show(ui, |ui| {
         ui.label(egui::RichText::new("Crop").strong());
@@ -895,6 +1100,183 @@ pub(super) fn render_crop_section_impl(ui: &mut egui::Ui, editor: &mut EditorSta
     });
 }
 
+fn render_overlay_section(ui: &mut egui::Ui, editor: &mut EditorState) {
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.label(egui::RichText::new("Overlays").strong());
+        ui.add_space(4.0);
+
+        let output_duration = editor.output_duration_secs();
+        let mut remove_index = None;
+        for (index, overlay) in editor.overlays.iter_mut().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    match &mut overlay.content {
+                        OverlayContent::Text {
+                            text, font_size, ..
+                        } => {
+                            ui.add(egui::TextEdit::singleline(text).desired_width(140.0));
+                            ui.add(
+                                egui::DragValue::new(font_size)
+                                    .range(8..=300)
+                                    .suffix(" px"),
+                            );
+                        }
+                        OverlayContent::Image { path, .. } => {
+                            let name = path
+                                .file_name()
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .unwrap_or_default();
+                            ui.label(name).on_hover_text(path.display().to_string());
+                        }
+                    }
+                    if ui
+                        .small_button("✕")
+                        .on_hover_text("Remove overlay")
+                        .clicked()
+                    {
+                        remove_index = Some(index);
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    egui::ComboBox::from_id_salt("overlay_anchor")
+                        .selected_text(overlay.anchor.label())
+                        .show_ui(ui, |ui| {
+                            for anchor in OverlayAnchor::ALL {
+                                ui.selectable_value(&mut overlay.anchor, anchor, anchor.label());
+                            }
+                        });
+                    ui.add(
+                        egui::Slider::new(&mut overlay.opacity, 0.1..=1.0).text("Opacity"),
+                    );
+                });
+                let mut timed = overlay.start_secs.is_some() || overlay.end_secs.is_some();
+                if ui.checkbox(&mut timed, "Only show between").changed() {
+                    if timed {
+                        overlay.start_secs = Some(0.0);
+                        overlay.end_secs = Some(output_duration);
+                    } else {
+                        overlay.start_secs = None;
+                        overlay.end_secs = None;
+                    }
+                }
+                if timed {
+                    ui.horizontal_wrapped(|ui| {
+                        let mut start = overlay.start_secs.unwrap_or(0.0);
+                        let mut end = overlay.end_secs.unwrap_or(output_duration);
+                        ui.add(
+                            egui::DragValue::new(&mut start)
+                                .range(0.0..=end)
+                                .speed(0.1)
+                                .suffix(" s"),
+                        );
+                        ui.label("and");
+                        ui.add(
+                            egui::DragValue::new(&mut end)
+                                .range(start..=output_duration.max(start))
+                                .speed(0.1)
+                                .suffix(" s"),
+                        );
+                        overlay.start_secs = Some(start);
+                        overlay.end_secs = Some(end);
+                    });
+                }
+            });
+            ui.separator();
+        }
+        if let Some(index) = remove_index {
+            editor.overlays.remove(index);
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("Add text").clicked() {
+                editor.overlays.push(ExportOverlay::text("LiteClip"));
+            }
+            if ui.button("Add image...").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Images", &["png", "jpg", "jpeg", "webp", "bmp"])
+                    .pick_file()
+                {
+                    editor.overlays.push(ExportOverlay::image(path));
+                }
+            }
+        });
+
+        if !editor.overlays.is_empty() {
+            ui.label(
+                egui::RichText::new(
+                    "Overlays require re-encoding (stream copy disabled). Times are on the exported clip.",
+                )
+                .small()
+                .color(egui::Color32::from_rgb(255, 180, 80)),
+            );
+        }
+    });
+}
+
+fn render_audio_mix_section(ui: &mut egui::Ui, editor: &mut EditorState) {
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.label(egui::RichText::new("Audio").strong());
+        ui.add_space(4.0);
+
+        let track_count = editor.video.metadata.audio_track_count.max(1);
+        if editor.audio_mix.sources.len() != track_count {
+            editor
+                .audio_mix
+                .sources
+                .resize(track_count, AudioSourceMix::default());
+        }
+        for (index, source) in editor.audio_mix.sources.iter_mut().enumerate() {
+            ui.horizontal_wrapped(|ui| {
+                if track_count > 1 {
+                    ui.label(format!("Track {}", index + 1));
+                }
+                ui.add_enabled(
+                    !source.muted,
+                    egui::Slider::new(&mut source.gain_db, MIN_TRACK_GAIN_DB..=MAX_TRACK_GAIN_DB)
+                        .suffix(" dB")
+                        .step_by(0.5),
+                );
+                ui.checkbox(&mut source.muted, "Mute");
+            });
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Fade in:");
+            ui.add(
+                egui::DragValue::new(&mut editor.audio_mix.fade_in_secs)
+                    .range(0.0..=MAX_FADE_SECS)
+                    .speed(0.01)
+                    .suffix(" s"),
+            );
+            ui.label("Fade out:");
+            ui.add(
+                egui::DragValue::new(&mut editor.audio_mix.fade_out_secs)
+                    .range(0.0..=MAX_FADE_SECS)
+                    .speed(0.01)
+                    .suffix(" s"),
+            );
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Crossfade at joins:");
+            ui.add(
+                egui::DragValue::new(&mut editor.audio_mix.crossfade_secs)
+                    .range(0.0..=MAX_CROSSFADE_SECS)
+                    .speed(0.005)
+                    .suffix(" s"),
+            )
+            .on_hover_text("Blends the audio across each cut between snippets to avoid clicks");
+        });
+
+        if !editor.audio_mix.is_passthrough() {
+            ui.label(
+                egui::RichText::new("Audio mixing requires re-encoding (stream copy disabled)")
+                    .small()
+                    .color(egui::Color32::from_rgb(255, 180, 80)),
+            );
+        }
+    });
+}
+
 /// Convert a CropRect from video pixel coordinates to the preview image rect.
 fn crop_to_preview_rect(
     image_rect: egui::Rect,
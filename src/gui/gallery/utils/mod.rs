@@ -183,6 +183,8 @@ pub(super) fn clear_cut_points_impl(editor: &mut EditorState) {
     editor.cut_points.clear();
     editor.snippet_enabled.clear();
     editor.snippet_enabled.push(true);
+    editor.snippet_speeds.clear();
+    editor.snippet_speeds.push(1.0);
     editor.selected_cut_point = None;
 }
 
@@ -225,8 +227,16 @@ pub(super) fn add_cut_point_impl(editor: &mut EditorState, time_secs: f64) -> bo
         .get(insert_index)
         .copied()
         .unwrap_or(true);
+    let inherited_speed = editor
+        .snippet_speeds
+        .get(insert_index)
+        .copied()
+        .unwrap_or(1.0);
     editor.cut_points.insert(insert_index, cut_time);
     editor.snippet_enabled.insert(insert_index + 1, inherited);
+    editor
+        .snippet_speeds
+        .insert(insert_index + 1, inherited_speed);
     normalize_cut_points_impl(&mut editor.cut_points, duration);
     editor.selected_cut_point = Some(insert_index);
     editor.error_message = None;
@@ -247,6 +257,10 @@ pub(super) fn remove_cut_point_impl(editor: &mut EditorState, index: usize) {
     if let Some(left_enabled) = editor.snippet_enabled.get_mut(index) {
         *left_enabled = *left_enabled || right_enabled;
     }
+    // The merged snippet keeps the speed of its left half.
+    if index + 1 < editor.snippet_speeds.len() {
+        editor.snippet_speeds.remove(index + 1);
+    }
     editor.selected_cut_point = index
         .checked_sub(1)
         .or(Some(index).filter(|i| *i < editor.cut_points.len()));
@@ -283,13 +297,16 @@ pub(super) fn enabled_time_ranges_impl(
     duration_secs: f64,
     cut_points: &[f64],
     snippet_enabled: &[bool],
+    snippet_speeds: &[f64],
 ) -> Vec<TimeRange> {
     snippet_segments_impl(duration_secs, cut_points, snippet_enabled)
         .into_iter()
-        .filter(|segment| segment.enabled && segment.duration_secs() >= MIN_RANGE_SECS)
-        .map(|segment| TimeRange {
+        .enumerate()
+        .filter(|(_, segment)| segment.enabled && segment.duration_secs() >= MIN_RANGE_SECS)
+        .map(|(index, segment)| TimeRange {
             start_secs: segment.start_secs,
             end_secs: segment.end_secs,
+            speed: snippet_speeds.get(index).copied().unwrap_or(1.0),
         })
         .collect()
 }
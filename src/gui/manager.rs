@@ -21,11 +21,16 @@ pub enum GuiMessage {
     ShowSettings(
         TokioSender<AppEvent>,
         Option<AudioLevelMonitor>,
+        Option<crate::buffer::ReplayBuffer>,
         crate::config::Config,
         Arc<FileLogGuard>,
     ),
     ShowGallery(TokioSender<AppEvent>, crate::config::Config),
     Toast(ToastKind, String),
+    /// Ask whether to save the replay buffer of a crashed previous session (its length in
+    /// seconds). The answer is sent back as [`AppEvent::RecoverPreviousSession`] or
+    /// [`AppEvent::DiscardPreviousSession`].
+    RecoveryPrompt(TokioSender<AppEvent>, f64),
 }
 
 pub enum ToastKind {
@@ -35,6 +40,14 @@ pub enum ToastKind {
     Warning,
 }
 
+/// Pending answer to a [`GuiMessage::RecoveryPrompt`].
+struct RecoveryPrompt {
+    tx: TokioSender<AppEvent>,
+    duration_secs: f64,
+    /// Screen area of the prompt in the last frame, kept clickable.
+    rect: Option<egui::Rect>,
+}
+
 #[derive(Default)]
 struct GuiManagerState {
     tx: Option<Sender<GuiMessage>>,
@@ -52,6 +65,7 @@ const TOAST_WINDOW_SIZE: [f32; 2] = [350.0, 300.0];
 /// When idle, shrink the overlay so it does not block clicks elsewhere (1×1 logical pixel).
 const TOAST_WINDOW_IDLE_SIZE: [f32; 2] = [1.0, 1.0];
 const TOAST_WINDOW_MARGIN: [f32; 2] = [20.0, 20.0];
+const RECOVERY_PROMPT_WIDTH: f32 = 240.0;
 const GUI_IDLE_SHUTDOWN_DELAY: Duration = Duration::from_secs(3);
 /// GUI Manager for the application.
 ///
@@ -268,6 +282,12 @@ pub fn show_toast(kind: ToastKind, message: impl Into<String>) {
     send_gui_message(GuiMessage::Toast(kind, message.into()));
 }
 
+/// Offers to save the replay buffer of a crashed previous session. Stays on screen until the
+/// user picks save or discard, which is sent to `tx`.
+pub fn show_recovery_prompt(tx: TokioSender<AppEvent>, duration_secs: f64) {
+    send_gui_message(GuiMessage::RecoveryPrompt(tx, duration_secs));
+}
+
 pub fn shutdown_gui() {
     // Signal the GUI control thread to close by dropping the sender. If a GUI
     // session is running, wake the event loop so run_app_on_demand can observe
@@ -305,6 +325,7 @@ struct GuiManagerApp {
     settings_open_flag: Arc<AtomicBool>,
     gallery_open_flag: Arc<AtomicBool>,
     toasts: Toasts,
+    recovery_prompt: Option<RecoveryPrompt>,
     overlay_toast_area: bool,
     last_mouse_passthrough: Option<bool>,
     idle_since: Option<std::time::Instant>,
@@ -325,6 +346,7 @@ impl GuiManagerApp {
             settings_open_flag: Arc::new(AtomicBool::new(false)),
             gallery_open_flag: Arc::new(AtomicBool::new(false)),
             toasts: Toasts::default().with_anchor(Anchor::TopRight),
+            recovery_prompt: None,
             overlay_toast_area: false,
             last_mouse_passthrough: None,
             idle_since: Some(std::time::Instant::now()),
@@ -350,14 +372,56 @@ impl GuiManagerApp {
             settings_open_flag: Arc::new(AtomicBool::new(false)),
             gallery_open_flag: Arc::new(AtomicBool::new(false)),
             toasts: Toasts::default().with_anchor(Anchor::TopRight),
+            recovery_prompt: None,
             overlay_toast_area: false,
             last_mouse_passthrough: None,
             idle_since: Some(std::time::Instant::now()),
         }
     }
 
+    fn has_overlay_content(&self) -> bool {
+        !self.toasts.is_empty() || self.recovery_prompt.is_some()
+    }
+
+    fn show_recovery_prompt(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = self.recovery_prompt.as_mut() else {
+            return;
+        };
+
+        let mut answer = None;
+        let response = egui::Area::new(egui::Id::new("recovery_prompt"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(RECOVERY_PROMPT_WIDTH);
+                    ui.label(format!(
+                        "LiteClip closed unexpectedly. Save the last {:.0}s of replay as a clip?",
+                        prompt.duration_secs
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Save clip").clicked() {
+                            answer = Some(AppEvent::RecoverPreviousSession);
+                        }
+                        if ui.button("Discard").clicked() {
+                            answer = Some(AppEvent::DiscardPreviousSession);
+                        }
+                    });
+                });
+            })
+            .response;
+        prompt.rect = Some(response.rect);
+
+        if let Some(event) = answer {
+            if let Err(e) = prompt.tx.try_send(event) {
+                warn!("Failed to send recovery answer: {}", e);
+            }
+            self.recovery_prompt = None;
+            ctx.request_repaint();
+        }
+    }
+
     fn sync_overlay_window_size(&mut self, ctx: &egui::Context) {
-        let needs_toast_area = !self.toasts.is_empty();
+        let needs_toast_area = self.has_overlay_content();
         if needs_toast_area == self.overlay_toast_area {
             return;
         }
@@ -383,10 +447,17 @@ impl GuiManagerApp {
         let mut should_passthrough = true;
 
         // Only consider blocking if we have toasts and mouse is in the viewport
-        if !self.toasts.is_empty() {
+        if self.has_overlay_content() {
             if let Some(mouse_pos) = ctx.input(|i| i.pointer.hover_pos()) {
                 let viewport_rect = ctx.available_rect();
-                if viewport_rect.contains(mouse_pos) {
+                let over_prompt = self
+                    .recovery_prompt
+                    .as_ref()
+                    .and_then(|prompt| prompt.rect)
+                    .is_some_and(|rect| rect.contains(mouse_pos));
+                if over_prompt {
+                    should_passthrough = false;
+                } else if !self.toasts.is_empty() && viewport_rect.contains(mouse_pos) {
                     // Only block mouse events in the top-right corner where toasts appear
                     // This leaves most of the overlay area clickable for other windows
                     let toast_region = egui::Rect::from_min_max(
@@ -410,12 +481,12 @@ impl GuiManagerApp {
         GuiActivityState {
             settings_open: self.settings_open_flag.load(Ordering::Acquire),
             gallery_open: self.gallery_open_flag.load(Ordering::Acquire),
-            has_toasts: !self.toasts.is_empty(),
+            has_toasts: self.has_overlay_content(),
         }
     }
 
     fn release_idle_resources(&mut self, ctx: &egui::Context) {
-        if !self.toasts.is_empty() {
+        if self.has_overlay_content() {
             return;
         }
         let settings_open = self.settings_open_flag.load(Ordering::Acquire);
@@ -462,12 +533,19 @@ impl eframe::App for GuiManagerApp {
 
             match maybe_msg {
                 Ok(msg) => match msg {
-                    GuiMessage::ShowSettings(tx, level_monitor, config, log_guard) => {
+                    GuiMessage::ShowSettings(
+                        tx,
+                        level_monitor,
+                        replay_buffer,
+                        config,
+                        log_guard,
+                    ) => {
                         *self.settings.lock().unwrap_or_else(|e| e.into_inner()) =
                             Some(crate::gui::settings::SettingsApp::new(
                                 config,
                                 tx,
                                 level_monitor,
+                                replay_buffer,
                                 log_guard,
                             ));
                         self.settings_open_flag.store(true, Ordering::Release);
@@ -506,6 +584,14 @@ impl eframe::App for GuiManagerApp {
                         }
                         ctx.request_repaint();
                     }
+                    GuiMessage::RecoveryPrompt(tx, duration_secs) => {
+                        self.recovery_prompt = Some(RecoveryPrompt {
+                            tx,
+                            duration_secs,
+                            rect: None,
+                        });
+                        ctx.request_repaint();
+                    }
                 },
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => {
@@ -582,6 +668,7 @@ impl eframe::App for GuiManagerApp {
         let now = Instant::now();
 
         self.toasts.show(ctx);
+        self.show_recovery_prompt(ctx);
         self.sync_mouse_passthrough(ctx);
         self.sync_overlay_window_size(ctx);
         self.release_idle_resources(ctx);
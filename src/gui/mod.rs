@@ -31,15 +31,17 @@
 //! // Initialize the GUI manager lazily before first use.
 //! init_gui_manager();
 //!
-//! // Show settings window (level_monitor is None for testing)
+//! // Show settings window (no level monitor or replay buffer for testing)
 //! let (tx, rx) = channel(1);
 //! let config = Config::default();
 //! let log_guard = Arc::new(FileLogGuard::default_for_test());
-//! show_settings_gui(tx, None, config, log_guard);
+//! show_settings_gui(tx, None, None, config, log_guard);
 //! ```
 
 pub mod manager;
-pub use manager::{init_gui_manager, send_gui_message, show_toast, shutdown_gui, ToastKind};
+pub use manager::{
+    init_gui_manager, send_gui_message, show_recovery_prompt, show_toast, shutdown_gui, ToastKind,
+};
 
 pub mod settings;
 pub use settings::show_settings_gui;
@@ -2,12 +2,15 @@ use eframe::egui;
 use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
 
+use crate::buffer::{BufferStats, ReplayBuffer, StreamStats};
 use crate::capture::audio::AudioLevelMonitor;
 use crate::capture::detect_display_resolution;
 use crate::config::{config_mod::types::*, Config};
 use crate::config::{
-    MAX_REPLAY_MEMORY_LIMIT_MB, MIN_REPLAY_MEMORY_LIMIT_MB, REPLAY_MEMORY_LIMIT_AUTO_MB,
+    MAX_CLIP_AUDIO_BITRATE_KBPS, MAX_REPLAY_DISK_LIMIT_GB, MAX_REPLAY_MEMORY_LIMIT_MB,
+    MIN_CLIP_AUDIO_BITRATE_KBPS, MIN_REPLAY_MEMORY_LIMIT_MB, REPLAY_MEMORY_LIMIT_AUTO_MB,
 };
+use crate::encode::StreamType;
 use crate::error_log::FileLogGuard;
 use crate::platform::AppEvent;
 use crate::updater::UpdateInfo;
@@ -15,12 +18,14 @@ use crate::updater::UpdateInfo;
 pub fn show_settings_gui(
     event_tx: Sender<AppEvent>,
     level_monitor: Option<AudioLevelMonitor>,
+    replay_buffer: Option<ReplayBuffer>,
     config: Config,
     log_guard: Arc<FileLogGuard>,
 ) {
     crate::gui::manager::send_gui_message(crate::gui::manager::GuiMessage::ShowSettings(
         event_tx,
         level_monitor,
+        replay_buffer,
         config,
         log_guard,
     ));
@@ -31,6 +36,7 @@ struct HotkeyValidationErrors {
     save_clip: Option<String>,
     toggle_recording: Option<String>,
     open_gallery: Option<String>,
+    bookmark: Option<String>,
 }
 
 /// State for the update checker UI.
@@ -76,6 +82,16 @@ fn render_hotkey_field(
     });
 }
 
+fn render_stream_usage_row(ui: &mut egui::Ui, name: &str, stream: &StreamStats, gaps: usize) {
+    ui.label(name);
+    ui.label(format!("{:.1} MB", stream.bytes as f64 / 1_048_576.0));
+    ui.label(format!("{:.1} MB", stream.disk_bytes as f64 / 1_048_576.0));
+    ui.label((stream.packet_count + stream.disk_packet_count).to_string());
+    ui.label(format!("{:.1} s", stream.span_secs));
+    ui.label(gaps.to_string());
+    ui.end_row();
+}
+
 use crate::capture::audio::level_monitor::AudioLevels;
 
 fn render_audio_level_meter(ui: &mut egui::Ui, active: bool, levels: AudioLevels) {
@@ -168,6 +184,11 @@ pub struct SettingsApp {
     hotkey_errors: HotkeyValidationErrors,
     level_monitor: Option<AudioLevelMonitor>,
     last_audio_levels: Option<(AudioLevels, AudioLevels)>,
+    replay_buffer: Option<ReplayBuffer>,
+    /// Buffer usage and gap count per stream (video, system audio, microphone), refreshed
+    /// about once a second while the Advanced tab is shown.
+    cached_buffer_usage: Option<(BufferStats, [usize; 3])>,
+    buffer_usage_refresh_time: std::time::Instant,
     mic_devices: Vec<(String, String)>,
     current_tab: SettingsTab,
     last_tab: SettingsTab,
@@ -185,6 +206,7 @@ impl SettingsApp {
         config: Config,
         event_tx: Sender<AppEvent>,
         level_monitor: Option<AudioLevelMonitor>,
+        replay_buffer: Option<ReplayBuffer>,
         log_guard: Arc<FileLogGuard>,
     ) -> Self {
         let mic_devices = crate::capture::audio::device_info::list_capture_devices();
@@ -207,6 +229,9 @@ impl SettingsApp {
             hotkey_errors: HotkeyValidationErrors::default(),
             level_monitor,
             last_audio_levels: None,
+            replay_buffer,
+            cached_buffer_usage: None,
+            buffer_usage_refresh_time: std::time::Instant::now(),
             mic_devices,
             current_tab: SettingsTab::default(),
             last_tab: SettingsTab::default(),
@@ -232,6 +257,8 @@ impl SettingsApp {
         self.save_status = None;
         self.level_monitor = None;
         self.last_audio_levels = None;
+        self.replay_buffer = None;
+        self.cached_buffer_usage = None;
     }
 
     /// Refresh microphone device list from system
@@ -401,9 +428,18 @@ impl SettingsApp {
 
         ui.add_space(8.0);
 
+        // Longer windows only make sense when evicted packets can spill to disk.
+        let max_replay_secs = if self.config.general.replay_disk_limit_gb > 0 {
+            3600
+        } else {
+            300
+        };
         ui.add(
-            egui::Slider::new(&mut self.config.general.replay_duration_secs, 5..=300)
-                .text("Replay Duration (s)"),
+            egui::Slider::new(
+                &mut self.config.general.replay_duration_secs,
+                5..=max_replay_secs,
+            )
+            .text("Replay Duration (s)"),
         );
 
         ui.add_space(8.0);
@@ -428,6 +464,70 @@ impl SettingsApp {
             .small()
             .weak(),
         );
+        ui.checkbox(
+            &mut self.config.general.write_metadata_sidecar,
+            "Write clip metadata sidecar (.json)",
+        );
+        ui.label(
+            egui::RichText::new(
+                "Game, encoder, framerate and dropped frames are always embedded in the clip; the sidecar keeps a readable copy in the .cache folder.",
+            )
+            .small()
+            .weak(),
+        );
+        ui.checkbox(
+            &mut self.config.general.crash_recovery_enabled,
+            "Recover replay after a crash",
+        );
+        ui.label(
+            egui::RichText::new(
+                "Checkpoints the replay buffer to disk every few seconds; a crashed session is saved as a clip on next start.",
+            )
+            .small()
+            .weak(),
+        );
+
+        ui.add_space(4.0);
+        let save_mode_label = |mode: ClipSaveMode| match mode {
+            ClipSaveMode::Restart => "Restart replay",
+            ClipSaveMode::Independent => "Keep replay",
+            ClipSaveMode::Extend => "Keep replay, extend overlapping clip",
+        };
+        egui::ComboBox::from_label("After Saving a Clip")
+            .selected_text(save_mode_label(self.config.general.clip_save_mode))
+            .show_ui(ui, |ui| {
+                for mode in [
+                    ClipSaveMode::Restart,
+                    ClipSaveMode::Independent,
+                    ClipSaveMode::Extend,
+                ] {
+                    ui.selectable_value(
+                        &mut self.config.general.clip_save_mode,
+                        mode,
+                        save_mode_label(mode),
+                    );
+                }
+            });
+
+        ui.add_space(4.0);
+        egui::ComboBox::from_label("Save Container")
+            .selected_text(self.config.general.save_container.label())
+            .show_ui(ui, |ui| {
+                for container in SaveContainer::ALL {
+                    ui.selectable_value(
+                        &mut self.config.general.save_container,
+                        container,
+                        container.label(),
+                    );
+                }
+            });
+        ui.label(
+            egui::RichText::new(
+                "Fragmented MP4 and MKV stay playable if the save is interrupted. MOV cannot hold Opus audio.",
+            )
+            .small()
+            .weak(),
+        );
 
         ui.add_space(8.0);
         ui.separator();
@@ -791,6 +891,34 @@ impl SettingsApp {
             );
         });
 
+        ui.add_space(8.0);
+        ui.checkbox(
+            &mut self.config.export.normalize_loudness,
+            "Normalize exported clips",
+        );
+        ui.label(
+            egui::RichText::new(
+                "Measures exports and brings them to their own target loudness. Re-encodes the audio.",
+            )
+            .small(),
+        );
+        ui.add_enabled_ui(self.config.export.normalize_loudness, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.config.export.target_lufs, -23..=-14)
+                    .text("Export Loudness (LUFS)"),
+            );
+            ui.checkbox(
+                &mut self.config.export.true_peak_limiter_enabled,
+                "Export true-peak limiter",
+            );
+            ui.add_enabled_ui(self.config.export.true_peak_limiter_enabled, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut self.config.export.true_peak_limit_dbtp, -3..=0)
+                        .text("Export Limiter Ceiling (dBTP)"),
+                );
+            });
+        });
+
         ui.add_space(12.0);
         ui.separator();
         ui.add_space(12.0);
@@ -803,6 +931,101 @@ impl SettingsApp {
         ui.add(
             egui::Slider::new(&mut self.config.audio.balance, -100..=100).text("Stereo Balance"),
         );
+
+        ui.add_space(8.0);
+        egui::ComboBox::from_label("Clip Audio Codec")
+            .selected_text(self.config.audio.clip_codec.label())
+            .show_ui(ui, |ui| {
+                for codec in ClipAudioCodec::ALL {
+                    ui.selectable_value(&mut self.config.audio.clip_codec, codec, codec.label());
+                }
+            });
+        // Encoded buffer audio is copied into clips as-is, so it has to match the clip codec.
+        if self.config.audio.buffer_codec.is_compressed()
+            && self.config.audio.clip_codec.buffer_codec() != Some(self.config.audio.buffer_codec)
+        {
+            self.config.audio.buffer_codec = self
+                .config
+                .audio
+                .clip_codec
+                .buffer_codec()
+                .unwrap_or(BufferAudioCodec::Pcm);
+        }
+        ui.add_enabled(
+            self.config.audio.clip_codec.uses_bitrate(),
+            egui::Slider::new(
+                &mut self.config.audio.clip_bitrate_kbps,
+                MIN_CLIP_AUDIO_BITRATE_KBPS..=MAX_CLIP_AUDIO_BITRATE_KBPS,
+            )
+            .text("Audio Bitrate (kbps)"),
+        );
+        if !self
+            .config
+            .general
+            .save_container
+            .supports_audio_codec(self.config.audio.clip_codec)
+        {
+            ui.label(
+                egui::RichText::new(format!(
+                    "{} cannot hold {} audio; clips will be saved as MKV.",
+                    self.config.general.save_container.label(),
+                    self.config.audio.clip_codec.label()
+                ))
+                .small()
+                .color(egui::Color32::YELLOW),
+            );
+        }
+
+        ui.add_space(8.0);
+        let buffer_codec_label = |codec: BufferAudioCodec| match codec {
+            BufferAudioCodec::Pcm => "Uncompressed (PCM)",
+            BufferAudioCodec::Aac => "AAC",
+            BufferAudioCodec::Opus => "Opus",
+        };
+        egui::ComboBox::from_label("Replay Buffer Audio")
+            .selected_text(buffer_codec_label(self.config.audio.buffer_codec))
+            .show_ui(ui, |ui| {
+                // Only the clip codec can be buffered encoded; lossless clips need PCM.
+                let codecs = std::iter::once(BufferAudioCodec::Pcm)
+                    .chain(self.config.audio.clip_codec.buffer_codec());
+                for codec in codecs {
+                    ui.selectable_value(
+                        &mut self.config.audio.buffer_codec,
+                        codec,
+                        buffer_codec_label(codec),
+                    );
+                }
+            });
+        ui.label(
+            egui::RichText::new(
+                "Compressing audio as it is recorded uses far less replay memory and makes saving faster.",
+            )
+            .small()
+            .weak(),
+        );
+
+        ui.add_space(8.0);
+        ui.checkbox(
+            &mut self.config.audio.separate_tracks,
+            "Save game audio and microphone as separate tracks",
+        );
+        ui.add_enabled_ui(self.config.audio.separate_tracks, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Game track title:");
+                ui.text_edit_singleline(&mut self.config.audio.system_track_title);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Mic track title:");
+                ui.text_edit_singleline(&mut self.config.audio.mic_track_title);
+            });
+        });
+        ui.label(
+            egui::RichText::new(
+                "Video editors can then rebalance or mute your voice. Some players only play the first track.",
+            )
+            .small()
+            .weak(),
+        );
     }
 
     fn render_hotkeys_settings(&mut self, ui: &mut egui::Ui) {
@@ -830,6 +1053,20 @@ impl SettingsApp {
             &mut self.config.hotkeys.open_gallery,
             &mut self.hotkey_errors.open_gallery,
         );
+
+        ui.add_space(4.0);
+
+        render_hotkey_field(
+            ui,
+            "Bookmark Moment:",
+            &mut self.config.hotkeys.bookmark,
+            &mut self.hotkey_errors.bookmark,
+        );
+        ui.label(
+            egui::RichText::new("Bookmarks become chapters in saved clips.")
+                .small()
+                .weak(),
+        );
     }
 
     fn render_advanced_settings(&mut self, ui: &mut egui::Ui) {
@@ -889,6 +1126,76 @@ impl SettingsApp {
             "Replay estimate: {} MB, effective memory cap: {} MB",
             estimated_mb, effective_mb
         ));
+
+        ui.add_space(8.0);
+
+        ui.add(
+            egui::Slider::new(
+                &mut self.config.general.replay_disk_limit_gb,
+                0..=MAX_REPLAY_DISK_LIMIT_GB,
+            )
+            .text("Replay Disk Spill (GB, 0 = off)"),
+        );
+        if self.config.general.replay_disk_limit_gb > 0 {
+            ui.label(
+                egui::RichText::new(
+                    "Packets evicted from RAM are kept in the cache folder so longer replays fit.",
+                )
+                .small()
+                .weak(),
+            );
+        } else if self.config.general.replay_duration_secs > 300 {
+            self.config.general.replay_duration_secs = 300;
+        }
+
+        self.render_buffer_usage(ui);
+    }
+
+    /// Live per-stream breakdown of what the replay buffer currently holds.
+    fn render_buffer_usage(&mut self, ui: &mut egui::Ui) {
+        let Some(buffer) = self.replay_buffer.as_ref() else {
+            return;
+        };
+        if self.cached_buffer_usage.is_none()
+            || self.buffer_usage_refresh_time.elapsed() >= std::time::Duration::from_secs(1)
+        {
+            self.buffer_usage_refresh_time = std::time::Instant::now();
+            let gaps = [
+                StreamType::Video,
+                StreamType::SystemAudio,
+                StreamType::Microphone,
+            ]
+            .map(|stream| buffer.stream_gaps(stream).gap_count);
+            self.cached_buffer_usage = Some((buffer.stats(), gaps));
+        }
+        let Some((stats, gaps)) = self.cached_buffer_usage.as_ref() else {
+            return;
+        };
+
+        ui.add_space(8.0);
+        ui.separator();
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new("Replay Buffer Usage").strong());
+        egui::Grid::new("replay_buffer_usage")
+            .num_columns(6)
+            .spacing([16.0, 4.0])
+            .show(ui, |ui| {
+                for header in ["Stream", "RAM", "Disk", "Packets", "Span", "Gaps"] {
+                    ui.label(egui::RichText::new(header).weak());
+                }
+                ui.end_row();
+
+                let rows = [
+                    ("Video", &stats.video),
+                    ("System audio", &stats.system_audio),
+                    ("Microphone", &stats.microphone),
+                ];
+                for ((name, stream), gap_count) in rows.into_iter().zip(gaps) {
+                    render_stream_usage_row(ui, name, stream, *gap_count);
+                }
+            });
+        ui.ctx()
+            .request_repaint_after(std::time::Duration::from_secs(1));
     }
 
     fn render_logs_settings(&mut self, ui: &mut egui::Ui) {
@@ -269,8 +269,8 @@ async fn main() -> Result<()> {
     let platform_handle = Arc::new(platform_handle);
 
     info!(
-        "Hotkeys: save={} toggle={} (Ctrl+C exits)",
-        config.hotkeys.save_clip, config.hotkeys.toggle_recording
+        "Hotkeys: save={} toggle={} bookmark={} (Ctrl+C exits)",
+        config.hotkeys.save_clip, config.hotkeys.toggle_recording, config.hotkeys.bookmark
     );
 
     // Convert the crossbeam receiver to a tokio-compatible channel
@@ -327,6 +327,17 @@ async fn main() -> Result<()> {
         Err(e) => error!("Failed to start recording: {}", e),
     }
 
+    // Offer the replay buffer of a crashed previous session; it is only saved once the user
+    // accepts (AppEvent::RecoverPreviousSession).
+    let recoverable = app_state_blocking(&app_state, |s| {
+        s.recoverable_session()
+            .map(|session| session.duration_secs())
+    })
+    .await;
+    if let Ok(Some(duration_secs)) = recoverable {
+        liteclip::gui::show_recovery_prompt(tokio_tx.clone(), duration_secs);
+    }
+
     let mut should_restart = false;
     let save_in_progress = Arc::new(AtomicBool::new(false));
     let mut pipeline_health_interval =
@@ -384,6 +395,26 @@ async fn main() -> Result<()> {
                                                     config.clone(),
                                                 );
                                             }
+                                            liteclip::platform::HotkeyAction::Bookmark => {
+                                                info!("Hotkey: bookmark");
+                                                match app_state_blocking(&app_state, |s| {
+                                                    s.add_bookmark(None)
+                                                })
+                                                .await
+                                                {
+                                                    Ok(Some(bookmark)) => {
+                                                        info!("Bookmark added at pts {}", bookmark.pts);
+                                                        liteclip::gui::show_toast(
+                                                            liteclip::gui::ToastKind::Info,
+                                                            "Bookmark added",
+                                                        );
+                                                    }
+                                                    Ok(None) => {
+                                                        info!("Bookmark ignored: not recording");
+                                                    }
+                                                    Err(e) => error!("Failed to add bookmark: {}", e),
+                                                }
+                                            }
                                         }
                                     }
                                     liteclip::platform::AppEvent::Tray(tray_event) => {
@@ -410,14 +441,18 @@ async fn main() -> Result<()> {
         liteclip::platform::TrayEvent::OpenSettings => {
                                                  info!("Tray: Open Settings selected");
                                                  match app_state_blocking(&app_state, |s| {
-                                                     s.level_monitor().clone()
+                                                     (
+                                                         s.level_monitor().clone(),
+                                                         s.replay_buffer().clone(),
+                                                     )
                                                  })
                                                  .await
                                                  {
-                                                     Ok(level_monitor) => {
+                                                     Ok((level_monitor, replay_buffer)) => {
                                                          liteclip::gui::show_settings_gui(
                                                              tokio_tx.clone(),
                                                              Some(level_monitor),
+                                                             Some(replay_buffer),
                                                              config.clone(),
                                                              log_guard.clone(),
                                                          );
@@ -447,6 +482,30 @@ async fn main() -> Result<()> {
                                         should_restart = true;
                                         break;
                                     }
+                                    liteclip::platform::AppEvent::RecoverPreviousSession => {
+                                        info!("Recovering previous session");
+                                        match app_state_blocking(&app_state, |s| {
+                                            s.take_recovered_session()
+                                        })
+                                        .await
+                                        {
+                                            Ok(Some(session)) => {
+                                                spawn_recover_session_task(config.clone(), session);
+                                            }
+                                            Ok(None) => {}
+                                            Err(e) => error!("Failed to take recovered session: {}", e),
+                                        }
+                                    }
+                                    liteclip::platform::AppEvent::DiscardPreviousSession => {
+                                        info!("Discarding previous session");
+                                        if let Err(e) = app_state_blocking(&app_state, |s| {
+                                            s.discard_previous_session()
+                                        })
+                                        .await
+                                        {
+                                            error!("Failed to discard recovered session: {}", e);
+                                        }
+                                    }
                                     liteclip::platform::AppEvent::ConfigUpdated(new_config) => {
                                         info!("ConfigUpdated event received from settings GUI");
                                         let cfg = (*new_config).clone();
@@ -540,6 +599,10 @@ async fn main() -> Result<()> {
         Ok(()) => info!("Recording pipeline stopped"),
         Err(e) => warn!("Error stopping recording: {}", e),
     }
+    // Clean exit: remove the crash-recovery checkpoint so it is not offered next start.
+    if let Err(e) = app_state_blocking(&app_state, |s| s.stop_checkpointing()).await {
+        warn!("Failed to stop replay checkpointing: {}", e);
+    }
 
     // Wait for platform thread (should be quick; we sent Quit above).
     platform_handle.join().ok();
@@ -588,6 +651,34 @@ async fn main() -> Result<()> {
     std::process::exit(0);
 }
 
+/// Muxes the replay buffer of a crashed session off-thread, deleting its checkpoint once saved.
+///
+/// On failure the checkpoint is kept, so the session is offered again on the next start.
+fn spawn_recover_session_task(config: Config, session: liteclip::buffer::RecoveredSession) {
+    tokio::task::spawn_blocking(move || {
+        match liteclip::app::ClipManager::save_recovered_session(&config, &session) {
+            Ok(path) => {
+                session.discard();
+                let filename = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "clip".to_string());
+                liteclip::gui::show_toast(
+                    liteclip::gui::ToastKind::Success,
+                    format!("Recovered previous session: {}", filename),
+                );
+            }
+            Err(e) => {
+                error!("Failed to recover previous session: {:#}", e);
+                liteclip::gui::show_toast(
+                    liteclip::gui::ToastKind::Error,
+                    format!("Failed to recover previous session: {}", e),
+                );
+            }
+        }
+    });
+}
+
 async fn spawn_save_clip_task(
     app_state: &Arc<Mutex<AppState>>,
     _platform_handle: &Arc<liteclip::platform::PlatformHandle>,
@@ -41,6 +41,7 @@ impl Hotkey {
 const HOTKEY_ID_SAVE_CLIP: i32 = 1000;
 const HOTKEY_ID_TOGGLE_RECORDING: i32 = 1001;
 const HOTKEY_ID_OPEN_GALLERY: i32 = 1003;
+const HOTKEY_ID_BOOKMARK: i32 = 1004;
 
 /// Register all hotkeys from configuration
 ///
@@ -87,6 +88,16 @@ pub fn register_hotkeys(hwnd: HWND, config: &HotkeyConfig) -> Result<()> {
         debug!("Registered open gallery hotkey: {}", config.open_gallery);
         success_count += 1;
     }
+    // Register bookmark hotkey (default: Ctrl+Shift+B)
+    if let Err(e) = register_single_hotkey(hwnd, HOTKEY_ID_BOOKMARK, &config.bookmark) {
+        error!(
+            "Failed to register bookmark hotkey '{}': {}",
+            config.bookmark, e
+        );
+    } else {
+        debug!("Registered bookmark hotkey: {}", config.bookmark);
+        success_count += 1;
+    }
 
     if success_count == 0 {
         anyhow::bail!("Failed to register all configured hotkeys");
@@ -155,6 +166,7 @@ pub fn unregister_all_hotkeys(hwnd: HWND) -> Result<()> {
         HOTKEY_ID_SAVE_CLIP,
         HOTKEY_ID_TOGGLE_RECORDING,
         HOTKEY_ID_OPEN_GALLERY,
+        HOTKEY_ID_BOOKMARK,
     ];
 
     for id in &hotkey_ids {
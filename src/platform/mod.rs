@@ -11,7 +11,7 @@
 //!
 //! # Components
 //!
-//! - **Hotkeys**: Global keyboard shortcuts (Save Clip, Toggle Recording).
+//! - **Hotkeys**: Global keyboard shortcuts (Save Clip, Toggle Recording, Bookmark).
 //! - **Tray**: System tray icon with context menu and status updates.
 //! - **Message Loop**: The core Win32 event loop that processes UI events.
 //! - **Autostart**: Logic for registering the app to run on Windows startup.
@@ -45,6 +45,8 @@ pub enum HotkeyAction {
     ToggleRecording,
     /// Open gallery hotkey pressed
     OpenGallery,
+    /// Bookmark hotkey pressed
+    Bookmark,
 }
 
 /// Tray menu events
@@ -77,6 +79,10 @@ pub enum AppEvent {
     Restart,
     /// Configuration updated from settings GUI
     ConfigUpdated(Arc<crate::config::Config>),
+    /// User accepted the startup offer to save the crashed previous session
+    RecoverPreviousSession,
+    /// User declined the startup offer to save the crashed previous session
+    DiscardPreviousSession,
 }
 
 pub use crate::config::HotkeyConfig;
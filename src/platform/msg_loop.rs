@@ -24,6 +24,7 @@ const CLASS_NAME: &str = "LiteClip_HotkeyWindow";
 const HOTKEY_ID_SAVE_CLIP: i32 = 1000;
 const HOTKEY_ID_TOGGLE_RECORDING: i32 = 1001;
 const HOTKEY_ID_OPEN_GALLERY: i32 = 1003;
+const HOTKEY_ID_BOOKMARK: i32 = 1004;
 const IDLE_LOOP_SLEEP_MS: u64 = 8;
 
 /// Spawn the platform thread (hotkeys and tray).
@@ -78,8 +79,11 @@ fn run_platform_loop(
                 match cmd {
                     PlatformCommand::ReRegisterHotkeys(new_cfg) => {
                         info!(
-                            "Re-registering hotkeys: save={} toggle={} gallery={}",
-                            new_cfg.save_clip, new_cfg.toggle_recording, new_cfg.open_gallery
+                            "Re-registering hotkeys: save={} toggle={} gallery={} bookmark={}",
+                            new_cfg.save_clip,
+                            new_cfg.toggle_recording,
+                            new_cfg.open_gallery,
+                            new_cfg.bookmark
                         );
                         if let Err(e) = super::hotkeys::unregister_all_hotkeys(hwnd) {
                             error!("Unregister hotkeys: {e}");
@@ -203,6 +207,7 @@ fn hotkey_id_to_action(id: i32) -> Option<HotkeyAction> {
         HOTKEY_ID_SAVE_CLIP => Some(HotkeyAction::SaveClip),
         HOTKEY_ID_TOGGLE_RECORDING => Some(HotkeyAction::ToggleRecording),
         HOTKEY_ID_OPEN_GALLERY => Some(HotkeyAction::OpenGallery),
+        HOTKEY_ID_BOOKMARK => Some(HotkeyAction::Bookmark),
         _ => {
             debug!("WM_HOTKEY with unknown id={}", id);
             None